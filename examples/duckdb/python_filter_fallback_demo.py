@@ -2,16 +2,15 @@
 Demonstrate measure filter rendering with and without FILTER support.
 
 By default DuckDB supports FILTER (WHERE ...), but you can force the portable
-CASE-wrapped form by setting the env var SEMAFLOW_DISABLE_FILTERED_AGG=1.
+CASE-wrapped form per datasource with the `filtered_aggregates` planner setting.
 """
 
 import asyncio
-import os
 from pathlib import Path
 
 import duckdb
 
-from semaflow import DataSource, FlowHandle
+from semaflow import Config, DataSource, FlowHandle
 
 
 def seed_duckdb(db_path: Path) -> None:
@@ -67,12 +66,17 @@ async def main() -> None:
     print(sql)
     print()
 
-    os.environ["SEMAFLOW_DISABLE_FILTERED_AGG"] = "1"
-    sql_fallback = await flow.build_sql(request)
+    config = Config()
+    config.set_planner("duckdb_local", filtered_aggregates=False)
+    fallback_flow = FlowHandle.from_dir(
+        flow_root,
+        [DataSource.duckdb(str(db_path), name="duckdb_local")],
+        config=config,
+    )
+    sql_fallback = await fallback_flow.build_sql(request)
     print("Forced fallback (CASE-wrapped aggregate):")
     print(sql_fallback)
     print()
-    os.environ.pop("SEMAFLOW_DISABLE_FILTERED_AGG", None)
 
 
 if __name__ == "__main__":
@@ -5,12 +5,25 @@ Two layers:
 - Execution: `FlowHandle` (validated registry + connections) and the `build_flow_handles` helper.
 """
 
-from .core import DataSource, Dimension, FlowJoin, JoinKey, Measure, SemanticFlow, SemanticTable, TableHandle
+from .core import (
+    DataSource,
+    Dimension,
+    FlowJoin,
+    JoinKey,
+    Measure,
+    SemanticFlow,
+    SemanticTable,
+    StaleCursorError,
+    TableHandle,
+)
 from .handle import FlowHandle, build_flow_handles
+from .query import Query, QueryValidationError
 
 __all__ = [
     "FlowHandle",
     "build_flow_handles",
+    "Query",
+    "QueryValidationError",
     "DataSource",
     "Dimension",
     "TableHandle",
@@ -19,6 +32,7 @@ __all__ = [
     "SemanticFlow",
     "FlowJoin",
     "JoinKey",
+    "StaleCursorError",
 ]
 
 # Docstrings for Python help/autocomplete.
@@ -26,12 +40,13 @@ DataSource.__doc__ = """Connection configuration passed to tables/flows.
 
 Args:
     name: Logical name referenced by semantic tables.
-    uri: Backend-specific connection string (e.g., DuckDB file path).
+    uri: Connection URI; the backend follows the scheme: "postgres://...[#schema]",
+         "bigquery://project/dataset", or a DuckDB file path.
     max_concurrency: Optional limit to throttle queries per backend.
 
 Methods:
     duckdb(path, name=None, max_concurrency=None): Create a DuckDB data source.
-    postgres(connection_string, schema, name=None, max_concurrency=None): Create a PostgreSQL data source.
+    postgres(connection_string, schema="public", name=None, max_concurrency=None): Create a PostgreSQL data source.
     bigquery(project_id, dataset, service_account_path=None, name=None): Create a BigQuery data source.
     register_dataframe(table_name, data): Register an Arrow RecordBatchReader as a table (DuckDB only).
     table(name): Create a TableHandle reference to a table in this data source.
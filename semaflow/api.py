@@ -24,13 +24,13 @@ Example:
 """
 
 from enum import Enum
-from typing import Any, Dict, List, Optional
+from typing import Any, Dict, List, Optional, Union
 
 from semaflow import FlowHandle, SemanticFlow, build_flow_handles
 
 try:
-    from fastapi import APIRouter, FastAPI, HTTPException  # type: ignore
-    from pydantic import BaseModel
+    from fastapi import APIRouter, FastAPI, HTTPException, Query  # type: ignore
+    from pydantic import BaseModel, Field
 except ImportError as e:  # pragma: no cover - handled at runtime
     raise RuntimeError("fastapi is required; install with `pip install semaflow[api]`") from e
 
@@ -56,6 +56,9 @@ class FilterOp(str, Enum):
     NotIn = "not in"
     Like = "like"
     ILike = "ilike"
+    IsTrue = "is true"
+    IsFalse = "is false"
+    IsUnknown = "is unknown"
 
 
 class Filter(BaseModel):
@@ -63,7 +66,40 @@ class Filter(BaseModel):
 
     field: str
     op: FilterOp
-    value: Any
+    value: Any = None
+
+
+class FilterAll(BaseModel):
+    """Filter group that matches when every member matches (AND)."""
+
+    all: List["FilterNode"]
+
+
+class FilterAny(BaseModel):
+    """Filter group that matches when at least one member matches (OR)."""
+
+    any: List["FilterNode"]
+
+
+class FilterNot(BaseModel):
+    """Filter group that negates its member. Serialized as ``{"not": ...}``."""
+
+    not_: "FilterNode" = Field(alias="not")
+
+    model_config = {"populate_by_name": True}
+
+
+FilterNode = Union[Filter, FilterAll, FilterAny, FilterNot]
+FilterAll.model_rebuild()
+FilterAny.model_rebuild()
+FilterNot.model_rebuild()
+
+
+class TimeDimension(BaseModel):
+    """Time dimension requested at a specific grain, e.g. ``{"field": "created_at", "grain": "week"}``."""
+
+    field: str
+    grain: str
 
 
 class OrderDirection(str, Enum):
@@ -84,22 +120,62 @@ class QueryPayload(BaseModel):
     """Request body accepted by ``POST /flows/{flow}/query``.
 
     Fields mirror the ``SemanticFlow.execute`` request:
-    - ``dimensions``: optional list of dimension names (qualified alias.field ok)
+    - ``dimensions``: optional list of dimension names (qualified alias.field ok) or
+      :class:`TimeDimension` objects truncating a table's ``time_dimension`` to a grain
     - ``measures``: optional list of measure names (qualified alias.field ok)
-    - ``filters``: optional list of :class:`Filter` objects
+    - ``filters``: optional list of :class:`Filter` objects or nested ``{"all": [...]}`` /
+      ``{"any": [...]}`` / ``{"not": ...}`` groups
     - ``order``: optional list of :class:`OrderItem` objects
     - ``limit``: optional total row limit (caps results)
     - ``page_size``: optional page size (enables cursor-based pagination)
     - ``cursor``: optional cursor for subsequent pages
+    - ``count_total``: report the full result's row count as ``total_rows`` on each page
+    - ``tags``: optional workload tags forwarded to the warehouse for cost attribution
+    - ``bins``: optional numeric bucketing per dimension, e.g. ``{"amount": {"edges": [0, 10, 50]}}``
+      or ``{"amount": {"width": 10}}``
+    - ``time_filter``: optional relative date range on the base table's ``time_dimension``,
+      e.g. ``"last_30_days"``, ``"this_quarter"``, ``"previous_month"``, ``"ytd"``
+    - ``fill_gaps``: emit a row for every time bucket between the first and last result
+    - ``gap_fill``: per-measure fill for added buckets: ``"zero"``, ``"null"`` (default) or ``"previous"``
+    - ``totals``: add a grand total row
+    - ``subtotals``: requested dimensions to subtotal; total rows carry a non-zero ``_grouping``
+    - ``calculations``: table calculations over the results, e.g.
+      ``{"name": "growth", "type": "percent_change", "measure": "revenue", "partition_by": ["country"]}``;
+      types are ``"rank"``, ``"difference"`` and ``"percent_change"``
+    - ``drill``: move a requested dimension along its hierarchy, e.g.
+      ``{"dimension": "c.country", "level": "city"}`` groups by country, region and city
+    - ``segments``: named filters of the flow or its tables, e.g. ``["active_customers", "eu_only"]``
+    - ``ignore_default_filters``: skip the ``default_filters`` declared on the flow's tables
+    - ``params``: values for the flow's declared parameters, e.g. ``{"currency": "EUR"}``
+    - ``query_timeout_ms``: cancel the query after this many milliseconds (0 = no timeout)
+    - ``timezone``: IANA time zone time grains truncate in, e.g. ``"Europe/Berlin"``
+    - ``strategy``: force the plan: ``"flat"``, ``"preagg"`` or ``"auto"`` (default); rejected
+      when unsafe for the request
     """
 
-    dimensions: Optional[List[str]] = None
+    dimensions: Optional[List[Union[str, TimeDimension]]] = None
     measures: Optional[List[str]] = None
-    filters: Optional[List[Filter]] = None
+    filters: Optional[List[FilterNode]] = None
     order: Optional[List[OrderItem]] = None
     limit: Optional[int] = None
     page_size: Optional[int] = None
     cursor: Optional[str] = None
+    count_total: Optional[bool] = None
+    tags: Optional[Dict[str, str]] = None
+    bins: Optional[Dict[str, Dict[str, Any]]] = None
+    time_filter: Optional[str] = None
+    fill_gaps: Optional[bool] = None
+    gap_fill: Optional[Dict[str, str]] = None
+    totals: Optional[bool] = None
+    subtotals: Optional[List[str]] = None
+    calculations: Optional[List[Dict[str, Any]]] = None
+    drill: Optional[Dict[str, str]] = None
+    segments: Optional[List[str]] = None
+    ignore_default_filters: Optional[bool] = None
+    params: Optional[Dict[str, Any]] = None
+    query_timeout_ms: Optional[int] = None
+    timezone: Optional[str] = None
+    strategy: Optional[str] = None
 
     model_config = {"arbitrary_types_allowed": True}
 
@@ -116,8 +192,11 @@ class FlowSchemaResponse(BaseModel):
     name: str
     description: Optional[str]
     time_dimension: Optional[str]
-    dimensions: Dict[str, Dict[str, Optional[str]]]
-    measures: Dict[str, Dict[str, Optional[str]]]
+    dimensions: Dict[str, Dict[str, Any]]
+    measures: Dict[str, Dict[str, Any]]
+    hierarchies: Optional[List[Dict[str, Any]]] = None
+    total_fields: Optional[int] = None
+    next_offset: Optional[int] = None
 
     model_config = {"arbitrary_types_allowed": True}
 
@@ -132,17 +211,32 @@ class QueryResponse(BaseModel):
         rows: List of result rows for this page/request.
         cursor: Opaque cursor string for fetching the next page. None if last page or not paginated.
         has_more: True if more rows exist beyond this response.
-        total_rows: Total result count (BigQuery only, None for other backends).
+        total_rows: Total result count (BigQuery, or other backends with ``count_total``; else None).
+        deprecations: Deprecated flow and fields the request uses, each with ``kind``,
+            ``name`` and ``since``/``replacement``/``message`` when set.
     """
 
     rows: List[Dict[str, Any]]
     cursor: Optional[str] = None
     has_more: bool = False
     total_rows: Optional[int] = None
+    deprecations: List[Dict[str, Any]] = []
 
     model_config = {"arbitrary_types_allowed": True}
 
 
+class ValidationResponse(BaseModel):
+    """Response model for dry-run request validation.
+
+    Attributes:
+        valid: True when the request can be executed.
+        errors: Every problem found, each with ``code``, ``message`` and ``details``.
+    """
+
+    valid: bool
+    errors: List[Dict[str, Any]]
+
+
 def _prepare_flow_handle(flows: Any) -> FlowHandle:
     if isinstance(flows, FlowHandle):
         return flows
@@ -151,6 +245,19 @@ def _prepare_flow_handle(flows: Any) -> FlowHandle:
     raise TypeError("flows must be a FlowHandle or a dict[str, SemanticFlow]")
 
 
+def _field_metadata(field: Dict[str, Any]) -> Dict[str, Any]:
+    """Catalog metadata (tags, folder, deprecated) present on a schema field."""
+    return {key: field[key] for key in ("tags", "folder", "deprecated") if key in field}
+
+
+def _error_detail(exc: Exception) -> Any:
+    """HTTP error detail: the message, plus SemaFlow's error ``code`` and ``details``."""
+    code = getattr(exc, "code", None)
+    if code is None:
+        return str(exc)
+    return {"code": code, "message": str(exc), "details": getattr(exc, "details", {})}
+
+
 def create_router(flows: Any):
     """Build an ``APIRouter`` exposing SemaFlow flows keyed by name."""
     handle: FlowHandle = _prepare_flow_handle(flows)
@@ -178,10 +285,32 @@ def create_router(flows: Any):
         return FlowList(flows=flow_map)
 
     @router.get("/flows/{flow}", response_model=FlowSchemaResponse)
-    async def describe_flow(flow: str):
+    async def describe_flow(
+        flow: str,
+        kind: Optional[str] = None,
+        tags: Optional[List[str]] = Query(None),
+        folder: Optional[str] = None,
+        include_deprecated: bool = True,
+        offset: int = 0,
+        limit: Optional[int] = None,
+    ):
+        """Describe a flow's fields, optionally narrowed for autocomplete clients.
+
+        ``kind`` selects ``dimensions`` or ``measures``; ``tags`` (repeatable) keeps
+        fields with any listed tag; ``folder`` keeps a folder and its subfolders;
+        ``offset``/``limit`` page over dimensions then measures.
+        """
         try:
             _ensure_flow(flow)
-            schema: Dict[str, Any] = handle.get_flow(flow)
+            schema: Dict[str, Any] = handle.get_flow(
+                flow,
+                kind=kind,
+                tags=tags,
+                folder=folder,
+                include_deprecated=include_deprecated,
+                offset=offset,
+                limit=limit,
+            )
             name = schema.get("name")
             if not isinstance(name, str):
                 raise HTTPException(status_code=500, detail="flow schema missing name")
@@ -190,7 +319,7 @@ def create_router(flows: Any):
                 raise HTTPException(
                     status_code=500, detail=f"flow {name} description must be a string or None"
                 )
-            dims_map: Dict[str, Dict[str, Optional[str]]] = {}
+            dims_map: Dict[str, Dict[str, Any]] = {}
             for dim in schema.get("dimensions", []):
                 if not isinstance(dim, dict):
                     raise HTTPException(status_code=500, detail=f"invalid dimension in flow {name}")
@@ -202,9 +331,10 @@ def create_router(flows: Any):
                     if isinstance(dim.get("description"), str)
                     else None,
                     "data_type": dim.get("data_type") if isinstance(dim.get("data_type"), str) else None,
+                    **_field_metadata(dim),
                 }
 
-            measures_map: Dict[str, Dict[str, Optional[str]]] = {}
+            measures_map: Dict[str, Dict[str, Any]] = {}
             for measure in schema.get("measures", []):
                 if not isinstance(measure, dict):
                     raise HTTPException(status_code=500, detail=f"invalid measure in flow {name}")
@@ -218,6 +348,7 @@ def create_router(flows: Any):
                     "data_type": measure.get("data_type")
                     if isinstance(measure.get("data_type"), str)
                     else None,
+                    **_field_metadata(measure),
                 }
             return FlowSchemaResponse(
                 name=name,
@@ -225,9 +356,12 @@ def create_router(flows: Any):
                 time_dimension=schema.get("time_dimension"),
                 dimensions=dims_map,
                 measures=measures_map,
+                hierarchies=schema.get("hierarchies"),
+                total_fields=schema.get("total_fields"),
+                next_offset=schema.get("next_offset"),
             )
         except Exception as exc:  # pragma: no cover - simple pass-through
-            raise HTTPException(status_code=400, detail=str(exc)) from exc
+            raise HTTPException(status_code=400, detail=_error_detail(exc)) from exc
 
     @router.post("/flows/{flow}/query", response_model=QueryResponse)
     async def query(flow: str, req: QueryPayload):
@@ -241,9 +375,10 @@ def create_router(flows: Any):
         """
         try:
             _ensure_flow(flow)
-            payload = req.model_dump(exclude_none=True)
+            payload = req.model_dump(exclude_none=True, by_alias=True)
             payload["flow"] = flow
             result = await handle.execute(payload)
+            deprecations = handle.deprecations(payload)
 
             # Normalize response: handle.execute returns list or dict based on page_size
             if isinstance(result, dict):
@@ -253,12 +388,27 @@ def create_router(flows: Any):
                     cursor=result.get("cursor"),
                     has_more=result.get("has_more", False),
                     total_rows=result.get("total_rows"),
+                    deprecations=deprecations,
                 )
             else:
                 # Non-paginated result (list of rows)
-                return QueryResponse(rows=result, has_more=False)
+                return QueryResponse(rows=result, has_more=False, deprecations=deprecations)
+        except TimeoutError as exc:
+            raise HTTPException(status_code=504, detail=_error_detail(exc)) from exc
+        except Exception as exc:  # pragma: no cover - simple pass-through
+            raise HTTPException(status_code=400, detail=_error_detail(exc)) from exc
+
+    @router.post("/flows/{flow}/validate", response_model=ValidationResponse)
+    async def validate(flow: str, req: QueryPayload):
+        """Check a query against a flow without executing it."""
+        _ensure_flow(flow)
+        payload = req.model_dump(exclude_none=True, by_alias=True)
+        payload["flow"] = flow
+        try:
+            errors = handle.validate_request(payload)
         except Exception as exc:  # pragma: no cover - simple pass-through
-            raise HTTPException(status_code=400, detail=str(exc)) from exc
+            raise HTTPException(status_code=400, detail=_error_detail(exc)) from exc
+        return ValidationResponse(valid=not errors, errors=errors)
 
     return router
 
@@ -8,6 +8,7 @@ from .semaflow import (
     Measure,
     SemanticFlow,
     SemanticTable,
+    StaleCursorError,
     TableHandle,
 )
 
@@ -19,5 +20,6 @@ __all__ = [
     "FlowJoin",
     "SemanticTable",
     "SemanticFlow",
+    "StaleCursorError",
     "TableHandle",
 ]
@@ -5,9 +5,10 @@ Build validated, connection-aware handles from class-based flow definitions.
 
 import asyncio
 from pathlib import Path
-from typing import Any, Dict, List, Mapping, Optional, TypedDict, Union
+from typing import Any, AsyncIterator, Callable, Dict, List, Literal, Mapping, Optional, TypedDict, Union
 
 from .core import SemanticFlow, SemanticTable
+from .query import Query
 from .semaflow import Config
 from .semaflow import SemanticFlowHandle as _SemanticFlowHandle
 
@@ -15,24 +16,47 @@ Tables = List[SemanticTable]
 Flows = List[SemanticFlow]
 DataSources = Dict[str, str] | List[Any]
 Request = Dict[str, Any]
+# Attribute name -> value or list of values, matched by tables' access_policies
+UserAttributes = Dict[str, Any]
+
+
+class ColumnInfo(TypedDict):
+    """Metadata for one result column.
+
+    Attributes:
+        name: Column name, in the same qualified format as row keys.
+        data_type: Backend type name, or the field's declared data_type.
+        nullable: Whether the column may hold NULLs, if the backend reports it.
+        kind: "dimension", "measure", or None for helper columns.
+    """
+
+    name: str
+    data_type: Optional[str]
+    nullable: Optional[bool]
+    kind: Optional[str]
 
 
 class PaginatedResult(TypedDict, total=False):
     """Result from a paginated query execution.
 
     Attributes:
+        columns: Typed metadata for each result column.
         rows: List of row dicts for this page.
         cursor: Opaque cursor string for next page (None if last page).
         has_more: Whether more rows exist after this page.
-        total_rows: Total result count (BigQuery only, None for other backends).
+        total_rows: Total result count (BigQuery, or other backends with count_total; else None).
     """
 
+    columns: List[ColumnInfo]
     rows: List[Dict[str, Any]]
     cursor: Optional[str]
     has_more: bool
     total_rows: Optional[int]
 
 
+# Rows per page for execute_page when neither the call nor the request sets one
+DEFAULT_PAGE_SIZE = 1000
+
 # Return type for execute: list of rows (non-paginated) or PaginatedResult (paginated)
 ExecuteResult = Union[List[Dict[str, Any]], PaginatedResult]
 
@@ -95,26 +119,74 @@ class FlowHandle:
         obj.description = description
         return obj
 
-    async def build_sql(self, request: Request) -> str:
-        return await asyncio.to_thread(self._inner.build_sql, request)
+    async def build_sql(
+        self, request: Request, user_attributes: Optional[UserAttributes] = None
+    ) -> str:
+        return await asyncio.to_thread(self._inner.build_sql, request, user_attributes)
+
+    async def explain(
+        self, request: Request, user_attributes: Optional[UserAttributes] = None
+    ) -> Dict[str, Any]:
+        """Build SQL and return it with the planner strategy and fanout diagnostics."""
+        return await asyncio.to_thread(self._inner.explain, request, user_attributes)
+
+    def query(self, flow: str) -> Query:
+        """Start a fluent query against ``flow``, validated as it is built.
+
+        Example:
+            >>> rows = await handle.query("sales").dimensions("c.country").measures("o.order_total").execute()
+        """
+        return Query(self, flow)
+
+    def validate(self) -> List[Dict[str, Any]]:
+        """Check the model against the data sources; returns every issue found (empty if valid).
+
+        Each issue is a dict with ``severity`` ("error" or "warning"), ``table``,
+        ``flow``, ``field`` and ``message``. Build the handle with
+        ``Config.set_validation_warn_only(True)`` to construct it despite issues.
+        """
+        return self._inner.validate()
+
+    def concurrency_stats(self) -> List[Dict[str, Any]]:
+        """Queue depth, wait times and rejections of each enforced concurrency budget."""
+        return self._inner.concurrency_stats()
+
+    async def health_check(self) -> Dict[str, Optional[str]]:
+        """Check every data source; maps each name to None if healthy, else the error message."""
+        return await asyncio.to_thread(self._inner.health_check)
+
+    def validate_request(self, request: Request) -> List[Dict[str, Any]]:
+        """Check a request without running it; returns every error found (empty if valid)."""
+        return self._inner.validate_request(request)
 
-    async def execute(self, request: Request) -> ExecuteResult:
+    def deprecations(self, request: Request) -> List[Dict[str, Any]]:
+        """Deprecated flow and fields a request uses, with their since/replacement/message."""
+        return self._inner.deprecations(request)
+
+    async def execute(
+        self, request: Request, user_attributes: Optional[UserAttributes] = None
+    ) -> ExecuteResult:
         """Execute a query request.
 
         Args:
             request: Query request dict with flow, dimensions, measures, etc.
                      Include 'page_size' to enable pagination.
                      Include 'cursor' for subsequent pages.
+            user_attributes: Caller attribute values for the tables' access policies.
 
         Returns:
             If page_size is NOT set: list of row dicts (backwards compatible).
             If page_size IS set: PaginatedResult dict with rows, cursor, has_more, total_rows.
         """
-        result = await asyncio.to_thread(self._inner.execute, request)
+        result = await asyncio.to_thread(self._inner.execute, request, user_attributes)
         # Transform result keys from SQL-safe format (c__country) back to qualified format (c.country)
         if isinstance(result, dict):
             # Paginated result - transform rows within the dict
             return {
+                "columns": [
+                    {**column, "name": column["name"].replace("__", ".")}
+                    for column in result.get("columns", [])
+                ],
                 "rows": [_unsanitize_keys(row) for row in result["rows"]],
                 "cursor": result.get("cursor"),
                 "has_more": result.get("has_more", False),
@@ -124,13 +196,141 @@ class FlowHandle:
             # Non-paginated result - list of rows
             return [_unsanitize_keys(row) for row in result]
 
+    async def execute_page(
+        self,
+        request: Request,
+        page_size: Optional[int] = None,
+        user_attributes: Optional[UserAttributes] = None,
+    ) -> PaginatedResult:
+        """Execute one page of a query request.
+
+        Pass the previous page's ``cursor`` in ``request`` to continue.
+        ``page_size`` overrides the request's, which defaults to
+        ``DEFAULT_PAGE_SIZE``.
+        """
+        request = {**request}
+        if page_size is not None:
+            request["page_size"] = page_size
+        request.setdefault("page_size", DEFAULT_PAGE_SIZE)
+        return await self.execute(request, user_attributes)
+
+    async def execute_pages(
+        self,
+        request: Request,
+        page_size: Optional[int] = None,
+        user_attributes: Optional[UserAttributes] = None,
+    ) -> AsyncIterator[PaginatedResult]:
+        """Iterate over every page of a query request, fetching each lazily.
+
+        Example:
+            >>> async for page in handle.execute_pages(request, page_size=500):
+            ...     process(page["rows"])
+        """
+        while True:
+            page = await self.execute_page(request, page_size, user_attributes)
+            yield page
+            if not page.get("has_more") or not page.get("cursor"):
+                return
+            request = {**request, "cursor": page["cursor"]}
+
+    async def execute_arrow(
+        self, request: Request, user_attributes: Optional[UserAttributes] = None
+    ) -> Any:
+        """Execute a query request and return the rows as a ``pyarrow.Table``.
+
+        Column names are converted back to the qualified format (``c.country``).
+        """
+
+        def _read() -> Any:
+            table = self._inner.execute_arrow(request, user_attributes).read_all()
+            return table.rename_columns(
+                [name.replace("__", ".") for name in table.column_names]
+            )
+
+        return await asyncio.to_thread(_read)
+
+    async def execute_df(
+        self,
+        request: Request,
+        engine: Literal["pandas", "polars"] = "pandas",
+        user_attributes: Optional[UserAttributes] = None,
+    ) -> Any:
+        """Execute a query request and return the rows as a DataFrame.
+
+        The result is fetched with ``execute_arrow`` and converted by
+        ``engine``, ``"pandas"`` or ``"polars"``, which must be installed.
+        """
+        if engine not in ("pandas", "polars"):
+            raise ValueError(f"unknown DataFrame engine {engine!r}; use 'pandas' or 'polars'")
+        table = await self.execute_arrow(request, user_attributes)
+        if engine == "polars":
+            import polars
+
+            return polars.from_arrow(table)
+        return table.to_pandas()
+
+    async def execute_with_callback(
+        self,
+        request: Request,
+        on_batch: Callable[[List[Dict[str, Any]]], Any],
+        batch_size: int = 1000,
+        user_attributes: Optional[UserAttributes] = None,
+    ) -> int:
+        """Execute a query request, streaming rows to ``on_batch`` in batches.
+
+        ``on_batch`` is called from a worker thread with a list of row dicts
+        each time a batch arrives; raising from it cancels the query.
+
+        Returns:
+            Total number of rows delivered.
+        """
+
+        def _deliver(rows: List[Dict[str, Any]]) -> None:
+            on_batch([_unsanitize_keys(row) for row in rows])
+
+        return await asyncio.to_thread(
+            self._inner.execute_with_callback,
+            request,
+            _deliver,
+            batch_size,
+            user_attributes,
+        )
+
+    async def execute_blend(
+        self, request: Dict[str, Any], user_attributes: Optional[UserAttributes] = None
+    ) -> List[Dict[str, Any]]:
+        """Execute a blend request joining several flows on shared dimensions."""
+        rows = await asyncio.to_thread(
+            self._inner.execute_blend, request, user_attributes
+        )
+        return [_unsanitize_keys(row) for row in rows]
+
     def list_flows(self):
         """Return a list of all flow names in this handle."""
         return self._inner.list_flows()
 
-    def get_flow(self, name: str) -> Dict[str, Any]:
-        """Return the flow schema for the given name."""
-        return self._inner.get_flow(name)
+    def diff(self, previous: "FlowHandle") -> Dict[str, Any]:
+        """Changes from ``previous``'s semantic model to this one, classified
+        as breaking, additive or cosmetic."""
+        return self._inner.diff(previous._inner)
+
+    def lineage(self) -> Dict[str, Any]:
+        """Lineage graph from physical columns through dimensions and measures
+        to flows, as ``nodes`` and ``edges``."""
+        return self._inner.lineage()
+
+    def search(self, term: str, limit: Optional[int] = None) -> List[Dict[str, Any]]:
+        """Flows, dimensions and measures matching ``term`` by name, synonym,
+        tag or description, best match first."""
+        return self._inner.search(term, limit)
+
+    def get_flow(self, name: str, **filters: Any) -> Dict[str, Any]:
+        """Return the flow schema for the given name.
+
+        Keyword filters (``kind``, ``tags``, ``folder``, ``include_deprecated``,
+        ``offset``, ``limit``) narrow the returned fields.
+        """
+        return self._inner.get_flow(name, **filters)
 
 
 def _unsanitize_keys(row: Dict[str, Any]) -> Dict[str, Any]:
@@ -0,0 +1,160 @@
+"""Fluent builder for query requests.
+
+``handle.query("sales").dimensions("c.country").measures("o.order_total")``
+builds the same request dict ``FlowHandle.execute`` takes, checking each step
+against the flow so typos fail where they are written, with suggestions.
+"""
+
+import copy
+from typing import TYPE_CHECKING, Any, AsyncIterator, Dict, List, Literal, Optional
+
+if TYPE_CHECKING:
+    from .handle import FlowHandle, PaginatedResult, Request, UserAttributes
+
+FILTER_OPS = (
+    "==", "!=", ">", ">=", "<", "<=", "in", "not in",
+    "like", "ilike", "is true", "is false", "is unknown",
+)
+
+# Raised by planning until something is selected; not an error while building
+_NOTHING_SELECTED = "at least one dimension or measure"
+
+
+class QueryValidationError(ValueError):
+    """A query request refers to fields or values the flow does not accept.
+
+    Attributes:
+        errors: One dict per problem with ``code``, ``message`` and
+            ``details`` (including ``suggestions`` for unknown fields).
+    """
+
+    def __init__(self, errors: List[Dict[str, Any]]):
+        super().__init__("; ".join(error["message"] for error in errors))
+        self.errors = errors
+
+
+class Query:
+    """An immutable query against one flow; every method returns a new Query.
+
+    Example:
+        >>> query = (
+        ...     handle.query("sales")
+        ...     .dimensions("c.country")
+        ...     .measures("o.order_total")
+        ...     .filter("c.country", "in", ["US", "DE"])
+        ...     .order_by("o.order_total", "desc")
+        ...     .limit(100)
+        ... )
+        >>> rows = await query.execute()
+    """
+
+    def __init__(self, handle: "FlowHandle", flow: str):
+        self._handle = handle
+        self._request: Dict[str, Any] = {"flow": flow}
+        self._validate()
+
+    def dimensions(self, *fields: str) -> "Query":
+        """Group by these dimensions."""
+        return self._extend("dimensions", list(fields))
+
+    def dimension(self, field: str, grain: Optional[str] = None) -> "Query":
+        """Group by one dimension, truncating a time dimension to ``grain``."""
+        requested: Any = {"field": field, "grain": grain} if grain else field
+        return self._extend("dimensions", [requested])
+
+    def measures(self, *fields: str) -> "Query":
+        """Aggregate these measures."""
+        return self._extend("measures", list(fields))
+
+    def filter(self, field: str, op: str, value: Any = None) -> "Query":
+        """Keep rows where ``field op value`` holds, e.g. ``("country", "==", "US")``."""
+        if op not in FILTER_OPS:
+            raise QueryValidationError([{
+                "code": "validation_error",
+                "message": f"unknown filter operator {op!r}; use one of {', '.join(FILTER_OPS)}",
+                "details": {"op": op},
+            }])
+        condition: Dict[str, Any] = {"field": field, "op": op}
+        if value is not None:
+            condition["value"] = value
+        return self._extend("filters", [condition])
+
+    def order_by(self, column: str, direction: Literal["asc", "desc"] = "asc") -> "Query":
+        """Sort by a selected column."""
+        return self._extend("order", [{"column": column, "direction": direction}])
+
+    def limit(self, limit: int) -> "Query":
+        return self._set("limit", limit)
+
+    def offset(self, offset: int) -> "Query":
+        return self._set("offset", offset)
+
+    def page_size(self, page_size: int) -> "Query":
+        """Paginate results; ``execute`` then returns a PaginatedResult."""
+        return self._set("page_size", page_size)
+
+    def params(self, **values: Any) -> "Query":
+        """Set flow parameters referenced as ``{{ name }}`` in the model."""
+        return self._set("params", {**self._request.get("params", {}), **values})
+
+    def to_dict(self) -> "Request":
+        """The request dict, as accepted by ``FlowHandle.execute``."""
+        return copy.deepcopy(self._request)
+
+    async def execute(self, user_attributes: Optional["UserAttributes"] = None) -> Any:
+        return await self._handle.execute(self.to_dict(), user_attributes)
+
+    async def execute_page(
+        self,
+        page_size: Optional[int] = None,
+        user_attributes: Optional["UserAttributes"] = None,
+    ) -> "PaginatedResult":
+        return await self._handle.execute_page(self.to_dict(), page_size, user_attributes)
+
+    async def execute_pages(
+        self,
+        page_size: Optional[int] = None,
+        user_attributes: Optional["UserAttributes"] = None,
+    ) -> AsyncIterator["PaginatedResult"]:
+        async for page in self._handle.execute_pages(self.to_dict(), page_size, user_attributes):
+            yield page
+
+    async def execute_arrow(self, user_attributes: Optional["UserAttributes"] = None) -> Any:
+        return await self._handle.execute_arrow(self.to_dict(), user_attributes)
+
+    async def execute_df(
+        self,
+        engine: Literal["pandas", "polars"] = "pandas",
+        user_attributes: Optional["UserAttributes"] = None,
+    ) -> Any:
+        return await self._handle.execute_df(self.to_dict(), engine, user_attributes)
+
+    async def build_sql(self, user_attributes: Optional["UserAttributes"] = None) -> str:
+        return await self._handle.build_sql(self.to_dict(), user_attributes)
+
+    def __repr__(self) -> str:
+        return f"Query({self._request!r})"
+
+    def _extend(self, key: str, items: List[Any]) -> "Query":
+        request = self.to_dict()
+        request.setdefault(key, []).extend(items)
+        return self._derive(request)
+
+    def _set(self, key: str, value: Any) -> "Query":
+        request = self.to_dict()
+        request[key] = value
+        return self._derive(request)
+
+    def _derive(self, request: Dict[str, Any]) -> "Query":
+        query = object.__new__(Query)
+        query._handle = self._handle
+        query._request = request
+        query._validate()
+        return query
+
+    def _validate(self) -> None:
+        errors = self._handle.validate_request(self._request)
+        if not (self._request.get("dimensions") or self._request.get("measures")):
+            errors = [e for e in errors if _NOTHING_SELECTED not in e["message"]]
+        if errors:
+            raise QueryValidationError(errors)
@@ -1,24 +1,25 @@
 //! BigQuery backend implementation using gcp-bigquery-client.
 
-use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use gcp_bigquery_client::model::get_query_results_parameters::GetQueryResultsParameters;
 use gcp_bigquery_client::model::get_query_results_response::GetQueryResultsResponse;
+use gcp_bigquery_client::model::job_reference::JobReference;
 use gcp_bigquery_client::model::query_request::QueryRequest;
-use gcp_bigquery_client::model::query_response::ResultSet;
+use gcp_bigquery_client::model::query_response::{QueryResponse, ResultSet};
 use gcp_bigquery_client::Client;
-use tokio::sync::Semaphore;
 
+use crate::cancel;
 use crate::config::BigQueryConfig;
 use crate::dialect::BigQueryDialect;
 use crate::error::{Result, SemaflowError};
 use crate::executor::{ColumnMeta, PaginatedResult, QueryResult};
 use crate::pagination::Cursor;
+use crate::query_tags::QueryTags;
 use crate::schema_cache::TableSchema;
 
-use super::BackendConnection;
+use super::{BackendConnection, BatchCallback};
 
 pub struct BigQueryConnection {
     client: Client,
@@ -26,8 +27,6 @@ pub struct BigQueryConnection {
     dataset: String,
     dialect: BigQueryDialect,
     config: BigQueryConfig,
-    /// Semaphore to limit concurrent BigQuery queries for backpressure.
-    limiter: Arc<Semaphore>,
 }
 
 impl BigQueryConnection {
@@ -76,7 +75,6 @@ impl BigQueryConnection {
         tracing::info!(
             project_id = %project_id,
             dataset = %dataset,
-            max_concurrent = config.max_concurrent_queries,
             "BigQuery connection established"
         );
 
@@ -85,7 +83,6 @@ impl BigQueryConnection {
             project_id: project_id.to_string(),
             dataset: dataset.to_string(),
             dialect: BigQueryDialect::new(project_id, dataset),
-            limiter: Arc::new(Semaphore::new(config.max_concurrent_queries)),
             config,
         })
     }
@@ -130,7 +127,6 @@ impl BigQueryConnection {
         tracing::info!(
             project_id = %project_id,
             dataset = %dataset,
-            max_concurrent = config.max_concurrent_queries,
             "BigQuery connection established via ADC"
         );
 
@@ -139,7 +135,6 @@ impl BigQueryConnection {
             project_id: project_id.to_string(),
             dataset: dataset.to_string(),
             dialect: BigQueryDialect::new(project_id, dataset),
-            limiter: Arc::new(Semaphore::new(config.max_concurrent_queries)),
             config,
         })
     }
@@ -159,46 +154,69 @@ impl BigQueryConnection {
         &self.dataset
     }
 
-    /// Acquire a slot for query execution with backpressure.
+    /// Build a query request with config options and job labels applied.
+    fn build_query_request(&self, sql: &str, tags: &QueryTags) -> QueryRequest {
+        let mut query_request = QueryRequest::new(sql);
+        query_request.use_query_cache = Some(self.config.use_query_cache);
+        if self.config.maximum_bytes_billed > 0 {
+            query_request.maximum_bytes_billed = Some(self.config.maximum_bytes_billed.to_string());
+        }
+        if !tags.is_empty() {
+            query_request.labels = Some(tags.to_bigquery_labels().into_iter().collect());
+        }
+        query_request
+    }
+
+    /// Run a query job, waiting at most `query_timeout_ms` (or until the
+    /// query's deadline, if sooner) for it to complete.
     ///
-    /// If all slots are in use, waits up to `queue_timeout_ms` before rejecting.
-    /// This prevents unbounded request queuing under load.
-    async fn acquire_slot(&self) -> Result<tokio::sync::OwnedSemaphorePermit> {
-        let available = self.limiter.available_permits();
-        if available == 0 {
-            tracing::debug!(
-                max_concurrent = self.config.max_concurrent_queries,
-                queue_timeout_ms = self.config.queue_timeout_ms,
-                "BigQuery slots exhausted, waiting for permit"
-            );
+    /// A job still running when the wait ends is cancelled so it stops
+    /// consuming slots.
+    async fn run_job(&self, mut query_request: QueryRequest) -> Result<QueryResponse> {
+        let scope = cancel::current();
+        let mut wait = Duration::from_millis(self.config.query_timeout_ms);
+        if let Some(remaining) = scope.remaining() {
+            wait = wait.min(remaining);
+        }
+        if !wait.is_zero() {
+            query_request.timeout_ms = Some(wait.as_millis().min(i32::MAX as u128) as i32);
         }
 
-        let timeout_ms = self.config.queue_timeout_ms;
-        if timeout_ms == 0 {
-            // No timeout - wait indefinitely (not recommended for production)
-            self.limiter
-                .clone()
-                .acquire_owned()
-                .await
-                .map_err(|e| SemaflowError::Execution(format!("limiter closed: {e}")))
-        } else {
-            // Wait with timeout for backpressure
-            let timeout = Duration::from_millis(timeout_ms);
-            match tokio::time::timeout(timeout, self.limiter.clone().acquire_owned()).await {
-                Ok(Ok(permit)) => Ok(permit),
-                Ok(Err(e)) => Err(SemaflowError::Execution(format!("limiter closed: {e}"))),
-                Err(_) => {
-                    tracing::warn!(
-                        max_concurrent = self.config.max_concurrent_queries,
-                        timeout_ms = timeout_ms,
-                        "BigQuery request rejected: queue timeout exceeded"
-                    );
-                    Err(SemaflowError::Execution(format!(
-                        "BigQuery overloaded: request queued for {}ms, max concurrent queries ({}) reached",
-                        timeout_ms, self.config.max_concurrent_queries
-                    )))
-                }
+        let query = self.client.job().query(&self.project_id, query_request);
+        let response = tokio::select! {
+            response = query => response
+                .map_err(|e| SemaflowError::Execution(format!("bigquery query: {e}")))?,
+            // With a deadline, BigQuery's own wait ends with it and the job is
+            // cancelled below; without one, nothing identifies the job yet
+            _ = scope.cancelled(), if scope.remaining().is_none() => {
+                return Err(cancel::cancelled_error(&scope));
+            }
+        };
+
+        if response.job_complete == Some(false) {
+            if let Some(job) = &response.job_reference {
+                self.cancel_job(job).await;
             }
+            return Err(SemaflowError::Timeout(format!(
+                "bigquery job did not complete within {} ms",
+                wait.as_millis()
+            )));
+        }
+        Ok(response)
+    }
+
+    async fn cancel_job(&self, job: &JobReference) {
+        let Some(job_id) = job.job_id.as_deref() else {
+            return;
+        };
+        tracing::debug!(job_id = %job_id, "cancelling BigQuery job");
+        if let Err(e) = self
+            .client
+            .job()
+            .cancel_job(&self.project_id, job_id, job.location.as_deref())
+            .await
+        {
+            tracing::warn!(error = %e, job_id = %job_id, "failed to cancel BigQuery job");
         }
     }
 
@@ -206,10 +224,7 @@ impl BigQueryConnection {
     ///
     /// Uses query() instead of query_all() to get schema and data from the same response,
     /// avoiding column ordering mismatches between separate API calls.
-    async fn execute_query(&self, sql: &str) -> Result<QueryResult> {
-        // Acquire slot with backpressure - rejects if queue timeout exceeded
-        let _permit = self.acquire_slot().await?;
-
+    async fn execute_query(&self, sql: &str, tags: &QueryTags) -> Result<QueryResult> {
         let start = Instant::now();
         tracing::debug!(
             project = %self.project_id,
@@ -219,25 +234,16 @@ impl BigQueryConnection {
         );
         tracing::trace!(sql = %sql, "BigQuery SQL");
 
-        // Build query request with config options
-        let mut query_request = QueryRequest::new(sql);
-        query_request.use_query_cache = Some(self.config.use_query_cache);
-        if self.config.maximum_bytes_billed > 0 {
-            query_request.maximum_bytes_billed = Some(self.config.maximum_bytes_billed.to_string());
-        }
+        let query_request = self.build_query_request(sql, tags);
 
         // Execute query - returns schema and data together, ensuring column order matches
-        let response = self
-            .client
-            .job()
-            .query(&self.project_id, query_request)
-            .await
-            .map_err(|e| {
-                tracing::error!(error = %e, "BigQuery query execution failed");
-                SemaflowError::Execution(format!("bigquery query: {e}"))
-            })?;
+        let response = self.run_job(query_request).await.map_err(|e| {
+            tracing::error!(error = %e, "BigQuery query execution failed");
+            e
+        })?;
 
         // Use ResultSet to handle schema and data together
+        let schema = response.schema.clone();
         let mut rs = ResultSet::new_from_query_response(response);
 
         // IMPORTANT: column_names() returns HashMap keys in arbitrary order (Rust HashMap)
@@ -246,10 +252,7 @@ impl BigQueryConnection {
         let col_names: Vec<String> = rs.column_names().iter().map(|s| s.to_string()).collect();
 
         // Build column metadata
-        let columns: Vec<ColumnMeta> = col_names
-            .iter()
-            .map(|name| ColumnMeta { name: name.clone() })
-            .collect();
+        let columns = columns_from_schema(&col_names, schema.as_ref());
 
         // Convert rows to JSON maps - use get_json_value_by_name for correct mapping
         let mut result_rows = Vec::new();
@@ -334,11 +337,12 @@ impl BackendConnection for BigQueryConnection {
             columns,
             primary_keys,
             foreign_keys,
+            unique_keys: Vec::new(),
         })
     }
 
     async fn execute_sql(&self, sql: &str) -> Result<QueryResult> {
-        self.execute_query(sql).await
+        self.execute_query(sql, &QueryTags::none()).await
     }
 
     async fn execute_sql_paginated(
@@ -348,9 +352,68 @@ impl BackendConnection for BigQueryConnection {
         cursor: Option<&Cursor>,
         query_hash: u64,
     ) -> Result<PaginatedResult> {
-        // Acquire slot with backpressure - rejects if queue timeout exceeded
-        let _permit = self.acquire_slot().await?;
+        self.execute_paginated(sql, page_size, cursor, query_hash, &QueryTags::none())
+            .await
+    }
+
+    async fn execute_sql_tagged(&self, sql: &str, tags: &QueryTags) -> Result<QueryResult> {
+        self.execute_query(sql, tags).await
+    }
 
+    async fn execute_sql_paginated_tagged(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+        tags: &QueryTags,
+    ) -> Result<PaginatedResult> {
+        self.execute_paginated(sql, page_size, cursor, query_hash, tags)
+            .await
+    }
+
+    /// Streams by walking the job's result pages, so each page reaches the
+    /// caller as soon as BigQuery returns it.
+    async fn execute_sql_streaming(
+        &self,
+        sql: &str,
+        batch_size: u32,
+        tags: &QueryTags,
+        on_batch: &mut BatchCallback<'_>,
+    ) -> Result<u64> {
+        let batch_size = batch_size.max(1);
+        let mut cursor: Option<Cursor> = None;
+        let mut total = 0u64;
+        loop {
+            let page = self
+                .execute_paginated(sql, batch_size, cursor.as_ref(), 0, tags)
+                .await?;
+            let next = page.cursor.as_deref().map(Cursor::decode).transpose()?;
+            total += page.rows.len() as u64;
+            if !page.rows.is_empty() {
+                on_batch(QueryResult {
+                    columns: page.columns,
+                    rows: page.rows,
+                })?;
+            }
+            match next {
+                Some(c) => cursor = Some(c),
+                None => return Ok(total),
+            }
+        }
+    }
+}
+
+impl BigQueryConnection {
+    /// Paginated execution. Labels only apply to the first page, which creates the job.
+    async fn execute_paginated(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+        tags: &QueryTags,
+    ) -> Result<PaginatedResult> {
         let start = Instant::now();
 
         // Handle subsequent pages (from cursor) vs first page differently
@@ -361,6 +424,7 @@ impl BackendConnection for BigQueryConnection {
                 page_token,
                 query_hash: cursor_hash,
                 offset,
+                ..
             }) => {
                 // Validate cursor matches this query
                 if *cursor_hash != query_hash {
@@ -404,31 +468,19 @@ impl BackendConnection for BigQueryConnection {
                     "executing initial BigQuery paginated query"
                 );
 
-                let mut query_request = QueryRequest::new(sql);
+                let mut query_request = self.build_query_request(sql, tags);
                 query_request.max_results = Some(page_size as i32);
-                query_request.use_query_cache = Some(self.config.use_query_cache);
-                if self.config.maximum_bytes_billed > 0 {
-                    query_request.maximum_bytes_billed =
-                        Some(self.config.maximum_bytes_billed.to_string());
-                }
 
-                let response = self
-                    .client
-                    .job()
-                    .query(&self.project_id, query_request)
-                    .await
-                    .map_err(|e| {
-                        tracing::error!(error = %e, "BigQuery paginated query failed");
-                        SemaflowError::Execution(format!("bigquery query: {e}"))
-                    })?;
+                let response = self.run_job(query_request).await.map_err(|e| {
+                    tracing::error!(error = %e, "BigQuery paginated query failed");
+                    e
+                })?;
 
                 self.process_query_response_paginated(response, query_hash, start)
             }
         }
     }
-}
 
-impl BigQueryConnection {
     /// Process QueryResponse (first page) for paginated results.
     fn process_query_response_paginated(
         &self,
@@ -454,13 +506,11 @@ impl BigQueryConnection {
 
         // Convert response to rows using ResultSet
         // IMPORTANT: Use get_json_value_by_name to avoid HashMap ordering issues
+        let schema = response.schema.clone();
         let mut rs = ResultSet::new_from_query_response(response);
         let col_names: Vec<String> = rs.column_names().iter().map(|s| s.to_string()).collect();
 
-        let columns: Vec<ColumnMeta> = col_names
-            .iter()
-            .map(|name| ColumnMeta { name: name.clone() })
-            .collect();
+        let columns = columns_from_schema(&col_names, schema.as_ref());
 
         let mut rows = Vec::new();
         while rs.next_row() {
@@ -536,10 +586,7 @@ impl BigQueryConnection {
             .map(|fields| fields.iter().map(|f| f.name.clone()).collect())
             .unwrap_or_default();
 
-        let columns: Vec<ColumnMeta> = col_names
-            .iter()
-            .map(|name| ColumnMeta { name: name.clone() })
-            .collect();
+        let columns = columns_from_schema(&col_names, response.schema.as_ref());
 
         // Convert rows
         let mut rows = Vec::new();
@@ -588,3 +635,22 @@ impl BigQueryConnection {
         })
     }
 }
+
+/// Column metadata for `names`, typed from the query response schema.
+fn columns_from_schema(
+    names: &[String],
+    schema: Option<&gcp_bigquery_client::model::table_schema::TableSchema>,
+) -> Vec<ColumnMeta> {
+    let fields = schema.and_then(|s| s.fields.as_ref());
+    names
+        .iter()
+        .map(|name| {
+            let field = fields.and_then(|fields| fields.iter().find(|f| f.name == *name));
+            ColumnMeta {
+                data_type: field.map(|f| format!("{:?}", f.r#type)),
+                nullable: field.map(|f| f.mode.as_ref().is_none_or(|m| m != "REQUIRED")),
+                ..ColumnMeta::named(name)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,586 @@
+//! Per-datasource circuit breaker.
+//!
+//! Wraps a backend connection and tracks consecutive execution failures. Once
+//! `failure_threshold` is reached the breaker opens and queries fail fast with
+//! [`SemaflowError::Unavailable`] instead of piling up against a dead source.
+//! While open, a background task probes the source with
+//! [`BackendConnection::health_check`]; after
+//! `open_timeout_ms` a single trial query is also let through (half-open).
+//! Either success closes the breaker again.
+//!
+//! When `serve_stale` is enabled, the last successful result for each SQL
+//! string is retained and returned while the breaker is open.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use crate::config::CircuitBreakerConfig;
+use crate::dialect::Dialect;
+use crate::error::{Result, SemaflowError};
+#[cfg(feature = "arrow")]
+use crate::executor::ArrowResult;
+use crate::executor::{PaginatedResult, QueryResult};
+use crate::pagination::Cursor;
+use crate::query_tags::QueryTags;
+use crate::schema_cache::TableSchema;
+
+use super::{BackendConnection, BatchCallback};
+
+/// Observable breaker state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Healthy; queries flow through.
+    Closed,
+    /// Failing fast until the source recovers.
+    Open,
+    /// A trial query is in flight after the open timeout elapsed.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+    probing: bool,
+}
+
+/// Bounded SQL -> last good result map used for stale serving.
+#[derive(Debug, Default)]
+struct StaleResults {
+    results: HashMap<String, QueryResult>,
+    order: VecDeque<String>,
+}
+
+impl StaleResults {
+    fn insert(&mut self, sql: &str, result: &QueryResult, max_size: usize) {
+        if max_size == 0 {
+            return;
+        }
+        if self
+            .results
+            .insert(sql.to_string(), result.clone())
+            .is_none()
+        {
+            self.order.push_back(sql.to_string());
+        }
+        while self.order.len() > max_size {
+            if let Some(oldest) = self.order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Admission of one query; for a half-open trial, dropping it before the
+/// outcome is recorded (e.g. when the query is cancelled or times out)
+/// releases the trial slot so a later query can try again.
+struct Admission {
+    trial: Option<Arc<Mutex<BreakerState>>>,
+}
+
+impl Admission {
+    /// The outcome was recorded, which already ended any trial.
+    fn finish(mut self) {
+        self.trial = None;
+    }
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        if let Some(state) = self.trial.take() {
+            state.lock().unwrap().trial_in_flight = false;
+        }
+    }
+}
+
+/// Backend connection guarded by a circuit breaker.
+pub struct CircuitBreakerConnection {
+    name: String,
+    inner: Arc<dyn BackendConnection>,
+    config: CircuitBreakerConfig,
+    state: Arc<Mutex<BreakerState>>,
+    stale: Mutex<StaleResults>,
+}
+
+impl CircuitBreakerConnection {
+    pub fn new(
+        name: impl Into<String>,
+        inner: Arc<dyn BackendConnection>,
+        config: CircuitBreakerConfig,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            inner,
+            config,
+            state: Arc::new(Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                opened_at: None,
+                trial_in_flight: false,
+                probing: false,
+            })),
+            stale: Mutex::new(StaleResults::default()),
+        }
+    }
+
+    /// Current breaker state.
+    pub fn state(&self) -> CircuitState {
+        let state = self.state.lock().unwrap();
+        match state.opened_at {
+            None => CircuitState::Closed,
+            Some(_) if state.trial_in_flight => CircuitState::HalfOpen,
+            Some(_) => CircuitState::Open,
+        }
+    }
+
+    /// The wrapped connection.
+    pub fn inner(&self) -> &Arc<dyn BackendConnection> {
+        &self.inner
+    }
+
+    /// Decide whether a query may proceed. Errors when the breaker is open.
+    fn admit(&self) -> Result<Admission> {
+        let mut state = self.state.lock().unwrap();
+        let Some(opened_at) = state.opened_at else {
+            return Ok(Admission { trial: None });
+        };
+        let open_for = Duration::from_millis(self.config.open_timeout_ms);
+        if !state.trial_in_flight && opened_at.elapsed() >= open_for {
+            tracing::debug!(data_source = %self.name, "circuit half-open, allowing trial query");
+            state.trial_in_flight = true;
+            return Ok(Admission {
+                trial: Some(self.state.clone()),
+            });
+        }
+        Err(SemaflowError::Unavailable(format!(
+            "data source {} unavailable after {} consecutive failures; retrying in background",
+            self.name, state.consecutive_failures
+        )))
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.opened_at.is_some() {
+            tracing::info!(data_source = %self.name, "circuit closed, data source recovered");
+        }
+        state.consecutive_failures = 0;
+        state.opened_at = None;
+        state.trial_in_flight = false;
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        let was_trial = state.trial_in_flight;
+        state.trial_in_flight = false;
+        if was_trial || state.consecutive_failures >= self.config.failure_threshold {
+            if state.opened_at.is_none() {
+                tracing::warn!(
+                    data_source = %self.name,
+                    failures = state.consecutive_failures,
+                    "circuit opened, failing fast"
+                );
+            }
+            state.opened_at = Some(Instant::now());
+            if !state.probing {
+                state.probing = self.spawn_probe();
+            }
+        }
+    }
+
+    /// Spawn a background recovery probe. Returns false when no tokio runtime is
+    /// available or probing is disabled; the half-open trial still applies.
+    fn spawn_probe(&self) -> bool {
+        if self.config.probe_interval_ms == 0 {
+            return false;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return false;
+        };
+        let name = self.name.clone();
+        let inner = self.inner.clone();
+        let state = self.state.clone();
+        let interval = Duration::from_millis(self.config.probe_interval_ms);
+        handle.spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if state.lock().unwrap().opened_at.is_none() {
+                    break;
+                }
+                match inner.health_check().await {
+                    Ok(_) => {
+                        tracing::info!(data_source = %name, "recovery probe succeeded, closing circuit");
+                        let mut guard = state.lock().unwrap();
+                        guard.consecutive_failures = 0;
+                        guard.opened_at = None;
+                        break;
+                    }
+                    Err(e) => {
+                        tracing::debug!(data_source = %name, error = %e, "recovery probe failed");
+                    }
+                }
+            }
+            state.lock().unwrap().probing = false;
+        });
+        true
+    }
+
+    /// Record the outcome of a call, counting only source failures.
+    fn observe<T>(&self, admission: Admission, result: &Result<T>) {
+        match result {
+            Ok(_) => self.record_success(),
+            Err(e) if is_source_failure(e) => self.record_failure(),
+            // Caller errors (bad cursor, validation) say nothing about source health.
+            Err(_) => {
+                self.state.lock().unwrap().trial_in_flight = false;
+            }
+        }
+        admission.finish();
+    }
+
+    fn stale_result(&self, sql: &str, err: SemaflowError) -> Result<QueryResult> {
+        if self.config.serve_stale {
+            if let Some(result) = self.stale.lock().unwrap().results.get(sql) {
+                tracing::warn!(data_source = %self.name, "serving stale result while source unavailable");
+                return Ok(result.clone());
+            }
+        }
+        Err(err)
+    }
+
+    async fn guarded_execute(&self, sql: &str, tags: &QueryTags) -> Result<QueryResult> {
+        let admission = match self.admit() {
+            Ok(admission) => admission,
+            Err(e) => return self.stale_result(sql, e),
+        };
+        let result = self.inner.execute_sql_tagged(sql, tags).await;
+        self.observe(admission, &result);
+        match result {
+            Ok(r) => {
+                if self.config.serve_stale {
+                    self.stale
+                        .lock()
+                        .unwrap()
+                        .insert(sql, &r, self.config.stale_cache_size);
+                }
+                Ok(r)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Whether an error indicates the source itself is failing. Timeouts are
+/// imposed by the caller, so a slow query doesn't open the breaker.
+fn is_source_failure(err: &SemaflowError) -> bool {
+    match err {
+        SemaflowError::Execution(_) | SemaflowError::Io(_) => true,
+        #[cfg(feature = "duckdb")]
+        SemaflowError::DuckDb(_) => true,
+        _ => false,
+    }
+}
+
+#[async_trait]
+impl BackendConnection for CircuitBreakerConnection {
+    fn dialect(&self) -> &(dyn Dialect + Send + Sync) {
+        self.inner.dialect()
+    }
+
+    async fn fetch_schema(&self, table: &str) -> Result<TableSchema> {
+        let admission = self.admit()?;
+        let result = self.inner.fetch_schema(table).await;
+        self.observe(admission, &result);
+        result
+    }
+
+    async fn execute_sql(&self, sql: &str) -> Result<QueryResult> {
+        self.guarded_execute(sql, &QueryTags::none()).await
+    }
+
+    async fn execute_sql_paginated(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+    ) -> Result<PaginatedResult> {
+        self.execute_sql_paginated_tagged(sql, page_size, cursor, query_hash, &QueryTags::none())
+            .await
+    }
+
+    async fn execute_sql_tagged(&self, sql: &str, tags: &QueryTags) -> Result<QueryResult> {
+        self.guarded_execute(sql, tags).await
+    }
+
+    async fn execute_sql_paginated_tagged(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+        tags: &QueryTags,
+    ) -> Result<PaginatedResult> {
+        // Pages are never served stale: a cached page could not be continued.
+        let admission = self.admit()?;
+        let result = self
+            .inner
+            .execute_sql_paginated_tagged(sql, page_size, cursor, query_hash, tags)
+            .await;
+        self.observe(admission, &result);
+        result
+    }
+
+    async fn execute_sql_streaming(
+        &self,
+        sql: &str,
+        batch_size: u32,
+        tags: &QueryTags,
+        on_batch: &mut BatchCallback<'_>,
+    ) -> Result<u64> {
+        // Streams are never served stale: batches may already have been delivered.
+        let admission = self.admit()?;
+        let result = self
+            .inner
+            .execute_sql_streaming(sql, batch_size, tags, on_batch)
+            .await;
+        self.observe(admission, &result);
+        result
+    }
+
+    #[cfg(feature = "arrow")]
+    async fn execute_sql_arrow(&self, sql: &str, tags: &QueryTags) -> Result<ArrowResult> {
+        // Stale results are kept as rows, so Arrow results are never served stale.
+        let admission = self.admit()?;
+        let result = self.inner.execute_sql_arrow(sql, tags).await;
+        self.observe(admission, &result);
+        result
+    }
+
+    /// Checks the source even while open, so callers see its real health;
+    /// a passing check closes the breaker.
+    async fn health_check(&self) -> Result<()> {
+        let result = self.inner.health_check().await;
+        self.observe(Admission { trial: None }, &result);
+        result
+    }
+}
+
+#[cfg(all(test, feature = "duckdb"))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct FlakyConnection {
+        down: AtomicBool,
+        calls: AtomicUsize,
+    }
+
+    impl FlakyConnection {
+        fn new(down: bool) -> Self {
+            Self {
+                down: AtomicBool::new(down),
+                calls: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl BackendConnection for FlakyConnection {
+        fn dialect(&self) -> &(dyn Dialect + Send + Sync) {
+            &crate::dialect::DuckDbDialect
+        }
+
+        async fn fetch_schema(&self, _table: &str) -> Result<TableSchema> {
+            Ok(TableSchema {
+                columns: vec![],
+                primary_keys: vec![],
+                foreign_keys: vec![],
+                unique_keys: vec![],
+            })
+        }
+
+        async fn execute_sql(&self, sql: &str) -> Result<QueryResult> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.down.load(Ordering::SeqCst) {
+                return Err(SemaflowError::Execution("connection refused".to_string()));
+            }
+            let mut row = serde_json::Map::new();
+            row.insert("sql".to_string(), serde_json::Value::from(sql));
+            Ok(QueryResult {
+                columns: vec![],
+                rows: vec![row],
+            })
+        }
+
+        async fn execute_sql_paginated(
+            &self,
+            _sql: &str,
+            _page_size: u32,
+            _cursor: Option<&Cursor>,
+            _query_hash: u64,
+        ) -> Result<PaginatedResult> {
+            unimplemented!()
+        }
+    }
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 2,
+            open_timeout_ms: 60_000,
+            probe_interval_ms: 0,
+            serve_stale: false,
+            stale_cache_size: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn opens_after_threshold_and_fails_fast() {
+        let inner = Arc::new(FlakyConnection::new(true));
+        let breaker = CircuitBreakerConnection::new("wh", inner.clone(), config());
+
+        assert!(breaker.execute_sql("SELECT 2").await.is_err());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.execute_sql("SELECT 2").await.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let err = breaker.execute_sql("SELECT 2").await.unwrap_err();
+        assert!(matches!(err, SemaflowError::Unavailable(_)));
+        assert_eq!(
+            inner.calls.load(Ordering::SeqCst),
+            2,
+            "open breaker skips backend"
+        );
+    }
+
+    #[tokio::test]
+    async fn half_open_trial_closes_on_success() {
+        let inner = Arc::new(FlakyConnection::new(true));
+        let breaker = CircuitBreakerConnection::new(
+            "wh",
+            inner.clone(),
+            CircuitBreakerConfig {
+                open_timeout_ms: 0,
+                ..config()
+            },
+        );
+        let _ = breaker.execute_sql("SELECT 2").await;
+        let _ = breaker.execute_sql("SELECT 2").await;
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        inner.down.store(false, Ordering::SeqCst);
+        assert!(breaker.execute_sql("SELECT 2").await.is_ok());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn dropped_trial_releases_the_half_open_slot() {
+        let inner = Arc::new(FlakyConnection::new(true));
+        let breaker = CircuitBreakerConnection::new(
+            "wh",
+            inner.clone(),
+            CircuitBreakerConfig {
+                open_timeout_ms: 0,
+                ..config()
+            },
+        );
+        let _ = breaker.execute_sql("SELECT 2").await;
+        let _ = breaker.execute_sql("SELECT 2").await;
+
+        // A trial whose query is cancelled never records an outcome
+        let trial = breaker.admit().unwrap();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.admit().is_err());
+        drop(trial);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        inner.down.store(false, Ordering::SeqCst);
+        assert!(breaker.execute_sql("SELECT 2").await.is_ok());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn timeouts_are_not_source_failures() {
+        let breaker =
+            CircuitBreakerConnection::new("wh", Arc::new(FlakyConnection::new(false)), config());
+        for _ in 0..3 {
+            let timeout: Result<()> = Err(SemaflowError::Timeout("query timed out".to_string()));
+            breaker.observe(Admission { trial: None }, &timeout);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn serves_stale_result_when_open() {
+        let inner = Arc::new(FlakyConnection::new(false));
+        let breaker = CircuitBreakerConnection::new(
+            "wh",
+            inner.clone(),
+            CircuitBreakerConfig {
+                serve_stale: true,
+                ..config()
+            },
+        );
+        let fresh = breaker.execute_sql("SELECT 2").await.unwrap();
+
+        inner.down.store(true, Ordering::SeqCst);
+        let _ = breaker.execute_sql("SELECT 3").await;
+        let _ = breaker.execute_sql("SELECT 3").await;
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let stale = breaker.execute_sql("SELECT 2").await.unwrap();
+        assert_eq!(stale.rows, fresh.rows);
+        assert!(matches!(
+            breaker.execute_sql("SELECT 3").await,
+            Err(SemaflowError::Unavailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn health_check_bypasses_open_breaker() {
+        let inner = Arc::new(FlakyConnection::new(true));
+        let breaker = CircuitBreakerConnection::new("wh", inner.clone(), config());
+        let _ = breaker.execute_sql("SELECT 2").await;
+        let _ = breaker.execute_sql("SELECT 2").await;
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        assert!(matches!(
+            breaker.health_check().await,
+            Err(SemaflowError::Execution(_))
+        ));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        inner.down.store(false, Ordering::SeqCst);
+        assert!(breaker.health_check().await.is_ok());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn background_probe_recovers() {
+        let inner = Arc::new(FlakyConnection::new(true));
+        let breaker = CircuitBreakerConnection::new(
+            "wh",
+            inner.clone(),
+            CircuitBreakerConfig {
+                probe_interval_ms: 5,
+                ..config()
+            },
+        );
+        let _ = breaker.execute_sql("SELECT 2").await;
+        let _ = breaker.execute_sql("SELECT 2").await;
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        inner.down.store(false, Ordering::SeqCst);
+        for _ in 0..100 {
+            if breaker.state() == CircuitState::Closed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
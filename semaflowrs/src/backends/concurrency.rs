@@ -0,0 +1,339 @@
+//! Concurrency budgets enforced by the connection manager.
+//!
+//! Before a query reaches its backend it takes a slot from its flow's budget,
+//! its datasource's budget and the global budget, in that order. A query that
+//! waits longer than a budget's `queue_timeout_ms` for a slot fails with
+//! [`SemaflowError::Overloaded`] instead of queuing behind a saturated source.
+//! Budgets with `max_concurrent = 0` are not enforced.
+//!
+//! Every budget counts queued queries and wait times; see [`BudgetStats`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::{ConcurrencyConfig, ConcurrencyLimit};
+use crate::error::{Result, SemaflowError};
+
+/// What a budget limits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "scope", content = "name", rename_all = "snake_case")]
+pub enum BudgetScope {
+    /// Queries against all datasources.
+    Global,
+    /// Queries against one datasource.
+    DataSource(String),
+    /// Queries of one flow.
+    Flow(String),
+}
+
+impl fmt::Display for BudgetScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetScope::Global => f.write_str("global budget"),
+            BudgetScope::DataSource(name) => write!(f, "data source {name}"),
+            BudgetScope::Flow(name) => write!(f, "flow {name}"),
+        }
+    }
+}
+
+/// Counters of one budget.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStats {
+    #[serde(flatten)]
+    pub scope: BudgetScope,
+    pub max_concurrent: usize,
+    /// Queries holding a slot.
+    pub in_flight: usize,
+    /// Queries waiting for a slot.
+    pub queued: usize,
+    /// Queries given a slot since startup.
+    pub admitted: u64,
+    /// Queries rejected after waiting `queue_timeout_ms`.
+    pub rejected: u64,
+    /// Total time admitted queries waited for a slot.
+    pub total_wait_ms: u64,
+    /// Longest time a query waited for a slot.
+    pub max_wait_ms: u64,
+}
+
+struct Budget {
+    scope: BudgetScope,
+    limit: ConcurrencyLimit,
+    semaphore: Arc<Semaphore>,
+    queued: AtomicUsize,
+    admitted: AtomicU64,
+    rejected: AtomicU64,
+    wait_us: AtomicU64,
+    max_wait_us: AtomicU64,
+}
+
+/// Counts a query as queued until dropped, so cancelled waits leave the queue too.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl<'a> Queued<'a> {
+    fn enter(queued: &'a AtomicUsize) -> Self {
+        queued.fetch_add(1, Ordering::Relaxed);
+        Self(queued)
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Budget {
+    fn new(scope: BudgetScope, limit: ConcurrencyLimit) -> Self {
+        Self {
+            scope,
+            semaphore: Arc::new(Semaphore::new(limit.max_concurrent)),
+            limit,
+            queued: AtomicUsize::new(0),
+            admitted: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            wait_us: AtomicU64::new(0),
+            max_wait_us: AtomicU64::new(0),
+        }
+    }
+
+    async fn acquire(&self) -> Result<OwnedSemaphorePermit> {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            self.admitted.fetch_add(1, Ordering::Relaxed);
+            return Ok(permit);
+        }
+        tracing::debug!(
+            budget = %self.scope,
+            max_concurrent = self.limit.max_concurrent,
+            queued = self.queued.load(Ordering::Relaxed),
+            "concurrency budget exhausted, waiting for a slot"
+        );
+
+        let start = Instant::now();
+        let permit = {
+            let _queued = Queued::enter(&self.queued);
+            let acquire = self.semaphore.clone().acquire_owned();
+            match self.limit.queue_timeout() {
+                Some(timeout) => tokio::time::timeout(timeout, acquire).await.ok(),
+                None => Some(acquire.await),
+            }
+        };
+        let waited = start.elapsed();
+        match permit {
+            Some(permit) => {
+                let permit =
+                    permit.map_err(|e| SemaflowError::Execution(format!("limiter closed: {e}")))?;
+                self.record_wait(waited);
+                Ok(permit)
+            }
+            None => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    budget = %self.scope,
+                    max_concurrent = self.limit.max_concurrent,
+                    queue_timeout_ms = self.limit.queue_timeout_ms,
+                    "query rejected: queue timeout exceeded"
+                );
+                Err(SemaflowError::Overloaded(format!(
+                    "{} is at its limit of {} concurrent queries; waited {}ms for a slot",
+                    self.scope,
+                    self.limit.max_concurrent,
+                    waited.as_millis()
+                )))
+            }
+        }
+    }
+
+    fn record_wait(&self, waited: Duration) {
+        let us = waited.as_micros() as u64;
+        self.admitted.fetch_add(1, Ordering::Relaxed);
+        self.wait_us.fetch_add(us, Ordering::Relaxed);
+        self.max_wait_us.fetch_max(us, Ordering::Relaxed);
+        tracing::debug!(
+            budget = %self.scope,
+            wait_ms = waited.as_millis(),
+            "query admitted after waiting for a slot"
+        );
+    }
+
+    fn stats(&self) -> BudgetStats {
+        BudgetStats {
+            scope: self.scope.clone(),
+            max_concurrent: self.limit.max_concurrent,
+            in_flight: self
+                .limit
+                .max_concurrent
+                .saturating_sub(self.semaphore.available_permits()),
+            queued: self.queued.load(Ordering::Relaxed),
+            admitted: self.admitted.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            total_wait_ms: self.wait_us.load(Ordering::Relaxed) / 1000,
+            max_wait_ms: self.max_wait_us.load(Ordering::Relaxed) / 1000,
+        }
+    }
+}
+
+/// Slots held by a running query; released when dropped.
+#[derive(Debug)]
+pub struct QueryPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+/// Global, per-datasource and per-flow concurrency budgets.
+///
+/// Datasource and flow budgets are created on first use.
+#[derive(Default)]
+pub struct ConcurrencyLimiter {
+    global: Option<Budget>,
+    flow_limits: HashMap<String, ConcurrencyLimit>,
+    flows: Mutex<HashMap<String, Arc<Budget>>>,
+    data_sources: Mutex<HashMap<String, Arc<Budget>>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(config: &ConcurrencyConfig) -> Self {
+        Self {
+            global: (config.global.max_concurrent > 0)
+                .then(|| Budget::new(BudgetScope::Global, config.global.clone())),
+            flow_limits: config.flows.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Take a slot from every budget applying to a query of `flow` against
+    /// `data_source`, whose own budget is `data_source_limit`.
+    pub async fn acquire(
+        &self,
+        flow: &str,
+        data_source: &str,
+        data_source_limit: &ConcurrencyLimit,
+    ) -> Result<QueryPermit> {
+        let mut permits = Vec::new();
+        if let Some(limit) = self.flow_limits.get(flow) {
+            if let Some(budget) = budget(&self.flows, flow, BudgetScope::Flow, limit) {
+                permits.push(budget.acquire().await?);
+            }
+        }
+        if let Some(budget) = budget(
+            &self.data_sources,
+            data_source,
+            BudgetScope::DataSource,
+            data_source_limit,
+        ) {
+            permits.push(budget.acquire().await?);
+        }
+        if let Some(budget) = &self.global {
+            permits.push(budget.acquire().await?);
+        }
+        Ok(QueryPermit { _permits: permits })
+    }
+
+    /// Counters of every enforced budget: global first, then datasources and
+    /// flows by name.
+    pub fn stats(&self) -> Vec<BudgetStats> {
+        let mut stats: Vec<BudgetStats> = self.global.iter().map(Budget::stats).collect();
+        for budgets in [&self.data_sources, &self.flows] {
+            let budgets = budgets.lock().unwrap();
+            let mut names: Vec<&String> = budgets.keys().collect();
+            names.sort();
+            stats.extend(names.into_iter().map(|name| budgets[name].stats()));
+        }
+        stats
+    }
+}
+
+/// The budget for `name` in `budgets`, created on first use; `None` when
+/// `limit` is unlimited.
+fn budget(
+    budgets: &Mutex<HashMap<String, Arc<Budget>>>,
+    name: &str,
+    scope: fn(String) -> BudgetScope,
+    limit: &ConcurrencyLimit,
+) -> Option<Arc<Budget>> {
+    if limit.max_concurrent == 0 {
+        return None;
+    }
+    let mut budgets = budgets.lock().unwrap();
+    let budget = budgets
+        .entry(name.to_string())
+        .or_insert_with(|| Arc::new(Budget::new(scope(name.to_string()), limit.clone())));
+    Some(budget.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(max_concurrent: usize, queue_timeout_ms: u64) -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            max_concurrent,
+            queue_timeout_ms,
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_after_queue_timeout() {
+        let limiter = ConcurrencyLimiter::default();
+        let held = limiter.acquire("sales", "wh", &limit(1, 10)).await.unwrap();
+
+        let err = limiter
+            .acquire("sales", "wh", &limit(1, 10))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SemaflowError::Overloaded(_)), "{err}");
+        assert!(err.to_string().contains("data source wh"), "{err}");
+
+        // Other datasources have their own budget
+        limiter
+            .acquire("sales", "local", &limit(1, 10))
+            .await
+            .unwrap();
+
+        drop(held);
+        limiter.acquire("sales", "wh", &limit(1, 10)).await.unwrap();
+
+        let stats = limiter.stats();
+        let wh = stats
+            .iter()
+            .find(|s| s.scope == BudgetScope::DataSource("wh".to_string()))
+            .unwrap();
+        assert_eq!(
+            (wh.admitted, wh.rejected, wh.queued, wh.in_flight),
+            (2, 1, 0, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn waiting_query_gets_released_slot() {
+        let config = ConcurrencyConfig {
+            global: limit(0, 0),
+            flows: HashMap::from([("finance".to_string(), limit(1, 0))]),
+        };
+        let limiter = Arc::new(ConcurrencyLimiter::new(&config));
+        let held = limiter
+            .acquire("finance", "wh", &limit(0, 0))
+            .await
+            .unwrap();
+
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire("finance", "wh", &limit(0, 0)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(limiter.stats()[0].queued, 1);
+
+        drop(held);
+        waiter.await.unwrap().unwrap();
+        let stats = limiter.stats();
+        assert_eq!(stats.len(), 1, "unlimited budgets are not tracked");
+        assert_eq!(stats[0].scope, BudgetScope::Flow("finance".to_string()));
+        assert_eq!((stats[0].admitted, stats[0].queued), (2, 0));
+        assert!(stats[0].max_wait_ms >= 10);
+    }
+}
@@ -7,13 +7,15 @@ use std::time::Instant;
 use arrow::array::RecordBatch;
 use arrow::datatypes::{DataType, Schema};
 use async_trait::async_trait;
-use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+use tokio::sync::Mutex;
 
+use crate::cancel;
 use crate::config::DuckDbConfig;
-use crate::dialect::DuckDbDialect;
+use crate::dialect::{Dialect, DuckDbDialect};
 use crate::error::{Result, SemaflowError};
-use crate::executor::{ColumnMeta, PaginatedResult, QueryResult};
+use crate::executor::{ArrowResult, ColumnMeta, PaginatedResult, QueryResult};
 use crate::pagination::Cursor;
+use crate::query_tags::QueryTags;
 use crate::schema_cache::{ForeignKey, TableSchema};
 
 use super::BackendConnection;
@@ -23,9 +25,13 @@ use super::BackendConnection;
 pub struct DuckDbConnection {
     database_path: PathBuf,
     dialect: DuckDbDialect,
-    limiter: Arc<Semaphore>,
+    /// Idle connections, at most `config.pool_size`.
     pool: Arc<Mutex<Vec<duckdb::Connection>>>,
-    /// Whether this is an in-memory database (connections cannot be recreated)
+    /// The first connection opened. Later connections are cloned from it so
+    /// they share one database instance rather than each opening the file.
+    database: Arc<std::sync::Mutex<Option<duckdb::Connection>>>,
+    config: DuckDbConfig,
+    /// Whether this is an in-memory database
     is_memory: bool,
 }
 
@@ -40,54 +46,82 @@ impl DuckDbConnection {
         let is_memory = path.to_str() == Some(":memory:");
         tracing::info!(
             path = %path.display(),
-            max_concurrency = config.max_concurrency,
+            pool_size = config.pool_size,
+            read_only = config.read_only,
             is_memory = is_memory,
             "creating DuckDB connection"
         );
         Self {
             database_path: path,
             dialect: DuckDbDialect,
-            limiter: Arc::new(Semaphore::new(config.max_concurrency)),
             pool: Arc::new(Mutex::new(Vec::new())),
+            database: Arc::new(std::sync::Mutex::new(None)),
+            config,
             is_memory,
         }
     }
 
-    /// Configure maximum concurrent executions; callers can tune based on hardware.
-    pub fn with_max_concurrency(mut self, max_in_flight: usize) -> Self {
+    /// Open the database and pool one connection, so a missing file or an
+    /// invalid setting fails here rather than on the first query.
+    pub async fn initialize_pool(&self) -> Result<()> {
+        let conn = self.open_connection()?;
+        self.release(conn).await;
         tracing::debug!(
-            max_concurrency = max_in_flight,
-            "configuring DuckDB concurrency"
+            path = %self.database_path.display(),
+            "initialized DuckDB connection pool"
         );
-        self.limiter = Arc::new(Semaphore::new(max_in_flight));
-        self
+        Ok(())
     }
 
-    /// Initialize the connection pool with one connection.
+    /// Open a connection, cloning the first one so every connection shares
+    /// one database instance: in-memory databases stay visible to all of
+    /// them, and a file is opened (and locked) once per process.
     ///
-    /// For in-memory databases, this MUST be called before any queries,
-    /// as new connections cannot be created (they would be empty databases).
-    pub async fn initialize_pool(&self) -> Result<()> {
-        let conn = duckdb::Connection::open(self.database_path.clone())
-            .map_err(|e| SemaflowError::Execution(format!("open duckdb: {e}")))?;
-        let mut guard = self.pool.lock().await;
-        guard.push(conn);
+    /// The first connection opens the database with the configured access
+    /// mode, `database_threads` and `database_memory_limit`; the database
+    /// instance applies them to all connections and queries together.
+    fn open_connection(&self) -> Result<duckdb::Connection> {
+        let mut database = self.database.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(conn) = database.as_ref() {
+            return conn
+                .try_clone()
+                .map_err(|e| SemaflowError::Execution(format!("clone duckdb connection: {e}")));
+        }
+
+        let mut flags = duckdb::Config::default();
+        // An in-memory database starts empty, so it cannot be read-only
+        if self.config.read_only && !self.is_memory {
+            flags = flags.access_mode(duckdb::AccessMode::ReadOnly)?;
+        }
+        if let Some(threads) = self.config.database_threads {
+            flags = flags.threads(threads as i64)?;
+        }
+        if let Some(limit) = &self.config.database_memory_limit {
+            flags = flags.max_memory(limit)?;
+        }
         tracing::debug!(
             path = %self.database_path.display(),
-            "initialized DuckDB connection pool"
+            read_only = self.config.read_only,
+            threads = ?self.config.database_threads,
+            memory_limit = ?self.config.database_memory_limit,
+            "opening DuckDB database"
         );
-        Ok(())
+        let conn = duckdb::Connection::open_with_flags(&self.database_path, flags)
+            .map_err(|e| SemaflowError::Execution(format!("open duckdb: {e}")))?;
+        let clone = conn
+            .try_clone()
+            .map_err(|e| SemaflowError::Execution(format!("clone duckdb connection: {e}")))?;
+        *database = Some(conn);
+        Ok(clone)
     }
 
-    async fn acquire_slot(&self) -> Result<SemaphorePermit<'_>> {
-        let available = self.limiter.available_permits();
-        if available == 0 {
-            tracing::debug!("all DuckDB slots in use, waiting for permit");
+    /// Return `conn` to the pool, or close it when `pool_size` idle
+    /// connections are already kept.
+    async fn release(&self, conn: duckdb::Connection) {
+        let mut pool = self.pool.lock().await;
+        if pool.len() < self.config.pool_size.max(1) {
+            pool.push(conn);
         }
-        self.limiter
-            .acquire()
-            .await
-            .map_err(|e| SemaflowError::Execution(format!("limiter closed: {e}")))
     }
 
     async fn checkout_connection(&self) -> Result<duckdb::Connection> {
@@ -104,46 +138,42 @@ impl DuckDbConnection {
                 return Ok(conn);
             }
         }
-
-        // Pool is empty - for in-memory databases, we need to wait for one
-        // since opening a new :memory: connection creates a separate empty database.
-        if self.is_memory {
-            // This should only happen during concurrent access after pool was initialized.
-            // If we get here before initialization, we have a bug.
-            tracing::debug!("waiting for in-memory DuckDB connection to be returned to pool");
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
-                let mut guard = self.pool.lock().await;
-                if let Some(conn) = guard.pop() {
-                    tracing::trace!("got in-memory DuckDB connection from pool");
-                    return Ok(conn);
-                }
-            }
-        }
-
-        // For file-based databases, open a new connection
         tracing::debug!(path = %self.database_path.display(), "opening new DuckDB connection");
-        duckdb::Connection::open(self.database_path.clone())
-            .map_err(|e| SemaflowError::Execution(format!("open duckdb: {e}")))
+        self.open_connection()
     }
 
-    /// Get a connection from pool, or create one if pool is empty.
+    /// Run `work` on `conn` in a blocking task, then return the connection to
+    /// the pool.
     ///
-    /// Unlike `checkout_connection`, this will create a new connection if needed,
-    /// which is correct for initial setup (like register_arrow_table) but NOT for
-    /// queries on in-memory databases (where we must reuse the existing connection).
-    async fn get_or_create_connection(&self) -> Result<duckdb::Connection> {
-        {
-            let mut guard = self.pool.lock().await;
-            if let Some(conn) = guard.pop() {
-                tracing::trace!("reusing pooled DuckDB connection for registration");
-                return Ok(conn);
+    /// If the query's [`cancel`] scope is cancelled first, the connection is
+    /// interrupted and the task awaited, so the connection (and the caller's
+    /// concurrency slot) is only released once DuckDB has stopped.
+    async fn run_cancellable<T, F>(&self, conn: duckdb::Connection, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&duckdb::Connection) -> Result<T> + Send + 'static,
+    {
+        let scope = cancel::current();
+        let interrupt = conn.interrupt_handle();
+        let mut task = tokio::task::spawn_blocking(move || {
+            let result = work(&conn);
+            (result, conn)
+        });
+        let joined = tokio::select! {
+            joined = &mut task => joined,
+            _ = scope.cancelled() => {
+                tracing::debug!("interrupting cancelled DuckDB query");
+                interrupt.interrupt();
+                task.await
             }
+        };
+        let (result, conn) =
+            joined.map_err(|e| SemaflowError::Execution(format!("task join error: {e}")))?;
+        self.release(conn).await;
+        match result {
+            Err(_) if scope.is_cancelled() => Err(cancel::cancelled_error(&scope)),
+            result => result,
         }
-        // Create new connection - this is OK for initial setup
-        tracing::debug!(path = %self.database_path.display(), "creating initial DuckDB connection");
-        duckdb::Connection::open(self.database_path.clone())
-            .map_err(|e| SemaflowError::Execution(format!("open duckdb: {e}")))
     }
 
     /// Register an Arrow table in DuckDB by creating a table from schema and appending batches.
@@ -157,9 +187,7 @@ impl DuckDbConnection {
     ) -> Result<()> {
         let table_name = table_name.to_string();
         let schema = schema.clone();
-        // Use get_or_create since this might be the first call (pool empty)
-        let conn = self.get_or_create_connection().await?;
-        let pool = self.pool.clone();
+        let conn = self.checkout_connection().await?;
 
         let result = tokio::task::spawn_blocking(move || -> Result<duckdb::Connection> {
             let start = Instant::now();
@@ -196,10 +224,7 @@ impl DuckDbConnection {
         .map_err(|e| SemaflowError::Execution(format!("task join error: {e}")))?;
 
         let conn = result?;
-        {
-            let mut guard = pool.lock().await;
-            guard.push(conn);
-        }
+        self.release(conn).await;
         Ok(())
     }
 }
@@ -254,7 +279,6 @@ impl BackendConnection for DuckDbConnection {
     async fn fetch_schema(&self, table: &str) -> Result<TableSchema> {
         let table = table.to_string();
         let conn = self.checkout_connection().await?;
-        let pool = self.pool.clone();
         let result =
             tokio::task::spawn_blocking(move || -> Result<(TableSchema, duckdb::Connection)> {
                 let start = Instant::now();
@@ -307,6 +331,7 @@ impl BackendConnection for DuckDbConnection {
                         columns,
                         primary_keys,
                         foreign_keys,
+                        unique_keys: Vec::new(),
                     },
                     conn,
                 ))
@@ -315,67 +340,57 @@ impl BackendConnection for DuckDbConnection {
             .map_err(|e| SemaflowError::Execution(format!("task join error: {e}")))?;
 
         let (schema, conn) = result?;
-        {
-            let mut guard = pool.lock().await;
-            guard.push(conn);
-        }
+        self.release(conn).await;
         Ok(schema)
     }
 
     async fn execute_sql(&self, sql: &str) -> Result<QueryResult> {
         let sql = sql.to_string();
-        let _permit = self.acquire_slot().await?;
         let conn = self.checkout_connection().await?;
-        let pool = self.pool.clone();
-        let result =
-            tokio::task::spawn_blocking(move || -> Result<(QueryResult, duckdb::Connection)> {
-                let start = Instant::now();
-                let conn = conn;
-                let mut stmt = conn.prepare(&sql)?;
-                let mut rows_iter = stmt.query([])?;
-                let stmt_ref = rows_iter
-                    .as_ref()
-                    .ok_or_else(|| SemaflowError::Execution("statement missing".to_string()))?;
-                let mut column_names = Vec::new();
-                for idx in 0..stmt_ref.column_count() {
-                    let name = stmt_ref
-                        .column_name(idx)
-                        .map_err(|e| SemaflowError::Execution(e.to_string()))?;
-                    column_names.push(name.to_string());
-                }
-                let mut rows = Vec::new();
-                while let Some(row) = rows_iter.next()? {
-                    let mut map = serde_json::Map::new();
-                    for (idx, name) in column_names.iter().enumerate() {
-                        let value =
-                            crate::executor::duck_value_to_json(row.get_ref(idx)?.to_owned());
-                        map.insert(name.clone(), value);
-                    }
-                    rows.push(map);
+        self.run_cancellable(conn, move |conn| -> Result<QueryResult> {
+            let start = Instant::now();
+            let mut stmt = conn.prepare(&sql)?;
+            let mut rows_iter = stmt.query([])?;
+            let stmt_ref = rows_iter
+                .as_ref()
+                .ok_or_else(|| SemaflowError::Execution("statement missing".to_string()))?;
+            let mut column_names = Vec::new();
+            let mut column_types = Vec::new();
+            for idx in 0..stmt_ref.column_count() {
+                let name = stmt_ref
+                    .column_name(idx)
+                    .map_err(|e| SemaflowError::Execution(e.to_string()))?;
+                column_names.push(name.to_string());
+                column_types.push(stmt_ref.column_type(idx).to_string());
+            }
+            let mut rows = Vec::new();
+            while let Some(row) = rows_iter.next()? {
+                let mut map = serde_json::Map::new();
+                for (idx, name) in column_names.iter().enumerate() {
+                    let value = crate::executor::duck_value_to_json(row.get_ref(idx)?.to_owned());
+                    map.insert(name.clone(), value);
                 }
+                rows.push(map);
+            }
 
-                let columns: Vec<_> = column_names
-                    .into_iter()
-                    .map(|name| ColumnMeta { name })
-                    .collect();
-                let elapsed = start.elapsed();
-                tracing::debug!(
-                    rows = rows.len(),
-                    columns = columns.len(),
-                    ms = elapsed.as_millis(),
-                    "duckdb execute_sql"
-                );
-                Ok((QueryResult { columns, rows }, conn))
-            })
-            .await
-            .map_err(|e| SemaflowError::Execution(format!("task join error: {e}")))?;
-
-        let (result, conn) = result?;
-        {
-            let mut guard = pool.lock().await;
-            guard.push(conn);
-        }
-        Ok(result)
+            let columns: Vec<_> = column_names
+                .into_iter()
+                .zip(column_types)
+                .map(|(name, data_type)| ColumnMeta {
+                    data_type: Some(data_type),
+                    ..ColumnMeta::named(name)
+                })
+                .collect();
+            let elapsed = start.elapsed();
+            tracing::debug!(
+                rows = rows.len(),
+                columns = columns.len(),
+                ms = elapsed.as_millis(),
+                "duckdb execute_sql"
+            );
+            Ok(QueryResult { columns, rows })
+        })
+        .await
     }
 
     async fn execute_sql_paginated(
@@ -396,7 +411,13 @@ impl BackendConnection for DuckDbConnection {
 
         // Fetch page_size + 1 to detect if more rows exist
         let fetch_limit = page_size as u64 + 1;
-        let paginated_sql = format!("{sql} LIMIT {fetch_limit} OFFSET {offset}");
+        let paginated_sql = format!(
+            "{sql}{}",
+            self.dialect
+                .capabilities()
+                .limit
+                .render(Some(fetch_limit), Some(offset))
+        );
 
         tracing::debug!(
             page_size = page_size,
@@ -432,4 +453,27 @@ impl BackendConnection for DuckDbConnection {
             total_rows: None, // DuckDB doesn't provide total count efficiently
         })
     }
+
+    async fn execute_sql_arrow(&self, sql: &str, _tags: &QueryTags) -> Result<ArrowResult> {
+        let sql = sql.to_string();
+        let conn = self.checkout_connection().await?;
+        self.run_cancellable(conn, move |conn| -> Result<ArrowResult> {
+            let start = Instant::now();
+            let mut stmt = conn.prepare(&sql)?;
+            let arrow = stmt.query_arrow([])?;
+            let schema = arrow.get_schema();
+            let result = ArrowResult {
+                schema,
+                batches: arrow.collect(),
+            };
+            tracing::debug!(
+                rows = result.num_rows(),
+                batches = result.batches.len(),
+                ms = start.elapsed().as_millis(),
+                "duckdb execute_sql_arrow"
+            );
+            Ok(result)
+        })
+        .await
+    }
 }
@@ -0,0 +1,233 @@
+//! Connect-on-first-use backend connections.
+//!
+//! Some backends are slow to construct (BigQuery resolves credentials and
+//! builds an HTTP client asynchronously). A [`LazyConnection`] holds a
+//! connector closure instead and runs it the first time the connection is
+//! used, caching the result for every later call. A failed connect is not
+//! cached, so the next query tries again.
+//!
+//! The dialect is supplied up front because SQL is generated before anything
+//! is executed.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use tokio::sync::OnceCell;
+
+use crate::dialect::Dialect;
+use crate::error::Result;
+#[cfg(feature = "arrow")]
+use crate::executor::ArrowResult;
+use crate::executor::{PaginatedResult, QueryResult};
+use crate::pagination::Cursor;
+use crate::query_tags::QueryTags;
+use crate::schema_cache::TableSchema;
+
+use super::{BackendConnection, BatchCallback};
+
+type Connector =
+    Box<dyn Fn() -> BoxFuture<'static, Result<Arc<dyn BackendConnection>>> + Send + Sync>;
+
+/// Backend connection established by a connector on first use.
+pub struct LazyConnection {
+    name: String,
+    dialect: Box<dyn Dialect + Send + Sync>,
+    connector: Connector,
+    conn: OnceCell<Arc<dyn BackendConnection>>,
+}
+
+impl LazyConnection {
+    pub fn new<D, F, Fut>(name: impl Into<String>, dialect: D, connector: F) -> Self
+    where
+        D: Dialect + Send + Sync + 'static,
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Arc<dyn BackendConnection>>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            dialect: Box::new(dialect),
+            connector: Box::new(move || Box::pin(connector())),
+            conn: OnceCell::new(),
+        }
+    }
+
+    /// Whether the connector has already run successfully.
+    pub fn is_connected(&self) -> bool {
+        self.conn.initialized()
+    }
+
+    /// Return the cached connection, connecting first if needed. Concurrent
+    /// callers share a single connect attempt.
+    pub async fn connect(&self) -> Result<&Arc<dyn BackendConnection>> {
+        self.conn
+            .get_or_try_init(|| async {
+                let start = std::time::Instant::now();
+                let conn = (self.connector)().await;
+                match &conn {
+                    Ok(_) => tracing::info!(
+                        data_source = %self.name,
+                        ms = start.elapsed().as_millis(),
+                        "lazy connection established"
+                    ),
+                    Err(e) => tracing::warn!(
+                        data_source = %self.name,
+                        error = %e,
+                        "lazy connection failed"
+                    ),
+                }
+                conn
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl BackendConnection for LazyConnection {
+    fn dialect(&self) -> &(dyn Dialect + Send + Sync) {
+        self.dialect.as_ref()
+    }
+
+    async fn fetch_schema(&self, table: &str) -> Result<TableSchema> {
+        self.connect().await?.fetch_schema(table).await
+    }
+
+    async fn execute_sql(&self, sql: &str) -> Result<QueryResult> {
+        self.connect().await?.execute_sql(sql).await
+    }
+
+    async fn execute_sql_paginated(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+    ) -> Result<PaginatedResult> {
+        self.connect()
+            .await?
+            .execute_sql_paginated(sql, page_size, cursor, query_hash)
+            .await
+    }
+
+    async fn execute_sql_tagged(&self, sql: &str, tags: &QueryTags) -> Result<QueryResult> {
+        self.connect().await?.execute_sql_tagged(sql, tags).await
+    }
+
+    async fn execute_sql_paginated_tagged(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+        tags: &QueryTags,
+    ) -> Result<PaginatedResult> {
+        self.connect()
+            .await?
+            .execute_sql_paginated_tagged(sql, page_size, cursor, query_hash, tags)
+            .await
+    }
+
+    async fn execute_sql_streaming(
+        &self,
+        sql: &str,
+        batch_size: u32,
+        tags: &QueryTags,
+        on_batch: &mut BatchCallback<'_>,
+    ) -> Result<u64> {
+        self.connect()
+            .await?
+            .execute_sql_streaming(sql, batch_size, tags, on_batch)
+            .await
+    }
+
+    #[cfg(feature = "arrow")]
+    async fn execute_sql_arrow(&self, sql: &str, tags: &QueryTags) -> Result<ArrowResult> {
+        self.connect().await?.execute_sql_arrow(sql, tags).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.connect().await?.health_check().await
+    }
+}
+
+#[cfg(all(test, feature = "duckdb"))]
+mod tests {
+    use super::*;
+    use crate::error::SemaflowError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct EchoConnection;
+
+    #[async_trait]
+    impl BackendConnection for EchoConnection {
+        fn dialect(&self) -> &(dyn Dialect + Send + Sync) {
+            &crate::dialect::DuckDbDialect
+        }
+
+        async fn fetch_schema(&self, _table: &str) -> Result<TableSchema> {
+            Ok(TableSchema {
+                columns: vec![],
+                primary_keys: vec![],
+                foreign_keys: vec![],
+                unique_keys: vec![],
+            })
+        }
+
+        async fn execute_sql(&self, _sql: &str) -> Result<QueryResult> {
+            Ok(QueryResult {
+                columns: vec![],
+                rows: vec![],
+            })
+        }
+
+        async fn execute_sql_paginated(
+            &self,
+            _sql: &str,
+            _page_size: u32,
+            _cursor: Option<&Cursor>,
+            _query_hash: u64,
+        ) -> Result<PaginatedResult> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn connects_once_on_first_use() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let lazy = LazyConnection::new("wh", crate::dialect::DuckDbDialect, move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(Arc::new(EchoConnection) as Arc<dyn BackendConnection>)
+            }
+        });
+        assert!(!lazy.is_connected());
+        assert_eq!(attempts.load(Ordering::SeqCst), 0);
+
+        lazy.execute_sql("SELECT 1").await.unwrap();
+        lazy.fetch_schema("orders").await.unwrap();
+        assert!(lazy.is_connected());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_after_failed_connect() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let lazy = LazyConnection::new("wh", crate::dialect::DuckDbDialect, move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(SemaflowError::Execution("credentials expired".to_string()));
+                }
+                Ok(Arc::new(EchoConnection) as Arc<dyn BackendConnection>)
+            }
+        });
+        assert!(lazy.execute_sql("SELECT 1").await.is_err());
+        assert!(!lazy.is_connected());
+        assert!(lazy.execute_sql("SELECT 1").await.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
@@ -3,16 +3,28 @@
 //! Each backend is implemented in its own file and gated behind a feature flag.
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use async_trait::async_trait;
 
-use crate::config::{ResolvedDatasourceConfig, SemaflowConfig};
+use crate::config::{ConnectionConfig, ResolvedDatasourceConfig, SemaflowConfig};
 use crate::dialect::Dialect;
-use crate::error::Result;
+use crate::error::{Result, SemaflowError};
+#[cfg(feature = "arrow")]
+use crate::executor::ArrowResult;
 use crate::executor::{PaginatedResult, QueryResult};
-use crate::pagination::Cursor;
-use crate::schema_cache::TableSchema;
+use crate::flows::SemanticFlow;
+use crate::pagination::{Cursor, RowCountCache};
+use crate::query_tags::QueryTags;
+use crate::registry::FlowRegistry;
+use crate::result_cache::{InMemoryQueryCache, QueryCache};
+use crate::schema_cache::{SchemaCache, TableSchema, TableStats};
+
+/// Receives row batches from [`BackendConnection::execute_sql_streaming`].
+/// Returning an error stops the stream.
+pub type BatchCallback<'a> = dyn FnMut(QueryResult) -> Result<()> + Send + 'a;
 
 /// Unified interface for all database backends.
 #[async_trait]
@@ -42,6 +54,104 @@ pub trait BackendConnection: Send + Sync {
         cursor: Option<&Cursor>,
         query_hash: u64,
     ) -> Result<PaginatedResult>;
+
+    /// Execute SQL with workload tags attached for cost attribution.
+    ///
+    /// Backends without a native tagging mechanism ignore the tags.
+    async fn execute_sql_tagged(&self, sql: &str, tags: &QueryTags) -> Result<QueryResult> {
+        let _ = tags;
+        self.execute_sql(sql).await
+    }
+
+    /// Paginated variant of [`BackendConnection::execute_sql_tagged`].
+    async fn execute_sql_paginated_tagged(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+        tags: &QueryTags,
+    ) -> Result<PaginatedResult> {
+        let _ = tags;
+        self.execute_sql_paginated(sql, page_size, cursor, query_hash)
+            .await
+    }
+
+    /// Execute SQL and hand rows to `on_batch` in batches of at most
+    /// `batch_size` as they arrive. Returns the number of rows delivered.
+    ///
+    /// The default runs the query to completion and then splits the result;
+    /// backends that can fetch incrementally override this.
+    async fn execute_sql_streaming(
+        &self,
+        sql: &str,
+        batch_size: u32,
+        tags: &QueryTags,
+        on_batch: &mut BatchCallback<'_>,
+    ) -> Result<u64> {
+        let result = self.execute_sql_tagged(sql, tags).await?;
+        deliver_in_batches(result, batch_size, on_batch)
+    }
+
+    /// Execute SQL and return the result as Arrow record batches.
+    ///
+    /// The default converts the JSON rows, inferring column types from the
+    /// values; backends with native Arrow results override this to keep the
+    /// source types (e.g. full int64 and decimal precision).
+    #[cfg(feature = "arrow")]
+    async fn execute_sql_arrow(&self, sql: &str, tags: &QueryTags) -> Result<ArrowResult> {
+        self.execute_sql_tagged(sql, tags).await?.try_into()
+    }
+
+    /// Check that the source accepts queries. The default runs `SELECT 1`.
+    async fn health_check(&self) -> Result<()> {
+        self.execute_sql("SELECT 1").await.map(|_| ())
+    }
+}
+
+/// Create a BigQuery client for a configured connection.
+#[cfg(feature = "bigquery")]
+async fn connect_bigquery(
+    project: String,
+    dataset: String,
+    credentials_file: Option<String>,
+    config: crate::config::BigQueryConfig,
+) -> Result<Arc<dyn BackendConnection>> {
+    let conn = match credentials_file {
+        Some(path) => {
+            BigQueryConnection::from_service_account_key_file_with_config(
+                &path, &project, &dataset, config,
+            )
+            .await?
+        }
+        None => {
+            BigQueryConnection::from_application_default_credentials_with_config(
+                &project, &dataset, config,
+            )
+            .await?
+        }
+    };
+    Ok(Arc::new(conn))
+}
+
+/// Split a complete result into batches for a streaming callback.
+pub(crate) fn deliver_in_batches(
+    result: QueryResult,
+    batch_size: u32,
+    on_batch: &mut BatchCallback<'_>,
+) -> Result<u64> {
+    let batch_size = batch_size.max(1) as usize;
+    let QueryResult { columns, rows } = result;
+    let total = rows.len() as u64;
+    let mut rows = rows.into_iter().peekable();
+    while rows.peek().is_some() {
+        let batch: Vec<_> = rows.by_ref().take(batch_size).collect();
+        on_batch(QueryResult {
+            columns: columns.clone(),
+            rows: batch,
+        })?;
+    }
+    Ok(total)
 }
 
 /// Minimal connection manager keyed by data source name.
@@ -49,6 +159,10 @@ pub trait BackendConnection: Send + Sync {
 pub struct ConnectionManager {
     connections: HashMap<String, Arc<dyn BackendConnection>>,
     config: Option<SemaflowConfig>,
+    result_cache: Option<Arc<dyn QueryCache>>,
+    row_counts: Arc<RowCountCache>,
+    concurrency: Arc<ConcurrencyLimiter>,
+    schemas: Arc<Mutex<SchemaCache>>,
 }
 
 impl ConnectionManager {
@@ -56,15 +170,142 @@ impl ConnectionManager {
         Self {
             connections: HashMap::new(),
             config: None,
+            result_cache: None,
+            row_counts: Arc::default(),
+            concurrency: Arc::default(),
+            schemas: Arc::default(),
         }
     }
 
     /// Create a connection manager with configuration.
+    ///
+    /// When `result_cache` is enabled, results are cached in an
+    /// [`InMemoryQueryCache`] unless another store is set with
+    /// [`Self::set_result_cache`].
     pub fn with_config(config: SemaflowConfig) -> Self {
+        let result_cache = config.result_cache.enabled.then(|| {
+            Arc::new(InMemoryQueryCache::new(config.result_cache.max_entries))
+                as Arc<dyn QueryCache>
+        });
+        let concurrency = Arc::new(ConcurrencyLimiter::new(&config.concurrency));
+        let schemas = Arc::new(Mutex::new(SchemaCache::for_config(&config)));
         Self {
             connections: HashMap::new(),
             config: Some(config),
+            result_cache,
+            row_counts: Arc::default(),
+            concurrency,
+            schemas,
+        }
+    }
+
+    /// Load the TOML configuration at `path` and connect every datasource
+    /// with a `[datasources.<name>.connection]`; see [`Self::from_config`].
+    pub fn from_config_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_config(SemaflowConfig::from_file(path)?)
+    }
+
+    /// Create a connection manager with configuration, connected to every
+    /// datasource the configuration gives a [`ConnectionConfig`].
+    ///
+    /// Connections are built with their datasource's backend options after
+    /// expanding environment variables and secret files. DuckDB and
+    /// PostgreSQL connect on the first query; BigQuery clients are created
+    /// on first use as with [`Self::insert_lazy`].
+    pub fn from_config(config: SemaflowConfig) -> Result<Self> {
+        let mut declared: Vec<(String, ConnectionConfig)> = config
+            .datasources
+            .iter()
+            .filter_map(|(name, ds)| Some((name.clone(), ds.connection.clone()?)))
+            .collect();
+        declared.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut connections = Self::with_config(config);
+        for (name, connection) in declared {
+            let connection = connection.expanded().map_err(|e| match e {
+                SemaflowError::Config(message) => {
+                    SemaflowError::Config(format!("datasource {name}: {message}"))
+                }
+                other => other,
+            })?;
+            connections.connect(name, connection)?;
         }
+        Ok(connections)
+    }
+
+    /// Register a connection to `name` built from `connection`.
+    fn connect(&mut self, name: String, connection: ConnectionConfig) -> Result<()> {
+        match connection {
+            #[cfg(feature = "duckdb")]
+            ConnectionConfig::Duckdb { path } => {
+                let conn = DuckDbConnection::with_config(path, self.config_for(&name).duckdb);
+                self.insert(name, Arc::new(conn));
+            }
+            #[cfg(feature = "postgres")]
+            ConnectionConfig::Postgres { url, schema } => {
+                let config = self.config_for(&name).postgres;
+                let conn = PostgresConnection::with_config(&url, &schema, config)?;
+                self.insert(name, Arc::new(conn));
+            }
+            #[cfg(feature = "bigquery")]
+            ConnectionConfig::Bigquery {
+                project,
+                dataset,
+                credentials_file,
+            } => {
+                let dialect = crate::dialect::BigQueryDialect::new(&project, &dataset);
+                let bq_config = self.config_for(&name).bigquery;
+                self.insert_lazy(name, dialect, move || {
+                    connect_bigquery(
+                        project.clone(),
+                        dataset.clone(),
+                        credentials_file.clone(),
+                        bq_config.clone(),
+                    )
+                });
+            }
+            #[allow(unreachable_patterns)]
+            other => {
+                return Err(SemaflowError::Config(format!(
+                    "datasource {name}: backend '{}' is not available in this build",
+                    other.backend()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Use `cache` (e.g. a shared Redis store) for query results.
+    ///
+    /// Results are only cached for flows the configured `result_cache`
+    /// gives a TTL.
+    pub fn set_result_cache(&mut self, cache: Arc<dyn QueryCache>) {
+        self.result_cache = Some(cache);
+    }
+
+    /// The result cache and TTL applying to queries of `flow`, if caching is on.
+    pub fn result_cache_for(&self, flow: &str) -> Option<(&Arc<dyn QueryCache>, Duration)> {
+        let ttl = self.config.as_ref()?.result_cache.ttl_for(flow)?;
+        Some((self.result_cache.as_ref()?, ttl))
+    }
+
+    /// Total row counts cached for paginated queries with `count_total`.
+    pub fn row_counts(&self) -> &RowCountCache {
+        &self.row_counts
+    }
+
+    /// Wait for a slot in the concurrency budgets of `flow`, `data_source`
+    /// and all datasources; the query may run while the permit is held.
+    ///
+    /// Fails with [`SemaflowError::Overloaded`] when a budget stays full for
+    /// its `queue_timeout_ms`.
+    pub async fn acquire(&self, flow: &str, data_source: &str) -> Result<QueryPermit> {
+        let limit = self.config_for(data_source).concurrency;
+        self.concurrency.acquire(flow, data_source, &limit).await
+    }
+
+    /// Queue depth, wait times and rejections of each enforced concurrency budget.
+    pub fn concurrency_stats(&self) -> Vec<BudgetStats> {
+        self.concurrency.stats()
     }
 
     /// Get the configuration, if set.
@@ -80,15 +321,126 @@ impl ConnectionManager {
         }
     }
 
+    /// Register a connection. When the circuit breaker is enabled for this
+    /// datasource, the connection is wrapped in a [`CircuitBreakerConnection`].
     pub fn insert(&mut self, name: impl Into<String>, conn: Arc<dyn BackendConnection>) {
-        self.connections.insert(name.into(), conn);
+        let name = name.into();
+        let breaker = self.config_for(&name).circuit_breaker;
+        let conn: Arc<dyn BackendConnection> = if breaker.enabled {
+            Arc::new(CircuitBreakerConnection::new(name.clone(), conn, breaker))
+        } else {
+            conn
+        };
+        self.connections.insert(name, conn);
+    }
+
+    /// Register a connection that is only established when first used.
+    ///
+    /// `connector` runs on the first schema fetch or query against `name`;
+    /// its result is cached. `dialect` is needed up front to build SQL.
+    pub fn insert_lazy<D, F, Fut>(&mut self, name: impl Into<String>, dialect: D, connector: F)
+    where
+        D: Dialect + Send + Sync + 'static,
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Arc<dyn BackendConnection>>> + Send + 'static,
+    {
+        let name = name.into();
+        let conn = LazyConnection::new(name.clone(), dialect, connector);
+        self.insert(name, Arc::new(conn));
     }
 
     pub fn get(&self, name: &str) -> Option<&Arc<dyn BackendConnection>> {
         self.connections.get(name)
     }
+
+    /// Run [`BackendConnection::health_check`] against `name`.
+    ///
+    /// A source behind an open circuit breaker is checked directly, and a
+    /// passing check closes the breaker.
+    pub async fn health_check(&self, name: &str) -> Result<()> {
+        let conn = self
+            .get(name)
+            .ok_or_else(|| SemaflowError::UnknownDataSource {
+                data_source: name.to_string(),
+            })?;
+        conn.health_check().await
+    }
+
+    /// Count the rows of every physical table in `registry` and the distinct
+    /// values of its key columns, and cache them for planning; see
+    /// [`FlowRegistry::stats`]. Tables on unregistered data sources, and
+    /// tables defined by `sql`, `query` or `union`, are skipped. Returns the number
+    /// of tables counted.
+    ///
+    /// Statistics expire with the schema cache TTL; call again to refresh.
+    pub async fn collect_stats(&self, registry: &FlowRegistry) -> Result<usize> {
+        let mut tables: Vec<_> = registry
+            .tables
+            .values()
+            .filter(|table| !table.is_derived())
+            .collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut collected = 0;
+        for table in tables {
+            let Some(conn) = self.get(&table.data_source) else {
+                continue;
+            };
+            let columns = crate::query_builder::stats_columns(registry, table);
+            let sql = crate::query_builder::stats_sql(conn.dialect(), &table.table, &columns);
+            let result = conn.execute_sql(&sql).await?;
+            let stats = crate::query_builder::parse_stats(&table.table, &columns, &result)?;
+            self.schemas
+                .lock()
+                .map_err(|e| SemaflowError::Other(anyhow::anyhow!("schema cache lock: {e}")))?
+                .insert_stats(table.data_source.clone(), table.table.clone(), stats);
+            collected += 1;
+        }
+        Ok(collected)
+    }
+
+    /// Statistics cached for `table` of `data_source`, unless expired.
+    pub fn table_stats(&self, data_source: &str, table: &str) -> Option<TableStats> {
+        let cache = self.schemas.lock().unwrap_or_else(|e| e.into_inner());
+        cache.get_stats(data_source, table).cloned()
+    }
+
+    /// Cached statistics of the tables `flow` reads, by semantic table name.
+    pub(crate) fn flow_stats(
+        &self,
+        registry: &FlowRegistry,
+        flow: &SemanticFlow,
+    ) -> HashMap<String, TableStats> {
+        std::iter::once(&flow.base_table.semantic_table)
+            .chain(flow.joins.values().map(|join| &join.semantic_table))
+            .filter_map(|name| {
+                let table = registry.get_table(name)?;
+                let stats = self.table_stats(&table.data_source, &table.table)?;
+                Some((name.clone(), stats))
+            })
+            .collect()
+    }
+
+    /// Check every registered source, sorted by name.
+    pub async fn health_check_all(&self) -> Vec<(String, Result<()>)> {
+        let mut names: Vec<&String> = self.connections.keys().collect();
+        names.sort();
+        let checks = names.into_iter().map(|name| async move {
+            let result = self.connections[name].health_check().await;
+            (name.clone(), result)
+        });
+        futures::future::join_all(checks).await
+    }
 }
 
+mod circuit_breaker;
+pub use circuit_breaker::{CircuitBreakerConnection, CircuitState};
+
+mod concurrency;
+pub use concurrency::{BudgetScope, BudgetStats, ConcurrencyLimiter, QueryPermit};
+
+mod lazy;
+pub use lazy::LazyConnection;
+
 // Feature-gated backend implementations
 #[cfg(feature = "duckdb")]
 mod duckdb;
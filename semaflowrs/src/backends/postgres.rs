@@ -1,17 +1,22 @@
 //! PostgreSQL backend implementation.
 
+use std::future::Future;
 use std::time::Instant;
 
 use async_trait::async_trait;
+use futures::TryStreamExt;
+use tokio_postgres::types::ToSql;
 
+use crate::cancel;
 use crate::config::PostgresConfig;
-use crate::dialect::PostgresDialect;
+use crate::dialect::{Dialect, PostgresDialect};
 use crate::error::{Result, SemaflowError};
 use crate::executor::{ColumnMeta, PaginatedResult, QueryResult};
 use crate::pagination::Cursor;
+use crate::query_tags::QueryTags;
 use crate::schema_cache::{ForeignKey, TableSchema};
 
-use super::BackendConnection;
+use super::{BackendConnection, BatchCallback};
 
 pub struct PostgresConnection {
     pool: deadpool_postgres::Pool,
@@ -67,6 +72,14 @@ impl PostgresConnection {
             cfg
         };
 
+        // Server-side backstop for queries the runtime fails to cancel
+        if config.statement_timeout_ms > 0 {
+            db_config.options = Some(format!(
+                "-c statement_timeout={}",
+                config.statement_timeout_ms
+            ));
+        }
+
         // Apply pool size from config
         db_config.pool = Some(deadpool_postgres::PoolConfig {
             max_size: config.pool_size,
@@ -107,6 +120,187 @@ impl PostgresConnection {
     pub fn schema(&self) -> &str {
         &self.schema
     }
+
+    /// Run a query, optionally setting `application_name` for its duration.
+    async fn run_query(&self, sql: &str, application_name: Option<&str>) -> Result<QueryResult> {
+        let start = Instant::now();
+        let pool_status = self.pool.status();
+        tracing::debug!(
+            available = pool_status.available,
+            size = pool_status.size,
+            max_size = pool_status.max_size,
+            sql_len = sql.len(),
+            "acquiring PostgreSQL connection for query"
+        );
+        tracing::trace!(sql = %sql, "executing PostgreSQL query");
+
+        let mut client = self.pool.get().await.map_err(|e| {
+            tracing::error!(error = %e, "failed to get PostgreSQL connection");
+            SemaflowError::Execution(format!("get postgres connection: {e}"))
+        })?;
+
+        let rows = match application_name {
+            Some(name) => {
+                let tx = tagged_transaction(&mut client, name).await?;
+                let rows = cancellable(tx.cancel_token(), async {
+                    tx.query(sql, &[]).await.map_err(query_error)
+                })
+                .await?;
+                commit(tx).await?;
+                rows
+            }
+            None => {
+                cancellable(client.cancel_token(), async {
+                    client.query(sql, &[]).await.map_err(query_error)
+                })
+                .await?
+            }
+        };
+
+        // Convert rows to JSON
+        let mut result_rows = Vec::new();
+        let mut columns: Vec<ColumnMeta> = Vec::new();
+
+        if let Some(first_row) = rows.first() {
+            // Get column metadata from first row
+            columns = first_row
+                .columns()
+                .iter()
+                .map(|col| ColumnMeta {
+                    data_type: Some(col.type_().name().to_string()),
+                    ..ColumnMeta::named(col.name())
+                })
+                .collect();
+        }
+
+        for row in &rows {
+            result_rows.push(row_to_json(row));
+        }
+
+        let elapsed = start.elapsed();
+        tracing::debug!(
+            rows = result_rows.len(),
+            columns = columns.len(),
+            ms = elapsed.as_millis(),
+            "postgres execute_sql"
+        );
+
+        Ok(QueryResult {
+            columns,
+            rows: result_rows,
+        })
+    }
+
+    async fn run_query_paginated(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+        application_name: Option<&str>,
+    ) -> Result<PaginatedResult> {
+        // Extract offset from cursor, validate query hash
+        let offset = match cursor {
+            Some(c) => {
+                c.validate_query_hash(query_hash)?;
+                c.offset()
+            }
+            None => 0,
+        };
+
+        // Fetch page_size + 1 to detect if more rows exist
+        let fetch_limit = page_size as u64 + 1;
+        let paginated_sql = format!(
+            "{sql}{}",
+            self.dialect
+                .capabilities()
+                .limit
+                .render(Some(fetch_limit), Some(offset))
+        );
+
+        tracing::debug!(
+            page_size = page_size,
+            offset = offset,
+            "executing paginated PostgreSQL query"
+        );
+
+        // Execute the paginated query
+        let result = self.run_query(&paginated_sql, application_name).await?;
+
+        // Determine if there are more rows
+        let has_more = result.rows.len() > page_size as usize;
+        let rows = if has_more {
+            result.rows.into_iter().take(page_size as usize).collect()
+        } else {
+            result.rows
+        };
+
+        // Build next cursor if there are more rows
+        let next_cursor = if has_more {
+            let next_offset = offset + page_size as u64;
+            let cursor = Cursor::sql(next_offset, query_hash);
+            Some(cursor.encode()?)
+        } else {
+            None
+        };
+
+        Ok(PaginatedResult {
+            columns: result.columns,
+            rows,
+            cursor: next_cursor,
+            has_more,
+            total_rows: None, // PostgreSQL doesn't provide total count efficiently
+        })
+    }
+
+    /// Stream rows from a single query, delivering them in batches as they
+    /// arrive from the server instead of buffering the full result.
+    async fn run_query_streaming(
+        &self,
+        sql: &str,
+        batch_size: u32,
+        application_name: Option<&str>,
+        on_batch: &mut BatchCallback<'_>,
+    ) -> Result<u64> {
+        let start = Instant::now();
+        let mut client = self.pool.get().await.map_err(|e| {
+            tracing::error!(error = %e, "failed to get PostgreSQL connection");
+            SemaflowError::Execution(format!("get postgres connection: {e}"))
+        })?;
+
+        let batch_size = batch_size.max(1) as usize;
+        let no_params = std::iter::empty::<&(dyn ToSql + Sync)>;
+        let streamed = match application_name {
+            Some(name) => {
+                let tx = tagged_transaction(&mut client, name).await?;
+                let streamed = cancellable(
+                    tx.cancel_token(),
+                    stream_rows(tx.query_raw(sql, no_params()), batch_size, on_batch),
+                )
+                .await;
+                match streamed {
+                    Ok(rows) => commit(tx).await.map(|()| rows),
+                    Err(e) => Err(e),
+                }
+            }
+            None => {
+                cancellable(
+                    client.cancel_token(),
+                    stream_rows(client.query_raw(sql, no_params()), batch_size, on_batch),
+                )
+                .await
+            }
+        };
+
+        if let Ok(rows) = &streamed {
+            tracing::debug!(
+                rows = rows,
+                ms = start.elapsed().as_millis(),
+                "postgres execute_sql_streaming"
+            );
+        }
+        streamed
+    }
 }
 
 #[async_trait]
@@ -201,6 +395,33 @@ impl BackendConnection for PostgresConnection {
             })
             .collect();
 
+        // Query unique constraints, one row per column
+        let unique_sql = r#"
+            SELECT tc.constraint_name, kcu.column_name
+            FROM information_schema.table_constraints tc
+            JOIN information_schema.key_column_usage kcu
+                ON tc.constraint_name = kcu.constraint_name
+                AND tc.table_schema = kcu.table_schema
+            WHERE tc.table_schema = $1
+                AND tc.table_name = $2
+                AND tc.constraint_type = 'UNIQUE'
+            ORDER BY tc.constraint_name, kcu.ordinal_position
+        "#;
+        let unique_rows = client
+            .query(unique_sql, &[&self.schema, &table])
+            .await
+            .map_err(|e| SemaflowError::Execution(format!("fetch unique keys: {e}")))?;
+
+        let mut unique_keys: Vec<(String, Vec<String>)> = Vec::new();
+        for row in &unique_rows {
+            let constraint: String = row.get(0);
+            let column: String = row.get(1);
+            match unique_keys.last_mut() {
+                Some((name, columns)) if *name == constraint => columns.push(column),
+                _ => unique_keys.push((constraint, vec![column])),
+            }
+        }
+
         let elapsed = start.elapsed();
         tracing::debug!(
             table = table,
@@ -213,123 +434,172 @@ impl BackendConnection for PostgresConnection {
             columns,
             primary_keys,
             foreign_keys,
+            unique_keys: unique_keys
+                .into_iter()
+                .map(|(_, columns)| columns)
+                .collect(),
         })
     }
 
     async fn execute_sql(&self, sql: &str) -> Result<QueryResult> {
-        let start = Instant::now();
-        let pool_status = self.pool.status();
-        tracing::debug!(
-            available = pool_status.available,
-            size = pool_status.size,
-            max_size = pool_status.max_size,
-            sql_len = sql.len(),
-            "acquiring PostgreSQL connection for query"
-        );
-        tracing::trace!(sql = %sql, "executing PostgreSQL query");
+        self.run_query(sql, None).await
+    }
 
-        let client = self.pool.get().await.map_err(|e| {
-            tracing::error!(error = %e, "failed to get PostgreSQL connection");
-            SemaflowError::Execution(format!("get postgres connection: {e}"))
-        })?;
+    async fn execute_sql_paginated(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+    ) -> Result<PaginatedResult> {
+        self.run_query_paginated(sql, page_size, cursor, query_hash, None)
+            .await
+    }
 
-        let rows = client.query(sql, &[]).await.map_err(|e| {
-            tracing::error!(error = %e, "PostgreSQL query execution failed");
-            SemaflowError::Execution(format!("execute query: {e}"))
-        })?;
+    async fn execute_sql_tagged(&self, sql: &str, tags: &QueryTags) -> Result<QueryResult> {
+        let application_name = (!tags.is_empty()).then(|| tags.to_application_name());
+        self.run_query(sql, application_name.as_deref()).await
+    }
 
-        // Convert rows to JSON
-        let mut result_rows = Vec::new();
-        let mut columns: Vec<ColumnMeta> = Vec::new();
+    async fn execute_sql_paginated_tagged(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&Cursor>,
+        query_hash: u64,
+        tags: &QueryTags,
+    ) -> Result<PaginatedResult> {
+        let application_name = (!tags.is_empty()).then(|| tags.to_application_name());
+        self.run_query_paginated(
+            sql,
+            page_size,
+            cursor,
+            query_hash,
+            application_name.as_deref(),
+        )
+        .await
+    }
 
-        if let Some(first_row) = rows.first() {
-            // Get column metadata from first row
-            columns = first_row
+    async fn execute_sql_streaming(
+        &self,
+        sql: &str,
+        batch_size: u32,
+        tags: &QueryTags,
+        on_batch: &mut BatchCallback<'_>,
+    ) -> Result<u64> {
+        let application_name = (!tags.is_empty()).then(|| tags.to_application_name());
+        self.run_query_streaming(sql, batch_size, application_name.as_deref(), on_batch)
+            .await
+    }
+}
+
+async fn stream_rows(
+    query: impl Future<Output = std::result::Result<tokio_postgres::RowStream, tokio_postgres::Error>>,
+    batch_size: usize,
+    on_batch: &mut BatchCallback<'_>,
+) -> Result<u64> {
+    let stream = query.await.map_err(query_error)?;
+    futures::pin_mut!(stream);
+
+    let mut columns: Vec<ColumnMeta> = Vec::new();
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut total = 0u64;
+    while let Some(row) = stream
+        .try_next()
+        .await
+        .map_err(|e| SemaflowError::Execution(format!("fetch row: {e}")))?
+    {
+        if columns.is_empty() {
+            columns = row
                 .columns()
                 .iter()
                 .map(|col| ColumnMeta {
-                    name: col.name().to_string(),
+                    data_type: Some(col.type_().name().to_string()),
+                    ..ColumnMeta::named(col.name())
                 })
                 .collect();
         }
-
-        for row in &rows {
-            let mut map = serde_json::Map::new();
-            for (idx, col) in row.columns().iter().enumerate() {
-                let value = pg_value_to_json(row, idx, col);
-                map.insert(col.name().to_string(), value);
-            }
-            result_rows.push(map);
+        batch.push(row_to_json(&row));
+        if batch.len() >= batch_size {
+            total += batch.len() as u64;
+            on_batch(QueryResult {
+                columns: columns.clone(),
+                rows: std::mem::replace(&mut batch, Vec::with_capacity(batch_size)),
+            })?;
         }
-
-        let elapsed = start.elapsed();
-        tracing::debug!(
-            rows = result_rows.len(),
-            columns = columns.len(),
-            ms = elapsed.as_millis(),
-            "postgres execute_sql"
-        );
-
-        Ok(QueryResult {
+    }
+    if !batch.is_empty() {
+        total += batch.len() as u64;
+        on_batch(QueryResult {
             columns,
-            rows: result_rows,
-        })
+            rows: batch,
+        })?;
     }
+    Ok(total)
+}
 
-    async fn execute_sql_paginated(
-        &self,
-        sql: &str,
-        page_size: u32,
-        cursor: Option<&Cursor>,
-        query_hash: u64,
-    ) -> Result<PaginatedResult> {
-        // Extract offset from cursor, validate query hash
-        let offset = match cursor {
-            Some(c) => {
-                c.validate_query_hash(query_hash)?;
-                c.offset()
+/// Await `query`, asking the server to cancel it through `cancel_token` if
+/// the query's [`cancel`] scope is cancelled first.
+async fn cancellable<T>(
+    cancel_token: tokio_postgres::CancelToken,
+    query: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let scope = cancel::current();
+    futures::pin_mut!(query);
+    tokio::select! {
+        result = &mut query => result,
+        _ = scope.cancelled() => {
+            tracing::debug!("cancelling PostgreSQL query");
+            if let Err(e) = cancel_token.cancel_query(tokio_postgres::NoTls).await {
+                tracing::warn!(error = %e, "failed to cancel PostgreSQL query");
             }
-            None => 0,
-        };
-
-        // Fetch page_size + 1 to detect if more rows exist
-        let fetch_limit = page_size as u64 + 1;
-        let paginated_sql = format!("{sql} LIMIT {fetch_limit} OFFSET {offset}");
-
-        tracing::debug!(
-            page_size = page_size,
-            offset = offset,
-            "executing paginated PostgreSQL query"
-        );
+            // Wait for the aborted statement so the pooled connection is idle again
+            match query.await {
+                Err(_) => Err(cancel::cancelled_error(&scope)),
+                result => result,
+            }
+        }
+    }
+}
 
-        // Execute the paginated query
-        let result = self.execute_sql(&paginated_sql).await?;
+fn row_to_json(row: &tokio_postgres::Row) -> serde_json::Map<String, serde_json::Value> {
+    let mut map = serde_json::Map::new();
+    for (idx, col) in row.columns().iter().enumerate() {
+        map.insert(col.name().to_string(), pg_value_to_json(row, idx, col));
+    }
+    map
+}
 
-        // Determine if there are more rows
-        let has_more = result.rows.len() > page_size as usize;
-        let rows = if has_more {
-            result.rows.into_iter().take(page_size as usize).collect()
-        } else {
-            result.rows
-        };
+fn query_error(e: tokio_postgres::Error) -> SemaflowError {
+    tracing::error!(error = %e, "PostgreSQL query execution failed");
+    SemaflowError::Execution(format!("execute query: {e}"))
+}
 
-        // Build next cursor if there are more rows
-        let next_cursor = if has_more {
-            let next_offset = offset + page_size as u64;
-            let cursor = Cursor::sql(next_offset, query_hash);
-            Some(cursor.encode()?)
-        } else {
-            None
-        };
+/// Open a transaction whose `application_name` is `name`, so the query shows
+/// up tagged in `pg_stat_activity` and server logs.
+///
+/// `SET LOCAL` ends with the transaction, and a transaction dropped with its
+/// query (on cancellation or timeout) is rolled back, so the pooled
+/// connection never keeps the tags.
+async fn tagged_transaction<'a>(
+    client: &'a mut deadpool_postgres::Object,
+    name: &str,
+) -> Result<deadpool_postgres::Transaction<'a>> {
+    let tx = client
+        .transaction()
+        .await
+        .map_err(|e| SemaflowError::Execution(format!("begin transaction: {e}")))?;
+    let escaped = name.replace('\'', "''");
+    tx.batch_execute(&format!("SET LOCAL application_name = '{escaped}'"))
+        .await
+        .map_err(|e| SemaflowError::Execution(format!("set application_name: {e}")))?;
+    Ok(tx)
+}
 
-        Ok(PaginatedResult {
-            columns: result.columns,
-            rows,
-            cursor: next_cursor,
-            has_more,
-            total_rows: None, // PostgreSQL doesn't provide total count efficiently
-        })
-    }
+async fn commit(tx: deadpool_postgres::Transaction<'_>) -> Result<()> {
+    tx.commit()
+        .await
+        .map_err(|e| SemaflowError::Execution(format!("commit transaction: {e}")))
 }
 
 /// Convert a PostgreSQL value to JSON.
@@ -0,0 +1,32 @@
+//! Print the JSON Schema of model files.
+//!
+//! Usage: `semaflow-json-schema [model|table|flow] [OUTPUT]`. Writes to
+//! `OUTPUT` when given, otherwise to stdout.
+
+use std::process::ExitCode;
+
+use semaflow::json_schema::{flow_schema, model_file_schema, table_schema};
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let schema = match args.next().as_deref() {
+        None | Some("model") => model_file_schema(),
+        Some("table") => table_schema(),
+        Some("flow") => flow_schema(),
+        Some(other) => {
+            eprintln!("unknown schema '{other}'; expected model, table or flow");
+            return ExitCode::FAILURE;
+        }
+    };
+    let json = serde_json::to_string_pretty(&schema).unwrap_or_default();
+    match args.next() {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, json + "\n") {
+                eprintln!("failed to write {path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        None => println!("{json}"),
+    }
+    ExitCode::SUCCESS
+}
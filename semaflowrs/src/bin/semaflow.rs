@@ -0,0 +1,569 @@
+//! `semaflow` command line: validate models, render SQL, run queries and
+//! describe flows without writing Rust or Python.
+//!
+//! Data sources are attached with `--connect NAME=BACKEND:TARGET`, e.g.
+//! `--connect warehouse=duckdb:data.duckdb` or
+//! `--connect warehouse=postgres:postgresql://localhost/db#analytics`.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::{Map, Value};
+
+use semaflow::dialect::Dialect;
+use semaflow::flows::{
+    DimensionRequest, Filter, FilterExpr, FilterOp, OrderItem, QueryRequest, SortDirection,
+    TimeGrain,
+};
+use semaflow::query_builder::SqlBuilder;
+use semaflow::registry::FlowRegistry;
+use semaflow::{ConnectionManager, QueryContext, QueryResult, SemaflowError, Validator};
+
+#[derive(Parser)]
+#[command(
+    name = "semaflow",
+    version,
+    about = "Semantic layer for analytical databases"
+)]
+struct Cli {
+    /// Directory holding the model files (`tables/` and `flows/`).
+    #[arg(
+        long,
+        short,
+        global = true,
+        default_value = ".",
+        env = "SEMAFLOW_MODELS"
+    )]
+    models: PathBuf,
+    /// TOML configuration file. Datasources with a `connection` in it are
+    /// attached.
+    #[arg(long, global = true, env = "SEMAFLOW_CONFIG")]
+    config: Option<PathBuf>,
+    /// Attach a data source: `NAME=duckdb:PATH`, `NAME=postgres:URL[#SCHEMA]`
+    /// or `NAME=bigquery:PROJECT/DATASET`. May be repeated.
+    #[arg(long = "connect", global = true, value_name = "NAME=SPEC")]
+    connections: Vec<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Load and check the models. With data sources attached, fields are
+    /// also checked against the tables' columns.
+    Validate {
+        /// Model directory; defaults to `--models`.
+        dir: Option<PathBuf>,
+    },
+    /// Print the SQL for a request without running it.
+    Sql {
+        #[command(flatten)]
+        query: QueryArgs,
+        /// Dialect to render when no data source is attached: `duckdb`,
+        /// `postgres[:SCHEMA]` or `bigquery:PROJECT/DATASET`.
+        #[arg(long, default_value = "duckdb")]
+        dialect: String,
+    },
+    /// Run a request against the attached data sources.
+    Run {
+        #[command(flatten)]
+        query: QueryArgs,
+        #[arg(long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// Compare the attached tables' live columns with the model and with
+    /// the columns last seen (kept across runs by `schema_cache.path` in
+    /// `--config`). Fails when a change may break queries.
+    Drift {
+        #[arg(long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// List flows, or show the dimensions and measures of one flow.
+    Describe {
+        flow: Option<String>,
+        #[arg(long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// Read the request from a JSON or YAML file; other options add to it.
+    #[arg(long)]
+    request: Option<PathBuf>,
+    #[arg(long)]
+    flow: Option<String>,
+    /// Dimensions, comma separated; `field:grain` truncates a time dimension.
+    #[arg(long, value_delimiter = ',')]
+    dims: Vec<String>,
+    /// Measures, comma separated.
+    #[arg(long, value_delimiter = ',')]
+    measures: Vec<String>,
+    /// Filter such as `country==US` or `amount>100`. May be repeated.
+    #[arg(long = "filter", value_name = "FIELD OP VALUE")]
+    filters: Vec<String>,
+    /// Sort column, prefixed with `-` for descending. May be repeated.
+    #[arg(long, allow_hyphen_values = true)]
+    order: Vec<String>,
+    #[arg(long)]
+    limit: Option<u32>,
+    /// Flow parameter as `NAME=VALUE`. May be repeated.
+    #[arg(long = "param", value_name = "NAME=VALUE")]
+    params: Vec<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Filter operators in matching order: two-character ones first.
+const FILTER_OPS: [(&str, FilterOp); 6] = [
+    ("==", FilterOp::Eq),
+    ("!=", FilterOp::Neq),
+    (">=", FilterOp::Gte),
+    ("<=", FilterOp::Lte),
+    (">", FilterOp::Gt),
+    ("<", FilterOp::Lt),
+];
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), SemaflowError> {
+    let connections = connect(&cli).await?;
+    match cli.command {
+        Command::Validate { dir } => {
+            let dir = dir.as_deref().unwrap_or(&cli.models);
+            let mut registry = FlowRegistry::load_from_dir(dir)?;
+            let attached = !cli.connections.is_empty();
+            let validator = Validator::new(connections, false);
+            if attached {
+                validator.validate_registry(&mut registry).await?;
+            } else {
+                validator.validate_flows(&registry)?;
+            }
+            println!(
+                "ok: {} tables, {} flows{}",
+                registry.tables.len(),
+                registry.flows.len(),
+                if attached {
+                    ""
+                } else {
+                    " (columns not checked; attach data sources with --connect)"
+                }
+            );
+        }
+        Command::Sql { query, dialect } => {
+            let registry = FlowRegistry::load_from_dir(&cli.models)?;
+            let request = query.to_request()?;
+            let builder = SqlBuilder::for_request(&registry, &connections, &request);
+            let context = QueryContext::default();
+            let sql = if cli.connections.is_empty() {
+                let dialect = parse_dialect(&dialect)?;
+                builder.build_with_context(&registry, &request, &context, dialect.as_ref())?
+            } else {
+                builder.build_for_request(&registry, &connections, &request, &context)?
+            };
+            println!("{sql}");
+        }
+        Command::Run { query, format } => {
+            let registry = FlowRegistry::load_from_dir(&cli.models)?;
+            let request = query.to_request()?;
+            let result = semaflow::runtime::run_query(
+                &registry,
+                &connections,
+                &request,
+                &QueryContext::default(),
+            )
+            .await?;
+            print_result(&result, format);
+        }
+        Command::Drift { format } => {
+            let registry = FlowRegistry::load_from_dir(&cli.models)?;
+            let report = Validator::new(connections, false)
+                .check_drift(&registry)
+                .await?;
+            if let Format::Json = format {
+                print_json(&report);
+            } else {
+                let rows = report
+                    .drifts
+                    .iter()
+                    .map(|d| {
+                        vec![
+                            d.kind.as_str().to_string(),
+                            d.table.clone(),
+                            d.column.clone().unwrap_or_default(),
+                            d.message.clone(),
+                        ]
+                    })
+                    .collect();
+                print_rows(&["kind", "table", "column", "message"], rows, format);
+            }
+            if report.has_breaking() {
+                return Err(SemaflowError::Validation(
+                    "schema drift may break queries".to_string(),
+                ));
+            }
+        }
+        Command::Describe { flow, format } => {
+            let registry = FlowRegistry::load_from_dir(&cli.models)?;
+            describe(&registry, flow.as_deref(), format)?;
+        }
+    }
+    Ok(())
+}
+
+async fn connect(cli: &Cli) -> Result<ConnectionManager, SemaflowError> {
+    let mut connections = match &cli.config {
+        Some(path) => ConnectionManager::from_config_file(path)?,
+        None => ConnectionManager::new(),
+    };
+    for spec in &cli.connections {
+        let (name, target) = spec.split_once('=').ok_or_else(|| {
+            SemaflowError::Config(format!("expected NAME=BACKEND:TARGET, got '{spec}'"))
+        })?;
+        let (backend, target) = target.split_once(':').unwrap_or((target, ""));
+        let conn = open_connection(backend, target, &connections.config_for(name)).await?;
+        connections.insert(name, conn);
+    }
+    Ok(connections)
+}
+
+async fn open_connection(
+    backend: &str,
+    target: &str,
+    config: &semaflow::config::ResolvedDatasourceConfig,
+) -> Result<Arc<dyn semaflow::BackendConnection>, SemaflowError> {
+    let _ = (target, config);
+    match backend {
+        #[cfg(feature = "duckdb")]
+        "duckdb" => {
+            let path = if target.is_empty() {
+                ":memory:"
+            } else {
+                target
+            };
+            let conn = semaflow::DuckDbConnection::with_config(path, config.duckdb.clone());
+            conn.initialize_pool().await?;
+            Ok(Arc::new(conn))
+        }
+        #[cfg(feature = "postgres")]
+        "postgres" => {
+            let (url, schema) = target.rsplit_once('#').unwrap_or((target, "public"));
+            let conn =
+                semaflow::PostgresConnection::with_config(url, schema, config.postgres.clone())?;
+            Ok(Arc::new(conn))
+        }
+        #[cfg(feature = "bigquery")]
+        "bigquery" => {
+            let (project, dataset) = split_bigquery_target(target)?;
+            let conn =
+                semaflow::BigQueryConnection::from_application_default_credentials_with_config(
+                    project,
+                    dataset,
+                    config.bigquery.clone(),
+                )
+                .await?;
+            Ok(Arc::new(conn))
+        }
+        other => Err(SemaflowError::Config(format!(
+            "backend '{other}' is not available in this build"
+        ))),
+    }
+}
+
+fn parse_dialect(spec: &str) -> Result<Box<dyn Dialect>, SemaflowError> {
+    let (backend, target) = spec.split_once(':').unwrap_or((spec, ""));
+    let _ = target;
+    match backend {
+        #[cfg(feature = "duckdb")]
+        "duckdb" => Ok(Box::new(semaflow::dialect::DuckDbDialect)),
+        #[cfg(feature = "postgres")]
+        "postgres" => Ok(Box::new(semaflow::dialect::PostgresDialect {
+            schema: if target.is_empty() { "public" } else { target }.to_string(),
+        })),
+        #[cfg(feature = "bigquery")]
+        "bigquery" => {
+            let (project, dataset) = split_bigquery_target(target)?;
+            Ok(Box::new(semaflow::dialect::BigQueryDialect {
+                project_id: project.to_string(),
+                dataset: dataset.to_string(),
+            }))
+        }
+        other => Err(SemaflowError::Config(format!(
+            "dialect '{other}' is not available in this build"
+        ))),
+    }
+}
+
+#[cfg(feature = "bigquery")]
+fn split_bigquery_target(target: &str) -> Result<(&str, &str), SemaflowError> {
+    target.split_once('/').ok_or_else(|| {
+        SemaflowError::Config(format!("expected bigquery:PROJECT/DATASET, got '{target}'"))
+    })
+}
+
+impl QueryArgs {
+    fn to_request(&self) -> Result<QueryRequest, SemaflowError> {
+        let mut request = match &self.request {
+            Some(path) => read_request(path)?,
+            None => QueryRequest::default(),
+        };
+        if let Some(flow) = &self.flow {
+            request.flow = flow.clone();
+        }
+        if request.flow.is_empty() {
+            return Err(SemaflowError::Validation(
+                "no flow given; pass --flow or --request".to_string(),
+            ));
+        }
+        for dim in &self.dims {
+            request.dimensions.push(parse_dimension(dim)?);
+        }
+        request.measures.extend(self.measures.iter().cloned());
+        for filter in &self.filters {
+            request.filters.push(parse_filter(filter)?);
+        }
+        for column in &self.order {
+            request.order.push(match column.strip_prefix('-') {
+                Some(column) => OrderItem {
+                    column: column.to_string(),
+                    direction: SortDirection::Desc,
+                },
+                None => OrderItem {
+                    column: column.clone(),
+                    direction: SortDirection::Asc,
+                },
+            });
+        }
+        if self.limit.is_some() {
+            request.limit = self.limit;
+        }
+        for param in &self.params {
+            let (name, value) = param.split_once('=').ok_or_else(|| {
+                SemaflowError::Validation(format!("expected NAME=VALUE, got '{param}'"))
+            })?;
+            request.params.insert(name.to_string(), parse_value(value));
+        }
+        Ok(request)
+    }
+}
+
+fn read_request(path: &Path) -> Result<QueryRequest, SemaflowError> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_yaml::from_str(&contents).map_err(|e| {
+        SemaflowError::Validation(format!("failed to parse request {}: {e}", path.display()))
+    })
+}
+
+fn parse_dimension(spec: &str) -> Result<DimensionRequest, SemaflowError> {
+    let Some((field, grain)) = spec.split_once(':') else {
+        return Ok(spec.into());
+    };
+    let grain: TimeGrain = serde_json::from_value(Value::String(grain.to_string()))
+        .map_err(|_| SemaflowError::Validation(format!("unknown time grain '{grain}'")))?;
+    Ok(DimensionRequest {
+        field: field.to_string(),
+        grain: Some(grain),
+    })
+}
+
+fn parse_filter(spec: &str) -> Result<FilterExpr, SemaflowError> {
+    let (index, symbol, op) = FILTER_OPS
+        .iter()
+        .filter_map(|(symbol, op)| spec.find(symbol).map(|i| (i, *symbol, op.clone())))
+        .min_by_key(|(i, symbol, _)| (*i, std::cmp::Reverse(symbol.len())))
+        .ok_or_else(|| {
+            SemaflowError::Validation(format!(
+                "filter '{spec}' has no operator (==, !=, >, >=, <, <=)"
+            ))
+        })?;
+    Ok(FilterExpr::Condition(Filter {
+        field: spec[..index].trim().to_string(),
+        op,
+        value: parse_value(spec[index + symbol.len()..].trim()),
+    }))
+}
+
+/// A JSON literal when `raw` parses as one (`42`, `true`, `"x"`), otherwise
+/// the text itself.
+fn parse_value(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+fn describe(
+    registry: &FlowRegistry,
+    flow: Option<&str>,
+    format: Format,
+) -> Result<(), SemaflowError> {
+    let Some(flow) = flow else {
+        let mut summaries = registry.list_flow_summaries();
+        summaries.sort_by(|a, b| a.name.cmp(&b.name));
+        match format {
+            Format::Json => print_json(&summaries),
+            _ => {
+                let rows = summaries
+                    .iter()
+                    .map(|s| vec![s.name.clone(), s.description.clone().unwrap_or_default()])
+                    .collect();
+                print_rows(&["flow", "description"], rows, format);
+            }
+        }
+        return Ok(());
+    };
+    let schema = registry.flow_schema(flow)?;
+    if let Format::Json = format {
+        print_json(&schema);
+        return Ok(());
+    }
+    let mut rows = Vec::new();
+    for d in &schema.dimensions {
+        rows.push(vec![
+            "dimension".to_string(),
+            d.qualified_name.clone(),
+            d.data_type.clone().unwrap_or_default(),
+            d.description.clone().unwrap_or_default(),
+        ]);
+    }
+    for m in &schema.measures {
+        rows.push(vec![
+            "measure".to_string(),
+            m.qualified_name.clone(),
+            m.data_type.clone().unwrap_or_default(),
+            m.description.clone().unwrap_or_default(),
+        ]);
+    }
+    if let Format::Table = format {
+        println!(
+            "flow {} (base table {})",
+            schema.name, schema.base_table.semantic_table
+        );
+        if let Some(description) = &schema.description {
+            println!("{description}");
+        }
+        println!();
+    }
+    print_rows(&["kind", "name", "type", "description"], rows, format);
+    Ok(())
+}
+
+fn print_result(result: &QueryResult, format: Format) {
+    if let Format::Json = format {
+        print_json(&result.rows);
+        return;
+    }
+    let headers: Vec<&str> = result.columns.iter().map(|c| c.name.as_str()).collect();
+    let rows = result
+        .rows
+        .iter()
+        .map(|row| headers.iter().map(|h| cell(row, h)).collect())
+        .collect();
+    print_rows(&headers, rows, format);
+}
+
+fn cell(row: &Map<String, Value>, column: &str) -> String {
+    match row.get(column) {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn print_json<T: serde::Serialize>(value: &T) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).unwrap_or_default()
+    );
+}
+
+fn print_rows(headers: &[&str], rows: Vec<Vec<String>>, format: Format) {
+    if let Format::Csv = format {
+        println!(
+            "{}",
+            headers
+                .iter()
+                .map(|h| csv_field(h))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        for row in rows {
+            println!(
+                "{}",
+                row.iter()
+                    .map(|c| csv_field(c))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+        }
+        return;
+    }
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    let line = |values: Vec<&str>| {
+        let padded: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{v:<w$}"))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    line(headers.to_vec());
+    line(rule.iter().map(String::as_str).collect());
+    for row in &rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_filters_and_dimensions() {
+        let FilterExpr::Condition(filter) = parse_filter("amount >= 100").unwrap() else {
+            panic!("expected a condition");
+        };
+        assert_eq!(filter.field, "amount");
+        assert!(matches!(filter.op, FilterOp::Gte));
+        assert_eq!(filter.value, Value::from(100));
+
+        let FilterExpr::Condition(filter) = parse_filter("country==US").unwrap() else {
+            panic!("expected a condition");
+        };
+        assert!(matches!(filter.op, FilterOp::Eq));
+        assert_eq!(filter.value, Value::from("US"));
+        assert!(parse_filter("country").is_err());
+
+        let dim = parse_dimension("created_at:month").unwrap();
+        assert_eq!(dim.grain, Some(TimeGrain::Month));
+        assert!(parse_dimension("created_at:fortnight").is_err());
+    }
+}
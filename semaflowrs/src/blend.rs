@@ -0,0 +1,439 @@
+//! Metric blending across flows.
+//!
+//! A [`BlendRequest`] asks for measures from several flows grouped by the same
+//! conformed dimensions. Each flow is planned as an ordinary
+//! [`QueryRequest`](crate::flows::QueryRequest)
+//! and the per-flow results are full-outer-joined on the dimension columns:
+//! - Same data source: one SQL statement, each flow query a CTE joined with
+//!   `FULL OUTER JOIN ... ON ... IS NOT DISTINCT FROM ...`
+//! - Different data sources: each flow runs on its own connection and the rows
+//!   are merged in memory
+//!
+//! Ordering and `limit` apply to the blended rows in both cases.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{Map, Value};
+
+use crate::backends::ConnectionManager;
+use crate::dialect::Dialect;
+use crate::error::{Result, SemaflowError};
+use crate::executor::{compare_json_values, ColumnKind, ColumnMeta, QueryResult};
+use crate::flows::{BlendRequest, SortDirection};
+use crate::policy::QueryContext;
+use crate::query_builder::SqlBuilder;
+use crate::query_tags::QueryTags;
+use crate::registry::FlowRegistry;
+use crate::runtime::run_query;
+use crate::sql_ast::sanitize_alias;
+
+const FLOW_CTE_PREFIX: &str = "__blend_";
+
+/// Output columns of a validated blend: shared dimensions, then each flow's
+/// measures in flow order.
+struct BlendColumns {
+    dimensions: Vec<String>,
+    measures: Vec<Vec<String>>,
+}
+
+impl BlendColumns {
+    fn all(&self) -> impl Iterator<Item = &String> {
+        self.dimensions.iter().chain(self.measures.iter().flatten())
+    }
+
+    /// Mark result columns as the blend's dimensions or measures.
+    fn annotate(&self, columns: &mut [ColumnMeta]) {
+        for column in columns {
+            column.kind = if self.dimensions.contains(&column.name) {
+                Some(ColumnKind::Dimension)
+            } else if self.measures.iter().flatten().any(|m| *m == column.name) {
+                Some(ColumnKind::Measure)
+            } else {
+                None
+            };
+        }
+    }
+}
+
+fn blend_columns(blend: &BlendRequest) -> Result<BlendColumns> {
+    if blend.flows.len() < 2 {
+        return Err(SemaflowError::Validation(
+            "blend requests need at least two flows".to_string(),
+        ));
+    }
+    let dimensions: Vec<String> = blend
+        .dimensions
+        .iter()
+        .map(|d| sanitize_alias(&d.field))
+        .collect();
+    let mut seen: HashSet<String> = HashSet::new();
+    for (dim, alias) in blend.dimensions.iter().zip(&dimensions) {
+        if !seen.insert(alias.clone()) {
+            return Err(SemaflowError::Validation(format!(
+                "blend dimension '{}' is requested more than once",
+                dim.field
+            )));
+        }
+    }
+    let mut measures = Vec::new();
+    for (flow, part) in &blend.flows {
+        if part.measures.is_empty() {
+            return Err(SemaflowError::Validation(format!(
+                "blend flow '{flow}' requests no measures"
+            )));
+        }
+        let mut aliases = Vec::new();
+        for measure in &part.measures {
+            let alias = sanitize_alias(measure);
+            if !seen.insert(alias.clone()) {
+                return Err(SemaflowError::Validation(format!(
+                    "blend output column '{alias}' (measure '{measure}' of flow '{flow}') \
+                     collides with another dimension or measure"
+                )));
+            }
+            aliases.push(alias);
+        }
+        measures.push(aliases);
+    }
+    for item in &blend.order {
+        if !seen.contains(&sanitize_alias(&item.column)) {
+            return Err(SemaflowError::Validation(format!(
+                "blend can only order by requested fields; '{}' is not selected",
+                item.column
+            )));
+        }
+    }
+    Ok(BlendColumns {
+        dimensions,
+        measures,
+    })
+}
+
+/// Build a single SQL statement blending every flow with `dialect`, each
+/// planned by `builder`.
+///
+/// Only meaningful when all flows read from the same data source; see
+/// [`run_blend_query`] for the general case.
+pub fn build_blend_sql(
+    builder: &SqlBuilder,
+    registry: &FlowRegistry,
+    blend: &BlendRequest,
+    context: &QueryContext,
+    dialect: &dyn Dialect,
+) -> Result<String> {
+    let columns = blend_columns(blend)?;
+    let q = |ident: &str| dialect.quote_ident(ident);
+    let ctes: Vec<String> = (0..blend.flows.len())
+        .map(|i| format!("{FLOW_CTE_PREFIX}{i}"))
+        .collect();
+    let col = |cte: &str, ident: &str| format!("{}.{}", q(cte), q(ident));
+
+    let mut with = Vec::new();
+    for (cte, request) in ctes.iter().zip(blend.flow_requests()) {
+        let sql = builder.build_with_context(registry, &request, context, dialect)?;
+        with.push(format!("{} AS ({sql})", q(cte)));
+    }
+
+    // A dimension's value comes from whichever flows have a row for it
+    let merged_key = |dim: &str, upto: usize| -> String {
+        if upto == 1 {
+            return col(&ctes[0], dim);
+        }
+        let parts: Vec<String> = ctes[..upto].iter().map(|c| col(c, dim)).collect();
+        format!("COALESCE({})", parts.join(", "))
+    };
+
+    let mut select: Vec<String> = columns
+        .dimensions
+        .iter()
+        .map(|dim| format!("{} AS {}", merged_key(dim, ctes.len()), q(dim)))
+        .collect();
+    for (cte, measures) in ctes.iter().zip(&columns.measures) {
+        select.extend(
+            measures
+                .iter()
+                .map(|m| format!("{} AS {}", col(cte, m), q(m))),
+        );
+    }
+
+    let mut from = q(&ctes[0]);
+    for (i, cte) in ctes.iter().enumerate().skip(1) {
+        if columns.dimensions.is_empty() {
+            from.push_str(&format!(" CROSS JOIN {}", q(cte)));
+            continue;
+        }
+        let on: Vec<String> = columns
+            .dimensions
+            .iter()
+            .map(|dim| {
+                format!(
+                    "{} IS NOT DISTINCT FROM {}",
+                    merged_key(dim, i),
+                    col(cte, dim)
+                )
+            })
+            .collect();
+        from.push_str(&format!(
+            " FULL OUTER JOIN {} ON {}",
+            q(cte),
+            on.join(" AND ")
+        ));
+    }
+
+    let mut sql = format!(
+        "WITH {} SELECT {} FROM {from}",
+        with.join(", "),
+        select.join(", ")
+    );
+    if !blend.order.is_empty() {
+        let orders: Vec<String> = blend
+            .order
+            .iter()
+            .map(|item| {
+                let dir = match item.direction {
+                    SortDirection::Asc => "ASC",
+                    SortDirection::Desc => "DESC",
+                };
+                format!("{} {dir}", q(&sanitize_alias(&item.column)))
+            })
+            .collect();
+        sql.push_str(&format!(" ORDER BY {}", orders.join(", ")));
+    }
+    sql.push_str(
+        &dialect
+            .capabilities()
+            .limit
+            .render(blend.limit.map(u64::from), None),
+    );
+    Ok(sql)
+}
+
+/// Execute a blend, in SQL when every flow shares a data source and by
+/// merging per-flow results in memory otherwise.
+pub async fn run_blend_query(
+    registry: &FlowRegistry,
+    connections: &ConnectionManager,
+    blend: &BlendRequest,
+    context: &QueryContext,
+) -> Result<QueryResult> {
+    let start = std::time::Instant::now();
+    let columns = blend_columns(blend)?;
+    let requests = blend.flow_requests();
+    let mut sources = Vec::new();
+    for request in &requests {
+        sources.push(flow_data_source(registry, &request.flow)?);
+    }
+
+    let mut result = if sources.iter().all(|s| *s == sources[0]) {
+        let data_source = sources[0];
+        let ds = connections
+            .get(data_source)
+            .ok_or_else(|| SemaflowError::UnknownDataSource {
+                data_source: data_source.to_string(),
+            })?;
+        let builder = SqlBuilder::with_planner(connections.config_for(data_source).planner);
+        let sql = build_blend_sql(&builder, registry, blend, context, ds.dialect())?;
+        let tags = QueryTags::for_request(
+            &requests[0],
+            context,
+            &connections.config_for(data_source).tagging,
+        );
+        let _permit = connections.acquire(&requests[0].flow, data_source).await?;
+        tracing::debug!(data_source, flows = requests.len(), "executing blended SQL");
+        ds.execute_sql_tagged(&tags.annotate(&sql), &tags).await?
+    } else {
+        tracing::debug!(flows = requests.len(), "blending results in memory");
+        let results = futures::future::try_join_all(
+            requests
+                .iter()
+                .map(|request| run_query(registry, connections, request, context)),
+        )
+        .await?;
+        merge_results(blend, &columns, results)
+    };
+    columns.annotate(&mut result.columns);
+
+    tracing::info!(
+        flows = requests.len(),
+        rows = result.rows.len(),
+        ms = start.elapsed().as_millis(),
+        "blend query completed"
+    );
+    Ok(result)
+}
+
+fn flow_data_source<'a>(registry: &'a FlowRegistry, flow_name: &str) -> Result<&'a str> {
+    let flow = registry
+        .get_flow(flow_name)
+        .ok_or_else(|| SemaflowError::UnknownFlow {
+            flow: flow_name.to_string(),
+        })?;
+    let table = registry
+        .get_table(&flow.base_table.semantic_table)
+        .ok_or_else(|| {
+            SemaflowError::Validation(format!(
+                "flow {} base table {} not found",
+                flow.name, flow.base_table.semantic_table
+            ))
+        })?;
+    Ok(&table.data_source)
+}
+
+/// Full outer join of per-flow results on the dimension columns, in the order
+/// rows are first seen.
+fn merge_results(
+    blend: &BlendRequest,
+    columns: &BlendColumns,
+    results: Vec<QueryResult>,
+) -> QueryResult {
+    let mut rows: Vec<Map<String, Value>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (result, measures) in results.into_iter().zip(&columns.measures) {
+        for mut row in result.rows {
+            let key_values: Vec<&Value> = columns
+                .dimensions
+                .iter()
+                .map(|d| row.get(d).unwrap_or(&Value::Null))
+                .collect();
+            let key = serde_json::to_string(&key_values).unwrap_or_default();
+            let pos = *index.entry(key).or_insert_with(|| {
+                let mut merged = Map::new();
+                for dim in &columns.dimensions {
+                    merged.insert(dim.clone(), row.get(dim).cloned().unwrap_or(Value::Null));
+                }
+                rows.push(merged);
+                rows.len() - 1
+            });
+            for measure in measures {
+                let value = row.remove(measure).unwrap_or(Value::Null);
+                rows[pos].insert(measure.clone(), value);
+            }
+        }
+    }
+    for row in &mut rows {
+        for column in columns.all() {
+            row.entry(column.clone()).or_insert(Value::Null);
+        }
+    }
+
+    if !blend.order.is_empty() {
+        let keys: Vec<(String, bool)> = blend
+            .order
+            .iter()
+            .map(|item| {
+                (
+                    sanitize_alias(&item.column),
+                    matches!(item.direction, SortDirection::Desc),
+                )
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            keys.iter()
+                .map(|(column, desc)| compare_json_values(&a[column], &b[column], *desc))
+                .find(|ord| *ord != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+    if let Some(limit) = blend.limit {
+        rows.truncate(limit as usize);
+    }
+
+    QueryResult {
+        columns: columns.all().map(ColumnMeta::named).collect(),
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flows::{BlendFlow, OrderItem};
+    use serde_json::json;
+
+    fn blend() -> BlendRequest {
+        BlendRequest {
+            dimensions: vec!["country".into()],
+            flows: [
+                (
+                    "sales".to_string(),
+                    BlendFlow {
+                        measures: vec!["revenue".to_string()],
+                        filters: vec![],
+                        params: Default::default(),
+                    },
+                ),
+                (
+                    "marketing".to_string(),
+                    BlendFlow {
+                        measures: vec!["spend".to_string()],
+                        filters: vec![],
+                        params: Default::default(),
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn result(rows: Value) -> QueryResult {
+        QueryResult {
+            columns: vec![],
+            rows: rows
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r.as_object().unwrap().clone())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn merges_rows_on_shared_dimensions() {
+        let mut blend = blend();
+        blend.order = vec![OrderItem {
+            column: "spend".to_string(),
+            direction: SortDirection::Desc,
+        }];
+        let columns = blend_columns(&blend).unwrap();
+        // Flows are keyed by name, so "marketing" comes first
+        let merged = merge_results(
+            &blend,
+            &columns,
+            vec![
+                result(json!([
+                    {"country": "US", "spend": 10},
+                    {"country": "FR", "spend": 30},
+                ])),
+                result(json!([
+                    {"country": "US", "revenue": 100},
+                    {"country": null, "revenue": 5},
+                ])),
+            ],
+        );
+        let names: Vec<&str> = merged.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["country", "spend", "revenue"]);
+        assert_eq!(
+            Value::Array(merged.rows.into_iter().map(Value::Object).collect()),
+            json!([
+                {"country": "FR", "spend": 30, "revenue": null},
+                {"country": "US", "spend": 10, "revenue": 100},
+                {"country": null, "spend": null, "revenue": 5},
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_colliding_measures_and_single_flow() {
+        let mut single = blend();
+        single.flows.remove("marketing");
+        assert!(blend_columns(&single).is_err());
+
+        let mut colliding = blend();
+        colliding.flows.get_mut("marketing").unwrap().measures = vec!["revenue".to_string()];
+        let err = blend_columns(&colliding).err().unwrap().to_string();
+        assert!(err.contains("collides"), "{err}");
+    }
+}
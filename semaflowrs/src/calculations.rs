@@ -0,0 +1,290 @@
+//! Table calculations: rank, difference and percent change over aggregated rows.
+//!
+//! Calculations run after aggregation, over every result row before `limit`
+//! and `offset` are applied. Dialects with window functions compute them in
+//! SQL by wrapping the planned query; otherwise [`run_query`] executes the
+//! request without them and [`apply_in_memory`] fills them in afterwards.
+//!
+//! [`run_query`]: crate::runtime::run_query
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::error::{Result, SemaflowError};
+use crate::executor::{compare_json_values, ColumnKind, ColumnMeta, QueryResult};
+use crate::flows::{CalculationKind, OrderItem, QueryRequest, SortDirection};
+use crate::sql_ast::sanitize_alias;
+
+/// Check calculation names, measures and partitions against the request.
+pub(crate) fn validate(request: &QueryRequest) -> Result<()> {
+    let mut names: HashSet<String> = request
+        .dimensions
+        .iter()
+        .map(|d| sanitize_alias(&d.field))
+        .chain(request.measures.iter().map(|m| sanitize_alias(m)))
+        .collect();
+    let has_row_order = !row_order(request).is_empty();
+    for calc in &request.calculations {
+        if !names.insert(sanitize_alias(&calc.name)) {
+            return Err(SemaflowError::Validation(format!(
+                "calculation '{}' clashes with another requested field or calculation",
+                calc.name
+            )));
+        }
+        if !request.measures.contains(&calc.measure) {
+            return Err(SemaflowError::Validation(format!(
+                "calculation '{}' reads measure '{}' which is not in the requested measures",
+                calc.name, calc.measure
+            )));
+        }
+        for dim in &calc.partition_by {
+            if !request.dimensions.iter().any(|d| d.field == *dim) {
+                return Err(SemaflowError::Validation(format!(
+                    "calculation '{}' partitions by '{dim}' which is not a requested dimension",
+                    calc.name
+                )));
+            }
+        }
+        if calc.kind != CalculationKind::Rank && !has_row_order {
+            return Err(SemaflowError::Validation(format!(
+                "calculation '{}' needs an order on requested fields to define the previous row",
+                calc.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The request without calculations, ordered only by its non-calculation
+/// fields and without `limit`/`offset`, which apply after calculating.
+pub(crate) fn base_request(request: &QueryRequest) -> QueryRequest {
+    QueryRequest {
+        order: row_order(request).into_iter().cloned().collect(),
+        limit: None,
+        offset: None,
+        calculations: Vec::new(),
+        ..request.clone()
+    }
+}
+
+/// Order items that reference requested fields rather than calculations.
+/// They define the "previous row" for differences.
+pub(crate) fn row_order(request: &QueryRequest) -> Vec<&OrderItem> {
+    request
+        .order
+        .iter()
+        .filter(|item| !request.calculations.iter().any(|c| c.name == item.column))
+        .collect()
+}
+
+/// Compute the request's calculations over rows returned by its
+/// [`base_request`], then apply the full order, `offset` and `limit`.
+pub(crate) fn apply_in_memory(request: &QueryRequest, result: &mut QueryResult) -> Result<()> {
+    validate(request)?;
+    let rows = &mut result.rows;
+    for calc in &request.calculations {
+        let measure = sanitize_alias(&calc.measure);
+        let partition_keys: Vec<String> = calc
+            .partition_by
+            .iter()
+            .map(|d| sanitize_alias(d))
+            .collect();
+        let mut partitions: HashMap<String, Vec<usize>> = HashMap::new();
+        for (idx, row) in rows.iter().enumerate() {
+            let key: Vec<&Value> = partition_keys
+                .iter()
+                .map(|k| row.get(k).unwrap_or(&Value::Null))
+                .collect();
+            let key = serde_json::to_string(&key).unwrap_or_default();
+            partitions.entry(key).or_default().push(idx);
+        }
+
+        let mut values = vec![Value::Null; rows.len()];
+        for members in partitions.values() {
+            let measure_at = |idx: usize| rows[idx].get(&measure).unwrap_or(&Value::Null);
+            match calc.kind {
+                CalculationKind::Rank => {
+                    let mut ranked = members.clone();
+                    ranked
+                        .sort_by(|a, b| compare_numbers(measure_at(*a), measure_at(*b)).reverse());
+                    for (pos, &idx) in ranked.iter().enumerate() {
+                        // Ties share the rank of the first row with that value
+                        let first = ranked[..pos]
+                            .iter()
+                            .position(|&other| {
+                                compare_numbers(measure_at(other), measure_at(idx))
+                                    == Ordering::Equal
+                            })
+                            .unwrap_or(pos);
+                        values[idx] = Value::from(first as u64 + 1);
+                    }
+                }
+                CalculationKind::Difference | CalculationKind::PercentChange => {
+                    // Rows arrive in the request's row order
+                    for pair in members.windows(2) {
+                        let (prev, cur) = (measure_at(pair[0]), measure_at(pair[1]));
+                        values[pair[1]] = if calc.kind == CalculationKind::Difference {
+                            difference(cur, prev)
+                        } else {
+                            percent_change(cur, prev)
+                        };
+                    }
+                }
+            }
+        }
+        for (row, value) in rows.iter_mut().zip(values) {
+            row.insert(sanitize_alias(&calc.name), value);
+        }
+        result.columns.push(ColumnMeta {
+            kind: Some(ColumnKind::Measure),
+            ..ColumnMeta::named(sanitize_alias(&calc.name))
+        });
+    }
+
+    if request.order.len() > row_order(request).len() {
+        let keys: Vec<(String, bool)> = request
+            .order
+            .iter()
+            .map(|item| {
+                (
+                    sanitize_alias(&item.column),
+                    matches!(item.direction, SortDirection::Desc),
+                )
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            keys.iter()
+                .map(|(column, desc)| {
+                    let null = Value::Null;
+                    compare_json_values(
+                        a.get(column).unwrap_or(&null),
+                        b.get(column).unwrap_or(&null),
+                        *desc,
+                    )
+                })
+                .find(|ord| *ord != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+    let offset = (request.offset.unwrap_or(0) as usize).min(rows.len());
+    rows.drain(..offset);
+    if let Some(limit) = request.limit {
+        rows.truncate(limit as usize);
+    }
+    Ok(())
+}
+
+/// Numeric value of a result cell; decimals may arrive as strings.
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Numeric ordering with non-numeric values (NULLs) last.
+fn compare_numbers(a: &Value, b: &Value) -> Ordering {
+    match (as_number(a), as_number(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn difference(cur: &Value, prev: &Value) -> Value {
+    if let (Some(c), Some(p)) = (cur.as_i64(), prev.as_i64()) {
+        if let Some(diff) = c.checked_sub(p) {
+            return Value::from(diff);
+        }
+    }
+    match (as_number(cur), as_number(prev)) {
+        (Some(c), Some(p)) => Value::from(c - p),
+        _ => Value::Null,
+    }
+}
+
+fn percent_change(cur: &Value, prev: &Value) -> Value {
+    match (as_number(cur), as_number(prev)) {
+        (Some(c), Some(p)) if p != 0.0 => Value::from((c - p) / p),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flows::TableCalculation;
+    use serde_json::{json, Map};
+
+    fn result(rows: Value) -> QueryResult {
+        QueryResult {
+            columns: vec![],
+            rows: rows
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r.as_object().unwrap().clone())
+                .collect::<Vec<Map<String, Value>>>(),
+        }
+    }
+
+    #[test]
+    fn computes_rank_difference_and_percent_change() {
+        let request: QueryRequest = serde_json::from_value(json!({
+            "flow": "sales",
+            "dimensions": ["country", "month"],
+            "measures": ["revenue"],
+            "order": [{"column": "month", "direction": "asc"}, {"column": "rank", "direction": "asc"}],
+            "limit": 4,
+            "calculations": [
+                {"name": "rank", "type": "rank", "measure": "revenue", "partition_by": ["month"]},
+                {"name": "delta", "type": "difference", "measure": "revenue", "partition_by": ["country"]},
+                {"name": "growth", "type": "percent_change", "measure": "revenue", "partition_by": ["country"]},
+            ],
+        }))
+        .unwrap();
+        let mut result = result(json!([
+            {"country": "FR", "month": 1, "revenue": 50},
+            {"country": "US", "month": 1, "revenue": 100},
+            {"country": "FR", "month": 2, "revenue": 75},
+            {"country": "US", "month": 2, "revenue": 75},
+            {"country": "US", "month": 3, "revenue": null},
+        ]));
+        apply_in_memory(&request, &mut result).unwrap();
+
+        let rows: Vec<Value> = result.rows.into_iter().map(Value::Object).collect();
+        assert_eq!(
+            rows,
+            vec![
+                json!({"country": "US", "month": 1, "revenue": 100, "rank": 1, "delta": null, "growth": null}),
+                json!({"country": "FR", "month": 1, "revenue": 50, "rank": 2, "delta": null, "growth": null}),
+                json!({"country": "FR", "month": 2, "revenue": 75, "rank": 1, "delta": 25, "growth": 0.5}),
+                json!({"country": "US", "month": 2, "revenue": 75, "rank": 1, "delta": -25, "growth": -0.25}),
+            ]
+        );
+        let names: Vec<&str> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["rank", "delta", "growth"]);
+    }
+
+    #[test]
+    fn difference_requires_row_order() {
+        let request = QueryRequest {
+            flow: "sales".to_string(),
+            dimensions: vec!["month".into()],
+            measures: vec!["revenue".to_string()],
+            calculations: vec![TableCalculation {
+                name: "delta".to_string(),
+                kind: CalculationKind::Difference,
+                measure: "revenue".to_string(),
+                partition_by: vec![],
+            }],
+            ..Default::default()
+        };
+        let err = validate(&request).unwrap_err().to_string();
+        assert!(err.contains("needs an order"), "{err}");
+    }
+}
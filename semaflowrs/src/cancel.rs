@@ -0,0 +1,163 @@
+//! Query timeouts and cancellation.
+//!
+//! The runtime executes every query inside a [`QueryCancellation`] scope.
+//! When the query outlives its timeout (`query.timeout_ms`, or the request's
+//! `query_timeout_ms`) the scope's token is cancelled and the backend stops
+//! the query on the server - DuckDB interrupts the connection, PostgreSQL
+//! sends a cancel request, BigQuery cancels the job - so the connection and
+//! concurrency slot are released instead of being held by a runaway query.
+//!
+//! Embedders can also cancel queries themselves, e.g. when a client
+//! disconnects:
+//!
+//! ```ignore
+//! let token = CancellationToken::new();
+//! let result = cancel::with_token(token.clone(), run_query(&registry, &conns, &req, &ctx));
+//! // elsewhere: token.cancel();
+//! ```
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+pub use tokio_util::sync::CancellationToken;
+
+use crate::error::{Result, SemaflowError};
+
+/// How long a cancelled query may take to stop and release its connection
+/// before the runtime stops waiting for it.
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+tokio::task_local! {
+    static CURRENT: QueryCancellation;
+}
+
+/// Cancellation signal and deadline of the query being executed.
+#[derive(Debug, Clone, Default)]
+pub struct QueryCancellation {
+    token: CancellationToken,
+    deadline: Option<Instant>,
+}
+
+impl QueryCancellation {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once the query is cancelled or times out.
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    /// Time left before the query times out, if it has a timeout.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+/// The cancellation scope of the running query. Outside any scope this is
+/// never cancelled.
+pub fn current() -> QueryCancellation {
+    CURRENT.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// Run `fut` so that cancelling `token` cancels the queries it executes.
+pub async fn with_token<F: Future>(token: CancellationToken, fut: F) -> F::Output {
+    let scope = QueryCancellation {
+        token,
+        deadline: current().deadline,
+    };
+    CURRENT.scope(scope, fut).await
+}
+
+/// Run a query execution, cancelling it once `timeout` elapses.
+///
+/// The execution inherits the caller's scope, so an outer token still
+/// cancels it. After the timeout the backend gets a short grace period to
+/// stop the query; a result that arrives in it is still returned.
+pub(crate) async fn with_timeout<T, F>(timeout: Option<Duration>, fut: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let parent = current();
+    let Some(timeout) = timeout else {
+        return fut.await;
+    };
+    let deadline = Instant::now() + timeout;
+    let scope = QueryCancellation {
+        token: parent.token.child_token(),
+        deadline: Some(parent.deadline.map_or(deadline, |d| d.min(deadline))),
+    };
+    let token = scope.token.clone();
+    let fut = CURRENT.scope(scope, fut);
+    tokio::pin!(fut);
+
+    tokio::select! {
+        result = &mut fut => return result,
+        _ = tokio::time::sleep(timeout) => {}
+    }
+    tracing::warn!(
+        timeout_ms = timeout.as_millis(),
+        "query timed out, cancelling"
+    );
+    token.cancel();
+    match tokio::time::timeout(CANCEL_GRACE, fut).await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(_)) | Err(_) => Err(SemaflowError::Timeout(format!(
+            "query exceeded {} ms",
+            timeout.as_millis()
+        ))),
+    }
+}
+
+/// The error a backend returns for a query it stopped because of `scope`.
+pub(crate) fn cancelled_error(scope: &QueryCancellation) -> SemaflowError {
+    if scope.remaining() == Some(Duration::ZERO) {
+        SemaflowError::Timeout("query exceeded its timeout".to_string())
+    } else {
+        SemaflowError::Cancelled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn timed_out_query_is_cancelled() {
+        let result: Result<()> = with_timeout(Some(Duration::from_millis(10)), async {
+            let scope = current();
+            assert!(scope.remaining().is_some());
+            scope.cancelled().await;
+            Err(cancelled_error(&scope))
+        })
+        .await;
+        assert!(matches!(result, Err(SemaflowError::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn outer_token_cancels_timed_query() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = with_token(
+            token,
+            with_timeout(Some(Duration::from_secs(60)), async {
+                current().cancelled().await;
+                Err::<(), _>(cancelled_error(&current()))
+            }),
+        )
+        .await;
+        assert!(matches!(result, Err(SemaflowError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn no_timeout_runs_to_completion() {
+        let result = with_timeout(None, async { Ok(1) }).await.unwrap();
+        assert_eq!(result, 1);
+        assert!(current().remaining().is_none());
+    }
+}
@@ -2,8 +2,9 @@
 //!
 //! Supports TOML-based configuration with global defaults and per-datasource overrides.
 
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +20,12 @@ pub struct SemaflowConfig {
     /// Per-datasource configuration overrides (keyed by datasource name).
     #[serde(default)]
     pub datasources: HashMap<String, DatasourceConfig>,
+
+    /// Caching of query results across requests.
+    pub result_cache: ResultCacheConfig,
+
+    /// Global and per-flow concurrency budgets.
+    pub concurrency: ConcurrencyConfig,
 }
 
 /// Global default settings.
@@ -30,6 +37,11 @@ pub struct GlobalDefaults {
     pub pool: PoolConfig,
     pub schema_cache: SchemaCacheConfig,
     pub validation: ValidationConfig,
+    pub tagging: TaggingConfig,
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Budget applied to each datasource on its own.
+    pub concurrency: ConcurrencyLimit,
+    pub planner: PlannerConfig,
 }
 
 /// Query execution configuration.
@@ -62,6 +74,9 @@ pub struct SchemaCacheConfig {
     pub ttl_secs: u64,
     /// Maximum cached schemas (default: 1000).
     pub max_size: usize,
+    /// File keeping cached schemas across restarts (default: none). Only
+    /// read from `defaults`; data sources can override the TTL.
+    pub path: Option<PathBuf>,
 }
 
 /// Validation configuration.
@@ -73,6 +88,126 @@ pub struct ValidationConfig {
     pub warn_only: bool,
 }
 
+/// Workload tagging for warehouse cost attribution.
+///
+/// When enabled, every query is tagged with the flow name, a hash of the
+/// request, the static tags below and any tags supplied on the request
+/// (BigQuery job labels, PostgreSQL `application_name`, and optionally a SQL
+/// comment).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+#[derive(Default)]
+pub struct TaggingConfig {
+    /// Attach tags to executed queries (default: false).
+    pub enabled: bool,
+    /// Static tags added to every query (e.g. `team = "analytics"`).
+    pub tags: BTreeMap<String, String>,
+    /// Also prepend the tags to each statement as a `/* ... */` comment, which
+    /// shows up in every warehouse's query history (default: false).
+    pub comment: bool,
+}
+
+/// Query planner switches. Each only takes effect where the datasource's
+/// dialect supports the feature.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PlannerConfig {
+    /// Render measure filters as `FILTER (WHERE ...)` rather than wrapping
+    /// the aggregated expression in `CASE WHEN` (default: true).
+    pub filtered_aggregates: bool,
+    /// End grouped queries in `GROUP BY ALL` rather than listing every
+    /// grouping key (default: true).
+    pub group_by_all: bool,
+    /// Honour flows' `fanout_strategy: symmetric`; when false they are
+    /// pre-aggregated instead (default: true).
+    pub symmetric_aggregates: bool,
+    /// Pre-aggregate COUNT DISTINCT measures as sketches merged in the final
+    /// query, rather than joining back a flat distinct count (default: true).
+    pub distinct_sketches: bool,
+}
+
+/// Circuit breaker for failing data sources.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// Enable the circuit breaker (default: false).
+    pub enabled: bool,
+    /// Consecutive execution failures before the breaker opens (default: 5).
+    pub failure_threshold: u32,
+    /// Time (ms) the breaker stays open before a trial query is let through (default: 30000).
+    pub open_timeout_ms: u64,
+    /// Interval (ms) between background recovery probes while open (default: 5000, 0 = disabled).
+    pub probe_interval_ms: u64,
+    /// Serve the last successful result for identical SQL while open (default: false).
+    pub serve_stale: bool,
+    /// Maximum results retained for stale serving (default: 100).
+    pub stale_cache_size: usize,
+}
+
+/// A concurrency budget: how many queries may run at once, and how long a
+/// query waits for a slot before it is rejected.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConcurrencyLimit {
+    /// Maximum queries in flight (default: 0 = unlimited).
+    pub max_concurrent: usize,
+    /// Maximum time (ms) to wait for a slot before rejecting (default: 5000).
+    /// Set to 0 to wait indefinitely.
+    pub queue_timeout_ms: u64,
+}
+
+impl ConcurrencyLimit {
+    /// The wait allowed for a slot, or `None` to wait indefinitely.
+    pub fn queue_timeout(&self) -> Option<Duration> {
+        (self.queue_timeout_ms > 0).then(|| Duration::from_millis(self.queue_timeout_ms))
+    }
+}
+
+/// Concurrency budgets enforced by the [`crate::backends::ConnectionManager`]
+/// before a query reaches its backend. Per-datasource budgets are set with
+/// `defaults.concurrency` and `datasources.<name>.concurrency`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConcurrencyConfig {
+    /// Budget shared by queries against all datasources.
+    #[serde(flatten)]
+    pub global: ConcurrencyLimit,
+    /// Per-flow budgets, keyed by flow name.
+    pub flows: HashMap<String, ConcurrencyLimit>,
+}
+
+/// Result cache consulted by [`crate::runtime::run_query`].
+///
+/// Results are keyed by data source and rendered SQL, so requests that
+/// differ only in access-policy context or parameters never share entries.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ResultCacheConfig {
+    /// Cache query results (default: false).
+    pub enabled: bool,
+    /// Time (secs) a cached result is served before re-running the query (default: 300).
+    pub ttl_secs: u64,
+    /// Maximum results held by the built-in in-memory cache (default: 1000).
+    pub max_entries: usize,
+    /// Per-flow TTL overrides in seconds; 0 disables caching for that flow.
+    pub flow_ttl_secs: HashMap<String, u64>,
+}
+
+impl ResultCacheConfig {
+    /// How long results of `flow` are cached, or `None` when they are not.
+    pub fn ttl_for(&self, flow: &str) -> Option<Duration> {
+        if !self.enabled {
+            return None;
+        }
+        let secs = self
+            .flow_ttl_secs
+            .get(flow)
+            .copied()
+            .unwrap_or(self.ttl_secs);
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+}
+
 /// Per-datasource configuration (can override globals).
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(default)]
@@ -80,6 +215,13 @@ pub struct DatasourceConfig {
     pub query: Option<QueryConfig>,
     pub pool: Option<PoolConfig>,
     pub schema_cache: Option<SchemaCacheConfig>,
+    pub tagging: Option<TaggingConfig>,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    pub concurrency: Option<ConcurrencyLimit>,
+    pub planner: Option<PlannerConfig>,
+
+    /// How to connect, for [`crate::backends::ConnectionManager::from_config_file`].
+    pub connection: Option<ConnectionConfig>,
 
     /// BigQuery-specific options.
     pub bigquery: Option<BigQueryConfig>,
@@ -91,6 +233,162 @@ pub struct DatasourceConfig {
     pub postgres: Option<PostgresConfig>,
 }
 
+/// Where a datasource lives, keyed by `backend`.
+///
+/// String values may reference `${VAR}` or `${VAR:-default}` environment
+/// variables and `${file:PATH}` secret files, whose contents (without the
+/// trailing newline) are substituted; see [`Self::expanded`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum ConnectionConfig {
+    /// A DuckDB database file, or `:memory:` (default).
+    Duckdb {
+        #[serde(default = "default_duckdb_path")]
+        path: String,
+    },
+    /// A PostgreSQL connection string, key-value or URL, and the schema
+    /// tables are read from (default: public).
+    Postgres {
+        url: String,
+        #[serde(default = "default_postgres_schema")]
+        schema: String,
+    },
+    /// A BigQuery dataset, read with the service account key in
+    /// `credentials_file` or else application default credentials.
+    Bigquery {
+        project: String,
+        dataset: String,
+        #[serde(default)]
+        credentials_file: Option<String>,
+    },
+}
+
+fn default_duckdb_path() -> String {
+    ":memory:".to_string()
+}
+
+fn default_postgres_schema() -> String {
+    "public".to_string()
+}
+
+impl ConnectionConfig {
+    /// The `backend` name.
+    pub fn backend(&self) -> &'static str {
+        match self {
+            ConnectionConfig::Duckdb { .. } => "duckdb",
+            ConnectionConfig::Postgres { .. } => "postgres",
+            ConnectionConfig::Bigquery { .. } => "bigquery",
+        }
+    }
+
+    /// A copy with environment variables and secret files substituted.
+    /// Unset variables without a default and unreadable files are errors.
+    pub fn expanded(&self) -> Result<Self> {
+        Ok(match self {
+            ConnectionConfig::Duckdb { path } => ConnectionConfig::Duckdb {
+                path: expand_value(path)?,
+            },
+            ConnectionConfig::Postgres { url, schema } => ConnectionConfig::Postgres {
+                url: expand_postgres_url(url)?,
+                schema: expand_value(schema)?,
+            },
+            ConnectionConfig::Bigquery {
+                project,
+                dataset,
+                credentials_file,
+            } => ConnectionConfig::Bigquery {
+                project: expand_value(project)?,
+                dataset: expand_value(dataset)?,
+                credentials_file: credentials_file.as_deref().map(expand_value).transpose()?,
+            },
+        })
+    }
+}
+
+/// Substitute `${file:PATH}` secret files and `${VAR}` environment variables.
+fn expand_value(input: &str) -> Result<String> {
+    expand_with(input, |value| value)
+}
+
+/// Like [`expand_value`] for a PostgreSQL connection string. In the URL
+/// form, values substituted into the user and password are percent-encoded,
+/// so secrets containing `@`, `:`, `/` or `#` don't change how it splits.
+fn expand_postgres_url(url: &str) -> Result<String> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return expand_value(url);
+    };
+    let unquoted = outside_variables(rest);
+    let authority_end = unquoted
+        .iter()
+        .find(|(_, c)| matches!(c, '/' | '?' | '#'))
+        .map_or(rest.len(), |(i, _)| *i);
+    let Some(at) = unquoted
+        .iter()
+        .filter(|(i, c)| *i < authority_end && *c == '@')
+        .map(|(i, _)| *i)
+        .next_back()
+    else {
+        return expand_value(url);
+    };
+    Ok(format!(
+        "{}://{}{}",
+        expand_value(scheme)?,
+        expand_with(&rest[..at], percent_encode)?,
+        expand_value(&rest[at..])?
+    ))
+}
+
+/// The characters of `input` outside `${...}` references, with their offsets.
+fn outside_variables(input: &str) -> Vec<(usize, char)> {
+    let mut chars = Vec::new();
+    let mut in_variable = false;
+    let mut iter = input.char_indices().peekable();
+    while let Some((i, c)) = iter.next() {
+        if in_variable {
+            in_variable = c != '}';
+        } else if c == '$' && iter.peek().is_some_and(|&(_, next)| next == '{') {
+            in_variable = true;
+        } else {
+            chars.push((i, c));
+        }
+    }
+    chars
+}
+
+/// Percent-encode everything but unreserved URL characters.
+fn percent_encode(value: String) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Substitute variables and secret files, passing each value through `encode`.
+fn expand_with(input: &str, encode: impl Fn(String) -> String) -> Result<String> {
+    let unreadable = std::cell::RefCell::new(None);
+    let expanded = crate::model_files::interpolate_env(input, |name| {
+        let Some(path) = name.strip_prefix("file:") else {
+            return std::env::var(name).ok().map(&encode);
+        };
+        match std::fs::read_to_string(path) {
+            Ok(secret) => Some(encode(secret.trim_end_matches(['\r', '\n']).to_string())),
+            Err(e) => {
+                unreadable.replace(Some(format!("failed to read secret file {path}: {e}")));
+                None
+            }
+        }
+    });
+    match unreadable.into_inner() {
+        Some(message) => Err(SemaflowError::Config(message)),
+        None => expanded,
+    }
+}
+
 /// BigQuery-specific configuration.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
@@ -101,21 +399,40 @@ pub struct BigQueryConfig {
     pub maximum_bytes_billed: i64,
     /// Query timeout in milliseconds (overrides query.timeout_ms for BigQuery).
     pub query_timeout_ms: u64,
-    /// Maximum concurrent queries to BigQuery (default: 40).
-    /// Prevents overwhelming BigQuery and provides backpressure.
-    pub max_concurrent_queries: usize,
-    /// Maximum time (ms) to wait in queue before rejecting (default: 5000).
-    /// When all slots are in use, requests wait up to this duration.
-    /// Set to 0 for unlimited wait (not recommended for production).
-    pub queue_timeout_ms: u64,
+    /// Shorthand for `concurrency.max_concurrent` of this datasource, used
+    /// when `concurrency` is not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_queries: Option<usize>,
+    /// Shorthand for `concurrency.queue_timeout_ms` of this datasource, used
+    /// when `concurrency` is not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_timeout_ms: Option<u64>,
 }
 
 /// DuckDB-specific configuration.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 pub struct DuckDbConfig {
-    /// Maximum concurrent queries (default: 16).
-    pub max_concurrency: usize,
+    /// Shorthand for `concurrency.max_concurrent` of this datasource, used
+    /// when `concurrency` is not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
+    /// Idle connections kept open for reuse (default: 16). All connections
+    /// share one database instance, so concurrent queries run side by side.
+    pub pool_size: usize,
+    /// Open the database file read-only (default: false), so other processes
+    /// can read it at the same time. Ignored for `:memory:`.
+    pub read_only: bool,
+    /// DuckDB `threads` setting, set when the database is opened: worker
+    /// threads shared by every connection and query (default: DuckDB's own,
+    /// one per core). DuckDB has no per-connection thread count.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database_threads: Option<usize>,
+    /// DuckDB `memory_limit` setting, set when the database is opened: the
+    /// memory all connections and queries share, e.g. `"4GB"` (default:
+    /// DuckDB's own, 80% of RAM). DuckDB has no per-query limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database_memory_limit: Option<String>,
 }
 
 /// PostgreSQL-specific configuration.
@@ -154,6 +471,51 @@ impl Default for SchemaCacheConfig {
         Self {
             ttl_secs: 3600,
             max_size: 1000,
+            path: None,
+        }
+    }
+}
+
+impl Default for PlannerConfig {
+    fn default() -> Self {
+        Self {
+            filtered_aggregates: true,
+            group_by_all: true,
+            symmetric_aggregates: true,
+            distinct_sketches: true,
+        }
+    }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            failure_threshold: 5,
+            open_timeout_ms: 30_000,
+            probe_interval_ms: 5_000,
+            serve_stale: false,
+            stale_cache_size: 100,
+        }
+    }
+}
+
+impl Default for ConcurrencyLimit {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 0, // 0 = unlimited
+            queue_timeout_ms: 5_000,
+        }
+    }
+}
+
+impl Default for ResultCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: 300,
+            max_entries: 1000,
+            flow_ttl_secs: HashMap::new(),
         }
     }
 }
@@ -164,8 +526,8 @@ impl Default for BigQueryConfig {
             use_query_cache: true,
             maximum_bytes_billed: 0, // 0 = unlimited
             query_timeout_ms: 30_000,
-            max_concurrent_queries: 30,
-            queue_timeout_ms: 1_500, // ~5× base latency for fast rejection
+            max_concurrent_queries: None,
+            queue_timeout_ms: None,
         }
     }
 }
@@ -173,7 +535,11 @@ impl Default for BigQueryConfig {
 impl Default for DuckDbConfig {
     fn default() -> Self {
         Self {
-            max_concurrency: 16,
+            max_concurrency: None,
+            pool_size: 16,
+            read_only: false,
+            database_threads: None,
+            database_memory_limit: None,
         }
     }
 }
@@ -245,12 +611,37 @@ impl SemaflowConfig {
     }
 }
 
+impl DatasourceConfig {
+    /// The budget set through `duckdb.max_concurrency` or
+    /// `bigquery.max_concurrent_queries` / `bigquery.queue_timeout_ms`, with
+    /// unset values taken from `defaults`.
+    fn backend_concurrency(&self, defaults: &ConcurrencyLimit) -> Option<ConcurrencyLimit> {
+        let (max_concurrent, queue_timeout_ms) = match (&self.bigquery, &self.duckdb) {
+            (Some(bq), _)
+                if bq.max_concurrent_queries.is_some() || bq.queue_timeout_ms.is_some() =>
+            {
+                (bq.max_concurrent_queries, bq.queue_timeout_ms)
+            }
+            (_, Some(duck)) if duck.max_concurrency.is_some() => (duck.max_concurrency, None),
+            _ => return None,
+        };
+        Some(ConcurrencyLimit {
+            max_concurrent: max_concurrent.unwrap_or(defaults.max_concurrent),
+            queue_timeout_ms: queue_timeout_ms.unwrap_or(defaults.queue_timeout_ms),
+        })
+    }
+}
+
 /// Fully resolved configuration for a datasource (no Option fields).
 #[derive(Debug, Clone)]
 pub struct ResolvedDatasourceConfig {
     pub query: QueryConfig,
     pub pool: PoolConfig,
     pub schema_cache: SchemaCacheConfig,
+    pub tagging: TaggingConfig,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub concurrency: ConcurrencyLimit,
+    pub planner: PlannerConfig,
     pub bigquery: BigQueryConfig,
     pub duckdb: DuckDbConfig,
     pub postgres: PostgresConfig,
@@ -266,6 +657,23 @@ impl ResolvedDatasourceConfig {
                     .schema_cache
                     .clone()
                     .unwrap_or_else(|| defaults.schema_cache.clone()),
+                tagging: ds
+                    .tagging
+                    .clone()
+                    .unwrap_or_else(|| defaults.tagging.clone()),
+                circuit_breaker: ds
+                    .circuit_breaker
+                    .clone()
+                    .unwrap_or_else(|| defaults.circuit_breaker.clone()),
+                concurrency: ds
+                    .concurrency
+                    .clone()
+                    .or_else(|| ds.backend_concurrency(&defaults.concurrency))
+                    .unwrap_or_else(|| defaults.concurrency.clone()),
+                planner: ds
+                    .planner
+                    .clone()
+                    .unwrap_or_else(|| defaults.planner.clone()),
                 bigquery: ds.bigquery.clone().unwrap_or_default(),
                 duckdb: ds.duckdb.clone().unwrap_or_default(),
                 postgres: ds.postgres.clone().unwrap_or_default(),
@@ -274,6 +682,10 @@ impl ResolvedDatasourceConfig {
                 query: defaults.query.clone(),
                 pool: defaults.pool.clone(),
                 schema_cache: defaults.schema_cache.clone(),
+                tagging: defaults.tagging.clone(),
+                circuit_breaker: defaults.circuit_breaker.clone(),
+                concurrency: defaults.concurrency.clone(),
+                planner: defaults.planner.clone(),
                 bigquery: BigQueryConfig::default(),
                 duckdb: DuckDbConfig::default(),
                 postgres: PostgresConfig::default(),
@@ -333,4 +745,184 @@ size = 32
         let prod_resolved = cfg.for_datasource("prod");
         assert_eq!(prod_resolved.pool.size, 32);
     }
+
+    #[test]
+    fn test_planner_override() {
+        let toml = r#"
+[defaults.planner]
+group_by_all = false
+
+[datasources.legacy.planner]
+filtered_aggregates = false
+symmetric_aggregates = false
+"#;
+        let cfg = SemaflowConfig::from_toml(toml).unwrap();
+
+        let default_resolved = cfg.for_datasource("local");
+        assert!(!default_resolved.planner.group_by_all);
+        assert!(default_resolved.planner.filtered_aggregates);
+
+        let legacy = cfg.for_datasource("legacy");
+        assert!(!legacy.planner.filtered_aggregates);
+        assert!(!legacy.planner.symmetric_aggregates);
+        assert!(legacy.planner.group_by_all);
+        assert!(legacy.planner.distinct_sketches);
+    }
+
+    #[test]
+    fn test_connection_expansion() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("pg_password");
+        std::fs::write(&secret, "s3:c/r@t#\n").unwrap();
+        let toml = format!(
+            r#"
+[datasources.warehouse.connection]
+backend = "postgres"
+url = "postgresql://analytics:${{file:{}}}@${{SEMAFLOW_TEST_UNSET_HOST:-db}}/analytics"
+
+[datasources.local.connection]
+backend = "duckdb"
+"#,
+            secret.display()
+        );
+        let cfg = SemaflowConfig::from_toml(&toml).unwrap();
+
+        let warehouse = cfg.datasources["warehouse"].connection.as_ref().unwrap();
+        assert_eq!(
+            warehouse.expanded().unwrap(),
+            ConnectionConfig::Postgres {
+                url: "postgresql://analytics:s3%3Ac%2Fr%40t%23@db/analytics".to_string(),
+                schema: "public".to_string(),
+            }
+        );
+        let local = cfg.datasources["local"].connection.as_ref().unwrap();
+        assert_eq!(
+            local,
+            &ConnectionConfig::Duckdb {
+                path: ":memory:".to_string()
+            }
+        );
+
+        let missing = ConnectionConfig::Duckdb {
+            path: "${file:/nonexistent/semaflow_secret}".to_string(),
+        };
+        let err = missing.expanded().unwrap_err().to_string();
+        assert!(err.contains("failed to read secret file"), "{err}");
+    }
+
+    #[test]
+    fn test_tagging_override() {
+        let toml = r#"
+[defaults.tagging]
+enabled = true
+tags = { team = "analytics" }
+
+[datasources.warehouse.tagging]
+enabled = true
+comment = true
+tags = { team = "finance", cost_center = "cc-42" }
+"#;
+        let cfg = SemaflowConfig::from_toml(toml).unwrap();
+
+        let default_resolved = cfg.for_datasource("local");
+        assert!(default_resolved.tagging.enabled);
+        assert!(!default_resolved.tagging.comment);
+        assert_eq!(default_resolved.tagging.tags["team"], "analytics");
+
+        let wh_resolved = cfg.for_datasource("warehouse");
+        assert_eq!(wh_resolved.tagging.tags["team"], "finance");
+        assert_eq!(wh_resolved.tagging.tags["cost_center"], "cc-42");
+        assert!(wh_resolved.tagging.comment);
+    }
+
+    #[test]
+    fn test_result_cache_flow_ttl() {
+        let toml = r#"
+[result_cache]
+enabled = true
+ttl_secs = 60
+
+[result_cache.flow_ttl_secs]
+finance = 600
+live = 0
+"#;
+        let cfg = SemaflowConfig::from_toml(toml).unwrap();
+        let cache = &cfg.result_cache;
+        assert_eq!(cache.ttl_for("sales"), Some(Duration::from_secs(60)));
+        assert_eq!(cache.ttl_for("finance"), Some(Duration::from_secs(600)));
+        assert_eq!(cache.ttl_for("live"), None);
+
+        assert_eq!(
+            SemaflowConfig::default().result_cache.ttl_for("sales"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_concurrency_budgets() {
+        let toml = r#"
+[concurrency]
+max_concurrent = 64
+
+[concurrency.flows.finance]
+max_concurrent = 2
+queue_timeout_ms = 0
+
+[defaults.concurrency]
+max_concurrent = 16
+
+[datasources.warehouse.concurrency]
+max_concurrent = 4
+queue_timeout_ms = 1000
+"#;
+        let cfg = SemaflowConfig::from_toml(toml).unwrap();
+        assert_eq!(cfg.concurrency.global.max_concurrent, 64);
+        assert_eq!(
+            cfg.concurrency.global.queue_timeout(),
+            Some(Duration::from_millis(5000))
+        );
+        assert_eq!(cfg.concurrency.flows["finance"].max_concurrent, 2);
+        assert_eq!(cfg.concurrency.flows["finance"].queue_timeout(), None);
+
+        assert_eq!(cfg.for_datasource("local").concurrency.max_concurrent, 16);
+        let warehouse = cfg.for_datasource("warehouse").concurrency;
+        assert_eq!(warehouse.max_concurrent, 4);
+        assert_eq!(warehouse.queue_timeout_ms, 1000);
+    }
+
+    #[test]
+    fn test_backend_concurrency_feeds_the_datasource_budget() {
+        let toml = r#"
+[defaults.concurrency]
+max_concurrent = 16
+queue_timeout_ms = 2000
+
+[datasources.local.duckdb]
+max_concurrency = 4
+
+[datasources.warehouse.bigquery]
+max_concurrent_queries = 30
+queue_timeout_ms = 1500
+
+[datasources.pinned.duckdb]
+max_concurrency = 4
+
+[datasources.pinned.concurrency]
+max_concurrent = 2
+
+[datasources.plain.duckdb]
+read_only = true
+"#;
+        let cfg = SemaflowConfig::from_toml(toml).unwrap();
+        let local = cfg.for_datasource("local").concurrency;
+        assert_eq!((local.max_concurrent, local.queue_timeout_ms), (4, 2000));
+        let warehouse = cfg.for_datasource("warehouse").concurrency;
+        assert_eq!(
+            (warehouse.max_concurrent, warehouse.queue_timeout_ms),
+            (30, 1500)
+        );
+        // An explicit budget wins over the backend shorthand
+        assert_eq!(cfg.for_datasource("pinned").concurrency.max_concurrent, 2);
+        assert_eq!(cfg.for_datasource("plain").concurrency.max_concurrent, 16);
+    }
 }
@@ -0,0 +1,71 @@
+//! Warnings for requests using deprecated flows and fields.
+//!
+//! Flows, dimensions and measures marked `deprecated` keep working, so
+//! existing dashboards don't break while they migrate. [`FlowRegistry::deprecations`]
+//! lists the deprecated parts a request uses, with the deprecation's
+//! `since`, `replacement` and `message`; the SQL builder logs each one, the
+//! explain output returns them, and the Python bindings raise them as
+//! `DeprecationWarning`s.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::flows::{Deprecation, QueryRequest};
+use crate::registry::FlowRegistry;
+
+/// What a deprecation warning refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeprecatedKind {
+    Flow,
+    Dimension,
+    Measure,
+}
+
+impl fmt::Display for DeprecatedKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DeprecatedKind::Flow => "flow",
+            DeprecatedKind::Dimension => "dimension",
+            DeprecatedKind::Measure => "measure",
+        })
+    }
+}
+
+/// A deprecated flow or field used by a request.
+///
+/// `Display` renders it as a one-line message for logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeprecationWarning {
+    pub kind: DeprecatedKind,
+    pub flow: String,
+    /// The flow name, or the field's qualified name (`alias.field`) in `flow`.
+    pub name: String,
+    #[serde(flatten)]
+    pub deprecation: Deprecation,
+}
+
+impl fmt::Display for DeprecationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} is deprecated", self.kind, self.name)?;
+        if let Some(since) = &self.deprecation.since {
+            write!(f, " since {since}")?;
+        }
+        if let Some(replacement) = &self.deprecation.replacement {
+            write!(f, "; use {replacement} instead")?;
+        }
+        if let Some(message) = &self.deprecation.message {
+            write!(f, ": {message}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FlowRegistry {
+    /// Deprecated flow and fields `request` uses, each once, in request
+    /// order. Fields that don't resolve are left to request validation.
+    pub fn deprecations(&self, request: &QueryRequest) -> Vec<DeprecationWarning> {
+        crate::query_builder::collect_deprecations(self, request)
+    }
+}
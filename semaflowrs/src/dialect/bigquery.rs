@@ -1,8 +1,9 @@
 //! BigQuery dialect implementation.
 
-use crate::flows::{Aggregation, Function, TimeGrain};
+use crate::flows::{Aggregation, FiscalCalendar, Function, SortDirection, TimeGrain};
 
-use super::Dialect;
+use super::fiscal::{render_fiscal_trunc, DateArithmetic};
+use super::{normalize_interval, render_bucket, Dialect, DialectCapabilities, LimitSyntax};
 
 #[derive(Debug, Clone)]
 pub struct BigQueryDialect {
@@ -40,8 +41,141 @@ impl Dialect for BigQueryDialect {
         format!("@p{}", idx)
     }
 
-    fn supports_filtered_aggregates(&self) -> bool {
-        false // BigQuery doesn't support FILTER (WHERE) syntax
+    fn capabilities(&self) -> DialectCapabilities {
+        DialectCapabilities {
+            // BigQuery doesn't support FILTER (WHERE) syntax
+            filtered_aggregates: false,
+            group_by_all: true,
+            window_functions: true,
+            ignore_nulls: true,
+            grouping_sets: true,
+            row_value_comparison: false,
+            distinct_sketches: true,
+            symmetric_aggregates: true,
+            parquet_export: false,
+            create_or_replace_table: true,
+            limit: LimitSyntax::LimitOffset,
+        }
+    }
+
+    fn weeks_start_sunday(&self) -> bool {
+        true
+    }
+
+    fn backslash_escapes(&self) -> bool {
+        true
+    }
+
+    /// BigQuery's `GROUPING()` takes a single argument, so build the
+    /// multi-column bitmask from one call per expression.
+    fn render_grouping(&self, exprs: &[String]) -> String {
+        let bits: Vec<String> = exprs
+            .iter()
+            .enumerate()
+            .map(|(i, expr)| match exprs.len() - 1 - i {
+                0 => format!("GROUPING({expr})"),
+                shift => format!("GROUPING({expr}) * {}", 1u64 << shift),
+            })
+            .collect();
+        match bits.as_slice() {
+            [single] => single.clone(),
+            _ => format!("({})", bits.join(" + ")),
+        }
+    }
+
+    fn render_date_spine(
+        &self,
+        source: &str,
+        column: &str,
+        grain: &TimeGrain,
+        output: &str,
+    ) -> String {
+        let unit = bq_grain_to_str(grain);
+        if grain < &TimeGrain::Day {
+            return format!(
+                "SELECT spine_ts AS {output} \
+                 FROM (SELECT MIN({column}) AS lo, MAX({column}) AS hi FROM {source}) AS bounds, \
+                 UNNEST(GENERATE_TIMESTAMP_ARRAY(TIMESTAMP(bounds.lo), TIMESTAMP(bounds.hi), INTERVAL 1 {unit})) AS spine_ts"
+            );
+        }
+        // GENERATE_TIMESTAMP_ARRAY stops at DAY steps, so step through dates
+        format!(
+            "SELECT TIMESTAMP(spine_date) AS {output} \
+             FROM (SELECT MIN({column}) AS lo, MAX({column}) AS hi FROM {source}) AS bounds, \
+             UNNEST(GENERATE_DATE_ARRAY(DATE(bounds.lo), DATE(bounds.hi), INTERVAL 1 {unit})) AS spine_date"
+        )
+    }
+
+    fn render_fiscal_trunc(
+        &self,
+        grain: &TimeGrain,
+        calendar: &FiscalCalendar,
+        expr: &str,
+    ) -> String {
+        render_fiscal_trunc(&BigQueryDates, grain, calendar, expr)
+    }
+
+    /// HyperLogLog++ sketches: distinct counts become estimates (about 1%
+    /// error at the default precision) once a query pre-aggregates.
+    fn render_distinct_sketch(&self, expr: &str) -> Option<String> {
+        Some(format!("HLL_COUNT.INIT({expr})"))
+    }
+
+    fn render_merge_sketches(&self, expr: &str) -> Option<String> {
+        Some(format!("HLL_COUNT.MERGE({expr})"))
+    }
+
+    fn render_symmetric_sum(&self, expr: &str, key: &str) -> Option<String> {
+        let offset =
+            format!("CAST(FARM_FINGERPRINT(CAST({key} AS STRING)) AS BIGNUMERIC) * 100000000");
+        Some(format!(
+            "CAST((SUM(DISTINCT {offset} + CAST(FLOOR(COALESCE({expr}, 0) * 1000000) AS BIGNUMERIC)) \
+             - SUM(DISTINCT {offset})) / 1000000 AS FLOAT64)"
+        ))
+    }
+
+    fn render_unnest(&self, expr: &str, alias: &str) -> String {
+        // The alias names the element itself
+        format!("UNNEST({expr}) AS {alias}")
+    }
+
+    fn render_at_time_zone(&self, expr: &str, zone: &str) -> String {
+        // TIMESTAMP_TRUNC takes a TIMESTAMP, so the local DATETIME goes back
+        // to one carrying the same wall-clock fields
+        let zone = self.render_literal(&serde_json::Value::String(zone.to_string()));
+        format!("TIMESTAMP(DATETIME({expr}, {zone}))")
+    }
+
+    fn render_bool(&self, value: bool) -> String {
+        // BigQuery style guide spells boolean literals in upper case
+        if value { "TRUE" } else { "FALSE" }.to_string()
+    }
+
+    fn render_relative_date(
+        &self,
+        truncate: Option<&TimeGrain>,
+        offsets: &[(TimeGrain, i64)],
+        as_timestamp: bool,
+    ) -> String {
+        let mut sql = match truncate {
+            // ISOWEEK starts on Monday, matching date_trunc('week') elsewhere
+            Some(TimeGrain::Week) => "DATE_TRUNC(CURRENT_DATE(), ISOWEEK)".to_string(),
+            Some(grain) => format!("DATE_TRUNC(CURRENT_DATE(), {})", bq_grain_to_str(grain)),
+            None => "CURRENT_DATE()".to_string(),
+        };
+        for (unit, amount) in offsets {
+            let (unit, amount) = normalize_interval(unit, *amount);
+            sql = format!(
+                "DATE_ADD({sql}, INTERVAL {amount} {})",
+                bq_grain_to_str(&unit)
+            );
+        }
+        // BigQuery refuses to compare DATE with TIMESTAMP implicitly
+        if as_timestamp {
+            format!("TIMESTAMP({sql})")
+        } else {
+            sql
+        }
     }
 
     fn render_aggregation(&self, agg: &Aggregation, expr: &str) -> String {
@@ -52,6 +186,16 @@ impl Dialect for BigQueryDialect {
             Aggregation::Median => {
                 format!("PERCENTILE_CONT({expr}, 0.5) OVER()")
             }
+            // PERCENTILE_CONT is analytic-only in BigQuery, so exact percentiles
+            // fall back to thousandth quantiles too
+            Aggregation::Percentile { p } | Aggregation::ApproxPercentile { p } => {
+                let offset = (p * 1000.0).round() as u32;
+                format!("APPROX_QUANTILES({expr}, 1000)[OFFSET({offset})]")
+            }
+            // BigQuery spells the boolean aggregates its own way
+            Aggregation::CountIf => format!("COUNTIF({expr})"),
+            Aggregation::BoolAnd => format!("LOGICAL_AND({expr})"),
+            Aggregation::BoolOr => format!("LOGICAL_OR({expr})"),
             // BigQuery STRING_AGG syntax
             Aggregation::StringAgg { separator } => {
                 let escaped = separator.replace('\'', "\\'");
@@ -59,10 +203,21 @@ impl Dialect for BigQueryDialect {
             }
             // BigQuery uses ARRAY_AGG
             Aggregation::ArrayAgg => format!("ARRAY_AGG({expr})"),
-            // BigQuery doesn't have FIRST/LAST natively - use ARRAY_AGG with OFFSET
-            Aggregation::First => format!("ARRAY_AGG({expr} IGNORE NULLS)[OFFSET(0)]"),
-            Aggregation::Last => format!(
-                "ARRAY_AGG({expr} IGNORE NULLS)[ORDINAL(ARRAY_LENGTH(ARRAY_AGG({expr} IGNORE NULLS)))]"
+            Aggregation::First {
+                order_by,
+                direction,
+            } => self.render_ordered_value(
+                expr,
+                &self.quote_column(order_by),
+                *direction == SortDirection::Desc,
+            ),
+            Aggregation::Last {
+                order_by,
+                direction,
+            } => self.render_ordered_value(
+                expr,
+                &self.quote_column(order_by),
+                *direction == SortDirection::Asc,
             ),
             // Standard aggregations
             Aggregation::Sum => format!("SUM({expr})"),
@@ -78,6 +233,12 @@ impl Dialect for BigQueryDialect {
         }
     }
 
+    fn render_ordered_value(&self, expr: &str, order_by: &str, descending: bool) -> String {
+        // BigQuery doesn't have FIRST/LAST natively - use ARRAY_AGG with LIMIT 1
+        let direction = if descending { "DESC" } else { "ASC" };
+        format!("ARRAY_AGG({expr} IGNORE NULLS ORDER BY {order_by} {direction} LIMIT 1)[SAFE_OFFSET(0)]")
+    }
+
     fn render_function(&self, func: &Function, args: Vec<String>) -> String {
         match func {
             // === Date/Time Functions (BigQuery-specific) ===
@@ -97,6 +258,10 @@ impl Dialect for BigQueryDialect {
             Function::DateAdd { unit } => {
                 let unit_str = bq_grain_to_str(unit);
                 match args.as_slice() {
+                    // DATE_ADD rejects sub-day parts, which need timestamps
+                    [amount, date] if unit < &TimeGrain::Day => {
+                        format!("TIMESTAMP_ADD({date}, INTERVAL {amount} {unit_str})")
+                    }
                     // BigQuery: DATE_ADD(date, INTERVAL n DAY)
                     [amount, date] => format!("DATE_ADD({date}, INTERVAL {amount} {unit_str})"),
                     _ => "NULL".to_string(),
@@ -105,6 +270,9 @@ impl Dialect for BigQueryDialect {
             Function::DateDiff { unit } => {
                 let unit_str = bq_grain_to_str(unit);
                 match args.as_slice() {
+                    [start, end] if unit < &TimeGrain::Day => {
+                        format!("TIMESTAMP_DIFF({end}, {start}, {unit_str})")
+                    }
                     // BigQuery: DATE_DIFF(end, start, DAY)
                     [start, end] => format!("DATE_DIFF({end}, {start}, {unit_str})"),
                     _ => "NULL".to_string(),
@@ -168,6 +336,34 @@ impl Dialect for BigQueryDialect {
                 [expr, substr] => format!("STRPOS({expr}, {substr}) > 0"),
                 _ => "NULL".to_string(),
             },
+            Function::RegexpMatches => match args.as_slice() {
+                [expr, pattern] => format!("REGEXP_CONTAINS({expr}, {pattern})"),
+                _ => "NULL".to_string(),
+            },
+            Function::RegexpExtract => match args.as_slice() {
+                [expr, pattern] => format!("REGEXP_EXTRACT({expr}, {pattern})"),
+                _ => "NULL".to_string(),
+            },
+            Function::RegexpReplace => match args.as_slice() {
+                [expr, pattern, replacement] => {
+                    format!("REGEXP_REPLACE({expr}, {pattern}, {replacement})")
+                }
+                _ => "NULL".to_string(),
+            },
+
+            // === Geo Functions (GEOGRAPHY) ===
+            Function::StPoint => match args.as_slice() {
+                [lon, lat] => format!("ST_GEOGPOINT({lon}, {lat})"),
+                _ => "NULL".to_string(),
+            },
+            Function::StDistance => match args.as_slice() {
+                [a, b] => format!("ST_DISTANCE({a}, {b})"),
+                _ => "NULL".to_string(),
+            },
+            Function::StWithin => match args.as_slice() {
+                [inner, outer] => format!("ST_WITHIN({inner}, {outer})"),
+                _ => "NULL".to_string(),
+            },
 
             // === Null Handling ===
             Function::Coalesce => format!("COALESCE({})", args.join(", ")),
@@ -218,13 +414,51 @@ impl Dialect for BigQueryDialect {
                 [expr] => format!("SAFE_CAST({expr} AS {data_type})"),
                 _ => "NULL".to_string(),
             },
+
+            // === Bucketing ===
+            Function::Bucket(spec) => match args.as_slice() {
+                [expr] => render_bucket(spec, expr),
+                _ => "NULL".to_string(),
+            },
         }
     }
 }
 
+/// DATE functions, as BigQuery has no DATE/INTERVAL operators.
+struct BigQueryDates;
+
+impl DateArithmetic for BigQueryDates {
+    fn trunc_shifted(&self, grain: &TimeGrain, date: &str, months: u32) -> String {
+        let unit = bq_grain_to_str(grain);
+        if months == 0 {
+            return format!("DATE_TRUNC({date}, {unit})");
+        }
+        format!(
+            "DATE_ADD(DATE_TRUNC(DATE_SUB({date}, INTERVAL {months} MONTH), {unit}), \
+             INTERVAL {months} MONTH)"
+        )
+    }
+
+    fn add_days(&self, date: &str, days: &str) -> String {
+        format!("DATE_ADD({date}, INTERVAL ({days}) DAY)")
+    }
+
+    fn days_between(&self, start: &str, end: &str) -> String {
+        format!("DATE_DIFF({end}, {start}, DAY)")
+    }
+}
+
 /// Convert TimeGrain to BigQuery date part string.
 fn bq_grain_to_str(grain: &TimeGrain) -> &'static str {
     match grain {
+        // The renderer maps fiscal units to their calendar counterparts
+        TimeGrain::FiscalWeek
+        | TimeGrain::FiscalMonth
+        | TimeGrain::FiscalQuarter
+        | TimeGrain::FiscalYear => bq_grain_to_str(&grain.calendar()),
+        TimeGrain::Second => "SECOND",
+        TimeGrain::Minute => "MINUTE",
+        TimeGrain::Hour => "HOUR",
         TimeGrain::Day => "DAY",
         TimeGrain::Week => "WEEK",
         TimeGrain::Month => "MONTH",
@@ -1,8 +1,8 @@
 //! DuckDB dialect implementation.
 
-use crate::flows::Function;
+use crate::flows::{Function, TimeGrain};
 
-use super::{grain_to_str, Dialect};
+use super::{grain_to_str, render_bucket, Dialect, DialectCapabilities, LimitSyntax};
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct DuckDbDialect;
@@ -12,8 +12,52 @@ impl Dialect for DuckDbDialect {
         format!("\"{}\"", ident.replace('"', "\"\""))
     }
 
-    fn supports_filtered_aggregates(&self) -> bool {
-        true
+    fn capabilities(&self) -> DialectCapabilities {
+        DialectCapabilities {
+            filtered_aggregates: true,
+            group_by_all: true,
+            window_functions: true,
+            ignore_nulls: true,
+            grouping_sets: true,
+            row_value_comparison: true,
+            distinct_sketches: true,
+            symmetric_aggregates: true,
+            parquet_export: true,
+            create_or_replace_table: true,
+            limit: LimitSyntax::LimitOffset,
+        }
+    }
+
+    fn render_distinct_sketch(&self, expr: &str) -> Option<String> {
+        Some(format!("LIST(DISTINCT {expr})"))
+    }
+
+    fn render_merge_sketches(&self, expr: &str) -> Option<String> {
+        // LIST_DISTINCT drops the NULL kept by LIST(DISTINCT ...)
+        Some(format!("LEN(LIST_DISTINCT(FLATTEN(LIST({expr}))))"))
+    }
+
+    fn render_symmetric_sum(&self, expr: &str, key: &str) -> Option<String> {
+        // All 64 bits of the UBIGINT hash; HUGEINT sums them without overflow
+        let offset = format!("CAST(HASH({key}) AS HUGEINT) * 100000000");
+        Some(format!(
+            "(CAST(SUM(DISTINCT {offset} + CAST(FLOOR(COALESCE({expr}, 0) * 1000000) AS HUGEINT)) \
+             - SUM(DISTINCT {offset}) AS DOUBLE) / 1000000)"
+        ))
+    }
+
+    fn render_date_spine(
+        &self,
+        source: &str,
+        column: &str,
+        grain: &TimeGrain,
+        output: &str,
+    ) -> String {
+        // generate_series returns a list in DuckDB; UNNEST turns it into rows
+        format!(
+            "SELECT UNNEST(generate_series(MIN({column}), MAX({column}), INTERVAL 1 {})) AS {output} FROM {source}",
+            grain_to_str(grain)
+        )
     }
 
     fn render_function(&self, func: &Function, args: Vec<String>) -> String {
@@ -99,6 +143,41 @@ impl Dialect for DuckDbDialect {
                 [expr, substr] => format!("contains({expr}, {substr})"),
                 _ => "NULL".to_string(),
             },
+            Function::RegexpMatches => match args.as_slice() {
+                [expr, pattern] => format!("regexp_matches({expr}, {pattern})"),
+                _ => "NULL".to_string(),
+            },
+            Function::RegexpExtract => match args.as_slice() {
+                // DuckDB returns the whole match unless asked for a group
+                [expr, pattern] if has_capture_group(pattern) => {
+                    format!("regexp_extract({expr}, {pattern}, 1)")
+                }
+                [expr, pattern] => format!("regexp_extract({expr}, {pattern})"),
+                _ => "NULL".to_string(),
+            },
+            Function::RegexpReplace => match args.as_slice() {
+                [expr, pattern, replacement] => {
+                    format!("regexp_replace({expr}, {pattern}, {replacement}, 'g')")
+                }
+                _ => "NULL".to_string(),
+            },
+
+            // === Geo Functions (spatial extension) ===
+            Function::StPoint => match args.as_slice() {
+                [lon, lat] => format!("ST_Point({lon}, {lat})"),
+                _ => "NULL".to_string(),
+            },
+            Function::StDistance => match args.as_slice() {
+                // ST_Distance_Sphere expects [latitude, longitude] points
+                [a, b] => {
+                    format!("ST_Distance_Sphere(ST_FlipCoordinates({a}), ST_FlipCoordinates({b}))")
+                }
+                _ => "NULL".to_string(),
+            },
+            Function::StWithin => match args.as_slice() {
+                [inner, outer] => format!("ST_Within({inner}, {outer})"),
+                _ => "NULL".to_string(),
+            },
 
             // === Null Handling ===
             Function::Coalesce => format!("coalesce({})", args.join(", ")),
@@ -147,6 +226,31 @@ impl Dialect for DuckDbDialect {
                 [expr] => format!("TRY_CAST({expr} AS {data_type})"),
                 _ => "NULL".to_string(),
             },
+
+            // === Bucketing ===
+            Function::Bucket(spec) => match args.as_slice() {
+                [expr] => render_bucket(spec, expr),
+                _ => "NULL".to_string(),
+            },
+        }
+    }
+}
+
+/// Whether a rendered pattern has a capturing group: an unescaped `(` outside
+/// a character class and not starting a `(?...)` group.
+fn has_capture_group(pattern: &str) -> bool {
+    let mut chars = pattern.chars().peekable();
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => in_class = true,
+            ']' => in_class = false,
+            '(' if !in_class && chars.peek() != Some(&'?') => return true,
+            _ => {}
         }
     }
+    false
 }
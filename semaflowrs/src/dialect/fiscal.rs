@@ -0,0 +1,99 @@
+//! Truncation to the `fiscal_*` grains of a [`FiscalCalendar`].
+//!
+//! Fiscal periods have no native `DATE_TRUNC` unit, so they are rendered as
+//! date arithmetic from the start of the fiscal year: calendar truncation of
+//! the date moved back by the start month's offset for month-based periods,
+//! and a `CASE` over day offsets for week-based (4-4-5 style) ones. Each
+//! dialect supplies the few date operations it spells differently.
+
+use crate::flows::{FiscalCalendar, TimeGrain};
+
+/// Date operations fiscal truncation is built from. Arguments and results
+/// are rendered SQL over DATE values.
+pub(crate) trait DateArithmetic {
+    /// `date` moved back `months`, truncated to the calendar `grain`, then
+    /// moved forward `months` again.
+    fn trunc_shifted(&self, grain: &TimeGrain, date: &str, months: u32) -> String;
+    /// `date` plus `days`, an integer expression.
+    fn add_days(&self, date: &str, days: &str) -> String;
+    /// Whole days from `start` to `end`.
+    fn days_between(&self, start: &str, end: &str) -> String;
+}
+
+/// Interval arithmetic accepted by DuckDB and PostgreSQL.
+pub(crate) struct StandardDates;
+
+impl DateArithmetic for StandardDates {
+    fn trunc_shifted(&self, grain: &TimeGrain, date: &str, months: u32) -> String {
+        let unit = super::grain_to_str(grain);
+        if months == 0 {
+            return format!("CAST(date_trunc('{unit}', {date}) AS DATE)");
+        }
+        format!(
+            "CAST(date_trunc('{unit}', {date} - INTERVAL '{months} month') \
+             + INTERVAL '{months} month' AS DATE)"
+        )
+    }
+
+    fn add_days(&self, date: &str, days: &str) -> String {
+        format!("({date} + ({days}))")
+    }
+
+    fn days_between(&self, start: &str, end: &str) -> String {
+        format!("({end} - {start})")
+    }
+}
+
+/// Render `expr` truncated to the start of its fiscal `grain` period, as a DATE.
+pub(crate) fn render_fiscal_trunc(
+    dates: &dyn DateArithmetic,
+    grain: &TimeGrain,
+    calendar: &FiscalCalendar,
+    expr: &str,
+) -> String {
+    let date = format!("CAST({expr} AS DATE)");
+    let shift = calendar.start_month.clamp(1, 12) - 1;
+    let year_start = dates.trunc_shifted(&TimeGrain::Year, &date, shift);
+    let day_of_year = dates.days_between(&year_start, &date);
+    match (grain, calendar.week_pattern) {
+        (TimeGrain::FiscalYear, _) => year_start,
+        (TimeGrain::FiscalWeek, _) => dates.add_days(
+            &year_start,
+            &format!("{day_of_year} - MOD({day_of_year}, 7)"),
+        ),
+        (TimeGrain::FiscalQuarter, None) => dates.trunc_shifted(&TimeGrain::Quarter, &date, shift),
+        (TimeGrain::FiscalMonth, None) => dates.trunc_shifted(&TimeGrain::Month, &date, 0),
+        (TimeGrain::FiscalQuarter, Some(_)) => {
+            period_start(dates, &year_start, &day_of_year, &[0, 91, 182, 273])
+        }
+        (TimeGrain::FiscalMonth, Some(pattern)) => {
+            let [first, second, _] = pattern.weeks();
+            let offsets: Vec<u32> = (0..4)
+                .flat_map(|quarter| {
+                    let start = quarter * 91;
+                    [start, start + first * 7, start + (first + second) * 7]
+                })
+                .collect();
+            period_start(dates, &year_start, &day_of_year, &offsets)
+        }
+        (other, _) => dates.trunc_shifted(&other.calendar(), &date, 0),
+    }
+}
+
+/// The start of the last period, given as ascending day offsets from the
+/// fiscal year start, that `day_of_year` has reached. The last period runs
+/// to the end of the year.
+fn period_start(
+    dates: &dyn DateArithmetic,
+    year_start: &str,
+    day_of_year: &str,
+    offsets: &[u32],
+) -> String {
+    let branches: String = offsets
+        .iter()
+        .rev()
+        .filter(|offset| **offset > 0)
+        .map(|offset| format!(" WHEN {day_of_year} >= {offset} THEN {offset}"))
+        .collect();
+    dates.add_days(year_start, &format!("CASE{branches} ELSE 0 END"))
+}
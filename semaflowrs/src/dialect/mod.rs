@@ -2,9 +2,88 @@
 //!
 //! Each dialect is implemented in its own file and gated behind a feature flag.
 
-#[cfg(any(feature = "duckdb", feature = "postgres"))]
-use crate::flows::TimeGrain;
-use crate::flows::{Aggregation, Function};
+#[cfg(any(feature = "duckdb", feature = "bigquery"))]
+use crate::flows::BucketSpec;
+use crate::flows::{Aggregation, FiscalCalendar, Function, SortDirection, TimeGrain};
+
+mod fiscal;
+
+/// SQL features a dialect accepts beyond a portable core. The default
+/// describes a dialect with none of them, which the query builder supports by
+/// falling back to portable SQL or in-memory processing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DialectCapabilities {
+    /// `AGG(x) FILTER (WHERE ...)`; without it measure filters are rendered
+    /// as `CASE WHEN` inside the aggregate.
+    pub filtered_aggregates: bool,
+    /// `GROUP BY ALL` (group by every non-aggregate select item).
+    pub group_by_all: bool,
+    /// Window functions (`RANK() OVER`, `LAG() OVER`). Without them table
+    /// calculations are computed in memory.
+    pub window_functions: bool,
+    /// `LAST_VALUE(x IGNORE NULLS)` in window functions.
+    pub ignore_nulls: bool,
+    /// `GROUP BY ROLLUP(...)` / `GROUPING SETS (...)` and `GROUPING()`.
+    /// Without them totals are emulated with `UNION ALL`.
+    pub grouping_sets: bool,
+    /// Row-value comparisons like `(a, b) > (1, 'x')`, so paginated queries
+    /// can seek past the previous page (see [`crate::pagination::Keyset`]).
+    /// Without them pages use OFFSET.
+    pub row_value_comparison: bool,
+    /// Distinct counts pre-aggregated as mergeable sketches
+    /// ([`Dialect::render_distinct_sketch`], which dialects with this
+    /// capability implement), keeping COUNT DISTINCT correct in multi-grain
+    /// queries. Without them it is computed in the final query.
+    pub distinct_sketches: bool,
+    /// Sums over distinct keys ([`Dialect::render_symmetric_sum`], which
+    /// dialects with this capability implement), so flows with
+    /// `fanout_strategy: symmetric` stay flat. Without them those flows are
+    /// pre-aggregated.
+    pub symmetric_aggregates: bool,
+    /// `COPY (query) TO 'file' (FORMAT PARQUET)` writing a local Parquet file.
+    pub parquet_export: bool,
+    /// `CREATE OR REPLACE TABLE ... AS`.
+    pub create_or_replace_table: bool,
+    /// How row limits and offsets are written.
+    pub limit: LimitSyntax,
+}
+
+/// How a dialect limits the rows of a query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LimitSyntax {
+    /// `LIMIT n OFFSET m`.
+    #[default]
+    LimitOffset,
+    /// `OFFSET m ROWS FETCH FIRST n ROWS ONLY`, the SQL standard form.
+    FetchFirst,
+}
+
+impl LimitSyntax {
+    /// The clause limiting a query to `limit` rows after skipping `offset`,
+    /// with a leading space; empty when neither is set.
+    pub fn render(self, limit: Option<u64>, offset: Option<u64>) -> String {
+        let mut sql = String::new();
+        match self {
+            LimitSyntax::LimitOffset => {
+                if let Some(limit) = limit {
+                    sql.push_str(&format!(" LIMIT {limit}"));
+                }
+                if let Some(offset) = offset {
+                    sql.push_str(&format!(" OFFSET {offset}"));
+                }
+            }
+            LimitSyntax::FetchFirst => {
+                if let Some(offset) = offset {
+                    sql.push_str(&format!(" OFFSET {offset} ROWS"));
+                }
+                if let Some(limit) = limit {
+                    sql.push_str(&format!(" FETCH FIRST {limit} ROWS ONLY"));
+                }
+            }
+        }
+        sql
+    }
+}
 
 /// Dialects render identifiers and primitive expression pieces.
 /// Expression tree walking lives in the query builder; the dialect
@@ -17,9 +96,24 @@ pub trait Dialect {
     fn placeholder(&self, _idx: usize) -> String {
         "?".to_string()
     }
-    fn supports_filtered_aggregates(&self) -> bool {
+    /// The SQL features this dialect accepts. The planner and renderer
+    /// choose constructs from it rather than checking for a backend.
+    fn capabilities(&self) -> DialectCapabilities {
+        DialectCapabilities::default()
+    }
+    /// Whether `DATE_TRUNC` to a week starts on Sunday rather than Monday.
+    fn weeks_start_sunday(&self) -> bool {
         false
     }
+    /// Whether a backslash escapes the next character in string literals and
+    /// quoted identifiers, as opposed to only doubled quotes.
+    fn backslash_escapes(&self) -> bool {
+        false
+    }
+    /// Render the `GROUPING()` bitmask over already rendered grouping expressions.
+    fn render_grouping(&self, exprs: &[String]) -> String {
+        format!("GROUPING({})", exprs.join(", "))
+    }
     fn render_function(&self, func: &Function, args: Vec<String>) -> String;
     fn render_aggregation(&self, agg: &Aggregation, expr: &str) -> String {
         match agg {
@@ -30,12 +124,17 @@ pub trait Dialect {
             Aggregation::Min => format!("MIN({expr})"),
             Aggregation::Max => format!("MAX({expr})"),
             Aggregation::Avg => format!("AVG({expr})"),
+            // Boolean aggregations
+            Aggregation::CountIf => format!("COUNT_IF({expr})"),
+            Aggregation::BoolAnd => format!("BOOL_AND({expr})"),
+            Aggregation::BoolOr => format!("BOOL_OR({expr})"),
             // Statistical aggregations
             Aggregation::Median => format!("MEDIAN({expr})"),
             Aggregation::Stddev => format!("STDDEV_POP({expr})"),
             Aggregation::StddevSamp => format!("STDDEV_SAMP({expr})"),
             Aggregation::Variance => format!("VAR_POP({expr})"),
             Aggregation::VarianceSamp => format!("VAR_SAMP({expr})"),
+            Aggregation::Percentile { p } => format!("QUANTILE_CONT({expr}, {p})"),
             // List/String aggregations
             Aggregation::StringAgg { separator } => {
                 let escaped = separator.replace('\'', "''");
@@ -44,15 +143,135 @@ pub trait Dialect {
             Aggregation::ArrayAgg => format!("ARRAY_AGG({expr})"),
             // Approximate aggregations
             Aggregation::ApproxCountDistinct => format!("APPROX_COUNT_DISTINCT({expr})"),
+            Aggregation::ApproxPercentile { p } => format!("APPROX_QUANTILE({expr}, {p})"),
             // First/Last
-            Aggregation::First => format!("FIRST({expr})"),
-            Aggregation::Last => format!("LAST({expr})"),
+            Aggregation::First {
+                order_by,
+                direction,
+            } => self.render_ordered_value(
+                expr,
+                &self.quote_column(order_by),
+                *direction == SortDirection::Desc,
+            ),
+            Aggregation::Last {
+                order_by,
+                direction,
+            } => self.render_ordered_value(
+                expr,
+                &self.quote_column(order_by),
+                *direction == SortDirection::Asc,
+            ),
+        }
+    }
+    /// Render `expr` on the row with the smallest `order_by`, or the largest
+    /// when `descending`, skipping rows where `expr` is NULL. `order_by` is
+    /// already quoted.
+    fn render_ordered_value(&self, expr: &str, order_by: &str, descending: bool) -> String {
+        let func = if descending { "ARG_MAX" } else { "ARG_MIN" };
+        format!("{func}({expr}, {order_by})")
+    }
+    /// Quote a column name, `table.`-qualified or not.
+    fn quote_column(&self, column: &str) -> String {
+        column
+            .split('.')
+            .map(|part| self.quote_ident(part))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+    fn render_bool(&self, value: bool) -> String {
+        value.to_string()
+    }
+    /// Tri-state boolean test. `IS NULL` rather than `IS UNKNOWN` since not
+    /// every backend accepts the latter.
+    fn render_boolean_test(&self, expr: &str, value: Option<bool>) -> String {
+        match value {
+            Some(true) => format!("({expr} IS TRUE)"),
+            Some(false) => format!("({expr} IS FALSE)"),
+            None => format!("({expr} IS NULL)"),
+        }
+    }
+    /// Render `current_date`, truncated to `truncate` and shifted by `offsets`.
+    /// The default suits DuckDB and PostgreSQL, which compare DATE against
+    /// TIMESTAMP columns implicitly.
+    fn render_relative_date(
+        &self,
+        truncate: Option<&TimeGrain>,
+        offsets: &[(TimeGrain, i64)],
+        _as_timestamp: bool,
+    ) -> String {
+        let mut sql = match truncate {
+            Some(grain) => format!("date_trunc('{}', current_date)", grain_to_str(grain)),
+            None => "current_date".to_string(),
+        };
+        for (unit, amount) in offsets {
+            let (unit, amount) = normalize_interval(unit, *amount);
+            sql = format!("{sql} + INTERVAL '{amount} {}'", grain_to_str(&unit));
         }
+        format!("CAST({sql} AS DATE)")
+    }
+    /// Render `expr` truncated to the start of its `fiscal_*` grain period in
+    /// `calendar`, as a DATE. The default suits DuckDB and PostgreSQL.
+    fn render_fiscal_trunc(
+        &self,
+        grain: &TimeGrain,
+        calendar: &FiscalCalendar,
+        expr: &str,
+    ) -> String {
+        fiscal::render_fiscal_trunc(&fiscal::StandardDates, grain, calendar, expr)
+    }
+    /// Render the UTC instant `expr` as wall-clock time in the IANA `zone`,
+    /// ready for `DATE_TRUNC`.
+    fn render_at_time_zone(&self, expr: &str, zone: &str) -> String {
+        let zone = self.render_literal(&serde_json::Value::String(zone.to_string()));
+        format!("({expr} AT TIME ZONE {zone})")
+    }
+    /// Aggregate `expr` into a sketch of its distinct non-NULL values that
+    /// [`Self::render_merge_sketches`] can combine across rows; `None`
+    /// without [`DialectCapabilities::distinct_sketches`].
+    fn render_distinct_sketch(&self, _expr: &str) -> Option<String> {
+        None
+    }
+    /// The distinct count of the union of the sketches in `expr`; `None`
+    /// without [`DialectCapabilities::distinct_sketches`].
+    fn render_merge_sketches(&self, _expr: &str) -> Option<String> {
+        None
+    }
+    /// `SUM({expr})` over the rows with distinct `key`s: each row's value,
+    /// scaled to six decimal places, is offset by a large multiple of a
+    /// hash of its key, so repeated rows collapse under `SUM(DISTINCT ...)`
+    /// and the offsets are subtracted again. NULLs count as zero. `None`
+    /// without [`DialectCapabilities::symmetric_aggregates`].
+    ///
+    /// Keys whose hashes collide drop one offset from the second sum, so the
+    /// hash needs at least 60 bits and a type that sums them without overflow.
+    fn render_symmetric_sum(&self, _expr: &str, _key: &str) -> Option<String> {
+        None
+    }
+    /// A relation with one row per element of the array `expr`, whose
+    /// single column is named like the relation, `alias` (already quoted).
+    fn render_unnest(&self, expr: &str, alias: &str) -> String {
+        format!("UNNEST({expr}) AS {alias}({alias})")
+    }
+    /// Render a query returning one row per `grain` step, aliased `output`,
+    /// from the smallest to the largest value of `column` in `source`.
+    /// `source`, `column` and `output` are already quoted.
+    fn render_date_spine(
+        &self,
+        source: &str,
+        column: &str,
+        grain: &TimeGrain,
+        output: &str,
+    ) -> String {
+        let (unit, amount) = normalize_interval(grain, 1);
+        format!(
+            "SELECT generate_series(MIN({column}), MAX({column}), INTERVAL '{amount} {}') AS {output} FROM {source}",
+            grain_to_str(&unit)
+        )
     }
     fn render_literal(&self, value: &serde_json::Value) -> String {
         match value {
             serde_json::Value::Null => "NULL".to_string(),
-            serde_json::Value::Bool(b) => b.to_string(),
+            serde_json::Value::Bool(b) => self.render_bool(*b),
             serde_json::Value::Number(n) => n.to_string(),
             serde_json::Value::String(s) => format!("'{}'", s.replace('\'', "''")),
             serde_json::Value::Array(items) => {
@@ -66,15 +285,88 @@ pub trait Dialect {
     }
 }
 
+/// Render a numeric bucket portably.
+///
+/// Width buckets yield the lower bound of the range; edge buckets yield a
+/// string label via a CASE ladder. NULL inputs stay NULL.
+#[cfg(any(feature = "duckdb", feature = "bigquery"))]
+pub(crate) fn render_bucket(spec: &BucketSpec, expr: &str) -> String {
+    match spec {
+        BucketSpec::Width(width) => {
+            let w = format_bucket_edge(*width);
+            format!("floor({expr} / {w}) * {w}")
+        }
+        BucketSpec::Edges(edges) => {
+            let labels = bucket_labels(edges);
+            let mut sql = String::from("CASE");
+            for (edge, label) in edges.iter().zip(&labels) {
+                let edge = format_bucket_edge(*edge);
+                sql.push_str(&format!(" WHEN {expr} < {edge} THEN '{label}'"));
+            }
+            if let (Some(last), Some(label)) = (edges.last(), labels.last()) {
+                let last = format_bucket_edge(*last);
+                sql.push_str(&format!(" WHEN {expr} >= {last} THEN '{label}'"));
+            }
+            sql.push_str(" ELSE NULL END");
+            sql
+        }
+    }
+}
+
+/// Labels for edge buckets, in order: `< first`, `[a-b)` for each pair of
+/// edges, then `>= last`. One more label than there are edges.
+#[cfg(any(feature = "duckdb", feature = "postgres", feature = "bigquery"))]
+pub(crate) fn bucket_labels(edges: &[f64]) -> Vec<String> {
+    let Some((first, last)) = edges.first().zip(edges.last()) else {
+        return Vec::new();
+    };
+    let mut labels = vec![format!("< {}", format_bucket_edge(*first))];
+    labels.extend(edges.windows(2).map(|w| {
+        format!(
+            "[{}-{})",
+            format_bucket_edge(w[0]),
+            format_bucket_edge(w[1])
+        )
+    }));
+    labels.push(format!(">= {}", format_bucket_edge(*last)));
+    labels
+}
+
+/// Format a bucket boundary without a trailing `.0` for whole numbers.
+#[cfg(any(feature = "duckdb", feature = "postgres", feature = "bigquery"))]
+pub(crate) fn format_bucket_edge(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Express week and quarter offsets as days and months, which every
+/// backend's interval syntax accepts.
+pub(crate) fn normalize_interval(unit: &TimeGrain, amount: i64) -> (TimeGrain, i64) {
+    match unit {
+        TimeGrain::Week => (TimeGrain::Day, amount * 7),
+        TimeGrain::Quarter => (TimeGrain::Month, amount * 3),
+        other => (other.clone(), amount),
+    }
+}
+
 /// Convert TimeGrain to SQL interval string (shared by DuckDB and PostgreSQL).
-#[cfg(any(feature = "duckdb", feature = "postgres"))]
 pub(crate) fn grain_to_str(grain: &TimeGrain) -> &'static str {
     match grain {
+        TimeGrain::Second => "second",
+        TimeGrain::Minute => "minute",
+        TimeGrain::Hour => "hour",
         TimeGrain::Day => "day",
         TimeGrain::Week => "week",
+        TimeGrain::FiscalWeek => "fiscal_week",
         TimeGrain::Month => "month",
+        TimeGrain::FiscalMonth => "fiscal_month",
         TimeGrain::Quarter => "quarter",
+        TimeGrain::FiscalQuarter => "fiscal_quarter",
         TimeGrain::Year => "year",
+        TimeGrain::FiscalYear => "fiscal_year",
     }
 }
 
@@ -1,8 +1,10 @@
 //! PostgreSQL dialect implementation.
 
-use crate::flows::{Aggregation, Function, TimeGrain};
+use crate::flows::{Aggregation, BucketSpec, Function, SortDirection, TimeGrain};
 
-use super::{grain_to_str, Dialect};
+use super::{
+    bucket_labels, format_bucket_edge, grain_to_str, Dialect, DialectCapabilities, LimitSyntax,
+};
 
 #[derive(Debug, Clone)]
 pub struct PostgresDialect {
@@ -34,18 +36,42 @@ impl Dialect for PostgresDialect {
         format!("${}", idx + 1) // PostgreSQL uses $1, $2, ...
     }
 
-    fn supports_filtered_aggregates(&self) -> bool {
-        true // PostgreSQL 9.4+ supports FILTER
+    fn capabilities(&self) -> DialectCapabilities {
+        DialectCapabilities {
+            // PostgreSQL 9.4+ supports FILTER
+            filtered_aggregates: true,
+            group_by_all: false,
+            window_functions: true,
+            ignore_nulls: false,
+            // PostgreSQL 9.5+
+            grouping_sets: true,
+            row_value_comparison: true,
+            distinct_sketches: false,
+            symmetric_aggregates: true,
+            parquet_export: false,
+            create_or_replace_table: false,
+            limit: LimitSyntax::LimitOffset,
+        }
     }
 
     fn render_aggregation(&self, agg: &Aggregation, expr: &str) -> String {
         match agg {
-            // PostgreSQL uses FIRST_VALUE/LAST_VALUE with window functions,
-            // but for simple aggregates we can use (array_agg(x))[1]
-            Aggregation::First => format!("(array_agg({expr}))[1]"),
-            Aggregation::Last => {
-                format!("(array_agg({expr}))[array_length(array_agg({expr}), 1)]")
-            }
+            Aggregation::First {
+                order_by,
+                direction,
+            } => self.render_ordered_value(
+                expr,
+                &self.quote_column(order_by),
+                *direction == SortDirection::Desc,
+            ),
+            Aggregation::Last {
+                order_by,
+                direction,
+            } => self.render_ordered_value(
+                expr,
+                &self.quote_column(order_by),
+                *direction == SortDirection::Asc,
+            ),
             // All others are standard SQL
             _ => {
                 // Delegate to default implementation for standard aggregations
@@ -58,6 +84,9 @@ impl Dialect for PostgresDialect {
                     Aggregation::Min => format!("MIN({expr})"),
                     Aggregation::Max => format!("MAX({expr})"),
                     Aggregation::Avg => format!("AVG({expr})"),
+                    Aggregation::CountIf => format!("COUNT(CASE WHEN {expr} THEN 1 END)"),
+                    Aggregation::BoolAnd => format!("BOOL_AND({expr})"),
+                    Aggregation::BoolOr => format!("BOOL_OR({expr})"),
                     Aggregation::Median => {
                         format!("PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY {expr})")
                     }
@@ -65,18 +94,40 @@ impl Dialect for PostgresDialect {
                     Aggregation::StddevSamp => format!("STDDEV_SAMP({expr})"),
                     Aggregation::Variance => format!("VAR_POP({expr})"),
                     Aggregation::VarianceSamp => format!("VAR_SAMP({expr})"),
+                    // No approximate percentile in PG
+                    Aggregation::Percentile { p } | Aggregation::ApproxPercentile { p } => {
+                        format!("PERCENTILE_CONT({p}) WITHIN GROUP (ORDER BY {expr})")
+                    }
                     Aggregation::StringAgg { separator } => {
                         let escaped = separator.replace('\'', "''");
                         format!("STRING_AGG({expr}, '{escaped}')")
                     }
                     Aggregation::ArrayAgg => format!("ARRAY_AGG({expr})"),
                     Aggregation::ApproxCountDistinct => format!("COUNT(DISTINCT {expr})"), // No native approx in PG
-                    Aggregation::First | Aggregation::Last => unreachable!(),
+                    Aggregation::First { .. } | Aggregation::Last { .. } => unreachable!(),
                 }
             }
         }
     }
 
+    fn render_ordered_value(&self, expr: &str, order_by: &str, descending: bool) -> String {
+        // No ARG_MIN/ARG_MAX in PG: take the first non-NULL element of an
+        // ordered array, as ARG_MIN/ARG_MAX and IGNORE NULLS do elsewhere
+        let direction = if descending { "DESC" } else { "ASC" };
+        format!("(ARRAY_REMOVE(ARRAY_AGG({expr} ORDER BY {order_by} {direction}), NULL))[1]")
+    }
+
+    fn render_symmetric_sum(&self, expr: &str, key: &str) -> Option<String> {
+        // 60 bits of the key's MD5; NUMERIC sums don't overflow
+        let offset = format!(
+            "('x' || LEFT(MD5(CAST({key} AS TEXT)), 15))::BIT(60)::BIGINT::NUMERIC * 100000000"
+        );
+        Some(format!(
+            "((SUM(DISTINCT {offset} + FLOOR(COALESCE({expr}, 0) * 1000000)::NUMERIC) \
+             - SUM(DISTINCT {offset})) / 1000000)::FLOAT8"
+        ))
+    }
+
     fn render_function(&self, func: &Function, args: Vec<String>) -> String {
         match func {
             // === Date/Time Functions ===
@@ -101,9 +152,20 @@ impl Dialect for PostgresDialect {
             }
             Function::DateDiff { unit } => {
                 let unit_str = grain_to_str(unit);
-                match args.as_slice() {
+                // date_part on an interval reads one component, so whole
+                // hours/minutes/seconds come from the elapsed epoch seconds
+                let seconds = match unit {
+                    TimeGrain::Second => Some(1),
+                    TimeGrain::Minute => Some(60),
+                    TimeGrain::Hour => Some(3600),
+                    _ => None,
+                };
+                match (args.as_slice(), seconds) {
+                    ([start, end], Some(seconds)) => {
+                        format!("floor(extract(epoch FROM {end} - {start}) / {seconds})")
+                    }
                     // PostgreSQL: date_part('unit', end - start)
-                    [start, end] => format!("date_part('{unit_str}', {end} - {start})"),
+                    ([start, end], None) => format!("date_part('{unit_str}', {end} - {start})"),
                     _ => "NULL".to_string(),
                 }
             }
@@ -164,6 +226,37 @@ impl Dialect for PostgresDialect {
                 [expr, substr] => format!("position({substr} IN {expr}) > 0"),
                 _ => "NULL".to_string(),
             },
+            Function::RegexpMatches => match args.as_slice() {
+                [expr, pattern] => format!("({expr} ~ {pattern})"),
+                _ => "NULL".to_string(),
+            },
+            Function::RegexpExtract => match args.as_slice() {
+                // Yields the first parenthesized group when there is one
+                [expr, pattern] => format!("substring({expr} FROM {pattern})"),
+                _ => "NULL".to_string(),
+            },
+            Function::RegexpReplace => match args.as_slice() {
+                [expr, pattern, replacement] => {
+                    format!("regexp_replace({expr}, {pattern}, {replacement}, 'g')")
+                }
+                _ => "NULL".to_string(),
+            },
+
+            // === Geo Functions (PostGIS) ===
+            Function::StPoint => match args.as_slice() {
+                [lon, lat] => format!("ST_SetSRID(ST_MakePoint({lon}, {lat}), 4326)::geography"),
+                _ => "NULL".to_string(),
+            },
+            Function::StDistance => match args.as_slice() {
+                // Geography distances are in meters
+                [a, b] => format!("ST_Distance(({a})::geography, ({b})::geography)"),
+                _ => "NULL".to_string(),
+            },
+            Function::StWithin => match args.as_slice() {
+                // ST_Within is only defined for geometries
+                [inner, outer] => format!("ST_Within(({inner})::geometry, ({outer})::geometry)"),
+                _ => "NULL".to_string(),
+            },
 
             // === Null Handling ===
             Function::Coalesce => format!("coalesce({})", args.join(", ")),
@@ -217,6 +310,34 @@ impl Dialect for PostgresDialect {
                 [expr] => format!("CAST({expr} AS {data_type})"),
                 _ => "NULL".to_string(),
             },
+
+            // === Bucketing ===
+            // Cast first: integer division truncates toward zero in PostgreSQL.
+            Function::Bucket(BucketSpec::Width(width)) => match args.as_slice() {
+                [expr] => {
+                    let w = format_bucket_edge(*width);
+                    format!("floor(CAST({expr} AS double precision) / {w}) * {w}")
+                }
+                _ => "NULL".to_string(),
+            },
+            // width_bucket returns 0 below the first edge and len(edges) at or
+            // above the last, so it indexes straight into the label array.
+            Function::Bucket(BucketSpec::Edges(edges)) => match args.as_slice() {
+                [expr] => {
+                    let labels: Vec<String> = bucket_labels(edges)
+                        .iter()
+                        .map(|l| format!("'{l}'"))
+                        .collect();
+                    let bounds: Vec<String> =
+                        edges.iter().map(|e| format_bucket_edge(*e)).collect();
+                    format!(
+                        "(ARRAY[{}])[width_bucket(CAST({expr} AS double precision), ARRAY[{}]::double precision[]) + 1]",
+                        labels.join(", "),
+                        bounds.join(", ")
+                    )
+                }
+                _ => "NULL".to_string(),
+            },
         }
     }
 }
@@ -224,6 +345,14 @@ impl Dialect for PostgresDialect {
 /// Convert TimeGrain to PostgreSQL interval unit string.
 fn pg_interval_unit(grain: &TimeGrain) -> &'static str {
     match grain {
+        // The renderer maps fiscal units to their calendar counterparts
+        TimeGrain::FiscalWeek
+        | TimeGrain::FiscalMonth
+        | TimeGrain::FiscalQuarter
+        | TimeGrain::FiscalYear => pg_interval_unit(&grain.calendar()),
+        TimeGrain::Second => "second",
+        TimeGrain::Minute => "minute",
+        TimeGrain::Hour => "hour",
         TimeGrain::Day => "day",
         TimeGrain::Week => "week",
         TimeGrain::Month => "month",
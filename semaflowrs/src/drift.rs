@@ -0,0 +1,239 @@
+//! Schema drift: differences between the columns a model reads and the
+//! warehouse tables it reads them from.
+//!
+//! [`crate::validation::Validator::check_drift`] fetches the live schema of
+//! every physical table and compares it with the model and with the schema
+//! last seen for the table: columns the model reads that disappeared or
+//! changed type break queries, while new columns no field reads yet are
+//! reported so they can be modeled. The report serializes to JSON for
+//! alerting.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::expr_utils::collect_column_refs;
+use crate::flows::SemanticTable;
+use crate::lineage::{Lineage, LineageNodeKind};
+use crate::schema_cache::TableSchema;
+
+/// What changed about a table or column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    /// The table's schema could not be fetched, e.g. it was dropped.
+    TableUnavailable,
+    /// A column the model reads no longer exists.
+    ColumnMissing,
+    /// A column the model reads has a different type than when last seen.
+    TypeChanged,
+    /// A column appeared since the table was last seen and no field reads it.
+    NewColumn,
+}
+
+impl DriftKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DriftKind::TableUnavailable => "table_unavailable",
+            DriftKind::ColumnMissing => "column_missing",
+            DriftKind::TypeChanged => "type_changed",
+            DriftKind::NewColumn => "new_column",
+        }
+    }
+
+    /// Whether queries reading the table may fail or change meaning.
+    pub fn is_breaking(self) -> bool {
+        !matches!(self, DriftKind::NewColumn)
+    }
+}
+
+/// One difference between the model and a warehouse table.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaDrift {
+    pub kind: DriftKind,
+    /// Semantic table reading the physical table.
+    pub table: String,
+    pub data_source: String,
+    pub physical_table: String,
+    /// `None` when the whole table is affected.
+    pub column: Option<String>,
+    pub previous_type: Option<String>,
+    pub current_type: Option<String>,
+    /// Lineage ids of the fields and flows reading the column, and the
+    /// keys, filters and policies of the table naming it.
+    pub used_by: Vec<String>,
+    pub message: String,
+}
+
+/// Every drift found, breaking ones first, then by table and column.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DriftReport {
+    pub drifts: Vec<SchemaDrift>,
+}
+
+impl DriftReport {
+    pub fn is_empty(&self) -> bool {
+        self.drifts.is_empty()
+    }
+
+    /// Whether any drift may break queries.
+    pub fn has_breaking(&self) -> bool {
+        self.drifts.iter().any(|drift| drift.kind.is_breaking())
+    }
+
+    pub(crate) fn sort(&mut self) {
+        self.drifts.sort_by(|a, b| {
+            (!a.kind.is_breaking(), &a.table, &a.column, a.kind).cmp(&(
+                !b.kind.is_breaking(),
+                &b.table,
+                &b.column,
+                b.kind,
+            ))
+        });
+    }
+}
+
+/// The columns of `table` read by the model, with what reads each.
+pub(crate) fn model_columns(
+    table: &SemanticTable,
+    lineage: &Lineage,
+) -> BTreeMap<String, Vec<String>> {
+    let mut used: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let column_prefix = Lineage::column_id(&table.data_source, &table.table, "");
+    for node in &lineage.nodes {
+        if node.kind != LineageNodeKind::Column || !node.id.starts_with(&column_prefix) {
+            continue;
+        }
+        // Only fields of this semantic table and the flows joining on it
+        let readers = lineage.downstream(&node.id).into_iter().filter(|reader| {
+            reader.kind == LineageNodeKind::Flow || reader.table.as_deref() == Some(&table.name)
+        });
+        let entry = used.entry(node.name.clone()).or_default();
+        entry.extend(readers.map(|reader| reader.id.clone()));
+    }
+    let mut add = |column: &str, reader: String| {
+        used.entry(column.to_string()).or_default().insert(reader);
+    };
+    for column in &table.primary_keys {
+        add(column, format!("primary_key:{}", table.name));
+    }
+    for column in table.unique_keys.iter().flatten() {
+        add(column, format!("unique_key:{}", table.name));
+    }
+    for (name, entity) in &table.entities {
+        add(entity.column(name), format!("entity:{}.{name}", table.name));
+    }
+    for filter in &table.default_filters {
+        let mut columns = Vec::new();
+        collect_column_refs(filter, &mut columns);
+        for column in columns {
+            add(&column, format!("default_filter:{}", table.name));
+        }
+    }
+    for (attribute, expr) in &table.access_policies {
+        let mut columns = Vec::new();
+        collect_column_refs(expr, &mut columns);
+        for column in columns {
+            add(&column, format!("access_policy:{}.{attribute}", table.name));
+        }
+    }
+    used.into_iter()
+        .filter(|(_, readers)| !readers.is_empty())
+        .map(|(column, readers)| (column, readers.into_iter().collect()))
+        .collect()
+}
+
+/// Drift of `table` between `previous`, the schema last seen if any, and
+/// `live`. New columns are only reported against a previous schema.
+pub(crate) fn compare(
+    table: &SemanticTable,
+    used: &BTreeMap<String, Vec<String>>,
+    previous: Option<&TableSchema>,
+    live: &TableSchema,
+) -> Vec<SchemaDrift> {
+    let live_types: BTreeMap<&str, &str> = live
+        .columns
+        .iter()
+        .map(|c| (c.name.as_str(), c.data_type.as_str()))
+        .collect();
+    let previous_types: BTreeMap<&str, &str> = previous
+        .map(|schema| {
+            schema
+                .columns
+                .iter()
+                .map(|c| (c.name.as_str(), c.data_type.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let drift =
+        |kind: DriftKind, column: &str, previous_type: Option<&str>, current_type: Option<&str>| {
+            let message = match kind {
+                DriftKind::ColumnMissing => format!(
+                    "column {column} of {} read by the model no longer exists",
+                    table.table
+                ),
+                DriftKind::TypeChanged => format!(
+                    "column {column} of {} changed type from {} to {}",
+                    table.table,
+                    previous_type.unwrap_or_default(),
+                    current_type.unwrap_or_default()
+                ),
+                _ => format!(
+                    "column {column} was added to {} and is not modeled",
+                    table.table
+                ),
+            };
+            SchemaDrift {
+                kind,
+                table: table.name.clone(),
+                data_source: table.data_source.clone(),
+                physical_table: table.table.clone(),
+                column: Some(column.to_string()),
+                previous_type: previous_type.map(str::to_string),
+                current_type: current_type.map(str::to_string),
+                used_by: used.get(column).cloned().unwrap_or_default(),
+                message,
+            }
+        };
+
+    let mut drifts = Vec::new();
+    for column in used.keys() {
+        let previous_type = previous_types.get(column.as_str()).copied();
+        match live_types.get(column.as_str()) {
+            None => drifts.push(drift(DriftKind::ColumnMissing, column, previous_type, None)),
+            Some(current) => {
+                if previous_type.is_some_and(|prev| !prev.eq_ignore_ascii_case(current)) {
+                    drifts.push(drift(
+                        DriftKind::TypeChanged,
+                        column,
+                        previous_type,
+                        Some(current),
+                    ));
+                }
+            }
+        }
+    }
+    if previous.is_some() {
+        for (column, current) in &live_types {
+            if !previous_types.contains_key(column) && !used.contains_key(*column) {
+                drifts.push(drift(DriftKind::NewColumn, column, None, Some(current)));
+            }
+        }
+    }
+    drifts
+}
+
+/// Drift of a table whose schema could not be fetched.
+pub(crate) fn unavailable(table: &SemanticTable, error: &str) -> SchemaDrift {
+    SchemaDrift {
+        kind: DriftKind::TableUnavailable,
+        table: table.name.clone(),
+        data_source: table.data_source.clone(),
+        physical_table: table.table.clone(),
+        column: None,
+        previous_type: None,
+        current_type: None,
+        used_by: Vec::new(),
+        message: format!("schema of {} could not be fetched: {error}", table.table),
+    }
+}
@@ -1,3 +1,7 @@
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::{json, Value};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, SemaflowError>;
@@ -12,17 +16,224 @@ pub enum SemaflowError {
     Json(#[from] serde_json::Error),
     #[error("validation error: {0}")]
     Validation(String),
+    #[error("unknown flow {flow}")]
+    UnknownFlow { flow: String },
+    #[error("unknown {kind} {field} in flow {flow}{}", did_you_mean(suggestions))]
+    UnknownField {
+        kind: FieldKind,
+        field: String,
+        flow: String,
+        /// Similarly named fields of the flow, closest first.
+        suggestions: Vec<String>,
+    },
+    #[error("ambiguous {kind} {field}; found on aliases {}", candidates.join(", "))]
+    AmbiguousField {
+        kind: FieldKind,
+        field: String,
+        /// Table aliases (or join roles) defining the field; qualify it as
+        /// `alias.field`.
+        candidates: Vec<String>,
+    },
+    #[error("join cycle in flow {flow}: {}", path.join(" -> "))]
+    JoinCycle {
+        flow: String,
+        /// Join aliases along the cycle, ending where it started.
+        path: Vec<String>,
+    },
+    #[error(
+        "Multi-table measures require cardinality hint for join '{join}' → '{to_table}'. \
+         Add `cardinality: many_to_one` (or appropriate value) to the join definition."
+    )]
+    CardinalityRequired { join: String, to_table: String },
+    #[error("filter on {field} expects a {expected} value, got {value}")]
+    InvalidFilterValue {
+        field: String,
+        /// Kind of value the dimension's `data_type` calls for, e.g. `number`.
+        expected: String,
+        value: Value,
+    },
+    #[error("data source {data_source} not registered")]
+    UnknownDataSource { data_source: String },
     #[error("schema error: {0}")]
     Schema(String),
     #[error("sql generation error: {0}")]
     Sql(String),
     #[error("execution error: {0}")]
     Execution(String),
+    #[error("source unavailable: {0}")]
+    Unavailable(String),
+    #[error("overloaded: {0}")]
+    Overloaded(String),
+    #[error("query timed out: {0}")]
+    Timeout(String),
+    #[error("query cancelled")]
+    Cancelled,
+    #[error("stale cursor: {0}")]
+    StaleCursor(String),
     #[error("config error: {0}")]
     Config(String),
     #[cfg(feature = "duckdb")]
     #[error("duckdb error: {0}")]
     DuckDb(#[from] duckdb::Error),
+    #[cfg(feature = "arrow")]
+    #[error("arrow error: {0}")]
+    Arrow(#[from] arrow::error::ArrowError),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+/// The kind of semantic field an error refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    Dimension,
+    Measure,
+    TimeDimension,
+    /// A dimension or a measure.
+    Field,
+    /// A named filter of the flow or one of its tables.
+    Segment,
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldKind::Dimension => "dimension",
+            FieldKind::Measure => "measure",
+            FieldKind::TimeDimension => "time dimension",
+            FieldKind::Field => "field",
+            FieldKind::Segment => "segment",
+        })
+    }
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!("; did you mean {}?", suggestions.join(", "))
+    }
+}
+
+impl SemaflowError {
+    /// Stable, machine-readable identifier of the error, e.g. `unknown_field`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SemaflowError::Io(_) => "io_error",
+            SemaflowError::Yaml(_) => "yaml_error",
+            SemaflowError::Json(_) => "json_error",
+            SemaflowError::Validation(_) => "validation_error",
+            SemaflowError::UnknownFlow { .. } => "unknown_flow",
+            SemaflowError::UnknownField { .. } => "unknown_field",
+            SemaflowError::AmbiguousField { .. } => "ambiguous_field",
+            SemaflowError::JoinCycle { .. } => "join_cycle",
+            SemaflowError::CardinalityRequired { .. } => "cardinality_required",
+            SemaflowError::InvalidFilterValue { .. } => "invalid_filter_value",
+            SemaflowError::UnknownDataSource { .. } => "unknown_data_source",
+            SemaflowError::Schema(_) => "schema_error",
+            SemaflowError::Sql(_) => "sql_error",
+            SemaflowError::Execution(_) => "execution_error",
+            SemaflowError::Unavailable(_) => "source_unavailable",
+            SemaflowError::Overloaded(_) => "overloaded",
+            SemaflowError::Timeout(_) => "timeout",
+            SemaflowError::Cancelled => "cancelled",
+            SemaflowError::StaleCursor(_) => "stale_cursor",
+            SemaflowError::Config(_) => "config_error",
+            #[cfg(feature = "duckdb")]
+            SemaflowError::DuckDb(_) => "execution_error",
+            #[cfg(feature = "arrow")]
+            SemaflowError::Arrow(_) => "arrow_error",
+            SemaflowError::Other(_) => "internal_error",
+        }
+    }
+
+    /// Whether the error is caused by the request or model definition rather
+    /// than by executing the query.
+    pub fn is_validation(&self) -> bool {
+        matches!(
+            self,
+            SemaflowError::Validation(_)
+                | SemaflowError::UnknownFlow { .. }
+                | SemaflowError::UnknownField { .. }
+                | SemaflowError::AmbiguousField { .. }
+                | SemaflowError::JoinCycle { .. }
+                | SemaflowError::CardinalityRequired { .. }
+                | SemaflowError::InvalidFilterValue { .. }
+                | SemaflowError::UnknownDataSource { .. }
+        )
+    }
+
+    /// Structured fields of typed errors, for frontends rendering their own
+    /// messages. Empty for errors that only carry a message.
+    pub fn details(&self) -> Value {
+        match self {
+            SemaflowError::UnknownFlow { flow } => json!({ "flow": flow }),
+            SemaflowError::UnknownField {
+                kind,
+                field,
+                flow,
+                suggestions,
+            } => json!({
+                "kind": kind,
+                "field": field,
+                "flow": flow,
+                "suggestions": suggestions,
+            }),
+            SemaflowError::AmbiguousField {
+                kind,
+                field,
+                candidates,
+            } => json!({ "kind": kind, "field": field, "candidates": candidates }),
+            SemaflowError::JoinCycle { flow, path } => json!({ "flow": flow, "path": path }),
+            SemaflowError::CardinalityRequired { join, to_table } => {
+                json!({ "join": join, "to_table": to_table })
+            }
+            SemaflowError::InvalidFilterValue {
+                field,
+                expected,
+                value,
+            } => json!({ "field": field, "expected": expected, "value": value }),
+            SemaflowError::UnknownDataSource { data_source } => {
+                json!({ "data_source": data_source })
+            }
+            _ => json!({}),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_errors_have_codes_and_details() {
+        let err = SemaflowError::UnknownField {
+            kind: FieldKind::Measure,
+            field: "revenu".to_string(),
+            flow: "sales".to_string(),
+            suggestions: vec!["revenue".to_string()],
+        };
+        assert_eq!(err.code(), "unknown_field");
+        assert!(err.is_validation());
+        assert_eq!(
+            err.to_string(),
+            "unknown measure revenu in flow sales; did you mean revenue?"
+        );
+        assert_eq!(err.details()["kind"], "measure");
+        assert_eq!(err.details()["suggestions"][0], "revenue");
+
+        let err = SemaflowError::AmbiguousField {
+            kind: FieldKind::TimeDimension,
+            field: "created_at".to_string(),
+            candidates: vec!["o".to_string(), "c".to_string()],
+        };
+        assert_eq!(
+            err.to_string(),
+            "ambiguous time dimension created_at; found on aliases o, c"
+        );
+        assert_eq!(err.details()["kind"], "time_dimension");
+
+        assert_eq!(SemaflowError::Cancelled.code(), "cancelled");
+        assert_eq!(SemaflowError::Cancelled.details(), json!({}));
+    }
+}
@@ -1,18 +1,156 @@
+use std::cmp::Ordering;
+#[cfg(feature = "arrow")]
+use std::sync::Arc;
+
+#[cfg(feature = "arrow")]
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, NullArray, RecordBatch, RecordBatchOptions,
+    StringArray,
+};
+#[cfg(feature = "arrow")]
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 #[cfg(feature = "duckdb")]
 use duckdb::types::Value as DuckValue;
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ColumnMeta {
     pub name: String,
+    /// Type name reported by the backend (e.g. `BIGINT`, `int8`, `Int64`),
+    /// or the field's declared `data_type` when the backend gives none.
+    pub data_type: Option<String>,
+    /// Whether the column may hold NULLs, when the backend reports it.
+    pub nullable: Option<bool>,
+    /// The kind of requested field the column holds; `None` for helper
+    /// columns such as `_grouping`.
+    pub kind: Option<ColumnKind>,
 }
 
-#[derive(Debug, Clone)]
+impl ColumnMeta {
+    /// A column known only by name.
+    pub fn named(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnKind {
+    Dimension,
+    Measure,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryResult {
     pub columns: Vec<ColumnMeta>,
     pub rows: Vec<Map<String, Value>>,
 }
 
+#[cfg(feature = "arrow")]
+impl QueryResult {
+    /// Convert the rows into a single Arrow [`RecordBatch`].
+    ///
+    /// Column types are inferred from the values: all-boolean columns become
+    /// `Boolean`, all-integer `Int64`, other numeric `Float64`, and anything
+    /// else `Utf8` (nested values as JSON text). NULLs don't affect the type;
+    /// a column with no non-null values is `Null`.
+    pub fn to_arrow(&self) -> crate::error::Result<RecordBatch> {
+        let mut fields = Vec::with_capacity(self.columns.len());
+        let mut arrays = Vec::with_capacity(self.columns.len());
+        for column in &self.columns {
+            let values: Vec<&Value> = self
+                .rows
+                .iter()
+                .map(|row| row.get(&column.name).unwrap_or(&Value::Null))
+                .collect();
+            let data_type = infer_arrow_type(&values);
+            arrays.push(json_to_arrow_array(&values, &data_type));
+            fields.push(Field::new(&column.name, data_type, true));
+        }
+        let options = RecordBatchOptions::new().with_row_count(Some(self.rows.len()));
+        Ok(RecordBatch::try_new_with_options(
+            Arc::new(Schema::new(fields)),
+            arrays,
+            &options,
+        )?)
+    }
+}
+
+/// Query results as Arrow record batches, with the schema kept for empty results.
+#[cfg(feature = "arrow")]
+#[derive(Debug, Clone)]
+pub struct ArrowResult {
+    pub schema: SchemaRef,
+    pub batches: Vec<RecordBatch>,
+}
+
+#[cfg(feature = "arrow")]
+impl ArrowResult {
+    pub fn num_rows(&self) -> usize {
+        self.batches.iter().map(RecordBatch::num_rows).sum()
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl TryFrom<QueryResult> for ArrowResult {
+    type Error = crate::error::SemaflowError;
+
+    fn try_from(result: QueryResult) -> crate::error::Result<Self> {
+        let batch = result.to_arrow()?;
+        Ok(Self {
+            schema: batch.schema(),
+            batches: vec![batch],
+        })
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn infer_arrow_type(values: &[&Value]) -> DataType {
+    let mut data_type = DataType::Null;
+    for value in values {
+        let value_type = match value {
+            Value::Null => continue,
+            Value::Bool(_) => DataType::Boolean,
+            Value::Number(n) if n.is_i64() => DataType::Int64,
+            Value::Number(_) => DataType::Float64,
+            _ => return DataType::Utf8,
+        };
+        data_type = match (data_type, value_type) {
+            (DataType::Null, t) => t,
+            (a, b) if a == b => a,
+            (DataType::Int64, DataType::Float64) | (DataType::Float64, DataType::Int64) => {
+                DataType::Float64
+            }
+            _ => return DataType::Utf8,
+        };
+    }
+    data_type
+}
+
+#[cfg(feature = "arrow")]
+fn json_to_arrow_array(values: &[&Value], data_type: &DataType) -> ArrayRef {
+    match data_type {
+        DataType::Boolean => Arc::new(values.iter().map(|v| v.as_bool()).collect::<BooleanArray>()),
+        DataType::Int64 => Arc::new(values.iter().map(|v| v.as_i64()).collect::<Int64Array>()),
+        DataType::Float64 => Arc::new(values.iter().map(|v| v.as_f64()).collect::<Float64Array>()),
+        DataType::Null => Arc::new(NullArray::new(values.len())),
+        _ => Arc::new(
+            values
+                .iter()
+                .map(|v| match v {
+                    Value::Null => None,
+                    Value::String(s) => Some(s.clone()),
+                    other => Some(other.to_string()),
+                })
+                .collect::<StringArray>(),
+        ),
+    }
+}
+
 /// Result of a paginated query execution.
 ///
 /// Contains the current page of results plus metadata for pagination.
@@ -25,11 +163,39 @@ pub struct PaginatedResult {
     /// Whether more pages exist after this one.
     pub has_more: bool,
     /// Total number of rows in the result set.
-    /// Only available for BigQuery (which provides this in the response).
-    /// None for SQL backends that don't expose total count efficiently.
+    /// BigQuery provides this in the response; other backends fill it in
+    /// with an extra `COUNT(*)` when the request sets `count_total`.
     pub total_rows: Option<u64>,
 }
 
+/// Order two result values; NULLs sort last in either direction.
+pub(crate) fn compare_json_values(a: &Value, b: &Value, desc: bool) -> Ordering {
+    let rank = |v: &Value| match v {
+        Value::Bool(_) => 0,
+        Value::Number(_) => 1,
+        Value::String(_) => 2,
+        Value::Array(_) | Value::Object(_) => 3,
+        Value::Null => 4,
+    };
+    let ord = match (a, b) {
+        (Value::Null, Value::Null) => return Ordering::Equal,
+        (Value::Null, _) => return Ordering::Greater,
+        (_, Value::Null) => return Ordering::Less,
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::Number(x), Value::Number(y)) => x
+            .as_f64()
+            .partial_cmp(&y.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    };
+    if desc {
+        ord.reverse()
+    } else {
+        ord
+    }
+}
+
 #[cfg(feature = "duckdb")]
 pub(crate) fn duck_value_to_json(value: DuckValue) -> Value {
     match value {
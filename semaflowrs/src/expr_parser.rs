@@ -1,5 +1,5 @@
 use crate::error::SemaflowError;
-use crate::flows::{Aggregation, BinaryOp, Expr, FormulaAst, Function};
+use crate::flows::{Aggregation, BinaryOp, CaseBranch, Expr, FormulaAst, Function};
 
 // ============================================================================
 // Formula Parser (for complex measures)
@@ -238,9 +238,27 @@ impl FormulaParser {
         }
     }
 
+    /// Check whether the next token is the given keyword (case-insensitive)
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(name)) if name.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), SemaflowError> {
+        if self.peek_keyword(keyword) {
+            self.advance();
+            return Ok(());
+        }
+        Err(SemaflowError::Validation(format!(
+            "Formula parse error in '{}': expected '{}', found {:?}",
+            self.raw,
+            keyword,
+            self.peek()
+        )))
+    }
+
     /// Parse the complete formula
     fn parse(&mut self) -> Result<FormulaAst, SemaflowError> {
-        let expr = self.parse_comparison()?;
+        let expr = self.parse_or()?;
         if self.pos < self.tokens.len() {
             return Err(SemaflowError::Validation(format!(
                 "Formula parse error in '{}': unexpected token {:?} at end",
@@ -251,10 +269,106 @@ impl FormulaParser {
         Ok(expr)
     }
 
-    /// Parse comparison operators (lowest precedence)
+    /// Parse `or` (lowest precedence, left-associative)
+    fn parse_or(&mut self) -> Result<FormulaAst, SemaflowError> {
+        let mut left = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.advance();
+            let right = self.parse_and()?;
+            left = FormulaAst::Binary {
+                op: BinaryOp::Or,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    /// Parse `and` (binds tighter than `or`, left-associative)
+    fn parse_and(&mut self) -> Result<FormulaAst, SemaflowError> {
+        let mut left = self.parse_not()?;
+        while self.peek_keyword("and") {
+            self.advance();
+            let right = self.parse_not()?;
+            left = FormulaAst::Binary {
+                op: BinaryOp::And,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    /// Parse prefix `not` (binds tighter than `and`)
+    fn parse_not(&mut self) -> Result<FormulaAst, SemaflowError> {
+        if self.peek_keyword("not") {
+            self.advance();
+            let expr = self.parse_not()?;
+            return Ok(FormulaAst::Not {
+                expr: Box::new(expr),
+            });
+        }
+        self.parse_comparison()
+    }
+
+    /// Parse comparison operators and the `in` / `is null` / `between` predicates
     fn parse_comparison(&mut self) -> Result<FormulaAst, SemaflowError> {
         let left = self.parse_additive()?;
 
+        if self.peek_keyword("is") {
+            self.advance();
+            let negated = self.peek_keyword("not");
+            if negated {
+                self.advance();
+            }
+            self.expect_keyword("null")?;
+            return Ok(FormulaAst::IsNull {
+                expr: Box::new(left),
+                negated,
+            });
+        }
+
+        // `not` here can only introduce `not in` / `not between`
+        let negated = self.peek_keyword("not");
+        if negated {
+            self.advance();
+        }
+        if self.peek_keyword("in") {
+            self.advance();
+            self.expect(&Token::LParen)?;
+            let list = self.parse_args()?;
+            self.expect(&Token::RParen)?;
+            if list.is_empty() {
+                return Err(SemaflowError::Validation(format!(
+                    "Formula parse error in '{}': 'in' requires at least one value",
+                    self.raw
+                )));
+            }
+            return Ok(FormulaAst::InList {
+                expr: Box::new(left),
+                list,
+                negated,
+            });
+        }
+        if self.peek_keyword("between") {
+            self.advance();
+            let low = self.parse_additive()?;
+            self.expect_keyword("and")?;
+            let high = self.parse_additive()?;
+            return Ok(FormulaAst::Between {
+                expr: Box::new(left),
+                low: Box::new(low),
+                high: Box::new(high),
+                negated,
+            });
+        }
+        if negated {
+            return Err(SemaflowError::Validation(format!(
+                "Formula parse error in '{}': expected 'in' or 'between' after 'not'",
+                self.raw
+            )));
+        }
+
         let op = match self.peek() {
             Some(Token::Gt) => BinaryOp::Gt,
             Some(Token::Gte) => BinaryOp::Gte,
@@ -354,6 +468,9 @@ impl FormulaParser {
             }
             Some(Token::Ident(name)) => {
                 self.advance();
+                if name.eq_ignore_ascii_case("case") && self.peek() != Some(&Token::LParen) {
+                    return self.parse_case();
+                }
                 // Check for function call
                 if let Some(Token::LParen) = self.peek() {
                     self.advance();
@@ -380,10 +497,36 @@ impl FormulaParser {
                                 )))
                             }
                         };
+                        // Optional `filter (where <condition>)` suffix
+                        let filter = if self.peek_keyword("filter") {
+                            self.advance();
+                            self.expect(&Token::LParen)?;
+                            self.expect_keyword("where")?;
+                            let condition = self.parse_or()?;
+                            self.expect(&Token::RParen)?;
+                            Some(Box::new(condition))
+                        } else {
+                            None
+                        };
                         return Ok(FormulaAst::Aggregation {
                             agg,
                             column: col_name,
-                            filter: None,
+                            filter,
+                        });
+                    }
+
+                    // `if(cond, a, b)` is shorthand for a single-branch case
+                    if name.eq_ignore_ascii_case("if") {
+                        let [condition, then, otherwise]: [FormulaAst; 3] =
+                            args.try_into().map_err(|args: Vec<FormulaAst>| {
+                                SemaflowError::Validation(format!(
+                                    "Function 'if' requires exactly 3 arguments, got {}",
+                                    args.len()
+                                ))
+                            })?;
+                        return Ok(FormulaAst::Case {
+                            branches: vec![(condition, then)],
+                            else_expr: Some(Box::new(otherwise)),
                         });
                     }
 
@@ -397,7 +540,7 @@ impl FormulaParser {
             }
             Some(Token::LParen) => {
                 self.advance();
-                let expr = self.parse_comparison()?;
+                let expr = self.parse_or()?;
                 self.expect(&Token::RParen)?;
                 Ok(expr)
             }
@@ -412,6 +555,35 @@ impl FormulaParser {
         }
     }
 
+    /// Parse the rest of `case when <cond> then <expr> ... [else <expr>] end`
+    fn parse_case(&mut self) -> Result<FormulaAst, SemaflowError> {
+        let mut branches = Vec::new();
+        while self.peek_keyword("when") {
+            self.advance();
+            let condition = self.parse_or()?;
+            self.expect_keyword("then")?;
+            let result = self.parse_or()?;
+            branches.push((condition, result));
+        }
+        if branches.is_empty() {
+            return Err(SemaflowError::Validation(format!(
+                "Formula parse error in '{}': 'case' requires at least one 'when' branch",
+                self.raw
+            )));
+        }
+        let else_expr = if self.peek_keyword("else") {
+            self.advance();
+            Some(Box::new(self.parse_or()?))
+        } else {
+            None
+        };
+        self.expect_keyword("end")?;
+        Ok(FormulaAst::Case {
+            branches,
+            else_expr,
+        })
+    }
+
     /// Parse comma-separated arguments
     fn parse_args(&mut self) -> Result<Vec<FormulaAst>, SemaflowError> {
         let mut args = Vec::new();
@@ -421,11 +593,11 @@ impl FormulaParser {
             return Ok(args);
         }
 
-        args.push(self.parse_comparison()?);
+        args.push(self.parse_or()?);
 
         while let Some(Token::Comma) = self.peek() {
             self.advance();
-            args.push(self.parse_comparison()?);
+            args.push(self.parse_or()?);
         }
 
         Ok(args)
@@ -441,10 +613,20 @@ fn parse_aggregation(name: &str) -> Option<Aggregation> {
         "min" => Some(Aggregation::Min),
         "max" => Some(Aggregation::Max),
         "avg" => Some(Aggregation::Avg),
+        "count_if" => Some(Aggregation::CountIf),
+        "bool_and" => Some(Aggregation::BoolAnd),
+        "bool_or" => Some(Aggregation::BoolOr),
         "median" => Some(Aggregation::Median),
         "stddev" => Some(Aggregation::Stddev),
         "variance" => Some(Aggregation::Variance),
-        _ => None,
+        // p50, p90, p99, ...
+        other => other
+            .strip_prefix('p')
+            .filter(|n| (1..=2).contains(&n.len()))
+            .and_then(|n| n.parse::<u8>().ok())
+            .map(|n| Aggregation::Percentile {
+                p: f64::from(n) / 100.0,
+            }),
     }
 }
 
@@ -463,19 +645,29 @@ pub fn parse_formula(raw: &str) -> Result<FormulaAst, SemaflowError> {
 /// Extremely small, safe parser for concise filter/post_expr strings.
 /// Supports:
 /// - safe_divide(arg1, arg2)
-/// - simple binary comparisons on identifiers/literals (==, !=, >, >=, <, <=)
+/// - simple binary comparisons on identifiers/literals (==, =, !=, >, >=, <, <=)
+/// - predicates: `x in (...)`, `x is [not] null`, `x [not] between a and b`, `not ...`
+/// - `and` / `or` combinations of the above, with parentheses for grouping
 /// - bare identifiers or string/number literals
 pub fn parse_expr(input: &str) -> Result<Expr, SemaflowError> {
     let s = input.trim();
     if let Some(expr) = parse_safe_divide(s) {
         return Ok(expr);
     }
+    if let Some(expr) = parse_predicate(s) {
+        return Ok(expr);
+    }
     if let Some(expr) = parse_binary(s) {
         return Ok(expr);
     }
     if let Some(expr) = parse_literal(s) {
         return Ok(expr);
     }
+    if let Some(name) = parse_param_ref(s) {
+        return Ok(Expr::Param {
+            name: name.to_string(),
+        });
+    }
     if is_ident(s) {
         return Ok(Expr::Column {
             column: s.to_string(),
@@ -511,20 +703,106 @@ fn parse_safe_divide(s: &str) -> Option<Expr> {
     })
 }
 
+/// Parse `and` / `or` / `in` / `is null` / `between` / `not` predicates with
+/// the formula tokenizer. Returns `None` for anything else so the simpler
+/// parsers can try.
+fn parse_predicate(s: &str) -> Option<Expr> {
+    let ast = parse_formula(s).ok()?;
+    match ast {
+        FormulaAst::Binary {
+            op: BinaryOp::And | BinaryOp::Or,
+            ..
+        }
+        | FormulaAst::InList { .. }
+        | FormulaAst::IsNull { .. }
+        | FormulaAst::Between { .. }
+        | FormulaAst::Not { .. } => predicate_operand(ast),
+        _ => None,
+    }
+}
+
+fn predicate_operand(ast: FormulaAst) -> Option<Expr> {
+    let boxed = |ast: Box<FormulaAst>| predicate_operand(*ast).map(Box::new);
+    Some(match ast {
+        FormulaAst::Column { column } => Expr::Column { column },
+        FormulaAst::Literal { value } => Expr::Literal { value },
+        FormulaAst::Binary { op, left, right } => Expr::Binary {
+            op,
+            left: boxed(left)?,
+            right: boxed(right)?,
+        },
+        FormulaAst::InList {
+            expr,
+            list,
+            negated,
+        } => Expr::InList {
+            expr: boxed(expr)?,
+            list: list
+                .into_iter()
+                .map(predicate_operand)
+                .collect::<Option<Vec<_>>>()?,
+            negated,
+        },
+        FormulaAst::IsNull { expr, negated } => Expr::IsNull {
+            expr: boxed(expr)?,
+            negated,
+        },
+        FormulaAst::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => Expr::Between {
+            expr: boxed(expr)?,
+            low: boxed(low)?,
+            high: boxed(high)?,
+            negated,
+        },
+        FormulaAst::Not { expr } => Expr::Not { expr: boxed(expr)? },
+        FormulaAst::Case {
+            branches,
+            else_expr,
+        } => Expr::Case {
+            branches: branches
+                .into_iter()
+                .map(|(when, then)| {
+                    Some(CaseBranch {
+                        when: predicate_operand(when)?,
+                        then: predicate_operand(then)?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?,
+            else_expr: match else_expr {
+                Some(expr) => boxed(expr)?,
+                None => Box::new(Expr::Literal {
+                    value: serde_json::Value::Null,
+                }),
+            },
+        },
+        FormulaAst::Aggregation { .. }
+        | FormulaAst::MeasureRef { .. }
+        | FormulaAst::Function { .. } => return None,
+    })
+}
+
 fn parse_binary(s: &str) -> Option<Expr> {
-    for op in ["==", "!=", ">=", "<=", ">", "<"] {
+    for op in ["==", "!=", ">=", "<=", ">", "<", "="] {
         if let Some(idx) = s.find(op) {
             let (left, right_with_op) = s.split_at(idx);
             let right = &right_with_op[op.len()..];
             let left = left.trim();
             let right = right.trim();
-            let right_expr = parse_literal(right).or_else(|| {
-                Some(Expr::Column {
-                    column: right.to_string(),
+            let right_expr = parse_literal(right)
+                .or_else(|| {
+                    parse_param_ref(right).map(|name| Expr::Param {
+                        name: name.to_string(),
+                    })
                 })
-            })?;
+                .unwrap_or_else(|| Expr::Column {
+                    column: right.to_string(),
+                });
             let bop = match op {
-                "==" => BinaryOp::Eq,
+                "==" | "=" => BinaryOp::Eq,
                 "!=" => BinaryOp::Neq,
                 ">" => BinaryOp::Gt,
                 ">=" => BinaryOp::Gte,
@@ -565,6 +843,13 @@ fn parse_literal(s: &str) -> Option<Expr> {
     None
 }
 
+/// The parameter name in a `{{ name }}` reference, if `s` is one.
+pub fn parse_param_ref(s: &str) -> Option<&str> {
+    let name = s.strip_prefix("{{")?.strip_suffix("}}")?.trim();
+    (!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .then_some(name)
+}
+
 fn is_ident(s: &str) -> bool {
     !s.is_empty()
         && s.chars()
@@ -600,10 +885,10 @@ mod tests {
 
     #[test]
     fn parse_float_literal() {
-        let ast = parse_formula("3.14").unwrap();
+        let ast = parse_formula("2.75").unwrap();
         if let FormulaAst::Literal { value } = ast {
             let f = value.as_f64().unwrap();
-            assert!((f - 3.14).abs() < 0.001);
+            assert!((f - 2.75).abs() < 0.001);
         } else {
             panic!("expected literal");
         }
@@ -627,6 +912,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_filtered_aggregation() {
+        let ast = parse_formula("count(id) filter (where status == 'paid')").unwrap();
+        match ast {
+            FormulaAst::Aggregation {
+                agg,
+                column,
+                filter: Some(filter),
+            } => {
+                assert_eq!(agg, Aggregation::Count);
+                assert_eq!(column, "id");
+                assert!(matches!(*filter, FormulaAst::Binary { .. }), "{filter:?}");
+            }
+            _ => panic!("expected filtered aggregation, got {:?}", ast),
+        }
+    }
+
+    #[test]
+    fn parse_percentile_aggregation() {
+        let ast = parse_formula("p95(latency_ms)").unwrap();
+        match ast {
+            FormulaAst::Aggregation { agg, column, .. } => {
+                assert_eq!(agg, Aggregation::Percentile { p: 0.95 });
+                assert_eq!(column, "latency_ms");
+            }
+            _ => panic!("expected aggregation, got {:?}", ast),
+        }
+    }
+
     #[test]
     fn parse_count_distinct() {
         let ast = parse_formula("count_distinct(customer_id)").unwrap();
@@ -749,6 +1063,177 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_in_list_and_not_in() {
+        let ast = parse_formula("status not in ('a', 'b')").unwrap();
+        match ast {
+            FormulaAst::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                assert!(negated);
+                assert!(matches!(*expr, FormulaAst::Column { column } if column == "status"));
+                assert_eq!(list.len(), 2);
+            }
+            _ => panic!("expected in list, got {:?}", ast),
+        }
+    }
+
+    #[test]
+    fn parse_is_null_and_between() {
+        let ast = parse_formula("x IS NOT NULL").unwrap();
+        assert!(matches!(ast, FormulaAst::IsNull { negated: true, .. }));
+
+        let ast = parse_formula("amount between 1 and 10 + 5").unwrap();
+        match ast {
+            FormulaAst::Between {
+                low, high, negated, ..
+            } => {
+                assert!(!negated);
+                assert!(matches!(*low, FormulaAst::Literal { .. }));
+                assert!(matches!(
+                    *high,
+                    FormulaAst::Binary {
+                        op: BinaryOp::Add,
+                        ..
+                    }
+                ));
+            }
+            _ => panic!("expected between, got {:?}", ast),
+        }
+    }
+
+    #[test]
+    fn parse_not_prefix() {
+        let ast = parse_formula("not (x is null)").unwrap();
+        match ast {
+            FormulaAst::Not { expr } => {
+                assert!(matches!(*expr, FormulaAst::IsNull { negated: false, .. }))
+            }
+            _ => panic!("expected not, got {:?}", ast),
+        }
+        assert!(parse_formula("x not 5").is_err());
+        assert!(parse_formula("x between 1").is_err());
+    }
+
+    #[test]
+    fn parse_and_or_precedence() {
+        let ast = parse_formula("a > 1 or not b > 2 and c in (1, 2)").unwrap();
+        match ast {
+            FormulaAst::Binary {
+                op: BinaryOp::Or,
+                right,
+                ..
+            } => match *right {
+                FormulaAst::Binary {
+                    op: BinaryOp::And,
+                    left,
+                    right,
+                } => {
+                    assert!(matches!(*left, FormulaAst::Not { .. }));
+                    assert!(matches!(*right, FormulaAst::InList { .. }));
+                }
+                other => panic!("expected and, got {:?}", other),
+            },
+            _ => panic!("expected or, got {:?}", ast),
+        }
+
+        // `between ... and` keeps its own `and`
+        let ast = parse_formula("x between 1 and 5 and y = 2").unwrap();
+        assert!(matches!(
+            ast,
+            FormulaAst::Binary {
+                op: BinaryOp::And,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_case_and_if() {
+        let ast = parse_formula(
+            "CASE WHEN sum(amount) > 100 THEN 'high' WHEN sum(amount) > 10 THEN 'mid' END",
+        )
+        .unwrap();
+        match ast {
+            FormulaAst::Case {
+                branches,
+                else_expr,
+            } => {
+                assert_eq!(branches.len(), 2);
+                assert!(matches!(
+                    branches[0].0,
+                    FormulaAst::Binary {
+                        op: BinaryOp::Gt,
+                        ..
+                    }
+                ));
+                assert!(else_expr.is_none());
+            }
+            _ => panic!("expected case, got {:?}", ast),
+        }
+
+        let ast = parse_formula("if(status == 'paid', amount, 0)").unwrap();
+        assert_eq!(
+            ast,
+            parse_formula("case when status == 'paid' then amount else 0 end").unwrap()
+        );
+        assert!(parse_formula("if(status == 'paid', amount)").is_err());
+        assert!(parse_formula("case else 1 end").is_err());
+        assert!(parse_formula("case when x then 1").is_err());
+    }
+
+    #[test]
+    fn parse_expr_handles_predicates() {
+        let expr = parse_expr("status in ('paid', 'shipped')").unwrap();
+        assert!(matches!(expr, Expr::InList { negated: false, ref list, .. } if list.len() == 2));
+        let expr = parse_expr("discount is null").unwrap();
+        assert!(matches!(expr, Expr::IsNull { negated: false, .. }));
+        let expr =
+            parse_expr("status = 'paid' and (country in ('US', 'CA') or amount > 100)").unwrap();
+        match expr {
+            Expr::Binary {
+                op: BinaryOp::And,
+                left,
+                right,
+            } => {
+                assert!(matches!(
+                    *left,
+                    Expr::Binary {
+                        op: BinaryOp::Eq,
+                        ..
+                    }
+                ));
+                assert!(matches!(
+                    *right,
+                    Expr::Binary {
+                        op: BinaryOp::Or,
+                        ..
+                    }
+                ));
+            }
+            other => panic!("expected and, got {:?}", other),
+        }
+        // Existing comparisons keep going through the simple parser
+        let expr = parse_expr("amount > 10").unwrap();
+        assert!(matches!(
+            expr,
+            Expr::Binary {
+                op: BinaryOp::Gt,
+                ..
+            }
+        ));
+        let expr = parse_expr("status = 'completed'").unwrap();
+        assert!(matches!(
+            expr,
+            Expr::Binary {
+                op: BinaryOp::Eq,
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn parse_error_unclosed_paren() {
         let result = parse_formula("(a + b");
@@ -2,7 +2,7 @@
 //!
 //! Shared helpers for traversing and analyzing semantic expressions.
 
-use crate::flows::Expr;
+use crate::flows::{Expr, Function, TimeGrain};
 
 /// Recursively collect all measure references from an expression.
 ///
@@ -27,7 +27,21 @@ pub fn collect_measure_refs(expr: &Expr, out: &mut Vec<String>) {
             collect_measure_refs(left, out);
             collect_measure_refs(right, out);
         }
-        Expr::Column { .. } | Expr::Literal { .. } => {}
+        Expr::InList { expr, list, .. } => {
+            collect_measure_refs(expr, out);
+            for item in list {
+                collect_measure_refs(item, out);
+            }
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            collect_measure_refs(expr, out);
+            collect_measure_refs(low, out);
+            collect_measure_refs(high, out);
+        }
+        Expr::IsNull { expr, .. } | Expr::Not { expr } => collect_measure_refs(expr, out),
+        Expr::Column { .. } | Expr::Literal { .. } | Expr::Param { .. } | Expr::Sql { .. } => {}
     }
 }
 
@@ -66,10 +80,129 @@ pub fn collect_column_refs(expr: &Expr, out: &mut Vec<String>) {
             collect_column_refs(left, out);
             collect_column_refs(right, out);
         }
-        Expr::Literal { .. } | Expr::MeasureRef { .. } => {}
+        Expr::InList { expr, list, .. } => {
+            collect_column_refs(expr, out);
+            for item in list {
+                collect_column_refs(item, out);
+            }
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            collect_column_refs(expr, out);
+            collect_column_refs(low, out);
+            collect_column_refs(high, out);
+        }
+        Expr::IsNull { expr, .. } | Expr::Not { expr } => collect_column_refs(expr, out),
+        // Raw SQL is not checked against the schema; see `raw_sql_columns`
+        Expr::Literal { .. } | Expr::MeasureRef { .. } | Expr::Param { .. } | Expr::Sql { .. } => {}
     }
 }
 
+/// Recursively collect all `{{ param }}` references from an expression.
+///
+/// Used for validation to ensure every referenced parameter is declared by the flow.
+pub fn collect_param_refs(expr: &Expr, out: &mut Vec<String>) {
+    match expr {
+        Expr::Param { name } => out.push(name.clone()),
+        Expr::Func { args, .. } => args.iter().for_each(|a| collect_param_refs(a, out)),
+        Expr::Case {
+            branches,
+            else_expr,
+        } => {
+            for b in branches {
+                collect_param_refs(&b.when, out);
+                collect_param_refs(&b.then, out);
+            }
+            collect_param_refs(else_expr, out);
+        }
+        Expr::Binary { left, right, .. } => {
+            collect_param_refs(left, out);
+            collect_param_refs(right, out);
+        }
+        Expr::InList { expr, list, .. } => {
+            collect_param_refs(expr, out);
+            for item in list {
+                collect_param_refs(item, out);
+            }
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            collect_param_refs(expr, out);
+            collect_param_refs(low, out);
+            collect_param_refs(high, out);
+        }
+        Expr::IsNull { expr, .. } | Expr::Not { expr } => collect_param_refs(expr, out),
+        Expr::Column { .. } | Expr::Literal { .. } | Expr::MeasureRef { .. } | Expr::Sql { .. } => {
+        }
+    }
+}
+
+/// Recursively collect the grains of every `date_trunc` in an expression.
+///
+/// Used to hold dimensions to their table's `smallest_time_grain`.
+pub fn collect_time_grains(expr: &Expr, out: &mut Vec<TimeGrain>) {
+    match expr {
+        Expr::Func { func, args } => {
+            if let Function::DateTrunc(grain) = func {
+                out.push(grain.clone());
+            }
+            for arg in args {
+                collect_time_grains(arg, out);
+            }
+        }
+        Expr::Case {
+            branches,
+            else_expr,
+        } => {
+            for b in branches {
+                collect_time_grains(&b.when, out);
+                collect_time_grains(&b.then, out);
+            }
+            collect_time_grains(else_expr, out);
+        }
+        Expr::Binary { left, right, .. } => {
+            collect_time_grains(left, out);
+            collect_time_grains(right, out);
+        }
+        Expr::InList { expr, list, .. } => {
+            collect_time_grains(expr, out);
+            for item in list {
+                collect_time_grains(item, out);
+            }
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            collect_time_grains(expr, out);
+            collect_time_grains(low, out);
+            collect_time_grains(high, out);
+        }
+        Expr::IsNull { expr, .. } | Expr::Not { expr } => collect_time_grains(expr, out),
+        Expr::Column { .. }
+        | Expr::Literal { .. }
+        | Expr::MeasureRef { .. }
+        | Expr::Param { .. }
+        | Expr::Sql { .. } => {}
+    }
+}
+
+/// Columns written as `${TABLE}.column` in raw SQL, for lineage. Other
+/// column references in the SQL can't be told apart from functions and
+/// keywords, so they are not reported.
+pub fn raw_sql_columns(sql: &str) -> Vec<String> {
+    sql.split("${TABLE}.")
+        .skip(1)
+        .filter_map(|rest| {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (end > 0).then(|| rest[..end].to_string())
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,6 +312,42 @@ mod tests {
         assert_eq!(refs, vec!["nullable_col", "fallback_col"]);
     }
 
+    #[test]
+    fn collects_column_refs_from_predicates() {
+        let column = |name: &str| {
+            Box::new(Expr::Column {
+                column: name.to_string(),
+            })
+        };
+        let expr = Expr::Not {
+            expr: Box::new(Expr::Between {
+                expr: column("amount"),
+                low: column("min_amount"),
+                high: Box::new(Expr::Literal {
+                    value: serde_json::Value::Number(100.into()),
+                }),
+                negated: false,
+            }),
+        };
+        let mut refs = Vec::new();
+        collect_column_refs(&expr, &mut refs);
+        assert_eq!(refs, vec!["amount", "min_amount"]);
+
+        let expr = Expr::InList {
+            expr: column("status"),
+            list: vec![Expr::MeasureRef {
+                name: "total".to_string(),
+            }],
+            negated: true,
+        };
+        let mut refs = Vec::new();
+        collect_column_refs(&expr, &mut refs);
+        assert_eq!(refs, vec!["status"]);
+        let mut measures = Vec::new();
+        collect_measure_refs(&expr, &mut measures);
+        assert_eq!(measures, vec!["total"]);
+    }
+
     #[test]
     fn ignores_measure_refs_and_literals() {
         let expr = Expr::Binary {
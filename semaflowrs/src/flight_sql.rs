@@ -0,0 +1,455 @@
+//! Arrow Flight SQL frontend.
+//!
+//! [`FlightSqlServer`] streams query results as Arrow record batches, so
+//! clients pulling large results (ADBC, the Flight SQL JDBC driver, pyarrow)
+//! skip the JSON encoding of [`run_query`](crate::runtime::run_query). Flows
+//! are listed as views of the `semaflow` catalog. Statements use the SQL
+//! dialect of the Postgres frontend (see [`crate::pgwire::sql`]) or are a JSON
+//! [`QueryRequest`].
+//!
+//! Statement handles and tickets carry the planned request itself, so the
+//! server keeps no per-client state and any replica can serve any ticket.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::ipc::writer::IpcWriteOptions;
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::flight_service_server::FlightServiceServer;
+use arrow_flight::sql::metadata::{SqlInfoData, SqlInfoDataBuilder};
+use arrow_flight::sql::server::FlightSqlService;
+use arrow_flight::sql::{
+    ActionClosePreparedStatementRequest, ActionCreatePreparedStatementRequest,
+    ActionCreatePreparedStatementResult, CommandGetCatalogs, CommandGetDbSchemas,
+    CommandGetSqlInfo, CommandGetTableTypes, CommandGetTables, CommandPreparedStatementQuery,
+    CommandStatementQuery, ProstMessageExt, SqlInfo, TicketStatementQuery,
+};
+use arrow_flight::{
+    Action, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, IpcMessage, SchemaAsIpc,
+    Ticket,
+};
+use futures::{Stream, TryStreamExt};
+use once_cell::sync::Lazy;
+use prost::Message;
+use tonic::{Request, Response, Status};
+
+use crate::backends::ConnectionManager;
+use crate::error::{Result, SemaflowError};
+use crate::flows::{Aggregation, QueryRequest};
+use crate::pgwire::sql::{self, Statement};
+use crate::policy::QueryContext;
+use crate::registry::{FlowRegistry, FlowSchema};
+use crate::runtime;
+use crate::sql_ast::sanitize_alias;
+
+/// Catalog under which flows are listed.
+pub const CATALOG: &str = "semaflow";
+/// Schema under which flows are listed.
+pub const SCHEMA: &str = "flows";
+const TABLE_TYPE: &str = "VIEW";
+
+static SQL_INFO: Lazy<SqlInfoData> = Lazy::new(|| {
+    let mut builder = SqlInfoDataBuilder::new();
+    builder.append(SqlInfo::FlightSqlServerName, "SemaFlow");
+    builder.append(SqlInfo::FlightSqlServerVersion, env!("CARGO_PKG_VERSION"));
+    builder.append(SqlInfo::FlightSqlServerReadOnly, true);
+    builder.build().expect("static SQL info is valid")
+});
+
+type FlightDataStream = Pin<Box<dyn Stream<Item = std::result::Result<FlightData, Status>> + Send>>;
+
+/// Serves flows to Arrow Flight SQL clients.
+pub struct FlightSqlServer {
+    registry: Arc<FlowRegistry>,
+    connections: Arc<ConnectionManager>,
+}
+
+impl FlightSqlServer {
+    pub fn new(registry: Arc<FlowRegistry>, connections: Arc<ConnectionManager>) -> Self {
+        Self {
+            registry,
+            connections,
+        }
+    }
+
+    /// The gRPC service, for mounting alongside other tonic services.
+    pub fn into_service(self) -> FlightServiceServer<Self> {
+        FlightServiceServer::new(self)
+    }
+
+    /// Serve on `addr` until the transport fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        tracing::info!(addr = %addr, "flight sql server listening");
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve(addr)
+            .await
+            .map_err(|e| SemaflowError::Other(e.into()))
+    }
+
+    /// The request a statement stands for: the SQL dialect, or a JSON request.
+    fn plan(&self, query: &str) -> Result<QueryRequest> {
+        if query.trim_start().starts_with('{') {
+            return Ok(serde_json::from_str(query)?);
+        }
+        match sql::parse_statement(query)? {
+            Statement::Select(select) => {
+                let schema = self.registry.flow_schema(&select.flow)?;
+                select.to_flow_request(&schema)
+            }
+            _ => Err(SemaflowError::Validation(
+                "only SELECT statements are supported".to_string(),
+            )),
+        }
+    }
+
+    /// The result schema of a request from the fields' declared types. Batches
+    /// carry the backend's own types, which may be more precise.
+    fn request_schema(&self, request: &QueryRequest) -> Result<Schema> {
+        let schema = self.registry.flow_schema(&request.flow)?;
+        let dimensions = request
+            .dimensions
+            .iter()
+            .map(|d| (d.field.as_str(), dimension_type(&schema, &d.field)));
+        let measures = request
+            .measures
+            .iter()
+            .map(|m| (m.as_str(), measure_type(&schema, m)));
+        Ok(Schema::new(
+            dimensions
+                .chain(measures)
+                .map(|(name, data_type)| Field::new(sanitize_alias(name), data_type, true))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    async fn execute(&self, handle: &[u8]) -> std::result::Result<FlightDataStream, Status> {
+        let request: QueryRequest = serde_json::from_slice(handle)
+            .map_err(|e| Status::invalid_argument(format!("invalid statement handle: {e}")))?;
+        let result = runtime::run_query_arrow(
+            &self.registry,
+            &self.connections,
+            &request,
+            &QueryContext::default(),
+        )
+        .await
+        .map_err(status)?;
+        Ok(batch_stream(result.schema, result.batches))
+    }
+}
+
+#[tonic::async_trait]
+impl FlightSqlService for FlightSqlServer {
+    type FlightService = FlightSqlServer;
+
+    async fn get_flight_info_statement(
+        &self,
+        query: CommandStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let plan = self.plan(&query.query).map_err(status)?;
+        let schema = self.request_schema(&plan).map_err(status)?;
+        let handle = serde_json::to_vec(&plan).map_err(|e| status(e.into()))?;
+        let ticket = TicketStatementQuery {
+            statement_handle: handle.into(),
+        };
+        flight_info(ticket, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_prepared_statement(
+        &self,
+        query: CommandPreparedStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let plan: QueryRequest = serde_json::from_slice(&query.prepared_statement_handle)
+            .map_err(|e| Status::invalid_argument(format!("invalid statement handle: {e}")))?;
+        let schema = self.request_schema(&plan).map_err(status)?;
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = query.into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_table_types(
+        &self,
+        query: CommandGetTableTypes,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = query.into_builder().schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn get_flight_info_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder(&SQL_INFO).schema();
+        flight_info(query, &schema, request.into_inner())
+    }
+
+    async fn do_get_statement(
+        &self,
+        ticket: TicketStatementQuery,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<FlightDataStream>, Status> {
+        Ok(Response::new(self.execute(&ticket.statement_handle).await?))
+    }
+
+    async fn do_get_prepared_statement(
+        &self,
+        query: CommandPreparedStatementQuery,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<FlightDataStream>, Status> {
+        Ok(Response::new(
+            self.execute(&query.prepared_statement_handle).await?,
+        ))
+    }
+
+    async fn do_get_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<FlightDataStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG);
+        let schema = builder.schema();
+        let batch = builder.build()?;
+        Ok(Response::new(batch_stream(schema, vec![batch])))
+    }
+
+    async fn do_get_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<FlightDataStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG, SCHEMA);
+        let schema = builder.schema();
+        let batch = builder.build()?;
+        Ok(Response::new(batch_stream(schema, vec![batch])))
+    }
+
+    async fn do_get_tables(
+        &self,
+        query: CommandGetTables,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<FlightDataStream>, Status> {
+        let include_schema = query.include_schema;
+        let mut builder = query.into_builder();
+        for flow in self.registry.list_flow_summaries() {
+            let table_schema = match self.registry.flow_schema(&flow.name) {
+                Ok(schema) if include_schema => flow_arrow_schema(&schema),
+                Ok(_) => Schema::empty(),
+                // Flows with missing tables are reported by validation, not here
+                Err(_) => continue,
+            };
+            builder.append(CATALOG, SCHEMA, &flow.name, TABLE_TYPE, &table_schema)?;
+        }
+        let schema = builder.schema();
+        let batch = builder.build()?;
+        Ok(Response::new(batch_stream(schema, vec![batch])))
+    }
+
+    async fn do_get_table_types(
+        &self,
+        query: CommandGetTableTypes,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<FlightDataStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(TABLE_TYPE);
+        let schema = builder.schema();
+        let batch = builder.build()?;
+        Ok(Response::new(batch_stream(schema, vec![batch])))
+    }
+
+    async fn do_get_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<FlightDataStream>, Status> {
+        let builder = query.into_builder(&SQL_INFO);
+        let schema = builder.schema();
+        let batch = builder.build()?;
+        Ok(Response::new(batch_stream(schema, vec![batch])))
+    }
+
+    async fn do_action_create_prepared_statement(
+        &self,
+        query: ActionCreatePreparedStatementRequest,
+        _request: Request<Action>,
+    ) -> std::result::Result<ActionCreatePreparedStatementResult, Status> {
+        let plan = self.plan(&query.query).map_err(status)?;
+        let schema = self.request_schema(&plan).map_err(status)?;
+        let IpcMessage(dataset_schema) = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(|e: arrow::error::ArrowError| status(e.into()))?;
+        let handle = serde_json::to_vec(&plan).map_err(|e| status(e.into()))?;
+        Ok(ActionCreatePreparedStatementResult {
+            prepared_statement_handle: handle.into(),
+            dataset_schema,
+            // Statements take no parameters
+            parameter_schema: Default::default(),
+        })
+    }
+
+    async fn do_action_close_prepared_statement(
+        &self,
+        _query: ActionClosePreparedStatementRequest,
+        _request: Request<Action>,
+    ) -> std::result::Result<(), Status> {
+        // Handles hold the whole request; there is nothing to release
+        Ok(())
+    }
+
+    async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
+}
+
+/// Flight info with a single endpoint whose ticket is `command`.
+#[allow(clippy::result_large_err)]
+fn flight_info(
+    command: impl ProstMessageExt,
+    schema: &Schema,
+    descriptor: FlightDescriptor,
+) -> std::result::Result<Response<FlightInfo>, Status> {
+    let ticket = Ticket::new(command.as_any().encode_to_vec());
+    let info = FlightInfo::new()
+        .try_with_schema(schema)
+        .map_err(|e| status(e.into()))?
+        .with_endpoint(FlightEndpoint::new().with_ticket(ticket))
+        .with_descriptor(descriptor);
+    Ok(Response::new(info))
+}
+
+fn batch_stream(schema: SchemaRef, batches: Vec<RecordBatch>) -> FlightDataStream {
+    let batches = futures::stream::iter(batches.into_iter().map(Ok));
+    Box::pin(
+        FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .build(batches)
+            .map_err(Status::from),
+    )
+}
+
+/// The columns of a flow as listed by `GetTables`, named by qualified name.
+fn flow_arrow_schema(schema: &FlowSchema) -> Schema {
+    let dimensions = schema
+        .dimensions
+        .iter()
+        .map(|d| (&d.qualified_name, dimension_type(schema, &d.qualified_name)));
+    let measures = schema
+        .measures
+        .iter()
+        .map(|m| (&m.qualified_name, measure_type(schema, &m.qualified_name)));
+    Schema::new(
+        dimensions
+            .chain(measures)
+            .map(|(name, data_type)| Field::new(name, data_type, true))
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn dimension_type(schema: &FlowSchema, field: &str) -> DataType {
+    schema
+        .dimensions
+        .iter()
+        .find(|d| d.qualified_name == field || d.name == field)
+        .and_then(|d| arrow_type(d.data_type.as_deref()))
+        .unwrap_or(DataType::Utf8)
+}
+
+fn measure_type(schema: &FlowSchema, field: &str) -> DataType {
+    let Some(measure) = schema
+        .measures
+        .iter()
+        .find(|m| m.qualified_name == field || m.name == field)
+    else {
+        return DataType::Float64;
+    };
+    arrow_type(measure.data_type.as_deref()).unwrap_or(match measure.agg {
+        Some(Aggregation::Count | Aggregation::CountDistinct | Aggregation::CountIf) => {
+            DataType::Int64
+        }
+        Some(Aggregation::BoolAnd | Aggregation::BoolOr) => DataType::Boolean,
+        _ => DataType::Float64,
+    })
+}
+
+/// Arrow type for a declared `data_type`.
+fn arrow_type(data_type: Option<&str>) -> Option<DataType> {
+    let data_type = data_type?.to_ascii_lowercase();
+    Some(if data_type.contains("int") {
+        DataType::Int64
+    } else if ["float", "double", "real", "numeric", "decimal"]
+        .iter()
+        .any(|t| data_type.contains(t))
+    {
+        DataType::Float64
+    } else if data_type.starts_with("bool") {
+        DataType::Boolean
+    } else if data_type.contains("timestamp") || data_type == "datetime" {
+        DataType::Timestamp(TimeUnit::Microsecond, None)
+    } else if data_type == "date" {
+        DataType::Date32
+    } else {
+        DataType::Utf8
+    })
+}
+
+fn status(error: SemaflowError) -> Status {
+    let message = error.to_string();
+    match error {
+        SemaflowError::UnknownFlow { .. } => Status::not_found(message),
+        SemaflowError::Timeout(_) => Status::deadline_exceeded(message),
+        SemaflowError::Cancelled => Status::cancelled(message),
+        SemaflowError::Unavailable(_) => Status::unavailable(message),
+        SemaflowError::Overloaded(_) => Status::resource_exhausted(message),
+        e if e.is_validation() => Status::invalid_argument(message),
+        _ => Status::internal(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_declared_types_to_arrow() {
+        assert_eq!(arrow_type(Some("BIGINT")), Some(DataType::Int64));
+        assert_eq!(arrow_type(Some("decimal(18,2)")), Some(DataType::Float64));
+        assert_eq!(
+            arrow_type(Some("timestamp")),
+            Some(DataType::Timestamp(TimeUnit::Microsecond, None))
+        );
+        assert_eq!(arrow_type(Some("date")), Some(DataType::Date32));
+        assert_eq!(arrow_type(Some("varchar")), Some(DataType::Utf8));
+        assert_eq!(arrow_type(None), None);
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use crate::expr_parser::parse_expr;
+use crate::expr_parser::{parse_expr, parse_formula, parse_param_ref};
 use serde::{de, Deserializer};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -9,14 +9,193 @@ use serde_json::Value;
 pub struct SemanticTable {
     pub data_source: String,
     pub name: String,
+    /// Physical table read; for derived tables, a label (the table's name
+    /// unless set).
     pub table: String,
+    /// SQL read as a derived table instead of `table`, e.g. to keep only the
+    /// latest record per key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+    /// Request on another flow whose result is read as a derived table. Its
+    /// columns are the requested field names with `.` replaced by `__`
+    /// (`c.country` is read as `c__country`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<QueryRequest>,
+    /// Other semantic tables whose rows are appended with `UNION ALL` and
+    /// read as a derived table, e.g. yearly `orders_2023` and `orders_2024`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub union: Option<TableUnion>,
     /// Primary key columns. Supports composite keys.
     pub primary_keys: Vec<String>,
+    /// Other column sets identifying at most one row each, e.g. `[[email]]`.
+    /// Joins keyed on all columns of one are inferred to be "to one".
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unique_keys: Vec<Vec<String>>,
+    /// Known nullability by column, e.g. `customer_id: false`. Validation
+    /// warns when an INNER join is keyed on a nullable column, since it
+    /// drops the rows holding NULL.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub nullable: BTreeMap<String, bool>,
     pub time_dimension: Option<String>,
     pub smallest_time_grain: Option<TimeGrain>,
+    /// IANA time zone (e.g. `Europe/Berlin`) time truncations render in,
+    /// for timestamps stored as UTC instants. See [`QueryRequest::timezone`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
     pub dimensions: BTreeMap<String, Dimension>,
     pub measures: BTreeMap<String, Measure>,
     pub description: Option<String>,
+    /// Row filters every query reading this table inherits, such as
+    /// `deleted_at IS NULL`, unless the request sets `ignore_default_filters`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub default_filters: Vec<Expr>,
+    /// Named row filters a request applies by listing them in
+    /// [`QueryRequest::segments`], e.g. `active: status = 'active'`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub segments: BTreeMap<String, Expr>,
+    /// Row-level security: maps a user attribute to the expression its values
+    /// must match, e.g. `region: sales_region`. See [`crate::policy`].
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub access_policies: BTreeMap<String, Expr>,
+    /// Drill-down paths: named lists of dimensions from the coarsest level
+    /// to the finest, e.g. `geography: [country, region, city]`. See
+    /// [`QueryRequest::drill`].
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub hierarchies: BTreeMap<String, Vec<String>>,
+    /// Business keys the table carries, by entity name (e.g. `customer`).
+    /// Flow joins without `join_keys` are keyed on an entity shared by
+    /// both sides. See [`crate::registry::FlowRegistry::infer_entity_joins`].
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub entities: BTreeMap<String, Entity>,
+}
+
+impl SemanticTable {
+    /// Whether rows are read from `sql`, `query` or `union` rather than from
+    /// the physical `table`.
+    pub fn is_derived(&self) -> bool {
+        self.sql.is_some() || self.query.is_some() || self.union.is_some()
+    }
+}
+
+/// The tables a `union` table appends and the columns they line up on.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableUnion {
+    /// Columns of the union, read from every member.
+    pub columns: Vec<String>,
+    /// Members in order; each is a physical or `sql` table on the same data
+    /// source.
+    pub tables: Vec<UnionMember>,
+}
+
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnionMember {
+    pub semantic_table: String,
+    /// Member column read for a union column it names differently, e.g.
+    /// `amount: total_amount`; `null` when the member lacks the column.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub columns: BTreeMap<String, Option<String>>,
+}
+
+impl UnionMember {
+    /// Member column read for union column `column`; `None` reads NULL.
+    pub fn source_column<'a>(&'a self, column: &'a str) -> Option<&'a str> {
+        match self.columns.get(column) {
+            Some(mapped) => mapped.as_deref(),
+            None => Some(column),
+        }
+    }
+}
+
+/// A key column identifying an entity, such as a customer or an order.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entity {
+    #[serde(rename = "type")]
+    pub kind: EntityType,
+    /// Column holding the key; the entity name by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+}
+
+impl Entity {
+    /// Column holding the key of the entity called `name`.
+    pub fn column<'a>(&'a self, name: &'a str) -> &'a str {
+        self.column.as_deref().unwrap_or(name)
+    }
+}
+
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityType {
+    /// Each row is one entity, e.g. `customer` on the customers table.
+    Primary,
+    /// At most one row per entity, but not every entity has a row.
+    Unique,
+    /// Rows reference an entity owned by another table, e.g. `customer` on
+    /// the orders table.
+    Foreign,
+}
+
+impl EntityType {
+    /// Whether a key of this type identifies at most one row.
+    pub fn is_unique(self) -> bool {
+        matches!(self, EntityType::Primary | EntityType::Unique)
+    }
+}
+
+/// File form of [`SemanticTable`].
+#[derive(Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SemanticTableFile {
+    data_source: String,
+    name: String,
+    #[serde(default)]
+    table: Option<String>,
+    #[serde(default)]
+    sql: Option<String>,
+    #[serde(default)]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<Value>"))]
+    query: Option<QueryRequest>,
+    #[serde(default)]
+    union: Option<TableUnion>,
+    // Support both single key and composite keys
+    #[serde(default)]
+    primary_key: Option<String>,
+    #[serde(default)]
+    primary_keys: Option<Vec<String>>,
+    #[serde(default)]
+    unique_keys: Vec<Vec<String>>,
+    #[serde(default)]
+    nullable: BTreeMap<String, bool>,
+    time_dimension: Option<String>,
+    smallest_time_grain: Option<TimeGrain>,
+    #[serde(default)]
+    timezone: Option<String>,
+    #[serde(default)]
+    dimensions: BTreeMap<String, Dimension>,
+    #[serde(default)]
+    measures: BTreeMap<String, Measure>,
+    description: Option<String>,
+    #[serde(default)]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Vec<Expr>"))]
+    default_filters: Vec<Value>,
+    #[serde(default)]
+    #[cfg_attr(feature = "json-schema", schemars(with = "BTreeMap<String, Expr>"))]
+    segments: BTreeMap<String, Value>,
+    #[serde(default)]
+    #[cfg_attr(feature = "json-schema", schemars(with = "BTreeMap<String, Expr>"))]
+    access_policies: BTreeMap<String, Value>,
+    #[serde(default)]
+    hierarchies: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    entities: BTreeMap<String, Entity>,
 }
 
 impl<'de> Deserialize<'de> for SemanticTable {
@@ -24,27 +203,7 @@ impl<'de> Deserialize<'de> for SemanticTable {
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        #[serde(deny_unknown_fields)]
-        struct Raw {
-            data_source: String,
-            name: String,
-            table: String,
-            // Support both single key and composite keys
-            #[serde(default)]
-            primary_key: Option<String>,
-            #[serde(default)]
-            primary_keys: Option<Vec<String>>,
-            time_dimension: Option<String>,
-            smallest_time_grain: Option<TimeGrain>,
-            #[serde(default)]
-            dimensions: BTreeMap<String, Dimension>,
-            #[serde(default)]
-            measures: BTreeMap<String, Measure>,
-            description: Option<String>,
-        }
-
-        let raw = Raw::deserialize(deserializer)?;
+        let raw = SemanticTableFile::deserialize(deserializer)?;
 
         // Resolve primary keys: prefer primary_keys, fall back to primary_key
         let primary_keys = match (raw.primary_keys, raw.primary_key) {
@@ -57,16 +216,83 @@ impl<'de> Deserialize<'de> for SemanticTable {
             }
         };
 
+        let sources = [raw.sql.is_some(), raw.query.is_some(), raw.union.is_some()];
+        let derived = sources.iter().filter(|set| **set).count();
+        if derived > 1 {
+            return Err(de::Error::custom(
+                "sql, query and union are mutually exclusive",
+            ));
+        }
+        let table = match raw.table {
+            Some(table) => table,
+            None if derived == 1 => raw.name.clone(),
+            None => {
+                return Err(de::Error::custom(
+                    "one of table, sql, query or union must be specified",
+                ))
+            }
+        };
+
+        let default_filters = raw
+            .default_filters
+            .into_iter()
+            .map(|value| match value {
+                Value::String(s) => parse_expr(&s)
+                    .map_err(|e| de::Error::custom(format!("invalid default filter '{s}': {e}"))),
+                other => serde_json::from_value(other).map_err(de::Error::custom),
+            })
+            .collect::<Result<Vec<Expr>, D::Error>>()?;
+
+        let segments = raw
+            .segments
+            .into_iter()
+            .map(|(name, value)| {
+                let expr = match value {
+                    Value::String(s) => parse_expr(&s)
+                        .map_err(|e| de::Error::custom(format!("invalid segment '{name}': {e}"))),
+                    other => serde_json::from_value(other).map_err(de::Error::custom),
+                }?;
+                Ok((name, expr))
+            })
+            .collect::<Result<BTreeMap<String, Expr>, D::Error>>()?;
+
+        let access_policies = raw
+            .access_policies
+            .into_iter()
+            .map(|(attribute, value)| {
+                let expr = match value {
+                    Value::String(s) => parse_expr(&s).map_err(|e| {
+                        de::Error::custom(format!(
+                            "invalid access policy for attribute '{attribute}': {e}"
+                        ))
+                    }),
+                    other => serde_json::from_value(other).map_err(de::Error::custom),
+                }?;
+                Ok((attribute, expr))
+            })
+            .collect::<Result<BTreeMap<String, Expr>, D::Error>>()?;
+
         Ok(SemanticTable {
             data_source: raw.data_source,
             name: raw.name,
-            table: raw.table,
+            table,
+            sql: raw.sql,
+            query: raw.query,
+            union: raw.union,
             primary_keys,
+            unique_keys: raw.unique_keys,
+            nullable: raw.nullable,
             time_dimension: raw.time_dimension,
             smallest_time_grain: raw.smallest_time_grain,
+            timezone: raw.timezone,
             dimensions: raw.dimensions,
             measures: raw.measures,
             description: raw.description,
+            default_filters,
+            segments,
+            access_policies,
+            hierarchies: raw.hierarchies,
+            entities: raw.entities,
         })
     }
 }
@@ -76,6 +302,160 @@ pub struct Dimension {
     pub expr: Expr,
     pub data_type: Option<String>,
     pub description: Option<String>,
+    /// Free-form labels for catalog browsing (e.g. `finance`, `pii`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Other names users may search for, e.g. `revenue` for `order_total`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub synonyms: Vec<String>,
+    /// Display folder grouping related fields, e.g. `Customer/Geography`.
+    /// Model files may call it `group`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// Kept for existing queries but hidden from schema listings by request;
+    /// requests using it get a [`crate::deprecation::DeprecationWarning`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+    /// Display name for frontends, e.g. `Customer Country`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Display format for frontends, e.g. `yyyy-mm-dd`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Queryable, but not shown to end users, e.g. a join helper column.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+    /// `expr` is an array: the dimension takes each of its elements, one
+    /// row per element, through a lateral `UNNEST`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub unnest: bool,
+    /// Display labels keyed by stored value, e.g. `"1": Active`. The
+    /// dimension returns the label (NULL for unlisted values); keys that
+    /// parse as numbers are compared as numbers.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Dimension {
+    /// Whether the dimension is declared boolean (`data_type: boolean` or `bool`).
+    pub fn is_boolean(&self) -> bool {
+        self.data_type
+            .as_deref()
+            .is_some_and(|t| t.eq_ignore_ascii_case("boolean") || t.eq_ignore_ascii_case("bool"))
+    }
+
+    /// The expression selected for the dimension: `expr`, decoded through
+    /// a CASE when the dimension has `labels`.
+    pub fn labelled_expr(&self) -> Expr {
+        if self.labels.is_empty() {
+            return self.expr.clone();
+        }
+        let literal = |value: Value| Expr::Literal { value };
+        Expr::Case {
+            branches: self
+                .labels
+                .iter()
+                .map(|(stored, label)| CaseBranch {
+                    when: Expr::Binary {
+                        op: BinaryOp::Eq,
+                        left: Box::new(self.expr.clone()),
+                        right: Box::new(literal(stored_value(stored))),
+                    },
+                    then: literal(Value::String(label.clone())),
+                })
+                .collect(),
+            else_expr: Box::new(literal(Value::Null)),
+        }
+    }
+
+    /// The stored value displayed as `label`, if one is.
+    pub fn unlabel(&self, label: &Value) -> Option<Value> {
+        let label = label.as_str()?;
+        self.labels
+            .iter()
+            .find(|(_, l)| *l == label)
+            .map(|(stored, _)| stored_value(stored))
+    }
+}
+
+/// Lifecycle details of a deprecated flow or field, for a managed migration
+/// off it, e.g. a renamed metric.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Deprecation {
+    /// Model version or date it was deprecated in, e.g. `2024-06`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// Flow or field to use instead, e.g. `o.net_revenue`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// File form of `deprecated`: a flag, or the deprecation's details.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub(crate) enum DeprecatedFile {
+    Flag(bool),
+    Details(Deprecation),
+}
+
+impl DeprecatedFile {
+    pub(crate) fn into_deprecation(self) -> Option<Deprecation> {
+        match self {
+            DeprecatedFile::Flag(flag) => flag.then(Deprecation::default),
+            DeprecatedFile::Details(details) => Some(details),
+        }
+    }
+}
+
+fn deserialize_deprecated<'de, D>(deserializer: D) -> Result<Option<Deprecation>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<DeprecatedFile>::deserialize(deserializer)?
+        .and_then(DeprecatedFile::into_deprecation))
+}
+
+/// A `labels` key as a literal: a number when it parses as one.
+fn stored_value(key: &str) -> Value {
+    key.parse::<i64>()
+        .map(Value::from)
+        .or_else(|_| key.parse::<f64>().map(Value::from))
+        .unwrap_or_else(|_| Value::String(key.to_string()))
+}
+
+/// Full (non-shorthand) file form of [`Dimension`].
+#[derive(Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DimensionFile {
+    #[serde(default)]
+    expr: Option<Expr>,
+    /// Raw SQL instead of `expr`; see [`Expr::Sql`].
+    #[serde(default)]
+    sql: Option<String>,
+    data_type: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    synonyms: Vec<String>,
+    #[serde(alias = "group")]
+    folder: Option<String>,
+    #[serde(default)]
+    deprecated: Option<DeprecatedFile>,
+    label: Option<String>,
+    format: Option<String>,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    unnest: bool,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
 }
 
 impl<'de> Deserialize<'de> for Dimension {
@@ -89,20 +469,45 @@ impl<'de> Deserialize<'de> for Dimension {
                 expr: Expr::Column { column: s },
                 data_type: None,
                 description: None,
+                tags: Vec::new(),
+                synonyms: Vec::new(),
+                folder: None,
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
+                unnest: false,
+                labels: BTreeMap::new(),
             }),
             other => {
-                #[derive(Deserialize)]
-                #[serde(deny_unknown_fields)]
-                struct Full {
-                    expr: Expr,
-                    data_type: Option<String>,
-                    description: Option<String>,
-                }
-                let full = Full::deserialize(other).map_err(de::Error::custom)?;
+                let full = DimensionFile::deserialize(other).map_err(de::Error::custom)?;
+                let expr = match (full.expr, full.sql) {
+                    (Some(expr), None) => expr,
+                    (None, Some(sql)) => Expr::Sql { sql },
+                    (Some(_), Some(_)) => {
+                        return Err(de::Error::custom(
+                            "Dimension is invalid: cannot specify both 'expr' and 'sql'.",
+                        ))
+                    }
+                    (None, None) => {
+                        return Err(de::Error::custom(
+                            "Dimension is invalid: must specify either 'expr' or 'sql'.",
+                        ))
+                    }
+                };
                 Ok(Dimension {
-                    expr: full.expr,
+                    expr,
                     data_type: full.data_type,
                     description: full.description,
+                    tags: full.tags,
+                    synonyms: full.synonyms,
+                    folder: full.folder,
+                    deprecated: full.deprecated.and_then(DeprecatedFile::into_deprecation),
+                    label: full.label,
+                    format: full.format,
+                    hidden: full.hidden,
+                    unnest: full.unnest,
+                    labels: full.labels,
                 })
             }
         }
@@ -138,6 +543,29 @@ pub struct Measure {
     pub post_expr: Option<Expr>,
     pub data_type: Option<String>,
     pub description: Option<String>,
+    /// Free-form labels for catalog browsing (e.g. `finance`, `pii`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Other names users may search for, e.g. `revenue` for `order_total`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub synonyms: Vec<String>,
+    /// Display folder grouping related fields, e.g. `Revenue/Net`. Model
+    /// files may call it `group`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// Kept for existing queries but hidden from schema listings by request;
+    /// requests using it get a [`crate::deprecation::DeprecationWarning`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+    /// Display name for frontends, e.g. `Net Revenue`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Number format for frontends, e.g. `$0,0.00` or `percent`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Queryable, but not shown to end users, e.g. a formula's input.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
 }
 
 impl Measure {
@@ -152,28 +580,120 @@ impl Measure {
     }
 }
 
+/// File form of [`Measure`].
+#[derive(Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MeasureFile {
+    #[serde(default)]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<Expr>"))]
+    expr: Option<Value>,
+    /// Raw SQL to aggregate instead of `expr`; see [`Expr::Sql`].
+    #[serde(default)]
+    sql: Option<String>,
+    #[serde(default)]
+    agg: Option<Aggregation>,
+    #[serde(default)]
+    formula: Option<String>,
+    #[serde(default)]
+    ratio: Option<RatioFile>,
+    #[serde(default)]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<Expr>"))]
+    filter: Option<Value>,
+    #[serde(default)]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<Expr>"))]
+    post_expr: Option<Value>,
+    data_type: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    synonyms: Vec<String>,
+    #[serde(alias = "group")]
+    folder: Option<String>,
+    #[serde(default)]
+    deprecated: Option<DeprecatedFile>,
+    label: Option<String>,
+    format: Option<String>,
+    #[serde(default)]
+    hidden: bool,
+}
+
+/// `ratio:` sugar for a share-of or conversion-rate measure: one (usually
+/// filtered) aggregate over another, expanded into a `safe_divide` formula.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RatioFile {
+    numerator: RatioPart,
+    denominator: RatioPart,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+struct RatioPart {
+    /// Column to aggregate.
+    expr: String,
+    /// Formula aggregation name; `sum` by default.
+    #[serde(default = "RatioPart::default_agg")]
+    agg: String,
+    /// Formula condition the aggregated rows must meet.
+    #[serde(default)]
+    filter: Option<String>,
+}
+
+impl RatioPart {
+    fn default_agg() -> String {
+        "sum".to_string()
+    }
+
+    fn formula(&self) -> String {
+        let aggregate = format!("{}({})", self.agg, self.expr);
+        match &self.filter {
+            Some(filter) => format!("{aggregate} filter (where {filter})"),
+            None => aggregate,
+        }
+    }
+}
+
+impl RatioFile {
+    fn formula(&self) -> String {
+        format!(
+            "safe_divide({}, {})",
+            self.numerator.formula(),
+            self.denominator.formula()
+        )
+    }
+}
+
 impl<'de> Deserialize<'de> for Measure {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        #[derive(Deserialize)]
-        #[serde(deny_unknown_fields)]
-        struct Raw {
-            #[serde(default)]
-            expr: Option<Value>,
-            #[serde(default)]
-            agg: Option<Aggregation>,
-            #[serde(default)]
-            formula: Option<String>,
-            #[serde(default)]
-            filter: Option<Value>,
-            #[serde(default)]
-            post_expr: Option<Value>,
-            data_type: Option<String>,
-            description: Option<String>,
-        }
-        let raw = Raw::deserialize(deserializer)?;
+        let mut raw = MeasureFile::deserialize(deserializer)?;
+
+        if let Some(ratio) = raw.ratio.take() {
+            if raw.formula.is_some() {
+                return Err(de::Error::custom(
+                    "Measure is invalid: cannot specify both 'ratio' and 'formula'.",
+                ));
+            }
+            let formula = ratio.formula();
+            parse_formula(&formula)
+                .map_err(|e| de::Error::custom(format!("Measure is invalid: bad ratio: {e}")))?;
+            raw.formula = Some(formula);
+        }
+
+        if let Some(sql) = raw.sql.take() {
+            if raw.expr.is_some() {
+                return Err(de::Error::custom(
+                    "Measure is invalid: cannot specify both 'expr' and 'sql'.",
+                ));
+            }
+            raw.expr = Some(serde_json::json!({ "type": "sql", "sql": sql }));
+        }
 
         // Validate mutual exclusivity
         let has_simple = raw.expr.is_some() || raw.agg.is_some();
@@ -208,6 +728,15 @@ impl<'de> Deserialize<'de> for Measure {
             }
         }
 
+        if let Some(Aggregation::Percentile { p } | Aggregation::ApproxPercentile { p }) = &raw.agg
+        {
+            if !(0.0..=1.0).contains(p) {
+                return Err(de::Error::custom(format!(
+                    "Measure is invalid: percentile p must be between 0 and 1, got {p}"
+                )));
+            }
+        }
+
         // Formula measures cannot have filter (filter goes inside the formula)
         if has_formula && raw.filter.is_some() {
             return Err(de::Error::custom(
@@ -264,6 +793,13 @@ impl<'de> Deserialize<'de> for Measure {
             post_expr,
             data_type: raw.data_type,
             description: raw.description,
+            tags: raw.tags,
+            synonyms: raw.synonyms,
+            folder: raw.folder,
+            deprecated: raw.deprecated.and_then(DeprecatedFile::into_deprecation),
+            label: raw.label,
+            format: raw.format,
+            hidden: raw.hidden,
         })
     }
 }
@@ -293,6 +829,96 @@ pub enum Expr {
         left: Box<Expr>,
         right: Box<Expr>,
     },
+    /// `expr [NOT] IN (list...)`
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        #[serde(default)]
+        negated: bool,
+    },
+    /// `expr IS [NOT] NULL`
+    IsNull {
+        expr: Box<Expr>,
+        #[serde(default)]
+        negated: bool,
+    },
+    /// `expr [NOT] BETWEEN low AND high` (inclusive on both ends)
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        #[serde(default)]
+        negated: bool,
+    },
+    /// `NOT expr`
+    Not {
+        expr: Box<Expr>,
+    },
+    /// `{{ name }}`: a flow parameter, replaced by the request's value.
+    Param {
+        name: String,
+    },
+    /// Dialect-specific SQL passed through as written, with `${TABLE}`
+    /// replaced by the table's alias. Its columns are not validated.
+    Sql {
+        sql: String,
+    },
+}
+
+/// Object form of [`Expr`] in model files.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum TaggedExpr {
+    Column {
+        column: String,
+    },
+    Literal {
+        value: Value,
+    },
+    MeasureRef {
+        name: String,
+    },
+    Func {
+        func: Function,
+        args: Vec<Expr>,
+    },
+    Case {
+        branches: Vec<CaseBranch>,
+        else_expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        #[serde(default)]
+        negated: bool,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        #[serde(default)]
+        negated: bool,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        #[serde(default)]
+        negated: bool,
+    },
+    Not {
+        expr: Box<Expr>,
+    },
+    Param {
+        name: String,
+    },
+    Sql {
+        sql: String,
+    },
 }
 
 impl<'de> Deserialize<'de> for Expr {
@@ -302,7 +928,12 @@ impl<'de> Deserialize<'de> for Expr {
     {
         let value = serde_json::Value::deserialize(deserializer)?;
         match value {
-            Value::String(s) => Ok(Expr::Column { column: s }),
+            Value::String(s) => Ok(match parse_param_ref(&s) {
+                Some(name) => Expr::Param {
+                    name: name.to_string(),
+                },
+                None => Expr::Column { column: s },
+            }),
             Value::Object(map) if map.len() == 1 && map.contains_key("measure") => {
                 let name = map
                     .get("measure")
@@ -313,32 +944,6 @@ impl<'de> Deserialize<'de> for Expr {
                 })
             }
             other => {
-                #[derive(Deserialize)]
-                #[serde(tag = "type", rename_all = "snake_case")]
-                enum TaggedExpr {
-                    Column {
-                        column: String,
-                    },
-                    Literal {
-                        value: Value,
-                    },
-                    MeasureRef {
-                        name: String,
-                    },
-                    Func {
-                        func: Function,
-                        args: Vec<Expr>,
-                    },
-                    Case {
-                        branches: Vec<CaseBranch>,
-                        else_expr: Box<Expr>,
-                    },
-                    Binary {
-                        op: BinaryOp,
-                        left: Box<Expr>,
-                        right: Box<Expr>,
-                    },
-                }
                 let tagged: TaggedExpr =
                     TaggedExpr::deserialize(other).map_err(de::Error::custom)?;
                 Ok(match tagged {
@@ -354,18 +959,44 @@ impl<'de> Deserialize<'de> for Expr {
                         else_expr,
                     },
                     TaggedExpr::Binary { op, left, right } => Expr::Binary { op, left, right },
+                    TaggedExpr::InList {
+                        expr,
+                        list,
+                        negated,
+                    } => Expr::InList {
+                        expr,
+                        list,
+                        negated,
+                    },
+                    TaggedExpr::IsNull { expr, negated } => Expr::IsNull { expr, negated },
+                    TaggedExpr::Between {
+                        expr,
+                        low,
+                        high,
+                        negated,
+                    } => Expr::Between {
+                        expr,
+                        low,
+                        high,
+                        negated,
+                    },
+                    TaggedExpr::Not { expr } => Expr::Not { expr },
+                    TaggedExpr::Param { name } => Expr::Param { name },
+                    TaggedExpr::Sql { sql } => Expr::Sql { sql },
                 })
             }
         }
     }
 }
 
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CaseBranch {
     pub when: Expr,
     pub then: Expr,
 }
 
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Function {
@@ -420,6 +1051,15 @@ pub enum Function {
     EndsWith,
     /// Check if string contains substring
     Contains,
+    /// Check if some part of a string matches a regular expression:
+    /// RegexpMatches(str, pattern)
+    RegexpMatches,
+    /// First match of a regular expression, or of its capture group when it
+    /// has one: RegexpExtract(str, pattern)
+    RegexpExtract,
+    /// Replace every match of a regular expression: RegexpReplace(str,
+    /// pattern, replacement), with `\1` referring to capture groups
+    RegexpReplace,
 
     // === Null Handling ===
     Coalesce,
@@ -454,6 +1094,14 @@ pub enum Function {
     /// Sign (-1, 0, 1)
     Sign,
 
+    // === Geo Functions ===
+    /// WGS84 point: StPoint(longitude, latitude)
+    StPoint,
+    /// Distance in meters on the sphere: StDistance(a, b)
+    StDistance,
+    /// Check if a geography lies within another: StWithin(inner, outer)
+    StWithin,
+
     // === Type Conversion ===
     Cast {
         data_type: String,
@@ -462,8 +1110,49 @@ pub enum Function {
     TryCast {
         data_type: String,
     },
+
+    // === Bucketing ===
+    /// Group a numeric value into ranges
+    Bucket(BucketSpec),
 }
 
+/// How a numeric value is grouped into ranges by [`Function::Bucket`].
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketSpec {
+    /// Fixed-width ranges; renders the lower bound, e.g. `floor(x / 10) * 10`.
+    Width(f64),
+    /// Custom ascending edges; renders range labels like `[0-10)`, `[10-50)`,
+    /// plus `< first` and `>= last` for values outside the edges.
+    Edges(Vec<f64>),
+}
+
+impl BucketSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            BucketSpec::Width(width) => {
+                if !(width.is_finite() && *width > 0.0) {
+                    return Err(format!("bucket width must be positive, got {width}"));
+                }
+            }
+            BucketSpec::Edges(edges) => {
+                if edges.is_empty() {
+                    return Err("bucket edges must not be empty".to_string());
+                }
+                if edges.iter().any(|e| !e.is_finite()) {
+                    return Err("bucket edges must be finite numbers".to_string());
+                }
+                if edges.windows(2).any(|w| w[0] >= w[1]) {
+                    return Err("bucket edges must be strictly ascending".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum BinaryOp {
@@ -482,6 +1171,7 @@ pub enum BinaryOp {
     Or,
 }
 
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Aggregation {
@@ -493,6 +1183,14 @@ pub enum Aggregation {
     Max,
     Avg,
 
+    // === Boolean Aggregations (over a predicate expression) ===
+    /// Rows where the expression is true
+    CountIf,
+    /// Whether the expression is true on every row
+    BoolAnd,
+    /// Whether the expression is true on any row
+    BoolOr,
+
     // === Statistical Aggregations ===
     /// Median (50th percentile)
     Median,
@@ -504,6 +1202,14 @@ pub enum Aggregation {
     Variance,
     /// Variance (sample)
     VarianceSamp,
+    /// Continuous percentile, `p` between 0 and 1 (0.95 for P95)
+    Percentile {
+        p: f64,
+    },
+    /// Approximate percentile, cheaper on large tables
+    ApproxPercentile {
+        p: f64,
+    },
 
     // === List/String Aggregations ===
     /// Concatenate strings with separator
@@ -517,11 +1223,50 @@ pub enum Aggregation {
     /// Approximate count distinct using HyperLogLog
     ApproxCountDistinct,
 
-    // === First/Last (requires ORDER BY in aggregate) ===
-    /// First value in group
-    First,
-    /// Last value in group
-    Last,
+    // === First/Last (by an ordering column of the measure's table) ===
+    /// Value on the group's first row by `order_by`
+    First {
+        order_by: String,
+        #[serde(default)]
+        direction: SortDirection,
+    },
+    /// Value on the group's last row by `order_by`
+    Last {
+        order_by: String,
+        #[serde(default)]
+        direction: SortDirection,
+    },
+}
+
+impl Aggregation {
+    /// The aggregation with a `first`/`last` ordering column qualified by
+    /// the table `alias`, so it stays unambiguous across joins.
+    pub(crate) fn qualify_order_by(&self, alias: &str) -> Aggregation {
+        let qualify = |order_by: &String| {
+            if order_by.contains('.') {
+                order_by.clone()
+            } else {
+                format!("{alias}.{order_by}")
+            }
+        };
+        match self {
+            Aggregation::First {
+                order_by,
+                direction,
+            } => Aggregation::First {
+                order_by: qualify(order_by),
+                direction: direction.clone(),
+            },
+            Aggregation::Last {
+                order_by,
+                direction,
+            } => Aggregation::Last {
+                order_by: qualify(order_by),
+                direction: direction.clone(),
+            },
+            other => other.clone(),
+        }
+    }
 }
 
 // ============================================================================
@@ -569,18 +1314,155 @@ pub enum FormulaAst {
     },
     /// Function call: `round(x, 2)`, `coalesce(a, b)`
     Function { name: String, args: Vec<FormulaAst> },
+    /// Membership test: `status in ('a', 'b')`, `status not in ('a')`
+    InList {
+        expr: Box<FormulaAst>,
+        list: Vec<FormulaAst>,
+        #[serde(default)]
+        negated: bool,
+    },
+    /// Null test: `x is null`, `x is not null`
+    IsNull {
+        expr: Box<FormulaAst>,
+        #[serde(default)]
+        negated: bool,
+    },
+    /// Inclusive range test: `x between 1 and 10`
+    Between {
+        expr: Box<FormulaAst>,
+        low: Box<FormulaAst>,
+        high: Box<FormulaAst>,
+        #[serde(default)]
+        negated: bool,
+    },
+    /// Logical negation: `not x`
+    Not { expr: Box<FormulaAst> },
+    /// Conditional: `case when x > 0 then 'pos' else 'neg' end`, `if(c, a, b)`
+    Case {
+        branches: Vec<(FormulaAst, FormulaAst)>,
+        /// `NULL` when omitted
+        #[serde(default)]
+        else_expr: Option<Box<FormulaAst>>,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Time grains, declared from finest to coarsest so they order by size.
+///
+/// The `fiscal_*` grains follow the flow's [`FiscalCalendar`]; without one
+/// they match their calendar counterparts.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum TimeGrain {
+    Second,
+    Minute,
+    Hour,
     Day,
     Week,
+    FiscalWeek,
     Month,
+    FiscalMonth,
     Quarter,
+    FiscalQuarter,
     Year,
+    FiscalYear,
+}
+
+impl TimeGrain {
+    /// Every grain, finest first.
+    pub const ALL: [TimeGrain; 12] = [
+        TimeGrain::Second,
+        TimeGrain::Minute,
+        TimeGrain::Hour,
+        TimeGrain::Day,
+        TimeGrain::Week,
+        TimeGrain::FiscalWeek,
+        TimeGrain::Month,
+        TimeGrain::FiscalMonth,
+        TimeGrain::Quarter,
+        TimeGrain::FiscalQuarter,
+        TimeGrain::Year,
+        TimeGrain::FiscalYear,
+    ];
+
+    pub fn is_fiscal(&self) -> bool {
+        matches!(
+            self,
+            TimeGrain::FiscalWeek
+                | TimeGrain::FiscalMonth
+                | TimeGrain::FiscalQuarter
+                | TimeGrain::FiscalYear
+        )
+    }
+
+    /// The calendar grain of the same nominal length (`fiscal_quarter` →
+    /// `quarter`); calendar grains map to themselves.
+    pub fn calendar(&self) -> TimeGrain {
+        match self {
+            TimeGrain::FiscalWeek => TimeGrain::Week,
+            TimeGrain::FiscalMonth => TimeGrain::Month,
+            TimeGrain::FiscalQuarter => TimeGrain::Quarter,
+            TimeGrain::FiscalYear => TimeGrain::Year,
+            other => other.clone(),
+        }
+    }
 }
 
+/// A flow's fiscal calendar, which the `fiscal_*` time grains follow.
+///
+/// The fiscal year starts on the first day of `start_month`. Without a
+/// `week_pattern`, fiscal quarters are three calendar months from there and
+/// fiscal months are calendar months. With one, fiscal quarters are 13-week
+/// blocks split into months of the pattern's weeks; the last quarter and
+/// its last month run to the end of the fiscal year. Fiscal weeks count
+/// seven days from the start of the fiscal year.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FiscalCalendar {
+    /// Month (1-12) the fiscal year starts in.
+    #[serde(default = "default_fiscal_start_month")]
+    pub start_month: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub week_pattern: Option<WeekPattern>,
+}
+
+fn default_fiscal_start_month() -> u32 {
+    1
+}
+
+impl Default for FiscalCalendar {
+    fn default() -> Self {
+        FiscalCalendar {
+            start_month: default_fiscal_start_month(),
+            week_pattern: None,
+        }
+    }
+}
+
+/// Weeks in each month of a 13-week fiscal quarter.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WeekPattern {
+    #[serde(rename = "4-4-5")]
+    FourFourFive,
+    #[serde(rename = "4-5-4")]
+    FourFiveFour,
+    #[serde(rename = "5-4-4")]
+    FiveFourFour,
+}
+
+impl WeekPattern {
+    pub fn weeks(self) -> [u32; 3] {
+        match self {
+            WeekPattern::FourFourFive => [4, 4, 5],
+            WeekPattern::FourFiveFour => [4, 5, 4],
+            WeekPattern::FiveFourFour => [5, 4, 4],
+        }
+    }
+}
+
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct SemanticFlow {
@@ -589,8 +1471,258 @@ pub struct SemanticFlow {
     #[serde(default)]
     pub joins: BTreeMap<String, FlowJoin>,
     pub description: Option<String>,
+    /// Named parameters referenced as `{{ name }}` in the flow's dimension and
+    /// measure expressions, supplied per query via `QueryRequest::params`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, FlowParam>,
+    /// Free-form labels for catalog browsing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Other names users may search for, e.g. `revenue` for a `sales` flow.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub synonyms: Vec<String>,
+    /// Guardrails the planner enforces on every request of the flow.
+    #[serde(default, skip_serializing_if = "FlowLimits::is_empty")]
+    pub limits: FlowLimits,
+    /// Summary tables, by name, that requests covered by their grain are
+    /// answered from instead of the base table.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aggregates: BTreeMap<String, FlowAggregate>,
+    /// Calendar of the `fiscal_*` time grains; calendar years by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fiscal_calendar: Option<FiscalCalendar>,
+    /// Time zone of the flow's time truncations, overriding its tables'.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// How measures are kept correct when a join can repeat their rows.
+    #[serde(default, skip_serializing_if = "FanoutStrategy::is_default")]
+    pub fanout_strategy: FanoutStrategy,
+    /// Filters every request of the flow inherits, resolved like request
+    /// filters, unless the request sets `ignore_default_filters`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Vec<serde_json::Value>"))]
+    pub default_filters: Vec<FilterExpr>,
+    /// Named filters over the flow's fields, resolved like request filters
+    /// when listed in [`QueryRequest::segments`]. They take precedence over
+    /// the tables' segments of the same name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "BTreeMap<String, serde_json::Value>")
+    )]
+    pub segments: BTreeMap<String, FilterExpr>,
+    /// Flow this one was built from; see
+    /// [`crate::model_files::resolve_flow_extends`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Kept for existing requests, which get a
+    /// [`crate::deprecation::DeprecationWarning`]; `true` or the details.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_deprecated",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[cfg_attr(feature = "json-schema", schemars(with = "Option<DeprecatedFile>"))]
+    pub deprecated: Option<Deprecation>,
+}
+
+/// How the planner keeps measures correct when joins fan out their rows.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FanoutStrategy {
+    /// Aggregate each table in its own CTE before joining.
+    #[default]
+    PreAggregate,
+    /// Stay flat, computing sums, counts and averages over each table's
+    /// distinct primary keys (Looker-style symmetric aggregates). Requests
+    /// this cannot express fall back to pre-aggregation.
+    Symmetric,
+}
+
+impl FanoutStrategy {
+    fn is_default(&self) -> bool {
+        *self == FanoutStrategy::PreAggregate
+    }
+}
+
+/// An existing summary table holding the flow's measures pre-aggregated to
+/// a coarser grain.
+///
+/// The table is read from the base table's data source. Its rows are assumed
+/// to already reflect the flow's joins and the tables' `default_filters`.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlowAggregate {
+    /// Physical table name, e.g. `analytics.orders_daily`.
+    pub table: String,
+    /// Summary column per dimension of the grain, keyed by the flow field
+    /// name (e.g. `c.country`).
+    #[serde(default)]
+    pub dimensions: BTreeMap<String, String>,
+    /// Summary column per measure. Sums and counts are re-aggregated with
+    /// `sum`, minimums and maximums with `min`/`max`; other aggregations
+    /// cannot be rolled up and are not answered from the table.
+    #[serde(default)]
+    pub measures: BTreeMap<String, String>,
+    /// Column holding the base table's time dimension truncated to `grain`.
+    #[serde(default)]
+    pub time_column: Option<String>,
+    /// Grain of `time_column`; requests may group by it or any coarser grain.
+    #[serde(default)]
+    pub grain: Option<TimeGrain>,
+}
+
+/// Per-flow request guardrails, so an unfiltered request on a very large
+/// flow is rejected instead of scanning the whole table.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlowLimits {
+    /// Largest `limit` (or `page_size`) a request may ask for. Requests
+    /// without one are limited to this many rows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows: Option<u64>,
+    /// Dimensions every request must filter on with `==`, `in` or a range.
+    /// A `time_filter` counts as a filter on the base table's time dimension.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub require_filter_on: Vec<String>,
+    /// Widest time range a request may cover, from its `time_filter` or
+    /// lower and upper bound filters on the base table's time dimension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_time_range_days: Option<u32>,
+}
+
+impl FlowLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl SemanticFlow {
+    /// Alias of the join playing `role`, or `qualifier` itself when no
+    /// join has that role.
+    pub fn role_alias<'a>(&'a self, qualifier: &'a str) -> &'a str {
+        self.joins
+            .values()
+            .find(|join| join.role.as_deref() == Some(qualifier))
+            .map_or(qualifier, |join| join.alias.as_str())
+    }
+
+    /// How fields of the table under `alias` are qualified: its join's role
+    /// when it has one, otherwise the alias.
+    pub fn qualifier<'a>(&'a self, alias: &'a str) -> &'a str {
+        self.joins
+            .values()
+            .find(|join| join.alias == alias)
+            .and_then(|join| join.role.as_deref())
+            .unwrap_or(alias)
+    }
+
+    /// Check `supplied` against the declared params and fill in defaults.
+    /// Declared params without a value or default are left out, rendering as NULL.
+    pub fn resolve_params(
+        &self,
+        supplied: &BTreeMap<String, Value>,
+    ) -> Result<BTreeMap<String, Value>, String> {
+        if let Some(name) = supplied.keys().find(|k| !self.params.contains_key(*k)) {
+            return Err(format!("flow {} has no parameter named {name}", self.name));
+        }
+        let mut resolved = BTreeMap::new();
+        for (name, param) in &self.params {
+            let Some(value) = supplied.get(name).or(param.default.as_ref()) else {
+                continue;
+            };
+            param
+                .check(value)
+                .map_err(|e| format!("parameter {name}: {e}"))?;
+            resolved.insert(name.clone(), value.clone());
+        }
+        Ok(resolved)
+    }
+}
+
+/// A typed flow parameter.
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlowParam {
+    #[serde(rename = "type")]
+    pub data_type: ParamType,
+    /// Value used when the request does not supply one.
+    #[serde(default)]
+    pub default: Option<Value>,
+    /// When non-empty, the only values a request may supply.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_values: Vec<Value>,
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
+impl FlowParam {
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        if !self.data_type.matches(value) {
+            return Err(format!(
+                "expected a {} value, got {value}",
+                self.data_type.as_str()
+            ));
+        }
+        if !self.allowed_values.is_empty() && !self.allowed_values.contains(value) {
+            return Err(format!("{value} is not one of the allowed values"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    /// An ISO `YYYY-MM-DD` string.
+    Date,
+}
+
+impl ParamType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParamType::String => "string",
+            ParamType::Number => "number",
+            ParamType::Integer => "integer",
+            ParamType::Boolean => "boolean",
+            ParamType::Date => "date",
+        }
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            ParamType::String => value.is_string(),
+            ParamType::Number => value.is_number(),
+            ParamType::Integer => value.is_i64() || value.is_u64(),
+            ParamType::Boolean => value.is_boolean(),
+            ParamType::Date => value.as_str().is_some_and(is_iso_date),
+        }
+    }
+}
+
+fn is_iso_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return false;
+    };
+    let digits = |p: &str, len: usize| p.len() == len && p.bytes().all(|b| b.is_ascii_digit());
+    digits(year, 4)
+        && digits(month, 2)
+        && digits(day, 2)
+        && (1..=12).contains(&month.parse::<u8>().unwrap_or(0))
+        && (1..=31).contains(&day.parse::<u8>().unwrap_or(0))
+}
+
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct FlowTableRef {
@@ -598,22 +1730,74 @@ pub struct FlowTableRef {
     pub alias: String,
 }
 
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct FlowJoin {
     pub semantic_table: String,
     pub alias: String,
+    /// Alias the join attaches to. A join `through` a bridge attaches to the
+    /// bridge; `to_table` may then be left out or name the bridge's own
+    /// `to_table`.
+    #[serde(default)]
     pub to_table: String,
     pub join_type: JoinType,
+    /// Column pairs of the ON clause. Left empty, they are inferred from an
+    /// entity both tables declare.
+    #[serde(default)]
     pub join_keys: Vec<JoinKey>,
     /// Optional cardinality hint. If not provided, inferred from primary keys.
     /// Use this when the system can't correctly infer the relationship.
     #[serde(default)]
     pub cardinality: Option<JoinCardinality>,
+    /// Alias of the bridge join a many-to-many join goes through, e.g.
+    /// `order_tags` between orders and tags. `join_keys` pair bridge columns
+    /// (left) with this table's, and measures on the near side count each
+    /// row once per group rather than once per bridge row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub through: Option<String>,
+    /// Bridge column allocating a row's measures across the rows it bridges
+    /// to, e.g. `0.5` for an order with two tags. Sums and counts are then
+    /// weighted instead of deduplicated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<String>,
+    /// What the table stands for in this join when the flow joins it more
+    /// than once, e.g. `ship_date` and `order_date` against one `dates`
+    /// table. Requests may qualify the join's fields with the role instead
+    /// of the alias (`ship_date.month`), and schemas list them that way.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Predicate ANDed into the ON clause after the key pairs, e.g.
+    /// `o.created_at BETWEEN c.valid_from AND c.valid_to` to pick the
+    /// version of a slowly changing dimension current at the time. Bare
+    /// columns are this table's.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_join_condition",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub extra_conditions: Option<Expr>,
     pub description: Option<String>,
 }
 
+/// A join condition given as an expression string or a tagged `Expr`.
+fn deserialize_join_condition<'de, D>(deserializer: D) -> Result<Option<Expr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::String(s)) => parse_expr(&s)
+            .map(Some)
+            .map_err(|e| de::Error::custom(format!("invalid join condition '{s}': {e}"))),
+        Some(other) => serde_json::from_value(other)
+            .map(Some)
+            .map_err(de::Error::custom),
+        None => Ok(None),
+    }
+}
+
 /// Cardinality of a join relationship (user-specified hint).
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum JoinCardinality {
@@ -627,6 +1811,7 @@ pub enum JoinCardinality {
     ManyToMany,
 }
 
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct JoinKey {
@@ -634,6 +1819,7 @@ pub struct JoinKey {
     pub right: String,
 }
 
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum JoinType {
@@ -647,12 +1833,20 @@ pub enum JoinType {
 #[serde(deny_unknown_fields)]
 pub struct QueryRequest {
     pub flow: String,
+    /// Dimension names, or `{field, grain}` objects truncating a time
+    /// dimension to the requested grain.
     #[serde(default)]
-    pub dimensions: Vec<String>,
+    pub dimensions: Vec<DimensionRequest>,
     #[serde(default)]
     pub measures: Vec<String>,
+    /// Row-level filters, combined with AND. Entries may be nested
+    /// `{all: [...]}` / `{any: [...]}` / `{not: ...}` groups.
     #[serde(default)]
-    pub filters: Vec<Filter>,
+    pub filters: Vec<FilterExpr>,
+    /// Named segments applied as filters, combined with AND: the flow's
+    /// own, or a table's, optionally qualified as `alias.segment`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<String>,
     #[serde(default)]
     pub order: Vec<OrderItem>,
     /// Maximum total rows to return. Caps the result set.
@@ -669,6 +1863,297 @@ pub struct QueryRequest {
     /// Cursor from a previous paginated response. Use to fetch subsequent pages.
     #[serde(default)]
     pub cursor: Option<String>,
+    /// Count the rows of the whole result and report them as `total_rows`
+    /// on every page. Backends without a native count run an extra
+    /// `COUNT(*)` query, once per query; ignored without `page_size`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub count_total: bool,
+    /// Caller workload tags (e.g. team, user) forwarded to the backend for
+    /// cost attribution when tagging is enabled. Does not affect the SQL.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// Numeric dimensions to group into ranges, keyed by requested dimension name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bins: BTreeMap<String, BucketSpec>,
+    /// Relative date range (e.g. `last_30_days`, `previous_month`, `ytd`) applied
+    /// to the base table's `time_dimension`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_filter: Option<RelativeDateRange>,
+    /// Emit a row for every time bucket between the first and last result,
+    /// even when no rows fall in it. Requires one time-grained dimension.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fill_gaps: bool,
+    /// How `fill_gaps` fills each measure in generated buckets, keyed by
+    /// requested measure name. Unlisted measures stay NULL.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub gap_fill: BTreeMap<String, GapFill>,
+    /// Add a grand total row aggregated over every requested dimension.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub totals: bool,
+    /// Requested dimensions to subtotal. Each adds rows grouped by the
+    /// dimensions up to and including it, in request order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtotals: Vec<String>,
+    /// Post-aggregation calculations (rank, difference, percent change) added
+    /// as extra output columns. `order` may reference them by name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calculations: Vec<TableCalculation>,
+    /// Move a requested dimension up or down its table's hierarchy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drill: Option<Drill>,
+    /// Skip the `default_filters` declared on the flow's tables, e.g. to
+    /// include soft-deleted rows in an audit query.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ignore_default_filters: bool,
+    /// Values for the flow's declared `params`, keyed by parameter name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, Value>,
+    /// Cancel the query after this many milliseconds, overriding the
+    /// datasource's `query.timeout_ms`. 0 disables the timeout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_timeout_ms: Option<u64>,
+    /// IANA time zone time grains truncate in, e.g. to bucket days by the
+    /// viewer's local midnight. Overrides the flow's and tables' `timezone`;
+    /// dimensions declared `data_type: date` are never converted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Plan the request must use instead of the planner's choice, for when
+    /// its fanout heuristics misfire. Rejected when unsafe for the request.
+    #[serde(default, skip_serializing_if = "StrategyOverride::is_auto")]
+    pub strategy: StrategyOverride,
+}
+
+/// Plan strategy forced by a request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrategyOverride {
+    /// Let the planner choose.
+    #[default]
+    Auto,
+    /// One query joining the flow's tables, without summary tables or
+    /// pre-aggregation CTEs.
+    Flat,
+    /// Aggregate measures in CTEs below their joins, without summary tables.
+    #[serde(rename = "preagg", alias = "pre_aggregated")]
+    PreAggregate,
+}
+
+impl StrategyOverride {
+    fn is_auto(&self) -> bool {
+        *self == StrategyOverride::Auto
+    }
+}
+
+/// Drill along a hierarchy: the requested `dimension`, one of its levels,
+/// is replaced by every level from the top of the hierarchy down to `level`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Drill {
+    /// Requested dimension to drill from, e.g. `c.country`.
+    pub dimension: String,
+    /// Level of the same hierarchy to drill to, finer or coarser.
+    pub level: String,
+}
+
+/// A calculation over the aggregated result rows, output as column `name`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableCalculation {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: CalculationKind,
+    /// Requested measure the calculation reads.
+    pub measure: String,
+    /// Requested dimensions that restart the calculation, e.g. rank within
+    /// each country.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partition_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalculationKind {
+    /// Rank by the measure, largest first; ties share a rank.
+    Rank,
+    /// Measure minus its value in the previous row of the request's `order`.
+    Difference,
+    /// `(current - previous) / previous` along the request's `order`.
+    PercentChange,
+}
+
+/// Value given to a measure in a time bucket added by `fill_gaps`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GapFill {
+    /// Leave the measure NULL.
+    #[default]
+    Null,
+    /// Report zero, e.g. for counts and sums.
+    #[serde(alias = "0")]
+    Zero,
+    /// Carry forward the last non-NULL value, e.g. for balances.
+    Previous,
+}
+
+/// Measures from several flows blended on shared (conformed) dimensions.
+///
+/// Each flow is queried separately with the same dimensions and the results
+/// are full-outer-joined on those dimensions, so a dimension value missing
+/// from one flow still appears with NULL measures for that flow.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BlendRequest {
+    /// Dimensions every flow is grouped by. Each must resolve in every flow.
+    #[serde(default)]
+    pub dimensions: Vec<DimensionRequest>,
+    /// Per-flow measures and filters, keyed by flow name.
+    pub flows: BTreeMap<String, BlendFlow>,
+    /// Filters applied to every flow, typically on the shared dimensions.
+    #[serde(default)]
+    pub filters: Vec<FilterExpr>,
+    /// Ordering of the blended rows by dimension or measure name.
+    #[serde(default)]
+    pub order: Vec<OrderItem>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// Timeout applied to each flow's query; see [`QueryRequest::query_timeout_ms`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_timeout_ms: Option<u64>,
+    /// Time zone of each flow's time grains; see [`QueryRequest::timezone`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+/// One flow's part of a [`BlendRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BlendFlow {
+    pub measures: Vec<String>,
+    /// Filters applied to this flow only, in addition to the shared filters.
+    #[serde(default)]
+    pub filters: Vec<FilterExpr>,
+    /// Values for this flow's declared `params`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, Value>,
+}
+
+impl BlendRequest {
+    /// The per-flow requests this blend executes, in flow name order.
+    pub fn flow_requests(&self) -> Vec<QueryRequest> {
+        self.flows
+            .iter()
+            .map(|(flow, part)| QueryRequest {
+                flow: flow.clone(),
+                dimensions: self.dimensions.clone(),
+                measures: part.measures.clone(),
+                filters: self.filters.iter().chain(&part.filters).cloned().collect(),
+                tags: self.tags.clone(),
+                params: part.params.clone(),
+                query_timeout_ms: self.query_timeout_ms,
+                timezone: self.timezone.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+/// A date range relative to the current date, parsed from strings such as
+/// `today`, `last_30_days`, `trailing_12_months`, `this_quarter`,
+/// `previous_month`, or `ytd`. Ranges are half-open: start inclusive, end exclusive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum RelativeDateRange {
+    Today,
+    Yesterday,
+    /// `last_N_<unit>` / `trailing_N_<unit>`: the N units ending today (inclusive).
+    Last {
+        count: u32,
+        unit: TimeGrain,
+    },
+    /// `this_<grain>`: the whole current period.
+    Current(TimeGrain),
+    /// `previous_<grain>`: the whole period before the current one.
+    Previous(TimeGrain),
+    /// `ytd` / `qtd` / `mtd` / `wtd`: start of the current period through today.
+    ToDate(TimeGrain),
+}
+
+impl TryFrom<String> for RelativeDateRange {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let s = raw.trim().to_ascii_lowercase();
+        let grain = |name: &str| -> Option<TimeGrain> {
+            match name.strip_suffix('s').unwrap_or(name) {
+                "day" => Some(TimeGrain::Day),
+                "week" => Some(TimeGrain::Week),
+                "month" => Some(TimeGrain::Month),
+                "quarter" => Some(TimeGrain::Quarter),
+                "year" => Some(TimeGrain::Year),
+                _ => None,
+            }
+        };
+        let parsed = match s.as_str() {
+            "today" => Some(RelativeDateRange::Today),
+            "yesterday" => Some(RelativeDateRange::Yesterday),
+            "wtd" => Some(RelativeDateRange::ToDate(TimeGrain::Week)),
+            "mtd" => Some(RelativeDateRange::ToDate(TimeGrain::Month)),
+            "qtd" => Some(RelativeDateRange::ToDate(TimeGrain::Quarter)),
+            "ytd" => Some(RelativeDateRange::ToDate(TimeGrain::Year)),
+            _ => {
+                let parts: Vec<&str> = s.split('_').collect();
+                match parts.as_slice() {
+                    ["last" | "trailing", count, unit] => count
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|c| *c > 0)
+                        .zip(grain(unit))
+                        .map(|(count, unit)| RelativeDateRange::Last { count, unit }),
+                    ["this", g] if !g.ends_with('s') => grain(g).map(RelativeDateRange::Current),
+                    ["previous", g] if !g.ends_with('s') => {
+                        grain(g).map(RelativeDateRange::Previous)
+                    }
+                    _ => None,
+                }
+            }
+        };
+        parsed.ok_or_else(|| {
+            format!(
+                "invalid relative date range '{raw}': expected today, yesterday, \
+                 last_<n>_<unit>, trailing_<n>_<unit>, this_<grain>, previous_<grain>, \
+                 wtd, mtd, qtd or ytd"
+            )
+        })
+    }
+}
+
+impl From<RelativeDateRange> for String {
+    fn from(range: RelativeDateRange) -> Self {
+        let grain = |g: &TimeGrain| match g {
+            TimeGrain::Second => "second",
+            TimeGrain::Minute => "minute",
+            TimeGrain::Hour => "hour",
+            TimeGrain::Day => "day",
+            TimeGrain::Week => "week",
+            TimeGrain::FiscalWeek => "fiscal_week",
+            TimeGrain::Month => "month",
+            TimeGrain::FiscalMonth => "fiscal_month",
+            TimeGrain::Quarter => "quarter",
+            TimeGrain::FiscalQuarter => "fiscal_quarter",
+            TimeGrain::Year => "year",
+            TimeGrain::FiscalYear => "fiscal_year",
+        };
+        match &range {
+            RelativeDateRange::Today => "today".to_string(),
+            RelativeDateRange::Yesterday => "yesterday".to_string(),
+            RelativeDateRange::Last { count, unit } => format!("last_{count}_{}s", grain(unit)),
+            RelativeDateRange::Current(g) => format!("this_{}", grain(g)),
+            RelativeDateRange::Previous(g) => format!("previous_{}", grain(g)),
+            RelativeDateRange::ToDate(g) => format!("{}td", &grain(g)[..1]),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -676,9 +2161,154 @@ pub struct QueryRequest {
 pub struct Filter {
     pub field: String,
     pub op: FilterOp,
+    /// Comparison value. Not needed for `is true` / `is false` / `is unknown`.
+    #[serde(default)]
     pub value: serde_json::Value,
 }
 
+/// A single filter condition or a boolean group of conditions.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum FilterExpr {
+    Condition(Filter),
+    /// Every member must match (AND).
+    All {
+        all: Vec<FilterExpr>,
+    },
+    /// At least one member must match (OR).
+    Any {
+        any: Vec<FilterExpr>,
+    },
+    /// The member must not match.
+    Not {
+        not: Box<FilterExpr>,
+    },
+}
+
+/// A requested dimension: a plain name, or a time dimension with a grain
+/// override (`{field: "created_at", grain: "week"}`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DimensionRequest {
+    pub field: String,
+    pub grain: Option<TimeGrain>,
+}
+
+impl From<&str> for DimensionRequest {
+    fn from(field: &str) -> Self {
+        DimensionRequest {
+            field: field.to_string(),
+            grain: None,
+        }
+    }
+}
+
+impl From<String> for DimensionRequest {
+    fn from(field: String) -> Self {
+        DimensionRequest { field, grain: None }
+    }
+}
+
+impl Serialize for DimensionRequest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        #[derive(Serialize)]
+        struct WithGrain<'a> {
+            field: &'a str,
+            grain: &'a TimeGrain,
+        }
+        match &self.grain {
+            Some(grain) => WithGrain {
+                field: &self.field,
+                grain,
+            }
+            .serialize(serializer),
+            None => serializer.serialize_str(&self.field),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DimensionRequest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Raw {
+            field: String,
+            grain: Option<TimeGrain>,
+        }
+
+        let value = Value::deserialize(deserializer)?;
+        match value {
+            Value::String(field) => Ok(field.into()),
+            Value::Object(_) => {
+                let raw: Raw = serde_json::from_value(value).map_err(de::Error::custom)?;
+                Ok(DimensionRequest {
+                    field: raw.field,
+                    grain: raw.grain,
+                })
+            }
+            other => Err(de::Error::custom(format!(
+                "dimension must be a name or {{field, grain}} object, got {other}"
+            ))),
+        }
+    }
+}
+
+impl From<Filter> for FilterExpr {
+    fn from(filter: Filter) -> Self {
+        FilterExpr::Condition(filter)
+    }
+}
+
+impl<'de> Deserialize<'de> for FilterExpr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let group_key = match &value {
+            Value::Object(map) if map.len() == 1 => map
+                .keys()
+                .next()
+                .filter(|k| matches!(k.as_str(), "all" | "any" | "not"))
+                .cloned(),
+            _ => None,
+        };
+        let Some(key) = group_key else {
+            return serde_json::from_value(value)
+                .map(FilterExpr::Condition)
+                .map_err(de::Error::custom);
+        };
+        let Value::Object(mut map) = value else {
+            unreachable!("group key only found on objects")
+        };
+        let inner = map.remove(&key).unwrap_or(Value::Null);
+        match key.as_str() {
+            "not" => Ok(FilterExpr::Not {
+                not: Box::new(serde_json::from_value(inner).map_err(de::Error::custom)?),
+            }),
+            _ => {
+                let members: Vec<FilterExpr> =
+                    serde_json::from_value(inner).map_err(de::Error::custom)?;
+                if members.is_empty() {
+                    return Err(de::Error::custom(format!(
+                        "filter group '{key}' must contain at least one filter"
+                    )));
+                }
+                Ok(if key == "all" {
+                    FilterExpr::All { all: members }
+                } else {
+                    FilterExpr::Any { any: members }
+                })
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FilterOp {
     #[serde(rename = "==")]
@@ -701,6 +2331,13 @@ pub enum FilterOp {
     Like,
     #[serde(rename = "ilike")]
     ILike,
+    #[serde(rename = "is true")]
+    IsTrue,
+    #[serde(rename = "is false")]
+    IsFalse,
+    /// Boolean is NULL (SQL three-valued `UNKNOWN`).
+    #[serde(rename = "is unknown")]
+    IsUnknown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -710,9 +2347,11 @@ pub struct OrderItem {
     pub direction: SortDirection,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum SortDirection {
+    #[default]
     Asc,
     Desc,
 }
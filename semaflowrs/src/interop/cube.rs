@@ -0,0 +1,269 @@
+//! Export a registry as a Cube data model (YAML).
+//!
+//! Each semantic table becomes a cube and each flow a view. A join becomes a
+//! join on the cube it starts from, so a table joined under different
+//! aliases in different flows keeps one join per target cube. Formula
+//! measures and expressions without a portable SQL spelling are left out
+//! with a warning.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{json, Map, Value};
+
+use crate::flows::{Aggregation, JoinCardinality, Measure, SemanticFlow, SemanticTable};
+use crate::registry::FlowRegistry;
+
+use super::{is_numeric_type, is_time_type, render_sql, sorted_model, Export};
+
+/// Render `registry` as a Cube YAML model with `cubes` and `views`.
+pub fn export(registry: &FlowRegistry) -> Export {
+    let (tables, flows) = sorted_model(registry);
+    let mut warnings = Vec::new();
+
+    // Cube name -> joins declared on it, collected from every flow
+    let mut joins: HashMap<&str, BTreeMap<String, Value>> = HashMap::new();
+    let mut views = Vec::new();
+    for flow in flows {
+        views.push(flow_view(flow, &mut joins, &mut warnings));
+    }
+
+    let cubes: Vec<Value> = tables
+        .into_iter()
+        .map(|table| {
+            let cube_joins = joins.remove(table.name.as_str()).unwrap_or_default();
+            table_cube(table, cube_joins, &mut warnings)
+        })
+        .collect();
+
+    let model = json!({ "cubes": cubes, "views": views });
+    Export {
+        contents: serde_yaml::to_string(&model).unwrap_or_default(),
+        warnings,
+    }
+}
+
+fn table_cube(
+    table: &SemanticTable,
+    joins: BTreeMap<String, Value>,
+    warnings: &mut Vec<String>,
+) -> Value {
+    let column = |c: &str| format!("{{CUBE}}.{c}");
+    let measure_ref = |m: &str| format!("{{{m}}}");
+
+    let mut dimensions = Vec::new();
+    for (name, dimension) in &table.dimensions {
+        let sql = match render_sql(&dimension.expr, &column, &measure_ref) {
+            Ok(sql) => sql,
+            Err(e) => {
+                warnings.push(format!("{}.{name}: {e}; skipped", table.name));
+                continue;
+            }
+        };
+        let data_type = dimension.data_type.as_deref();
+        let kind = if is_time_type(data_type) {
+            "time"
+        } else if is_numeric_type(data_type) {
+            "number"
+        } else if dimension.is_boolean() {
+            "boolean"
+        } else {
+            "string"
+        };
+        let mut entry = Map::new();
+        entry.insert("name".into(), json!(name));
+        entry.insert("sql".into(), json!(sql));
+        entry.insert("type".into(), json!(kind));
+        if table.primary_keys.len() == 1 && table.primary_keys[0] == *name {
+            entry.insert("primary_key".into(), json!(true));
+        }
+        if let Some(label) = &dimension.label {
+            entry.insert("title".into(), json!(label));
+        }
+        if let Some(description) = &dimension.description {
+            entry.insert("description".into(), json!(description));
+        }
+        if dimension.hidden {
+            entry.insert("public".into(), json!(false));
+        }
+        dimensions.push(Value::Object(entry));
+    }
+
+    let mut measures = Vec::new();
+    for (name, measure) in &table.measures {
+        let converted = if let Some(post_expr) = &measure.post_expr {
+            render_sql(post_expr, &column, &measure_ref).map(|sql| ("number", sql, None))
+        } else if measure.is_formula() {
+            Err("formula measures are not supported".to_string())
+        } else {
+            simple_measure(measure, &column, &measure_ref)
+        };
+        let (kind, sql, filter) = match converted {
+            Ok(converted) => converted,
+            Err(e) => {
+                warnings.push(format!("{}.{name}: {e}; skipped", table.name));
+                continue;
+            }
+        };
+        let mut entry = Map::new();
+        entry.insert("name".into(), json!(name));
+        entry.insert("sql".into(), json!(sql));
+        entry.insert("type".into(), json!(kind));
+        if let Some(filter) = filter {
+            entry.insert("filters".into(), json!([{ "sql": filter }]));
+        }
+        if let Some(label) = &measure.label {
+            entry.insert("title".into(), json!(label));
+        }
+        if let Some(description) = &measure.description {
+            entry.insert("description".into(), json!(description));
+        }
+        if measure.hidden {
+            entry.insert("public".into(), json!(false));
+        }
+        measures.push(Value::Object(entry));
+    }
+
+    if !table.default_filters.is_empty() {
+        warnings.push(format!("{}: default filters are not exported", table.name));
+    }
+    let mut cube = Map::new();
+    cube.insert("name".into(), json!(table.name));
+    match &table.sql {
+        Some(sql) => cube.insert("sql".into(), json!(sql.trim())),
+        None => cube.insert("sql_table".into(), json!(table.table)),
+    };
+    if table.query.is_some() || table.union.is_some() {
+        warnings.push(format!(
+            "{}: query and union tables are not exported",
+            table.name
+        ));
+    }
+    cube.insert("data_source".into(), json!(table.data_source));
+    if let Some(description) = &table.description {
+        cube.insert("description".into(), json!(description));
+    }
+    if !joins.is_empty() {
+        cube.insert(
+            "joins".into(),
+            json!(joins.into_values().collect::<Vec<_>>()),
+        );
+    }
+    cube.insert("dimensions".into(), json!(dimensions));
+    cube.insert("measures".into(), json!(measures));
+    Value::Object(cube)
+}
+
+type SimpleMeasure = (&'static str, String, Option<String>);
+
+fn simple_measure(
+    measure: &Measure,
+    column: &dyn Fn(&str) -> String,
+    measure_ref: &dyn Fn(&str) -> String,
+) -> Result<SimpleMeasure, String> {
+    let kind = match measure.agg.as_ref().ok_or("measure has no aggregation")? {
+        Aggregation::Sum => "sum",
+        Aggregation::Count => "count",
+        Aggregation::CountDistinct => "count_distinct",
+        Aggregation::ApproxCountDistinct => "count_distinct_approx",
+        Aggregation::Min => "min",
+        Aggregation::Max => "max",
+        Aggregation::Avg => "avg",
+        other => return Err(format!("aggregation {other:?} is not supported")),
+    };
+    let expr = measure.expr.as_ref().ok_or("measure has no expression")?;
+    let sql = render_sql(expr, column, measure_ref)?;
+    let filter = match &measure.filter {
+        Some(filter) => Some(render_sql(filter, column, measure_ref)?),
+        None => None,
+    };
+    Ok((kind, sql, filter))
+}
+
+/// A view exposing the flow's cubes along their join paths, and the joins
+/// the paths need.
+fn flow_view<'a>(
+    flow: &'a SemanticFlow,
+    joins: &mut HashMap<&'a str, BTreeMap<String, Value>>,
+    warnings: &mut Vec<String>,
+) -> Value {
+    let base = &flow.base_table;
+    // Alias -> semantic table and join path from the base cube
+    let mut tables: HashMap<&str, &str> =
+        HashMap::from([(base.alias.as_str(), base.semantic_table.as_str())]);
+    let mut paths: HashMap<&str, String> =
+        HashMap::from([(base.alias.as_str(), base.semantic_table.clone())]);
+    let mut cubes = vec![json!({ "join_path": base.semantic_table, "includes": "*" })];
+
+    for join in flow.joins.values() {
+        if join.extra_conditions.is_some() {
+            warnings.push(format!(
+                "{}: extra conditions of join {} are not exported",
+                flow.name, join.alias
+            ));
+        }
+    }
+
+    // Joins may reference aliases declared later, so resolve until stable
+    let mut pending: Vec<_> = flow.joins.values().collect();
+    while !pending.is_empty() {
+        let before = pending.len();
+        pending.retain(|&join| {
+            let Some(&from) = tables.get(join.to_table.as_str()) else {
+                return true;
+            };
+            let path = format!("{}.{}", paths[join.to_table.as_str()], join.semantic_table);
+            let on: Vec<String> = join
+                .join_keys
+                .iter()
+                .map(|k| {
+                    format!(
+                        "{{CUBE}}.{} = {{{}}}.{}",
+                        k.left, join.semantic_table, k.right
+                    )
+                })
+                .collect();
+            let relationship = match join.cardinality {
+                Some(JoinCardinality::OneToMany) => "one_to_many",
+                Some(JoinCardinality::OneToOne) => "one_to_one",
+                _ => "many_to_one",
+            };
+            let declared = joins.entry(from).or_default();
+            let entry = json!({
+                "name": join.semantic_table,
+                "sql": on.join(" AND "),
+                "relationship": relationship,
+            });
+            match declared.get(&join.semantic_table) {
+                Some(existing) if *existing != entry => warnings.push(format!(
+                    "{}: join {} -> {} differs from another flow's; kept the first",
+                    flow.name, from, join.semantic_table
+                )),
+                Some(_) => {}
+                None => {
+                    declared.insert(join.semantic_table.clone(), entry);
+                }
+            }
+            cubes.push(json!({ "join_path": &path, "includes": "*", "prefix": true }));
+            tables.insert(&join.alias, &join.semantic_table);
+            paths.insert(&join.alias, path);
+            false
+        });
+        if pending.len() == before {
+            for join in &pending {
+                warnings.push(format!(
+                    "{}: join {} references unknown alias {}; skipped",
+                    flow.name, join.alias, join.to_table
+                ));
+            }
+            break;
+        }
+    }
+
+    let mut view = Map::new();
+    view.insert("name".into(), json!(flow.name));
+    if let Some(description) = &flow.description {
+        view.insert("description".into(), json!(description));
+    }
+    view.insert("cubes".into(), json!(cubes));
+    Value::Object(view)
+}
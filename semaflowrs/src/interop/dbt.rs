@@ -0,0 +1,850 @@
+//! Import dbt semantic models and metrics from a `manifest.json`, and export
+//! a registry as dbt semantic model and metric YAML.
+//!
+//! Each dbt semantic model becomes a [`SemanticTable`]: its dimensions and
+//! entities become dimensions, its measures become measures, its primary
+//! entity provides the primary key and its primary, unique and foreign
+//! entities are kept as table entities. Each model with a primary entity also
+//! becomes a [`SemanticFlow`] joining (left, many-to-one) every model whose
+//! primary entity matches one of its foreign entities.
+//!
+//! Simple metrics become measures named after the metric, and ratio metrics
+//! whose numerator and denominator live on the same model become formula
+//! measures. Metric filters, derived, cumulative and conversion metrics, and
+//! fields whose SQL expression is more than a column reference are skipped
+//! with a warning.
+//!
+//! [`export`] goes the other way: tables become semantic models (referencing
+//! a dbt model named after the physical table), join keys become foreign
+//! entities, simple measures become simple metrics and measures dividing two
+//! measures become ratio metrics.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::error::{Result, SemaflowError};
+use crate::expr_parser::parse_formula;
+use crate::flows::{
+    Aggregation, BinaryOp, Dimension, Entity, EntityType, Expr, FlowJoin, FlowTableRef,
+    FormulaExpr, Function, JoinCardinality, JoinKey, JoinType, Measure, SemanticFlow,
+    SemanticTable,
+};
+use crate::registry::FlowRegistry;
+
+use super::{filtered_input_sql, is_time_type, render_sql, sorted_model, Export};
+
+/// Options for [`import_manifest`].
+#[derive(Debug, Clone)]
+pub struct DbtImportOptions {
+    /// Data source every imported table reads from.
+    pub data_source: String,
+}
+
+/// Tables and flows converted from a dbt manifest.
+#[derive(Debug, Clone, Default)]
+pub struct DbtImport {
+    pub tables: Vec<SemanticTable>,
+    pub flows: Vec<SemanticFlow>,
+    /// Constructs that were skipped, one message each.
+    pub warnings: Vec<String>,
+}
+
+impl DbtImport {
+    pub fn into_registry(self) -> FlowRegistry {
+        FlowRegistry::from_parts(self.tables, self.flows)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    semantic_models: BTreeMap<String, DbtSemanticModel>,
+    #[serde(default)]
+    metrics: BTreeMap<String, DbtMetric>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DbtSemanticModel {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    node_relation: Option<DbtNodeRelation>,
+    #[serde(default)]
+    defaults: Option<DbtDefaults>,
+    #[serde(default)]
+    entities: Vec<DbtEntity>,
+    #[serde(default)]
+    dimensions: Vec<DbtDimension>,
+    #[serde(default)]
+    measures: Vec<DbtMeasure>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DbtNodeRelation {
+    alias: String,
+    #[serde(default)]
+    schema_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DbtDefaults {
+    agg_time_dimension: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DbtEntity {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    expr: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DbtDimension {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    expr: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DbtMeasure {
+    name: String,
+    agg: String,
+    #[serde(default)]
+    expr: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DbtMetric {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    filter: Option<Value>,
+    #[serde(default)]
+    type_params: DbtMetricParams,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DbtMetricParams {
+    #[serde(default)]
+    measure: Option<DbtInput>,
+    #[serde(default)]
+    numerator: Option<DbtInput>,
+    #[serde(default)]
+    denominator: Option<DbtInput>,
+}
+
+/// A measure or metric input, written either as a name or as an object.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DbtInput {
+    Name(String),
+    Object {
+        name: String,
+        #[serde(default)]
+        filter: Option<Value>,
+    },
+}
+
+impl DbtInput {
+    fn name(&self) -> &str {
+        match self {
+            DbtInput::Name(name) | DbtInput::Object { name, .. } => name,
+        }
+    }
+
+    fn has_filter(&self) -> bool {
+        matches!(self, DbtInput::Object { filter: Some(f), .. } if !f.is_null())
+    }
+}
+
+/// Read a dbt `manifest.json` and convert its semantic models and metrics.
+pub fn import_manifest<P: AsRef<Path>>(path: P, options: &DbtImportOptions) -> Result<DbtImport> {
+    let contents = std::fs::read_to_string(path)?;
+    import_manifest_str(&contents, options)
+}
+
+/// Convert the semantic models and metrics of a manifest's JSON text.
+pub fn import_manifest_str(contents: &str, options: &DbtImportOptions) -> Result<DbtImport> {
+    let manifest: Manifest = serde_json::from_str(contents)
+        .map_err(|e| SemaflowError::Validation(format!("invalid dbt manifest: {e}")))?;
+    let mut import = DbtImport::default();
+
+    // Measure name -> table defining it, for resolving metrics
+    let mut measure_tables: HashMap<String, usize> = HashMap::new();
+    for model in manifest.semantic_models.values() {
+        let table = convert_model(model, options, &mut import.warnings);
+        for name in table.measures.keys() {
+            measure_tables.insert(name.clone(), import.tables.len());
+        }
+        import.tables.push(table);
+    }
+    import.flows = build_flows(&manifest, &import.tables);
+
+    // Metric name -> underlying measure, so ratios can reference simple metrics
+    let mut metric_measures: HashMap<&str, &str> = HashMap::new();
+    for metric in manifest.metrics.values() {
+        if let ("simple", Some(measure)) = (metric.kind.as_str(), &metric.type_params.measure) {
+            metric_measures.insert(&metric.name, measure.name());
+        }
+    }
+    for metric in manifest.metrics.values() {
+        if let Err(reason) = convert_metric(metric, &metric_measures, &measure_tables, &mut import)
+        {
+            import
+                .warnings
+                .push(format!("metric {}: {reason}; skipped", metric.name));
+        }
+    }
+    Ok(import)
+}
+
+fn convert_model(
+    model: &DbtSemanticModel,
+    options: &DbtImportOptions,
+    warnings: &mut Vec<String>,
+) -> SemanticTable {
+    let table = match &model.node_relation {
+        Some(DbtNodeRelation {
+            alias,
+            schema_name: Some(schema),
+        }) => format!("{schema}.{alias}"),
+        Some(relation) => relation.alias.clone(),
+        None => model.name.clone(),
+    };
+    let primary_keys = model
+        .entities
+        .iter()
+        .filter(|e| e.kind == "primary")
+        .map(|e| e.expr.clone().unwrap_or_else(|| e.name.clone()))
+        .collect();
+    let entities = model
+        .entities
+        .iter()
+        .filter_map(|e| {
+            let kind = match e.kind.as_str() {
+                "primary" => EntityType::Primary,
+                "unique" => EntityType::Unique,
+                "foreign" => EntityType::Foreign,
+                _ => return None,
+            };
+            let Expr::Column { column } = column_expr(e.expr.as_deref().unwrap_or(&e.name))? else {
+                return None;
+            };
+            Some((
+                e.name.clone(),
+                Entity {
+                    kind,
+                    column: Some(column),
+                },
+            ))
+        })
+        .collect();
+
+    let mut dimensions = BTreeMap::new();
+    let mut time_dimensions = Vec::new();
+    let fields = model
+        .entities
+        .iter()
+        .map(|e| (&e.name, &e.expr, &e.description, "entity"))
+        .chain(
+            model
+                .dimensions
+                .iter()
+                .map(|d| (&d.name, &d.expr, &d.description, d.kind.as_str())),
+        );
+    for (name, expr, description, kind) in fields {
+        let expr = expr.as_deref().unwrap_or(name);
+        match column_expr(expr) {
+            Some(expr) => {
+                if kind == "time" {
+                    time_dimensions.push(name.clone());
+                }
+                dimensions.insert(
+                    name.clone(),
+                    Dimension {
+                        expr,
+                        data_type: (kind == "time").then(|| "timestamp".to_string()),
+                        description: description.clone(),
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
+                        unnest: false,
+                        labels: BTreeMap::new(),
+                    },
+                );
+            }
+            None => warnings.push(unsupported_expr(&model.name, name, expr)),
+        }
+    }
+
+    let mut measures = BTreeMap::new();
+    for measure in &model.measures {
+        let Some(agg) = convert_agg(&measure.agg) else {
+            warnings.push(format!(
+                "{}.{}: aggregation {} is not supported; skipped",
+                model.name, measure.name, measure.agg
+            ));
+            continue;
+        };
+        let expr = measure.expr.as_deref().unwrap_or(&measure.name);
+        match column_expr(expr) {
+            Some(expr) => {
+                measures.insert(
+                    measure.name.clone(),
+                    Measure {
+                        expr: Some(expr),
+                        agg: Some(agg),
+                        formula: None,
+                        filter: None,
+                        post_expr: None,
+                        data_type: None,
+                        description: measure.description.clone(),
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
+                    },
+                );
+            }
+            None => warnings.push(unsupported_expr(&model.name, &measure.name, expr)),
+        }
+    }
+
+    let time_dimension = model
+        .defaults
+        .as_ref()
+        .and_then(|d| d.agg_time_dimension.clone())
+        .or_else(|| time_dimensions.into_iter().next());
+    SemanticTable {
+        data_source: options.data_source.clone(),
+        name: model.name.clone(),
+        table,
+        sql: None,
+        query: None,
+        union: None,
+        primary_keys,
+        unique_keys: Vec::new(),
+        nullable: Default::default(),
+        time_dimension,
+        smallest_time_grain: None,
+        timezone: None,
+        dimensions,
+        measures,
+        description: model.description.clone(),
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        access_policies: Default::default(),
+        hierarchies: Default::default(),
+        entities,
+    }
+}
+
+/// dbt expressions are arbitrary SQL; only plain column references are
+/// imported.
+fn column_expr(expr: &str) -> Option<Expr> {
+    let expr = expr.trim();
+    let is_column = !expr.is_empty()
+        && expr
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    is_column.then(|| Expr::Column {
+        column: expr.to_string(),
+    })
+}
+
+fn unsupported_expr(model: &str, field: &str, expr: &str) -> String {
+    format!("{model}.{field}: expression '{expr}' is not a column reference; skipped")
+}
+
+fn convert_agg(agg: &str) -> Option<Aggregation> {
+    Some(match agg {
+        "sum" => Aggregation::Sum,
+        "count" => Aggregation::Count,
+        "count_distinct" => Aggregation::CountDistinct,
+        "min" => Aggregation::Min,
+        "max" => Aggregation::Max,
+        "average" => Aggregation::Avg,
+        "median" => Aggregation::Median,
+        "sum_boolean" => Aggregation::CountIf,
+        _ => return None,
+    })
+}
+
+/// One flow per model with a primary entity, joining the models its foreign
+/// entities point at.
+fn build_flows(manifest: &Manifest, tables: &[SemanticTable]) -> Vec<SemanticFlow> {
+    let primary_entity = |model: &DbtSemanticModel| {
+        model
+            .entities
+            .iter()
+            .find(|e| e.kind == "primary")
+            .map(|e| {
+                (
+                    e.name.clone(),
+                    e.expr.clone().unwrap_or_else(|| e.name.clone()),
+                )
+            })
+    };
+    let mut owners: HashMap<String, (&str, String)> = HashMap::new();
+    for model in manifest.semantic_models.values() {
+        if let Some((entity, column)) = primary_entity(model) {
+            owners.insert(entity, (model.name.as_str(), column));
+        }
+    }
+
+    let mut flows = Vec::new();
+    for model in manifest.semantic_models.values() {
+        if primary_entity(model).is_none() || !tables.iter().any(|t| t.name == model.name) {
+            continue;
+        }
+        let mut joins = BTreeMap::new();
+        for entity in model.entities.iter().filter(|e| e.kind == "foreign") {
+            let Some((target, column)) = owners.get(&entity.name) else {
+                continue;
+            };
+            if *target == model.name {
+                continue;
+            }
+            joins.insert(
+                target.to_string(),
+                FlowJoin {
+                    semantic_table: target.to_string(),
+                    alias: target.to_string(),
+                    to_table: model.name.clone(),
+                    join_type: JoinType::Left,
+                    join_keys: vec![JoinKey {
+                        left: entity.expr.clone().unwrap_or_else(|| entity.name.clone()),
+                        right: column.clone(),
+                    }],
+                    cardinality: Some(JoinCardinality::ManyToOne),
+                    through: None,
+                    weight: None,
+                    role: None,
+                    extra_conditions: None,
+                    description: entity.description.clone(),
+                },
+            );
+        }
+        flows.push(SemanticFlow {
+            name: model.name.clone(),
+            base_table: FlowTableRef {
+                semantic_table: model.name.clone(),
+                alias: model.name.clone(),
+            },
+            joins,
+            description: model.description.clone(),
+            params: Default::default(),
+            tags: Vec::new(),
+            synonyms: Vec::new(),
+            limits: Default::default(),
+            aggregates: Default::default(),
+            fiscal_calendar: None,
+            timezone: None,
+            fanout_strategy: Default::default(),
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            extends: None,
+            deprecated: None,
+        });
+    }
+    flows
+}
+
+fn convert_metric(
+    metric: &DbtMetric,
+    metric_measures: &HashMap<&str, &str>,
+    measure_tables: &HashMap<String, usize>,
+    import: &mut DbtImport,
+) -> std::result::Result<(), String> {
+    if metric.filter.as_ref().is_some_and(|f| !f.is_null()) {
+        return Err("metric filters are not supported".to_string());
+    }
+    match metric.kind.as_str() {
+        "simple" => {
+            let input = metric
+                .type_params
+                .measure
+                .as_ref()
+                .ok_or("missing measure")?;
+            if input.has_filter() {
+                return Err("measure filters are not supported".to_string());
+            }
+            let index = *measure_tables
+                .get(input.name())
+                .ok_or_else(|| format!("unknown measure {}", input.name()))?;
+            let measures = &mut import.tables[index].measures;
+            let mut measure = measures[input.name()].clone();
+            if metric.description.is_some() {
+                measure.description = metric.description.clone();
+            }
+            if metric.label.is_some() {
+                measure.label = metric.label.clone();
+            }
+            measures.insert(metric.name.clone(), measure);
+            Ok(())
+        }
+        "ratio" => {
+            let params = &metric.type_params;
+            let (Some(numerator), Some(denominator)) = (&params.numerator, &params.denominator)
+            else {
+                return Err("missing numerator or denominator".to_string());
+            };
+            if numerator.has_filter() || denominator.has_filter() {
+                return Err("input filters are not supported".to_string());
+            }
+            let resolve = |input: &DbtInput| {
+                let name = metric_measures
+                    .get(input.name())
+                    .copied()
+                    .unwrap_or(input.name());
+                measure_tables
+                    .get(name)
+                    .map(|index| (*index, name.to_string()))
+                    .ok_or_else(|| format!("unknown input {}", input.name()))
+            };
+            let (index, numerator) = resolve(numerator)?;
+            let (other, denominator) = resolve(denominator)?;
+            if index != other {
+                return Err("numerator and denominator are on different models".to_string());
+            }
+            let raw = format!("{numerator} / {denominator}");
+            let ast = parse_formula(&raw).map_err(|e| e.to_string())?;
+            import.tables[index].measures.insert(
+                metric.name.clone(),
+                Measure {
+                    expr: None,
+                    agg: None,
+                    formula: Some(FormulaExpr { raw, ast }),
+                    filter: None,
+                    post_expr: None,
+                    data_type: None,
+                    description: metric.description.clone(),
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: metric.label.clone(),
+                    format: None,
+                    hidden: false,
+                },
+            );
+            Ok(())
+        }
+        other => Err(format!("{other} metrics are not supported")),
+    }
+}
+
+/// Render `registry` as dbt `semantic_models` and `metrics` YAML.
+pub fn export(registry: &FlowRegistry) -> Export {
+    let (tables, flows) = sorted_model(registry);
+    let mut warnings = Vec::new();
+    let mut models = Vec::new();
+    let mut metrics = Vec::new();
+    for table in tables {
+        let entities = export_entities(table, &flows, registry, &mut warnings);
+        let column = |c: &str| c.to_string();
+
+        let mut dimensions = Vec::new();
+        for (name, dimension) in &table.dimensions {
+            let expr = match render_sql(&dimension.expr, &column, &column) {
+                Ok(expr) => expr,
+                Err(e) => {
+                    warnings.push(format!("{}.{name}: {e}; skipped", table.name));
+                    continue;
+                }
+            };
+            let mut entry = Map::new();
+            entry.insert("name".into(), json!(name));
+            if is_time_type(dimension.data_type.as_deref()) {
+                entry.insert("type".into(), json!("time"));
+                entry.insert("type_params".into(), json!({ "time_granularity": "day" }));
+            } else {
+                entry.insert("type".into(), json!("categorical"));
+            }
+            entry.insert("expr".into(), json!(expr));
+            if let Some(description) = &dimension.description {
+                entry.insert("description".into(), json!(description));
+            }
+            dimensions.push(Value::Object(entry));
+        }
+
+        let mut measures = Vec::new();
+        for (name, measure) in &table.measures {
+            let converted = if let Some(post_expr) = &measure.post_expr {
+                ratio_inputs(post_expr).map(|(numerator, denominator)| {
+                    metrics.push(metric(
+                        name,
+                        measure,
+                        "ratio",
+                        json!({
+                            "numerator": numerator,
+                            "denominator": denominator,
+                        }),
+                    ));
+                })
+            } else if measure.is_formula() {
+                Err("formula measures are not supported".to_string())
+            } else {
+                export_measure(measure).map(|(agg, expr)| {
+                    let mut entry = Map::new();
+                    entry.insert("name".into(), json!(name));
+                    entry.insert("agg".into(), json!(agg));
+                    entry.insert("expr".into(), json!(expr));
+                    if let Some(description) = &measure.description {
+                        entry.insert("description".into(), json!(description));
+                    }
+                    measures.push(Value::Object(entry));
+                    metrics.push(metric(name, measure, "simple", json!({ "measure": name })));
+                })
+            };
+            if let Err(e) = converted {
+                warnings.push(format!("{}.{name}: {e}; skipped", table.name));
+            }
+        }
+
+        if !table.default_filters.is_empty() {
+            warnings.push(format!("{}: default filters are not exported", table.name));
+        }
+        let model_name = table.table.rsplit('.').next().unwrap_or(&table.table);
+        let mut model = Map::new();
+        model.insert("name".into(), json!(table.name));
+        if let Some(description) = &table.description {
+            model.insert("description".into(), json!(description));
+        }
+        model.insert("model".into(), json!(format!("ref('{model_name}')")));
+        if let Some(time_dimension) = &table.time_dimension {
+            model.insert(
+                "defaults".into(),
+                json!({ "agg_time_dimension": time_dimension }),
+            );
+        }
+        model.insert("entities".into(), json!(entities));
+        model.insert("dimensions".into(), json!(dimensions));
+        model.insert("measures".into(), json!(measures));
+        models.push(Value::Object(model));
+    }
+
+    let document = json!({ "semantic_models": models, "metrics": metrics });
+    Export {
+        contents: serde_yaml::to_string(&document).unwrap_or_default(),
+        warnings,
+    }
+}
+
+/// The table's primary entity, plus a foreign entity for every flow join
+/// from this table on a single key to another table's primary key.
+fn export_entities(
+    table: &SemanticTable,
+    flows: &[&SemanticFlow],
+    registry: &FlowRegistry,
+    warnings: &mut Vec<String>,
+) -> Vec<Value> {
+    let mut entities = BTreeMap::new();
+    match table.primary_keys.as_slice() {
+        [key] => {
+            entities.insert(
+                table.name.clone(),
+                json!({ "name": table.name, "type": "primary", "expr": key }),
+            );
+        }
+        _ => warnings.push(format!(
+            "{}: composite primary keys have no dbt entity",
+            table.name
+        )),
+    }
+    for flow in flows {
+        let alias_table = |alias: &str| {
+            if flow.base_table.alias == alias {
+                Some(flow.base_table.semantic_table.as_str())
+            } else {
+                flow.joins
+                    .values()
+                    .find(|j| j.alias == alias)
+                    .map(|j| j.semantic_table.as_str())
+            }
+        };
+        for join in flow.joins.values() {
+            if alias_table(join.to_table.as_str()) != Some(table.name.as_str()) {
+                continue;
+            }
+            let Some(target) = registry.get_table(&join.semantic_table) else {
+                continue;
+            };
+            match (join.join_keys.as_slice(), target.primary_keys.as_slice()) {
+                ([key], [primary]) if key.right == *primary => {
+                    entities.entry(target.name.clone()).or_insert_with(
+                        || json!({ "name": target.name, "type": "foreign", "expr": key.left }),
+                    );
+                }
+                _ => warnings.push(format!(
+                    "{}: join {} is not on a single primary key; no foreign entity",
+                    flow.name, join.alias
+                )),
+            }
+        }
+    }
+    entities.into_values().collect()
+}
+
+fn export_measure(measure: &Measure) -> std::result::Result<(&'static str, String), String> {
+    let agg = match measure.agg.as_ref().ok_or("measure has no aggregation")? {
+        Aggregation::Sum => "sum",
+        Aggregation::Count => "count",
+        Aggregation::CountDistinct => "count_distinct",
+        Aggregation::Min => "min",
+        Aggregation::Max => "max",
+        Aggregation::Avg => "average",
+        Aggregation::Median => "median",
+        Aggregation::CountIf => "sum_boolean",
+        other => return Err(format!("aggregation {other:?} is not supported")),
+    };
+    Ok((agg, filtered_input_sql(measure, &|c: &str| c.to_string())?))
+}
+
+/// Numerator and denominator of a post-aggregation expression dividing one
+/// measure by another.
+fn ratio_inputs(expr: &Expr) -> std::result::Result<(&str, &str), String> {
+    let (numerator, denominator) = match expr {
+        Expr::Binary {
+            op: BinaryOp::Divide,
+            left,
+            right,
+        } => (left.as_ref(), right.as_ref()),
+        Expr::Func {
+            func: Function::SafeDivide,
+            args,
+        } if args.len() == 2 => (&args[0], &args[1]),
+        _ => return Err("only ratios of two measures are supported".to_string()),
+    };
+    match (numerator, denominator) {
+        (Expr::MeasureRef { name: numerator }, Expr::MeasureRef { name: denominator }) => {
+            Ok((numerator.as_str(), denominator.as_str()))
+        }
+        _ => Err("only ratios of two measures are supported".to_string()),
+    }
+}
+
+fn metric(name: &str, measure: &Measure, kind: &str, type_params: Value) -> Value {
+    let mut entry = Map::new();
+    entry.insert("name".into(), json!(name));
+    entry.insert(
+        "label".into(),
+        json!(measure.label.as_deref().unwrap_or(name)),
+    );
+    if let Some(description) = &measure.description {
+        entry.insert("description".into(), json!(description));
+    }
+    entry.insert("type".into(), json!(kind));
+    entry.insert("type_params".into(), type_params);
+    Value::Object(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+      "nodes": {},
+      "semantic_models": {
+        "semantic_model.shop.orders": {
+          "name": "orders",
+          "description": "Order facts",
+          "node_relation": {"alias": "fct_orders", "schema_name": "analytics"},
+          "defaults": {"agg_time_dimension": "ordered_at"},
+          "entities": [
+            {"name": "order", "type": "primary", "expr": "order_id"},
+            {"name": "customer", "type": "foreign", "expr": "customer_id"}
+          ],
+          "dimensions": [
+            {"name": "ordered_at", "type": "time", "type_params": {"time_granularity": "day"}},
+            {"name": "status", "type": "categorical"},
+            {"name": "is_large", "type": "categorical", "expr": "case when amount > 100 then 1 end"}
+          ],
+          "measures": [
+            {"name": "order_total", "agg": "sum", "expr": "amount"},
+            {"name": "order_count", "agg": "count", "expr": "order_id"},
+            {"name": "p90_amount", "agg": "percentile", "expr": "amount"}
+          ]
+        },
+        "semantic_model.shop.customers": {
+          "name": "customers",
+          "node_relation": {"alias": "dim_customers", "schema_name": "analytics"},
+          "entities": [{"name": "customer", "type": "primary", "expr": "id"}],
+          "dimensions": [{"name": "country", "type": "categorical"}],
+          "measures": []
+        }
+      },
+      "metrics": {
+        "metric.shop.revenue": {
+          "name": "revenue", "type": "simple", "description": "Total revenue",
+          "type_params": {"measure": {"name": "order_total", "filter": null}}
+        },
+        "metric.shop.aov": {
+          "name": "aov", "type": "ratio",
+          "type_params": {"numerator": "revenue", "denominator": {"name": "order_count"}}
+        },
+        "metric.shop.revenue_growth": {
+          "name": "revenue_growth", "type": "derived", "type_params": {}
+        }
+      }
+    }"#;
+
+    #[test]
+    fn imports_models_metrics_and_entity_joins() {
+        let options = DbtImportOptions {
+            data_source: "warehouse".to_string(),
+        };
+        let import = import_manifest_str(MANIFEST, &options).unwrap();
+
+        let orders = import.tables.iter().find(|t| t.name == "orders").unwrap();
+        assert_eq!(orders.table, "analytics.fct_orders");
+        assert_eq!(orders.primary_keys, ["order_id"]);
+        assert_eq!(orders.time_dimension.as_deref(), Some("ordered_at"));
+        assert!(orders.dimensions.contains_key("customer"));
+        assert!(!orders.dimensions.contains_key("is_large"));
+        assert_eq!(orders.measures["revenue"].agg, Some(Aggregation::Sum));
+        assert_eq!(
+            orders.measures["revenue"].description.as_deref(),
+            Some("Total revenue")
+        );
+        let aov = orders.measures["aov"].formula.as_ref().unwrap();
+        assert_eq!(aov.raw, "order_total / order_count");
+
+        let flow = import.flows.iter().find(|f| f.name == "orders").unwrap();
+        let join = &flow.joins["customers"];
+        assert_eq!(join.join_keys[0].left, "customer_id");
+        assert_eq!(join.join_keys[0].right, "id");
+
+        assert_eq!(import.warnings.len(), 3, "{:?}", import.warnings);
+        assert!(import.warnings.iter().any(|w| w.contains("percentile")));
+        assert!(import.warnings.iter().any(|w| w.contains("revenue_growth")));
+
+        let registry = import.into_registry();
+        assert!(registry.get_flow("customers").is_some());
+    }
+}
@@ -0,0 +1,229 @@
+//! Export a registry as LookML views and explores.
+//!
+//! Each semantic table becomes a view and each flow an explore whose joins
+//! keep the flow's aliases (`join: c { from: customers }`), so join
+//! conditions can reference tables by alias. Time dimensions become
+//! dimension groups. Measure filters are folded into the measure's SQL, the
+//! base table's default filters become `sql_always_where`, and formula
+//! measures, right joins and expressions without a portable SQL spelling are
+//! left out with a warning.
+
+use std::fmt::Write;
+
+use crate::flows::{Aggregation, JoinCardinality, JoinType, Measure, SemanticFlow, SemanticTable};
+use crate::registry::FlowRegistry;
+
+use super::{filtered_input_sql, is_numeric_type, is_time_type, render_sql, sorted_model, Export};
+
+const TIMEFRAMES: &str = "[raw, date, week, month, quarter, year]";
+
+/// Render `registry` as one LookML file with a view per table and an
+/// explore per flow.
+pub fn export(registry: &FlowRegistry) -> Export {
+    let (tables, flows) = sorted_model(registry);
+    let mut export = Export::default();
+    for table in tables {
+        table_view(table, &mut export);
+    }
+    for flow in flows {
+        flow_explore(flow, registry, &mut export);
+    }
+    export
+}
+
+fn table_view(table: &SemanticTable, export: &mut Export) {
+    let column = |c: &str| format!("${{TABLE}}.{c}");
+    let measure_ref = |m: &str| format!("${{{m}}}");
+    let out = &mut export.contents;
+
+    let _ = writeln!(out, "view: {} {{", table.name);
+    match &table.sql {
+        Some(sql) => {
+            let _ = writeln!(out, "  derived_table: {{\n    sql: {} ;;\n  }}", sql.trim());
+        }
+        None => {
+            let _ = writeln!(out, "  sql_table_name: {} ;;", table.table);
+        }
+    }
+    if table.query.is_some() || table.union.is_some() {
+        export.warnings.push(format!(
+            "{}: query and union tables are not exported",
+            table.name
+        ));
+    }
+    for (name, dimension) in &table.dimensions {
+        let sql = match render_sql(&dimension.expr, &column, &measure_ref) {
+            Ok(sql) => sql,
+            Err(e) => {
+                export
+                    .warnings
+                    .push(format!("{}.{name}: {e}; skipped", table.name));
+                continue;
+            }
+        };
+        let data_type = dimension.data_type.as_deref();
+        let _ = writeln!(out);
+        if is_time_type(data_type) {
+            let _ = writeln!(out, "  dimension_group: {name} {{");
+            let _ = writeln!(out, "    type: time");
+            let _ = writeln!(out, "    timeframes: {TIMEFRAMES}");
+        } else {
+            let _ = writeln!(out, "  dimension: {name} {{");
+            if table.primary_keys.len() == 1 && table.primary_keys[0] == *name {
+                let _ = writeln!(out, "    primary_key: yes");
+            }
+            let kind = if is_numeric_type(data_type) {
+                "number"
+            } else if dimension.is_boolean() {
+                "yesno"
+            } else {
+                "string"
+            };
+            let _ = writeln!(out, "    type: {kind}");
+        }
+        let _ = writeln!(out, "    sql: {sql} ;;");
+        if let Some(label) = &dimension.label {
+            let _ = writeln!(out, "    label: {}", quote(label));
+        }
+        if let Some(description) = &dimension.description {
+            let _ = writeln!(out, "    description: {}", quote(description));
+        }
+        if dimension.hidden {
+            let _ = writeln!(out, "    hidden: yes");
+        }
+        let _ = writeln!(out, "  }}");
+    }
+
+    for (name, measure) in &table.measures {
+        let converted = if let Some(post_expr) = &measure.post_expr {
+            render_sql(post_expr, &column, &measure_ref).map(|sql| ("number", sql))
+        } else if measure.is_formula() {
+            Err("formula measures are not supported".to_string())
+        } else {
+            simple_measure(measure, &column)
+        };
+        let (kind, sql) = match converted {
+            Ok(converted) => converted,
+            Err(e) => {
+                export
+                    .warnings
+                    .push(format!("{}.{name}: {e}; skipped", table.name));
+                continue;
+            }
+        };
+        let _ = writeln!(out);
+        let _ = writeln!(out, "  measure: {name} {{");
+        let _ = writeln!(out, "    type: {kind}");
+        let _ = writeln!(out, "    sql: {sql} ;;");
+        if let Some(label) = &measure.label {
+            let _ = writeln!(out, "    label: {}", quote(label));
+        }
+        if let Some(description) = &measure.description {
+            let _ = writeln!(out, "    description: {}", quote(description));
+        }
+        if measure.hidden {
+            let _ = writeln!(out, "    hidden: yes");
+        }
+        let _ = writeln!(out, "  }}");
+    }
+    let _ = writeln!(out, "}}\n");
+}
+
+fn simple_measure(
+    measure: &Measure,
+    column: &dyn Fn(&str) -> String,
+) -> Result<(&'static str, String), String> {
+    let input = filtered_input_sql(measure, column)?;
+    Ok(
+        match measure.agg.as_ref().ok_or("measure has no aggregation")? {
+            Aggregation::Sum => ("sum", input),
+            Aggregation::Avg => ("average", input),
+            Aggregation::Min => ("min", input),
+            Aggregation::Max => ("max", input),
+            Aggregation::Median => ("median", input),
+            Aggregation::CountDistinct => ("count_distinct", input),
+            // LookML's count counts rows; COUNT(expr) skips NULLs
+            Aggregation::Count => ("number", format!("COUNT({input})")),
+            other => return Err(format!("aggregation {other:?} is not supported")),
+        },
+    )
+}
+
+fn flow_explore(flow: &SemanticFlow, registry: &FlowRegistry, export: &mut Export) {
+    let base = &flow.base_table;
+    let out = &mut export.contents;
+    let _ = writeln!(out, "explore: {} {{", flow.name);
+    let _ = writeln!(out, "  from: {}", base.semantic_table);
+    let _ = writeln!(out, "  view_name: {}", base.alias);
+    if let Some(description) = &flow.description {
+        let _ = writeln!(out, "  description: {}", quote(description));
+    }
+    if let Some(table) = registry.get_table(&base.semantic_table) {
+        let column = |c: &str| format!("{}.{c}", base.alias);
+        let filters: Result<Vec<String>, String> = table
+            .default_filters
+            .iter()
+            .map(|f| render_sql(f, &column, &|m: &str| m.to_string()))
+            .collect();
+        match filters {
+            Ok(filters) if !filters.is_empty() => {
+                let _ = writeln!(out, "  sql_always_where: {} ;;", filters.join(" AND "));
+            }
+            Ok(_) => {}
+            Err(e) => export
+                .warnings
+                .push(format!("{}: default filter {e}; skipped", flow.name)),
+        }
+    }
+
+    for join in flow.joins.values() {
+        let join_type = match join.join_type {
+            JoinType::Left => "left_outer",
+            JoinType::Inner => "inner",
+            JoinType::Full => "full_outer",
+            JoinType::Right => {
+                export.warnings.push(format!(
+                    "{}: right join {} is not supported; skipped",
+                    flow.name, join.alias
+                ));
+                continue;
+            }
+        };
+        let relationship = match join.cardinality {
+            Some(JoinCardinality::OneToMany) => "one_to_many",
+            Some(JoinCardinality::OneToOne) => "one_to_one",
+            _ => "many_to_one",
+        };
+        let on: Vec<String> = join
+            .join_keys
+            .iter()
+            .map(|k| format!("{}.{} = {}.{}", join.to_table, k.left, join.alias, k.right))
+            .collect();
+        let _ = writeln!(out, "  join: {} {{", join.alias);
+        let _ = writeln!(out, "    from: {}", join.semantic_table);
+        let _ = writeln!(out, "    type: {join_type}");
+        let _ = writeln!(out, "    relationship: {relationship}");
+        let _ = writeln!(out, "    sql_on: {} ;;", on.join(" AND "));
+        let _ = writeln!(out, "  }}");
+        if join.extra_conditions.is_some() {
+            export.warnings.push(format!(
+                "{}: extra conditions of join {} are not exported",
+                flow.name, join.alias
+            ));
+        }
+        if registry
+            .get_table(&join.semantic_table)
+            .is_some_and(|t| !t.default_filters.is_empty())
+        {
+            export.warnings.push(format!(
+                "{}: default filters of joined table {} are not exported",
+                flow.name, join.semantic_table
+            ));
+        }
+    }
+    let _ = writeln!(out, "}}\n");
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
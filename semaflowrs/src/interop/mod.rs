@@ -0,0 +1,240 @@
+//! Interoperability with other semantic layer tools.
+//!
+//! Importers convert another tool's model definitions into [`SemanticTable`]
+//! and [`SemanticFlow`] entries; exporters render a [`FlowRegistry`] in
+//! another tool's format. Both report constructs they cannot express as
+//! warnings instead of failing the whole conversion.
+//!
+//! [`SemanticTable`]: crate::flows::SemanticTable
+//! [`SemanticFlow`]: crate::flows::SemanticFlow
+//! [`FlowRegistry`]: crate::registry::FlowRegistry
+
+pub mod cube;
+pub mod dbt;
+pub mod lookml;
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::flows::{BinaryOp, Expr, Measure, SemanticFlow, SemanticTable};
+use crate::registry::FlowRegistry;
+
+/// Model definitions rendered for another tool.
+#[derive(Debug, Clone, Default)]
+pub struct Export {
+    pub contents: String,
+    /// Constructs that were left out or approximated, one message each.
+    pub warnings: Vec<String>,
+}
+
+/// Tables and flows of `registry`, sorted by name.
+pub(crate) fn sorted_model(registry: &FlowRegistry) -> (Vec<&SemanticTable>, Vec<&SemanticFlow>) {
+    let tables: BTreeMap<_, _> = registry.tables.iter().collect();
+    let flows: BTreeMap<_, _> = registry.flows.iter().collect();
+    (
+        tables.into_values().collect(),
+        flows.into_values().collect(),
+    )
+}
+
+/// Render `expr` as ANSI-style SQL text. `column` and `measure` format column
+/// and measure references in the target tool's syntax. Parameters and
+/// functions without a portable spelling are reported as errors.
+pub(crate) fn render_sql(
+    expr: &Expr,
+    column: &dyn Fn(&str) -> String,
+    measure: &dyn Fn(&str) -> String,
+) -> Result<String, String> {
+    let render = |expr: &Expr| render_sql(expr, column, measure);
+    Ok(match expr {
+        Expr::Column { column: name } => column(name),
+        Expr::MeasureRef { name } => measure(name),
+        Expr::Literal { value } => render_literal(value),
+        Expr::Param { name } => return Err(format!("parameter {name} is not supported")),
+        Expr::Sql { .. } => return Err("raw SQL is not supported".to_string()),
+        Expr::Func { func, args } => {
+            let args = args.iter().map(render).collect::<Result<Vec<_>, _>>()?;
+            match serde_json::to_value(func).ok() {
+                Some(Value::String(name)) if name == "safe_divide" && args.len() == 2 => {
+                    format!("{} / NULLIF({}, 0)", args[0], args[1])
+                }
+                Some(Value::String(name))
+                    if name.starts_with("regexp_") || name.starts_with("st_") =>
+                {
+                    return Err(format!("function {name} is not supported"))
+                }
+                Some(Value::String(name)) => {
+                    format!("{}({})", name.to_uppercase(), args.join(", "))
+                }
+                _ => return Err(format!("function {func:?} is not supported")),
+            }
+        }
+        Expr::Case {
+            branches,
+            else_expr,
+        } => {
+            let mut sql = "CASE".to_string();
+            for branch in branches {
+                sql.push_str(&format!(
+                    " WHEN {} THEN {}",
+                    render(&branch.when)?,
+                    render(&branch.then)?
+                ));
+            }
+            format!("{sql} ELSE {} END", render(else_expr)?)
+        }
+        Expr::Binary { op, left, right } => {
+            format!("({} {} {})", render(left)?, binary_op(op), render(right)?)
+        }
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let list = list.iter().map(render).collect::<Result<Vec<_>, _>>()?;
+            let not = if *negated { " NOT" } else { "" };
+            format!("{}{not} IN ({})", render(expr)?, list.join(", "))
+        }
+        Expr::IsNull { expr, negated } => {
+            let not = if *negated { " NOT" } else { "" };
+            format!("{} IS{not} NULL", render(expr)?)
+        }
+        Expr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => {
+            let not = if *negated { " NOT" } else { "" };
+            format!(
+                "{}{not} BETWEEN {} AND {}",
+                render(expr)?,
+                render(low)?,
+                render(high)?
+            )
+        }
+        Expr::Not { expr } => format!("NOT ({})", render(expr)?),
+    })
+}
+
+fn render_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::String(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Bool(b) => b.to_string().to_uppercase(),
+        other => other.to_string(),
+    }
+}
+
+fn binary_op(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Modulo => "%",
+        BinaryOp::Eq => "=",
+        BinaryOp::Neq => "<>",
+        BinaryOp::Gt => ">",
+        BinaryOp::Gte => ">=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Lte => "<=",
+        BinaryOp::And => "AND",
+        BinaryOp::Or => "OR",
+    }
+}
+
+/// SQL of a simple measure's aggregated expression, with its filter folded
+/// in as `CASE WHEN filter THEN expr END` for tools without measure filters.
+pub(crate) fn filtered_input_sql(
+    measure: &Measure,
+    column: &dyn Fn(&str) -> String,
+) -> Result<String, String> {
+    let no_measures = |name: &str| name.to_string();
+    let expr = measure.expr.as_ref().ok_or("measure has no expression")?;
+    let sql = render_sql(expr, column, &no_measures)?;
+    Ok(match &measure.filter {
+        Some(filter) => format!(
+            "CASE WHEN {} THEN {sql} END",
+            render_sql(filter, column, &no_measures)?
+        ),
+        None => sql,
+    })
+}
+
+/// Whether a dimension's `data_type` names a date or timestamp.
+pub(crate) fn is_time_type(data_type: Option<&str>) -> bool {
+    data_type.is_some_and(|t| {
+        let t = t.to_ascii_lowercase();
+        t.starts_with("date") || t.starts_with("timestamp") || t == "time"
+    })
+}
+
+/// Whether a dimension's `data_type` names a numeric type.
+pub(crate) fn is_numeric_type(data_type: Option<&str>) -> bool {
+    data_type.is_some_and(|t| {
+        let t = t.to_ascii_lowercase();
+        [
+            "int", "bigint", "smallint", "double", "float", "decimal", "numeric", "real", "number",
+        ]
+        .iter()
+        .any(|n| t.starts_with(n))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flows::{CaseBranch, Function};
+
+    #[test]
+    fn renders_expressions_as_sql() {
+        let column = |c: &str| format!("${{TABLE}}.{c}");
+        let measure = |m: &str| format!("${{{m}}}");
+        let expr = Expr::Case {
+            branches: vec![CaseBranch {
+                when: Expr::Binary {
+                    op: BinaryOp::Eq,
+                    left: Box::new(Expr::Column {
+                        column: "status".to_string(),
+                    }),
+                    right: Box::new(Expr::Literal {
+                        value: Value::String("it's".to_string()),
+                    }),
+                },
+                then: Expr::Func {
+                    func: Function::Lower,
+                    args: vec![Expr::Column {
+                        column: "name".to_string(),
+                    }],
+                },
+            }],
+            else_expr: Box::new(Expr::Literal { value: Value::Null }),
+        };
+        assert_eq!(
+            render_sql(&expr, &column, &measure).unwrap(),
+            "CASE WHEN (${TABLE}.status = 'it''s') THEN LOWER(${TABLE}.name) ELSE NULL END"
+        );
+
+        let ratio = Expr::Func {
+            func: Function::SafeDivide,
+            args: vec![
+                Expr::MeasureRef {
+                    name: "revenue".to_string(),
+                },
+                Expr::MeasureRef {
+                    name: "orders".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            render_sql(&ratio, &column, &measure).unwrap(),
+            "${revenue} / NULLIF(${orders}, 0)"
+        );
+        let param = Expr::Param {
+            name: "region".to_string(),
+        };
+        assert!(render_sql(&param, &column, &measure).is_err());
+    }
+}
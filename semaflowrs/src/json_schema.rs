@@ -0,0 +1,141 @@
+//! JSON Schema for model files, derived from the serde types in [`crate::flows`].
+//!
+//! Editors that understand JSON Schema (e.g. the YAML language server) use it
+//! to autocomplete and validate table and flow files. Types with hand-written
+//! `Deserialize` impls describe their accepted forms here, so the schema
+//! follows the shorthands the loader accepts (a bare string dimension, a
+//! `{measure: name}` reference, SQL strings for expressions).
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, SubschemaValidation};
+use schemars::{schema_for, JsonSchema};
+use serde_json::{json, Value};
+
+use crate::flows::{
+    Dimension, DimensionFile, Expr, Measure, MeasureFile, SemanticFlow, SemanticTable,
+    SemanticTableFile, TaggedExpr,
+};
+
+/// Schema of a semantic table file.
+pub fn table_schema() -> Value {
+    to_value(schema_for!(SemanticTable))
+}
+
+/// Schema of a semantic flow file.
+pub fn flow_schema() -> Value {
+    to_value(schema_for!(SemanticFlow))
+}
+
+/// Schema accepting either a table or a flow file.
+pub fn model_file_schema() -> Value {
+    to_value(schema_for!(ModelFile))
+}
+
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code, clippy::large_enum_variant)]
+enum ModelFile {
+    Table(SemanticTable),
+    Flow(SemanticFlow),
+}
+
+fn to_value(schema: schemars::schema::RootSchema) -> Value {
+    serde_json::to_value(schema).unwrap_or_default()
+}
+
+fn any_of(schemas: Vec<Schema>) -> Schema {
+    Schema::Object(SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(schemas),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+fn string_schema(description: &str) -> Schema {
+    let mut schema = SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        ..Default::default()
+    };
+    schema.metadata().description = Some(description.to_string());
+    Schema::Object(schema)
+}
+
+impl JsonSchema for Expr {
+    fn schema_name() -> String {
+        "Expr".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let measure_ref = serde_json::from_value(json!({
+            "type": "object",
+            "properties": { "measure": { "type": "string" } },
+            "required": ["measure"],
+            "additionalProperties": false,
+        }))
+        .expect("static schema is valid");
+        any_of(vec![
+            string_schema("A column name, SQL expression or `{{ param }}` reference"),
+            measure_ref,
+            gen.subschema_for::<TaggedExpr>(),
+        ])
+    }
+}
+
+impl JsonSchema for Dimension {
+    fn schema_name() -> String {
+        "Dimension".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        any_of(vec![
+            string_schema("Shorthand for `expr`"),
+            gen.subschema_for::<DimensionFile>(),
+        ])
+    }
+}
+
+impl JsonSchema for Measure {
+    fn schema_name() -> String {
+        "Measure".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        MeasureFile::json_schema(gen)
+    }
+}
+
+impl JsonSchema for SemanticTable {
+    fn schema_name() -> String {
+        "SemanticTable".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        SemanticTableFile::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_tables_and_flows() {
+        let table = table_schema();
+        let properties = &table["properties"];
+        for key in ["data_source", "primary_key", "dimensions", "measures"] {
+            assert!(properties.get(key).is_some(), "missing {key}");
+        }
+        assert_eq!(table["additionalProperties"], json!(false));
+        assert!(table["definitions"].get("Expr").is_some());
+        assert!(table["definitions"].get("Aggregation").is_some());
+
+        let flow = flow_schema();
+        assert!(flow["properties"].get("base_table").is_some());
+        assert!(flow["definitions"].get("JoinType").is_some());
+
+        let model = model_file_schema();
+        assert_eq!(model["anyOf"].as_array().map(Vec::len), Some(2));
+    }
+}
@@ -1,19 +1,43 @@
 pub mod backends;
+pub mod blend;
+pub mod calculations;
+pub mod cancel;
 pub mod config;
+pub mod deprecation;
 pub mod dialect;
+pub mod drift;
 pub mod error;
 pub mod executor;
 pub mod expr_parser;
 pub mod expr_utils;
+#[cfg(feature = "flight-sql")]
+pub mod flight_sql;
 pub mod flows;
+pub mod interop;
+#[cfg(feature = "json-schema")]
+pub mod json_schema;
+pub mod lineage;
+pub mod model_files;
 pub mod pagination;
+#[cfg(feature = "pgwire")]
+pub mod pgwire;
+pub mod policy;
 #[cfg(feature = "python")]
 pub mod python;
 pub mod query_builder;
+pub mod query_tags;
 pub mod registry;
+pub mod registry_diff;
+pub mod result_cache;
 pub mod runtime;
+pub mod schedule;
 pub mod schema_cache;
+pub mod search;
 pub mod sql_ast;
+pub mod sql_format;
+pub mod telemetry;
+pub mod testing;
+pub mod type_inference;
 pub mod validation;
 
 use std::path::Path;
@@ -31,33 +55,45 @@ pub async fn load_and_validate<P: AsRef<Path>>(
     Ok(registry)
 }
 
-pub use crate::validation::Validator;
+pub use crate::validation::{Severity, ValidationIssue, Validator};
 #[cfg(feature = "bigquery")]
 pub use backends::BigQueryConnection;
 #[cfg(feature = "duckdb")]
 pub use backends::DuckDbConnection;
 #[cfg(feature = "postgres")]
 pub use backends::PostgresConnection;
-pub use backends::{BackendConnection, ConnectionManager};
+pub use backends::{BackendConnection, BatchCallback, ConnectionManager};
 pub use error::SemaflowError;
+#[cfg(feature = "arrow")]
+pub use executor::ArrowResult;
 pub use executor::{PaginatedResult, QueryResult};
 pub use flows::{QueryRequest, SemanticFlow, SemanticTable};
+pub use lineage::Lineage;
 pub use pagination::{compute_query_hash, Cursor};
-pub use query_builder::SqlBuilder;
-pub use registry::{DimensionInfo, FlowSchema, FlowSummary, MeasureInfo};
-pub use schema_cache::TableSchema;
+pub use policy::QueryContext;
+pub use query_builder::{FanoutDiagnostic, PlanStrategy, QueryExplain, SqlBuilder};
+pub use query_tags::QueryTags;
+pub use registry::{
+    DimensionInfo, FlowSchema, FlowSummary, HierarchyInfo, MeasureInfo, SchemaFieldKind,
+    SchemaFilter,
+};
+pub use registry_diff::{ModelVersion, RegistryDiff};
+pub use result_cache::{CacheKey, InMemoryQueryCache, QueryCache};
+pub use schema_cache::{TableSchema, TableStats};
+pub use sql_format::RenderOptions;
 
 // Config re-exports
 pub use config::{
-    BigQueryConfig, DuckDbConfig, PostgresConfig, QueryConfig, ResolvedDatasourceConfig,
-    SchemaCacheConfig, SemaflowConfig,
+    BigQueryConfig, CircuitBreakerConfig, ConcurrencyConfig, ConcurrencyLimit, ConnectionConfig,
+    DuckDbConfig, PlannerConfig, PostgresConfig, QueryConfig, ResolvedDatasourceConfig,
+    ResultCacheConfig, SchemaCacheConfig, SemaflowConfig, TaggingConfig,
 };
 
 // Dialect re-exports
 #[cfg(feature = "bigquery")]
 pub use dialect::BigQueryDialect;
-pub use dialect::Dialect;
 #[cfg(feature = "duckdb")]
 pub use dialect::DuckDbDialect;
 #[cfg(feature = "postgres")]
 pub use dialect::PostgresDialect;
+pub use dialect::{Dialect, DialectCapabilities, LimitSyntax};
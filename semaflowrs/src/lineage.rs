@@ -0,0 +1,332 @@
+//! Model lineage: which physical columns feed which fields and flows.
+//!
+//! [`FlowRegistry::lineage`] builds a graph with edges from physical columns
+//! to the dimensions and measures computed from them, from measures to the
+//! derived measures referencing them, and from fields and join keys to the
+//! flows exposing them. [`Lineage::downstream`] answers "what breaks if I
+//! drop `orders.amount`", and the graph serializes to JSON for data catalogs.
+//!
+//! Node ids are `column:<data_source>.<table>.<column>` (the physical table),
+//! `dimension:<semantic_table>.<name>`, `measure:<semantic_table>.<name>` and
+//! `flow:<name>`.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use serde::Serialize;
+
+use crate::expr_utils::{collect_column_refs, collect_measure_refs, raw_sql_columns};
+use crate::flows::{Expr, FormulaAst, SemanticTable};
+use crate::registry::FlowRegistry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineageNodeKind {
+    Column,
+    Dimension,
+    Measure,
+    Flow,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineageNode {
+    pub id: String,
+    pub kind: LineageNodeKind,
+    /// Semantic table (or physical table, for columns) the node belongs to;
+    /// `None` for flows.
+    pub table: Option<String>,
+    pub name: String,
+}
+
+/// `from` is used to compute `to`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct LineageEdge {
+    pub from: String,
+    pub to: String,
+}
+
+/// Lineage graph of a registry, with nodes and edges sorted by id.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Lineage {
+    pub nodes: Vec<LineageNode>,
+    pub edges: Vec<LineageEdge>,
+}
+
+impl Lineage {
+    pub fn column_id(data_source: &str, table: &str, column: &str) -> String {
+        format!("column:{data_source}.{table}.{column}")
+    }
+
+    pub fn dimension_id(semantic_table: &str, name: &str) -> String {
+        format!("dimension:{semantic_table}.{name}")
+    }
+
+    pub fn measure_id(semantic_table: &str, name: &str) -> String {
+        format!("measure:{semantic_table}.{name}")
+    }
+
+    pub fn flow_id(name: &str) -> String {
+        format!("flow:{name}")
+    }
+
+    pub fn node(&self, id: &str) -> Option<&LineageNode> {
+        self.nodes
+            .binary_search_by(|node| node.id.as_str().cmp(id))
+            .ok()
+            .map(|i| &self.nodes[i])
+    }
+
+    /// Every node computed directly or indirectly from `id`, nearest first.
+    pub fn downstream(&self, id: &str) -> Vec<&LineageNode> {
+        self.walk(id, |edge| (&edge.from, &edge.to))
+    }
+
+    /// Every node `id` is computed from, directly or indirectly, nearest first.
+    pub fn upstream(&self, id: &str) -> Vec<&LineageNode> {
+        self.walk(id, |edge| (&edge.to, &edge.from))
+    }
+
+    fn walk<'a>(
+        &'a self,
+        id: &str,
+        direction: impl Fn(&'a LineageEdge) -> (&'a String, &'a String),
+    ) -> Vec<&'a LineageNode> {
+        let mut seen: BTreeSet<&str> = BTreeSet::from([id]);
+        let mut queue: VecDeque<&str> = VecDeque::from([id]);
+        let mut found = Vec::new();
+        while let Some(current) = queue.pop_front() {
+            for edge in &self.edges {
+                let (from, to) = direction(edge);
+                if from == current && seen.insert(to.as_str()) {
+                    queue.push_back(to);
+                    found.extend(self.node(to));
+                }
+            }
+        }
+        found
+    }
+}
+
+#[derive(Default)]
+struct LineageBuilder {
+    nodes: BTreeMap<String, LineageNode>,
+    edges: BTreeSet<LineageEdge>,
+}
+
+impl LineageBuilder {
+    fn node(
+        &mut self,
+        id: String,
+        kind: LineageNodeKind,
+        table: Option<&str>,
+        name: &str,
+    ) -> String {
+        self.nodes.entry(id.clone()).or_insert_with(|| LineageNode {
+            id: id.clone(),
+            kind,
+            table: table.map(str::to_string),
+            name: name.to_string(),
+        });
+        id
+    }
+
+    fn column(&mut self, table: &SemanticTable, column: &str) -> String {
+        // Formula columns may be qualified with the table alias
+        let column = column.rsplit('.').next().unwrap_or(column);
+        self.node(
+            Lineage::column_id(&table.data_source, &table.table, column),
+            LineageNodeKind::Column,
+            Some(&table.table),
+            column,
+        )
+    }
+
+    fn edge(&mut self, from: &str, to: &str) {
+        self.edges.insert(LineageEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+        });
+    }
+
+    fn add_table(&mut self, table: &SemanticTable) {
+        for (name, dimension) in &table.dimensions {
+            let id = self.node(
+                Lineage::dimension_id(&table.name, name),
+                LineageNodeKind::Dimension,
+                Some(&table.name),
+                name,
+            );
+            let mut columns = Vec::new();
+            collect_lineage_columns(&dimension.expr, &mut columns);
+            for column in columns {
+                let column = self.column(table, &column);
+                self.edge(&column, &id);
+            }
+        }
+        for (name, measure) in &table.measures {
+            let id = self.node(
+                Lineage::measure_id(&table.name, name),
+                LineageNodeKind::Measure,
+                Some(&table.name),
+                name,
+            );
+            let mut columns = Vec::new();
+            let mut measures = Vec::new();
+            for expr in [&measure.expr, &measure.filter].into_iter().flatten() {
+                collect_lineage_columns(expr, &mut columns);
+            }
+            if let Some(post_expr) = &measure.post_expr {
+                collect_measure_refs(post_expr, &mut measures);
+            }
+            if let Some(formula) = &measure.formula {
+                collect_formula_refs(&formula.ast, table, &mut columns, &mut measures);
+            }
+            for column in columns {
+                let column = self.column(table, &column);
+                self.edge(&column, &id);
+            }
+            for referenced in measures {
+                let referenced = referenced.rsplit('.').next().unwrap_or(&referenced);
+                if table.measures.contains_key(referenced) {
+                    let other_id = self.node(
+                        Lineage::measure_id(&table.name, referenced),
+                        LineageNodeKind::Measure,
+                        Some(&table.name),
+                        referenced,
+                    );
+                    self.edge(&other_id, &id);
+                }
+            }
+        }
+    }
+}
+
+/// Columns referenced by `expr`, including `${TABLE}.column` in raw SQL.
+fn collect_lineage_columns(expr: &Expr, columns: &mut Vec<String>) {
+    match expr {
+        Expr::Sql { sql } => columns.extend(raw_sql_columns(sql)),
+        other => collect_column_refs(other, columns),
+    }
+}
+
+/// Columns and measures referenced by a formula. Bare identifiers naming a
+/// measure of the table are measure references, as in validation.
+fn collect_formula_refs(
+    ast: &FormulaAst,
+    table: &SemanticTable,
+    columns: &mut Vec<String>,
+    measures: &mut Vec<String>,
+) {
+    let children: Vec<&FormulaAst> = match ast {
+        FormulaAst::Aggregation { column, filter, .. } => {
+            columns.push(column.clone());
+            filter.iter().map(|f| f.as_ref()).collect()
+        }
+        FormulaAst::MeasureRef { name } => {
+            measures.push(name.clone());
+            Vec::new()
+        }
+        FormulaAst::Column { column } => {
+            if table.measures.contains_key(column) {
+                measures.push(column.clone());
+            } else {
+                columns.push(column.clone());
+            }
+            Vec::new()
+        }
+        FormulaAst::Literal { .. } => Vec::new(),
+        FormulaAst::Binary { left, right, .. } => vec![left.as_ref(), right.as_ref()],
+        FormulaAst::Function { args, .. } => args.iter().collect(),
+        FormulaAst::InList { expr, list, .. } => {
+            std::iter::once(expr.as_ref()).chain(list).collect()
+        }
+        FormulaAst::Between {
+            expr, low, high, ..
+        } => vec![expr.as_ref(), low.as_ref(), high.as_ref()],
+        FormulaAst::IsNull { expr, .. } | FormulaAst::Not { expr } => vec![expr.as_ref()],
+        FormulaAst::Case {
+            branches,
+            else_expr,
+        } => branches
+            .iter()
+            .flat_map(|(when, then)| [when, then])
+            .chain(else_expr.as_deref())
+            .collect(),
+    };
+    for child in children {
+        collect_formula_refs(child, table, columns, measures);
+    }
+}
+
+impl FlowRegistry {
+    /// Lineage graph from physical columns through fields to flows.
+    pub fn lineage(&self) -> Lineage {
+        let mut builder = LineageBuilder::default();
+        let tables: BTreeMap<&String, &SemanticTable> = self.tables.iter().collect();
+        for table in tables.values() {
+            builder.add_table(table);
+        }
+
+        let flows: BTreeMap<&String, _> = self.flows.iter().collect();
+        for (name, flow) in flows {
+            let flow_id = builder.node(Lineage::flow_id(name), LineageNodeKind::Flow, None, name);
+            let table_names = std::iter::once(&flow.base_table.semantic_table)
+                .chain(flow.joins.values().map(|j| &j.semantic_table));
+            for table_name in table_names {
+                let Some(table) = self.tables.get(table_name) else {
+                    continue;
+                };
+                let fields = table
+                    .dimensions
+                    .keys()
+                    .map(|d| Lineage::dimension_id(&table.name, d))
+                    .chain(
+                        table
+                            .measures
+                            .keys()
+                            .map(|m| Lineage::measure_id(&table.name, m)),
+                    );
+                for field in fields.collect::<Vec<_>>() {
+                    builder.edge(&field, &flow_id);
+                }
+            }
+            // Join keys and conditions are read by every query that uses the join
+            let aliases: BTreeMap<&str, &SemanticTable> =
+                std::iter::once((&flow.base_table.alias, &flow.base_table.semantic_table))
+                    .chain(flow.joins.values().map(|j| (&j.alias, &j.semantic_table)))
+                    .filter_map(|(alias, table)| Some((alias.as_str(), self.tables.get(table)?)))
+                    .collect();
+            for join in flow.joins.values() {
+                for key in &join.join_keys {
+                    let sides = [
+                        (join.to_table.as_str(), &key.left),
+                        (join.alias.as_str(), &key.right),
+                    ];
+                    for (alias, column) in sides {
+                        if let Some(table) = aliases.get(alias) {
+                            let column = builder.column(table, column);
+                            builder.edge(&column, &flow_id);
+                        }
+                    }
+                }
+                let mut columns = Vec::new();
+                if let Some(condition) = &join.extra_conditions {
+                    collect_column_refs(condition, &mut columns);
+                }
+                for column in &columns {
+                    let (alias, column) = column
+                        .split_once('.')
+                        .unwrap_or((join.alias.as_str(), column));
+                    if let Some(table) = aliases.get(alias) {
+                        let column = builder.column(table, column);
+                        builder.edge(&column, &flow_id);
+                    }
+                }
+            }
+        }
+
+        Lineage {
+            nodes: builder.nodes.into_values().collect(),
+            edges: builder.edges.into_iter().collect(),
+        }
+    }
+}
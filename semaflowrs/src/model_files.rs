@@ -0,0 +1,479 @@
+//! Reading model definition files.
+//!
+//! Besides plain YAML, model files may use:
+//! - anchors, aliases and `<<` merge keys;
+//! - `!include path/to/file.yml`, replacing the tagged node with the parsed
+//!   contents of another file (relative to the including file);
+//! - `${VAR}` / `${VAR:-default}` environment variables in a table's
+//!   `data_source` and `table`;
+//! - number and boolean mapping keys, read as strings (e.g. a dimension's
+//!   `labels: {1: Active}`);
+//! - with the `templates` feature, Jinja templating in files ending in `.j2`
+//!   (e.g. `orders.yml.j2`), rendered with minijinja before parsing. Only
+//!   `.j2` files are rendered, so `{{ param }}` references in plain files are
+//!   left alone.
+//!
+//! Several files may define parts of the same semantic table (same `name`);
+//! [`merge_table_parts`] combines their dimensions, measures, segments,
+//! default filters, access policies, hierarchies and entities. Files whose
+//! name starts with `_` are partials: they are only read through `!include`,
+//! never loaded on their own.
+//!
+//! A flow may `extends` another; [`resolve_flow_extends`] builds it from
+//! the flow it extends.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{Mapping, Value};
+
+use crate::error::{Result, SemaflowError};
+
+/// Keys whose mappings are combined when a table is split across files.
+const MERGED_MAPPINGS: [&str; 6] = [
+    "dimensions",
+    "measures",
+    "segments",
+    "access_policies",
+    "hierarchies",
+    "entities",
+];
+/// Keys whose sequences are concatenated when a table is split across files.
+const MERGED_SEQUENCES: [&str; 1] = ["default_filters"];
+/// Flow keys a flow does not inherit from the flow it extends.
+const NOT_INHERITED: [&str; 5] = ["name", "description", "tags", "synonyms", "aggregates"];
+/// Table keys that may reference environment variables.
+const INTERPOLATED_KEYS: [&str; 2] = ["data_source", "table"];
+
+/// Parse a model file, resolving includes and merge keys.
+pub fn read_model_file(path: &Path) -> Result<Value> {
+    read_with_includes(path, &mut Vec::new())
+}
+
+/// Whether `path` is a partial, only read through `!include`.
+pub fn is_partial(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('_'))
+}
+
+/// Whether `path` is a Jinja template (`*.j2`).
+pub fn is_template(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "j2")
+}
+
+#[cfg(feature = "templates")]
+fn render_template(path: &Path, source: &str) -> Result<String> {
+    let env = minijinja::Environment::new();
+    env.render_str(source, minijinja::context! {})
+        .map_err(|e| SemaflowError::Validation(format!("failed to render {}: {e}", path.display())))
+}
+
+#[cfg(not(feature = "templates"))]
+fn render_template(path: &Path, _source: &str) -> Result<String> {
+    Err(SemaflowError::Config(format!(
+        "{} is a template; build with the `templates` feature to load it",
+        path.display()
+    )))
+}
+
+fn read_with_includes(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value> {
+    let canonical = fs::canonicalize(path)?;
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(SemaflowError::Validation(format!(
+            "include cycle: {}",
+            chain.join(" -> ")
+        )));
+    }
+    let mut contents = fs::read_to_string(path)?;
+    if is_template(path) {
+        contents = render_template(path, &contents)?;
+    }
+    let mut value: Value = serde_yaml::from_str(&contents).map_err(|e| {
+        SemaflowError::Validation(format!("failed to parse {}: {e}", path.display()))
+    })?;
+
+    stack.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    resolve_includes(&mut value, dir, stack)?;
+    stack.pop();
+
+    value.apply_merge().map_err(|e| {
+        SemaflowError::Validation(format!("invalid merge key in {}: {e}", path.display()))
+    })?;
+    stringify_keys(&mut value);
+    Ok(value)
+}
+
+/// Turn number and boolean mapping keys into strings, which is how the
+/// model types read every key.
+fn stringify_keys(value: &mut Value) {
+    match value {
+        Value::Mapping(map) => {
+            let entries = std::mem::take(map);
+            for (key, mut item) in entries {
+                stringify_keys(&mut item);
+                let key = match key {
+                    Value::Number(n) => Value::String(n.to_string()),
+                    Value::Bool(b) => Value::String(b.to_string()),
+                    other => other,
+                };
+                map.insert(key, item);
+            }
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(stringify_keys),
+        Value::Tagged(tagged) => stringify_keys(&mut tagged.value),
+        _ => {}
+    }
+}
+
+fn resolve_includes(value: &mut Value, dir: &Path, stack: &mut Vec<PathBuf>) -> Result<()> {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == "include" => {
+            let Value::String(target) = &tagged.value else {
+                return Err(SemaflowError::Validation(
+                    "!include expects a file path".to_string(),
+                ));
+            };
+            *value = read_with_includes(&dir.join(target), stack)?;
+        }
+        Value::Tagged(tagged) => resolve_includes(&mut tagged.value, dir, stack)?,
+        Value::Sequence(items) => {
+            for item in items {
+                resolve_includes(item, dir, stack)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                resolve_includes(item, dir, stack)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Combine table definitions sharing a `name`, in file order. Scalar keys
+/// such as `data_source` may be repeated only with the same value; fields
+/// may be defined by one part only.
+pub fn merge_table_parts(parts: Vec<(PathBuf, Value)>) -> Result<Vec<(PathBuf, Value)>> {
+    let mut merged: BTreeMap<String, (PathBuf, Mapping)> = BTreeMap::new();
+    for (path, value) in parts {
+        let Value::Mapping(part) = value else {
+            return Err(SemaflowError::Validation(format!(
+                "failed to parse table {}: expected a mapping",
+                path.display()
+            )));
+        };
+        let Some(name) = part.get("name").and_then(Value::as_str).map(str::to_string) else {
+            return Err(SemaflowError::Validation(format!(
+                "failed to parse table {}: missing name",
+                path.display()
+            )));
+        };
+        let Some((_, table)) = merged.get_mut(&name) else {
+            merged.insert(name, (path, part));
+            continue;
+        };
+        for (key, incoming) in part {
+            let key_name = key.as_str().unwrap_or_default().to_string();
+            match table.get_mut(&key) {
+                None => {
+                    table.insert(key, incoming);
+                }
+                Some(Value::Mapping(existing)) if MERGED_MAPPINGS.contains(&key_name.as_str()) => {
+                    let Value::Mapping(incoming) = incoming else {
+                        return Err(conflict(&name, &key_name, &path));
+                    };
+                    for (field, definition) in incoming {
+                        if existing.contains_key(&field) {
+                            let field = field.as_str().unwrap_or_default();
+                            return Err(conflict(&name, &format!("{key_name}.{field}"), &path));
+                        }
+                        existing.insert(field, definition);
+                    }
+                }
+                Some(Value::Sequence(existing))
+                    if MERGED_SEQUENCES.contains(&key_name.as_str()) =>
+                {
+                    let Value::Sequence(incoming) = incoming else {
+                        return Err(conflict(&name, &key_name, &path));
+                    };
+                    existing.extend(incoming);
+                }
+                Some(existing) if *existing == incoming => {}
+                Some(_) => return Err(conflict(&name, &key_name, &path)),
+            }
+        }
+    }
+    Ok(merged
+        .into_values()
+        .map(|(path, table)| (path, Value::Mapping(table)))
+        .collect())
+}
+
+fn conflict(table: &str, key: &str, path: &Path) -> SemaflowError {
+    SemaflowError::Validation(format!(
+        "table {table} defines {key} more than once (again in {})",
+        path.display()
+    ))
+}
+
+/// Build flows that `extends` another from a copy of it, minus its name,
+/// description, tags, synonyms and aggregates. The flow's own `joins` are
+/// combined with the inherited ones by name, a null join removing the
+/// inherited one; any other key replaces the inherited value.
+pub fn resolve_flow_extends(flows: Vec<(PathBuf, Value)>) -> Result<Vec<(PathBuf, Value)>> {
+    let mut raw: BTreeMap<String, (PathBuf, Mapping)> = BTreeMap::new();
+    let mut order = Vec::new();
+    for (path, value) in flows {
+        let Value::Mapping(flow) = value else {
+            return Err(SemaflowError::Validation(format!(
+                "failed to parse flow {}: expected a mapping",
+                path.display()
+            )));
+        };
+        let Some(name) = flow.get("name").and_then(Value::as_str).map(str::to_string) else {
+            return Err(SemaflowError::Validation(format!(
+                "failed to parse flow {}: missing name",
+                path.display()
+            )));
+        };
+        order.push(name.clone());
+        raw.insert(name, (path, flow));
+    }
+
+    let mut resolved = BTreeMap::new();
+    let mut out = Vec::new();
+    let mut emitted = std::collections::HashSet::new();
+    for name in order {
+        // A later file defining the same flow replaced the earlier one
+        if !emitted.insert(name.clone()) {
+            continue;
+        }
+        let flow = resolve_flow(&name, &raw, &mut resolved, &mut Vec::new())?;
+        out.push((raw[&name].0.clone(), Value::Mapping(flow)));
+    }
+    Ok(out)
+}
+
+fn resolve_flow(
+    name: &str,
+    raw: &BTreeMap<String, (PathBuf, Mapping)>,
+    resolved: &mut BTreeMap<String, Mapping>,
+    stack: &mut Vec<String>,
+) -> Result<Mapping> {
+    if let Some(flow) = resolved.get(name) {
+        return Ok(flow.clone());
+    }
+    if stack.iter().any(|n| n == name) {
+        let chain: Vec<&str> = stack.iter().map(String::as_str).chain([name]).collect();
+        return Err(SemaflowError::Validation(format!(
+            "flow extends cycle: {}",
+            chain.join(" -> ")
+        )));
+    }
+    let (path, flow) = &raw[name];
+    let parent = match flow.get("extends") {
+        None => {
+            resolved.insert(name.to_string(), flow.clone());
+            return Ok(flow.clone());
+        }
+        Some(Value::String(parent)) if raw.contains_key(parent) => parent,
+        Some(other) => {
+            return Err(SemaflowError::Validation(format!(
+                "flow {name} extends unknown flow {} ({})",
+                serde_yaml::to_string(other).unwrap_or_default().trim(),
+                path.display()
+            )))
+        }
+    };
+
+    stack.push(name.to_string());
+    let mut merged = resolve_flow(parent, raw, resolved, stack)?;
+    stack.pop();
+    for key in NOT_INHERITED {
+        merged.remove(key);
+    }
+    for (key, value) in flow {
+        match (key.as_str(), value) {
+            (Some("joins"), Value::Mapping(own)) => {
+                let mut joins = match merged.remove("joins") {
+                    Some(Value::Mapping(joins)) => joins,
+                    _ => Mapping::new(),
+                };
+                for (join, definition) in own {
+                    if definition.is_null() {
+                        joins.remove(join);
+                    } else {
+                        joins.insert(join.clone(), definition.clone());
+                    }
+                }
+                merged.insert(key.clone(), Value::Mapping(joins));
+            }
+            _ => {
+                merged.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    resolved.insert(name.to_string(), merged.clone());
+    Ok(merged)
+}
+
+/// Expand environment variables in a table's `data_source` and `table`.
+pub fn interpolate_table_env(table: &mut Value) -> Result<()> {
+    for key in INTERPOLATED_KEYS {
+        if let Some(Value::String(s)) = table.get_mut(key) {
+            *s = interpolate_env(s, |name| std::env::var(name).ok())?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace `${VAR}` and `${VAR:-default}` in `input` using `lookup`. An unset
+/// variable without a default is an error rather than an empty string.
+pub fn interpolate_env(input: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            return Err(SemaflowError::Config(format!(
+                "unterminated variable in '{input}'"
+            )));
+        };
+        let inner = &rest[start + 2..start + len];
+        let (name, default) = match inner.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (inner, None),
+        };
+        match lookup(name).or_else(|| default.map(str::to_string)) {
+            Some(value) => out.push_str(&value),
+            None => {
+                return Err(SemaflowError::Config(format!(
+                    "environment variable {name} is not set (in '{input}')"
+                )))
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        (name == "ENV").then(|| "prod".to_string())
+    }
+
+    #[test]
+    fn interpolates_variables_and_defaults() {
+        assert_eq!(
+            interpolate_env("warehouse_${ENV}", lookup).unwrap(),
+            "warehouse_prod"
+        );
+        assert_eq!(
+            interpolate_env("${SCHEMA:-analytics}.orders", lookup).unwrap(),
+            "analytics.orders"
+        );
+        assert_eq!(interpolate_env("orders", lookup).unwrap(), "orders");
+        assert!(interpolate_env("${MISSING}.orders", lookup).is_err());
+        assert!(interpolate_env("${ENV", lookup).is_err());
+    }
+
+    #[test]
+    fn merges_table_parts_and_rejects_conflicts() {
+        let part = |yaml: &str| serde_yaml::from_str::<Value>(yaml).unwrap();
+        let parts = vec![
+            (
+                PathBuf::from("a.yml"),
+                part("name: orders\ndata_source: ds\ndimensions: {id: {expr: id}}"),
+            ),
+            (
+                PathBuf::from("b.yml"),
+                part("name: orders\ndata_source: ds\nmeasures: {n: {expr: id, agg: count}}"),
+            ),
+        ];
+        let merged = merge_table_parts(parts).unwrap();
+        assert_eq!(merged.len(), 1);
+        let table = &merged[0].1;
+        assert!(table["dimensions"].get("id").is_some());
+        assert!(table["measures"].get("n").is_some());
+
+        let parts = vec![
+            (
+                PathBuf::from("a.yml"),
+                part("name: orders\ndata_source: ds"),
+            ),
+            (
+                PathBuf::from("b.yml"),
+                part("name: orders\ndata_source: other"),
+            ),
+        ];
+        let err = merge_table_parts(parts).unwrap_err().to_string();
+        assert!(err.contains("data_source"), "{err}");
+    }
+
+    #[test]
+    fn extended_flows_inherit_and_override_joins() {
+        let flow = |yaml: &str| {
+            (
+                PathBuf::from("f.yml"),
+                serde_yaml::from_str::<Value>(yaml).unwrap(),
+            )
+        };
+        let flows = vec![
+            flow(
+                "name: sales_with_returns\nextends: sales\njoins: {r: {alias: r}, s: null}",
+            ),
+            flow("name: sales\nbase_table: {alias: o}\ntags: [core]\njoins: {c: {alias: c}, s: {alias: s}}"),
+        ];
+        let resolved = resolve_flow_extends(flows).unwrap();
+        assert_eq!(resolved.len(), 2);
+        let child = &resolved[0].1;
+        assert_eq!(child["name"], "sales_with_returns");
+        assert_eq!(child["base_table"]["alias"], "o");
+        assert!(child.get("tags").is_none());
+        let joins: Vec<_> = child["joins"]
+            .as_mapping()
+            .unwrap()
+            .keys()
+            .filter_map(Value::as_str)
+            .collect();
+        assert_eq!(joins, ["c", "r"]);
+
+        let cycle = vec![flow("name: a\nextends: b"), flow("name: b\nextends: a")];
+        let err = resolve_flow_extends(cycle).unwrap_err().to_string();
+        assert!(err.contains("a -> b -> a"), "{err}");
+    }
+
+    #[cfg(feature = "templates")]
+    #[test]
+    fn renders_templated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.yml.j2");
+        let template = r#"
+name: orders
+measures:
+{% for status in ["placed", "shipped"] %}
+  {{ status }}_count:
+    expr: id
+    agg: count
+    filter: "status = '{{ status }}'"
+{% endfor %}
+"#;
+        fs::write(&path, template).unwrap();
+        let value = read_model_file(&path).unwrap();
+        assert!(value["measures"].get("placed_count").is_some());
+        assert!(value["measures"].get("shipped_count").is_some());
+    }
+}
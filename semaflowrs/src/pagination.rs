@@ -3,15 +3,27 @@
 //! Provides stateless cursor encoding/decoding for paginating query results.
 //! Supports two cursor types:
 //! - BigQuery: Uses native job_id + page_token for zero re-computation
-//! - SQL (Postgres/DuckDB): Uses LIMIT/OFFSET pagination
+//! - SQL (Postgres/DuckDB): Uses LIMIT/OFFSET pagination, or seeks past the
+//!   previous page's last row when the order identifies rows uniquely
+//!   ([`Keyset`])
 
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use crate::dialect::Dialect;
 use crate::error::{Result, SemaflowError};
-use crate::flows::QueryRequest;
+use crate::executor::ColumnMeta;
+use crate::flows::{QueryRequest, SortDirection};
+use crate::sql_ast::sanitize_alias;
+
+const PAGE_TABLE: &str = "__page";
+const COUNT_TABLE: &str = "__count";
 
 /// Cursor for paginating through query results.
 ///
@@ -32,14 +44,24 @@ pub enum Cursor {
         query_hash: u64,
         /// Row offset for graceful fallback if job expires
         offset: u64,
+        /// Flow definition version the first page was produced from
+        #[serde(default)]
+        model_version: u64,
     },
-    /// SQL cursor using LIMIT/OFFSET pagination.
+    /// SQL cursor using LIMIT/OFFSET or keyset pagination.
     /// Used for Postgres and DuckDB backends.
     Sql {
         /// Row offset for next page
         offset: u64,
         /// Query hash to validate cursor matches current query
         query_hash: u64,
+        /// Flow definition version the first page was produced from
+        #[serde(default)]
+        model_version: u64,
+        /// Sort key values of the previous page's last row; the next page
+        /// seeks past them instead of skipping `offset` rows
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        after: Option<Vec<Value>>,
     },
 }
 
@@ -51,12 +73,45 @@ impl Cursor {
             page_token,
             query_hash,
             offset,
+            model_version: 0,
         }
     }
 
     /// Create a new SQL cursor for LIMIT/OFFSET pagination.
     pub fn sql(offset: u64, query_hash: u64) -> Self {
-        Cursor::Sql { offset, query_hash }
+        Cursor::Sql {
+            offset,
+            query_hash,
+            model_version: 0,
+            after: None,
+        }
+    }
+
+    /// Create a SQL cursor that seeks past the sort key values `after`.
+    pub fn keyset(offset: u64, query_hash: u64, after: Vec<Value>) -> Self {
+        Cursor::Sql {
+            offset,
+            query_hash,
+            model_version: 0,
+            after: Some(after),
+        }
+    }
+
+    /// Stamp the flow definition version this cursor was produced from.
+    pub fn with_model_version(mut self, version: u64) -> Self {
+        match &mut self {
+            Cursor::BigQuery { model_version, .. } => *model_version = version,
+            Cursor::Sql { model_version, .. } => *model_version = version,
+        }
+        self
+    }
+
+    /// Get the flow definition version from this cursor (0 if never stamped).
+    pub fn model_version(&self) -> u64 {
+        match self {
+            Cursor::BigQuery { model_version, .. } => *model_version,
+            Cursor::Sql { model_version, .. } => *model_version,
+        }
     }
 
     /// Get the query hash from this cursor.
@@ -75,6 +130,14 @@ impl Cursor {
         }
     }
 
+    /// Get the sort key values to seek past, for keyset cursors.
+    pub fn after(&self) -> Option<&[Value]> {
+        match self {
+            Cursor::Sql { after, .. } => after.as_deref(),
+            Cursor::BigQuery { .. } => None,
+        }
+    }
+
     /// Encode cursor to a URL-safe base64 string.
     pub fn encode(&self) -> Result<String> {
         let json = serde_json::to_string(self)
@@ -103,6 +166,223 @@ impl Cursor {
         }
         Ok(())
     }
+
+    /// Validate that the flow definition has not changed since the first page.
+    ///
+    /// Returns [`SemaflowError::StaleCursor`] on mismatch so callers can restart
+    /// pagination instead of mixing pages produced by different definitions.
+    pub fn validate_model_version(&self, expected_version: u64) -> Result<()> {
+        if self.model_version() != expected_version {
+            return Err(SemaflowError::StaleCursor(
+                "flow definition changed since this cursor was issued - restart pagination"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Keyset (seek) pagination over a request's ORDER BY.
+///
+/// Deep OFFSET pages make the database produce and discard every earlier
+/// row. When the order is deterministic, each page instead filters on
+/// `(a, b) > (x, y)` with the previous page's last sort key values. NULLs sort
+/// first so rows after a seek key never hold NULL in a key column; a page
+/// ending on a NULL key value continues by OFFSET over the same ordering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyset {
+    /// Result column aliases of the sort keys, in order.
+    columns: Vec<String>,
+    descending: bool,
+}
+
+impl Keyset {
+    /// The keyset for `request`, if its order identifies result rows
+    /// uniquely: it sorts by every requested dimension (and optionally
+    /// measures or calculations), all in one direction, without totals rows.
+    pub fn for_request(request: &QueryRequest) -> Option<Self> {
+        let descending = matches!(request.order.first()?.direction, SortDirection::Desc);
+        if request.totals || !request.subtotals.is_empty() {
+            return None;
+        }
+        if request
+            .order
+            .iter()
+            .any(|item| matches!(item.direction, SortDirection::Desc) != descending)
+        {
+            return None;
+        }
+        let columns: Vec<String> = request
+            .order
+            .iter()
+            .map(|item| sanitize_alias(&item.column))
+            .collect();
+        let dimensions: HashSet<String> = request
+            .dimensions
+            .iter()
+            .map(|d| sanitize_alias(&d.field))
+            .collect();
+        let selected: HashSet<String> = dimensions
+            .iter()
+            .cloned()
+            .chain(request.measures.iter().map(|m| sanitize_alias(m)))
+            .chain(request.calculations.iter().map(|c| sanitize_alias(&c.name)))
+            .collect();
+        if !columns.iter().all(|c| selected.contains(c))
+            || !dimensions.iter().all(|d| columns.contains(d))
+        {
+            return None;
+        }
+        Some(Self {
+            columns,
+            descending,
+        })
+    }
+
+    /// Wrap `sql` in the keyset ordering, seeking past `after` when given.
+    /// The backend appends LIMIT (and OFFSET) to the result.
+    pub fn render(
+        &self,
+        sql: &str,
+        after: Option<&[Value]>,
+        dialect: &dyn Dialect,
+    ) -> Result<String> {
+        let keys: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| {
+                format!(
+                    "{}.{}",
+                    dialect.quote_ident(PAGE_TABLE),
+                    dialect.quote_ident(c)
+                )
+            })
+            .collect();
+        let mut page = format!("SELECT * FROM ({sql}) {}", dialect.quote_ident(PAGE_TABLE));
+        if let Some(after) = after {
+            if after.len() != keys.len() || !after.iter().all(is_scalar) {
+                return Err(SemaflowError::Validation(
+                    "invalid cursor: keyset values do not match the query order".to_string(),
+                ));
+            }
+            let values: Vec<String> = after.iter().map(|v| dialect.render_literal(v)).collect();
+            page.push_str(&format!(
+                " WHERE ({}) {} ({})",
+                keys.join(", "),
+                if self.descending { "<" } else { ">" },
+                values.join(", ")
+            ));
+        }
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        let order: Vec<String> = keys
+            .iter()
+            .map(|k| format!("{k} {direction} NULLS FIRST"))
+            .collect();
+        page.push_str(&format!(" ORDER BY {}", order.join(", ")));
+        Ok(page)
+    }
+
+    /// The sort key values of `row` to seek past on the next page, or `None`
+    /// when one of them cannot be compared as a SQL literal: NULLs, and
+    /// temporal or binary values, whose JSON form is not their SQL form.
+    pub fn after(&self, row: &Map<String, Value>, columns: &[ColumnMeta]) -> Option<Vec<Value>> {
+        self.columns
+            .iter()
+            .map(|name| {
+                let value = row.get(name).filter(|v| is_scalar(v))?;
+                let data_type = columns
+                    .iter()
+                    .find(|c| c.name == *name)
+                    .and_then(|c| c.data_type.as_deref())
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                let literal_safe = !["date", "time", "interval", "binary", "blob", "bytea"]
+                    .iter()
+                    .any(|t| data_type.contains(t));
+                literal_safe.then(|| value.clone())
+            })
+            .collect()
+    }
+}
+
+fn is_scalar(value: &Value) -> bool {
+    matches!(value, Value::Bool(_) | Value::Number(_) | Value::String(_))
+}
+
+/// `SELECT COUNT(*)` over the rows of `sql`, for `count_total`.
+pub fn count_sql(sql: &str, dialect: &dyn Dialect) -> String {
+    format!(
+        "SELECT COUNT(*) AS {} FROM ({sql}) {}",
+        dialect.quote_ident("total_rows"),
+        dialect.quote_ident(COUNT_TABLE)
+    )
+}
+
+/// Total row counts for `count_total`, so only the first page of a query
+/// pays for the extra `COUNT(*)`.
+///
+/// Entries are keyed by data source and rendered SQL rather than the request
+/// alone, so callers under different access policies never share a count.
+/// Counts expire after the TTL since the underlying data changes.
+#[derive(Debug)]
+pub struct RowCountCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<u64, (u64, Instant)>>,
+}
+
+impl Default for RowCountCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(300), 1024)
+    }
+}
+
+impl RowCountCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries: max_entries.max(1),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Cache key for the count of `sql` against `data_source`.
+    pub fn key(data_source: &str, sql: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        data_source.hash(&mut hasher);
+        sql.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn get(&self, key: u64) -> Option<u64> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&key)
+            .filter(|(_, counted_at)| counted_at.elapsed() < self.ttl)
+            .map(|(total, _)| *total)
+    }
+
+    pub fn insert(&self, key: u64, total: u64) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (_, counted_at)| counted_at.elapsed() < self.ttl);
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (_, counted_at))| *counted_at)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (total, Instant::now()));
+    }
+}
+
+/// Re-encode a cursor produced by a backend with the flow definition version.
+pub fn stamp_model_version(encoded: &str, version: u64) -> Result<String> {
+    Cursor::decode(encoded)?
+        .with_model_version(version)
+        .encode()
 }
 
 /// Compute a hash of the query request for cursor validation.
@@ -127,10 +407,35 @@ pub fn compute_query_hash(request: &QueryRequest) -> u64 {
         order_json.hash(&mut hasher);
     }
 
+    // Bins change the dimension values, so a cursor must not cross them
+    if let Ok(bins_json) = serde_json::to_string(&request.bins) {
+        bins_json.hash(&mut hasher);
+    }
+
+    // The time filter narrows the rows just like an explicit filter
+    if let Some(range) = &request.time_filter {
+        String::from(range.clone()).hash(&mut hasher);
+    }
+
+    // Gap filling adds rows, so it changes what a cursor points into
+    request.fill_gaps.hash(&mut hasher);
+    request.gap_fill.hash(&mut hasher);
+    request.totals.hash(&mut hasher);
+    request.subtotals.hash(&mut hasher);
+    request.calculations.hash(&mut hasher);
+    request.drill.hash(&mut hasher);
+    request.ignore_default_filters.hash(&mut hasher);
+    request.segments.hash(&mut hasher);
+
+    // Parameter values change the computed dimensions and measures
+    if let Ok(params_json) = serde_json::to_string(&request.params) {
+        params_json.hash(&mut hasher);
+    }
+
     // Include limit in hash since it affects total result cap
     request.limit.hash(&mut hasher);
 
-    // Note: page_size, cursor, and offset are NOT included in hash
+    // Note: page_size, cursor, offset and count_total are NOT included in hash
     // since they're pagination controls, not query definition
 
     hasher.finish()
@@ -147,7 +452,9 @@ mod tests {
         let decoded = Cursor::decode(&encoded).unwrap();
 
         match decoded {
-            Cursor::Sql { offset, query_hash } => {
+            Cursor::Sql {
+                offset, query_hash, ..
+            } => {
                 assert_eq!(offset, 100);
                 assert_eq!(query_hash, 12345678);
             }
@@ -172,6 +479,7 @@ mod tests {
                 page_token,
                 query_hash,
                 offset,
+                ..
             } => {
                 assert_eq!(job_id, "job_abc123");
                 assert_eq!(page_token, "token_xyz");
@@ -206,7 +514,7 @@ mod tests {
     fn test_query_hash_consistency() {
         let request = QueryRequest {
             flow: "sales".to_string(),
-            dimensions: vec!["country".to_string()],
+            dimensions: vec!["country".into()],
             measures: vec!["revenue".to_string()],
             ..Default::default()
         };
@@ -221,14 +529,14 @@ mod tests {
     fn test_query_hash_different_queries() {
         let request1 = QueryRequest {
             flow: "sales".to_string(),
-            dimensions: vec!["country".to_string()],
+            dimensions: vec!["country".into()],
             measures: vec!["revenue".to_string()],
             ..Default::default()
         };
 
         let request2 = QueryRequest {
             flow: "sales".to_string(),
-            dimensions: vec!["region".to_string()], // Different dimension
+            dimensions: vec!["region".into()], // Different dimension
             measures: vec!["revenue".to_string()],
             ..Default::default()
         };
@@ -238,4 +546,163 @@ mod tests {
         let hash2 = compute_query_hash(&request2);
         assert_ne!(hash1, hash2);
     }
+
+    #[test]
+    fn test_model_version_roundtrip_and_validation() {
+        let encoded = Cursor::sql(10, 42).encode().unwrap();
+        let stamped = stamp_model_version(&encoded, 7).unwrap();
+        let decoded = Cursor::decode(&stamped).unwrap();
+        assert_eq!(decoded.model_version(), 7);
+        assert_eq!(decoded.offset(), 10);
+        assert!(decoded.validate_model_version(7).is_ok());
+        assert!(matches!(
+            decoded.validate_model_version(8),
+            Err(SemaflowError::StaleCursor(_))
+        ));
+    }
+
+    fn ordered_request(order: &[(&str, SortDirection)]) -> QueryRequest {
+        QueryRequest {
+            flow: "sales".to_string(),
+            dimensions: vec!["c.country".into(), "o.status".into()],
+            measures: vec!["o.revenue".to_string()],
+            order: order
+                .iter()
+                .map(|(column, direction)| crate::flows::OrderItem {
+                    column: column.to_string(),
+                    direction: direction.clone(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_keyset_requires_deterministic_order() {
+        use SortDirection::{Asc, Desc};
+        assert!(
+            Keyset::for_request(&ordered_request(&[("c.country", Asc), ("o.status", Asc)]))
+                .is_some()
+        );
+        // Not every dimension is ordered, so rows can tie
+        assert!(Keyset::for_request(&ordered_request(&[("c.country", Asc)])).is_none());
+        // Mixed directions cannot be one row-value comparison
+        assert!(
+            Keyset::for_request(&ordered_request(&[("c.country", Asc), ("o.status", Desc)]))
+                .is_none()
+        );
+        assert!(Keyset::for_request(&ordered_request(&[])).is_none());
+    }
+
+    #[cfg(feature = "duckdb")]
+    #[test]
+    fn test_keyset_renders_seek_predicate() {
+        let request = ordered_request(&[
+            ("o.revenue", SortDirection::Desc),
+            ("c.country", SortDirection::Desc),
+            ("o.status", SortDirection::Desc),
+        ]);
+        let keyset = Keyset::for_request(&request).unwrap();
+        let dialect = crate::dialect::DuckDbDialect;
+
+        let first = keyset.render("SELECT 1", None, &dialect).unwrap();
+        assert!(!first.contains("WHERE"));
+        assert!(first.ends_with(
+            "ORDER BY \"__page\".\"o__revenue\" DESC NULLS FIRST, \"__page\".\"c__country\" DESC NULLS FIRST, \"__page\".\"o__status\" DESC NULLS FIRST"
+        ));
+
+        let after = vec![
+            serde_json::json!(12.5),
+            serde_json::json!("O'Neil"),
+            serde_json::json!("complete"),
+        ];
+        let next = keyset.render("SELECT 1", Some(&after), &dialect).unwrap();
+        assert!(next.contains(
+            "WHERE (\"__page\".\"o__revenue\", \"__page\".\"c__country\", \"__page\".\"o__status\") < (12.5, 'O''Neil', 'complete')"
+        ));
+
+        // Tampered cursors cannot inject arbitrary values
+        assert!(keyset
+            .render("SELECT 1", Some(&[serde_json::json!({"a": 1})]), &dialect)
+            .is_err());
+    }
+
+    #[test]
+    fn test_keyset_after_skips_unsafe_values() {
+        let request = ordered_request(&[
+            ("c.country", SortDirection::Asc),
+            ("o.status", SortDirection::Asc),
+        ]);
+        let keyset = Keyset::for_request(&request).unwrap();
+        let row = |country: Value| {
+            let mut row = Map::new();
+            row.insert("c__country".to_string(), country);
+            row.insert("o__status".to_string(), Value::from("complete"));
+            row
+        };
+        let text = [
+            ColumnMeta::named("c__country"),
+            ColumnMeta::named("o__status"),
+        ];
+        assert_eq!(
+            keyset.after(&row(Value::from("US")), &text),
+            Some(vec![Value::from("US"), Value::from("complete")])
+        );
+        assert_eq!(keyset.after(&row(Value::Null), &text), None);
+
+        let temporal = [
+            ColumnMeta {
+                data_type: Some("Date32".to_string()),
+                ..ColumnMeta::named("c__country")
+            },
+            ColumnMeta::named("o__status"),
+        ];
+        assert_eq!(keyset.after(&row(Value::from(19723)), &temporal), None);
+    }
+
+    #[test]
+    fn test_keyset_cursor_roundtrip() {
+        let cursor = Cursor::keyset(20, 7, vec![Value::from("US"), Value::from(3)]);
+        let decoded = Cursor::decode(&cursor.encode().unwrap()).unwrap();
+        assert_eq!(decoded.offset(), 20);
+        assert_eq!(
+            decoded.after(),
+            Some(&[Value::from("US"), Value::from(3)][..])
+        );
+        assert_eq!(Cursor::sql(20, 7).after(), None);
+    }
+
+    #[test]
+    fn test_row_count_cache() {
+        let cache = RowCountCache::new(Duration::from_secs(60), 2);
+        let first = RowCountCache::key("warehouse", "SELECT 1");
+        assert_ne!(first, RowCountCache::key("replica", "SELECT 1"));
+        assert_eq!(cache.get(first), None);
+
+        cache.insert(first, 42);
+        assert_eq!(cache.get(first), Some(42));
+
+        // Full: an older count makes room
+        let second = RowCountCache::key("warehouse", "SELECT 2");
+        let third = RowCountCache::key("warehouse", "SELECT 3");
+        cache.insert(second, 7);
+        cache.insert(third, 9);
+        assert_eq!(cache.get(third), Some(9));
+        let kept = [first, second, third]
+            .iter()
+            .filter(|key| cache.get(**key).is_some())
+            .count();
+        assert_eq!(kept, 2);
+
+        let expired = RowCountCache::new(Duration::ZERO, 2);
+        expired.insert(first, 42);
+        assert_eq!(expired.get(first), None);
+    }
+
+    #[test]
+    fn test_unversioned_cursor_decodes() {
+        let legacy = URL_SAFE_NO_PAD.encode(br#"{"backend":"sql","offset":5,"query_hash":1}"#);
+        let decoded = Cursor::decode(&legacy).unwrap();
+        assert_eq!(decoded.model_version(), 0);
+    }
 }
@@ -0,0 +1,664 @@
+//! PostgreSQL wire-protocol frontend.
+//!
+//! [`PgWireServer`] lets any Postgres client (psql, JDBC/ODBC drivers, BI
+//! tools) query flows with a constrained SQL dialect, described in [`sql`]:
+//! the flow is the table, its dimensions and measures are the columns, and
+//! grouping follows from the selected dimensions. Statements are mapped onto a
+//! [`QueryRequest`] and rows are streamed back as the backend produces them.
+//!
+//! The simple and extended query protocols are supported, without bind
+//! parameters; results use the text format. Catalog queries (`pg_catalog`,
+//! `information_schema`) are not answered, so tools that browse the schema
+//! should use [`FlowRegistry::flow_schema`] through another frontend.
+//!
+//! Passwords are sent in cleartext and the server does not offer TLS, so on
+//! untrusted networks run it behind a TLS-terminating proxy. Queries run with
+//! the [`QueryContext`] an [`Authenticator`] returns for the login; without
+//! one they run with an empty context, whatever user name the client sends.
+
+mod protocol;
+pub mod sql;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufStream};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+use crate::backends::ConnectionManager;
+use crate::error::{Result, SemaflowError};
+use crate::executor::QueryResult;
+use crate::flows::{Aggregation, QueryRequest};
+use crate::policy::QueryContext;
+use crate::registry::FlowRegistry;
+use crate::runtime;
+use crate::sql_ast::sanitize_alias;
+
+use protocol::{FieldDescription, FrontendMessage, MessageWriter, StartupPacket};
+use sql::{Select, Statement};
+
+const DEFAULT_BATCH_SIZE: u32 = 1_000;
+
+/// Verifies client logins and decides who their queries run on behalf of.
+///
+/// The startup `user` parameter is chosen by the client, so it identifies the
+/// caller only once an authenticator has checked the password that came with it.
+#[async_trait]
+pub trait Authenticator: Send + Sync {
+    /// The context for `user` logging in with `password`, or `None` to refuse
+    /// the login.
+    async fn authenticate(&self, user: &str, password: &str) -> Option<QueryContext>;
+}
+
+/// One password shared by all clients; logins run with an empty context.
+struct SharedPassword(String);
+
+#[async_trait]
+impl Authenticator for SharedPassword {
+    async fn authenticate(&self, _user: &str, password: &str) -> Option<QueryContext> {
+        (password == self.0).then(QueryContext::default)
+    }
+}
+
+/// Serves flows to Postgres clients.
+pub struct PgWireServer {
+    registry: Arc<FlowRegistry>,
+    connections: Arc<ConnectionManager>,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    batch_size: u32,
+    next_process_id: AtomicI32,
+}
+
+impl PgWireServer {
+    pub fn new(registry: Arc<FlowRegistry>, connections: Arc<ConnectionManager>) -> Self {
+        Self {
+            registry,
+            connections,
+            authenticator: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            next_process_id: AtomicI32::new(1),
+        }
+    }
+
+    /// Require clients to send this password (cleartext authentication, so
+    /// terminate TLS in front of the server on untrusted networks).
+    ///
+    /// The password does not identify the client: queries run with an empty
+    /// [`QueryContext`], so tables with access policies return no rows. Use
+    /// [`Self::with_authenticator`] to run them on behalf of a user.
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.authenticator = Some(Arc::new(SharedPassword(password.into())));
+        self
+    }
+
+    /// Check each login's user name and cleartext password with
+    /// `authenticator`, running its queries with the context it returns.
+    /// Replaces [`Self::with_password`].
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Rows fetched from the backend per streamed batch.
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Accept connections until the listener fails, serving each on its own
+    /// task.
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        let server = Arc::new(self);
+        tracing::info!(addr = ?listener.local_addr().ok(), "pgwire server listening");
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let _ = stream.set_nodelay(true);
+            let server = Arc::clone(&server);
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_connection(stream).await {
+                    tracing::debug!(peer = %peer, error = %e, "pgwire connection closed");
+                }
+            });
+        }
+    }
+
+    /// Run the protocol on one client connection.
+    pub async fn handle_connection<S>(&self, stream: S) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut stream = BufStream::new(stream);
+        let mut out = MessageWriter::default();
+        let parameters = loop {
+            match protocol::read_startup(&mut stream).await? {
+                StartupPacket::Startup { parameters } => break parameters,
+                // No TLS or GSS encryption; clients retry in plaintext
+                StartupPacket::SslRequest | StartupPacket::GssEncRequest => {
+                    stream.write_all(b"N").await?;
+                    stream.flush().await?;
+                }
+                StartupPacket::CancelRequest => return Ok(()),
+                StartupPacket::Unsupported(code) => {
+                    out.error("0A000", &format!("unsupported protocol version {code}"));
+                    return send(&mut stream, &mut out).await;
+                }
+            }
+        };
+        let user = parameters
+            .iter()
+            .find(|(name, _)| name == "user")
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+
+        // The client names any user it likes, so without an authenticator
+        // its queries run on behalf of nobody
+        let context = match &self.authenticator {
+            Some(authenticator) => {
+                out.authentication_cleartext();
+                send(&mut stream, &mut out).await?;
+                let context = match protocol::read_message(&mut stream).await? {
+                    Some(FrontendMessage::Password(given)) => {
+                        authenticator.authenticate(&user, &given).await
+                    }
+                    _ => None,
+                };
+                match context {
+                    Some(context) => context,
+                    None => {
+                        out.error(
+                            "28P01",
+                            &format!("password authentication failed for user \"{user}\""),
+                        );
+                        return send(&mut stream, &mut out).await;
+                    }
+                }
+            }
+            None => QueryContext::default(),
+        };
+        out.authentication_ok();
+        for (name, value) in [
+            ("server_version", "14.0 (SemaFlow)"),
+            ("server_encoding", "UTF8"),
+            ("client_encoding", "UTF8"),
+            ("DateStyle", "ISO, MDY"),
+            ("TimeZone", "UTC"),
+            ("integer_datetimes", "on"),
+            ("standard_conforming_strings", "on"),
+        ] {
+            out.parameter_status(name, value);
+        }
+        let process_id = self.next_process_id.fetch_add(1, Ordering::Relaxed);
+        out.backend_key_data(process_id, 0);
+        out.ready_for_query();
+        send(&mut stream, &mut out).await?;
+        tracing::debug!(user = %user, process_id, "pgwire session started");
+
+        let mut session = Session {
+            context,
+            statements: HashMap::new(),
+            portals: HashMap::new(),
+            failed: false,
+        };
+        while let Some(message) = protocol::read_message(&mut stream).await? {
+            // After an error the extended protocol skips messages until Sync
+            if session.failed
+                && !matches!(message, FrontendMessage::Sync | FrontendMessage::Terminate)
+            {
+                continue;
+            }
+            match message {
+                FrontendMessage::Query(sql) => {
+                    self.simple_query(&mut stream, &mut out, &session, &sql)
+                        .await?;
+                    out.ready_for_query();
+                }
+                FrontendMessage::Parse { name, query, .. } => match self.prepare(&query) {
+                    Ok(prepared) => {
+                        session.statements.insert(name, prepared);
+                        out.parse_complete();
+                    }
+                    Err(e) => session.fail(&mut out, &e),
+                },
+                FrontendMessage::Bind {
+                    portal,
+                    statement,
+                    parameters,
+                    result_formats,
+                } => {
+                    let bound = if parameters > 0 {
+                        Err(SemaflowError::Validation(
+                            "bind parameters are not supported; inline literal values".to_string(),
+                        ))
+                    } else if result_formats.iter().any(|format| *format != 0) {
+                        Err(SemaflowError::Validation(
+                            "only the text result format is supported".to_string(),
+                        ))
+                    } else {
+                        session.statement(&statement).cloned()
+                    };
+                    match bound {
+                        Ok(prepared) => {
+                            session.portals.insert(portal, prepared);
+                            out.bind_complete();
+                        }
+                        Err(e) => session.fail(&mut out, &e),
+                    }
+                }
+                FrontendMessage::Describe { portal, name } => {
+                    let prepared = if portal {
+                        session.portal(&name)
+                    } else {
+                        session.statement(&name)
+                    };
+                    match prepared {
+                        Ok(prepared) => {
+                            if !portal {
+                                out.empty_parameter_description();
+                            }
+                            match &prepared.plan {
+                                Some(plan) => out.row_description(&plan.fields()),
+                                None => out.no_data(),
+                            }
+                        }
+                        Err(e) => session.fail(&mut out, &e),
+                    }
+                }
+                FrontendMessage::Execute { portal, .. } => {
+                    // Portals run to completion; row limits are not honoured
+                    let result = match session.portal(&portal).cloned() {
+                        Ok(prepared) => {
+                            self.execute(&mut stream, &mut out, &session, &prepared, false)
+                                .await?
+                        }
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = result {
+                        session.fail(&mut out, &e);
+                    }
+                }
+                FrontendMessage::Close { portal, name } => {
+                    if portal {
+                        session.portals.remove(&name);
+                    } else {
+                        session.statements.remove(&name);
+                    }
+                    out.close_complete();
+                }
+                FrontendMessage::Sync => {
+                    session.failed = false;
+                    session.portals.clear();
+                    out.ready_for_query();
+                }
+                FrontendMessage::Flush => {}
+                FrontendMessage::Terminate => break,
+                FrontendMessage::Password(_) => {
+                    session.fail(
+                        &mut out,
+                        &SemaflowError::Validation("unexpected password message".to_string()),
+                    );
+                }
+                FrontendMessage::Other(tag) => {
+                    session.fail(
+                        &mut out,
+                        &SemaflowError::Validation(format!(
+                            "unsupported message type '{}'",
+                            tag as char
+                        )),
+                    );
+                }
+            }
+            send(&mut stream, &mut out).await?;
+        }
+        Ok(())
+    }
+
+    async fn simple_query<S>(
+        &self,
+        stream: &mut BufStream<S>,
+        out: &mut MessageWriter,
+        session: &Session,
+        sql: &str,
+    ) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let statements = sql::split_statements(sql);
+        if statements.is_empty() {
+            out.empty_query_response();
+        }
+        // The remaining statements of a failed query string are skipped
+        for statement in statements {
+            let result = match self.prepare(statement) {
+                Ok(prepared) => self.execute(stream, out, session, &prepared, true).await?,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                out.error(sqlstate(&e), &e.to_string());
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a statement and plan its result columns.
+    fn prepare(&self, sql: &str) -> Result<Prepared> {
+        let statement = sql::parse_statement(sql)?;
+        let plan = match &statement {
+            Statement::Select(select) => Some(Arc::new(self.plan(select)?)),
+            _ => None,
+        };
+        Ok(Prepared { statement, plan })
+    }
+
+    fn plan(&self, select: &Select) -> Result<Plan> {
+        let schema = self.registry.flow_schema(&select.flow)?;
+        let dimension = |field: &str| {
+            schema
+                .dimensions
+                .iter()
+                .find(|d| d.qualified_name == field || d.name == field)
+        };
+        let measure = |field: &str| {
+            schema
+                .measures
+                .iter()
+                .find(|m| m.qualified_name == field || m.name == field)
+        };
+        let request = select.to_flow_request(&schema)?;
+
+        let columns = select
+            .items
+            .iter()
+            .map(|item| {
+                let (type_oid, type_len) = if let Some(d) = dimension(&item.field) {
+                    type_for(d.data_type.as_deref()).unwrap_or(TEXT)
+                } else if let Some(m) = measure(&item.field) {
+                    type_for(m.data_type.as_deref()).unwrap_or(match m.agg {
+                        Some(
+                            Aggregation::Count | Aggregation::CountDistinct | Aggregation::CountIf,
+                        ) => INT8,
+                        Some(Aggregation::BoolAnd | Aggregation::BoolOr) => BOOL,
+                        _ => FLOAT8,
+                    })
+                } else {
+                    TEXT
+                };
+                Column {
+                    key: sanitize_alias(&item.field),
+                    field: FieldDescription {
+                        name: item.label.clone(),
+                        type_oid,
+                        type_len,
+                    },
+                }
+            })
+            .collect();
+        Ok(Plan { request, columns })
+    }
+
+    /// Run a prepared statement, writing its rows and completion tag.
+    ///
+    /// The outer error aborts the connection; the inner one is reported to
+    /// the client.
+    async fn execute<S>(
+        &self,
+        stream: &mut BufStream<S>,
+        out: &mut MessageWriter,
+        session: &Session,
+        prepared: &Prepared,
+        describe: bool,
+    ) -> io::Result<Result<()>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let plan = match (&prepared.statement, &prepared.plan) {
+            (Statement::Select(_), Some(plan)) => plan,
+            (Statement::Session(tag), _) => {
+                out.command_complete(tag);
+                return Ok(Ok(()));
+            }
+            _ => {
+                out.empty_query_response();
+                return Ok(Ok(()));
+            }
+        };
+        if describe {
+            out.row_description(&plan.fields());
+        }
+        send(stream, out).await?;
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+        let keys: Vec<String> = plan.columns.iter().map(|c| c.key.clone()).collect();
+        let on_batch = move |batch: QueryResult| -> Result<()> {
+            let mut rows = MessageWriter::default();
+            for row in &batch.rows {
+                let values: Vec<Option<String>> =
+                    keys.iter().map(|key| text_value(row.get(key))).collect();
+                rows.data_row(&values);
+            }
+            tx.send(rows.take())
+                .map_err(|_| SemaflowError::Execution("client disconnected".to_string()))
+        };
+        let query = async move {
+            let mut on_batch = on_batch;
+            runtime::run_query_streaming(
+                &self.registry,
+                &self.connections,
+                &plan.request,
+                &session.context,
+                self.batch_size,
+                &mut on_batch,
+            )
+            .await
+        };
+        let forward = async {
+            while let Some(rows) = rx.recv().await {
+                stream.write_all(&rows).await?;
+                stream.flush().await?;
+            }
+            io::Result::Ok(())
+        };
+        let (result, forwarded) = tokio::join!(query, forward);
+        forwarded?;
+        Ok(result.map(|rows| out.command_complete(&format!("SELECT {rows}"))))
+    }
+}
+
+/// Per-connection state.
+struct Session {
+    context: QueryContext,
+    statements: HashMap<String, Prepared>,
+    portals: HashMap<String, Prepared>,
+    /// Set after an error in the extended protocol, until the next Sync.
+    failed: bool,
+}
+
+impl Session {
+    fn statement(&self, name: &str) -> Result<&Prepared> {
+        self.statements.get(name).ok_or_else(|| {
+            SemaflowError::Validation(format!("prepared statement \"{name}\" does not exist"))
+        })
+    }
+
+    fn portal(&self, name: &str) -> Result<&Prepared> {
+        self.portals
+            .get(name)
+            .ok_or_else(|| SemaflowError::Validation(format!("portal \"{name}\" does not exist")))
+    }
+
+    fn fail(&mut self, out: &mut MessageWriter, error: &SemaflowError) {
+        out.error(sqlstate(error), &error.to_string());
+        self.failed = true;
+    }
+}
+
+#[derive(Clone)]
+struct Prepared {
+    statement: Statement,
+    /// The request and result columns of a `SELECT`.
+    plan: Option<Arc<Plan>>,
+}
+
+struct Plan {
+    request: QueryRequest,
+    columns: Vec<Column>,
+}
+
+impl Plan {
+    fn fields(&self) -> Vec<FieldDescription> {
+        self.columns.iter().map(|c| c.field.clone()).collect()
+    }
+}
+
+struct Column {
+    /// Key of the column in result rows.
+    key: String,
+    field: FieldDescription,
+}
+
+const TEXT: (i32, i16) = (25, -1);
+const INT8: (i32, i16) = (20, 8);
+const FLOAT8: (i32, i16) = (701, 8);
+const BOOL: (i32, i16) = (16, 1);
+
+/// Postgres type OID and length for a declared `data_type`.
+fn type_for(data_type: Option<&str>) -> Option<(i32, i16)> {
+    let data_type = data_type?.to_ascii_lowercase();
+    Some(if data_type.contains("int") {
+        INT8
+    } else if ["float", "double", "real"]
+        .iter()
+        .any(|t| data_type.contains(t))
+    {
+        FLOAT8
+    } else if ["numeric", "decimal"].iter().any(|t| data_type.contains(t)) {
+        (1700, -1)
+    } else if data_type.starts_with("bool") {
+        BOOL
+    } else if data_type.contains("timestamp") || data_type == "datetime" {
+        (1114, 8)
+    } else if data_type == "date" {
+        (1082, 4)
+    } else {
+        TEXT
+    })
+}
+
+/// A result value in the text format; `None` is NULL.
+fn text_value(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(if *b { "t" } else { "f" }.to_string()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn sqlstate(error: &SemaflowError) -> &'static str {
+    match error {
+        SemaflowError::UnknownFlow { .. } => "42P01",
+        SemaflowError::UnknownField { .. } => "42703",
+        SemaflowError::AmbiguousField { .. } => "42702",
+        SemaflowError::InvalidFilterValue { .. } => "22P02",
+        SemaflowError::Timeout(_) | SemaflowError::Cancelled => "57014",
+        SemaflowError::Overloaded(_) => "53000",
+        e if e.is_validation() => "42000",
+        _ => "XX000",
+    }
+}
+
+async fn send<S>(stream: &mut BufStream<S>, out: &mut MessageWriter) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(&out.take()).await?;
+    stream.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    struct Analysts;
+
+    #[async_trait]
+    impl Authenticator for Analysts {
+        async fn authenticate(&self, user: &str, password: &str) -> Option<QueryContext> {
+            (user == "analyst" && password == "secret")
+                .then(|| QueryContext::default().with_attribute("region", ["EMEA"]))
+        }
+    }
+
+    fn server() -> PgWireServer {
+        PgWireServer::new(
+            Arc::new(FlowRegistry::new()),
+            Arc::new(ConnectionManager::new()),
+        )
+    }
+
+    /// Log in as `user`, answering a password request with `password`, and
+    /// return the tags and bodies of the server's messages up to
+    /// ReadyForQuery or the end of the connection.
+    async fn login(server: PgWireServer, user: &str, password: &str) -> Vec<(u8, Vec<u8>)> {
+        let (mut client, conn) = tokio::io::duplex(4096);
+        tokio::spawn(async move { server.handle_connection(conn).await });
+
+        // Protocol version 3.0
+        let mut startup = 196_608i32.to_be_bytes().to_vec();
+        for part in ["user", user, ""] {
+            startup.extend_from_slice(part.as_bytes());
+            startup.push(0);
+        }
+        let len = startup.len() as i32 + 4;
+        client.write_all(&len.to_be_bytes()).await.unwrap();
+        client.write_all(&startup).await.unwrap();
+
+        let mut messages = Vec::new();
+        while let Ok(tag) = client.read_u8().await {
+            let len = client.read_i32().await.unwrap();
+            let mut body = vec![0; len as usize - 4];
+            client.read_exact(&mut body).await.unwrap();
+            let cleartext_request = tag == b'R' && body == 3i32.to_be_bytes();
+            messages.push((tag, body));
+            if tag == b'Z' {
+                break;
+            }
+            if cleartext_request {
+                let len = password.len() as i32 + 5;
+                client.write_u8(b'p').await.unwrap();
+                client.write_all(&len.to_be_bytes()).await.unwrap();
+                client.write_all(password.as_bytes()).await.unwrap();
+                client.write_u8(0).await.unwrap();
+            }
+        }
+        messages
+    }
+
+    fn tags(messages: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        messages.iter().map(|(tag, _)| *tag).collect()
+    }
+
+    #[tokio::test]
+    async fn authenticator_checks_the_password_of_the_login() {
+        let accepted = login(server().with_authenticator(Analysts), "analyst", "secret").await;
+        assert_eq!(accepted[0], (b'R', 3i32.to_be_bytes().to_vec()));
+        assert_eq!(accepted[1], (b'R', 0i32.to_be_bytes().to_vec()));
+        assert_eq!(tags(&accepted).last(), Some(&b'Z'));
+
+        let refused = login(server().with_authenticator(Analysts), "admin", "secret").await;
+        assert_eq!(tags(&refused), vec![b'R', b'E']);
+    }
+
+    #[tokio::test]
+    async fn logins_without_an_authenticator_skip_the_password() {
+        let messages = login(server(), "admin", "").await;
+        assert_eq!(messages[0], (b'R', 0i32.to_be_bytes().to_vec()));
+        assert_eq!(tags(&messages).last(), Some(&b'Z'));
+    }
+}
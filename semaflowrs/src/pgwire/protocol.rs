@@ -0,0 +1,344 @@
+//! Message framing for the PostgreSQL frontend/backend protocol (version 3).
+//!
+//! Only the messages a query-only server needs are covered; see
+//! <https://www.postgresql.org/docs/current/protocol-message-formats.html>.
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub const PROTOCOL_VERSION: i32 = 196_608;
+pub const SSL_REQUEST: i32 = 80_877_103;
+pub const GSSENC_REQUEST: i32 = 80_877_104;
+pub const CANCEL_REQUEST: i32 = 80_877_102;
+
+/// Messages larger than this are rejected rather than buffered.
+const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+
+/// The first packet of a connection, which has no type byte.
+#[derive(Debug)]
+pub enum StartupPacket {
+    Startup { parameters: Vec<(String, String)> },
+    SslRequest,
+    GssEncRequest,
+    CancelRequest,
+    Unsupported(i32),
+}
+
+/// A message sent by the client after startup.
+#[derive(Debug)]
+pub enum FrontendMessage {
+    Query(String),
+    Parse {
+        name: String,
+        query: String,
+    },
+    Bind {
+        portal: String,
+        statement: String,
+        parameters: usize,
+        result_formats: Vec<i16>,
+    },
+    Describe {
+        portal: bool,
+        name: String,
+    },
+    Execute {
+        portal: String,
+    },
+    Close {
+        portal: bool,
+        name: String,
+    },
+    Password(String),
+    Sync,
+    Flush,
+    Terminate,
+    Other(u8),
+}
+
+pub async fn read_startup<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<StartupPacket> {
+    let len = reader.read_i32().await?;
+    let body = read_body(reader, len, 4).await?;
+    let mut body = Body::new(&body);
+    let code = body.i32()?;
+    Ok(match code {
+        PROTOCOL_VERSION => {
+            let mut parameters = Vec::new();
+            loop {
+                let name = body.string()?;
+                if name.is_empty() {
+                    break;
+                }
+                parameters.push((name, body.string()?));
+            }
+            StartupPacket::Startup { parameters }
+        }
+        SSL_REQUEST => StartupPacket::SslRequest,
+        GSSENC_REQUEST => StartupPacket::GssEncRequest,
+        CANCEL_REQUEST => StartupPacket::CancelRequest,
+        other => StartupPacket::Unsupported(other),
+    })
+}
+
+/// Read the next message; `None` when the client closed the connection.
+pub async fn read_message<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> io::Result<Option<FrontendMessage>> {
+    let tag = match reader.read_u8().await {
+        Ok(tag) => tag,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let len = reader.read_i32().await?;
+    let body = read_body(reader, len, 4).await?;
+    let mut body = Body::new(&body);
+    let message = match tag {
+        b'Q' => FrontendMessage::Query(body.string()?),
+        b'P' => FrontendMessage::Parse {
+            name: body.string()?,
+            query: body.string()?,
+        },
+        b'B' => {
+            let portal = body.string()?;
+            let statement = body.string()?;
+            let formats = body.i16()?;
+            for _ in 0..formats {
+                body.i16()?;
+            }
+            let parameters = body.i16()? as usize;
+            for _ in 0..parameters {
+                let len = body.i32()?;
+                if len > 0 {
+                    body.bytes(len as usize)?;
+                }
+            }
+            let result_formats = (0..body.i16()?)
+                .map(|_| body.i16())
+                .collect::<io::Result<_>>()?;
+            FrontendMessage::Bind {
+                portal,
+                statement,
+                parameters,
+                result_formats,
+            }
+        }
+        b'D' => FrontendMessage::Describe {
+            portal: body.u8()? == b'P',
+            name: body.string()?,
+        },
+        b'E' => FrontendMessage::Execute {
+            portal: body.string()?,
+        },
+        b'C' => FrontendMessage::Close {
+            portal: body.u8()? == b'P',
+            name: body.string()?,
+        },
+        b'p' => FrontendMessage::Password(body.string()?),
+        b'S' => FrontendMessage::Sync,
+        b'H' => FrontendMessage::Flush,
+        b'X' => FrontendMessage::Terminate,
+        other => FrontendMessage::Other(other),
+    };
+    Ok(Some(message))
+}
+
+async fn read_body<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    len: i32,
+    header: usize,
+) -> io::Result<Vec<u8>> {
+    let len = usize::try_from(len)
+        .ok()
+        .and_then(|len| len.checked_sub(header))
+        .filter(|len| *len <= MAX_MESSAGE_LEN)
+        .ok_or_else(|| invalid("invalid message length"))?;
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+/// A cursor over a message body.
+struct Body<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Body<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < n {
+            return Err(invalid("truncated message"));
+        }
+        let (head, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i16(&mut self) -> io::Result<i16> {
+        let b = self.bytes(2)?;
+        Ok(i16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        let b = self.bytes(4)?;
+        Ok(i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// A NUL-terminated string.
+    fn string(&mut self) -> io::Result<String> {
+        let end = self
+            .data
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| invalid("unterminated string"))?;
+        let s = String::from_utf8_lossy(&self.data[..end]).into_owned();
+        self.data = &self.data[end + 1..];
+        Ok(s)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A result column as announced in `RowDescription`.
+#[derive(Debug, Clone)]
+pub struct FieldDescription {
+    pub name: String,
+    pub type_oid: i32,
+    pub type_len: i16,
+}
+
+/// Builds backend messages into an output buffer.
+#[derive(Debug, Default)]
+pub struct MessageWriter {
+    pub buf: Vec<u8>,
+}
+
+impl MessageWriter {
+    fn message(&mut self, tag: u8, body: impl FnOnce(&mut Vec<u8>)) {
+        self.buf.push(tag);
+        let start = self.buf.len();
+        self.buf.extend_from_slice(&[0; 4]);
+        body(&mut self.buf);
+        let len = (self.buf.len() - start) as i32;
+        self.buf[start..start + 4].copy_from_slice(&len.to_be_bytes());
+    }
+
+    pub fn authentication_ok(&mut self) {
+        self.message(b'R', |b| b.extend_from_slice(&0i32.to_be_bytes()));
+    }
+
+    pub fn authentication_cleartext(&mut self) {
+        self.message(b'R', |b| b.extend_from_slice(&3i32.to_be_bytes()));
+    }
+
+    pub fn parameter_status(&mut self, name: &str, value: &str) {
+        self.message(b'S', |b| {
+            put_string(b, name);
+            put_string(b, value);
+        });
+    }
+
+    pub fn backend_key_data(&mut self, process_id: i32, secret: i32) {
+        self.message(b'K', |b| {
+            b.extend_from_slice(&process_id.to_be_bytes());
+            b.extend_from_slice(&secret.to_be_bytes());
+        });
+    }
+
+    /// Sessions never hold a transaction open, so the status is always idle.
+    pub fn ready_for_query(&mut self) {
+        self.message(b'Z', |b| b.push(b'I'));
+    }
+
+    pub fn row_description(&mut self, fields: &[FieldDescription]) {
+        self.message(b'T', |b| {
+            b.extend_from_slice(&(fields.len() as i16).to_be_bytes());
+            for field in fields {
+                put_string(b, &field.name);
+                b.extend_from_slice(&0i32.to_be_bytes()); // table oid
+                b.extend_from_slice(&0i16.to_be_bytes()); // column number
+                b.extend_from_slice(&field.type_oid.to_be_bytes());
+                b.extend_from_slice(&field.type_len.to_be_bytes());
+                b.extend_from_slice(&(-1i32).to_be_bytes()); // type modifier
+                b.extend_from_slice(&0i16.to_be_bytes()); // text format
+            }
+        });
+    }
+
+    /// A row of text-format values; `None` is NULL.
+    pub fn data_row(&mut self, values: &[Option<String>]) {
+        self.message(b'D', |b| {
+            b.extend_from_slice(&(values.len() as i16).to_be_bytes());
+            for value in values {
+                match value {
+                    Some(v) => {
+                        b.extend_from_slice(&(v.len() as i32).to_be_bytes());
+                        b.extend_from_slice(v.as_bytes());
+                    }
+                    None => b.extend_from_slice(&(-1i32).to_be_bytes()),
+                }
+            }
+        });
+    }
+
+    pub fn command_complete(&mut self, tag: &str) {
+        self.message(b'C', |b| put_string(b, tag));
+    }
+
+    pub fn empty_query_response(&mut self) {
+        self.message(b'I', |_| {});
+    }
+
+    pub fn parse_complete(&mut self) {
+        self.message(b'1', |_| {});
+    }
+
+    pub fn bind_complete(&mut self) {
+        self.message(b'2', |_| {});
+    }
+
+    pub fn close_complete(&mut self) {
+        self.message(b'3', |_| {});
+    }
+
+    pub fn no_data(&mut self) {
+        self.message(b'n', |_| {});
+    }
+
+    /// Statements never take parameters.
+    pub fn empty_parameter_description(&mut self) {
+        self.message(b't', |b| b.extend_from_slice(&0i16.to_be_bytes()));
+    }
+
+    /// An `ErrorResponse` with severity `ERROR`.
+    pub fn error(&mut self, sqlstate: &str, message: &str) {
+        self.message(b'E', |b| {
+            for (field, value) in [(b'S', "ERROR"), (b'V', "ERROR"), (b'C', sqlstate)] {
+                b.push(field);
+                put_string(b, value);
+            }
+            b.push(b'M');
+            put_string(b, message);
+            b.push(0);
+        });
+    }
+
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    // NUL cannot appear in protocol strings
+    buf.extend(s.bytes().filter(|b| *b != 0));
+    buf.push(0);
+}
@@ -0,0 +1,728 @@
+//! The SQL dialect accepted by the wire frontend.
+//!
+//! Queries select dimensions and measures of one flow; joins, grouping and
+//! aggregation are implied by the flow, so `GROUP BY` is accepted but not
+//! needed:
+//!
+//! ```sql
+//! SELECT c.country, date_trunc('month', o.created_at) AS month, o.order_total
+//! FROM sales
+//! WHERE c.country IN ('US', 'DE') AND o.amount >= 10
+//! ORDER BY o.order_total DESC
+//! LIMIT 10
+//! ```
+//!
+//! Field names may be quoted (`"c.country"`) and prefixed with the flow or
+//! schema (`public.sales."c.country"`), as generated by BI tools.
+
+use serde_json::Value;
+
+use crate::error::{Result, SemaflowError};
+use crate::flows::{
+    DimensionRequest, Filter, FilterExpr, FilterOp, OrderItem, QueryRequest, SortDirection,
+    TimeGrain,
+};
+use crate::registry::FlowSchema;
+
+/// A parsed statement.
+#[derive(Debug, Clone)]
+pub enum Statement {
+    /// A query against a flow.
+    Select(Select),
+    /// A session statement (`SET`, `BEGIN`, ...) acknowledged without effect;
+    /// holds the command tag to reply with.
+    Session(String),
+    Empty,
+}
+
+/// A query against a flow, before fields are classified as dimensions or
+/// measures.
+#[derive(Debug, Clone)]
+pub struct Select {
+    pub flow: String,
+    pub items: Vec<SelectItem>,
+    pub filters: Vec<FilterExpr>,
+    /// Column names (or output labels) with their direction.
+    pub order: Vec<(String, SortDirection)>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectItem {
+    pub field: String,
+    pub grain: Option<TimeGrain>,
+    /// Output column label: the alias, or the field name.
+    pub label: String,
+}
+
+impl Select {
+    /// The request for this query, classifying fields by the flow's schema.
+    /// Unknown fields are requested as dimensions and reported by the builder.
+    pub fn to_flow_request(&self, schema: &FlowSchema) -> Result<QueryRequest> {
+        self.to_request(|field| {
+            !schema
+                .dimensions
+                .iter()
+                .any(|d| d.qualified_name == field || d.name == field)
+                && schema
+                    .measures
+                    .iter()
+                    .any(|m| m.qualified_name == field || m.name == field)
+        })
+    }
+
+    /// The request for this query, given which selected fields are measures.
+    pub fn to_request(&self, is_measure: impl Fn(&str) -> bool) -> Result<QueryRequest> {
+        let mut request = QueryRequest {
+            flow: self.flow.clone(),
+            filters: self.filters.clone(),
+            limit: self.limit,
+            offset: self.offset,
+            ..Default::default()
+        };
+        for item in &self.items {
+            if is_measure(&item.field) {
+                if item.grain.is_some() {
+                    return Err(SemaflowError::Validation(format!(
+                        "date_trunc applies to time dimensions, not measure {}",
+                        item.field
+                    )));
+                }
+                request.measures.push(item.field.clone());
+            } else {
+                request.dimensions.push(DimensionRequest {
+                    field: item.field.clone(),
+                    grain: item.grain.clone(),
+                });
+            }
+        }
+        for (column, direction) in &self.order {
+            // ORDER BY may name an output label
+            let column = self
+                .items
+                .iter()
+                .find(|item| item.label == *column)
+                .map_or(column, |item| &item.field);
+            request.order.push(OrderItem {
+                column: column.clone(),
+                direction: direction.clone(),
+            });
+        }
+        Ok(request)
+    }
+}
+
+/// Parse one statement (a trailing `;` is allowed).
+pub fn parse_statement(sql: &str) -> Result<Statement> {
+    let tokens = tokenize(sql)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let statement = match parser.peek() {
+        None => Statement::Empty,
+        Some(Token::Word(w)) if w.eq_ignore_ascii_case("select") => {
+            Statement::Select(parser.select()?)
+        }
+        Some(Token::Word(w)) => {
+            let command = w.to_ascii_uppercase();
+            if !SESSION_COMMANDS.contains(&command.as_str()) {
+                return Err(unsupported(&format!("{command} statements")));
+            }
+            // START TRANSACTION replies with BEGIN
+            let tag = if command == "START" {
+                "BEGIN".to_string()
+            } else {
+                command
+            };
+            return Ok(Statement::Session(tag));
+        }
+        Some(other) => return Err(syntax(&format!("unexpected {other:?}"))),
+    };
+    parser.eat_symbol(";");
+    if let Some(token) = parser.peek() {
+        return Err(syntax(&format!("unexpected {token:?} after statement")));
+    }
+    Ok(statement)
+}
+
+/// Split a simple-query string into statements on `;` outside quotes.
+pub fn split_statements(sql: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in sql.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ';') => {
+                statements.push(&sql[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&sql[start..]);
+    statements
+        .into_iter()
+        .filter(|s| !s.trim().is_empty())
+        .collect()
+}
+
+/// Statements clients send while setting up a session.
+const SESSION_COMMANDS: [&str; 8] = [
+    "SET",
+    "RESET",
+    "BEGIN",
+    "START",
+    "COMMIT",
+    "ROLLBACK",
+    "DISCARD",
+    "DEALLOCATE",
+];
+
+#[derive(Debug, Clone)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    String(String),
+    Number(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 15] = [
+    "<>", "!=", ">=", "<=", "=", "<", ">", ",", "(", ")", ".", "*", ";", "::", "-",
+];
+
+fn tokenize(sql: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("--") {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+        } else if c == '\'' || c == '"' {
+            let (text, len) = quoted(rest, c)?;
+            tokens.push(if c == '\'' {
+                Token::String(text)
+            } else {
+                Token::Quoted(text)
+            });
+            rest = &rest[len..];
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(syntax(&format!("unexpected character '{c}'")));
+        }
+    }
+    Ok(tokens)
+}
+
+/// The text of a quoted token starting at `s`, with doubled quotes unescaped,
+/// and the token's length.
+fn quoted(s: &str, quote: char) -> Result<(String, usize)> {
+    let mut text = String::new();
+    let mut chars = s.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            if chars.peek().is_some_and(|(_, next)| *next == quote) {
+                chars.next();
+                text.push(quote);
+            } else {
+                return Ok((text, i + 1));
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    Err(syntax("unterminated quoted text"))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(syntax(&format!("expected {}", keyword.to_uppercase())))
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(syntax(&format!("expected '{symbol}'")))
+        }
+    }
+
+    fn select(&mut self) -> Result<Select> {
+        self.expect_keyword("select")?;
+        // Results are already grouped by the selected dimensions
+        self.eat_keyword("distinct");
+        let mut items = vec![self.select_item()?];
+        while self.eat_symbol(",") {
+            items.push(self.select_item()?);
+        }
+        self.expect_keyword("from")?;
+        let flow = self.path()?.pop().unwrap_or_default();
+        for item in &mut items {
+            item.field = field_name(&flow, item.field.split('.').map(String::from).collect());
+        }
+        if self.eat_keyword("as")
+            || matches!(self.peek(), Some(Token::Word(w)) if !is_clause_keyword(w))
+        {
+            self.identifier()?;
+        }
+
+        let mut filters = Vec::new();
+        if self.eat_keyword("where") {
+            filters = match self.or_filter(&flow)? {
+                FilterExpr::All { all } => all,
+                other => vec![other],
+            };
+        }
+        if self.eat_keyword("group") {
+            self.expect_keyword("by")?;
+            loop {
+                self.group_item(&flow)?;
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        if self.peek_keyword("having") {
+            return Err(unsupported("HAVING; filter measures in the flow instead"));
+        }
+        let mut order = Vec::new();
+        if self.eat_keyword("order") {
+            self.expect_keyword("by")?;
+            loop {
+                let column = match self.peek() {
+                    Some(Token::Number(_)) => {
+                        let position = self.unsigned()? as usize;
+                        let item = position
+                            .checked_sub(1)
+                            .and_then(|i| items.get(i))
+                            .ok_or_else(|| {
+                                syntax(&format!("ORDER BY {position} is out of range"))
+                            })?;
+                        item.label.clone()
+                    }
+                    _ => field_name(&flow, self.path()?),
+                };
+                let direction = if self.eat_keyword("desc") {
+                    SortDirection::Desc
+                } else {
+                    self.eat_keyword("asc");
+                    SortDirection::Asc
+                };
+                if self.eat_keyword("nulls")
+                    && !(self.eat_keyword("first") || self.eat_keyword("last"))
+                {
+                    return Err(syntax("expected FIRST or LAST"));
+                }
+                order.push((column, direction));
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        let mut limit = None;
+        let mut offset = None;
+        loop {
+            if self.eat_keyword("limit") {
+                limit = if self.eat_keyword("all") {
+                    None
+                } else {
+                    Some(self.unsigned()?)
+                };
+            } else if self.eat_keyword("offset") {
+                offset = Some(self.unsigned()?);
+                self.eat_keyword("rows");
+            } else {
+                break;
+            }
+        }
+        Ok(Select {
+            flow,
+            items,
+            filters,
+            order,
+            limit,
+            offset,
+        })
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem> {
+        let (field, grain) = if self.peek_keyword("date_trunc")
+            && matches!(self.tokens.get(self.pos + 1), Some(Token::Symbol("(")))
+        {
+            self.pos += 2;
+            let Some(Token::String(grain)) = self.next() else {
+                return Err(syntax("expected a date_trunc unit such as 'month'"));
+            };
+            let grain = parse_grain(&grain)?;
+            self.expect_symbol(",")?;
+            let field = self.path()?;
+            self.expect_symbol(")")?;
+            (field, Some(grain))
+        } else if matches!(self.peek(), Some(Token::Symbol("*"))) {
+            return Err(unsupported(
+                "SELECT *; list the dimensions and measures to select",
+            ));
+        } else {
+            (self.path()?, None)
+        };
+        // The label keeps a quoted field's spelling; the flow is resolved later
+        let default_label = field.last().cloned().unwrap_or_default();
+        let label = if self.eat_keyword("as")
+            || matches!(self.peek(), Some(Token::Quoted(_)))
+            || matches!(self.peek(), Some(Token::Word(w)) if !is_clause_keyword(w))
+        {
+            self.identifier()?
+        } else {
+            default_label
+        };
+        Ok(SelectItem {
+            field: field.join("."),
+            grain,
+            label,
+        })
+    }
+
+    /// A `GROUP BY` entry: a position, a field or a `date_trunc` call, all
+    /// implied by the select list.
+    fn group_item(&mut self, flow: &str) -> Result<()> {
+        if matches!(self.peek(), Some(Token::Number(_))) {
+            self.unsigned()?;
+        } else if self.peek_keyword("date_trunc") {
+            self.select_item()?;
+        } else {
+            field_name(flow, self.path()?);
+        }
+        Ok(())
+    }
+
+    fn or_filter(&mut self, flow: &str) -> Result<FilterExpr> {
+        let mut members = vec![self.and_filter(flow)?];
+        while self.eat_keyword("or") {
+            members.push(self.and_filter(flow)?);
+        }
+        Ok(if members.len() == 1 {
+            members.remove(0)
+        } else {
+            FilterExpr::Any { any: members }
+        })
+    }
+
+    fn and_filter(&mut self, flow: &str) -> Result<FilterExpr> {
+        let mut members = vec![self.not_filter(flow)?];
+        while self.eat_keyword("and") {
+            members.push(self.not_filter(flow)?);
+        }
+        Ok(if members.len() == 1 {
+            members.remove(0)
+        } else {
+            FilterExpr::All { all: members }
+        })
+    }
+
+    fn not_filter(&mut self, flow: &str) -> Result<FilterExpr> {
+        if self.eat_keyword("not") {
+            return Ok(FilterExpr::Not {
+                not: Box::new(self.not_filter(flow)?),
+            });
+        }
+        if self.eat_symbol("(") {
+            let filter = self.or_filter(flow)?;
+            self.expect_symbol(")")?;
+            return Ok(filter);
+        }
+        self.condition(flow)
+    }
+
+    fn condition(&mut self, flow: &str) -> Result<FilterExpr> {
+        let field = field_name(flow, self.path()?);
+        let condition = |op, value| {
+            FilterExpr::Condition(Filter {
+                field: field.clone(),
+                op,
+                value,
+            })
+        };
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            let op = if self.eat_keyword("true") {
+                if negated {
+                    FilterOp::IsFalse
+                } else {
+                    FilterOp::IsTrue
+                }
+            } else if self.eat_keyword("false") {
+                if negated {
+                    FilterOp::IsTrue
+                } else {
+                    FilterOp::IsFalse
+                }
+            } else if self.eat_keyword("unknown") && !negated {
+                FilterOp::IsUnknown
+            } else {
+                return Err(unsupported("IS tests other than TRUE, FALSE and UNKNOWN"));
+            };
+            return Ok(condition(op, Value::Null));
+        }
+        let negated = self.eat_keyword("not");
+        if self.eat_keyword("in") {
+            self.expect_symbol("(")?;
+            let mut values = vec![self.literal()?];
+            while self.eat_symbol(",") {
+                values.push(self.literal()?);
+            }
+            self.expect_symbol(")")?;
+            let op = if negated {
+                FilterOp::NotIn
+            } else {
+                FilterOp::In
+            };
+            return Ok(condition(op, Value::Array(values)));
+        }
+        if self.eat_keyword("between") {
+            let low = self.literal()?;
+            self.expect_keyword("and")?;
+            let high = self.literal()?;
+            let range = FilterExpr::All {
+                all: vec![
+                    condition(FilterOp::Gte, low),
+                    condition(FilterOp::Lte, high),
+                ],
+            };
+            return Ok(if negated {
+                FilterExpr::Not {
+                    not: Box::new(range),
+                }
+            } else {
+                range
+            });
+        }
+        let op = if self.eat_keyword("like") {
+            FilterOp::Like
+        } else if self.eat_keyword("ilike") {
+            FilterOp::ILike
+        } else if negated {
+            return Err(syntax("expected IN, BETWEEN, LIKE or ILIKE after NOT"));
+        } else {
+            match self.next() {
+                Some(Token::Symbol("=")) => FilterOp::Eq,
+                Some(Token::Symbol("<>" | "!=")) => FilterOp::Neq,
+                Some(Token::Symbol(">")) => FilterOp::Gt,
+                Some(Token::Symbol(">=")) => FilterOp::Gte,
+                Some(Token::Symbol("<")) => FilterOp::Lt,
+                Some(Token::Symbol("<=")) => FilterOp::Lte,
+                _ => return Err(syntax(&format!("expected a comparison after {field}"))),
+            }
+        };
+        let value = self.literal()?;
+        let filter = condition(op, value);
+        Ok(if negated {
+            FilterExpr::Not {
+                not: Box::new(filter),
+            }
+        } else {
+            filter
+        })
+    }
+
+    fn literal(&mut self) -> Result<Value> {
+        let negative = self.eat_symbol("-");
+        let value = match self.next() {
+            Some(Token::String(s)) => Value::String(s),
+            Some(Token::Number(n)) => {
+                let n = if negative { format!("-{n}") } else { n };
+                serde_json::from_str(&n).map_err(|_| syntax(&format!("invalid number {n}")))?
+            }
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("true") => Value::Bool(true),
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("false") => Value::Bool(false),
+            // Typed literals: DATE '2024-01-01', TIMESTAMP '...'
+            Some(Token::Word(w))
+                if ["date", "timestamp"]
+                    .iter()
+                    .any(|t| w.eq_ignore_ascii_case(t)) =>
+            {
+                match self.next() {
+                    Some(Token::String(s)) => Value::String(s),
+                    _ => return Err(syntax(&format!("expected a string after {w}"))),
+                }
+            }
+            other => return Err(syntax(&format!("expected a literal, got {other:?}"))),
+        };
+        // Casts such as '2024-01-01'::date keep the literal
+        if self.eat_symbol("::") {
+            self.identifier()?;
+        }
+        Ok(value)
+    }
+
+    fn unsigned(&mut self) -> Result<u32> {
+        match self.next() {
+            Some(Token::Number(n)) => n
+                .parse()
+                .map_err(|_| syntax(&format!("expected a whole number, got {n}"))),
+            other => Err(syntax(&format!("expected a number, got {other:?}"))),
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Word(w)) => Ok(w.to_lowercase()),
+            Some(Token::Quoted(q)) => Ok(q),
+            other => Err(syntax(&format!("expected a name, got {other:?}"))),
+        }
+    }
+
+    /// A dotted name such as `c.country`, `"c.country"` or `public.sales`.
+    fn path(&mut self) -> Result<Vec<String>> {
+        let mut parts = vec![self.identifier()?];
+        while self.eat_symbol(".") {
+            parts.push(self.identifier()?);
+        }
+        Ok(parts)
+    }
+}
+
+/// The field a dotted name refers to, dropping a leading schema and flow
+/// qualifier (`public.sales.c.country` → `c.country`).
+fn field_name(flow: &str, mut parts: Vec<String>) -> String {
+    if let Some(i) = parts.iter().position(|p| p == flow) {
+        if i + 1 < parts.len() {
+            parts.drain(..=i);
+        }
+    }
+    parts.join(".")
+}
+
+fn parse_grain(unit: &str) -> Result<TimeGrain> {
+    serde_json::from_value(Value::String(unit.to_lowercase()))
+        .map_err(|_| unsupported(&format!("date_trunc unit '{unit}'")))
+}
+
+fn is_clause_keyword(word: &str) -> bool {
+    [
+        "from", "where", "group", "having", "order", "limit", "offset", "as",
+    ]
+    .iter()
+    .any(|k| word.eq_ignore_ascii_case(k))
+}
+
+fn syntax(message: &str) -> SemaflowError {
+    SemaflowError::Validation(format!("syntax error: {message}"))
+}
+
+fn unsupported(what: &str) -> SemaflowError {
+    SemaflowError::Validation(format!("not supported: {what}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(sql: &str) -> Select {
+        match parse_statement(sql).unwrap() {
+            Statement::Select(select) => select,
+            other => panic!("expected a select, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_selects_into_requests() {
+        let query = select(
+            "SELECT c.country, date_trunc('month', o.created_at) AS month, \"o.order_total\" \
+             FROM public.sales \
+             WHERE c.country IN ('US', 'DE') AND (o.amount >= 10 OR o.status = 'it''s') \
+             GROUP BY 1, 2 ORDER BY 3 DESC, month LIMIT 10;",
+        );
+        assert_eq!(query.flow, "sales");
+        assert_eq!(query.items[1].grain, Some(TimeGrain::Month));
+        assert_eq!(query.items[1].label, "month");
+        assert_eq!(query.items[2].label, "o.order_total");
+        assert_eq!(query.filters.len(), 2);
+        assert!(matches!(&query.filters[1], FilterExpr::Any { any } if any.len() == 2));
+
+        let request = query.to_request(|f| f == "o.order_total").unwrap();
+        assert_eq!(request.measures, vec!["o.order_total"]);
+        assert_eq!(request.dimensions.len(), 2);
+        assert_eq!(request.order[0].column, "o.order_total");
+        assert_eq!(request.order[1].column, "o.created_at");
+        assert_eq!(request.limit, Some(10));
+
+        let query = select("select sales.\"c.country\" from sales where c.country not like 'A%'");
+        assert_eq!(query.items[0].field, "c.country");
+        assert!(matches!(&query.filters[0], FilterExpr::Not { .. }));
+    }
+
+    #[test]
+    fn handles_session_statements_and_errors() {
+        assert!(matches!(
+            parse_statement("SET extra_float_digits = 3").unwrap(),
+            Statement::Session(tag) if tag == "SET"
+        ));
+        assert!(matches!(
+            parse_statement("START TRANSACTION").unwrap(),
+            Statement::Session(tag) if tag == "BEGIN"
+        ));
+        assert!(matches!(parse_statement("  ").unwrap(), Statement::Empty));
+        assert!(parse_statement("SELECT * FROM sales").is_err());
+        assert!(parse_statement("DELETE FROM sales").is_err());
+        assert!(parse_statement("SELECT a FROM sales HAVING a > 1").is_err());
+        assert_eq!(
+            split_statements("SET a = 'x;y'; SELECT 1;"),
+            vec!["SET a = 'x;y'", " SELECT 1"]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Row-level access policies.
+//!
+//! A [`SemanticTable`] declares `access_policies` mapping a user attribute to
+//! an expression over its rows. Each query runs with a [`QueryContext`] holding
+//! the caller's attribute values, and a row of the table is visible only when,
+//! for every policy, the expression matches one of the caller's values:
+//!
+//! ```yaml
+//! access_policies:
+//!   region: sales_region
+//!   tenant: account_id
+//! ```
+//!
+//! Policies fail closed: a caller without the attribute (or with no values for
+//! it) sees no rows of the table. The planner applies the predicate wherever
+//! the table is read - the `WHERE` clause or pre-aggregation CTE for tables it
+//! scans, the `ON` clause for joined tables - so a hidden row behaves as if it
+//! did not exist.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::flows::{BinaryOp, Expr, SemanticTable};
+
+/// Who a query runs on behalf of.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryContext {
+    /// Attribute values of the calling user, e.g. `region: ["EMEA", "APAC"]`.
+    #[serde(default)]
+    pub user_attributes: BTreeMap<String, Vec<Value>>,
+    /// The authenticated caller, tagged onto executed queries when tagging is
+    /// enabled. Not used for access policies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+}
+
+impl QueryContext {
+    /// Set the authenticated caller.
+    pub fn with_principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = Some(principal.into());
+        self
+    }
+
+    /// Add the values of one user attribute.
+    pub fn with_attribute<V: Into<Value>>(
+        mut self,
+        name: impl Into<String>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        self.user_attributes
+            .insert(name.into(), values.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+/// The row filter `table`'s access policies impose for `context`, or `None`
+/// when the table has no policies.
+pub fn row_filter(table: &SemanticTable, context: &QueryContext) -> Option<Expr> {
+    table
+        .access_policies
+        .iter()
+        .map(|(attribute, expr)| {
+            let values = context.user_attributes.get(attribute).map(Vec::as_slice);
+            attribute_filter(expr, values)
+        })
+        .reduce(|left, right| Expr::Binary {
+            op: BinaryOp::And,
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+}
+
+/// `expr IN (values...)`, or `FALSE` when the caller has no values.
+fn attribute_filter(expr: &Expr, values: Option<&[Value]>) -> Expr {
+    match values {
+        Some(values) if !values.is_empty() => Expr::InList {
+            expr: Box::new(expr.clone()),
+            list: values
+                .iter()
+                .map(|value| Expr::Literal {
+                    value: value.clone(),
+                })
+                .collect(),
+            negated: false,
+        },
+        _ => Expr::Literal {
+            value: Value::Bool(false),
+        },
+    }
+}
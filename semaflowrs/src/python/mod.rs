@@ -2,30 +2,45 @@
 
 #[cfg(feature = "duckdb")]
 use crate::backends::DuckDbConnection;
+#[cfg(feature = "arrow")]
+use crate::runtime::run_query_arrow;
 use crate::{
     backends::ConnectionManager,
-    config::{BigQueryConfig, DatasourceConfig, DuckDbConfig, PostgresConfig, SemaflowConfig},
+    blend::run_blend_query,
+    cancel::{self, CancellationToken},
+    config::{
+        BigQueryConfig, ConcurrencyLimit, DatasourceConfig, DuckDbConfig, PostgresConfig,
+        SemaflowConfig,
+    },
     flows::{
-        Aggregation, Dimension, Expr, FlowJoin, FlowTableRef, SemanticFlow as CoreSemanticFlow,
-        SemanticTable,
+        Aggregation, BlendRequest, DeprecatedFile, Deprecation, Dimension, Expr, FlowJoin,
+        FlowTableRef, SemanticFlow as CoreSemanticFlow, SemanticTable,
     },
+    policy::QueryContext,
     query_builder::SqlBuilder,
-    registry::FlowRegistry,
-    runtime::{run_query, run_query_paginated},
+    registry::{FlowRegistry, SchemaFieldKind, SchemaFilter},
+    runtime::{run_query, run_query_paginated, run_query_streaming},
     validation::Validator,
     QueryRequest, SemaflowError,
 };
-#[cfg(feature = "duckdb")]
+#[cfg(feature = "arrow")]
 use arrow::array::RecordBatchReader;
 use once_cell::sync::OnceCell;
-use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::exceptions::{
+    PyDeprecationWarning, PyRuntimeError, PyTimeoutError, PyTypeError, PyValueError,
+};
 use pyo3::prelude::*;
 use pyo3::types::{PyAny, PyDict, PyList};
 use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Instant;
 use tracing_subscriber::{fmt, EnvFilter};
 
+/// Rows per callback batch for `execute_with_callback` when not specified.
+const DEFAULT_STREAM_BATCH_SIZE: u32 = 1000;
+
 fn runtime() -> &'static tokio::runtime::Runtime {
     static RUNTIME: OnceCell<tokio::runtime::Runtime> = OnceCell::new();
     RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().expect("create tokio runtime"))
@@ -50,6 +65,39 @@ fn to_validation_err<E: std::fmt::Display>(msg: E) -> PyErr {
     PyValueError::new_err(msg.to_string())
 }
 
+pyo3::create_exception!(
+    semaflow,
+    StaleCursorError,
+    PyValueError,
+    "Cursor was issued for a different flow definition; restart pagination without a cursor."
+);
+
+/// Map core errors to Python exceptions, preserving types callers act on.
+///
+/// Every exception carries the error's machine-readable `code` (e.g.
+/// `"unknown_field"`) and a `details` dict with its structured fields.
+fn to_py_err(err: SemaflowError) -> PyErr {
+    let code = err.code();
+    let details = err.details().to_string();
+    let py_err = match err {
+        SemaflowError::StaleCursor(_) => StaleCursorError::new_err(err.to_string()),
+        SemaflowError::Timeout(_) => PyTimeoutError::new_err(err.to_string()),
+        other => to_validation_err(other),
+    };
+    Python::with_gil(|py| {
+        let value = py_err.value(py);
+        let details = py
+            .import("json")
+            .and_then(|json| json.call_method1("loads", (details,)));
+        // Attribute errors only lose the extra context, never the exception
+        let _ = value.setattr("code", code);
+        if let Ok(details) = details {
+            let _ = value.setattr("details", details);
+        }
+    });
+    py_err
+}
+
 fn dumps(py: Python<'_>, obj: &Bound<'_, PyAny>) -> PyResult<String> {
     let json = py.import("json")?;
     json.call_method1("dumps", (obj,))?.extract()
@@ -133,20 +181,53 @@ pub struct PyDataSource {
     pub(crate) duckdb_conn: Option<Arc<DuckDbConnection>>,
 }
 
-#[pymethods]
 impl PyDataSource {
-    #[new]
-    #[pyo3(signature = (name, uri, max_concurrency=None))]
-    fn new(name: String, uri: String, max_concurrency: Option<usize>) -> Self {
-        Self {
+    /// Data source for a URI: `postgres://...[#schema]` (schema defaults to
+    /// `public`), `bigquery://project/dataset`, or a DuckDB database path.
+    fn from_uri(name: String, uri: String, max_concurrency: Option<usize>) -> PyResult<Self> {
+        let (backend_type, uri, schema) =
+            if uri.starts_with("postgres://") || uri.starts_with("postgresql://") {
+                let (url, schema) = uri.rsplit_once('#').unwrap_or((&uri, "public"));
+                ("postgres", url.to_string(), Some(schema.to_string()))
+            } else if let Some(path) = uri.strip_prefix("bigquery://") {
+                let (project_id, dataset) = path
+                    .trim_end_matches('/')
+                    .split_once('/')
+                    .filter(|(project_id, dataset)| !project_id.is_empty() && !dataset.is_empty())
+                    .ok_or_else(|| {
+                        PyValueError::new_err(format!(
+                            "BigQuery URI must be bigquery://project/dataset, got {uri}"
+                        ))
+                    })?;
+                (
+                    "bigquery",
+                    format!("{project_id}|{dataset}"),
+                    Some(dataset.to_string()),
+                )
+            } else {
+                ("duckdb", uri, None)
+            };
+        Ok(Self {
             name,
             uri,
             max_concurrency,
-            backend_type: "duckdb".to_string(),
-            schema: None,
+            backend_type: backend_type.to_string(),
+            schema,
             #[cfg(feature = "duckdb")]
             duckdb_conn: None,
-        }
+        })
+    }
+}
+
+#[pymethods]
+impl PyDataSource {
+    /// Create a data source from a URI; the backend follows the scheme
+    /// (`postgres://`, `bigquery://project/dataset`), and anything else is a
+    /// DuckDB database path.
+    #[new]
+    #[pyo3(signature = (name, uri, max_concurrency=None))]
+    fn new(name: String, uri: String, max_concurrency: Option<usize>) -> PyResult<Self> {
+        Self::from_uri(name, uri, max_concurrency)
     }
 
     /// Create a DuckDB data source.
@@ -176,11 +257,11 @@ impl PyDataSource {
     ///
     /// Args:
     ///     connection_string: PostgreSQL connection string (URL or key-value format)
-    ///     schema: PostgreSQL schema name (e.g., "public")
+    ///     schema: PostgreSQL schema name (defaults to "public")
     ///     name: Optional data source name (defaults to "postgres")
     ///     max_concurrency: Optional max pool size
     #[staticmethod]
-    #[pyo3(signature = (connection_string, schema, name=None, max_concurrency=None))]
+    #[pyo3(signature = (connection_string, schema="public".to_string(), name=None, max_concurrency=None))]
     fn postgres(
         connection_string: String,
         schema: String,
@@ -273,10 +354,10 @@ impl PyDataSource {
         let conn = match &self.duckdb_conn {
             Some(c) => c.clone(),
             None => {
-                let config = DuckDbConfig {
-                    max_concurrency: self.max_concurrency.unwrap_or(4),
-                };
-                let new_conn = Arc::new(DuckDbConnection::with_config(&self.uri, config));
+                let new_conn = Arc::new(DuckDbConnection::with_config(
+                    &self.uri,
+                    DuckDbConfig::default(),
+                ));
                 self.duckdb_conn = Some(new_conn.clone());
                 new_conn
             }
@@ -333,19 +414,48 @@ pub struct PyDimension {
 #[pymethods]
 impl PyDimension {
     #[new]
-    #[pyo3(signature = (expr, data_type=None, description=None))]
+    #[pyo3(signature = (expr, data_type=None, description=None, tags=None, folder=None, deprecated=None, synonyms=None, unnest=false, labels=None, label=None, format=None, hidden=false))]
+    #[allow(clippy::too_many_arguments)]
     fn new(
         py: Python<'_>,
         expr: &Bound<'_, PyAny>,
         data_type: Option<String>,
         description: Option<String>,
+        tags: Option<Vec<String>>,
+        folder: Option<String>,
+        deprecated: Option<&Bound<'_, PyAny>>,
+        synonyms: Option<Vec<String>>,
+        unnest: bool,
+        labels: Option<&Bound<'_, PyDict>>,
+        label: Option<String>,
+        format: Option<String>,
+        hidden: bool,
     ) -> PyResult<Self> {
         let expr = expr_from_py(py, expr)?;
+        let deprecated = deprecation_from_py(py, deprecated)?;
+        // Keys may be the stored numbers themselves, e.g. {1: "Active"}
+        let labels = labels
+            .map(|dict| {
+                dict.iter()
+                    .map(|(key, label)| Ok((key.str()?.to_string(), label.extract()?)))
+                    .collect::<PyResult<BTreeMap<String, String>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
         Ok(Self {
             inner: Dimension {
                 expr,
                 data_type,
                 description,
+                tags: tags.unwrap_or_default(),
+                synonyms: synonyms.unwrap_or_default(),
+                folder,
+                deprecated,
+                label,
+                format,
+                hidden,
+                unnest,
+                labels,
             },
         })
     }
@@ -360,7 +470,8 @@ pub struct PyMeasure {
 #[pymethods]
 impl PyMeasure {
     #[new]
-    #[pyo3(signature = (expr, agg, data_type=None, description=None, filter=None, post_expr=None))]
+    #[pyo3(signature = (expr, agg, data_type=None, description=None, filter=None, post_expr=None, tags=None, folder=None, deprecated=None, synonyms=None, label=None, format=None, hidden=false))]
+    #[allow(clippy::too_many_arguments)]
     fn new(
         py: Python<'_>,
         expr: &Bound<'_, PyAny>,
@@ -369,8 +480,16 @@ impl PyMeasure {
         description: Option<String>,
         filter: Option<&Bound<'_, PyAny>>,
         post_expr: Option<&Bound<'_, PyAny>>,
+        tags: Option<Vec<String>>,
+        folder: Option<String>,
+        deprecated: Option<&Bound<'_, PyAny>>,
+        synonyms: Option<Vec<String>>,
+        label: Option<String>,
+        format: Option<String>,
+        hidden: bool,
     ) -> PyResult<Self> {
         let expr = expr_from_py(py, expr)?;
+        let deprecated = deprecation_from_py(py, deprecated)?;
         let agg_enum = match agg {
             "sum" => Aggregation::Sum,
             "count" => Aggregation::Count,
@@ -403,6 +522,13 @@ impl PyMeasure {
                 post_expr,
                 data_type,
                 description,
+                tags: tags.unwrap_or_default(),
+                synonyms: synonyms.unwrap_or_default(),
+                folder,
+                deprecated,
+                label,
+                format,
+                hidden,
             },
         })
     }
@@ -418,14 +544,18 @@ pub struct PyFlowJoin {
 #[pymethods]
 impl PyFlowJoin {
     #[new]
-    #[pyo3(signature = (semantic_table, alias, to_table, join_keys, join_type="left", description=None))]
+    #[pyo3(signature = (semantic_table, alias, to_table, join_keys=None, join_type="left", description=None, through=None, weight=None, role=None, extra_conditions=None))]
     fn new(
         semantic_table: PySemanticTable,
         alias: String,
         to_table: String,
-        join_keys: Vec<PyJoinKey>,
+        join_keys: Option<Vec<PyJoinKey>>,
         join_type: &str,
         description: Option<String>,
+        through: Option<String>,
+        weight: Option<String>,
+        role: Option<String>,
+        extra_conditions: Option<String>,
     ) -> PyResult<Self> {
         let jt = match join_type {
             "inner" => crate::flows::JoinType::Inner,
@@ -439,12 +569,18 @@ impl PyFlowJoin {
             }
         };
         let keys = join_keys
+            .unwrap_or_default()
             .into_iter()
             .map(|k| crate::flows::JoinKey {
                 left: k.left,
                 right: k.right,
             })
             .collect();
+        let extra_conditions = extra_conditions
+            .as_deref()
+            .map(crate::expr_parser::parse_expr)
+            .transpose()
+            .map_err(py_err)?;
         Ok(Self {
             inner: FlowJoin {
                 semantic_table: semantic_table.inner.name.clone(),
@@ -454,6 +590,10 @@ impl PyFlowJoin {
                 join_keys: keys,
                 description,
                 cardinality: None,
+                through,
+                weight,
+                role,
+                extra_conditions,
             },
             table: semantic_table,
         })
@@ -470,7 +610,7 @@ pub struct PySemanticTable {
 #[pymethods]
 impl PySemanticTable {
     #[new]
-    #[pyo3(signature = (name, data_source, table, primary_key=None, primary_keys=None, time_dimension=None, dimensions=None, measures=None, description=None))]
+    #[pyo3(signature = (name, data_source, table, primary_key=None, primary_keys=None, time_dimension=None, dimensions=None, measures=None, description=None, default_filters=None, access_policies=None, timezone=None, hierarchies=None, entities=None, sql=None, unique_keys=None, nullable=None, segments=None))]
     fn new(
         py: Python<'_>,
         name: String,
@@ -482,6 +622,15 @@ impl PySemanticTable {
         dimensions: Option<&Bound<'_, PyAny>>,
         measures: Option<&Bound<'_, PyAny>>,
         description: Option<String>,
+        default_filters: Option<Vec<String>>,
+        access_policies: Option<BTreeMap<String, String>>,
+        timezone: Option<String>,
+        hierarchies: Option<BTreeMap<String, Vec<String>>>,
+        entities: Option<&Bound<'_, PyAny>>,
+        sql: Option<String>,
+        unique_keys: Option<Vec<Vec<String>>>,
+        nullable: Option<BTreeMap<String, bool>>,
+        segments: Option<BTreeMap<String, String>>,
     ) -> PyResult<Self> {
         let (ds_name, ds_obj) = if let Ok(ds) = data_source.extract::<PyDataSource>() {
             (ds.name.clone(), Some(ds))
@@ -490,6 +639,33 @@ impl PySemanticTable {
         };
         let dims = dimensions_from_py(py, dimensions)?;
         let measures = measures_from_py(py, measures)?;
+        let default_filters = default_filters
+            .unwrap_or_default()
+            .iter()
+            .map(|f| crate::expr_parser::parse_expr(f).map_err(py_err))
+            .collect::<PyResult<Vec<Expr>>>()?;
+        let access_policies = access_policies
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(attribute, expr)| {
+                crate::expr_parser::parse_expr(&expr)
+                    .map(|expr| (attribute, expr))
+                    .map_err(py_err)
+            })
+            .collect::<PyResult<BTreeMap<String, Expr>>>()?;
+        let segments = segments
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, expr)| {
+                crate::expr_parser::parse_expr(&expr)
+                    .map(|expr| (name, expr))
+                    .map_err(py_err)
+            })
+            .collect::<PyResult<BTreeMap<String, Expr>>>()?;
+        let entities = match entities {
+            Some(obj) => serde_json::from_str(&dumps(py, obj)?).map_err(py_err)?,
+            None => BTreeMap::new(),
+        };
 
         // Support both primary_key (single) and primary_keys (composite)
         let pks = match (primary_keys, primary_key) {
@@ -507,19 +683,30 @@ impl PySemanticTable {
                 name,
                 data_source: ds_name,
                 table,
+                sql,
+                query: None,
+                union: None,
                 primary_keys: pks,
+                unique_keys: unique_keys.unwrap_or_default(),
+                nullable: nullable.unwrap_or_default(),
                 time_dimension,
                 smallest_time_grain: None,
+                timezone,
                 dimensions: dims,
                 measures,
                 description,
+                default_filters,
+                segments,
+                access_policies,
+                hierarchies: hierarchies.unwrap_or_default(),
+                entities,
             },
             data_source_obj: ds_obj,
         })
     }
 
     #[staticmethod]
-    #[pyo3(signature = (name, table_handle, primary_key=None, primary_keys=None, time_dimension=None, dimensions=None, measures=None, description=None))]
+    #[pyo3(signature = (name, table_handle, primary_key=None, primary_keys=None, time_dimension=None, dimensions=None, measures=None, description=None, default_filters=None, access_policies=None, timezone=None, hierarchies=None, entities=None))]
     fn from_table(
         py: Python<'_>,
         name: String,
@@ -530,6 +717,11 @@ impl PySemanticTable {
         dimensions: Option<&Bound<'_, PyAny>>,
         measures: Option<&Bound<'_, PyAny>>,
         description: Option<String>,
+        default_filters: Option<Vec<String>>,
+        access_policies: Option<BTreeMap<String, String>>,
+        timezone: Option<String>,
+        hierarchies: Option<BTreeMap<String, Vec<String>>>,
+        entities: Option<&Bound<'_, PyAny>>,
     ) -> PyResult<Self> {
         let data_source_obj = pyo3::types::PyString::new(py, &table_handle.data_source);
         Self::new(
@@ -543,6 +735,15 @@ impl PySemanticTable {
             dimensions,
             measures,
             description,
+            default_filters,
+            access_policies,
+            timezone,
+            hierarchies,
+            entities,
+            None,
+            None,
+            None,
+            None,
         )
     }
 
@@ -567,14 +768,27 @@ pub struct PySemanticFlow {
 #[pymethods]
 impl PySemanticFlow {
     #[new]
-    #[pyo3(signature = (name, base_table, base_table_alias, joins=None, description=None))]
+    #[pyo3(signature = (name, base_table, base_table_alias, joins=None, description=None, params=None, tags=None, synonyms=None, limits=None, aggregates=None, fiscal_calendar=None, timezone=None, fanout_strategy=None, default_filters=None, segments=None, deprecated=None))]
+    #[allow(clippy::too_many_arguments)]
     fn new(
+        py: Python<'_>,
         name: String,
         base_table: PySemanticTable,
         base_table_alias: String,
         joins: Option<Vec<PyFlowJoin>>,
         description: Option<String>,
-    ) -> Self {
+        params: Option<&Bound<'_, PyAny>>,
+        tags: Option<Vec<String>>,
+        synonyms: Option<Vec<String>>,
+        limits: Option<&Bound<'_, PyAny>>,
+        aggregates: Option<&Bound<'_, PyAny>>,
+        fiscal_calendar: Option<&Bound<'_, PyAny>>,
+        timezone: Option<String>,
+        fanout_strategy: Option<String>,
+        default_filters: Option<&Bound<'_, PyAny>>,
+        segments: Option<&Bound<'_, PyAny>>,
+        deprecated: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
         let mut table_refs = vec![base_table.clone()];
         let mut join_map: BTreeMap<String, FlowJoin> = BTreeMap::new();
         if let Some(items) = joins {
@@ -583,7 +797,38 @@ impl PySemanticFlow {
                 join_map.insert(join.inner.alias.clone(), join.inner.clone());
             }
         }
-        Self {
+        let params = match params {
+            Some(obj) => serde_json::from_str(&dumps(py, obj)?).map_err(py_err)?,
+            None => BTreeMap::new(),
+        };
+        let limits = match limits {
+            Some(obj) => serde_json::from_str(&dumps(py, obj)?).map_err(py_err)?,
+            None => Default::default(),
+        };
+        let aggregates = match aggregates {
+            Some(obj) => serde_json::from_str(&dumps(py, obj)?).map_err(py_err)?,
+            None => BTreeMap::new(),
+        };
+        let fiscal_calendar = match fiscal_calendar {
+            Some(obj) => Some(serde_json::from_str(&dumps(py, obj)?).map_err(py_err)?),
+            None => None,
+        };
+        let fanout_strategy = match fanout_strategy {
+            Some(name) => {
+                serde_json::from_value(serde_json::Value::String(name)).map_err(py_err)?
+            }
+            None => Default::default(),
+        };
+        let default_filters = match default_filters {
+            Some(obj) => serde_json::from_str(&dumps(py, obj)?).map_err(py_err)?,
+            None => Vec::new(),
+        };
+        let segments = match segments {
+            Some(obj) => serde_json::from_str(&dumps(py, obj)?).map_err(py_err)?,
+            None => BTreeMap::new(),
+        };
+        let deprecated = deprecation_from_py(py, deprecated)?;
+        Ok(Self {
             inner: CoreSemanticFlow {
                 name,
                 base_table: FlowTableRef {
@@ -592,9 +837,21 @@ impl PySemanticFlow {
                 },
                 joins: join_map,
                 description,
+                params,
+                tags: tags.unwrap_or_default(),
+                synonyms: synonyms.unwrap_or_default(),
+                limits,
+                aggregates,
+                fiscal_calendar,
+                timezone,
+                fanout_strategy,
+                default_filters,
+                segments,
+                extends: None,
+                deprecated,
             },
             tables: table_refs,
-        }
+        })
     }
 
     fn referenced_tables(&self) -> Vec<PySemanticTable> {
@@ -607,6 +864,19 @@ impl PySemanticFlow {
     }
 }
 
+/// `deprecated` as `True` or a dict of `since`, `replacement` and `message`.
+fn deprecation_from_py(
+    py: Python<'_>,
+    deprecated: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Option<Deprecation>> {
+    match deprecated {
+        Some(obj) => Ok(serde_json::from_str::<DeprecatedFile>(&dumps(py, obj)?)
+            .map_err(py_err)?
+            .into_deprecation()),
+        None => Ok(None),
+    }
+}
+
 fn parse_tables(py: Python<'_>, obj: &Bound<'_, PyAny>) -> PyResult<Vec<SemanticTable>> {
     if let Ok(v) = obj.extract::<Vec<PySemanticTable>>() {
         return Ok(v.into_iter().map(|t| t.inner).collect());
@@ -629,10 +899,138 @@ fn parse_request(py: Python<'_>, obj: &Bound<'_, PyAny>) -> PyResult<QueryReques
     serde_json::from_str(&s).map_err(py_err)
 }
 
+/// Build a query context from a `user_attributes` dict; a scalar attribute
+/// value counts as a one-element list.
+fn parse_context(
+    py: Python<'_>,
+    user_attributes: Option<&Bound<'_, PyAny>>,
+) -> PyResult<QueryContext> {
+    let Some(obj) = user_attributes else {
+        return Ok(QueryContext::default());
+    };
+    let s = dumps(py, obj)?;
+    let raw: BTreeMap<String, serde_json::Value> = serde_json::from_str(&s).map_err(py_err)?;
+    let user_attributes = raw
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::Array(values) => (name, values),
+            other => (name, vec![other]),
+        })
+        .collect();
+    Ok(QueryContext {
+        user_attributes,
+        ..QueryContext::default()
+    })
+}
+
+/// Add the optional catalog metadata of a schema field, omitting defaults.
+fn set_field_metadata(
+    dict: &Bound<'_, PyDict>,
+    tags: Vec<String>,
+    synonyms: Vec<String>,
+    folder: Option<String>,
+    deprecated: Option<Deprecation>,
+) -> PyResult<()> {
+    if !tags.is_empty() {
+        dict.set_item("tags", tags)?;
+    }
+    if !synonyms.is_empty() {
+        dict.set_item("synonyms", synonyms)?;
+    }
+    if let Some(folder) = folder {
+        dict.set_item("folder", folder)?;
+    }
+    if let Some(deprecation) = deprecated {
+        set_deprecation(dict, &deprecation)?;
+    }
+    Ok(())
+}
+
+/// Set `deprecated` to `True`, or to a dict of the deprecation's details
+/// when it has any.
+fn set_deprecation(dict: &Bound<'_, PyDict>, deprecation: &Deprecation) -> PyResult<()> {
+    if *deprecation == Deprecation::default() {
+        return dict.set_item("deprecated", true);
+    }
+    let details = PyDict::new(dict.py());
+    if let Some(since) = &deprecation.since {
+        details.set_item("since", since)?;
+    }
+    if let Some(replacement) = &deprecation.replacement {
+        details.set_item("replacement", replacement)?;
+    }
+    if let Some(message) = &deprecation.message {
+        details.set_item("message", message)?;
+    }
+    dict.set_item("deprecated", details)
+}
+
+/// Issue a Python `DeprecationWarning` for each deprecated flow or field
+/// `request` uses.
+fn warn_deprecations(
+    py: Python<'_>,
+    registry: &FlowRegistry,
+    request: &QueryRequest,
+) -> PyResult<()> {
+    let category = py.get_type::<PyDeprecationWarning>();
+    for warning in registry.deprecations(request) {
+        let message = CString::new(warning.to_string()).map_err(py_err)?;
+        PyErr::warn(py, category.as_any(), &message, 1)?;
+    }
+    Ok(())
+}
+
+/// Add the display metadata of a dimension or measure, omitting defaults.
+fn set_display_metadata(
+    dict: &Bound<'_, PyDict>,
+    label: Option<String>,
+    format: Option<String>,
+    hidden: bool,
+) -> PyResult<()> {
+    if let Some(label) = label {
+        dict.set_item("label", label)?;
+    }
+    if let Some(format) = format {
+        dict.set_item("format", format)?;
+    }
+    if hidden {
+        dict.set_item("hidden", true)?;
+    }
+    Ok(())
+}
+
 fn build_registry(tables: Vec<SemanticTable>, flows: Vec<CoreSemanticFlow>) -> FlowRegistry {
     FlowRegistry::from_parts(tables, flows)
 }
 
+/// Validate a new handle's registry. With `validation.warn_only` set, problems
+/// are logged instead of failing construction; `validate()` lists them.
+fn validate_on_construction(
+    py: Python<'_>,
+    registry: &mut FlowRegistry,
+    connections: &ConnectionManager,
+    config: Option<&SemaflowConfig>,
+) -> PyResult<()> {
+    let warn_only = config.is_some_and(|c| c.defaults.validation.warn_only);
+    let validator = Validator::new(connections.clone(), warn_only);
+    if !warn_only {
+        return py
+            .allow_threads(|| runtime().block_on(validator.validate_registry(registry)))
+            .map_err(to_py_err);
+    }
+    let issues = py.allow_threads(|| runtime().block_on(validator.collect_issues(&*registry)));
+    for issue in issues {
+        tracing::warn!(
+            table = ?issue.table,
+            flow = ?issue.flow,
+            field = ?issue.field,
+            "{}",
+            issue.message
+        );
+    }
+    Ok(())
+}
+
 fn build_data_sources(
     mapping: &Bound<'_, PyAny>,
     config: Option<&SemaflowConfig>,
@@ -640,145 +1038,154 @@ fn build_data_sources(
     // Auto-load config from default locations if not provided
     let auto_config = config.cloned().unwrap_or_else(SemaflowConfig::load_default);
 
-    // Accept either: list[DataSource] or dict[name -> duckdb_path]
+    // Accept either: list[DataSource] or dict[name -> uri]
     if let Ok(list) = mapping.extract::<Vec<PyDataSource>>() {
-        let mut ds = ConnectionManager::with_config(auto_config.clone());
-        for item in list {
-            // Get resolved config for this datasource
-            let resolved = ds.config_for(&item.name);
-
-            match item.backend_type.as_str() {
-                #[cfg(feature = "duckdb")]
-                "duckdb" => {
-                    // Check if connection was pre-created (via register_dataframe)
-                    if let Some(existing_conn) = item.duckdb_conn.clone() {
-                        tracing::debug!(
-                            name = item.name.as_str(),
-                            "reusing pre-created DuckDB connection"
-                        );
-                        ds.insert(item.name.clone(), existing_conn);
-                    } else {
-                        // Create new connection
-                        let duck_config = crate::config::DuckDbConfig {
-                            max_concurrency: item
-                                .max_concurrency
-                                .unwrap_or(resolved.duckdb.max_concurrency),
-                        };
-                        let conn = DuckDbConnection::with_config(item.uri.clone(), duck_config);
-                        // Initialize pool so checkout_connection works
-                        // (especially important for :memory: databases)
-                        runtime().block_on(conn.initialize_pool()).map_err(py_err)?;
-                        ds.insert(item.name.clone(), Arc::new(conn));
-                    }
-                }
-                #[cfg(not(feature = "duckdb"))]
-                "duckdb" => {
-                    return Err(PyValueError::new_err(
-                        "DuckDB support not enabled. Rebuild with --features duckdb",
-                    ));
-                }
-                #[cfg(feature = "postgres")]
-                "postgres" => {
-                    use crate::backends::PostgresConnection;
-                    let schema = item.schema.as_deref().ok_or_else(|| {
-                        PyValueError::new_err("postgres data source requires schema parameter")
-                    })?;
-                    // Use pool_size from: PyDataSource param > config > default
-                    let pg_config = crate::config::PostgresConfig {
-                        pool_size: item.max_concurrency.unwrap_or(resolved.postgres.pool_size),
-                        statement_timeout_ms: resolved.postgres.statement_timeout_ms,
-                    };
-                    let conn = PostgresConnection::with_config(&item.uri, schema, pg_config)
-                        .map_err(py_err)?;
-                    ds.insert(item.name.clone(), Arc::new(conn));
-                }
-                #[cfg(not(feature = "postgres"))]
-                "postgres" => {
-                    return Err(PyValueError::new_err(
-                        "PostgreSQL support not enabled. Rebuild with --features postgres",
-                    ));
-                }
-                #[cfg(feature = "bigquery")]
-                "bigquery" => {
-                    use crate::backends::BigQueryConnection;
-                    // Parse URI format: project_id|dataset|optional_service_account_path
-                    let parts: Vec<&str> = item.uri.split('|').collect();
-                    if parts.len() < 2 {
-                        return Err(PyValueError::new_err(
-                            "BigQuery URI must contain project_id|dataset",
-                        ));
-                    }
-                    let project_id = parts[0];
-                    let dataset = parts[1];
+        return connect_data_sources(list, auto_config);
+    }
+    if let Ok(dict) = mapping.extract::<std::collections::HashMap<String, String>>() {
+        let list = dict
+            .into_iter()
+            .map(|(name, uri)| PyDataSource::from_uri(name, uri, None))
+            .collect::<PyResult<Vec<_>>>()?;
+        return connect_data_sources(list, auto_config);
+    }
+
+    Err(PyValueError::new_err(
+        "data_sources must be list[DataSource] or dict[name -> uri]",
+    ))
+}
 
-                    // Use config from resolved datasource config
-                    let bq_config = resolved.bigquery.clone();
+fn connect_data_sources(
+    list: Vec<PyDataSource>,
+    mut config: SemaflowConfig,
+) -> PyResult<ConnectionManager> {
+    // A DuckDB source's max_concurrency is its datasource budget
+    for item in list.iter().filter(|item| item.backend_type == "duckdb") {
+        if let Some(max) = item.max_concurrency {
+            let ds_config = config.datasources.entry(item.name.clone()).or_default();
+            ds_config
+                .duckdb
+                .get_or_insert_with(DuckDbConfig::default)
+                .max_concurrency = Some(max);
+        }
+    }
+    let mut ds = ConnectionManager::with_config(config);
+    for item in list {
+        // Get resolved config for this datasource
+        let resolved = ds.config_for(&item.name);
 
-                    let conn = if parts.len() >= 3 && !parts[2].is_empty() {
-                        // Service account key file provided
-                        runtime()
-                            .block_on(
-                                BigQueryConnection::from_service_account_key_file_with_config(
-                                    parts[2], project_id, dataset, bq_config,
-                                ),
-                            )
-                            .map_err(py_err)?
-                    } else {
-                        // Use application default credentials
-                        runtime()
-                            .block_on(BigQueryConnection::from_application_default_credentials_with_config(
-                                project_id, dataset, bq_config,
-                            ))
-                            .map_err(py_err)?
-                    };
+        match item.backend_type.as_str() {
+            #[cfg(feature = "duckdb")]
+            "duckdb" => {
+                // Check if connection was pre-created (via register_dataframe)
+                if let Some(existing_conn) = item.duckdb_conn.clone() {
+                    tracing::debug!(
+                        name = item.name.as_str(),
+                        "reusing pre-created DuckDB connection"
+                    );
+                    ds.insert(item.name.clone(), existing_conn);
+                } else {
+                    // Create new connection
+                    let conn =
+                        DuckDbConnection::with_config(item.uri.clone(), resolved.duckdb.clone());
+                    // Initialize pool so checkout_connection works
+                    // (especially important for :memory: databases)
+                    runtime().block_on(conn.initialize_pool()).map_err(py_err)?;
                     ds.insert(item.name.clone(), Arc::new(conn));
                 }
-                #[cfg(not(feature = "bigquery"))]
-                "bigquery" => {
+            }
+            #[cfg(not(feature = "duckdb"))]
+            "duckdb" => {
+                return Err(PyValueError::new_err(
+                    "DuckDB support not enabled. Rebuild with --features duckdb",
+                ));
+            }
+            #[cfg(feature = "postgres")]
+            "postgres" => {
+                use crate::backends::PostgresConnection;
+                let schema = item.schema.as_deref().ok_or_else(|| {
+                    PyValueError::new_err("postgres data source requires schema parameter")
+                })?;
+                // Use pool_size from: PyDataSource param > config > default
+                let pg_config = crate::config::PostgresConfig {
+                    pool_size: item.max_concurrency.unwrap_or(resolved.postgres.pool_size),
+                    statement_timeout_ms: resolved.postgres.statement_timeout_ms,
+                };
+                let conn = PostgresConnection::with_config(&item.uri, schema, pg_config)
+                    .map_err(py_err)?;
+                ds.insert(item.name.clone(), Arc::new(conn));
+            }
+            #[cfg(not(feature = "postgres"))]
+            "postgres" => {
+                return Err(PyValueError::new_err(
+                    "PostgreSQL support not enabled. Rebuild with --features postgres",
+                ));
+            }
+            #[cfg(feature = "bigquery")]
+            "bigquery" => {
+                use crate::backends::BigQueryConnection;
+                // Parse URI format: project_id|dataset|optional_service_account_path
+                let parts: Vec<&str> = item.uri.split('|').collect();
+                if parts.len() < 2 {
                     return Err(PyValueError::new_err(
-                        "BigQuery support not enabled. Rebuild with --features bigquery",
+                        "BigQuery URI must contain project_id|dataset",
                     ));
                 }
-                other => {
-                    return Err(PyValueError::new_err(format!(
-                        "unknown backend_type: {other}. Supported: duckdb, postgres, bigquery"
-                    )));
-                }
+                let project_id = parts[0].to_string();
+                let dataset = parts[1].to_string();
+                let key_file = parts
+                    .get(2)
+                    .filter(|p| !p.is_empty())
+                    .map(|p| p.to_string());
+
+                // Use config from resolved datasource config
+                let bq_config = resolved.bigquery.clone();
+
+                // Credential discovery and client setup are slow, so connect on first use
+                let dialect = crate::dialect::BigQueryDialect::new(&project_id, &dataset);
+                ds.insert_lazy(item.name.clone(), dialect, move || {
+                    let (project_id, dataset) = (project_id.clone(), dataset.clone());
+                    let (key_file, bq_config) = (key_file.clone(), bq_config.clone());
+                    async move {
+                        let conn = match key_file {
+                            // Service account key file provided
+                            Some(path) => {
+                                BigQueryConnection::from_service_account_key_file_with_config(
+                                    &path,
+                                    &project_id,
+                                    &dataset,
+                                    bq_config,
+                                )
+                                .await?
+                            }
+                            // Use application default credentials
+                            None => {
+                                BigQueryConnection::from_application_default_credentials_with_config(
+                                    &project_id,
+                                    &dataset,
+                                    bq_config,
+                                )
+                                .await?
+                            }
+                        };
+                        Ok(Arc::new(conn) as Arc<dyn crate::backends::BackendConnection>)
+                    }
+                });
+            }
+            #[cfg(not(feature = "bigquery"))]
+            "bigquery" => {
+                return Err(PyValueError::new_err(
+                    "BigQuery support not enabled. Rebuild with --features bigquery",
+                ));
+            }
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown backend_type: {other}. Supported: duckdb, postgres, bigquery"
+                )));
             }
         }
-        return Ok(ds);
-    }
-
-    // Legacy dict format: name -> duckdb_path (only when duckdb feature enabled)
-    #[cfg(feature = "duckdb")]
-    if let Ok(dict) = mapping.extract::<std::collections::HashMap<String, String>>() {
-        let mut ds = ConnectionManager::with_config(auto_config);
-        for (name, path) in dict {
-            let resolved = ds.config_for(&name);
-            let duck_config = crate::config::DuckDbConfig {
-                max_concurrency: resolved.duckdb.max_concurrency,
-            };
-            ds.insert(
-                name,
-                Arc::new(DuckDbConnection::with_config(path, duck_config)),
-            );
-        }
-        return Ok(ds);
     }
-
-    #[cfg(not(feature = "duckdb"))]
-    if mapping
-        .extract::<std::collections::HashMap<String, String>>()
-        .is_ok()
-    {
-        return Err(PyValueError::new_err(
-            "dict[name -> path] format requires DuckDB. Rebuild with --features duckdb or use list[DataSource]",
-        ));
-    }
-
-    Err(PyValueError::new_err(
-        "data_sources must be list[DataSource] or dict[name -> duckdb_path]",
-    ))
+    Ok(ds)
 }
 
 fn serde_json_to_py(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
@@ -814,8 +1221,10 @@ fn build_sql(
     let ds = build_data_sources(data_sources, None)?;
     let builder = SqlBuilder::default();
     let sql = py
-        .allow_threads(|| builder.build_for_request(&registry, &ds, &request))
-        .map_err(to_validation_err)?;
+        .allow_threads(|| {
+            builder.build_for_request(&registry, &ds, &request, &QueryContext::default())
+        })
+        .map_err(to_py_err)?;
     tracing::debug!(
         ms = start.elapsed().as_millis(),
         "build_sql (pyfunction) complete"
@@ -847,11 +1256,11 @@ fn run(
                     .validate_registry(&mut registry)
                     .await
                     .map_err(SemaflowError::from)?;
-                let result = run_query(&registry, &ds, &request).await?;
+                let result = run_query(&registry, &ds, &request, &QueryContext::default()).await?;
                 serde_json::to_string(&result.rows).map_err(SemaflowError::from)
             })
         })
-        .map_err(to_validation_err)?;
+        .map_err(to_py_err)?;
 
     let json = py.import("json")?;
     let py_obj = json.call_method1("loads", (rows_json,))?;
@@ -879,6 +1288,7 @@ fn semaflow(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
 
     m.add_class::<SemanticFlowHandle>()?;
     m.add_class::<PyConfig>()?;
+    m.add("StaleCursorError", m.py().get_type::<StaleCursorError>())?;
     Ok(())
 }
 
@@ -966,11 +1376,145 @@ impl PyConfig {
         self.inner.defaults.schema_cache.max_size = size;
     }
 
+    /// Persist cached schemas to a JSON file across restarts.
+    fn set_schema_cache_path(&mut self, path: PathBuf) {
+        self.inner.defaults.schema_cache.path = Some(path);
+    }
+
     /// Set validation to warn-only mode.
     fn set_validation_warn_only(&mut self, warn_only: bool) {
         self.inner.defaults.validation.warn_only = warn_only;
     }
 
+    /// Enable workload tagging for cost attribution.
+    ///
+    /// Args:
+    ///     enabled: Whether to tag queries (BigQuery labels, Postgres application_name)
+    ///     tags: Static tags added to every query, e.g. {"team": "analytics"}
+    ///     comment: Also prepend the tags to each statement as a SQL comment
+    #[pyo3(signature = (enabled=true, tags=None, comment=false))]
+    fn set_tagging(
+        &mut self,
+        enabled: bool,
+        tags: Option<BTreeMap<String, String>>,
+        comment: bool,
+    ) {
+        self.inner.defaults.tagging.enabled = enabled;
+        self.inner.defaults.tagging.comment = comment;
+        if let Some(tags) = tags {
+            self.inner.defaults.tagging.tags = tags;
+        }
+    }
+
+    /// Limit the queries in flight across all datasources.
+    ///
+    /// Args:
+    ///     max_concurrent: Maximum concurrent queries (0 = unlimited)
+    ///     queue_timeout_ms: Maximum wait for a slot before rejecting (0 = wait indefinitely)
+    #[pyo3(signature = (max_concurrent, queue_timeout_ms=None))]
+    fn set_concurrency(&mut self, max_concurrent: usize, queue_timeout_ms: Option<u64>) {
+        set_limit(
+            &mut self.inner.concurrency.global,
+            max_concurrent,
+            queue_timeout_ms,
+        );
+    }
+
+    /// Limit the queries of one flow in flight.
+    ///
+    /// Args:
+    ///     flow_name: Name of the flow
+    ///     max_concurrent: Maximum concurrent queries (0 = unlimited)
+    ///     queue_timeout_ms: Maximum wait for a slot before rejecting (0 = wait indefinitely)
+    #[pyo3(signature = (flow_name, max_concurrent, queue_timeout_ms=None))]
+    fn set_flow_concurrency(
+        &mut self,
+        flow_name: &str,
+        max_concurrent: usize,
+        queue_timeout_ms: Option<u64>,
+    ) {
+        let limit = self
+            .inner
+            .concurrency
+            .flows
+            .entry(flow_name.to_string())
+            .or_default();
+        set_limit(limit, max_concurrent, queue_timeout_ms);
+    }
+
+    /// Limit the queries against one datasource in flight.
+    ///
+    /// Args:
+    ///     datasource_name: Name of the datasource
+    ///     max_concurrent: Maximum concurrent queries (0 = unlimited)
+    ///     queue_timeout_ms: Maximum wait for a slot before rejecting (0 = wait indefinitely)
+    #[pyo3(signature = (datasource_name, max_concurrent, queue_timeout_ms=None))]
+    fn set_datasource_concurrency(
+        &mut self,
+        datasource_name: &str,
+        max_concurrent: usize,
+        queue_timeout_ms: Option<u64>,
+    ) {
+        let defaults = self.inner.defaults.concurrency.clone();
+        let ds_config = self
+            .inner
+            .datasources
+            .entry(datasource_name.to_string())
+            .or_insert_with(DatasourceConfig::default);
+        let limit = ds_config.concurrency.get_or_insert(defaults);
+        set_limit(limit, max_concurrent, queue_timeout_ms);
+    }
+
+    /// Switch planner features off, for every datasource or just one.
+    ///
+    /// Args:
+    ///     datasource_name: Datasource to configure (default: all datasources)
+    ///     filtered_aggregates: Render measure filters as FILTER (WHERE ...)
+    ///     group_by_all: End grouped queries in GROUP BY ALL
+    ///     symmetric_aggregates: Honour flows' `fanout_strategy: symmetric`
+    ///     distinct_sketches: Pre-aggregate COUNT DISTINCT measures as sketches
+    #[pyo3(signature = (
+        datasource_name=None,
+        *,
+        filtered_aggregates=None,
+        group_by_all=None,
+        symmetric_aggregates=None,
+        distinct_sketches=None
+    ))]
+    fn set_planner(
+        &mut self,
+        datasource_name: Option<&str>,
+        filtered_aggregates: Option<bool>,
+        group_by_all: Option<bool>,
+        symmetric_aggregates: Option<bool>,
+        distinct_sketches: Option<bool>,
+    ) {
+        let planner = match datasource_name {
+            Some(name) => {
+                let defaults = self.inner.defaults.planner.clone();
+                self.inner
+                    .datasources
+                    .entry(name.to_string())
+                    .or_insert_with(DatasourceConfig::default)
+                    .planner
+                    .get_or_insert(defaults)
+            }
+            None => &mut self.inner.defaults.planner,
+        };
+        if let Some(enabled) = filtered_aggregates {
+            planner.filtered_aggregates = enabled;
+        }
+        if let Some(enabled) = group_by_all {
+            planner.group_by_all = enabled;
+        }
+        if let Some(enabled) = symmetric_aggregates {
+            planner.symmetric_aggregates = enabled;
+        }
+        if let Some(enabled) = distinct_sketches {
+            planner.distinct_sketches = enabled;
+        }
+    }
+
     /// Configure BigQuery settings for a specific datasource.
     ///
     /// Args:
@@ -1010,9 +1554,28 @@ impl PyConfig {
     ///
     /// Args:
     ///     datasource_name: Name of the datasource
-    ///     max_concurrency: Maximum concurrent queries
-    #[pyo3(signature = (datasource_name, max_concurrency=None))]
-    fn set_duckdb_config(&mut self, datasource_name: &str, max_concurrency: Option<usize>) {
+    ///     max_concurrency: Maximum concurrent queries (the datasource's concurrency budget)
+    ///     pool_size: Idle connections kept open for reuse
+    ///     read_only: Open the database file read-only
+    ///     database_threads: DuckDB worker threads shared by all connections and queries
+    ///     database_memory_limit: DuckDB memory limit for the whole database, e.g. "4GB"
+    #[pyo3(signature = (
+        datasource_name,
+        max_concurrency=None,
+        pool_size=None,
+        read_only=None,
+        database_threads=None,
+        database_memory_limit=None
+    ))]
+    fn set_duckdb_config(
+        &mut self,
+        datasource_name: &str,
+        max_concurrency: Option<usize>,
+        pool_size: Option<usize>,
+        read_only: Option<bool>,
+        database_threads: Option<usize>,
+        database_memory_limit: Option<String>,
+    ) {
         let ds_config = self
             .inner
             .datasources
@@ -1020,8 +1583,20 @@ impl PyConfig {
             .or_insert_with(DatasourceConfig::default);
 
         let duck = ds_config.duckdb.get_or_insert_with(DuckDbConfig::default);
-        if let Some(max) = max_concurrency {
-            duck.max_concurrency = max;
+        if max_concurrency.is_some() {
+            duck.max_concurrency = max_concurrency;
+        }
+        if let Some(size) = pool_size {
+            duck.pool_size = size;
+        }
+        if let Some(read_only) = read_only {
+            duck.read_only = read_only;
+        }
+        if database_threads.is_some() {
+            duck.database_threads = database_threads;
+        }
+        if database_memory_limit.is_some() {
+            duck.database_memory_limit = database_memory_limit;
         }
     }
 
@@ -1056,6 +1631,13 @@ impl PyConfig {
     }
 }
 
+fn set_limit(limit: &mut ConcurrencyLimit, max_concurrent: usize, queue_timeout_ms: Option<u64>) {
+    limit.max_concurrent = max_concurrent;
+    if let Some(timeout) = queue_timeout_ms {
+        limit.queue_timeout_ms = timeout;
+    }
+}
+
 #[pyclass(name = "SemanticFlowHandle", module = "semaflow.semaflow")]
 #[derive(Clone)]
 pub struct SemanticFlowHandle {
@@ -1079,11 +1661,7 @@ impl SemanticFlowHandle {
         let mut registry = FlowRegistry::from_parts(tables, flows_vec);
         let cfg = config.as_ref().map(|c| &c.inner);
         let connections = build_data_sources(data_sources, cfg)?;
-        let validator = Validator::new(connections.clone(), false);
-        py.allow_threads(|| {
-            runtime().block_on(async { validator.validate_registry(&mut registry).await })
-        })
-        .map_err(to_validation_err)?;
+        validate_on_construction(py, &mut registry, &connections, cfg)?;
         Ok(Self {
             registry: Arc::new(registry),
             connections,
@@ -1098,14 +1676,10 @@ impl SemanticFlowHandle {
         data_sources: &Bound<'_, PyAny>,
         config: Option<PyConfig>,
     ) -> PyResult<Self> {
-        let mut registry = FlowRegistry::load_from_dir(flow_dir).map_err(to_validation_err)?;
+        let mut registry = FlowRegistry::load_from_dir(flow_dir).map_err(to_py_err)?;
         let cfg = config.as_ref().map(|c| &c.inner);
         let connections = build_data_sources(data_sources, cfg)?;
-        let validator = Validator::new(connections.clone(), false);
-        py.allow_threads(|| {
-            runtime().block_on(async { validator.validate_registry(&mut registry).await })
-        })
-        .map_err(to_validation_err)?;
+        validate_on_construction(py, &mut registry, &connections, cfg)?;
         Ok(Self {
             registry: Arc::new(registry),
             connections,
@@ -1126,11 +1700,7 @@ impl SemanticFlowHandle {
         let mut registry = build_registry(tables, flows_vec);
         let cfg = config.as_ref().map(|c| &c.inner);
         let connections = build_data_sources(data_sources, cfg)?;
-        let validator = Validator::new(connections.clone(), false);
-        py.allow_threads(|| {
-            runtime().block_on(async { validator.validate_registry(&mut registry).await })
-        })
-        .map_err(to_validation_err)?;
+        validate_on_construction(py, &mut registry, &connections, cfg)?;
         Ok(Self {
             registry: Arc::new(registry),
             connections,
@@ -1138,32 +1708,206 @@ impl SemanticFlowHandle {
     }
 
     /// Build SQL for a request dict.
-    #[pyo3(text_signature = "(self, request)")]
-    fn build_sql(&self, py: Python<'_>, request: &Bound<'_, PyAny>) -> PyResult<String> {
+    ///
+    /// `user_attributes` maps attribute names to a value or list of values for
+    /// the tables' access policies; without them, policy tables return no rows.
+    #[pyo3(
+        signature = (request, user_attributes = None),
+        text_signature = "(self, request, user_attributes=None)"
+    )]
+    fn build_sql(
+        &self,
+        py: Python<'_>,
+        request: &Bound<'_, PyAny>,
+        user_attributes: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<String> {
         let start = Instant::now();
         let request = parse_request(py, request)?;
+        let context = parse_context(py, user_attributes)?;
         let builder = SqlBuilder::default();
         let registry = self.registry.clone();
         let sql = py
-            .allow_threads(|| builder.build_for_request(&registry, &self.connections, &request))
-            .map_err(to_validation_err)?;
+            .allow_threads(|| {
+                builder.build_for_request(&registry, &self.connections, &request, &context)
+            })
+            .map_err(to_py_err)?;
         tracing::debug!(ms = start.elapsed().as_millis(), "build_sql complete");
         Ok(sql)
     }
 
+    /// Build SQL for a request dict and return it with planner diagnostics.
+    ///
+    /// Returns a dict with `sql`, `strategy` (`"flat"`, `"pre_aggregated"` or `"aggregate"`),
+    /// `diagnostics` (joins that may fan out measure rows), `plan` (the
+    /// logical plan), `plan_text` (the plan as indented text) and
+    /// `deprecations` (deprecated flow and fields the request uses).
+    #[pyo3(
+        signature = (request, user_attributes = None),
+        text_signature = "(self, request, user_attributes=None)"
+    )]
+    fn explain(
+        &self,
+        py: Python<'_>,
+        request: &Bound<'_, PyAny>,
+        user_attributes: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let request = parse_request(py, request)?;
+        let context = parse_context(py, user_attributes)?;
+        let builder = SqlBuilder::default();
+        let registry = self.registry.clone();
+        let explain = py
+            .allow_threads(|| {
+                builder.explain_for_request(&registry, &self.connections, &request, &context)
+            })
+            .map_err(to_py_err)?;
+        let mut value = serde_json::to_value(&explain)
+            .map_err(|e| PyValueError::new_err(format!("failed to serialize explain: {e}")))?;
+        value["plan_text"] = serde_json::Value::String(explain.plan.to_string());
+        serde_json_to_py(py, &value)
+    }
+
+    /// Check a request dict against the semantic model without running it.
+    ///
+    /// Returns every problem found as a list of dicts with `code`, `message`
+    /// and `details`; the list is empty when the request is valid.
+    #[pyo3(text_signature = "(self, request)")]
+    fn validate_request(&self, py: Python<'_>, request: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let request = parse_request(py, request)?;
+        let registry = self.registry.clone();
+        let builder = SqlBuilder::for_request(&registry, &self.connections, &request);
+        let errors = match py.allow_threads(|| builder.validate_request(&registry, &request)) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors,
+        };
+        let value = serde_json::Value::Array(
+            errors
+                .iter()
+                .map(|e| {
+                    serde_json::json!({
+                        "code": e.code(),
+                        "message": e.to_string(),
+                        "details": e.details(),
+                    })
+                })
+                .collect(),
+        );
+        serde_json_to_py(py, &value)
+    }
+
+    /// Deprecated flow and fields a request dict uses, without running it.
+    ///
+    /// Returns a list of dicts with `kind` (`"flow"`, `"dimension"` or
+    /// `"measure"`), `flow`, `name` (qualified for fields) and, when set,
+    /// `since`, `replacement` and `message`. Executing the request issues
+    /// the same as `DeprecationWarning`s.
+    #[pyo3(text_signature = "(self, request)")]
+    fn deprecations(&self, py: Python<'_>, request: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let request = parse_request(py, request)?;
+        let value = serde_json::to_value(self.registry.deprecations(&request))
+            .map_err(|e| PyValueError::new_err(format!("failed to serialize deprecations: {e}")))?;
+        serde_json_to_py(py, &value)
+    }
+
+    /// Check the semantic model against the data sources' table schemas.
+    ///
+    /// Returns every problem found rather than stopping at the first, as a
+    /// list of dicts with `severity` ("error" or "warning"), `table`, `flow`,
+    /// `field` and `message`; the list is empty when the model is valid.
+    #[pyo3(text_signature = "(self)")]
+    fn validate(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let validator = Validator::new(self.connections.clone(), false);
+        let registry = self.registry.clone();
+        let issues = py.allow_threads(|| runtime().block_on(validator.collect_issues(&registry)));
+        let value = serde_json::to_value(&issues)
+            .map_err(|e| PyValueError::new_err(format!("failed to serialize issues: {e}")))?;
+        serde_json_to_py(py, &value)
+    }
+
+    /// Compare the data sources' live table schemas with the model and with
+    /// the schemas last seen.
+    ///
+    /// Returns a list of dicts with `kind` ("table_unavailable",
+    /// "column_missing", "type_changed" or "new_column"), `table`,
+    /// `data_source`, `physical_table`, `column`, `previous_type`,
+    /// `current_type`, `used_by` and `message`, breaking changes first.
+    #[pyo3(text_signature = "(self)")]
+    fn check_drift(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let validator = Validator::new(self.connections.clone(), false);
+        let registry = self.registry.clone();
+        let report = py
+            .allow_threads(|| runtime().block_on(validator.check_drift(&registry)))
+            .map_err(py_err)?;
+        let value = serde_json::to_value(&report.drifts)
+            .map_err(|e| PyValueError::new_err(format!("failed to serialize drift: {e}")))?;
+        serde_json_to_py(py, &value)
+    }
+
+    /// Counters of each enforced concurrency budget.
+    ///
+    /// Returns a list of dicts with `scope` ("global", "data_source" or
+    /// "flow"), `name`, `max_concurrent`, `in_flight`, `queued`, `admitted`,
+    /// `rejected`, `total_wait_ms` and `max_wait_ms`.
+    #[pyo3(text_signature = "(self)")]
+    fn concurrency_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let value = serde_json::to_value(self.connections.concurrency_stats())
+            .map_err(|e| PyValueError::new_err(format!("failed to serialize stats: {e}")))?;
+        serde_json_to_py(py, &value)
+    }
+
+    /// Check that every data source accepts queries.
+    ///
+    /// Returns a dict mapping each data source name to None when healthy, or
+    /// to the error message. Sources behind an open circuit breaker are
+    /// checked directly, and a passing check closes the breaker.
+    #[pyo3(text_signature = "(self)")]
+    fn health_check(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let connections = self.connections.clone();
+        let checks = py.allow_threads(|| runtime().block_on(connections.health_check_all()));
+        let dict = PyDict::new(py);
+        for (name, result) in checks {
+            dict.set_item(name, result.err().map(|e| e.to_string()))?;
+        }
+        Ok(dict.unbind().into())
+    }
+
+    /// Count the rows and key column distinct values of every physical
+    /// table, for the planner to use until the schema cache TTL expires.
+    ///
+    /// Returns the number of tables counted.
+    #[pyo3(text_signature = "(self)")]
+    fn collect_stats(&self, py: Python<'_>) -> PyResult<usize> {
+        let connections = self.connections.clone();
+        let registry = self.registry.clone();
+        py.allow_threads(|| runtime().block_on(connections.collect_stats(&registry)))
+            .map_err(py_err)
+    }
+
     /// Execute a request dict and return results.
     ///
     /// If `page_size` is set in the request, returns a dict with pagination metadata:
     /// - `rows`: list of row dicts for this page
     /// - `cursor`: opaque cursor string for next page (None if last page)
     /// - `has_more`: whether more rows exist after this page
-    /// - `total_rows`: total result count (BigQuery only, None for other backends)
+    /// - `total_rows`: total result count (BigQuery, or other backends when the
+    ///   request sets `count_total`; None otherwise)
     ///
     /// If `page_size` is not set, returns list[dict] rows directly (backwards compatible).
-    #[pyo3(text_signature = "(self, request)")]
-    fn execute(&self, py: Python<'_>, request: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+    ///
+    /// `user_attributes` supplies the caller's values for access policies.
+    #[pyo3(
+        signature = (request, user_attributes = None),
+        text_signature = "(self, request, user_attributes=None)"
+    )]
+    fn execute(
+        &self,
+        py: Python<'_>,
+        request: &Bound<'_, PyAny>,
+        user_attributes: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
         let start = Instant::now();
         let request = parse_request(py, request)?;
+        let context = parse_context(py, user_attributes)?;
+        warn_deprecations(py, &self.registry, &request)?;
         let registry = self.registry.clone();
         let connections = self.connections.clone();
 
@@ -1173,11 +1917,13 @@ impl SemanticFlowHandle {
             let result_json: String = py
                 .allow_threads(|| {
                     runtime().block_on(async {
-                        let result = run_query_paginated(&registry, &connections, &request)
-                            .await
-                            .map_err(SemaflowError::from)?;
+                        let result =
+                            run_query_paginated(&registry, &connections, &request, &context)
+                                .await
+                                .map_err(SemaflowError::from)?;
                         // Serialize the full paginated result
                         let response = serde_json::json!({
+                            "columns": result.columns,
                             "rows": result.rows,
                             "cursor": result.cursor,
                             "has_more": result.has_more,
@@ -1186,7 +1932,7 @@ impl SemanticFlowHandle {
                         serde_json::to_string(&response).map_err(SemaflowError::from)
                     })
                 })
-                .map_err(to_validation_err)?;
+                .map_err(to_py_err)?;
             let json = py.import("json")?;
             let py_obj = json.call_method1("loads", (result_json,))?;
             tracing::debug!(
@@ -1200,13 +1946,13 @@ impl SemanticFlowHandle {
             let rows_json: String = py
                 .allow_threads(|| {
                     runtime().block_on(async {
-                        let result = run_query(&registry, &connections, &request)
+                        let result = run_query(&registry, &connections, &request, &context)
                             .await
                             .map_err(SemaflowError::from)?;
                         serde_json::to_string(&result.rows).map_err(SemaflowError::from)
                     })
                 })
-                .map_err(to_validation_err)?;
+                .map_err(to_py_err)?;
             let json = py.import("json")?;
             let py_obj = json.call_method1("loads", (rows_json,))?;
             tracing::debug!(
@@ -1218,6 +1964,157 @@ impl SemanticFlowHandle {
         }
     }
 
+    /// Execute a request dict and return a `pyarrow.RecordBatchReader`.
+    ///
+    /// Column types come from the backend rather than a JSON round trip, so
+    /// int64 and decimal values keep their precision. Pagination is ignored.
+    #[cfg(feature = "arrow")]
+    #[pyo3(
+        signature = (request, user_attributes = None),
+        text_signature = "(self, request, user_attributes=None)"
+    )]
+    fn execute_arrow(
+        &self,
+        py: Python<'_>,
+        request: &Bound<'_, PyAny>,
+        user_attributes: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<arrow::pyarrow::PyArrowType<Box<dyn RecordBatchReader + Send>>> {
+        let start = Instant::now();
+        let request = parse_request(py, request)?;
+        let context = parse_context(py, user_attributes)?;
+        warn_deprecations(py, &self.registry, &request)?;
+        let registry = self.registry.clone();
+        let connections = self.connections.clone();
+        let result = py
+            .allow_threads(|| {
+                runtime().block_on(run_query_arrow(&registry, &connections, &request, &context))
+            })
+            .map_err(to_py_err)?;
+        tracing::debug!(
+            ms = start.elapsed().as_millis(),
+            rows = result.num_rows(),
+            "execute_arrow complete"
+        );
+        let reader = arrow::array::RecordBatchIterator::new(
+            result.batches.into_iter().map(Ok),
+            result.schema,
+        );
+        Ok(arrow::pyarrow::PyArrowType(Box::new(reader)))
+    }
+
+    /// Execute a blend request dict and return list[dict] rows.
+    ///
+    /// A blend has `dimensions` shared by every flow and `flows` mapping each
+    /// flow name to `{measures, filters}`. The per-flow results are
+    /// full-outer-joined on the dimensions.
+    #[pyo3(
+        signature = (request, user_attributes = None),
+        text_signature = "(self, request, user_attributes=None)"
+    )]
+    fn execute_blend(
+        &self,
+        py: Python<'_>,
+        request: &Bound<'_, PyAny>,
+        user_attributes: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let start = Instant::now();
+        let s = dumps(py, request)?;
+        let blend: BlendRequest = serde_json::from_str(&s).map_err(py_err)?;
+        let context = parse_context(py, user_attributes)?;
+        let registry = self.registry.clone();
+        let connections = self.connections.clone();
+        let rows_json: String = py
+            .allow_threads(|| {
+                runtime().block_on(async {
+                    let result = run_blend_query(&registry, &connections, &blend, &context)
+                        .await
+                        .map_err(SemaflowError::from)?;
+                    serde_json::to_string(&result.rows).map_err(SemaflowError::from)
+                })
+            })
+            .map_err(to_py_err)?;
+        let json = py.import("json")?;
+        let py_obj = json.call_method1("loads", (rows_json,))?;
+        tracing::debug!(ms = start.elapsed().as_millis(), "execute_blend complete");
+        Ok(py_obj.unbind())
+    }
+
+    /// Execute a request dict, calling `on_batch(rows)` for each batch of rows
+    /// as results stream in. Returns the total number of rows delivered.
+    ///
+    /// `page_size` and `cursor` are ignored; `limit` still caps the result.
+    /// Raising from `on_batch` cancels the query and propagates the exception.
+    #[pyo3(
+        signature = (request, on_batch, batch_size = DEFAULT_STREAM_BATCH_SIZE, user_attributes = None),
+        text_signature = "(self, request, on_batch, batch_size=1000, user_attributes=None)"
+    )]
+    fn execute_with_callback(
+        &self,
+        py: Python<'_>,
+        request: &Bound<'_, PyAny>,
+        on_batch: &Bound<'_, PyAny>,
+        batch_size: u32,
+        user_attributes: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<u64> {
+        if !on_batch.is_callable() {
+            return Err(PyTypeError::new_err("on_batch must be callable"));
+        }
+        let start = Instant::now();
+        let request = parse_request(py, request)?;
+        let context = parse_context(py, user_attributes)?;
+        warn_deprecations(py, &self.registry, &request)?;
+        let registry = self.registry.clone();
+        let connections = self.connections.clone();
+
+        // The query runs on the tokio runtime; batches cross back to this
+        // thread so the callback is always invoked with the GIL held.
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let token = CancellationToken::new();
+        let task = runtime().spawn(cancel::with_token(token.clone(), async move {
+            let mut forward =
+                move |batch: crate::executor::QueryResult| -> crate::error::Result<()> {
+                    let rows_json = serde_json::to_string(&batch.rows)?;
+                    tx.send(rows_json).map_err(|_| {
+                        SemaflowError::Other(anyhow::anyhow!("streaming cancelled by caller"))
+                    })
+                };
+            run_query_streaming(
+                &registry,
+                &connections,
+                &request,
+                &context,
+                batch_size,
+                &mut forward,
+            )
+            .await
+        }));
+
+        let json = py.import("json")?;
+        while let Some(rows_json) = py.allow_threads(|| rx.blocking_recv()) {
+            let delivered = json
+                .call_method1("loads", (rows_json,))
+                .and_then(|rows| on_batch.call1((rows,)));
+            if let Err(e) = delivered {
+                // Stop the query in the backend rather than abandoning it
+                token.cancel();
+                drop(rx);
+                let _ = py.allow_threads(|| runtime().block_on(task));
+                return Err(e);
+            }
+        }
+
+        let rows = py
+            .allow_threads(|| runtime().block_on(task))
+            .map_err(py_err)?
+            .map_err(to_py_err)?;
+        tracing::debug!(
+            ms = start.elapsed().as_millis(),
+            rows = rows,
+            "execute_with_callback complete"
+        );
+        Ok(rows)
+    }
+
     /// List flows with names/descriptions.
     #[pyo3(text_signature = "(self)")]
     fn list_flows(&self, py: Python<'_>) -> PyResult<PyObject> {
@@ -1229,20 +2126,107 @@ impl SemanticFlowHandle {
             if let Some(desc) = s.description {
                 dict.set_item("description", desc)?;
             }
+            if let Some(deprecation) = &s.deprecated {
+                set_deprecation(&dict, deprecation)?;
+            }
+            dict.set_item("version", s.version.to_string())?;
             py_list.append(dict)?;
         }
         Ok(py_list.unbind().into())
     }
 
+    /// Compare this handle's semantic model with `previous`.
+    ///
+    /// Returns a dict with `changes` (each with `object`, `name`, `kind`,
+    /// `impact` and affected `flows`) and `breaking`, true when any change
+    /// can break existing queries.
+    #[pyo3(text_signature = "(self, previous)")]
+    fn diff(&self, py: Python<'_>, previous: PyRef<'_, SemanticFlowHandle>) -> PyResult<PyObject> {
+        let diff = FlowRegistry::diff(&previous.registry, &self.registry);
+        let mut value = serde_json::to_value(&diff)
+            .map_err(|e| PyValueError::new_err(format!("failed to serialize diff: {e}")))?;
+        value["breaking"] = serde_json::Value::Bool(diff.is_breaking());
+        serde_json_to_py(py, &value)
+    }
+
+    /// Lineage graph of the semantic model.
+    ///
+    /// Returns a dict with `nodes` (each with `id`, `kind`, `table` and
+    /// `name`) and `edges` (`from` → `to`), running from physical columns
+    /// through dimensions and measures to flows.
+    #[pyo3(text_signature = "(self)")]
+    fn lineage(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let value = serde_json::to_value(self.registry.lineage())
+            .map_err(|e| PyValueError::new_err(format!("failed to serialize lineage: {e}")))?;
+        serde_json_to_py(py, &value)
+    }
+
+    /// Flows, dimensions and measures matching `term`, best match first.
+    ///
+    /// Each hit is a dict with `kind` (`"flow"`, `"dimension"` or
+    /// `"measure"`), `flow`, `name` (qualified for fields), `description`,
+    /// `matched_on` and `score`.
+    #[pyo3(signature = (term, limit=None), text_signature = "(self, term, limit=None)")]
+    fn search(&self, py: Python<'_>, term: &str, limit: Option<usize>) -> PyResult<PyObject> {
+        let mut hits = self.registry.search(term);
+        if let Some(limit) = limit {
+            hits.truncate(limit);
+        }
+        let value = serde_json::to_value(hits)
+            .map_err(|e| PyValueError::new_err(format!("failed to serialize hits: {e}")))?;
+        serde_json_to_py(py, &value)
+    }
+
     /// Get flow schema (dimensions, measures, joins) by name.
-    #[pyo3(text_signature = "(self, name)")]
-    fn get_flow(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
-        let schema = self.registry.flow_schema(name).map_err(to_validation_err)?;
+    ///
+    /// Optional keyword arguments narrow the returned fields: `kind`
+    /// (`"dimensions"` or `"measures"`), `tags` (any match), `folder`
+    /// (including subfolders), `include_deprecated`, and `offset`/`limit`
+    /// paging over dimensions then measures.
+    #[pyo3(
+        signature = (name, *, kind=None, tags=None, folder=None, include_deprecated=true, offset=0, limit=None),
+        text_signature = "(self, name, *, kind=None, tags=None, folder=None, include_deprecated=True, offset=0, limit=None)"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    fn get_flow(
+        &self,
+        py: Python<'_>,
+        name: &str,
+        kind: Option<&str>,
+        tags: Option<Vec<String>>,
+        folder: Option<String>,
+        include_deprecated: bool,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> PyResult<PyObject> {
+        let kind = match kind {
+            None => None,
+            Some("dimensions") => Some(SchemaFieldKind::Dimensions),
+            Some("measures") => Some(SchemaFieldKind::Measures),
+            Some(other) => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown field kind {other}; expected 'dimensions' or 'measures'"
+                )))
+            }
+        };
+        let filter = SchemaFilter {
+            kind,
+            tags: tags.unwrap_or_default(),
+            folder,
+            include_deprecated,
+            offset,
+            limit,
+        };
+        let schema = self
+            .registry
+            .flow_schema_filtered(name, &filter)
+            .map_err(to_py_err)?;
         let dict = PyDict::new(py);
         dict.set_item("name", schema.name)?;
         if let Some(desc) = schema.description {
             dict.set_item("description", desc)?;
         }
+        set_field_metadata(&dict, schema.tags, schema.synonyms, None, schema.deprecated)?;
         dict.set_item("data_source", schema.data_source)?;
         if let Some(td) = schema.time_dimension {
             dict.set_item("time_dimension", td)?;
@@ -1250,6 +2234,7 @@ impl SemanticFlowHandle {
         if let Some(grain) = schema.smallest_time_grain {
             dict.set_item("smallest_time_grain", grain)?;
         }
+        dict.set_item("time_grains", schema.time_grains)?;
         let dims = PyList::empty(py);
         for d in schema.dimensions {
             let dct = PyDict::new(py);
@@ -1261,11 +2246,19 @@ impl SemanticFlowHandle {
             if let Some(dt) = d.data_type {
                 dct.set_item("data_type", dt)?;
             }
+            if let Some(grain) = d.time_grain {
+                dct.set_item("time_grain", grain)?;
+            }
             dct.set_item("semantic_table", d.semantic_table)?;
             dct.set_item("table_alias", d.table_alias)?;
+            if let Some(role) = d.role {
+                dct.set_item("role", role)?;
+            }
             let expr_json = serde_json::to_value(&d.expr).map_err(py_err)?;
             let expr_py = serde_json_to_py(py, &expr_json)?;
             dct.set_item("expr", expr_py)?;
+            set_field_metadata(&dct, d.tags, d.synonyms, d.folder, d.deprecated)?;
+            set_display_metadata(&dct, d.label, d.format, d.hidden)?;
             dims.append(dct)?;
         }
         dict.set_item("dimensions", dims)?;
@@ -1283,14 +2276,32 @@ impl SemanticFlowHandle {
             }
             dct.set_item("semantic_table", m.semantic_table)?;
             dct.set_item("table_alias", m.table_alias)?;
+            if let Some(role) = m.role {
+                dct.set_item("role", role)?;
+            }
             let expr_json = serde_json::to_value(&m.expr).map_err(py_err)?;
             let expr_py = serde_json_to_py(py, &expr_json)?;
             dct.set_item("expr", expr_py)?;
             dct.set_item("agg", format!("{:?}", m.agg))?;
+            set_field_metadata(&dct, m.tags, m.synonyms, m.folder, m.deprecated)?;
+            set_display_metadata(&dct, m.label, m.format, m.hidden)?;
             measures.append(dct)?;
         }
         dict.set_item("measures", measures)?;
 
+        let hierarchies = PyList::empty(py);
+        for h in schema.hierarchies {
+            let dct = PyDict::new(py);
+            dct.set_item("name", h.name)?;
+            dct.set_item("semantic_table", h.semantic_table)?;
+            dct.set_item("table_alias", h.table_alias)?;
+            dct.set_item("levels", h.levels)?;
+            hierarchies.append(dct)?;
+        }
+        dict.set_item("hierarchies", hierarchies)?;
+        dict.set_item("total_fields", schema.total_fields)?;
+        dict.set_item("next_offset", schema.next_offset)?;
+
         Ok(dict.unbind().into())
     }
 }
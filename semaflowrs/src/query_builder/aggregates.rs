@@ -0,0 +1,381 @@
+//! Aggregate awareness: answering requests from a flow's summary tables.
+//!
+//! A flow's `aggregates` declare existing tables holding its measures
+//! pre-aggregated to a coarser grain. When every field of a request maps to
+//! a column of one of them, the planner reads that table instead of the base
+//! table and its joins, re-aggregating the stored values to the requested
+//! grain. Among covering aggregates the one with the fewest dimensions wins,
+//! then the first by name.
+//!
+//! A request is covered when:
+//! - each dimension is mapped, or is the base table's time dimension at the
+//!   aggregate's grain or a coarser one;
+//! - each measure is mapped and sums, counts (`count_if` included) or takes
+//!   a minimum, maximum, `bool_and` or `bool_or`;
+//!   derived measures over covered measures are covered too;
+//! - filters and ordering use mapped dimensions only; filters on the time
+//!   dimension, including `time_filter`, need a `day` grain;
+//! - no access policy applies, no dimension is binned and default filters are
+//!   not ignored (summary rows already exclude what they filter);
+//! - no time grain is requested while a `timezone` is set.
+
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::flows::{
+    Aggregation, Expr, FlowAggregate, Function, QueryRequest, SemanticFlow, SemanticTable,
+    TimeGrain,
+};
+use crate::registry::FlowRegistry;
+use crate::sql_ast::{OrderItem, SqlExpr, TableRef};
+
+use super::builders::{
+    build_dimension_select, build_measure_selects, build_order_items, validate_non_empty_select,
+};
+use super::components::{
+    classify_measure, QueryComponents, ResolvedCondition, ResolvedDimension, ResolvedFilter,
+    ResolvedMeasure,
+};
+use super::explain::{FilterPlacement, LogicalPlan};
+use super::filters::render_resolved_filter;
+use super::measures::apply_measure_filter;
+use super::plan::{FlatPlan, QueryPlan};
+use super::render::expr_to_sql;
+use super::resolve::{build_alias_map, resolve_dimension, resolve_measure, resolve_time_dimension};
+
+/// The summary table answering `request`, if any, with its components
+/// rewritten to read from it.
+pub(crate) fn route(
+    flow: &SemanticFlow,
+    registry: &FlowRegistry,
+    request: &QueryRequest,
+    components: &QueryComponents,
+    supports_filtered_aggregates: bool,
+) -> Result<Option<(String, QueryComponents)>> {
+    if flow.aggregates.is_empty() {
+        return Ok(None);
+    }
+    let alias_map = build_alias_map(flow, registry)?;
+    // Stored periods start at the midnight of whatever zone built them
+    let zone = request.timezone.is_some()
+        || flow.timezone.is_some()
+        || alias_map.values().any(|t| t.timezone.is_some());
+    if !components.access_filters.is_empty()
+        || components.uses_unnest()
+        || !request.bins.is_empty()
+        || !request.segments.is_empty()
+        || (zone && request.dimensions.iter().any(|d| d.grain.is_some()))
+        || (request.ignore_default_filters
+            && (!flow.default_filters.is_empty()
+                || alias_map.values().any(|t| !t.default_filters.is_empty())))
+    {
+        return Ok(None);
+    }
+
+    let mut best: Option<(&String, &FlowAggregate, QueryComponents)> = None;
+    for (name, aggregate) in &flow.aggregates {
+        if best
+            .as_ref()
+            .is_some_and(|(_, b, _)| b.dimensions.len() <= aggregate.dimensions.len())
+        {
+            continue;
+        }
+        let coverage = Coverage {
+            flow,
+            registry,
+            alias_map: &alias_map,
+            aggregate,
+        };
+        if let Some(rewritten) =
+            coverage.rewrite(request, components, supports_filtered_aggregates)?
+        {
+            best = Some((name, aggregate, rewritten));
+        }
+    }
+    Ok(best.map(|(name, _, rewritten)| (name.clone(), rewritten)))
+}
+
+/// Plan a query reading the summary table: a flat SELECT without joins.
+pub(crate) fn build_aggregate_plan(
+    components: &QueryComponents,
+    logical: &mut LogicalPlan,
+) -> Result<QueryPlan> {
+    let mut plan = FlatPlan::new(components.base_table.clone());
+    for dim in &components.dimensions {
+        plan.select.push(build_dimension_select(dim));
+        plan.group_by.push(dim.expr.clone());
+    }
+    for f in &components.filters {
+        plan.filters
+            .push(render_resolved_filter(f, &SqlExpr::clone));
+        logical.add_filter(&f.condition, FilterPlacement::Where);
+    }
+    plan.order_by = build_order_items(components);
+    plan.limit = components.limit;
+    plan.offset = components.offset;
+
+    plan.select.extend(build_measure_selects(
+        &components.measures,
+        &components.base_measure_exprs,
+        true,
+    )?);
+    logical.add_measures(components);
+
+    validate_non_empty_select(&plan.select)?;
+    Ok(QueryPlan::Flat(plan))
+}
+
+/// Maps flow fields onto the columns of one aggregate.
+struct Coverage<'a> {
+    flow: &'a SemanticFlow,
+    registry: &'a FlowRegistry,
+    alias_map: &'a HashMap<String, &'a SemanticTable>,
+    aggregate: &'a FlowAggregate,
+}
+
+impl Coverage<'_> {
+    fn base_alias(&self) -> &str {
+        &self.flow.base_table.alias
+    }
+
+    fn column(&self, column: &str) -> SqlExpr {
+        SqlExpr::Column {
+            table: Some(self.base_alias().to_string()),
+            name: column.to_string(),
+        }
+    }
+
+    /// Summary column of the dimension `field`.
+    fn dimension(&self, field: &str) -> Option<SqlExpr> {
+        let (_, alias, dimension) =
+            resolve_dimension(field, self.flow, self.registry, self.alias_map).ok()?;
+        // Summary columns hold stored values, not their labels
+        if !dimension.labels.is_empty() {
+            return None;
+        }
+        self.aggregate
+            .dimensions
+            .iter()
+            .find(|(name, _)| {
+                resolve_dimension(name, self.flow, self.registry, self.alias_map)
+                    .is_ok_and(|(_, a, d)| a == alias && std::ptr::eq(d, dimension))
+            })
+            .map(|(_, column)| self.column(column))
+    }
+
+    /// Summary column of the measure `field`.
+    fn measure(&self, field: &str) -> Option<&str> {
+        let (_, alias, measure) =
+            resolve_measure(field, self.flow, self.registry, self.alias_map).ok()?;
+        self.aggregate
+            .measures
+            .iter()
+            .find(|(name, _)| {
+                resolve_measure(name, self.flow, self.registry, self.alias_map)
+                    .is_ok_and(|(_, a, m)| a == alias && std::ptr::eq(m, measure))
+            })
+            .map(|(_, column)| column.as_str())
+    }
+
+    fn is_time_dimension(&self, field: &str) -> bool {
+        resolve_time_dimension(field, self.flow, self.alias_map)
+            .is_ok_and(|(_, alias, _)| alias == self.base_alias())
+    }
+
+    /// The summary time column, when it holds whole days.
+    fn daily_time_column(&self) -> Option<SqlExpr> {
+        if self.aggregate.grain != Some(TimeGrain::Day) {
+            return None;
+        }
+        self.aggregate
+            .time_column
+            .as_deref()
+            .map(|c| self.column(c))
+    }
+
+    fn filter_column(&self, field: &str) -> Option<SqlExpr> {
+        self.dimension(field).or_else(|| {
+            self.daily_time_column()
+                .filter(|_| self.is_time_dimension(field))
+        })
+    }
+
+    fn condition(&self, condition: &ResolvedCondition) -> Option<ResolvedCondition> {
+        let all = |items: &[ResolvedCondition]| {
+            items
+                .iter()
+                .map(|c| self.condition(c))
+                .collect::<Option<Vec<_>>>()
+        };
+        Some(match condition {
+            ResolvedCondition::Leaf { filter, .. } => ResolvedCondition::Leaf {
+                filter: filter.clone(),
+                expr: self.filter_column(&filter.field)?,
+                alias: Some(self.base_alias().to_string()),
+            },
+            ResolvedCondition::TimeRange { range, .. } => ResolvedCondition::TimeRange {
+                range: range.clone(),
+                expr: self.daily_time_column()?,
+                as_timestamp: false,
+                alias: self.base_alias().to_string(),
+            },
+            // Default filters only appear at the top level, handled by the caller
+            ResolvedCondition::Predicate { .. } => return None,
+            ResolvedCondition::All(items) => ResolvedCondition::All(all(items)?),
+            ResolvedCondition::Any(items) => ResolvedCondition::Any(all(items)?),
+            ResolvedCondition::Not(inner) => {
+                ResolvedCondition::Not(Box::new(self.condition(inner)?))
+            }
+        })
+    }
+
+    /// The components reading from the aggregate; `None` when it does not
+    /// cover the request.
+    fn rewrite(
+        &self,
+        request: &QueryRequest,
+        components: &QueryComponents,
+        supports_filtered_aggregates: bool,
+    ) -> Result<Option<QueryComponents>> {
+        let base_alias = self.base_alias().to_string();
+        let aggregate = self.aggregate;
+
+        let mut dimensions = Vec::new();
+        for (requested, resolved) in request.dimensions.iter().zip(&components.dimensions) {
+            let expr = match &requested.grain {
+                Some(grain) => {
+                    let (Some(column), Some(stored)) = (&aggregate.time_column, &aggregate.grain)
+                    else {
+                        return Ok(None);
+                    };
+                    // Fiscal periods need not start on a stored period's start
+                    if grain < stored
+                        || (grain.is_fiscal() && stored > &TimeGrain::Day)
+                        || !self.is_time_dimension(&requested.field)
+                    {
+                        return Ok(None);
+                    }
+                    let truncated = Expr::Func {
+                        func: Function::DateTrunc(grain.clone()),
+                        args: vec![Expr::Column {
+                            column: column.clone(),
+                        }],
+                    };
+                    expr_to_sql(&truncated, &base_alias)
+                }
+                None => match self.dimension(&requested.field) {
+                    Some(expr) => expr,
+                    None => return Ok(None),
+                },
+            };
+            dimensions.push(ResolvedDimension {
+                name: resolved.name.clone(),
+                alias: base_alias.clone(),
+                expr,
+            });
+        }
+
+        let mut measures = Vec::new();
+        let mut base_measure_exprs = HashMap::new();
+        for m in &components.measures {
+            let mut measure = m.measure.clone();
+            let base_expr = if measure.is_formula() {
+                return Ok(None);
+            } else if measure.is_simple() {
+                let column = match (&measure.post_expr, self.measure(&m.name)) {
+                    (None, Some(column)) => column,
+                    _ => return Ok(None),
+                };
+                // Stored partial aggregates roll up with these
+                measure.agg = match measure.agg {
+                    Some(Aggregation::Sum | Aggregation::Count | Aggregation::CountIf) => {
+                        Some(Aggregation::Sum)
+                    }
+                    Some(Aggregation::Min) => Some(Aggregation::Min),
+                    Some(Aggregation::Max) => Some(Aggregation::Max),
+                    Some(Aggregation::BoolAnd) => Some(Aggregation::BoolAnd),
+                    Some(Aggregation::BoolOr) => Some(Aggregation::BoolOr),
+                    _ => return Ok(None),
+                };
+                measure.expr = Some(Expr::Column {
+                    column: column.to_string(),
+                });
+                measure.filter = None;
+                let expr = apply_measure_filter(
+                    &measure,
+                    self.column(column),
+                    &base_alias,
+                    supports_filtered_aggregates,
+                )?;
+                let unqualified = m.name.rsplit('.').next().unwrap_or(&m.name);
+                base_measure_exprs.insert(m.name.clone(), expr.clone());
+                base_measure_exprs
+                    .entry(unqualified.to_string())
+                    .or_insert_with(|| expr.clone());
+                base_measure_exprs
+                    .entry(format!("{}.{unqualified}", m.alias))
+                    .or_insert_with(|| expr.clone());
+                Some(expr)
+            } else if measure.post_expr.is_some() {
+                // Derived from other measures, which are rewritten themselves
+                None
+            } else {
+                return Ok(None);
+            };
+            measures.push(ResolvedMeasure {
+                name: m.name.clone(),
+                alias: base_alias.clone(),
+                strategy: classify_measure(&measure),
+                measure,
+                base_expr,
+                requested: m.requested,
+            });
+        }
+
+        let mut filters = Vec::new();
+        for filter in &components.filters {
+            if matches!(filter.condition, ResolvedCondition::Predicate { .. }) {
+                continue;
+            }
+            let Some(condition) = self.condition(&filter.condition) else {
+                return Ok(None);
+            };
+            filters.push(ResolvedFilter {
+                condition,
+                alias: Some(base_alias.clone()),
+            });
+        }
+
+        let mut order = Vec::new();
+        for (item, resolved) in request.order.iter().zip(&components.order) {
+            let expr = match &resolved.expr {
+                // Measures are ordered by their output column
+                SqlExpr::Column { table: None, .. } => resolved.expr.clone(),
+                _ => match self.dimension(&item.column) {
+                    Some(expr) => expr,
+                    None => return Ok(None),
+                },
+            };
+            order.push(OrderItem {
+                expr,
+                direction: resolved.direction.clone(),
+            });
+        }
+
+        Ok(Some(QueryComponents {
+            base_table: TableRef {
+                name: aggregate.table.clone(),
+                alias: Some(base_alias.clone()),
+                subquery: None,
+                sql: None,
+            },
+            dimensions,
+            measures,
+            base_measure_exprs,
+            filters,
+            order,
+            join_lookup: HashMap::new(),
+            ..components.clone()
+        }))
+    }
+}
@@ -9,11 +9,12 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::error::{Result, SemaflowError};
+use crate::expr_utils::collect_column_refs;
 use crate::flows::{FlowJoin, JoinType, SemanticFlow};
 use crate::sql_ast::SqlJoinType;
 
 use super::components::QueryComponents;
-use super::grain::{infer_join_cardinality, Cardinality, Grain};
+use super::grain::{infer_join_cardinality, unique_keys, Cardinality, Grain};
 
 /// Result of analyzing a query for fanout risk.
 #[derive(Debug, Clone)]
@@ -68,6 +69,37 @@ fn expand_join_chains(
     all_aliases
 }
 
+/// The join attaching `alias` to the base table: its own join, or the first
+/// join on the path from it towards the base.
+///
+/// Errors when the path breaks off at an alias without a join or returns to
+/// an alias it already passed.
+fn join_to_base<'a>(
+    alias: &str,
+    base_alias: &str,
+    join_lookup: &'a HashMap<String, FlowJoin>,
+    flow: &str,
+) -> Result<&'a FlowJoin> {
+    let mut path = vec![alias.to_string()];
+    loop {
+        let current = path.last().expect("path starts with alias");
+        let join = join_lookup.get(current).ok_or_else(|| {
+            SemaflowError::Validation(format!("missing join definition for alias {}", current))
+        })?;
+        if join.to_table == base_alias {
+            return Ok(join);
+        }
+        if path.contains(&join.to_table) {
+            path.push(join.to_table.clone());
+            return Err(SemaflowError::JoinCycle {
+                flow: flow.to_string(),
+                path,
+            });
+        }
+        path.push(join.to_table.clone());
+    }
+}
+
 /// Analyze the query components to determine if pre-aggregation is needed.
 ///
 /// Pre-aggregation is used when:
@@ -79,7 +111,14 @@ fn expand_join_chains(
 ///
 /// This avoids the "fanout problem" where joining to a many-side table
 /// would multiply base rows before aggregation.
-pub fn analyze_fanout_risk(components: &QueryComponents, _flow: &SemanticFlow) -> FanoutAnalysis {
+///
+/// With `force_preagg` (a request's `strategy: preagg`), conditions 3 and 5
+/// are waived: any joined dimension or filter is pre-aggregated below.
+pub fn analyze_fanout_risk(
+    components: &QueryComponents,
+    _flow: &SemanticFlow,
+    force_preagg: bool,
+) -> FanoutAnalysis {
     // No joins means no fanout risk
     if !components.has_joins() {
         return FanoutAnalysis::flat();
@@ -93,7 +132,7 @@ pub fn analyze_fanout_risk(components: &QueryComponents, _flow: &SemanticFlow) -
 
     // Check if there are filters on joined tables
     let has_join_filters = components.has_join_filters();
-    if !has_join_filters {
+    if !has_join_filters && !force_preagg {
         return FanoutAnalysis::flat();
     }
 
@@ -105,6 +144,9 @@ pub fn analyze_fanout_risk(components: &QueryComponents, _flow: &SemanticFlow) -
     let mut direct_aliases: HashSet<String> = HashSet::new();
     direct_aliases.extend(components.joined_dimension_aliases());
     direct_aliases.extend(components.joined_filter_aliases());
+    if direct_aliases.is_empty() {
+        return FanoutAnalysis::flat();
+    }
 
     // Expand to include all aliases in join chains back to base
     let needed_join_aliases =
@@ -119,28 +161,23 @@ pub fn analyze_fanout_risk(components: &QueryComponents, _flow: &SemanticFlow) -
             // Get the table we're joining from
             let from_alias = &join.to_table;
 
-            // Get primary keys for both sides
-            let left_pk: Grain = if from_alias == base_alias {
-                components
-                    .base_semantic_table
-                    .primary_keys
-                    .iter()
-                    .cloned()
-                    .collect()
+            // Get unique keys for both sides
+            let left_keys: Vec<Grain> = if from_alias == base_alias {
+                unique_keys(&components.base_semantic_table)
             } else if let Some(from_table) = components.alias_to_table.get(from_alias) {
-                from_table.primary_keys.iter().cloned().collect()
+                unique_keys(from_table)
             } else {
                 joins_compatible = false;
                 break;
             };
 
-            // Get joined table's primary keys
+            // Get joined table's unique keys
             if let Some(joined_table) = components.alias_to_table.get(alias) {
-                let right_pk: Grain = joined_table.primary_keys.iter().cloned().collect();
+                let right_keys = unique_keys(joined_table);
 
                 // Infer cardinality for this hop (user hint takes precedence)
                 let hint = join.cardinality.map(|c| c.into());
-                let cardinality = infer_join_cardinality(join, &left_pk, &right_pk, hint);
+                let cardinality = infer_join_cardinality(join, &left_keys, &right_keys, hint);
 
                 // Check if this join could cause fanout
                 if could_cause_fanout_for_filter(cardinality, join) {
@@ -159,7 +196,7 @@ pub fn analyze_fanout_risk(components: &QueryComponents, _flow: &SemanticFlow) -
 
     // If no fanout risk detected, we can use a flat query
     // (all joins are ManyToOne or OneToOne)
-    if !has_fanout_risk {
+    if !has_fanout_risk && !force_preagg {
         return FanoutAnalysis::flat();
     }
 
@@ -260,10 +297,12 @@ impl MultiGrainAnalysis {
 /// 2. Single-table measures with fanout risk (current preagg trigger)
 ///
 /// This function unifies the old PreAggPlan logic into the new MultiGrainPlan
-/// with N=1 CTE for single-table cases.
+/// with N=1 CTE for single-table cases. `force_preagg` pre-aggregates
+/// single-table measures whatever the fanout risk; see [`analyze_fanout_risk`].
 pub fn analyze_multi_grain(
     components: &QueryComponents,
     flow: &SemanticFlow,
+    force_preagg: bool,
 ) -> Result<MultiGrainAnalysis> {
     // Check for multi-table measures
     let multi_table = components.multi_table_measure_aliases();
@@ -274,7 +313,7 @@ pub fn analyze_multi_grain(
     }
 
     // Otherwise, check if single-table fanout risk triggers preagg
-    let fanout = analyze_fanout_risk(components, flow);
+    let fanout = analyze_fanout_risk(components, flow, force_preagg);
     if fanout.needs_preagg {
         return analyze_single_table_preagg(components, &fanout);
     }
@@ -285,7 +324,7 @@ pub fn analyze_multi_grain(
 /// Analyze multi-table measure requirements.
 fn analyze_multi_table_measures(
     components: &QueryComponents,
-    _flow: &SemanticFlow,
+    flow: &SemanticFlow,
     table_aliases: &[String],
 ) -> Result<MultiGrainAnalysis> {
     let base_alias = &components.base_alias;
@@ -311,10 +350,18 @@ fn analyze_multi_table_measures(
                 // For joined tables, grain is always join_keys.right (column on THIS table)
                 // The cardinality just tells us if this is safe
                 if matches!(cardinality, Cardinality::ManyToMany | Cardinality::Unknown) {
+                    return Err(SemaflowError::CardinalityRequired {
+                        join: alias.clone(),
+                        to_table: join.to_table.clone(),
+                    });
+                }
+
+                // The CTE join only pairs keys; conditions on other columns
+                // can't be applied between pre-aggregated rows
+                if join.extra_conditions.is_some() {
                     return Err(SemaflowError::Validation(format!(
-                        "Multi-table measures require cardinality hint for join '{}' → '{}'. \
-                         Add `cardinality: many_to_one` (or appropriate value) to the join definition.",
-                        alias, join.to_table
+                        "join '{alias}' has extra_conditions, which can't be applied when \
+                         its measures are pre-aggregated; request them in a separate query"
                     )));
                 }
 
@@ -345,31 +392,49 @@ fn analyze_multi_table_measures(
         }
     }
 
-    // Second pass: base table grain = FK columns to other measure tables
-    if table_aliases.contains(&base_alias.to_string()) {
-        let mut base_grain_columns: Vec<String> = Vec::new();
-
-        // Collect all FK columns from joins to tables with measures
-        for alias in table_aliases {
-            if alias != base_alias {
-                if let Some(join) = components.join_lookup.get(alias) {
-                    for k in &join.join_keys {
-                        if !base_grain_columns.contains(&k.left) {
-                            base_grain_columns.push(k.left.clone());
-                        }
+    // Second pass: base table grain = FK columns to other measure tables.
+    // The base CTE is built even without base measures: the others join to it
+    let mut base_grain_columns: Vec<String> = Vec::new();
+
+    // Collect all FK columns from joins to tables with measures
+    for alias in table_aliases {
+        if alias != base_alias {
+            if let Some(join) = components.join_lookup.get(alias) {
+                for k in &join.join_keys {
+                    if !base_grain_columns.contains(&k.left) {
+                        base_grain_columns.push(k.left.clone());
                     }
                 }
             }
         }
+    }
 
-        table_grains.insert(
-            base_alias.clone(),
-            TableGrain {
-                grain_columns: base_grain_columns,
-            },
-        );
+    // Tables read only for dimensions or filters join to the base CTE in the
+    // final query, so it keeps their join keys as well
+    let lookups: HashSet<String> = components
+        .dimensions
+        .iter()
+        .map(|d| d.alias.clone())
+        .chain(components.unnests.iter().map(|u| u.alias.clone()))
+        .chain(components.filters.iter().flat_map(|f| f.aliases()))
+        .filter(|alias| alias != base_alias && !table_aliases.contains(alias))
+        .collect();
+    for alias in &lookups {
+        let join = join_to_base(alias, base_alias, &components.join_lookup, &flow.name)?;
+        for k in &join.join_keys {
+            if !base_grain_columns.contains(&k.left) {
+                base_grain_columns.push(k.left.clone());
+            }
+        }
     }
 
+    table_grains.insert(
+        base_alias.clone(),
+        TableGrain {
+            grain_columns: base_grain_columns,
+        },
+    );
+
     Ok(MultiGrainAnalysis {
         needs_multi_grain: true,
         table_grains,
@@ -393,6 +458,25 @@ fn analyze_single_table_preagg(
             }
         }
     }
+    // Base columns read by extra join conditions, e.g. `o.created_at`
+    for alias in fanout.join_key_mappings.keys() {
+        let Some(condition) = components
+            .join_lookup
+            .get(alias)
+            .and_then(|join| join.extra_conditions.as_ref())
+        else {
+            continue;
+        };
+        let mut columns = Vec::new();
+        collect_column_refs(condition, &mut columns);
+        for column in columns {
+            if let Some(base_col) = column.strip_prefix(&format!("{base_alias}.")) {
+                if !grain_columns.iter().any(|c| c == base_col) {
+                    grain_columns.push(base_col.to_string());
+                }
+            }
+        }
+    }
 
     let mut table_grains = HashMap::new();
     table_grains.insert(base_alias.clone(), TableGrain { grain_columns });
@@ -420,8 +504,8 @@ fn analyze_single_table_preagg(
     })
 }
 
-/// Infer cardinality for a join, using hints or PK-based inference.
-fn infer_cardinality_for_join(
+/// Infer cardinality for a join, using hints or unique-key inference.
+pub(crate) fn infer_cardinality_for_join(
     join: &FlowJoin,
     components: &QueryComponents,
 ) -> Result<Cardinality> {
@@ -430,30 +514,24 @@ fn infer_cardinality_for_join(
         return Ok(hint.into());
     }
 
-    // Get PKs for both sides
+    // Get unique keys for both sides
     let from_alias = &join.to_table;
     let to_alias = &join.alias;
 
-    let left_pk: Grain = if from_alias == &components.base_alias {
-        components
-            .base_semantic_table
-            .primary_keys
-            .iter()
-            .cloned()
-            .collect()
+    let left_keys = if from_alias == &components.base_alias {
+        unique_keys(&components.base_semantic_table)
     } else if let Some(table) = components.alias_to_table.get(from_alias) {
-        table.primary_keys.iter().cloned().collect()
+        unique_keys(table)
     } else {
-        HashSet::new()
+        Vec::new()
     };
 
-    let right_pk: Grain = if let Some(table) = components.alias_to_table.get(to_alias) {
-        table.primary_keys.iter().cloned().collect()
-    } else {
-        HashSet::new()
+    let right_keys = match components.alias_to_table.get(to_alias) {
+        Some(table) => unique_keys(table),
+        None => Vec::new(),
     };
 
-    Ok(infer_join_cardinality(join, &left_pk, &right_pk, None))
+    Ok(infer_join_cardinality(join, &left_keys, &right_keys, None))
 }
 
 #[cfg(test)]
@@ -472,6 +550,10 @@ mod tests {
                 right: "id".to_string(),
             }],
             cardinality: None,
+            through: None,
+            weight: None,
+            role: None,
+            extra_conditions: None,
             description: None,
         }
     }
@@ -519,4 +601,29 @@ mod tests {
         assert!(expanded.contains("c"));
         assert!(expanded.contains("r"));
     }
+
+    #[test]
+    fn join_to_base_follows_the_path_to_the_base() {
+        // o (base) <- c <- r
+        let mut join_lookup = HashMap::new();
+        join_lookup.insert("c".to_string(), make_join("c", "o", JoinType::Left));
+        join_lookup.insert("r".to_string(), make_join("r", "c", JoinType::Left));
+
+        let join = join_to_base("r", "o", &join_lookup, "sales").unwrap();
+        assert_eq!(join.alias, "c");
+    }
+
+    #[test]
+    fn join_to_base_rejects_cycles_and_missing_links() {
+        let mut join_lookup = HashMap::new();
+        join_lookup.insert("c".to_string(), make_join("c", "r", JoinType::Left));
+        join_lookup.insert("r".to_string(), make_join("r", "c", JoinType::Left));
+        join_lookup.insert("p".to_string(), make_join("p", "x", JoinType::Left));
+
+        match join_to_base("c", "o", &join_lookup, "sales") {
+            Err(SemaflowError::JoinCycle { path, .. }) => assert_eq!(path, ["c", "r", "c"]),
+            other => panic!("expected a join cycle, got {other:?}"),
+        }
+        assert!(join_to_base("p", "o", &join_lookup, "sales").is_err());
+    }
 }
@@ -0,0 +1,145 @@
+//! Many-to-many joins through bridge tables.
+//!
+//! A join declared `through` a bridge reaches its table over the bridge's
+//! rows, so grouping or filtering by the bridge or the table beyond it
+//! repeats each row of the near side once per bridge row. Measures on the
+//! near side are then made to count each row once per group: weighted by
+//! the join's `weight` column (`SUM(x * w)`, counts become `SUM(w)`) when it
+//! has one, and aggregated over distinct primary keys (see
+//! [`super::symmetric`]) otherwise or when a measure cannot be weighted.
+
+use std::collections::HashMap;
+
+use crate::flows::{Aggregation, Function};
+use crate::sql_ast::{SqlBinaryOperator, SqlExpr};
+
+use super::components::QueryComponents;
+use super::explain::PlanStrategy;
+use super::measures::aggregate_input;
+use super::symmetric;
+
+/// The components with near-side measures counted once per group, and the
+/// strategy used, when the request reads across a bridge.
+pub(crate) fn rewrite(components: &QueryComponents) -> Option<(QueryComponents, PlanStrategy)> {
+    let read: Vec<String> = components
+        .joined_dimension_aliases()
+        .into_iter()
+        .chain(components.joined_filter_aliases())
+        .collect();
+    let join = components.join_lookup.values().find(|join| {
+        join.through
+            .as_deref()
+            .is_some_and(|bridge| read.iter().any(|alias| behind(alias, bridge, components)))
+    })?;
+    let bridge = join.through.as_deref()?;
+    if components
+        .measures
+        .iter()
+        .all(|m| behind(&m.alias, bridge, components))
+    {
+        return None;
+    }
+    join.weight
+        .as_deref()
+        .and_then(|weight| weighted(components, bridge, weight))
+        .map(|weighted| (weighted, PlanStrategy::Weighted))
+        .or_else(|| symmetric::rewrite(components).map(|c| (c, PlanStrategy::Symmetric)))
+}
+
+/// Whether `alias` is `ancestor` or joins to the flow through it.
+fn behind(alias: &str, ancestor: &str, components: &QueryComponents) -> bool {
+    let mut current = alias;
+    for _ in 0..=components.join_lookup.len() {
+        if current == ancestor {
+            return true;
+        }
+        match components.join_lookup.get(current) {
+            Some(join) => current = &join.to_table,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// The components with every near-side simple measure weighted by the
+/// bridge's `weight` column, or `None` when a measure cannot be.
+fn weighted(components: &QueryComponents, bridge: &str, weight: &str) -> Option<QueryComponents> {
+    let weight = SqlExpr::Column {
+        table: Some(bridge.to_string()),
+        name: weight.to_string(),
+    };
+    let mut measures = Vec::new();
+    let mut base_measure_exprs = HashMap::new();
+    for m in &components.measures {
+        let mut m = m.clone();
+        let near = !behind(&m.alias, bridge, components);
+        if near && m.measure.is_formula() {
+            return None;
+        }
+        if let Some(base_expr) = &m.base_expr {
+            let expr = if near {
+                weighted_expr(m.measure.agg.as_ref()?, base_expr, &weight)?
+            } else {
+                base_expr.clone()
+            };
+            let unqualified = m.name.rsplit('.').next().unwrap_or(&m.name);
+            base_measure_exprs.insert(m.name.clone(), expr.clone());
+            base_measure_exprs
+                .entry(unqualified.to_string())
+                .or_insert_with(|| expr.clone());
+            base_measure_exprs
+                .entry(format!("{}.{unqualified}", m.alias))
+                .or_insert_with(|| expr.clone());
+            m.base_expr = Some(expr);
+        }
+        measures.push(m);
+    }
+    Some(QueryComponents {
+        measures,
+        base_measure_exprs,
+        ..components.clone()
+    })
+}
+
+/// `base_expr`, an `agg` aggregate, with each row counted `weight` times.
+fn weighted_expr(agg: &Aggregation, base_expr: &SqlExpr, weight: &SqlExpr) -> Option<SqlExpr> {
+    let input = aggregate_input(base_expr)?;
+    let sum = |expr: SqlExpr| SqlExpr::Aggregate {
+        agg: Aggregation::Sum,
+        expr: Box::new(expr),
+    };
+    let weighted_sum = |expr: SqlExpr| {
+        sum(SqlExpr::BinaryOp {
+            op: SqlBinaryOperator::Multiply,
+            left: Box::new(expr),
+            right: Box::new(weight.clone()),
+        })
+    };
+    let weight_where = |condition: SqlExpr| {
+        sum(SqlExpr::Case {
+            branches: vec![(condition, weight.clone())],
+            else_expr: Box::new(SqlExpr::Literal(serde_json::Value::Null)),
+        })
+    };
+    let not_null = |expr: &SqlExpr| SqlExpr::IsNull {
+        expr: Box::new(expr.clone()),
+        negated: true,
+    };
+    match agg {
+        Aggregation::Sum => Some(weighted_sum(input)),
+        Aggregation::Count => Some(weight_where(not_null(&input))),
+        Aggregation::CountIf => Some(weight_where(input)),
+        Aggregation::Avg => Some(SqlExpr::Function {
+            func: Function::SafeDivide,
+            args: vec![weighted_sum(input.clone()), weight_where(not_null(&input))],
+        }),
+        // Weights don't change these
+        Aggregation::Min
+        | Aggregation::Max
+        | Aggregation::CountDistinct
+        | Aggregation::ApproxCountDistinct
+        | Aggregation::BoolAnd
+        | Aggregation::BoolOr => Some(base_expr.clone()),
+        _ => None,
+    }
+}
@@ -13,6 +13,7 @@ use crate::sql_ast::{
 
 use super::components::{QueryComponents, ResolvedDimension, ResolvedMeasure};
 use super::measures::resolve_measure_with_posts;
+use super::render::join_condition_to_sql;
 
 /// Convert a semantic JoinType to SQL JoinType.
 impl From<JoinType> for SqlJoinType {
@@ -81,6 +82,17 @@ pub fn build_measure_selects(
     Ok(selects)
 }
 
+/// Build the FROM or JOIN source for a semantic table, reading its `sql` as a
+/// derived table when it has one.
+pub fn semantic_table_ref(table: &SemanticTable, alias: &str) -> TableRef {
+    TableRef {
+        name: table.table.clone(),
+        alias: Some(alias.to_string()),
+        subquery: None,
+        sql: table.sql.clone(),
+    }
+}
+
 /// Build a JOIN clause from a FlowJoin.
 pub fn build_join(
     join: &FlowJoin,
@@ -93,7 +105,7 @@ pub fn build_join(
         ))
     })?;
 
-    let on_clause: Vec<SqlExpr> = join
+    let mut on_clause: Vec<SqlExpr> = join
         .join_keys
         .iter()
         .map(|k| SqlExpr::BinaryOp {
@@ -108,14 +120,15 @@ pub fn build_join(
             }),
         })
         .collect();
+    on_clause.extend(
+        join.extra_conditions
+            .as_ref()
+            .map(|expr| join_condition_to_sql(expr, &join.alias, &str::to_string)),
+    );
 
     Ok(Join {
         join_type: join.join_type.clone().into(),
-        table: TableRef {
-            name: join_table.table.clone(),
-            alias: Some(join.alias.clone()),
-            subquery: None,
-        },
+        table: semantic_table_ref(join_table, &join.alias),
         on: on_clause,
     })
 }
@@ -0,0 +1,55 @@
+//! Result column metadata derived from the request and the registry.
+
+use crate::error::{Result, SemaflowError};
+use crate::executor::{ColumnKind, ColumnMeta};
+use crate::flows::QueryRequest;
+use crate::registry::FlowRegistry;
+use crate::sql_ast::sanitize_alias;
+
+use super::resolve::{build_alias_map, resolve_dimension_inner, resolve_measure_inner};
+
+/// Mark each result column as a dimension or measure of `request`, and fall
+/// back to the field's declared `data_type` where the backend reported none.
+pub(crate) fn annotate_columns(
+    registry: &FlowRegistry,
+    request: &QueryRequest,
+    columns: &mut [ColumnMeta],
+) -> Result<()> {
+    let flow = registry
+        .get_flow(&request.flow)
+        .ok_or_else(|| SemaflowError::UnknownFlow {
+            flow: request.flow.clone(),
+        })?;
+    let alias_map = build_alias_map(flow, registry)?;
+
+    for column in columns {
+        if let Some(dim) = request
+            .dimensions
+            .iter()
+            .find(|d| sanitize_alias(&d.field) == column.name)
+        {
+            column.kind = Some(ColumnKind::Dimension);
+            if column.data_type.is_none() {
+                column.data_type = resolve_dimension_inner(&dim.field, flow, registry, &alias_map)?
+                    .and_then(|(_, _, d)| d.data_type.clone());
+            }
+        } else if let Some(name) = request
+            .measures
+            .iter()
+            .find(|m| sanitize_alias(m) == column.name)
+        {
+            column.kind = Some(ColumnKind::Measure);
+            if column.data_type.is_none() {
+                column.data_type = resolve_measure_inner(name, flow, registry, &alias_map)?
+                    .and_then(|(_, _, m)| m.data_type.clone());
+            }
+        } else if request
+            .calculations
+            .iter()
+            .any(|c| sanitize_alias(&c.name) == column.name)
+        {
+            column.kind = Some(ColumnKind::Measure);
+        }
+    }
+    Ok(())
+}
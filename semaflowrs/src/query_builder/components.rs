@@ -5,19 +5,26 @@
 
 use std::collections::HashMap;
 
+use serde::Serialize;
+
+use crate::dialect::grain_to_str;
 use crate::error::{Result, SemaflowError};
+use crate::expr_utils::collect_time_grains;
 use crate::flows::{
-    Aggregation, Filter, FlowJoin, Measure, QueryRequest, SemanticFlow, SemanticTable,
+    Aggregation, BinaryOp, Dimension, Expr, Filter, FilterExpr, FlowJoin, Function, Measure,
+    QueryRequest, RelativeDateRange, SemanticFlow, SemanticTable, TimeGrain,
 };
+use crate::policy::QueryContext;
 use crate::registry::FlowRegistry;
-use crate::sql_ast::{OrderItem, SqlExpr, TableRef};
+use crate::sql_ast::{OrderItem, SqlExpr, TableRef, Unnest};
 
 /// Strategy for how a measure should be handled in multi-grain queries.
 ///
 /// Different aggregation types require different re-aggregation strategies
 /// when pre-aggregating to a join grain and then re-aggregating to the
 /// requested dimension grain.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MeasureStrategy {
     /// SUM, COUNT - pre-aggregate then SUM in final query.
     /// SUM(SUM(x)) = SUM(x), SUM(COUNT(x)) = COUNT(x)
@@ -32,8 +39,10 @@ pub enum MeasureStrategy {
     /// Final: SUM(m__sum) / SUM(m__count)
     WeightedAverage,
 
-    /// COUNT DISTINCT - skip CTE pre-aggregation, calculate in final query.
-    /// DISTINCT naturally handles fanout duplication.
+    /// COUNT DISTINCT - pre-aggregate a sketch of the distinct values, then
+    /// merge the sketches in the final query. Dialects without sketches
+    /// count in a flat subquery at the requested grain, joined back on the
+    /// dimensions.
     DistinctSafe,
 
     /// MEDIAN, STDDEV, VARIANCE, etc. - cannot be decomposed.
@@ -45,10 +54,14 @@ pub enum MeasureStrategy {
 pub fn classify_aggregation(agg: &Aggregation) -> MeasureStrategy {
     match agg {
         // Pre-aggregatable: SUM of SUMs = total SUM, SUM of COUNTs = total COUNT
-        Aggregation::Sum | Aggregation::Count => MeasureStrategy::PreAggregatable,
+        Aggregation::Sum | Aggregation::Count | Aggregation::CountIf => {
+            MeasureStrategy::PreAggregatable
+        }
 
-        // Associative: MIN/MAX of MIN/MAX = overall MIN/MAX
-        Aggregation::Min | Aggregation::Max => MeasureStrategy::Associative,
+        // Associative: MIN/MAX of MIN/MAX = overall MIN/MAX, likewise AND/OR
+        Aggregation::Min | Aggregation::Max | Aggregation::BoolAnd | Aggregation::BoolOr => {
+            MeasureStrategy::Associative
+        }
 
         // Weighted average: need sum and count separately
         Aggregation::Avg => MeasureStrategy::WeightedAverage,
@@ -64,10 +77,12 @@ pub fn classify_aggregation(agg: &Aggregation) -> MeasureStrategy {
         | Aggregation::StddevSamp
         | Aggregation::Variance
         | Aggregation::VarianceSamp
+        | Aggregation::Percentile { .. }
+        | Aggregation::ApproxPercentile { .. }
         | Aggregation::StringAgg { .. }
         | Aggregation::ArrayAgg
-        | Aggregation::First
-        | Aggregation::Last => MeasureStrategy::NonDecomposable,
+        | Aggregation::First { .. }
+        | Aggregation::Last { .. } => MeasureStrategy::NonDecomposable,
     }
 }
 
@@ -82,12 +97,18 @@ pub fn classify_measure(measure: &Measure) -> MeasureStrategy {
     }
 }
 
+use super::builders::semantic_table_ref;
+use super::filters::{
+    check_filter_value, normalize_boolean_filter, render_filter_expr, unlabel_filter,
+};
 use super::measures::{
-    apply_measure_filter, collect_measure_refs, formula_to_sql, validate_no_measure_refs,
+    apply_measure_filter, collect_measure_refs, formula_to_sql, normalize_freeform,
+    validate_no_measure_refs,
 };
-use super::render::expr_to_sql;
+use super::render::{expr_to_local_sql, expr_to_sql};
 use super::resolve::{
-    build_alias_map, resolve_dimension, resolve_field_expression, resolve_measure, FieldKind,
+    build_alias_map, resolve_dimension, resolve_field_expression, resolve_measure, resolve_segment,
+    resolve_time_dimension, truncation_zone, unnest_alias, unnest_element, FieldKind, Segment,
 };
 use crate::expr_parser::parse_formula;
 
@@ -99,6 +120,15 @@ pub struct ResolvedDimension {
     pub expr: SqlExpr,
 }
 
+/// The array of a requested `unnest` dimension, joined in with one row per
+/// element.
+#[derive(Clone, Debug)]
+pub struct ResolvedUnnest {
+    /// Alias of the table the array is read from.
+    pub alias: String,
+    pub unnest: Unnest,
+}
+
 /// A resolved measure with its base expression and metadata.
 #[derive(Clone, Debug)]
 pub struct ResolvedMeasure {
@@ -111,14 +141,80 @@ pub struct ResolvedMeasure {
     pub strategy: MeasureStrategy,
 }
 
-/// A resolved filter ready for SQL generation.
+/// A resolved top-level request filter ready for SQL generation.
 #[derive(Clone, Debug)]
 pub struct ResolvedFilter {
-    pub filter: Filter,
-    pub expr: SqlExpr,
+    pub condition: ResolvedCondition,
+    /// Table alias shared by every condition in the filter; `None` when a
+    /// filter group spans more than one table.
     pub alias: Option<String>,
 }
 
+/// A filter condition tree with each leaf resolved to its dimension expression.
+#[derive(Clone, Debug)]
+pub enum ResolvedCondition {
+    Leaf {
+        filter: Filter,
+        expr: SqlExpr,
+        alias: Option<String>,
+    },
+    /// The request's relative `time_filter`, applied to the time dimension.
+    TimeRange {
+        range: RelativeDateRange,
+        expr: SqlExpr,
+        as_timestamp: bool,
+        alias: String,
+    },
+    /// A table's default filter, already rendered against its alias.
+    Predicate {
+        expr: SqlExpr,
+        alias: String,
+    },
+    All(Vec<ResolvedCondition>),
+    Any(Vec<ResolvedCondition>),
+    Not(Box<ResolvedCondition>),
+}
+
+impl ResolvedCondition {
+    /// Collect the table aliases referenced by every leaf condition.
+    pub fn collect_aliases(&self, out: &mut std::collections::HashSet<String>) {
+        match self {
+            ResolvedCondition::Leaf { alias, .. } => out.extend(alias.iter().cloned()),
+            ResolvedCondition::TimeRange { alias, .. }
+            | ResolvedCondition::Predicate { alias, .. } => {
+                out.insert(alias.clone());
+            }
+            ResolvedCondition::All(items) | ResolvedCondition::Any(items) => {
+                items.iter().for_each(|c| c.collect_aliases(out))
+            }
+            ResolvedCondition::Not(inner) => inner.collect_aliases(out),
+        }
+    }
+
+    /// Whether a condition tests the elements of an unnested dimension.
+    pub fn tests_elements(&self) -> bool {
+        match self {
+            ResolvedCondition::Predicate { expr, .. } => {
+                matches!(expr, SqlExpr::AnyElement { .. })
+            }
+            ResolvedCondition::All(items) | ResolvedCondition::Any(items) => {
+                items.iter().any(Self::tests_elements)
+            }
+            ResolvedCondition::Not(inner) => inner.tests_elements(),
+            ResolvedCondition::Leaf { .. } | ResolvedCondition::TimeRange { .. } => false,
+        }
+    }
+}
+
+impl ResolvedFilter {
+    /// All table aliases referenced by this filter.
+    pub fn aliases(&self) -> std::collections::HashSet<String> {
+        let mut out = std::collections::HashSet::new();
+        self.condition.collect_aliases(&mut out);
+        out
+    }
+}
+
 /// All resolved components needed to build a query.
 #[derive(Clone, Debug)]
 pub struct QueryComponents {
@@ -126,6 +222,9 @@ pub struct QueryComponents {
     pub base_table: TableRef,
     pub base_semantic_table: SemanticTable,
     pub dimensions: Vec<ResolvedDimension>,
+    /// Arrays of the requested `unnest` dimensions. Filters on other
+    /// unnested dimensions test the elements without adding rows.
+    pub unnests: Vec<ResolvedUnnest>,
     pub measures: Vec<ResolvedMeasure>,
     pub base_measure_exprs: HashMap<String, SqlExpr>,
     pub filters: Vec<ResolvedFilter>,
@@ -134,6 +233,8 @@ pub struct QueryComponents {
     pub offset: Option<u64>,
     pub alias_to_table: HashMap<String, SemanticTable>,
     pub join_lookup: HashMap<String, FlowJoin>,
+    /// Access policy predicate per table alias, applied wherever the table is read.
+    pub access_filters: HashMap<String, SqlExpr>,
 }
 
 /// Resolve all components from a query request.
@@ -141,6 +242,7 @@ pub fn resolve_components(
     flow: &SemanticFlow,
     registry: &FlowRegistry,
     request: &QueryRequest,
+    context: &QueryContext,
     supports_filtered_aggregates: bool,
 ) -> Result<QueryComponents> {
     let alias_to_table_refs = build_alias_map(flow, registry)?;
@@ -164,8 +266,16 @@ pub fn resolve_components(
         .map(|j| (j.alias.clone(), j.clone()))
         .collect();
 
+    let access_filters: HashMap<String, SqlExpr> = alias_to_table_refs
+        .iter()
+        .filter_map(|(alias, table)| {
+            crate::policy::row_filter(table, context)
+                .map(|filter| (alias.clone(), expr_to_sql(&filter, alias)))
+        })
+        .collect();
+
     // Resolve dimensions
-    let dimensions =
+    let (dimensions, unnests) =
         resolve_dimensions_from_request(request, flow, registry, &alias_to_table_refs)?;
 
     // Resolve measures
@@ -178,22 +288,20 @@ pub fn resolve_components(
     )?;
 
     // Resolve filters
-    let filters = resolve_filters_from_request(request, flow, registry, &alias_to_table_refs)?;
+    let filters =
+        resolve_filters_from_request(request, flow, registry, &alias_to_table_refs, &unnests)?;
 
     // Resolve order items
     let order = resolve_order_from_request(request, flow, registry, &alias_to_table_refs)?;
 
-    let base_table = TableRef {
-        name: base_semantic_table.table.clone(),
-        alias: Some(base_alias.clone()),
-        subquery: None,
-    };
+    let base_table = semantic_table_ref(base_semantic_table, &base_alias);
 
     Ok(QueryComponents {
         base_alias,
         base_table,
         base_semantic_table: (*base_semantic_table).clone(),
         dimensions,
+        unnests,
         measures,
         base_measure_exprs,
         filters,
@@ -202,6 +310,7 @@ pub fn resolve_components(
         offset: request.offset.map(|v| v as u64),
         alias_to_table,
         join_lookup,
+        access_filters,
     })
 }
 
@@ -210,18 +319,112 @@ fn resolve_dimensions_from_request(
     flow: &SemanticFlow,
     registry: &FlowRegistry,
     alias_to_table: &HashMap<String, &SemanticTable>,
-) -> Result<Vec<ResolvedDimension>> {
+) -> Result<(Vec<ResolvedDimension>, Vec<ResolvedUnnest>)> {
+    for (dim_name, spec) in &request.bins {
+        let Some(requested) = request.dimensions.iter().find(|d| d.field == *dim_name) else {
+            return Err(SemaflowError::Validation(format!(
+                "bin specified for dimension '{dim_name}' which is not in the requested dimensions"
+            )));
+        };
+        if requested.grain.is_some() {
+            return Err(SemaflowError::Validation(format!(
+                "dimension '{dim_name}' cannot have both a bin and a time grain"
+            )));
+        }
+        spec.validate().map_err(|e| {
+            SemaflowError::Validation(format!("invalid bin for dimension '{dim_name}': {e}"))
+        })?;
+    }
+
+    let zone = request.timezone.as_deref().or(flow.timezone.as_deref());
     let mut resolved = Vec::new();
-    for dim_name in &request.dimensions {
-        let (_table, alias, dimension) =
-            resolve_dimension(dim_name, flow, registry, alias_to_table)?;
+    let mut unnests: Vec<ResolvedUnnest> = Vec::new();
+    for requested in &request.dimensions {
+        let dim_name = &requested.field;
+        let (alias, expr) = match &requested.grain {
+            Some(grain) => {
+                let (table, alias, column) =
+                    resolve_time_dimension(dim_name, flow, alias_to_table)?;
+                check_grain(dim_name, grain, table)?;
+                let dimension = resolve_dimension(dim_name, flow, registry, alias_to_table)
+                    .ok()
+                    .map(|(_, _, d)| d);
+                let expr = Expr::Func {
+                    func: Function::DateTrunc(grain.clone()),
+                    args: vec![column],
+                };
+                let zone = truncation_zone(zone, table, dimension);
+                let expr = expr_to_local_sql(&expr, &alias, zone);
+                (alias, expr)
+            }
+            None => {
+                let (table, alias, dimension) =
+                    resolve_dimension(dim_name, flow, registry, alias_to_table)?;
+                check_dimension_grains(dim_name, dimension, table)?;
+                let zone = truncation_zone(zone, table, Some(dimension));
+                let expr = if dimension.unnest {
+                    let element = unnest_alias(&alias, dim_name);
+                    if !unnests.iter().any(|u| u.unnest.alias == element) {
+                        unnests.push(ResolvedUnnest {
+                            alias: alias.clone(),
+                            unnest: Unnest {
+                                expr: expr_to_local_sql(&dimension.expr, &alias, zone),
+                                alias: element,
+                            },
+                        });
+                    }
+                    unnest_element(&alias, dim_name)
+                } else {
+                    expr_to_local_sql(&dimension.labelled_expr(), &alias, zone)
+                };
+                let expr = match request.bins.get(dim_name) {
+                    Some(spec) => SqlExpr::Function {
+                        func: Function::Bucket(spec.clone()),
+                        args: vec![expr],
+                    },
+                    None => expr,
+                };
+                (alias, expr)
+            }
+        };
         resolved.push(ResolvedDimension {
             name: dim_name.clone(),
-            alias: alias.clone(),
-            expr: expr_to_sql(&dimension.expr, &alias),
+            alias,
+            expr,
         });
     }
-    Ok(resolved)
+    Ok((resolved, unnests))
+}
+
+/// Reject grains finer than the table's `smallest_time_grain`.
+pub(crate) fn check_grain(dim_name: &str, grain: &TimeGrain, table: &SemanticTable) -> Result<()> {
+    if let Some(smallest) = &table.smallest_time_grain {
+        if grain < smallest {
+            return Err(SemaflowError::Validation(format!(
+                "grain '{}' for {dim_name} is finer than the smallest_time_grain '{}' of table '{}'; \
+                 use '{}' or coarser",
+                grain_to_str(grain),
+                grain_to_str(smallest),
+                table.name,
+                grain_to_str(smallest)
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Reject a dimension truncating time finer than its table's
+/// `smallest_time_grain`.
+pub(crate) fn check_dimension_grains(
+    dim_name: &str,
+    dimension: &Dimension,
+    table: &SemanticTable,
+) -> Result<()> {
+    let mut grains = Vec::new();
+    collect_time_grains(&dimension.expr, &mut grains);
+    grains
+        .iter()
+        .try_for_each(|grain| check_grain(dim_name, grain, table))
 }
 
 fn resolve_measures_from_request(
@@ -276,6 +479,28 @@ fn resolve_measures_from_request(
         }
     }
 
+    // Measures of joined tables inherit their table's default filters; the
+    // base table's are applied to every row instead (see resolve_default_filters)
+    if !request.ignore_default_filters {
+        for m in &mut measures {
+            if m.alias == flow.base_table.alias || !m.measure.is_simple() {
+                continue;
+            }
+            if let Some(table) = alias_to_table.get(&m.alias) {
+                m.measure.filter = table
+                    .default_filters
+                    .iter()
+                    .cloned()
+                    .chain(m.measure.filter.as_ref().map(normalize_freeform))
+                    .reduce(|left, right| Expr::Binary {
+                        op: BinaryOp::And,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    });
+            }
+        }
+    }
+
     // Build base measure expressions for simple measures
     let mut base_measure_exprs: HashMap<String, SqlExpr> = HashMap::new();
     for m in &mut measures {
@@ -344,7 +569,8 @@ fn resolve_measures_from_request(
             };
 
             // Generate SQL from the formula AST
-            let formula_expr = formula_to_sql(&ast, &m.alias, &mut resolver)?;
+            let formula_expr =
+                formula_to_sql(&ast, &m.alias, supports_filtered_aggregates, &mut resolver)?;
             m.base_expr = Some(formula_expr.clone());
 
             // Insert into base_measure_exprs
@@ -366,23 +592,201 @@ fn resolve_filters_from_request(
     flow: &SemanticFlow,
     registry: &FlowRegistry,
     alias_to_table: &HashMap<String, &SemanticTable>,
+    unnests: &[ResolvedUnnest],
 ) -> Result<Vec<ResolvedFilter>> {
+    let zone = request.timezone.as_deref().or(flow.timezone.as_deref());
     let mut resolved = Vec::new();
-    for filter in &request.filters {
-        let (expr, kind, alias) =
-            resolve_field_expression(&filter.field, flow, registry, alias_to_table)?;
-        if matches!(kind, FieldKind::Measure) {
+    // The flow's default filters resolve like the request's own
+    let defaults = if request.ignore_default_filters {
+        &[][..]
+    } else {
+        &flow.default_filters[..]
+    };
+    // Flow segments resolve like request filters, table segments like the
+    // tables' default filters
+    let mut filters: Vec<&FilterExpr> = request.filters.iter().chain(defaults).collect();
+    let mut table_segments = Vec::new();
+    for name in &request.segments {
+        match resolve_segment(name, flow, alias_to_table)? {
+            Segment::Flow(filter) => filters.push(filter),
+            Segment::Table { alias, expr } => table_segments.push(ResolvedFilter {
+                condition: ResolvedCondition::Predicate {
+                    expr: expr_to_sql(expr, &alias),
+                    alias: alias.clone(),
+                },
+                alias: Some(alias),
+            }),
+        }
+    }
+    for filter in filters {
+        let condition = resolve_condition(filter, flow, registry, alias_to_table, zone, unnests)?;
+        let aliases = {
+            let mut out = std::collections::HashSet::new();
+            condition.collect_aliases(&mut out);
+            out
+        };
+        let alias = if aliases.len() == 1 {
+            aliases.into_iter().next()
+        } else {
+            None
+        };
+        resolved.push(ResolvedFilter { condition, alias });
+    }
+    resolved.extend(table_segments);
+    if let Some(range) = &request.time_filter {
+        resolved.push(resolve_time_filter(range, flow, alias_to_table)?);
+    }
+    if !request.ignore_default_filters {
+        resolved.extend(resolve_default_filters(flow, alias_to_table));
+    }
+    Ok(resolved)
+}
+
+/// Row filters from the base table's `default_filters`, applied like request
+/// filters. Joined tables' defaults filter their measures instead, so a LEFT
+/// join keeps its unmatched rows.
+fn resolve_default_filters(
+    flow: &SemanticFlow,
+    alias_to_table: &HashMap<String, &SemanticTable>,
+) -> Vec<ResolvedFilter> {
+    let alias = &flow.base_table.alias;
+    let Some(table) = alias_to_table.get(alias) else {
+        return Vec::new();
+    };
+    table
+        .default_filters
+        .iter()
+        .map(|filter| ResolvedFilter {
+            condition: ResolvedCondition::Predicate {
+                expr: expr_to_sql(filter, alias),
+                alias: alias.clone(),
+            },
+            alias: Some(alias.clone()),
+        })
+        .collect()
+}
+
+/// Apply the request's relative date range to the base table's time dimension.
+fn resolve_time_filter(
+    range: &RelativeDateRange,
+    flow: &SemanticFlow,
+    alias_to_table: &HashMap<String, &SemanticTable>,
+) -> Result<ResolvedFilter> {
+    let alias = flow.base_table.alias.clone();
+    let table = alias_to_table
+        .get(&alias)
+        .ok_or_else(|| SemaflowError::Validation(format!("missing base table alias {alias}")))?;
+    let column = table.time_dimension.clone().ok_or_else(|| {
+        SemaflowError::Validation(format!(
+            "time_filter requires a time_dimension on base table '{}'",
+            table.name
+        ))
+    })?;
+    // Dimensions exposing the time column tell us whether it holds timestamps
+    let as_timestamp = table
+        .dimensions
+        .iter()
+        .filter(|(name, dim)| {
+            **name == column || matches!(&dim.expr, Expr::Column { column: c } if *c == column)
+        })
+        .filter_map(|(_, dim)| dim.data_type.as_deref())
+        .any(|t| {
+            let t = t.to_ascii_lowercase();
+            t.starts_with("timestamp") || t.starts_with("datetime")
+        });
+    let condition = ResolvedCondition::TimeRange {
+        range: range.clone(),
+        expr: SqlExpr::Column {
+            table: Some(alias.clone()),
+            name: column,
+        },
+        as_timestamp,
+        alias: alias.clone(),
+    };
+    Ok(ResolvedFilter {
+        condition,
+        alias: Some(alias),
+    })
+}
+
+fn resolve_condition(
+    filter: &FilterExpr,
+    flow: &SemanticFlow,
+    registry: &FlowRegistry,
+    alias_to_table: &HashMap<String, &SemanticTable>,
+    zone: Option<&str>,
+    unnests: &[ResolvedUnnest],
+) -> Result<ResolvedCondition> {
+    let resolve_all = |items: &[FilterExpr]| {
+        if items.is_empty() {
             return Err(SemaflowError::Validation(
-                "filters on measures are not supported (row-level filters only)".to_string(),
+                "filter groups must contain at least one filter".to_string(),
             ));
         }
-        resolved.push(ResolvedFilter {
-            filter: filter.clone(),
-            expr,
+        items
+            .iter()
+            .map(|item| resolve_condition(item, flow, registry, alias_to_table, zone, unnests))
+            .collect::<Result<Vec<_>>>()
+    };
+    let filter = match filter {
+        FilterExpr::Condition(filter) => filter,
+        FilterExpr::All { all } => return Ok(ResolvedCondition::All(resolve_all(all)?)),
+        FilterExpr::Any { any } => return Ok(ResolvedCondition::Any(resolve_all(any)?)),
+        FilterExpr::Not { not } => {
+            return Ok(ResolvedCondition::Not(Box::new(resolve_condition(
+                not,
+                flow,
+                registry,
+                alias_to_table,
+                zone,
+                unnests,
+            )?)))
+        }
+    };
+    let (expr, kind, alias) =
+        resolve_field_expression(&filter.field, flow, registry, alias_to_table, zone)?;
+    if matches!(kind, FieldKind::Measure) {
+        return Err(SemaflowError::Validation(
+            "filters on measures are not supported (row-level filters only)".to_string(),
+        ));
+    }
+    let (table, dim_alias, dimension) =
+        resolve_dimension(&filter.field, flow, registry, alias_to_table)?;
+    if dimension.labels.is_empty() {
+        // Labelled dimensions are filtered by their labels, not stored values
+        check_filter_value(filter, dimension)?;
+    }
+    let filter = if dimension.is_boolean() {
+        normalize_boolean_filter(filter)?
+    } else {
+        filter.clone()
+    };
+    if let Some(filter) = unlabel_filter(&filter, dimension).filter(|_| !dimension.unnest) {
+        // Compare the stored values rather than decoding every row
+        let zone = truncation_zone(zone, table, Some(dimension));
+        return Ok(ResolvedCondition::Leaf {
+            filter,
+            expr: expr_to_local_sql(&dimension.expr, &dim_alias, zone),
             alias,
         });
     }
-    Ok(resolved)
+    let element = unnest_alias(&dim_alias, &filter.field);
+    if dimension.unnest && !unnests.iter().any(|u| u.unnest.alias == element) {
+        // Not grouped by: keep one row per array, with a matching element
+        return Ok(ResolvedCondition::Predicate {
+            expr: SqlExpr::AnyElement {
+                expr: Box::new(expr_to_sql(&dimension.expr, &dim_alias)),
+                alias: element,
+                condition: Box::new(render_filter_expr(expr, &filter)),
+            },
+            alias: dim_alias,
+        });
+    }
+    Ok(ResolvedCondition::Leaf {
+        filter,
+        expr,
+        alias,
+    })
 }
 
 fn resolve_order_from_request(
@@ -391,10 +795,11 @@ fn resolve_order_from_request(
     registry: &FlowRegistry,
     alias_to_table: &HashMap<String, &SemanticTable>,
 ) -> Result<Vec<OrderItem>> {
+    let zone = request.timezone.as_deref().or(flow.timezone.as_deref());
     let mut order_items = Vec::new();
     for item in &request.order {
         let (expr, _, _alias) =
-            resolve_field_expression(&item.column, flow, registry, alias_to_table)?;
+            resolve_field_expression(&item.column, flow, registry, alias_to_table, zone)?;
         order_items.push(OrderItem {
             expr,
             direction: item.direction.clone(),
@@ -417,9 +822,8 @@ impl QueryComponents {
     pub fn joined_filter_aliases(&self) -> std::collections::HashSet<String> {
         self.filters
             .iter()
-            .filter_map(|f| f.alias.as_ref())
-            .filter(|a| *a != &self.base_alias)
-            .cloned()
+            .flat_map(|f| f.aliases())
+            .filter(|a| a != &self.base_alias)
             .collect()
     }
 
@@ -451,10 +855,20 @@ impl QueryComponents {
             .any(|f| f.alias.as_ref() != Some(&self.base_alias))
     }
 
+    /// Check if any filter group mixes conditions from different tables.
+    pub fn has_cross_table_filters(&self) -> bool {
+        self.filters.iter().any(|f| f.aliases().len() > 1)
+    }
+
     /// Check if there are any joins in the flow.
     pub fn has_joins(&self) -> bool {
         !self.join_lookup.is_empty()
     }
+
+    /// Check if any requested or filtered dimension is unnested.
+    pub fn uses_unnest(&self) -> bool {
+        !self.unnests.is_empty() || self.filters.iter().any(|f| f.condition.tests_elements())
+    }
 }
 
 /// Extract the unqualified name from a potentially qualified name like "alias.column".
@@ -0,0 +1,126 @@
+//! Collection of the deprecated fields a request uses, for
+//! [`crate::registry::FlowRegistry::deprecations`].
+
+use std::collections::HashMap;
+
+use crate::deprecation::{DeprecatedKind, DeprecationWarning};
+use crate::flows::{Deprecation, FilterExpr, QueryRequest, SemanticFlow, SemanticTable};
+use crate::registry::FlowRegistry;
+
+use super::resolve::{build_alias_map, parse_qualified, resolve_dimension, resolve_measure};
+
+pub(crate) fn collect_deprecations(
+    registry: &FlowRegistry,
+    request: &QueryRequest,
+) -> Vec<DeprecationWarning> {
+    let Some(flow) = registry.get_flow(&request.flow) else {
+        return Vec::new();
+    };
+    let mut warnings = Vec::new();
+    if let Some(deprecation) = &flow.deprecated {
+        warnings.push(DeprecationWarning {
+            kind: DeprecatedKind::Flow,
+            flow: flow.name.clone(),
+            name: flow.name.clone(),
+            deprecation: deprecation.clone(),
+        });
+    }
+    let Ok(alias_map) = build_alias_map(flow, registry) else {
+        return warnings;
+    };
+    let mut collector = Collector {
+        flow,
+        registry,
+        alias_map: &alias_map,
+        warnings,
+    };
+
+    for requested in &request.dimensions {
+        collector.dimension(&requested.field);
+    }
+    for measure in &request.measures {
+        collector.measure(measure);
+    }
+    for filter in &request.filters {
+        collector.filter(filter);
+    }
+    for item in &request.order {
+        if !collector.dimension(&item.column) {
+            collector.measure(&item.column);
+        }
+    }
+    collector.warnings
+}
+
+struct Collector<'a> {
+    flow: &'a SemanticFlow,
+    registry: &'a FlowRegistry,
+    alias_map: &'a HashMap<String, &'a SemanticTable>,
+    warnings: Vec<DeprecationWarning>,
+}
+
+impl Collector<'_> {
+    /// Record `name` if it is a deprecated dimension; whether it resolved.
+    fn dimension(&mut self, name: &str) -> bool {
+        match resolve_dimension(name, self.flow, self.registry, self.alias_map) {
+            Ok((_, alias, dimension)) => {
+                self.push(
+                    DeprecatedKind::Dimension,
+                    name,
+                    &alias,
+                    &dimension.deprecated,
+                );
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn measure(&mut self, name: &str) {
+        if let Ok((_, alias, measure)) =
+            resolve_measure(name, self.flow, self.registry, self.alias_map)
+        {
+            self.push(DeprecatedKind::Measure, name, &alias, &measure.deprecated);
+        }
+    }
+
+    fn filter(&mut self, filter: &FilterExpr) {
+        match filter {
+            FilterExpr::Condition(condition) => {
+                self.dimension(&condition.field);
+            }
+            FilterExpr::All { all: items } | FilterExpr::Any { any: items } => {
+                for item in items {
+                    self.filter(item);
+                }
+            }
+            FilterExpr::Not { not } => self.filter(not),
+        }
+    }
+
+    fn push(
+        &mut self,
+        kind: DeprecatedKind,
+        requested: &str,
+        alias: &str,
+        deprecation: &Option<Deprecation>,
+    ) {
+        let Some(deprecation) = deprecation else {
+            return;
+        };
+        let field = parse_qualified(requested).map_or(requested, |(_, field)| field);
+        let name = format!("{}.{field}", self.flow.qualifier(alias));
+        let seen = self
+            .warnings
+            .iter()
+            .any(|warning| warning.kind == kind && warning.name == name);
+        if !seen {
+            self.warnings.push(DeprecationWarning {
+                kind,
+                flow: self.flow.name.clone(),
+                name,
+                deprecation: deprecation.clone(),
+            });
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Derived tables: semantic tables reading the result of a SQL query, of a
+//! request on another flow, or of a union of other tables instead of a
+//! physical table.
+//!
+//! A table's `sql` is rendered as a parenthesized subquery wherever the
+//! table appears in FROM or JOIN. A table's `query` is first built into SQL
+//! for the same dialect and user as the outer request, and a table's `union`
+//! into a `UNION ALL` of its members; both are then read like `sql`.
+
+use std::borrow::Cow;
+
+use crate::dialect::Dialect;
+use crate::error::{Result, SemaflowError};
+use crate::flows::{SemanticFlow, TableUnion};
+use crate::policy::QueryContext;
+use crate::registry::FlowRegistry;
+
+use super::SqlBuilder;
+
+/// The registry with the `sql` of every `query` and `union` table read by
+/// `flow` filled in; borrowed unchanged when the flow reads none.
+pub(crate) fn resolve_derived_tables<'a>(
+    builder: &SqlBuilder,
+    flow: &SemanticFlow,
+    registry: &'a FlowRegistry,
+    context: &QueryContext,
+    dialect: &dyn Dialect,
+) -> Result<Cow<'a, FlowRegistry>> {
+    let derived: Vec<&str> = flow_tables(flow)
+        .filter(|name| {
+            registry
+                .get_table(name)
+                .is_some_and(|table| table.query.is_some() || table.union.is_some())
+        })
+        .collect();
+    if derived.is_empty() {
+        return Ok(Cow::Borrowed(registry));
+    }
+    if let Some(cycle) = find_derived_cycle(registry, &flow.name) {
+        return Err(SemaflowError::Validation(format!(
+            "derived tables form a cycle between flows: {}",
+            cycle.join(" -> ")
+        )));
+    }
+
+    let mut resolved = registry.clone();
+    for name in derived {
+        let Some(table) = registry.get_table(name) else {
+            continue;
+        };
+        let sql = match (&table.query, &table.union) {
+            (Some(query), _) => builder.build_with_context(registry, query, context, dialect)?,
+            (None, Some(union)) => union_sql(registry, name, union, dialect)?,
+            (None, None) => continue,
+        };
+        if let Some(table) = resolved.tables.get_mut(name) {
+            table.sql = Some(sql);
+        }
+    }
+    Ok(Cow::Owned(resolved))
+}
+
+/// `UNION ALL` of the members of union table `name`, each selecting the
+/// union's columns in order under their union names.
+pub(crate) fn union_sql(
+    registry: &FlowRegistry,
+    name: &str,
+    union: &TableUnion,
+    dialect: &dyn Dialect,
+) -> Result<String> {
+    if union.columns.is_empty() || union.tables.is_empty() {
+        return Err(SemaflowError::Validation(format!(
+            "union table {name} needs columns and at least one member table"
+        )));
+    }
+    let branches = union
+        .tables
+        .iter()
+        .map(|member| {
+            let table = registry.get_table(&member.semantic_table).ok_or_else(|| {
+                SemaflowError::Validation(format!(
+                    "union table {name} appends unknown table {}",
+                    member.semantic_table
+                ))
+            })?;
+            if table.query.is_some() || table.union.is_some() {
+                return Err(SemaflowError::Validation(format!(
+                    "union table {name} appends {}, which reads a query or union; \
+                     members must be physical or sql tables",
+                    table.name
+                )));
+            }
+            let columns: Vec<String> = union
+                .columns
+                .iter()
+                .map(|column| {
+                    let alias = dialect.quote_ident(column);
+                    match member.source_column(column) {
+                        Some(source) if source == column => alias,
+                        Some(source) => format!("{} AS {alias}", dialect.quote_ident(source)),
+                        None => format!("NULL AS {alias}"),
+                    }
+                })
+                .collect();
+            let from = match &table.sql {
+                Some(sql) => format!("({}) {}", sql.trim(), dialect.quote_ident(&table.name)),
+                None => dialect.qualify_table(&table.table),
+            };
+            Ok(format!("SELECT {} FROM {from}", columns.join(", ")))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(branches.join(" UNION ALL "))
+}
+
+/// A chain of flows, starting and ending at the same flow, in which each
+/// flow reads a `query` table requesting the next.
+pub(crate) fn find_derived_cycle(registry: &FlowRegistry, flow: &str) -> Option<Vec<String>> {
+    fn visit(registry: &FlowRegistry, flow: &str, path: &mut Vec<String>) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|name| name == flow) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(flow.to_string());
+            return Some(cycle);
+        }
+        let semantic_flow = registry.get_flow(flow)?;
+        path.push(flow.to_string());
+        for name in flow_tables(semantic_flow) {
+            let Some(query) = registry.get_table(name).and_then(|t| t.query.as_ref()) else {
+                continue;
+            };
+            if let Some(cycle) = visit(registry, &query.flow, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        None
+    }
+    visit(registry, flow, &mut Vec::new())
+}
+
+fn flow_tables(flow: &SemanticFlow) -> impl Iterator<Item = &str> {
+    std::iter::once(flow.base_table.semantic_table.as_str())
+        .chain(flow.joins.values().map(|join| join.semantic_table.as_str()))
+}
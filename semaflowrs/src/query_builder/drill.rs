@@ -0,0 +1,82 @@
+//! Drilling along dimension hierarchies (`drill` requests).
+//!
+//! A semantic table's `hierarchies` list dimensions from the coarsest level
+//! to the finest. `drill: {dimension, level}` replaces the requested
+//! `dimension` with every level from the top of its hierarchy down to
+//! `level`, dropping the hierarchy's other requested levels, so the same
+//! request drills down (`country` to `city`) or back up. New levels are
+//! qualified like `dimension`.
+
+use std::borrow::Cow;
+
+use crate::error::{Result, SemaflowError};
+use crate::flows::{DimensionRequest, QueryRequest, SemanticFlow};
+use crate::registry::FlowRegistry;
+
+use super::resolve::{build_alias_map, parse_qualified, resolve_dimension};
+
+/// The request with its `drill` applied to the requested dimensions.
+pub(crate) fn apply_drill<'a>(
+    flow: &SemanticFlow,
+    registry: &FlowRegistry,
+    request: &'a QueryRequest,
+) -> Result<Cow<'a, QueryRequest>> {
+    let Some(drill) = &request.drill else {
+        return Ok(Cow::Borrowed(request));
+    };
+    let alias_map = build_alias_map(flow, registry)?;
+    let (table, alias, _) = resolve_dimension(&drill.dimension, flow, registry, &alias_map)?;
+    let field = |name: &str| parse_qualified(name).map_or(name, |(_, f)| f).to_string();
+    let from = field(&drill.dimension);
+    let Some((name, levels)) = table
+        .hierarchies
+        .iter()
+        .find(|(_, levels)| levels.contains(&from))
+    else {
+        return Err(SemaflowError::Validation(format!(
+            "cannot drill from {}: it is not a level of any hierarchy on table {}",
+            drill.dimension, table.name
+        )));
+    };
+    let to = field(&drill.level);
+    let Some(depth) = levels.iter().position(|level| *level == to) else {
+        return Err(SemaflowError::Validation(format!(
+            "cannot drill to {}: hierarchy {name} has levels {}",
+            drill.level,
+            levels.join(", ")
+        )));
+    };
+    if !request
+        .dimensions
+        .iter()
+        .any(|d| d.field == drill.dimension)
+    {
+        return Err(SemaflowError::Validation(format!(
+            "cannot drill from {}: it is not a requested dimension",
+            drill.dimension
+        )));
+    }
+
+    let qualify = |level: &str| match parse_qualified(&drill.dimension) {
+        Some(_) => format!("{alias}.{level}"),
+        None => level.to_string(),
+    };
+    let in_hierarchy = |requested: &DimensionRequest| {
+        requested.grain.is_none()
+            && resolve_dimension(&requested.field, flow, registry, &alias_map)
+                .is_ok_and(|(_, a, _)| a == alias && levels.contains(&field(&requested.field)))
+    };
+    let mut dimensions = Vec::new();
+    for requested in &request.dimensions {
+        if requested.field == drill.dimension {
+            dimensions.extend(levels[..=depth].iter().map(|l| qualify(l).into()));
+        } else if !in_hierarchy(requested) {
+            dimensions.push(requested.clone());
+        }
+    }
+    Ok(Cow::Owned(QueryRequest {
+        dimensions,
+        drill: None,
+        ..request.clone()
+    }))
+}
@@ -0,0 +1,124 @@
+//! Dry-run validation of query requests.
+//!
+//! [`SqlBuilder::validate_request`] checks a request
+//! against the semantic model without rendering or executing SQL, so an API
+//! can reject bad requests before they reach the warehouse. Unlike the
+//! builder, which stops at the first problem, it reports every unknown or
+//! ambiguous field, mistyped filter value and unsupported grain at once.
+
+use std::collections::HashMap;
+
+use crate::error::SemaflowError;
+use crate::flows::{FilterExpr, QueryRequest, SemanticFlow, SemanticTable};
+use crate::registry::FlowRegistry;
+
+use super::components::{check_dimension_grains, check_grain};
+use super::filters::check_filter_value;
+use super::resolve::{
+    build_alias_map, resolve_dimension, resolve_field_expression, resolve_measure, resolve_segment,
+    resolve_time_dimension, FieldKind,
+};
+use super::SqlBuilder;
+
+pub(crate) fn validate_request(
+    builder: &SqlBuilder,
+    registry: &FlowRegistry,
+    request: &QueryRequest,
+) -> Vec<SemaflowError> {
+    let Some(flow) = registry.get_flow(&request.flow) else {
+        return vec![SemaflowError::UnknownFlow {
+            flow: request.flow.clone(),
+        }];
+    };
+    let alias_map = match build_alias_map(flow, registry) {
+        Ok(map) => map,
+        Err(e) => return vec![e],
+    };
+
+    let mut errors = Vec::new();
+    if let Err(e) = flow.resolve_params(&request.params) {
+        errors.push(SemaflowError::Validation(e));
+    }
+    for requested in &request.dimensions {
+        let checked = match &requested.grain {
+            Some(grain) => resolve_time_dimension(&requested.field, flow, &alias_map)
+                .and_then(|(table, _, _)| check_grain(&requested.field, grain, table)),
+            None => resolve_dimension(&requested.field, flow, registry, &alias_map).and_then(
+                |(table, _, dimension)| check_dimension_grains(&requested.field, dimension, table),
+            ),
+        };
+        errors.extend(checked.err());
+    }
+    for measure in &request.measures {
+        errors.extend(resolve_measure(measure, flow, registry, &alias_map).err());
+    }
+    for filter in &request.filters {
+        check_filter(filter, flow, registry, &alias_map, &mut errors);
+    }
+    for name in &request.segments {
+        errors.extend(resolve_segment(name, flow, &alias_map).err());
+    }
+    for item in &request.order {
+        // Calculations are output columns, not fields of the flow
+        if request.calculations.iter().any(|c| c.name == item.column) {
+            continue;
+        }
+        errors
+            .extend(resolve_field_expression(&item.column, flow, registry, &alias_map, None).err());
+    }
+
+    // Everything else (bins, calculations, join cardinality, formulas) is
+    // checked by planning the query once the fields resolve
+    if errors.is_empty() {
+        errors.extend(builder.explain(registry, request).err());
+    }
+    errors
+}
+
+fn check_filter(
+    filter: &FilterExpr,
+    flow: &SemanticFlow,
+    registry: &FlowRegistry,
+    alias_map: &HashMap<String, &SemanticTable>,
+    errors: &mut Vec<SemaflowError>,
+) {
+    let segment_error = |field: &str| {
+        SemaflowError::Validation(format!(
+            "segments cannot be used as filter fields; request them in `segments`: {field}"
+        ))
+    };
+    let items = match filter {
+        FilterExpr::Condition(filter) => {
+            match resolve_field_expression(&filter.field, flow, registry, alias_map, None) {
+                Ok((_, FieldKind::Measure, _)) => errors.push(SemaflowError::Validation(format!(
+                    "filters on measures are not supported (row-level filters only): {}",
+                    filter.field
+                ))),
+                Ok((_, FieldKind::Segment, _)) => errors.push(segment_error(&filter.field)),
+                Ok((_, FieldKind::Dimension, _)) => {
+                    if let Ok((_, _, dimension)) =
+                        resolve_dimension(&filter.field, flow, registry, alias_map)
+                    {
+                        errors.extend(check_filter_value(filter, dimension).err());
+                    }
+                }
+                Err(_) if resolve_segment(&filter.field, flow, alias_map).is_ok() => {
+                    errors.push(segment_error(&filter.field))
+                }
+                Err(e) => errors.push(e),
+            }
+            return;
+        }
+        FilterExpr::All { all } => all,
+        FilterExpr::Any { any } => any,
+        FilterExpr::Not { not } => return check_filter(not, flow, registry, alias_map, errors),
+    };
+    if items.is_empty() {
+        errors.push(SemaflowError::Validation(
+            "filter groups must contain at least one filter".to_string(),
+        ));
+    }
+    for item in items {
+        check_filter(item, flow, registry, alias_map, errors);
+    }
+}
@@ -0,0 +1,333 @@
+//! Plan diagnostics surfaced alongside generated SQL.
+//!
+//! The planner avoids fanout where it can by pre-aggregating. When a flat
+//! plan still has to join across a one-to-many relationship (for example,
+//! grouping base-table measures by a dimension of a child table), each
+//! measure row can be counted more than once. These diagnostics name the
+//! join responsible so the flow owner can add a `cardinality` hint or
+//! restructure the flow.
+//!
+//! [`LogicalPlan`] records the rest of the planner's decisions - which joins
+//! were pruned, the grain of each pre-aggregation CTE, how each measure is
+//! re-aggregated and where each filter is applied - so the choice of
+//! strategy can be understood without reading the generated SQL.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::deprecation::DeprecationWarning;
+use crate::flows::{FlowJoin, JoinCardinality, JoinType, SemanticFlow};
+
+use super::analysis::infer_cardinality_for_join;
+use super::components::{MeasureStrategy, QueryComponents, ResolvedCondition};
+use super::grain::Cardinality;
+
+/// Query shape chosen by the planner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanStrategy {
+    /// Single SELECT with direct joins.
+    Flat,
+    /// Per-table aggregation CTEs joined at a common grain.
+    PreAggregated,
+    /// Single SELECT re-aggregating one of the flow's summary tables.
+    Aggregate,
+    /// Single SELECT with direct joins, aggregating measures over each
+    /// table's distinct primary keys so repeated rows count once.
+    Symmetric,
+    /// Single SELECT with direct joins, weighting measures read across a
+    /// many-to-many bridge by the bridge's `weight` column.
+    Weighted,
+}
+
+/// A join in the generated query that can multiply measure rows.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FanoutDiagnostic {
+    /// Alias of the joined table.
+    pub join_alias: String,
+    /// Semantic table behind the join.
+    pub semantic_table: String,
+    /// Alias the join attaches to.
+    pub from_alias: String,
+    /// Cardinality of the join from `from_alias` to `join_alias`.
+    pub cardinality: JoinCardinality,
+    /// `true` when the cardinality came from a hint on the join, `false` when inferred.
+    pub hinted: bool,
+    /// Strategy the planner used for the query.
+    pub strategy: PlanStrategy,
+    /// Human-readable explanation.
+    pub message: String,
+}
+
+/// Generated SQL together with the planner's decisions.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryExplain {
+    pub sql: String,
+    pub strategy: PlanStrategy,
+    pub diagnostics: Vec<FanoutDiagnostic>,
+    pub plan: LogicalPlan,
+    /// Deprecated flow and fields the request uses.
+    pub deprecations: Vec<DeprecationWarning>,
+}
+
+/// The planner's decisions for a request, independent of the SQL text.
+///
+/// `Display` renders it as indented text for logs and terminals.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogicalPlan {
+    pub flow: String,
+    pub strategy: PlanStrategy,
+    /// Alias of the flow's base table.
+    pub base_alias: String,
+    /// Every join of the flow, in definition order, and whether the query uses it.
+    pub joins: Vec<JoinPlan>,
+    /// Aggregation CTEs of a pre-aggregated plan; empty for flat plans.
+    pub ctes: Vec<CtePlan>,
+    /// Requested measures and the measures they are derived from.
+    pub measures: Vec<MeasurePlan>,
+    /// Request filters, table default filters and the time filter.
+    pub filters: Vec<FilterPlan>,
+    /// The flow aggregate read by an aggregate plan.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregate: Option<String>,
+}
+
+/// A flow join and whether the planner kept it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JoinPlan {
+    pub alias: String,
+    pub semantic_table: String,
+    /// Alias the join attaches to.
+    pub to_alias: String,
+    pub join_type: JoinType,
+    /// `false` when the join was pruned because no requested field needs it.
+    pub included: bool,
+}
+
+/// A per-table aggregation CTE of a pre-aggregated plan.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CtePlan {
+    /// Name of the CTE in the generated SQL.
+    pub name: String,
+    /// Alias of the table it aggregates.
+    pub alias: String,
+    /// Columns the table is grouped by before the CTEs are joined.
+    pub grain: Vec<String>,
+}
+
+/// How a measure is computed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MeasurePlan {
+    pub name: String,
+    /// Alias of the table the measure is defined on.
+    pub alias: String,
+    /// `false` for measures only computed as inputs of derived measures.
+    pub requested: bool,
+    /// Re-aggregation strategy; `None` in flat plans, which aggregate directly.
+    pub strategy: Option<MeasureStrategy>,
+}
+
+/// A filter and the part of the query it is applied in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FilterPlan {
+    /// Readable form of the filter, e.g. `c.country == "US"`.
+    pub filter: String,
+    pub placement: FilterPlacement,
+}
+
+/// Where a filter is applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FilterPlacement {
+    /// WHERE clause of a flat query.
+    Where,
+    /// Inside an aggregation CTE, before pre-aggregation.
+    Cte { name: String },
+    /// WHERE clause of the query joining the CTEs, after pre-aggregation.
+    FinalQuery,
+}
+
+impl LogicalPlan {
+    /// A plan for `flow` with every join marked as pruned and no CTEs,
+    /// measures or filters; the planner fills in the rest.
+    pub(crate) fn new(flow: &SemanticFlow, strategy: PlanStrategy) -> Self {
+        Self {
+            flow: flow.name.clone(),
+            strategy,
+            base_alias: flow.base_table.alias.clone(),
+            joins: flow
+                .joins
+                .values()
+                .map(|join| JoinPlan {
+                    alias: join.alias.clone(),
+                    semantic_table: join.semantic_table.clone(),
+                    to_alias: join.to_table.clone(),
+                    join_type: join.join_type.clone(),
+                    included: false,
+                })
+                .collect(),
+            ctes: Vec::new(),
+            measures: Vec::new(),
+            filters: Vec::new(),
+            aggregate: None,
+        }
+    }
+
+    pub(crate) fn include_join(&mut self, alias: &str) {
+        if let Some(join) = self.joins.iter_mut().find(|j| j.alias == alias) {
+            join.included = true;
+        }
+    }
+
+    pub(crate) fn add_measures(&mut self, components: &QueryComponents) {
+        let pre_aggregated = self.strategy == PlanStrategy::PreAggregated;
+        self.measures = components
+            .measures
+            .iter()
+            .map(|m| MeasurePlan {
+                name: m.name.clone(),
+                alias: m.alias.clone(),
+                requested: m.requested,
+                strategy: pre_aggregated.then(|| m.strategy.clone()),
+            })
+            .collect();
+    }
+
+    pub(crate) fn add_filter(&mut self, condition: &ResolvedCondition, placement: FilterPlacement) {
+        self.filters.push(FilterPlan {
+            filter: describe_condition(condition),
+            placement,
+        });
+    }
+}
+
+fn describe_condition(condition: &ResolvedCondition) -> String {
+    let group = |conditions: &[ResolvedCondition], op: &str| {
+        let parts: Vec<String> = conditions.iter().map(describe_condition).collect();
+        format!("({})", parts.join(op))
+    };
+    match condition {
+        ResolvedCondition::Leaf { filter, .. } => {
+            let op = serde_name(&filter.op);
+            if filter.value.is_null() {
+                format!("{} {op}", filter.field)
+            } else {
+                format!("{} {op} {}", filter.field, filter.value)
+            }
+        }
+        ResolvedCondition::TimeRange { alias, .. } => format!("time_filter on {alias}"),
+        ResolvedCondition::Predicate { alias, .. } => format!("default filter of {alias}"),
+        ResolvedCondition::All(conditions) => group(conditions, " AND "),
+        ResolvedCondition::Any(conditions) => group(conditions, " OR "),
+        ResolvedCondition::Not(inner) => format!("NOT {}", describe_condition(inner)),
+    }
+}
+
+/// The serde name of a unit enum variant, e.g. `pre_aggregated`.
+fn serde_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+impl fmt::Display for LogicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "flow {} ({} plan, base {})",
+            self.flow,
+            serde_name(&self.strategy),
+            self.base_alias
+        )?;
+        if let Some(aggregate) = &self.aggregate {
+            writeln!(f, "aggregate: {aggregate}")?;
+        }
+        if !self.joins.is_empty() {
+            writeln!(f, "joins:")?;
+            for join in &self.joins {
+                writeln!(
+                    f,
+                    "  {} join {} ({}) to {}: {}",
+                    serde_name(&join.join_type),
+                    join.alias,
+                    join.semantic_table,
+                    join.to_alias,
+                    if join.included { "included" } else { "pruned" }
+                )?;
+            }
+        }
+        if !self.ctes.is_empty() {
+            writeln!(f, "ctes:")?;
+            for cte in &self.ctes {
+                writeln!(f, "  {} grain [{}]", cte.name, cte.grain.join(", "))?;
+            }
+        }
+        if !self.measures.is_empty() {
+            writeln!(f, "measures:")?;
+            for measure in &self.measures {
+                write!(f, "  {}", measure.name)?;
+                if let Some(strategy) = &measure.strategy {
+                    write!(f, ": {}", serde_name(strategy))?;
+                }
+                if !measure.requested {
+                    write!(f, " (dependency)")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        if !self.filters.is_empty() {
+            writeln!(f, "filters:")?;
+            for filter in &self.filters {
+                let placement = match &filter.placement {
+                    FilterPlacement::Where => "where".to_string(),
+                    FilterPlacement::Cte { name } => format!("cte {name}"),
+                    FilterPlacement::FinalQuery => "final query".to_string(),
+                };
+                writeln!(f, "  {} -> {placement}", filter.filter)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Diagnose joins in a flat plan that fan out the rows its measures aggregate.
+pub(crate) fn flat_fanout_diagnostics(
+    components: &QueryComponents,
+    joins: &[&FlowJoin],
+) -> Vec<FanoutDiagnostic> {
+    let mut diagnostics = Vec::new();
+    for join in joins {
+        // Measures on the many side itself are not multiplied by this join
+        let affected: Vec<&str> = components
+            .measures
+            .iter()
+            .filter(|m| m.requested && m.alias != join.alias)
+            .map(|m| m.name.as_str())
+            .collect();
+        if affected.is_empty() {
+            continue;
+        }
+        let cardinality = match infer_cardinality_for_join(join, components) {
+            Ok(Cardinality::OneToMany) => JoinCardinality::OneToMany,
+            Ok(Cardinality::ManyToMany) => JoinCardinality::ManyToMany,
+            _ => continue,
+        };
+        let message = format!(
+            "join '{}' -> '{}' ({}) is {:?}; measures {:?} may be counted once per matching \
+             '{}' row. Add a `cardinality` hint to the join if this is not intended.",
+            join.to_table, join.alias, join.semantic_table, cardinality, affected, join.alias
+        );
+        diagnostics.push(FanoutDiagnostic {
+            join_alias: join.alias.clone(),
+            semantic_table: join.semantic_table.clone(),
+            from_alias: join.to_table.clone(),
+            cardinality,
+            hinted: join.cardinality.is_some(),
+            strategy: PlanStrategy::Flat,
+            message,
+        });
+    }
+    diagnostics
+}
@@ -1,6 +1,95 @@
-use crate::flows::{Filter, FilterOp};
+use crate::flows::{Dimension, Filter, FilterOp, RelativeDateRange, TimeGrain};
 use crate::sql_ast::{SqlBinaryOperator, SqlExpr};
 
+use super::components::{ResolvedCondition, ResolvedFilter};
+
+/// Render a resolved request filter, passing each leaf's column expression
+/// through `remap` first (e.g. to point it at a CTE).
+pub(crate) fn render_resolved_filter(
+    filter: &ResolvedFilter,
+    remap: &impl Fn(&SqlExpr) -> SqlExpr,
+) -> SqlExpr {
+    render_condition(&filter.condition, remap)
+}
+
+fn render_condition(
+    condition: &ResolvedCondition,
+    remap: &impl Fn(&SqlExpr) -> SqlExpr,
+) -> SqlExpr {
+    let combine = |items: &[ResolvedCondition], op: SqlBinaryOperator| {
+        items
+            .iter()
+            .map(|c| render_condition(c, remap))
+            .reduce(|left, right| SqlExpr::BinaryOp {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            })
+            .expect("filter groups are validated to be non-empty")
+    };
+    match condition {
+        ResolvedCondition::Leaf { filter, expr, .. } => render_filter_expr(remap(expr), filter),
+        ResolvedCondition::TimeRange {
+            range,
+            expr,
+            as_timestamp,
+            ..
+        } => render_time_range(remap(expr), range, *as_timestamp),
+        ResolvedCondition::Predicate { expr, .. } => expr.clone(),
+        ResolvedCondition::All(items) => combine(items, SqlBinaryOperator::And),
+        ResolvedCondition::Any(items) => combine(items, SqlBinaryOperator::Or),
+        ResolvedCondition::Not(inner) => SqlExpr::Not {
+            expr: Box::new(render_condition(inner, remap)),
+        },
+    }
+}
+
+/// Render a relative date range as `start <= expr < end`.
+fn render_time_range(expr: SqlExpr, range: &RelativeDateRange, as_timestamp: bool) -> SqlExpr {
+    let date =
+        |truncate: Option<TimeGrain>, offsets: Vec<(TimeGrain, i64)>| SqlExpr::RelativeDate {
+            truncate,
+            offsets,
+            as_timestamp,
+        };
+    let tomorrow = || date(None, vec![(TimeGrain::Day, 1)]);
+    let (start, end) = match range {
+        RelativeDateRange::Today => (date(None, vec![]), tomorrow()),
+        RelativeDateRange::Yesterday => {
+            (date(None, vec![(TimeGrain::Day, -1)]), date(None, vec![]))
+        }
+        RelativeDateRange::Last { count, unit } => (
+            date(
+                None,
+                vec![(TimeGrain::Day, 1), (unit.clone(), -i64::from(*count))],
+            ),
+            tomorrow(),
+        ),
+        RelativeDateRange::Current(grain) => (
+            date(Some(grain.clone()), vec![]),
+            date(Some(grain.clone()), vec![(grain.clone(), 1)]),
+        ),
+        RelativeDateRange::Previous(grain) => (
+            date(Some(grain.clone()), vec![(grain.clone(), -1)]),
+            date(Some(grain.clone()), vec![]),
+        ),
+        RelativeDateRange::ToDate(grain) => (date(Some(grain.clone()), vec![]), tomorrow()),
+    };
+    SqlExpr::BinaryOp {
+        op: SqlBinaryOperator::And,
+        left: Box::new(SqlExpr::BinaryOp {
+            op: SqlBinaryOperator::Gte,
+            left: Box::new(expr.clone()),
+            right: Box::new(start),
+        }),
+        right: Box::new(SqlExpr::BinaryOp {
+            op: SqlBinaryOperator::Lt,
+            left: Box::new(expr),
+            right: Box::new(end),
+        }),
+    }
+}
+
 pub(crate) fn render_filter_expr(base_expr: SqlExpr, filter: &Filter) -> SqlExpr {
     match filter.op {
         FilterOp::In | FilterOp::NotIn => {
@@ -16,6 +105,14 @@ pub(crate) fn render_filter_expr(base_expr: SqlExpr, filter: &Filter) -> SqlExpr
                 negated: matches!(filter.op, FilterOp::NotIn),
             }
         }
+        FilterOp::IsTrue | FilterOp::IsFalse | FilterOp::IsUnknown => SqlExpr::BooleanTest {
+            expr: Box::new(base_expr),
+            value: match filter.op {
+                FilterOp::IsTrue => Some(true),
+                FilterOp::IsFalse => Some(false),
+                _ => None,
+            },
+        },
         _ => {
             let op = match filter.op {
                 FilterOp::Eq => SqlBinaryOperator::Eq,
@@ -26,7 +123,11 @@ pub(crate) fn render_filter_expr(base_expr: SqlExpr, filter: &Filter) -> SqlExpr
                 FilterOp::Lte => SqlBinaryOperator::Lte,
                 FilterOp::Like => SqlBinaryOperator::Like,
                 FilterOp::ILike => SqlBinaryOperator::ILike,
-                FilterOp::In | FilterOp::NotIn => unreachable!(),
+                FilterOp::In
+                | FilterOp::NotIn
+                | FilterOp::IsTrue
+                | FilterOp::IsFalse
+                | FilterOp::IsUnknown => unreachable!(),
             };
             SqlExpr::BinaryOp {
                 op,
@@ -36,3 +137,154 @@ pub(crate) fn render_filter_expr(base_expr: SqlExpr, filter: &Filter) -> SqlExpr
         }
     }
 }
+
+/// Coerce string booleans (`"true"`, `"False"`) in a filter on a boolean
+/// dimension to real boolean literals, so the SQL compares booleans rather
+/// than the text `'true'`.
+pub(crate) fn normalize_boolean_filter(filter: &Filter) -> crate::error::Result<Filter> {
+    let coerce = |v: &serde_json::Value| -> crate::error::Result<serde_json::Value> {
+        match v {
+            serde_json::Value::Bool(_) | serde_json::Value::Null => Ok(v.clone()),
+            serde_json::Value::String(s) if s.eq_ignore_ascii_case("true") => {
+                Ok(serde_json::Value::Bool(true))
+            }
+            serde_json::Value::String(s) if s.eq_ignore_ascii_case("false") => {
+                Ok(serde_json::Value::Bool(false))
+            }
+            other => Err(crate::error::SemaflowError::Validation(format!(
+                "filter on boolean dimension {} expects true or false, got {other}",
+                filter.field
+            ))),
+        }
+    };
+    let value = match filter.op {
+        FilterOp::IsTrue | FilterOp::IsFalse | FilterOp::IsUnknown => filter.value.clone(),
+        FilterOp::Eq | FilterOp::Neq | FilterOp::In | FilterOp::NotIn => match &filter.value {
+            serde_json::Value::Array(items) => serde_json::Value::Array(
+                items
+                    .iter()
+                    .map(coerce)
+                    .collect::<crate::error::Result<_>>()?,
+            ),
+            other => coerce(other)?,
+        },
+        _ => {
+            return Err(crate::error::SemaflowError::Validation(format!(
+                "operator {:?} is not supported on boolean dimension {}",
+                filter.op, filter.field
+            )))
+        }
+    };
+    Ok(Filter {
+        value,
+        ..filter.clone()
+    })
+}
+
+/// Rewrite an equality or `in` filter on the labels of a dimension with
+/// `labels` into one on the stored values, so it can use the raw column.
+/// `None` when the filter compares other values or operators; it then
+/// applies to the decoded labels.
+pub(crate) fn unlabel_filter(filter: &Filter, dimension: &Dimension) -> Option<Filter> {
+    let value = match (&filter.op, &filter.value) {
+        (FilterOp::Eq | FilterOp::Neq, value) => dimension.unlabel(value)?,
+        (FilterOp::In | FilterOp::NotIn, serde_json::Value::Array(items)) => {
+            serde_json::Value::Array(
+                items
+                    .iter()
+                    .map(|item| dimension.unlabel(item))
+                    .collect::<Option<_>>()?,
+            )
+        }
+        _ => return None,
+    };
+    Some(Filter {
+        value,
+        ..filter.clone()
+    })
+}
+
+/// Kind of value a dimension holds, inferred from its free-form `data_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueKind {
+    Number,
+    Boolean,
+    Text,
+    Temporal,
+}
+
+impl ValueKind {
+    fn of(data_type: &str) -> Option<Self> {
+        let lower = data_type.to_ascii_lowercase();
+        // Drop precision/length arguments, e.g. `decimal(10, 2)` or `varchar(255)`
+        let base = lower.split('(').next().unwrap_or_default().trim();
+        match base {
+            "int" | "integer" | "bigint" | "smallint" | "tinyint" | "hugeint" | "int2" | "int4"
+            | "int8" | "int64" | "ubigint" | "uinteger" | "usmallint" | "utinyint" | "float"
+            | "float4" | "float8" | "float64" | "double" | "double precision" | "decimal"
+            | "numeric" | "bignumeric" | "real" | "number" => Some(ValueKind::Number),
+            "bool" | "boolean" => Some(ValueKind::Boolean),
+            "string" | "text" | "varchar" | "char" | "character" | "character varying"
+            | "bpchar" => Some(ValueKind::Text),
+            "date" | "datetime" | "time" => Some(ValueKind::Temporal),
+            _ if base.starts_with("timestamp") => Some(ValueKind::Temporal),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ValueKind::Number => "number",
+            ValueKind::Boolean => "boolean",
+            ValueKind::Text => "text",
+            ValueKind::Temporal => "date/time string",
+        }
+    }
+
+    fn accepts(self, value: &serde_json::Value) -> bool {
+        match (self, value) {
+            (_, serde_json::Value::Null) => true,
+            (ValueKind::Number, serde_json::Value::Number(_)) => true,
+            (ValueKind::Number, serde_json::Value::String(s)) => s.trim().parse::<f64>().is_ok(),
+            (ValueKind::Boolean, serde_json::Value::Bool(_)) => true,
+            (ValueKind::Text | ValueKind::Temporal, serde_json::Value::String(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Check a filter's value against its dimension's declared `data_type`.
+///
+/// Dimensions without a recognised `data_type` accept any value. Numeric
+/// strings are accepted for numeric dimensions.
+pub(crate) fn check_filter_value(
+    filter: &Filter,
+    dimension: &Dimension,
+) -> crate::error::Result<()> {
+    let kind = match dimension.data_type.as_deref().and_then(ValueKind::of) {
+        Some(ValueKind::Boolean) => return normalize_boolean_filter(filter).map(|_| ()),
+        kind => kind,
+    };
+    let (kind, values): (ValueKind, Vec<&serde_json::Value>) = match filter.op {
+        FilterOp::IsTrue | FilterOp::IsFalse | FilterOp::IsUnknown => return Ok(()),
+        // Patterns are strings whatever the column type
+        FilterOp::Like | FilterOp::ILike => (ValueKind::Text, vec![&filter.value]),
+        _ => {
+            let Some(kind) = kind else {
+                return Ok(());
+            };
+            match &filter.value {
+                serde_json::Value::Array(items) => (kind, items.iter().collect()),
+                value => (kind, vec![value]),
+            }
+        }
+    };
+    match values.into_iter().find(|value| !kind.accepts(value)) {
+        Some(value) => Err(crate::error::SemaflowError::InvalidFilterValue {
+            field: filter.field.clone(),
+            expected: kind.name().to_string(),
+            value: value.clone(),
+        }),
+        None => Ok(()),
+    }
+}
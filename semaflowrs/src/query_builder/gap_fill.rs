@@ -0,0 +1,207 @@
+//! Time bucket gap filling (`fill_gaps` requests).
+//!
+//! Aggregated results only contain buckets that have rows, which leaves holes
+//! in time-series charts. When a request sets `fill_gaps`, the planned query
+//! becomes a CTE and is LEFT JOINed onto a date spine covering its first to
+//! last bucket. Other requested dimensions are crossed with the spine so each
+//! series is filled independently, and each measure is filled according to
+//! the request's `gap_fill` mode.
+
+use std::collections::HashSet;
+
+use crate::dialect::Dialect;
+use crate::error::{Result, SemaflowError};
+use crate::flows::{Expr, Function, GapFill, QueryRequest, SemanticFlow, SortDirection, TimeGrain};
+use crate::registry::FlowRegistry;
+use crate::sql_ast::{sanitize_alias, SelectQuery, SqlRenderer};
+
+use super::resolve::{build_alias_map, resolve_dimension_inner};
+
+const RESULTS_CTE: &str = "__results";
+const SPINE_CTE: &str = "__spine";
+const KEYS_CTE: &str = "__keys";
+const BUCKET_COLUMN: &str = "__bucket";
+
+/// Wrap `query` so every time bucket between the first and last result is present.
+pub(crate) fn render_gap_filled(
+    mut query: SelectQuery,
+    flow: &SemanticFlow,
+    registry: &FlowRegistry,
+    request: &QueryRequest,
+    renderer: &SqlRenderer,
+    dialect: &dyn Dialect,
+) -> Result<String> {
+    let (time_dimension, grain) = find_time_dimension(flow, registry, request)?;
+    if grain.is_fiscal() {
+        return Err(SemaflowError::Validation(format!(
+            "fill_gaps does not support the fiscal grain of {time_dimension}"
+        )));
+    }
+    for (measure, fill) in &request.gap_fill {
+        if !request.measures.contains(measure) {
+            return Err(SemaflowError::Validation(format!(
+                "gap_fill specified for measure '{measure}' which is not in the requested measures"
+            )));
+        }
+        if *fill == GapFill::Previous && !dialect.capabilities().ignore_nulls {
+            return Err(SemaflowError::Validation(format!(
+                "gap_fill 'previous' for measure '{measure}' is not supported by this data source"
+            )));
+        }
+    }
+
+    let q = |ident: &str| dialect.quote_ident(ident);
+    let col = |table: &str, ident: &str| format!("{}.{}", q(table), q(ident));
+    let time_alias = sanitize_alias(&time_dimension);
+    let key_aliases: Vec<String> = request
+        .dimensions
+        .iter()
+        .map(|d| sanitize_alias(&d.field))
+        .filter(|alias| *alias != time_alias)
+        .collect();
+    let fills: Vec<(String, GapFill)> = request
+        .measures
+        .iter()
+        .map(|m| {
+            let fill = request.gap_fill.get(m).copied().unwrap_or_default();
+            (sanitize_alias(m), fill)
+        })
+        .collect();
+
+    // The outer query applies ordering and limits to the filled rows
+    query.order_by.clear();
+    let limit = query.limit.take();
+    let offset = query.offset.take();
+    let inner = renderer.render_select(&query);
+
+    let bucket = col(SPINE_CTE, BUCKET_COLUMN);
+    let partition = if key_aliases.is_empty() {
+        String::new()
+    } else {
+        let keys: Vec<String> = key_aliases.iter().map(|k| col(KEYS_CTE, k)).collect();
+        format!("PARTITION BY {} ", keys.join(", "))
+    };
+    let mut output = HashSet::new();
+    let select: Vec<String> = query
+        .select
+        .iter()
+        .filter_map(|item| item.alias.as_deref().map(sanitize_alias))
+        .map(|alias| {
+            output.insert(alias.clone());
+            let expr = if alias == time_alias {
+                bucket.clone()
+            } else if key_aliases.contains(&alias) {
+                col(KEYS_CTE, &alias)
+            } else {
+                let value = col(RESULTS_CTE, &alias);
+                match fills.iter().find(|(m, _)| *m == alias).map(|(_, f)| f) {
+                    Some(GapFill::Zero) => format!("COALESCE({value}, 0)"),
+                    Some(GapFill::Previous) => format!(
+                        "LAST_VALUE({value} IGNORE NULLS) OVER ({partition}ORDER BY {bucket} \
+                         ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW)"
+                    ),
+                    _ => value,
+                }
+            };
+            format!("{expr} AS {}", q(&alias))
+        })
+        .collect();
+
+    let mut ctes = vec![
+        format!("{} AS ({inner})", q(RESULTS_CTE)),
+        format!(
+            "{} AS ({})",
+            q(SPINE_CTE),
+            dialect.render_date_spine(&q(RESULTS_CTE), &q(&time_alias), &grain, &q(BUCKET_COLUMN))
+        ),
+    ];
+    let mut from = q(SPINE_CTE);
+    let mut on = vec![format!("{} = {bucket}", col(RESULTS_CTE, &time_alias))];
+    if !key_aliases.is_empty() {
+        let keys: Vec<String> = key_aliases.iter().map(|k| q(k)).collect();
+        ctes.push(format!(
+            "{} AS (SELECT DISTINCT {} FROM {})",
+            q(KEYS_CTE),
+            keys.join(", "),
+            q(RESULTS_CTE)
+        ));
+        from.push_str(&format!(" CROSS JOIN {}", q(KEYS_CTE)));
+        on.extend(key_aliases.iter().map(|k| {
+            format!(
+                "{} IS NOT DISTINCT FROM {}",
+                col(RESULTS_CTE, k),
+                col(KEYS_CTE, k)
+            )
+        }));
+    }
+
+    let mut sql = format!(
+        "WITH {} SELECT {} FROM {from} LEFT JOIN {} ON {}",
+        ctes.join(", "),
+        select.join(", "),
+        q(RESULTS_CTE),
+        on.join(" AND ")
+    );
+
+    if !request.order.is_empty() {
+        let mut orders = Vec::new();
+        for item in &request.order {
+            let alias = sanitize_alias(&item.column);
+            if !output.contains(&alias) {
+                return Err(SemaflowError::Validation(format!(
+                    "fill_gaps can only order by requested fields; '{}' is not selected",
+                    item.column
+                )));
+            }
+            let dir = match item.direction {
+                SortDirection::Asc => "ASC",
+                SortDirection::Desc => "DESC",
+            };
+            orders.push(format!("{} {dir}", q(&alias)));
+        }
+        sql.push_str(&format!(" ORDER BY {}", orders.join(", ")));
+    }
+    sql.push_str(&dialect.capabilities().limit.render(limit, offset));
+    Ok(sql)
+}
+
+/// The single requested dimension bucketed to a time grain, either with a
+/// `{field, grain}` override or by a `date_trunc` dimension definition.
+fn find_time_dimension(
+    flow: &SemanticFlow,
+    registry: &FlowRegistry,
+    request: &QueryRequest,
+) -> Result<(String, TimeGrain)> {
+    let alias_map = build_alias_map(flow, registry)?;
+    let mut found = Vec::new();
+    for requested in &request.dimensions {
+        let grain = match &requested.grain {
+            Some(grain) => Some(grain.clone()),
+            None => resolve_dimension_inner(&requested.field, flow, registry, &alias_map)?
+                .and_then(|(_, _, dim)| match &dim.expr {
+                    Expr::Func {
+                        func: Function::DateTrunc(grain),
+                        ..
+                    } => Some(grain.clone()),
+                    _ => None,
+                }),
+        };
+        if let Some(grain) = grain {
+            found.push((requested.field.clone(), grain));
+        }
+    }
+    match found.len() {
+        1 => Ok(found.remove(0)),
+        0 => Err(SemaflowError::Validation(
+            "fill_gaps requires a dimension truncated to a time grain".to_string(),
+        )),
+        _ => Err(SemaflowError::Validation(format!(
+            "fill_gaps supports a single time dimension; found {}",
+            found
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
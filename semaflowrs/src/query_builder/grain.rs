@@ -6,7 +6,7 @@
 
 use std::collections::HashSet;
 
-use crate::flows::{FlowJoin, JoinCardinality};
+use crate::flows::{FlowJoin, JoinCardinality, SemanticTable};
 
 /// Cardinality of a relationship between two tables.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -38,19 +38,49 @@ impl From<JoinCardinality> for Cardinality {
 /// The grain of a table - the set of columns that uniquely identify a row.
 pub type Grain = HashSet<String>;
 
+/// Column sets known to identify at most one row of `table`: its primary key,
+/// its declared unique keys and the column of each primary or unique entity.
+pub fn unique_keys(table: &SemanticTable) -> Vec<Grain> {
+    let primary: Grain = table.primary_keys.iter().cloned().collect();
+    std::iter::once(primary)
+        .chain(
+            table
+                .unique_keys
+                .iter()
+                .map(|key| key.iter().cloned().collect()),
+        )
+        .chain(
+            table
+                .entities
+                .iter()
+                .filter(|(_, entity)| entity.kind.is_unique())
+                .map(|(name, entity)| Grain::from([entity.column(name).to_string()])),
+        )
+        .filter(|key| !key.is_empty())
+        .collect()
+}
+
+/// Whether `columns` include every column of one of the unique `keys`, so
+/// that matching them selects at most one row.
+pub fn covers_unique_key(columns: &Grain, keys: &[Grain]) -> bool {
+    keys.iter()
+        .any(|key| !key.is_empty() && key.is_subset(columns))
+}
+
 /// Infer the cardinality of a join relationship.
 ///
 /// Logic:
-/// - If right join keys == right table's PK → Many-to-One (each left row maps to at most one right row)
-/// - If left join keys == left table's PK → One-to-Many (each right row maps to at most one left row)
+/// - If right join keys cover a unique key of the right table → Many-to-One (each left row maps to at most one right row)
+/// - If left join keys cover a unique key of the left table → One-to-Many (each right row maps to at most one left row)
 /// - If both → One-to-One
 /// - Otherwise → Unknown (potentially Many-to-Many)
 ///
+/// Unique keys may be composite; all of their columns must be joined on.
 /// A hint can override the inference if cardinality is known from domain knowledge.
 pub fn infer_join_cardinality(
     join: &FlowJoin,
-    left_pk: &Grain,
-    right_pk: &Grain,
+    left_keys: &[Grain],
+    right_keys: &[Grain],
     hint: Option<Cardinality>,
 ) -> Cardinality {
     // Explicit hint overrides inference
@@ -58,13 +88,13 @@ pub fn infer_join_cardinality(
         return h;
     }
 
-    let join_keys_right: HashSet<String> = join.join_keys.iter().map(|k| k.right.clone()).collect();
-    let join_keys_left: HashSet<String> = join.join_keys.iter().map(|k| k.left.clone()).collect();
+    let join_keys_right: Grain = join.join_keys.iter().map(|k| k.right.clone()).collect();
+    let join_keys_left: Grain = join.join_keys.iter().map(|k| k.left.clone()).collect();
 
-    let right_is_pk = !right_pk.is_empty() && join_keys_right == *right_pk;
-    let left_is_pk = !left_pk.is_empty() && join_keys_left == *left_pk;
+    let right_is_unique = covers_unique_key(&join_keys_right, right_keys);
+    let left_is_unique = covers_unique_key(&join_keys_left, left_keys);
 
-    match (left_is_pk, right_is_pk) {
+    match (left_is_unique, right_is_unique) {
         (true, true) => Cardinality::OneToOne,
         (true, false) => Cardinality::OneToMany,
         (false, true) => Cardinality::ManyToOne,
@@ -88,6 +118,10 @@ mod tests {
                 right: right_col.to_string(),
             }],
             cardinality: None,
+            through: None,
+            weight: None,
+            role: None,
+            extra_conditions: None,
             description: None,
         }
     }
@@ -98,7 +132,7 @@ mod tests {
         let left_pk: Grain = ["order_id"].iter().map(|s| s.to_string()).collect();
         let right_pk: Grain = ["id"].iter().map(|s| s.to_string()).collect();
 
-        let cardinality = infer_join_cardinality(&join, &left_pk, &right_pk, None);
+        let cardinality = infer_join_cardinality(&join, &[left_pk], &[right_pk], None);
         assert_eq!(cardinality, Cardinality::ManyToOne);
     }
 
@@ -108,7 +142,7 @@ mod tests {
         let left_pk: Grain = ["id"].iter().map(|s| s.to_string()).collect();
         let right_pk: Grain = ["order_id"].iter().map(|s| s.to_string()).collect();
 
-        let cardinality = infer_join_cardinality(&join, &left_pk, &right_pk, None);
+        let cardinality = infer_join_cardinality(&join, &[left_pk], &[right_pk], None);
         assert_eq!(cardinality, Cardinality::OneToMany);
     }
 
@@ -118,7 +152,7 @@ mod tests {
         let left_pk: Grain = ["id"].iter().map(|s| s.to_string()).collect();
         let right_pk: Grain = ["id"].iter().map(|s| s.to_string()).collect();
 
-        let cardinality = infer_join_cardinality(&join, &left_pk, &right_pk, None);
+        let cardinality = infer_join_cardinality(&join, &[left_pk], &[right_pk], None);
         assert_eq!(cardinality, Cardinality::OneToOne);
     }
 
@@ -128,7 +162,7 @@ mod tests {
         let left_pk: Grain = ["id"].iter().map(|s| s.to_string()).collect();
         let right_pk: Grain = ["id"].iter().map(|s| s.to_string()).collect();
 
-        let cardinality = infer_join_cardinality(&join, &left_pk, &right_pk, None);
+        let cardinality = infer_join_cardinality(&join, &[left_pk], &[right_pk], None);
         assert_eq!(cardinality, Cardinality::Unknown);
     }
 
@@ -139,7 +173,50 @@ mod tests {
         let right_pk = Grain::new();
 
         let cardinality =
-            infer_join_cardinality(&join, &left_pk, &right_pk, Some(Cardinality::ManyToOne));
+            infer_join_cardinality(&join, &[left_pk], &[right_pk], Some(Cardinality::ManyToOne));
+        assert_eq!(cardinality, Cardinality::ManyToOne);
+    }
+
+    #[test]
+    fn infers_many_to_one_when_right_composite_pk_is_covered() {
+        let mut join = make_join("order_id", "order_id");
+        join.join_keys.push(JoinKey {
+            left: "line_no".to_string(),
+            right: "line_no".to_string(),
+        });
+        let left_pk: Grain = ["shipment_id"].iter().map(|s| s.to_string()).collect();
+        let right_pk: Grain = ["order_id", "line_no"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let cardinality = infer_join_cardinality(&join, &[left_pk], &[right_pk], None);
+        assert_eq!(cardinality, Cardinality::ManyToOne);
+    }
+
+    #[test]
+    fn infers_unknown_when_composite_pk_is_partly_covered() {
+        let join = make_join("order_id", "order_id");
+        let left_pk: Grain = ["shipment_id"].iter().map(|s| s.to_string()).collect();
+        let right_pk: Grain = ["order_id", "line_no"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let cardinality = infer_join_cardinality(&join, &[left_pk], &[right_pk], None);
+        assert_eq!(cardinality, Cardinality::Unknown);
+    }
+
+    #[test]
+    fn infers_many_to_one_on_a_unique_key_other_than_the_pk() {
+        let join = make_join("customer_email", "email");
+        let left_pk: Grain = ["order_id"].iter().map(|s| s.to_string()).collect();
+        let right_keys: Vec<Grain> = [["id"], ["email"]]
+            .iter()
+            .map(|key| key.iter().map(|s| s.to_string()).collect())
+            .collect();
+
+        let cardinality = infer_join_cardinality(&join, &[left_pk], &right_keys, None);
         assert_eq!(cardinality, Cardinality::ManyToOne);
     }
 }
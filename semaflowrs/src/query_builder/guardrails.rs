@@ -0,0 +1,211 @@
+//! Per-flow request guardrails ([`FlowLimits`]).
+//!
+//! Checked before planning, so a request that would scan an entire large
+//! flow fails with a validation error naming the limit instead of reaching
+//! the warehouse. Only filters every row must satisfy count: top-level
+//! filters and those inside `all` groups, not `any` or `not` branches.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::error::{Result, SemaflowError};
+use crate::flows::{
+    Filter, FilterExpr, FilterOp, FlowLimits, QueryRequest, RelativeDateRange, SemanticFlow,
+    SemanticTable, TimeGrain,
+};
+use crate::registry::FlowRegistry;
+use crate::schedule::days_from_date;
+
+use super::resolve::{build_alias_map, resolve_dimension, resolve_time_dimension};
+
+/// Check `request` against the flow's limits, returning it with `max_rows`
+/// applied as the limit when it has neither a `limit` nor a `page_size`.
+pub(crate) fn enforce_limits<'a>(
+    flow: &SemanticFlow,
+    registry: &FlowRegistry,
+    request: &'a QueryRequest,
+) -> Result<Cow<'a, QueryRequest>> {
+    let limits = &flow.limits;
+    if limits.is_empty() {
+        return Ok(Cow::Borrowed(request));
+    }
+    let alias_map = build_alias_map(flow, registry)?;
+    let mut conditions = Vec::new();
+    collect_conditions(&request.filters, &mut conditions);
+    for name in &request.segments {
+        if let Some(segment) = flow.segments.get(name) {
+            collect_conditions(std::slice::from_ref(segment), &mut conditions);
+        }
+    }
+
+    for required in &limits.require_filter_on {
+        if !is_filtered(required, flow, registry, &alias_map, request, &conditions) {
+            return Err(SemaflowError::Validation(format!(
+                "flow '{}' requires a filter on {required}",
+                flow.name
+            )));
+        }
+    }
+
+    if let Some(max_days) = limits.max_time_range_days {
+        match time_range_days(flow, &alias_map, request, &conditions) {
+            None => {
+                return Err(SemaflowError::Validation(format!(
+                    "flow '{}' requires a time_filter or a bounded filter on its time dimension \
+                     covering at most {max_days} days",
+                    flow.name
+                )))
+            }
+            Some(days) if days > i64::from(max_days) => {
+                return Err(SemaflowError::Validation(format!(
+                    "time range of {days} days exceeds the {max_days}-day limit of flow '{}'",
+                    flow.name
+                )))
+            }
+            Some(_) => {}
+        }
+    }
+
+    check_max_rows(flow, limits, request)
+}
+
+fn check_max_rows<'a>(
+    flow: &SemanticFlow,
+    limits: &FlowLimits,
+    request: &'a QueryRequest,
+) -> Result<Cow<'a, QueryRequest>> {
+    let Some(max_rows) = limits.max_rows else {
+        return Ok(Cow::Borrowed(request));
+    };
+    for (name, value) in [("limit", request.limit), ("page_size", request.page_size)] {
+        if let Some(value) = value.filter(|v| u64::from(*v) > max_rows) {
+            return Err(SemaflowError::Validation(format!(
+                "{name} {value} exceeds max_rows {max_rows} of flow '{}'",
+                flow.name
+            )));
+        }
+    }
+    // Paginated requests are bounded per page by the backend
+    if request.limit.is_some() || request.page_size.is_some() {
+        return Ok(Cow::Borrowed(request));
+    }
+    Ok(Cow::Owned(QueryRequest {
+        limit: Some(u32::try_from(max_rows).unwrap_or(u32::MAX)),
+        ..request.clone()
+    }))
+}
+
+/// Leaf conditions every returned row satisfies.
+fn collect_conditions<'a>(filters: &'a [FilterExpr], out: &mut Vec<&'a Filter>) {
+    for filter in filters {
+        match filter {
+            FilterExpr::Condition(condition) => out.push(condition),
+            FilterExpr::All { all } => collect_conditions(all, out),
+            FilterExpr::Any { .. } | FilterExpr::Not { .. } => {}
+        }
+    }
+}
+
+/// Whether `field` names the base table's time dimension.
+fn is_base_time_dimension(
+    field: &str,
+    flow: &SemanticFlow,
+    alias_map: &HashMap<String, &SemanticTable>,
+) -> bool {
+    resolve_time_dimension(field, flow, alias_map)
+        .is_ok_and(|(_, alias, _)| alias == flow.base_table.alias)
+}
+
+fn is_filtered(
+    required: &str,
+    flow: &SemanticFlow,
+    registry: &FlowRegistry,
+    alias_map: &HashMap<String, &SemanticTable>,
+    request: &QueryRequest,
+    conditions: &[&Filter],
+) -> bool {
+    let mut conditions = conditions.iter().filter(|c| restricts(&c.op));
+    if is_base_time_dimension(required, flow, alias_map) {
+        return request.time_filter.is_some()
+            || conditions.any(|c| is_base_time_dimension(&c.field, flow, alias_map));
+    }
+    let Ok((_, alias, dimension)) = resolve_dimension(required, flow, registry, alias_map) else {
+        return false;
+    };
+    conditions.any(|c| {
+        resolve_dimension(&c.field, flow, registry, alias_map)
+            .is_ok_and(|(_, a, d)| a == alias && std::ptr::eq(d, dimension))
+    })
+}
+
+/// Whether `op` narrows a dimension to chosen values or a range; `!=`,
+/// `not in` and pattern matches leave nearly every row in place.
+fn restricts(op: &FilterOp) -> bool {
+    matches!(
+        op,
+        FilterOp::Eq | FilterOp::In | FilterOp::Gt | FilterOp::Gte | FilterOp::Lt | FilterOp::Lte
+    )
+}
+
+/// Days covered by the request on the base table's time dimension; `None`
+/// when it is unbounded on either side.
+fn time_range_days(
+    flow: &SemanticFlow,
+    alias_map: &HashMap<String, &SemanticTable>,
+    request: &QueryRequest,
+    conditions: &[&Filter],
+) -> Option<i64> {
+    let relative = request.time_filter.as_ref().map(relative_range_days);
+
+    let (mut lower, mut upper) = (None::<i64>, None::<i64>);
+    for condition in conditions {
+        if !is_base_time_dimension(&condition.field, flow, alias_map) {
+            continue;
+        }
+        let Some(day) = condition.value.as_str().and_then(days_from_date) else {
+            continue;
+        };
+        // Bounds as [lower, upper) in days
+        let (from, to) = match condition.op {
+            FilterOp::Eq => (Some(day), Some(day + 1)),
+            FilterOp::Gt | FilterOp::Gte => (Some(day), None),
+            FilterOp::Lt => (None, Some(day)),
+            FilterOp::Lte => (None, Some(day + 1)),
+            _ => (None, None),
+        };
+        lower = lower.max(from);
+        upper = match (upper, to) {
+            (Some(u), Some(t)) => Some(u.min(t)),
+            (u, t) => u.or(t),
+        };
+    }
+    let explicit = lower
+        .zip(upper)
+        .map(|(lower, upper)| (upper - lower).max(0));
+
+    match (relative, explicit) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Longest span of a relative range, in days.
+fn relative_range_days(range: &RelativeDateRange) -> i64 {
+    let grain_days = |grain: &TimeGrain| match grain {
+        TimeGrain::Second | TimeGrain::Minute | TimeGrain::Hour | TimeGrain::Day => 1,
+        TimeGrain::Week | TimeGrain::FiscalWeek => 7,
+        TimeGrain::Month => 31,
+        // A five-week month closing a week-based fiscal year
+        TimeGrain::FiscalMonth => 37,
+        TimeGrain::Quarter => 92,
+        TimeGrain::FiscalQuarter => 93,
+        TimeGrain::Year | TimeGrain::FiscalYear => 366,
+    };
+    match range {
+        RelativeDateRange::Today | RelativeDateRange::Yesterday => 1,
+        RelativeDateRange::Last { count, unit } => i64::from(*count) * grain_days(unit),
+        RelativeDateRange::Current(grain)
+        | RelativeDateRange::Previous(grain)
+        | RelativeDateRange::ToDate(grain) => grain_days(grain),
+    }
+}
@@ -2,12 +2,22 @@ use std::collections::{HashMap, HashSet};
 
 use crate::error::{Result, SemaflowError};
 use crate::flows::{FlowJoin, JoinType, SemanticFlow, SemanticTable};
+use crate::schema_cache::TableStats;
+
+use super::grain::unique_keys;
 
 pub(crate) fn select_required_joins<'a>(
     flow: &'a SemanticFlow,
     required_aliases: &HashSet<String>,
     alias_to_table: &HashMap<String, &'a SemanticTable>,
+    stats: &HashMap<String, TableStats>,
 ) -> Result<Vec<&'a FlowJoin>> {
+    if let Some(path) = find_join_cycle(flow) {
+        return Err(SemaflowError::JoinCycle {
+            flow: flow.name.clone(),
+            path,
+        });
+    }
     let base_alias = &flow.base_table.alias;
     let mut join_by_alias: HashMap<&str, &FlowJoin> = HashMap::new();
     for join in flow.joins.values() {
@@ -20,9 +30,16 @@ pub(crate) fn select_required_joins<'a>(
         .filter(|a| *a != base_alias)
         .cloned()
         .collect();
-    // Always include joins that are not safe to prune (e.g., inner or unknown cardinality).
+    // Always include joins that are not safe to prune (inner, or to a table without keys).
+    // Bridges and the joins through them only lead to the tables beyond.
+    let bridges: HashSet<&str> = flow
+        .joins
+        .values()
+        .filter_map(|join| join.through.as_deref())
+        .collect();
     for join in flow.joins.values() {
-        if !safe_to_prune(join, alias_to_table) && join.alias != *base_alias {
+        let bridged = join.through.is_some() || bridges.contains(join.alias.as_str());
+        if !bridged && !safe_to_prune(join, alias_to_table) && join.alias != *base_alias {
             stack.push(join.alias.clone());
         }
     }
@@ -51,23 +68,92 @@ pub(crate) fn select_required_joins<'a>(
             )?;
         }
     }
-    Ok(ordered)
+    Ok(order_by_size(ordered, base_alias, alias_to_table, stats))
+}
+
+/// Reorder inner and left joins so that, once the table each joins to is
+/// joined, inner joins come before left joins and smaller tables before
+/// larger ones, narrowing the rows early. Only applies when statistics are
+/// known for some joined table; right and full joins keep their order.
+fn order_by_size<'a>(
+    ordered: Vec<&'a FlowJoin>,
+    base_alias: &str,
+    alias_to_table: &HashMap<String, &SemanticTable>,
+    stats: &HashMap<String, TableStats>,
+) -> Vec<&'a FlowJoin> {
+    let row_count = |join: &FlowJoin| {
+        alias_to_table
+            .get(&join.alias)
+            .and_then(|table| stats.get(&table.name))
+            .map(|stats| stats.row_count)
+    };
+    let reorderable = ordered
+        .iter()
+        .all(|join| matches!(join.join_type, JoinType::Inner | JoinType::Left));
+    if !reorderable || !ordered.iter().any(|join| row_count(join).is_some()) {
+        return ordered;
+    }
+    let mut placed: HashSet<&str> = HashSet::from([base_alias]);
+    let mut remaining = ordered;
+    let mut sorted = Vec::with_capacity(remaining.len());
+    while let Some(next) = remaining
+        .iter()
+        .enumerate()
+        .filter(|(_, join)| placed.contains(join.to_table.as_str()))
+        .min_by_key(|(_, join)| {
+            (
+                join.join_type != JoinType::Inner,
+                row_count(join).unwrap_or(u64::MAX),
+            )
+        })
+        .map(|(i, _)| i)
+    {
+        let join = remaining.remove(next);
+        placed.insert(join.alias.as_str());
+        sorted.push(join);
+    }
+    sorted.extend(remaining);
+    sorted
+}
+
+/// Follow each join's `to_table` chain towards the base table and return the
+/// aliases of the first cycle found, ending with the alias it started from.
+/// Chains ending at an unknown alias are left to the caller to report.
+pub(crate) fn find_join_cycle(flow: &SemanticFlow) -> Option<Vec<String>> {
+    let join_by_alias: HashMap<&str, &FlowJoin> = flow
+        .joins
+        .values()
+        .map(|join| (join.alias.as_str(), join))
+        .collect();
+    for join in flow.joins.values() {
+        let mut path: Vec<&str> = vec![join.alias.as_str()];
+        let mut current = join;
+        while current.to_table != flow.base_table.alias {
+            let Some(next) = join_by_alias.get(current.to_table.as_str()) else {
+                break;
+            };
+            if let Some(start) = path.iter().position(|alias| *alias == next.alias) {
+                let mut cycle: Vec<String> = path[start..].iter().map(|a| a.to_string()).collect();
+                cycle.push(next.alias.clone());
+                return Some(cycle);
+            }
+            path.push(next.alias.as_str());
+            current = next;
+        }
+    }
+    None
 }
 
 fn safe_to_prune(join: &FlowJoin, alias_to_table: &HashMap<String, &SemanticTable>) -> bool {
     if join.join_type != JoinType::Left {
         return false;
     }
-    if let Some(table) = alias_to_table.get(&join.alias) {
-        // Safe to prune if join keys exactly match the primary keys
-        let join_right_keys: std::collections::HashSet<_> =
-            join.join_keys.iter().map(|k| &k.right).collect();
-        let pk_set: std::collections::HashSet<_> = table.primary_keys.iter().collect();
-        if !pk_set.is_empty() && join_right_keys == pk_set {
-            return true;
-        }
-    }
-    false
+    // A LEFT join keeps every row it starts from. Once the joined table's keys
+    // are known it either adds columns (keys covered) or repeats rows (keys not
+    // covered), and an unrequested table should do neither
+    alias_to_table
+        .get(&join.alias)
+        .is_some_and(|table| !unique_keys(table).is_empty())
 }
 
 fn visit_join<'a>(
@@ -15,7 +15,9 @@ pub(crate) fn validate_no_measure_refs(expr: &Expr) -> Result<()> {
         Expr::MeasureRef { name } => Err(SemaflowError::Validation(format!(
             "measure references are not allowed in filters ({name})"
         ))),
-        Expr::Column { .. } | Expr::Literal { .. } => Ok(()),
+        Expr::Column { .. } | Expr::Literal { .. } | Expr::Param { .. } | Expr::Sql { .. } => {
+            Ok(())
+        }
         Expr::Func { args, .. } => args.iter().try_for_each(validate_no_measure_refs),
         Expr::Case {
             branches,
@@ -31,6 +33,18 @@ pub(crate) fn validate_no_measure_refs(expr: &Expr) -> Result<()> {
             validate_no_measure_refs(left)?;
             validate_no_measure_refs(right)
         }
+        Expr::InList { expr, list, .. } => {
+            validate_no_measure_refs(expr)?;
+            list.iter().try_for_each(validate_no_measure_refs)
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            validate_no_measure_refs(expr)?;
+            validate_no_measure_refs(low)?;
+            validate_no_measure_refs(high)
+        }
+        Expr::IsNull { expr, .. } | Expr::Not { expr } => validate_no_measure_refs(expr),
     }
 }
 
@@ -46,6 +60,7 @@ pub(crate) fn apply_measure_filter(
     let agg = measure.agg.as_ref().expect(
         "apply_measure_filter called on formula measure - this is a bug, use measure.is_simple() to check"
     );
+    let agg = &agg.qualify_order_by(alias);
 
     if let Some(filter) = &measure.filter {
         let filter = normalize_freeform(filter);
@@ -74,6 +89,19 @@ pub(crate) fn apply_measure_filter(
     }
 }
 
+/// The rows a simple measure's aggregate reads: its expression, or a CASE
+/// yielding NULL where the measure filter doesn't hold.
+pub(crate) fn aggregate_input(agg_expr: &SqlExpr) -> Option<SqlExpr> {
+    match agg_expr {
+        SqlExpr::Aggregate { expr, .. } => Some((**expr).clone()),
+        SqlExpr::FilteredAggregate { expr, filter, .. } => Some(SqlExpr::Case {
+            branches: vec![((**filter).clone(), (**expr).clone())],
+            else_expr: Box::new(SqlExpr::Literal(serde_json::Value::Null)),
+        }),
+        _ => None,
+    }
+}
+
 pub(crate) fn resolve_measure_with_posts(
     name: &str,
     lookup: &HashMap<String, (&str, &Measure)>,
@@ -141,10 +169,10 @@ pub(crate) fn normalize_freeform(expr: &Expr) -> Expr {
 /// The `measure_resolver` is called for measure references, allowing resolution from base measures.
 ///
 /// Division operations are automatically wrapped in NULLIF for safety.
-#[allow(dead_code)] // Prepared for formula measure integration
 pub(crate) fn formula_to_sql(
     ast: &FormulaAst,
     alias: &str,
+    supports_filtered_aggregates: bool,
     measure_resolver: &mut impl FnMut(&str) -> Result<SqlExpr>,
 ) -> Result<SqlExpr> {
     match ast {
@@ -158,14 +186,29 @@ pub(crate) fn formula_to_sql(
                 name: column.clone(),
             };
 
-            // Handle filtered aggregation
+            // Handle filtered aggregation, as a CASE where FILTER isn't supported
             if let Some(filter_ast) = filter {
-                let filter_sql = formula_to_sql(filter_ast, alias, measure_resolver)?;
-                Ok(SqlExpr::FilteredAggregate {
-                    agg: agg.clone(),
-                    expr: Box::new(base_expr),
-                    filter: Box::new(filter_sql),
-                })
+                let filter_sql = formula_to_sql(
+                    filter_ast,
+                    alias,
+                    supports_filtered_aggregates,
+                    measure_resolver,
+                )?;
+                if supports_filtered_aggregates {
+                    Ok(SqlExpr::FilteredAggregate {
+                        agg: agg.clone(),
+                        expr: Box::new(base_expr),
+                        filter: Box::new(filter_sql),
+                    })
+                } else {
+                    Ok(SqlExpr::Aggregate {
+                        agg: agg.clone(),
+                        expr: Box::new(SqlExpr::Case {
+                            branches: vec![(filter_sql, base_expr)],
+                            else_expr: Box::new(SqlExpr::Literal(serde_json::Value::Null)),
+                        }),
+                    })
+                }
             } else {
                 Ok(SqlExpr::Aggregate {
                     agg: agg.clone(),
@@ -194,8 +237,10 @@ pub(crate) fn formula_to_sql(
         FormulaAst::Literal { value } => Ok(SqlExpr::Literal(value.clone())),
 
         FormulaAst::Binary { op, left, right } => {
-            let left_sql = formula_to_sql(left, alias, measure_resolver)?;
-            let right_sql = formula_to_sql(right, alias, measure_resolver)?;
+            let left_sql =
+                formula_to_sql(left, alias, supports_filtered_aggregates, measure_resolver)?;
+            let right_sql =
+                formula_to_sql(right, alias, supports_filtered_aggregates, measure_resolver)?;
 
             let sql_op = match op {
                 BinaryOp::Add => SqlBinaryOperator::Add,
@@ -233,7 +278,7 @@ pub(crate) fn formula_to_sql(
         FormulaAst::Function { name, args } => {
             let sql_args: Vec<SqlExpr> = args
                 .iter()
-                .map(|a| formula_to_sql(a, alias, measure_resolver))
+                .map(|a| formula_to_sql(a, alias, supports_filtered_aggregates, measure_resolver))
                 .collect::<Result<Vec<_>>>()?;
 
             // Map function names to Function enum
@@ -259,11 +304,19 @@ pub(crate) fn formula_to_sql(
                 "log10" => Function::Log10,
                 "exp" => Function::Exp,
                 "sign" => Function::Sign,
+                "regexp_matches" => Function::RegexpMatches,
+                "regexp_extract" => Function::RegexpExtract,
+                "regexp_replace" => Function::RegexpReplace,
+                "st_point" => Function::StPoint,
+                "st_distance" => Function::StDistance,
+                "st_within" => Function::StWithin,
                 unknown => {
                     return Err(SemaflowError::Validation(format!(
                         "Unknown function '{}' in formula. Supported: round, abs, floor, ceil, \
                          coalesce, ifnull, nullif, safe_divide, greatest, least, lower, upper, \
-                         length, trim, concat, power, sqrt, ln, log10, exp, sign",
+                         length, trim, concat, power, sqrt, ln, log10, exp, sign, \
+                         regexp_matches, regexp_extract, regexp_replace, st_point, st_distance, \
+                         st_within",
                         unknown
                     )));
                 }
@@ -274,6 +327,93 @@ pub(crate) fn formula_to_sql(
                 args: sql_args,
             })
         }
+
+        FormulaAst::InList {
+            expr,
+            list,
+            negated,
+        } => Ok(SqlExpr::InList {
+            expr: Box::new(formula_to_sql(
+                expr,
+                alias,
+                supports_filtered_aggregates,
+                measure_resolver,
+            )?),
+            list: list
+                .iter()
+                .map(|item| {
+                    formula_to_sql(item, alias, supports_filtered_aggregates, measure_resolver)
+                })
+                .collect::<Result<Vec<_>>>()?,
+            negated: *negated,
+        }),
+
+        FormulaAst::IsNull { expr, negated } => Ok(SqlExpr::IsNull {
+            expr: Box::new(formula_to_sql(
+                expr,
+                alias,
+                supports_filtered_aggregates,
+                measure_resolver,
+            )?),
+            negated: *negated,
+        }),
+
+        FormulaAst::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => Ok(SqlExpr::Between {
+            expr: Box::new(formula_to_sql(
+                expr,
+                alias,
+                supports_filtered_aggregates,
+                measure_resolver,
+            )?),
+            low: Box::new(formula_to_sql(
+                low,
+                alias,
+                supports_filtered_aggregates,
+                measure_resolver,
+            )?),
+            high: Box::new(formula_to_sql(
+                high,
+                alias,
+                supports_filtered_aggregates,
+                measure_resolver,
+            )?),
+            negated: *negated,
+        }),
+
+        FormulaAst::Not { expr } => Ok(SqlExpr::Not {
+            expr: Box::new(formula_to_sql(
+                expr,
+                alias,
+                supports_filtered_aggregates,
+                measure_resolver,
+            )?),
+        }),
+
+        FormulaAst::Case {
+            branches,
+            else_expr,
+        } => {
+            let mut to_sql = |node: &FormulaAst| {
+                formula_to_sql(node, alias, supports_filtered_aggregates, measure_resolver)
+            };
+            let branches = branches
+                .iter()
+                .map(|(when, then)| Ok((to_sql(when)?, to_sql(then)?)))
+                .collect::<Result<Vec<_>>>()?;
+            let else_expr = match else_expr {
+                Some(expr) => to_sql(expr)?,
+                None => SqlExpr::Literal(serde_json::Value::Null),
+            };
+            Ok(SqlExpr::Case {
+                branches,
+                else_expr: Box::new(else_expr),
+            })
+        }
     }
 }
 
@@ -294,7 +434,7 @@ mod tests {
     #[test]
     fn formula_sum_aggregation() {
         let ast = parse_formula("sum(amount)").unwrap();
-        let sql = formula_to_sql(&ast, "o", &mut mock_resolver).unwrap();
+        let sql = formula_to_sql(&ast, "o", true, &mut mock_resolver).unwrap();
 
         if let SqlExpr::Aggregate { agg, expr } = sql {
             assert!(matches!(agg, Aggregation::Sum));
@@ -309,10 +449,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn formula_filtered_aggregation() {
+        let ast = parse_formula("sum(amount) filter (where status = 'active')").unwrap();
+        let sql = formula_to_sql(&ast, "o", true, &mut mock_resolver).unwrap();
+        assert!(matches!(sql, SqlExpr::FilteredAggregate { .. }), "{sql:?}");
+
+        // Without FILTER support the condition moves into a CASE
+        let sql = formula_to_sql(&ast, "o", false, &mut mock_resolver).unwrap();
+        if let SqlExpr::Aggregate { agg, expr } = sql {
+            assert!(matches!(agg, Aggregation::Sum));
+            assert!(matches!(*expr, SqlExpr::Case { .. }), "{expr:?}");
+        } else {
+            panic!("Expected aggregate over CASE");
+        }
+    }
+
     #[test]
     fn formula_division_with_nullif() {
         let ast = parse_formula("sum(a) / count(b)").unwrap();
-        let sql = formula_to_sql(&ast, "t", &mut mock_resolver).unwrap();
+        let sql = formula_to_sql(&ast, "t", true, &mut mock_resolver).unwrap();
 
         // Should produce: SUM(t.a) / NULLIF(COUNT(t.b), 0)
         if let SqlExpr::BinaryOp { op, left, right } = sql {
@@ -333,7 +489,7 @@ mod tests {
     #[test]
     fn formula_function_call() {
         let ast = parse_formula("round(sum(amount), 2)").unwrap();
-        let sql = formula_to_sql(&ast, "o", &mut mock_resolver).unwrap();
+        let sql = formula_to_sql(&ast, "o", true, &mut mock_resolver).unwrap();
 
         if let SqlExpr::Function { func, args } = sql {
             assert!(matches!(func, Function::Round));
@@ -348,7 +504,7 @@ mod tests {
     #[test]
     fn formula_qualified_column() {
         let ast = parse_formula("o.amount").unwrap();
-        let sql = formula_to_sql(&ast, "ignored", &mut mock_resolver).unwrap();
+        let sql = formula_to_sql(&ast, "ignored", true, &mut mock_resolver).unwrap();
 
         if let SqlExpr::Column { table, name } = sql {
             assert_eq!(table, Some("o".to_string()));
@@ -361,7 +517,7 @@ mod tests {
     #[test]
     fn formula_arithmetic() {
         let ast = parse_formula("sum(a) + sum(b) * 2").unwrap();
-        let sql = formula_to_sql(&ast, "t", &mut mock_resolver).unwrap();
+        let sql = formula_to_sql(&ast, "t", true, &mut mock_resolver).unwrap();
 
         // Should be: (SUM(a) + (SUM(b) * 2)) due to precedence
         assert!(matches!(
@@ -376,7 +532,7 @@ mod tests {
     #[test]
     fn formula_unknown_function_error() {
         let ast = parse_formula("unknown_func(x)").unwrap();
-        let result = formula_to_sql(&ast, "t", &mut mock_resolver);
+        let result = formula_to_sql(&ast, "t", true, &mut mock_resolver);
 
         assert!(result.is_err());
         let err = result.unwrap_err().to_string();
@@ -1,66 +1,331 @@
 use crate::backends::ConnectionManager;
+use crate::config::PlannerConfig;
 use crate::error::{Result, SemaflowError};
 use crate::flows::QueryRequest;
+use crate::policy::QueryContext;
 use crate::registry::FlowRegistry;
 use crate::sql_ast::SqlRenderer;
+use crate::sql_format::RenderOptions;
 
+mod aggregates;
 mod analysis;
+mod bridge;
 mod builders;
+mod columns;
 mod components;
+mod deprecations;
+mod derived;
+mod drill;
+mod dry_run;
+mod explain;
 mod filters;
+mod gap_fill;
 mod grain;
+mod guardrails;
 mod joins;
 mod measures;
 mod plan;
 mod planner;
 mod render;
 mod resolve;
+mod stats;
+mod symmetric;
+mod totals;
+mod windows;
 
-pub struct SqlBuilder;
+pub(crate) use columns::annotate_columns;
+pub use components::MeasureStrategy;
+pub(crate) use deprecations::collect_deprecations;
+pub(crate) use derived::{find_derived_cycle, union_sql};
+pub use explain::{
+    CtePlan, FanoutDiagnostic, FilterPlacement, FilterPlan, JoinPlan, LogicalPlan, MeasurePlan,
+    PlanStrategy, QueryExplain,
+};
+pub(crate) use joins::find_join_cycle;
+pub(crate) use stats::{add_stat_keys, parse_stats, stats_columns, stats_sql};
+pub use totals::GROUPING_COLUMN;
 
-impl Default for SqlBuilder {
-    fn default() -> Self {
-        Self
-    }
+/// Builds SQL for requests. [`Self::default`] enables every planner
+/// feature the dialect supports; [`Self::with_planner`] switches some off.
+#[derive(Debug, Clone, Default)]
+pub struct SqlBuilder {
+    planner: PlannerConfig,
 }
 
 impl SqlBuilder {
+    /// A builder applying the planner switches in `planner`, e.g. a
+    /// datasource's [`crate::config::ResolvedDatasourceConfig::planner`].
+    pub fn with_planner(planner: PlannerConfig) -> Self {
+        Self { planner }
+    }
+
+    /// A builder applying the planner switches configured for the data
+    /// source of `request`'s flow; [`Self::default`] when `connections` has
+    /// no configuration or the flow is unknown.
+    pub fn for_request(
+        registry: &FlowRegistry,
+        connections: &ConnectionManager,
+        request: &QueryRequest,
+    ) -> Self {
+        registry
+            .get_flow(&request.flow)
+            .and_then(|flow| registry.get_table(&flow.base_table.semantic_table))
+            .map(|table| Self::with_planner(connections.config_for(&table.data_source).planner))
+            .unwrap_or_default()
+    }
+
     /// Build SQL using a provided dialect (useful for tests).
+    ///
+    /// Runs with an empty [`QueryContext`], so tables with access policies
+    /// return no rows; use [`Self::build_with_context`] to supply one.
     pub fn build_with_dialect(
         &self,
         registry: &FlowRegistry,
         request: &QueryRequest,
         dialect: &dyn crate::dialect::Dialect,
     ) -> Result<String> {
+        self.build_with_context(registry, request, &QueryContext::default(), dialect)
+    }
+
+    /// Build SQL using a provided dialect on behalf of the user in `context`.
+    pub fn build_with_context(
+        &self,
+        registry: &FlowRegistry,
+        request: &QueryRequest,
+        context: &QueryContext,
+        dialect: &dyn crate::dialect::Dialect,
+    ) -> Result<String> {
+        Ok(self
+            .explain_with_context(registry, request, context, dialect)?
+            .sql)
+    }
+
+    /// Like [`Self::build_with_dialect`], laid out according to `options`,
+    /// e.g. [`RenderOptions::pretty`] for debug output and audit logs.
+    pub fn build_with_options(
+        &self,
+        registry: &FlowRegistry,
+        request: &QueryRequest,
+        dialect: &dyn crate::dialect::Dialect,
+        options: &RenderOptions,
+    ) -> Result<String> {
+        Ok(self
+            .explain_rendered(
+                registry,
+                request,
+                &QueryContext::default(),
+                dialect,
+                options,
+            )?
+            .sql)
+    }
+
+    /// Build SQL and report the planning strategy and fanout diagnostics.
+    pub fn explain_with_dialect(
+        &self,
+        registry: &FlowRegistry,
+        request: &QueryRequest,
+        dialect: &dyn crate::dialect::Dialect,
+    ) -> Result<QueryExplain> {
+        self.explain_with_context(registry, request, &QueryContext::default(), dialect)
+    }
+
+    /// Like [`Self::explain_with_dialect`], on behalf of the user in `context`.
+    pub fn explain_with_context(
+        &self,
+        registry: &FlowRegistry,
+        request: &QueryRequest,
+        context: &QueryContext,
+        dialect: &dyn crate::dialect::Dialect,
+    ) -> Result<QueryExplain> {
+        self.explain_rendered(
+            registry,
+            request,
+            context,
+            dialect,
+            &RenderOptions::default(),
+        )
+    }
+
+    fn explain_rendered(
+        &self,
+        registry: &FlowRegistry,
+        request: &QueryRequest,
+        context: &QueryContext,
+        dialect: &dyn crate::dialect::Dialect,
+        options: &RenderOptions,
+    ) -> Result<QueryExplain> {
         let flow = registry
             .get_flow(&request.flow)
-            .ok_or_else(|| SemaflowError::Validation(format!("unknown flow {}", request.flow)))?;
-
-        let supports_filtered_aggregates = if std::env::var("SEMAFLOW_DISABLE_FILTERED_AGG")
-            .ok()
-            .as_deref()
-            == Some("1")
-        {
-            false
+            .ok_or_else(|| SemaflowError::UnknownFlow {
+                flow: request.flow.clone(),
+            })?;
+        let deprecations = registry.deprecations(request);
+        for warning in &deprecations {
+            tracing::warn!(flow = %request.flow, "{warning}");
+        }
+        let derived = derived::resolve_derived_tables(self, flow, registry, context, dialect)?;
+        let keyed = stats::with_stat_keys(&derived);
+        let registry: &FlowRegistry = &keyed;
+        let params = flow
+            .resolve_params(&request.params)
+            .map_err(SemaflowError::Validation)?;
+        let drilled = drill::apply_drill(flow, registry, request)?;
+        let limited = guardrails::enforce_limits(flow, registry, &drilled)?;
+        let request: &QueryRequest = &limited;
+
+        let capabilities = dialect.capabilities();
+        let supports_filtered_aggregates =
+            self.planner.filtered_aggregates && capabilities.filtered_aggregates;
+
+        let calculations = !request.calculations.is_empty();
+        let base_request;
+        let planned = if calculations {
+            crate::calculations::validate(request)?;
+            if !capabilities.window_functions {
+                return Err(SemaflowError::Validation(
+                    "calculations require window functions, which this dialect does not support"
+                        .to_string(),
+                ));
+            }
+            if request.fill_gaps || request.totals || !request.subtotals.is_empty() {
+                return Err(SemaflowError::Validation(
+                    "calculations cannot be combined with fill_gaps, totals or subtotals"
+                        .to_string(),
+                ));
+            }
+            base_request = crate::calculations::base_request(request);
+            &base_request
         } else {
-            dialect.supports_filtered_aggregates()
+            request
         };
 
-        let query = planner::build_query(flow, registry, request, supports_filtered_aggregates)?;
-        let renderer = SqlRenderer::new(dialect);
-        Ok(renderer.render_select(&query))
+        let (mut query, plan, diagnostics) = planner::build_query(
+            flow,
+            registry,
+            planned,
+            context,
+            supports_filtered_aggregates,
+            self.planner.distinct_sketches && capabilities.distinct_sketches,
+            self.planner.symmetric_aggregates && capabilities.symmetric_aggregates,
+        )?;
+        totals::apply_totals(&mut query, request)?;
+        let renderer = SqlRenderer::new(dialect)
+            .with_group_by_all(self.planner.group_by_all)
+            .with_params(params)
+            .with_fiscal_calendar(flow.fiscal_calendar.clone().unwrap_or_default())
+            .with_options(options.clone());
+        let sql = if calculations {
+            windows::render_with_calculations(query, request, &renderer, dialect)?
+        } else if request.fill_gaps {
+            if !query.grouping_sets.is_empty() {
+                return Err(SemaflowError::Validation(
+                    "fill_gaps cannot be combined with totals or subtotals".to_string(),
+                ));
+            }
+            gap_fill::render_gap_filled(query, flow, registry, request, &renderer, dialect)?
+        } else if !request.gap_fill.is_empty() {
+            return Err(SemaflowError::Validation(
+                "gap_fill requires fill_gaps to be enabled".to_string(),
+            ));
+        } else {
+            renderer.render_select(&query)
+        };
+        Ok(QueryExplain {
+            sql: renderer.format(&sql),
+            strategy: plan.strategy,
+            diagnostics,
+            plan,
+            deprecations,
+        })
+    }
+
+    /// Check a request against the semantic model without building or
+    /// executing SQL, returning every problem found rather than the first.
+    ///
+    /// Besides what [`Self::build_with_dialect`] checks, filter values are
+    /// compared to the `data_type` of the dimension they filter. Whatever
+    /// depends on the plan is checked with this builder's planner settings.
+    pub fn validate_request(
+        &self,
+        registry: &FlowRegistry,
+        request: &QueryRequest,
+    ) -> std::result::Result<(), Vec<SemaflowError>> {
+        let errors = dry_run::validate_request(self, registry, request);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Plan a request without rendering SQL: the chosen strategy, pruned
+    /// joins, CTE grains, measure re-aggregation and filter placement.
+    ///
+    /// Planning is dialect independent; use [`Self::explain_with_dialect`]
+    /// for the SQL as well.
+    pub fn explain(&self, registry: &FlowRegistry, request: &QueryRequest) -> Result<LogicalPlan> {
+        let flow = registry
+            .get_flow(&request.flow)
+            .ok_or_else(|| SemaflowError::UnknownFlow {
+                flow: request.flow.clone(),
+            })?;
+        let drilled = drill::apply_drill(flow, registry, request)?;
+        let limited = guardrails::enforce_limits(flow, registry, &drilled)?;
+        let request: &QueryRequest = &limited;
+        let base_request;
+        let planned = if request.calculations.is_empty() {
+            request
+        } else {
+            crate::calculations::validate(request)?;
+            base_request = crate::calculations::base_request(request);
+            &base_request
+        };
+        let (_, plan, _) = planner::build_query(
+            flow,
+            registry,
+            planned,
+            &QueryContext::default(),
+            self.planner.filtered_aggregates,
+            self.planner.distinct_sketches,
+            self.planner.symmetric_aggregates,
+        )?;
+        Ok(plan)
     }
 
     /// Build SQL by resolving the flow's data source to choose a dialect.
+    ///
+    /// Access policies of the flow's tables are applied for the user in `context`.
+    /// Statistics collected with [`ConnectionManager::collect_stats`] for the
+    /// flow's tables are used in planning.
     pub fn build_for_request(
         &self,
         registry: &FlowRegistry,
         connections: &ConnectionManager,
         request: &QueryRequest,
+        context: &QueryContext,
     ) -> Result<String> {
+        Ok(self
+            .explain_for_request(registry, connections, request, context)?
+            .sql)
+    }
+
+    /// Like [`Self::build_for_request`], but also returns planning diagnostics.
+    ///
+    /// When `connections` has a [`crate::config::SemaflowConfig`], its
+    /// planner settings for the flow's data source replace this builder's.
+    pub fn explain_for_request(
+        &self,
+        registry: &FlowRegistry,
+        connections: &ConnectionManager,
+        request: &QueryRequest,
+        context: &QueryContext,
+    ) -> Result<QueryExplain> {
         let flow = registry
             .get_flow(&request.flow)
-            .ok_or_else(|| SemaflowError::Validation(format!("unknown flow {}", request.flow)))?;
+            .ok_or_else(|| SemaflowError::UnknownFlow {
+                flow: request.flow.clone(),
+            })?;
         let base_table = registry
             .get_table(&flow.base_table.semantic_table)
             .ok_or_else(|| {
@@ -70,11 +335,28 @@ impl SqlBuilder {
                 ))
             })?;
         let data_source = connections.get(&base_table.data_source).ok_or_else(|| {
-            SemaflowError::Validation(format!(
-                "data source {} not registered",
-                base_table.data_source
-            ))
+            SemaflowError::UnknownDataSource {
+                data_source: base_table.data_source.clone(),
+            }
         })?;
-        self.build_with_dialect(registry, request, data_source.dialect())
+        let dialect = data_source.dialect();
+        let configured;
+        let builder = match connections.config() {
+            Some(config) => {
+                configured = SqlBuilder::with_planner(
+                    config.for_datasource(&base_table.data_source).planner,
+                );
+                &configured
+            }
+            None => self,
+        };
+        let stats = connections.flow_stats(registry, flow);
+        if stats.is_empty() {
+            return builder.explain_with_context(registry, request, context, dialect);
+        }
+        let mut registry = registry.clone();
+        registry.stats.extend(stats);
+        add_stat_keys(&mut registry);
+        builder.explain_with_context(&registry, request, context, dialect)
     }
 }
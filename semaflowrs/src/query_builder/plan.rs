@@ -6,7 +6,9 @@
 //! - Easier testing of plan generation
 //! - Future optimizations at the plan level
 
-use crate::sql_ast::{Join, OrderItem, SelectItem, SelectQuery, SqlExpr, SqlJoinType, TableRef};
+use crate::sql_ast::{
+    Join, OrderItem, SelectItem, SelectQuery, SqlExpr, SqlJoinType, TableRef, Unnest,
+};
 
 /// The top-level query plan, either flat or multi-grain pre-aggregated.
 #[derive(Debug, Clone)]
@@ -23,6 +25,7 @@ pub struct FlatPlan {
     pub from: TableRef,
     pub select: Vec<SelectItem>,
     pub joins: Vec<Join>,
+    pub unnests: Vec<Unnest>,
     pub filters: Vec<SqlExpr>,
     pub group_by: Vec<SqlExpr>,
     pub order_by: Vec<OrderItem>,
@@ -53,6 +56,8 @@ pub struct GrainedAggPlan {
     pub from: TableRef,
     /// Grain columns + aggregated measures.
     pub select: Vec<SelectItem>,
+    /// Unnested array dimensions of this table.
+    pub unnests: Vec<Unnest>,
     /// WHERE filters for this table.
     pub filters: Vec<SqlExpr>,
     /// The grain columns (GROUP BY).
@@ -70,6 +75,8 @@ pub struct FinalQueryPlan {
     pub cte_joins: Vec<CteJoin>,
     /// Joins to dimension tables (for non-measure dimensions).
     pub dimension_joins: Vec<Join>,
+    /// Unnested array dimensions of the dimension tables.
+    pub unnests: Vec<Unnest>,
     /// WHERE filters on the final query (e.g., filters on dimension-only tables).
     pub filters: Vec<SqlExpr>,
     /// GROUP BY expressions for re-aggregating from CTE grain to dimension grain.
@@ -112,6 +119,7 @@ impl FlatPlan {
             from,
             select: Vec::new(),
             joins: Vec::new(),
+            unnests: Vec::new(),
             filters: Vec::new(),
             group_by: Vec::new(),
             order_by: Vec::new(),
@@ -126,8 +134,10 @@ impl FlatPlan {
             select: self.select,
             from: self.from,
             joins: self.joins,
+            unnests: self.unnests,
             filters: self.filters,
             group_by: self.group_by,
+            grouping_sets: Vec::new(),
             order_by: self.order_by,
             limit: self.limit,
             offset: self.offset,
@@ -158,8 +168,10 @@ impl MultiGrainPlan {
                     select: cte.select,
                     from: cte.from,
                     joins: Vec::new(),
+                    unnests: cte.unnests,
                     filters: cte.filters,
                     group_by: cte.group_by,
+                    grouping_sets: Vec::new(),
                     order_by: Vec::new(),
                     limit: None,
                     offset: None,
@@ -178,6 +190,7 @@ impl MultiGrainPlan {
             name: String::new(),
             alias: Some(base_alias.clone()),
             subquery: Some(Box::new(base_query)),
+            sql: None,
         };
 
         let mut joins = Vec::new();
@@ -192,6 +205,7 @@ impl MultiGrainPlan {
                     name: String::new(),
                     alias: Some(cte_join.cte_alias.clone()),
                     subquery: subquery.map(Box::new),
+                    sql: None,
                 },
                 on: cte_join
                     .on
@@ -218,8 +232,10 @@ impl MultiGrainPlan {
             select: self.final_query.select,
             from: base_from,
             joins,
+            unnests: self.final_query.unnests,
             filters: self.final_query.filters,
             group_by: self.final_query.group_by,
+            grouping_sets: Vec::new(),
             order_by: self.final_query.order_by,
             limit: self.final_query.limit,
             offset: self.final_query.offset,
@@ -234,6 +250,7 @@ impl GrainedAggPlan {
             alias,
             from,
             select: Vec::new(),
+            unnests: Vec::new(),
             filters: Vec::new(),
             group_by: Vec::new(),
         }
@@ -248,6 +265,7 @@ impl FinalQueryPlan {
             select: Vec::new(),
             cte_joins: Vec::new(),
             dimension_joins: Vec::new(),
+            unnests: Vec::new(),
             filters: Vec::new(),
             group_by: Vec::new(),
             order_by: Vec::new(),
@@ -267,6 +285,7 @@ mod tests {
             name: "orders".to_string(),
             alias: Some("o".to_string()),
             subquery: None,
+            sql: None,
         });
         plan.select.push(SelectItem {
             expr: SqlExpr::Column {
@@ -295,6 +314,7 @@ mod tests {
                 name: "orders".to_string(),
                 alias: Some("o".to_string()),
                 subquery: None,
+                sql: None,
             },
         );
         let final_query = FinalQueryPlan::new("o_agg".to_string());
@@ -6,51 +6,186 @@
 use std::collections::HashSet;
 
 use crate::error::{Result, SemaflowError};
-use crate::flows::{Aggregation, QueryRequest, SemanticFlow};
+use crate::flows::{Aggregation, FanoutStrategy, QueryRequest, SemanticFlow, StrategyOverride};
+use crate::policy::QueryContext;
 use crate::registry::FlowRegistry;
-use crate::sql_ast::{SelectItem, SelectQuery, SqlExpr, SqlJoinType, TableRef};
+use crate::sql_ast::{
+    sanitize_alias, SelectItem, SelectQuery, SqlBinaryOperator, SqlExpr, SqlJoinType, TableRef,
+};
 
+use super::aggregates::{self, build_aggregate_plan};
 use super::analysis::{analyze_multi_grain, MultiGrainAnalysis};
+use super::bridge;
 use super::builders::{
     build_dimension_select, build_join, build_measure_selects, build_order_items,
-    build_preagg_measure_selects, build_preagg_order_items, validate_non_empty_select,
+    build_preagg_measure_selects, build_preagg_order_items, semantic_table_ref,
+    validate_non_empty_select,
+};
+use super::components::{resolve_components, MeasureStrategy, QueryComponents, ResolvedMeasure};
+use super::explain::{
+    flat_fanout_diagnostics, CtePlan, FanoutDiagnostic, FilterPlacement, LogicalPlan, PlanStrategy,
 };
-use super::components::{resolve_components, MeasureStrategy, QueryComponents};
-use super::filters::render_filter_expr;
+use super::filters::render_resolved_filter;
 use super::joins::select_required_joins;
+use super::measures::aggregate_input;
 use super::plan::{CteJoin, FinalQueryPlan, FlatPlan, GrainedAggPlan, MultiGrainPlan, QueryPlan};
-use super::render::expr_to_sql;
+use super::render::{expr_to_sql, join_condition_to_sql};
+use super::symmetric;
 
 /// Build a query from a flow and request.
 ///
 /// This is the main entry point for query building. It:
-/// 1. Resolves all components from the request
+/// 1. Resolves all components from the request, answering it from a summary
+///    table of the flow when one covers it, or flat when it reads across a
+///    many-to-many bridge
 /// 2. Analyzes for multi-grain pre-aggregation needs
 /// 3. Builds flat, multi-grain, or legacy pre-aggregated plan
 /// 4. Converts the plan to a SelectQuery
+///
+/// Also returns the logical plan and any fanout diagnostics for the plan.
+/// A request's `strategy` skips summary tables and forces a flat or
+/// pre-aggregated plan, failing when that plan would be wrong.
+/// With `distinct_sketches`, COUNT DISTINCT measures are pre-aggregated as
+/// sketches and merged in the final query of a multi-grain plan. Without
+/// `symmetric_aggregates`, flows asking for symmetric aggregates are
+/// pre-aggregated instead.
 pub fn build_query(
     flow: &SemanticFlow,
     registry: &FlowRegistry,
     request: &QueryRequest,
+    context: &QueryContext,
     supports_filtered_aggregates: bool,
-) -> Result<SelectQuery> {
+    distinct_sketches: bool,
+    symmetric_aggregates: bool,
+) -> Result<(SelectQuery, LogicalPlan, Vec<FanoutDiagnostic>)> {
     // Step 1: Resolve all components
-    let components = resolve_components(flow, registry, request, supports_filtered_aggregates)?;
+    let components = resolve_components(
+        flow,
+        registry,
+        request,
+        context,
+        supports_filtered_aggregates,
+    )?;
+
+    // Step 1b: Read a summary table instead when one covers the request
+    let routed = match request.strategy {
+        StrategyOverride::Auto => aggregates::route(
+            flow,
+            registry,
+            request,
+            &components,
+            supports_filtered_aggregates,
+        )?,
+        StrategyOverride::Flat | StrategyOverride::PreAggregate => None,
+    };
+    if let Some((name, components)) = routed {
+        let mut logical = LogicalPlan::new(flow, PlanStrategy::Aggregate);
+        logical.aggregate = Some(name);
+        let plan = build_aggregate_plan(&components, &mut logical)?;
+        return Ok((plan.into_select_query(), logical, Vec::new()));
+    }
+
+    // Step 1c: Measures read across a many-to-many bridge count each row
+    // once per group, in a flat query
+    if let Some((components, strategy)) = bridge::rewrite(&components) {
+        if request.strategy == StrategyOverride::PreAggregate {
+            return Err(SemaflowError::Validation(
+                "strategy 'preagg' cannot be used for measures read across a many-to-many \
+                 bridge, which are only counted correctly in a flat query"
+                    .to_string(),
+            ));
+        }
+        let mut logical = LogicalPlan::new(flow, strategy);
+        let (plan, _) = build_flat_plan(&components, flow, registry, &mut logical)?;
+        return Ok((plan.into_select_query(), logical, Vec::new()));
+    }
 
     // Step 2: Analyze for multi-grain pre-aggregation needs
     // This handles both multi-table measures AND single-table fanout risk
-    let mg_analysis = analyze_multi_grain(&components, flow)?;
+    let force_preagg = request.strategy == StrategyOverride::PreAggregate;
+    let mg_analysis = analyze_multi_grain(&components, flow, force_preagg)?;
 
     // Step 3: Build appropriate plan
-    let plan = if mg_analysis.needs_multi_grain {
+    let symmetric = match flow.fanout_strategy {
+        FanoutStrategy::Symmetric if symmetric_aggregates && !force_preagg => {
+            symmetric::rewrite(&components)
+        }
+        FanoutStrategy::Symmetric | FanoutStrategy::PreAggregate => None,
+    };
+    check_strategy_override(request.strategy, &mg_analysis, symmetric.is_some())?;
+    let (plan, logical, diagnostics) = if mg_analysis.needs_multi_grain && symmetric.is_none() {
         // Use new multi-grain path for both multi-table and single-table preagg
-        build_multi_grain_plan(&components, &mg_analysis, flow, registry)?
+        let mut logical = LogicalPlan::new(flow, PlanStrategy::PreAggregated);
+        let plan = build_multi_grain_plan(
+            &components,
+            &mg_analysis,
+            flow,
+            registry,
+            distinct_sketches,
+            &mut logical,
+        )?;
+        (plan, logical, Vec::new())
     } else {
-        build_flat_plan(&components, flow, registry)?
+        let mut logical = LogicalPlan::new(flow, PlanStrategy::Flat);
+        let (plan, diagnostics) = build_flat_plan(&components, flow, registry, &mut logical)?;
+        match symmetric {
+            // Joins repeat measure rows: aggregate over distinct keys instead
+            Some(symmetric) if mg_analysis.needs_multi_grain || !diagnostics.is_empty() => {
+                let mut logical = LogicalPlan::new(flow, PlanStrategy::Symmetric);
+                let (plan, _) = build_flat_plan(&symmetric, flow, registry, &mut logical)?;
+                (plan, logical, Vec::new())
+            }
+            _ => (plan, logical, diagnostics),
+        }
     };
 
+    for d in &diagnostics {
+        tracing::warn!(
+            flow = %flow.name,
+            join = %d.join_alias,
+            cardinality = ?d.cardinality,
+            hinted = d.hinted,
+            strategy = ?d.strategy,
+            "{}",
+            d.message
+        );
+    }
+
     // Step 4: Convert to SelectQuery
-    Ok(plan.into_select_query())
+    Ok((plan.into_select_query(), logical, diagnostics))
+}
+
+/// Reject a forced strategy the request can't be planned with: `flat` when
+/// joins would repeat measure rows that no symmetric rewrite corrects, and
+/// `preagg` when no measure has a join to be pre-aggregated below.
+fn check_strategy_override(
+    strategy: StrategyOverride,
+    analysis: &MultiGrainAnalysis,
+    symmetric: bool,
+) -> Result<()> {
+    match strategy {
+        StrategyOverride::Flat if analysis.needs_multi_grain && !symmetric => {
+            let mut joins: Vec<&str> = analysis
+                .cte_join_specs
+                .iter()
+                .map(|spec| spec.from_alias.as_str())
+                .collect();
+            joins.sort_unstable();
+            Err(SemaflowError::Validation(format!(
+                "strategy 'flat' is unsafe for this request: joins ({}) would repeat measure \
+                 rows before aggregation; use 'auto' or 'preagg'",
+                joins.join(", ")
+            )))
+        }
+        StrategyOverride::PreAggregate if !analysis.needs_multi_grain => {
+            Err(SemaflowError::Validation(
+                "strategy 'preagg' can't be applied to this request: its measures have no \
+                 joins to be pre-aggregated below"
+                    .to_string(),
+            ))
+        }
+        _ => Ok(()),
+    }
 }
 
 /// Build a flat query plan (standard SELECT with JOINs).
@@ -58,13 +193,24 @@ fn build_flat_plan(
     components: &QueryComponents,
     flow: &SemanticFlow,
     registry: &FlowRegistry,
-) -> Result<QueryPlan> {
+    logical: &mut LogicalPlan,
+) -> Result<(QueryPlan, Vec<FanoutDiagnostic>)> {
     let mut plan = FlatPlan::new(components.base_table.clone());
 
     // Collect required aliases for join pruning
     let mut required_aliases: HashSet<String> = HashSet::new();
     required_aliases.insert(components.base_alias.clone());
 
+    // Measures are read from their own table
+    for m in &components.measures {
+        required_aliases.insert(m.alias.clone());
+    }
+
+    for u in &components.unnests {
+        required_aliases.insert(u.alias.clone());
+        plan.unnests.push(u.unnest.clone());
+    }
+
     // Add dimension selects and group by
     for dim in &components.dimensions {
         required_aliases.insert(dim.alias.clone());
@@ -74,12 +220,17 @@ fn build_flat_plan(
 
     // Add filter expressions
     for f in &components.filters {
-        if let Some(alias) = &f.alias {
-            required_aliases.insert(alias.clone());
-        }
+        required_aliases.extend(f.aliases());
         plan.filters
-            .push(render_filter_expr(f.expr.clone(), &f.filter));
+            .push(render_resolved_filter(f, &SqlExpr::clone));
+        logical.add_filter(&f.condition, FilterPlacement::Where);
     }
+    plan.filters.extend(
+        components
+            .access_filters
+            .get(&components.base_alias)
+            .cloned(),
+    );
 
     // Add order by (also track aliases)
     for item in &components.order {
@@ -95,10 +246,21 @@ fn build_flat_plan(
     // Build required joins with pruning
     let alias_to_table_refs: std::collections::HashMap<String, &crate::flows::SemanticTable> =
         super::resolve::build_alias_map(flow, registry)?;
-    let required_joins = select_required_joins(flow, &required_aliases, &alias_to_table_refs)?;
+    let required_joins = select_required_joins(
+        flow,
+        &required_aliases,
+        &alias_to_table_refs,
+        &registry.stats,
+    )?;
+    let diagnostics = flat_fanout_diagnostics(components, &required_joins);
     for join in required_joins {
-        plan.joins
-            .push(build_join(join, &components.alias_to_table)?);
+        logical.include_join(&join.alias);
+        let mut built = build_join(join, &components.alias_to_table)?;
+        // Hidden rows of a joined table must not match, even on LEFT joins
+        built
+            .on
+            .extend(components.access_filters.get(&join.alias).cloned());
+        plan.joins.push(built);
     }
 
     // Add measure selects
@@ -108,10 +270,11 @@ fn build_flat_plan(
         true, // only requested
     )?;
     plan.select.extend(measure_selects);
+    logical.add_measures(components);
 
     validate_non_empty_select(&plan.select)?;
 
-    Ok(QueryPlan::Flat(plan))
+    Ok((QueryPlan::Flat(plan), diagnostics))
 }
 
 // ============================================================================
@@ -127,8 +290,21 @@ fn build_multi_grain_plan(
     analysis: &MultiGrainAnalysis,
     flow: &SemanticFlow,
     registry: &FlowRegistry,
+    distinct_sketches: bool,
+    logical: &mut LogicalPlan,
 ) -> Result<QueryPlan> {
     let base_alias = &components.base_alias;
+    // Placement of each request filter, reported in request order
+    let mut placements: Vec<Option<FilterPlacement>> = vec![None; components.filters.len()];
+
+    // Filters are placed per table below, so a group can't straddle tables
+    if components.has_cross_table_filters() {
+        return Err(SemaflowError::Validation(
+            "filter groups that combine fields from different tables are not supported \
+             when measures need pre-aggregation; split them into separate filters"
+                .to_string(),
+        ));
+    }
 
     // Build a lookup from alias -> join type for filter placement decisions
     // Base table has no join type; joined tables have INNER or LEFT
@@ -167,13 +343,24 @@ fn build_multi_grain_plan(
             SemaflowError::Validation(format!("missing semantic table for alias {}", alias))
         })?;
 
-        let from = TableRef {
-            name: table.table.clone(),
-            alias: Some(alias.clone()),
-            subquery: None,
-        };
+        let from = semantic_table_ref(table, alias);
 
         let mut cte = GrainedAggPlan::new(format!("{}_agg", alias), from);
+        logical.include_join(alias);
+        logical.ctes.push(CtePlan {
+            name: cte.alias.clone(),
+            alias: alias.clone(),
+            grain: grain.grain_columns.clone(),
+        });
+
+        // Each unnested element becomes part of the grain
+        cte.unnests.extend(
+            components
+                .unnests
+                .iter()
+                .filter(|u| u.alias == *alias)
+                .map(|u| u.unnest.clone()),
+        );
 
         // Track columns already added to avoid duplicates
         let mut added_columns: HashSet<String> = HashSet::new();
@@ -227,9 +414,20 @@ fn build_multi_grain_plan(
                         )));
                     }
                     MeasureStrategy::DistinctSafe => {
-                        // Skip CTE - will be calculated directly in final query
-                        // DISTINCT naturally handles fanout duplication
-                        continue;
+                        // Sketches of the distinct values merge correctly across
+                        // CTE rows, however the joins fan them out
+                        if let Some(sketch) = m
+                            .base_expr
+                            .as_ref()
+                            .filter(|_| distinct_sketches)
+                            .and_then(distinct_sketch)
+                        {
+                            cte.select.push(SelectItem {
+                                expr: sketch,
+                                alias: Some(measure_col_name),
+                            });
+                        }
+                        // Otherwise skip CTE - will be calculated directly in final query
                     }
                     MeasureStrategy::WeightedAverage => {
                         // AVG needs SUM and COUNT tracked separately
@@ -270,25 +468,26 @@ fn build_multi_grain_plan(
 
         // Add filters for this table - but only if it's the base table or uses INNER join.
         // LEFT join filters must go to outer query to preserve correct semantics.
-        for f in &components.filters {
+        for (i, f) in components.filters.iter().enumerate() {
             let is_base_table = alias == base_alias;
             let is_inner_join = join_type_lookup
                 .get(alias)
                 .is_some_and(|jt| matches!(jt, SqlJoinType::Inner));
 
             // Only add filter to CTE if base table or INNER join (early filter = optimization)
-            if is_base_table || is_inner_join {
-                if f.alias.as_deref() == Some(alias) {
-                    cte.filters
-                        .push(render_filter_expr(f.expr.clone(), &f.filter));
-                } else if is_base_table && f.alias.is_none() {
-                    // Base table gets unqualified filters
-                    cte.filters
-                        .push(render_filter_expr(f.expr.clone(), &f.filter));
-                }
+            // Base table also gets unqualified filters
+            if (is_base_table || is_inner_join)
+                && (f.alias.as_deref() == Some(alias) || (is_base_table && f.alias.is_none()))
+            {
+                cte.filters.push(render_resolved_filter(f, &SqlExpr::clone));
+                placements[i] = Some(FilterPlacement::Cte {
+                    name: cte.alias.clone(),
+                });
             }
             // LEFT join filters are handled later in the outer query
         }
+        cte.filters
+            .extend(components.access_filters.get(alias).cloned());
 
         cte_aliases.push(cte.alias.clone());
         ctes.push(cte);
@@ -345,14 +544,40 @@ fn build_multi_grain_plan(
         }
     }
 
+    // Unnests on dimension-only tables expand the joined rows
+    for u in &components.unnests {
+        if !analysis.table_grains.contains_key(&u.alias) {
+            dimension_join_aliases.insert(u.alias.clone());
+            final_query.unnests.push(u.unnest.clone());
+        }
+    }
+
+    // Filters on dimension-only tables are applied in the final query too
+    for f in &components.filters {
+        dimension_join_aliases.extend(
+            f.aliases()
+                .into_iter()
+                .filter(|alias| alias != base_alias && !analysis.table_grains.contains_key(alias)),
+        );
+    }
+
     // Add dimension table joins (tables without measures)
     if !dimension_join_aliases.is_empty() {
         let alias_to_table_refs = super::resolve::build_alias_map(flow, registry)?;
-        let required_joins =
-            select_required_joins(flow, &dimension_join_aliases, &alias_to_table_refs)?;
+        let required_joins = select_required_joins(
+            flow,
+            &dimension_join_aliases,
+            &alias_to_table_refs,
+            &registry.stats,
+        )?;
         for join in required_joins {
+            logical.include_join(&join.alias);
             // Remap join to reference CTE instead of base table
-            let remapped_join = remap_join_to_cte(join, &base_cte_alias, base_alias, components)?;
+            let mut remapped_join =
+                remap_join_to_cte(join, &base_cte_alias, base_alias, components)?;
+            remapped_join
+                .on
+                .extend(components.access_filters.get(&join.alias).cloned());
             final_query.dimension_joins.push(remapped_join);
         }
     }
@@ -360,7 +585,7 @@ fn build_multi_grain_plan(
     // Add filters to the final query:
     // 1. LEFT join table filters (must filter after join for correct semantics)
     // 2. Dimension-only table filters (tables without measures, not in CTEs)
-    for f in &components.filters {
+    for (i, f) in components.filters.iter().enumerate() {
         let filter_alias = f.alias.as_deref();
 
         if let Some(alias) = filter_alias {
@@ -371,20 +596,76 @@ fn build_multi_grain_plan(
 
             if is_left_join && is_in_cte {
                 // LEFT join table with measures - remap to CTE alias
-                let remapped_expr = remap_expr_to_cte(&f.expr, alias);
-                final_query
-                    .filters
-                    .push(render_filter_expr(remapped_expr, &f.filter));
+                final_query.filters.push(render_resolved_filter(f, &|expr| {
+                    remap_expr_to_cte(expr, alias)
+                }));
+                placements[i] = Some(FilterPlacement::FinalQuery);
             } else if !is_in_cte {
                 // Dimension-only table - use original expression
                 final_query
                     .filters
-                    .push(render_filter_expr(f.expr.clone(), &f.filter));
+                    .push(render_resolved_filter(f, &SqlExpr::clone));
+                placements[i] = Some(FilterPlacement::FinalQuery);
             }
             // Base table and INNER join filters already handled in CTEs
         }
     }
 
+    // COUNT DISTINCT without sketches: DISTINCT is immune to fanout, so
+    // count at the requested grain in a flat query over the flow's joins
+    // and join it back on the dimensions
+    let unsketched = |m: &&ResolvedMeasure| {
+        m.requested
+            && m.strategy == MeasureStrategy::DistinctSafe
+            && m.measure.post_expr.is_none()
+            && !(distinct_sketches && m.base_expr.as_ref().and_then(distinct_sketch).is_some())
+    };
+    let distinct_measures: Vec<ResolvedMeasure> = components
+        .measures
+        .iter()
+        .filter(unsketched)
+        .cloned()
+        .collect();
+    if !distinct_measures.is_empty() {
+        let distinct_components = QueryComponents {
+            measures: distinct_measures,
+            order: Vec::new(),
+            limit: None,
+            offset: None,
+            ..components.clone()
+        };
+        let mut scratch = LogicalPlan::new(flow, PlanStrategy::Flat);
+        let (distinct_plan, _) =
+            build_flat_plan(&distinct_components, flow, registry, &mut scratch)?;
+        let mut on: Vec<SqlExpr> = components
+            .dimensions
+            .iter()
+            .zip(&final_query.group_by)
+            .map(|(dim, expr)| {
+                null_safe_eq(
+                    expr.clone(),
+                    SqlExpr::Column {
+                        table: Some(DISTINCT_ALIAS.to_string()),
+                        name: sanitize_alias(&dim.name),
+                    },
+                )
+            })
+            .collect();
+        if on.is_empty() {
+            on.push(SqlExpr::Literal(serde_json::Value::Bool(true)));
+        }
+        final_query.dimension_joins.push(crate::sql_ast::Join {
+            join_type: SqlJoinType::Left,
+            table: TableRef {
+                name: String::new(),
+                alias: Some(DISTINCT_ALIAS.to_string()),
+                subquery: Some(Box::new(distinct_plan.into_select_query())),
+                sql: None,
+            },
+            on,
+        });
+    }
+
     // Add measure selects to final query with proper re-aggregation
     for m in &components.measures {
         if !m.requested {
@@ -466,13 +747,27 @@ fn build_multi_grain_plan(
                 }
             }
             MeasureStrategy::DistinctSafe => {
-                // COUNT DISTINCT - calculate directly on original table
-                // For now, use the base_expr which has the full aggregation
-                // TODO: This needs joining to the original table
-                if let Some(base_expr) = &m.base_expr {
-                    base_expr.clone()
-                } else {
-                    continue;
+                // COUNT DISTINCT → merge the CTE's sketches
+                match &m.base_expr {
+                    Some(base_expr)
+                        if distinct_sketches && distinct_sketch(base_expr).is_some() =>
+                    {
+                        SqlExpr::MergeSketches {
+                            expr: Box::new(SqlExpr::Column {
+                                table: Some(cte_alias),
+                                name: col_name.clone(),
+                            }),
+                        }
+                    }
+                    // Without sketches, take the matching row's distinct count
+                    Some(_) => SqlExpr::Aggregate {
+                        agg: Aggregation::Max,
+                        expr: Box::new(SqlExpr::Column {
+                            table: Some(DISTINCT_ALIAS.to_string()),
+                            name: sanitize_alias(&m.name),
+                        }),
+                    },
+                    None => continue,
                 }
             }
             MeasureStrategy::NonDecomposable => {
@@ -497,9 +792,48 @@ fn build_multi_grain_plan(
 
     validate_non_empty_select(&final_query.select)?;
 
+    logical.add_measures(components);
+    for (f, placement) in components.filters.iter().zip(placements) {
+        if let Some(placement) = placement {
+            logical.add_filter(&f.condition, placement);
+        }
+    }
+
     Ok(QueryPlan::MultiGrain(MultiGrainPlan { ctes, final_query }))
 }
 
+/// Alias of the subquery computing distinct counts at the requested grain.
+const DISTINCT_ALIAS: &str = "__distinct";
+
+/// `left = right`, also matching when both are NULL.
+fn null_safe_eq(left: SqlExpr, right: SqlExpr) -> SqlExpr {
+    let is_null = |expr: &SqlExpr| SqlExpr::IsNull {
+        expr: Box::new(expr.clone()),
+        negated: false,
+    };
+    SqlExpr::BinaryOp {
+        op: SqlBinaryOperator::Or,
+        left: Box::new(SqlExpr::BinaryOp {
+            op: SqlBinaryOperator::Eq,
+            left: Box::new(left.clone()),
+            right: Box::new(right.clone()),
+        }),
+        right: Box::new(SqlExpr::BinaryOp {
+            op: SqlBinaryOperator::And,
+            left: Box::new(is_null(&left)),
+            right: Box::new(is_null(&right)),
+        }),
+    }
+}
+
+/// A distinct-values sketch of a COUNT DISTINCT measure's input, with the
+/// measure filter folded in so filtered-out rows add NULLs.
+fn distinct_sketch(base_expr: &SqlExpr) -> Option<SqlExpr> {
+    Some(SqlExpr::DistinctSketch {
+        expr: Box::new(aggregate_input(base_expr)?),
+    })
+}
+
 /// Remap a join to reference a CTE instead of the base table.
 fn remap_join_to_cte(
     join: &crate::flows::FlowJoin,
@@ -512,7 +846,7 @@ fn remap_join_to_cte(
     })?;
 
     // Build ON clause - remap base table references to CTE
-    let on_clause: Vec<SqlExpr> = join
+    let mut on_clause: Vec<SqlExpr> = join
         .join_keys
         .iter()
         .map(|k| {
@@ -534,14 +868,19 @@ fn remap_join_to_cte(
             }
         })
         .collect();
+    on_clause.extend(join.extra_conditions.as_ref().map(|expr| {
+        join_condition_to_sql(expr, &join.alias, &|table| {
+            if table == base_alias {
+                cte_alias.to_string()
+            } else {
+                table.to_string()
+            }
+        })
+    }));
 
     Ok(crate::sql_ast::Join {
         join_type: join.join_type.clone().into(),
-        table: TableRef {
-            name: join_table.table.clone(),
-            alias: Some(join.alias.clone()),
-            subquery: None,
-        },
+        table: semantic_table_ref(join_table, &join.alias),
         on: on_clause,
     })
 }
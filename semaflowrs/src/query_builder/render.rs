@@ -1,30 +1,82 @@
-use crate::flows::{BinaryOp, Expr};
+use crate::flows::{BinaryOp, Expr, Function};
 use crate::sql_ast::{SqlBinaryOperator, SqlExpr};
 
 pub(crate) fn expr_to_sql(expr: &Expr, alias: &str) -> SqlExpr {
+    to_sql(expr, alias, None, None)
+}
+
+/// Like [`expr_to_sql`], with the timestamps each `date_trunc` truncates
+/// first converted to wall-clock time in the IANA `zone`.
+pub(crate) fn expr_to_local_sql(expr: &Expr, alias: &str, zone: Option<&str>) -> SqlExpr {
+    to_sql(expr, alias, zone, None)
+}
+
+/// A join's `extra_conditions` on `alias`: qualified columns (`o.created_at`)
+/// read the relation `table_for` their qualifier, bare ones the joined table.
+pub(crate) fn join_condition_to_sql(
+    expr: &Expr,
+    alias: &str,
+    table_for: &dyn Fn(&str) -> String,
+) -> SqlExpr {
+    to_sql(expr, alias, None, Some(table_for))
+}
+
+fn to_sql(
+    expr: &Expr,
+    alias: &str,
+    zone: Option<&str>,
+    qualify: Option<&dyn Fn(&str) -> String>,
+) -> SqlExpr {
     match expr {
-        Expr::Column { column } => SqlExpr::Column {
-            table: Some(alias.to_string()),
-            name: column.clone(),
+        Expr::Column { column } => match (column.split_once('.'), qualify) {
+            (Some((table, name)), Some(qualify)) => SqlExpr::Column {
+                table: Some(qualify(table)),
+                name: name.to_string(),
+            },
+            _ => SqlExpr::Column {
+                table: Some(alias.to_string()),
+                name: column.clone(),
+            },
         },
         Expr::Literal { value } => SqlExpr::Literal(value.clone()),
         Expr::MeasureRef { name } => SqlExpr::Column {
             table: None,
             name: name.clone(),
         },
-        Expr::Func { func, args } => SqlExpr::Function {
-            func: func.clone(),
-            args: args.iter().map(|a| expr_to_sql(a, alias)).collect(),
-        },
+        Expr::Func { func, args } => {
+            let args = match (func, zone) {
+                // Inner truncations stay as they are: their result is already local
+                (Function::DateTrunc(_), Some(zone)) => args
+                    .iter()
+                    .map(|a| SqlExpr::AtTimeZone {
+                        expr: Box::new(to_sql(a, alias, None, qualify)),
+                        zone: zone.to_string(),
+                    })
+                    .collect(),
+                _ => args
+                    .iter()
+                    .map(|a| to_sql(a, alias, zone, qualify))
+                    .collect(),
+            };
+            SqlExpr::Function {
+                func: func.clone(),
+                args,
+            }
+        }
         Expr::Case {
             branches,
             else_expr,
         } => SqlExpr::Case {
             branches: branches
                 .iter()
-                .map(|b| (expr_to_sql(&b.when, alias), expr_to_sql(&b.then, alias)))
+                .map(|b| {
+                    (
+                        to_sql(&b.when, alias, zone, qualify),
+                        to_sql(&b.then, alias, zone, qualify),
+                    )
+                })
                 .collect(),
-            else_expr: Box::new(expr_to_sql(else_expr, alias)),
+            else_expr: Box::new(to_sql(else_expr, alias, zone, qualify)),
         },
         Expr::Binary { op, left, right } => {
             let op = match op {
@@ -44,10 +96,45 @@ pub(crate) fn expr_to_sql(expr: &Expr, alias: &str) -> SqlExpr {
             };
             SqlExpr::BinaryOp {
                 op,
-                left: Box::new(expr_to_sql(left, alias)),
-                right: Box::new(expr_to_sql(right, alias)),
+                left: Box::new(to_sql(left, alias, zone, qualify)),
+                right: Box::new(to_sql(right, alias, zone, qualify)),
             }
         }
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => SqlExpr::InList {
+            expr: Box::new(to_sql(expr, alias, zone, qualify)),
+            list: list
+                .iter()
+                .map(|item| to_sql(item, alias, zone, qualify))
+                .collect(),
+            negated: *negated,
+        },
+        Expr::IsNull { expr, negated } => SqlExpr::IsNull {
+            expr: Box::new(to_sql(expr, alias, zone, qualify)),
+            negated: *negated,
+        },
+        Expr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => SqlExpr::Between {
+            expr: Box::new(to_sql(expr, alias, zone, qualify)),
+            low: Box::new(to_sql(low, alias, zone, qualify)),
+            high: Box::new(to_sql(high, alias, zone, qualify)),
+            negated: *negated,
+        },
+        Expr::Not { expr } => SqlExpr::Not {
+            expr: Box::new(to_sql(expr, alias, zone, qualify)),
+        },
+        Expr::Param { name } => SqlExpr::Param { name: name.clone() },
+        Expr::Sql { sql } => SqlExpr::Raw {
+            sql: sql.clone(),
+            table: Some(alias.to_string()),
+        },
     }
 }
 
@@ -109,5 +196,40 @@ pub(crate) fn render_post_expr(
                 right: Box::new(render_post_expr(right, measure_resolver)?),
             })
         }
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => Ok(SqlExpr::InList {
+            expr: Box::new(render_post_expr(expr, measure_resolver)?),
+            list: list
+                .iter()
+                .map(|item| render_post_expr(item, measure_resolver))
+                .collect::<crate::error::Result<Vec<_>>>()?,
+            negated: *negated,
+        }),
+        Expr::IsNull { expr, negated } => Ok(SqlExpr::IsNull {
+            expr: Box::new(render_post_expr(expr, measure_resolver)?),
+            negated: *negated,
+        }),
+        Expr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => Ok(SqlExpr::Between {
+            expr: Box::new(render_post_expr(expr, measure_resolver)?),
+            low: Box::new(render_post_expr(low, measure_resolver)?),
+            high: Box::new(render_post_expr(high, measure_resolver)?),
+            negated: *negated,
+        }),
+        Expr::Not { expr } => Ok(SqlExpr::Not {
+            expr: Box::new(render_post_expr(expr, measure_resolver)?),
+        }),
+        Expr::Param { name } => Ok(SqlExpr::Param { name: name.clone() }),
+        Expr::Sql { sql } => Ok(SqlExpr::Raw {
+            sql: sql.clone(),
+            table: None,
+        }),
     }
 }
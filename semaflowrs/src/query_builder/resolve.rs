@@ -1,16 +1,17 @@
 use std::collections::HashMap;
 
-use crate::error::{Result, SemaflowError};
-use crate::flows::{SemanticFlow, SemanticTable};
+use crate::error::{self, Result, SemaflowError};
+use crate::flows::{Dimension, Expr, FilterExpr, SemanticFlow, SemanticTable};
 use crate::registry::FlowRegistry;
 use crate::sql_ast::SqlExpr;
 
-use super::render::expr_to_sql;
+use super::render::expr_to_local_sql;
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum FieldKind {
     Dimension,
     Measure,
+    Segment,
 }
 
 pub(crate) fn build_alias_map<'a>(
@@ -45,9 +46,12 @@ pub(crate) fn resolve_dimension<'a>(
 ) -> Result<(&'a SemanticTable, String, &'a crate::flows::Dimension)> {
     match resolve_dimension_inner(name, flow, registry, alias_map)? {
         Some(found) => Ok(found),
-        None => Err(SemaflowError::Validation(format!(
-            "unknown dimension {name}"
-        ))),
+        None => Err(SemaflowError::UnknownField {
+            kind: error::FieldKind::Dimension,
+            field: name.to_string(),
+            flow: flow.name.clone(),
+            suggestions: suggest_fields(name, flow, alias_map, FieldKind::Dimension),
+        }),
     }
 }
 
@@ -59,7 +63,12 @@ pub(crate) fn resolve_measure<'a>(
 ) -> Result<(&'a SemanticTable, String, &'a crate::flows::Measure)> {
     match resolve_measure_inner(name, flow, registry, alias_map)? {
         Some(found) => Ok(found),
-        None => Err(SemaflowError::Validation(format!("unknown measure {name}"))),
+        None => Err(SemaflowError::UnknownField {
+            kind: error::FieldKind::Measure,
+            field: name.to_string(),
+            flow: flow.name.clone(),
+            suggestions: suggest_fields(name, flow, alias_map, FieldKind::Measure),
+        }),
     }
 }
 
@@ -69,7 +78,8 @@ pub(crate) fn resolve_dimension_inner<'a>(
     registry: &'a FlowRegistry,
     alias_map: &HashMap<String, &'a SemanticTable>,
 ) -> Result<Option<(&'a SemanticTable, String, &'a crate::flows::Dimension)>> {
-    if let Some((alias, field)) = parse_qualified(name) {
+    if let Some((qualifier, field)) = parse_qualified(name) {
+        let alias = flow.role_alias(qualifier);
         if alias == flow.base_table.alias {
             if let Some(base_table) = registry.get_table(&flow.base_table.semantic_table) {
                 if let Some(dim) = base_table.dimensions.get(field) {
@@ -100,23 +110,140 @@ pub(crate) fn resolve_dimension_inner<'a>(
     }
 
     if matches.len() > 1 {
-        let aliases: Vec<String> = matches.iter().map(|(_, alias, _)| alias.clone()).collect();
-        return Err(SemaflowError::Validation(format!(
-            "ambiguous dimension {name}; found on aliases {}",
-            aliases.join(", ")
-        )));
+        return Err(SemaflowError::AmbiguousField {
+            kind: error::FieldKind::Dimension,
+            field: name.to_string(),
+            candidates: matches
+                .iter()
+                .map(|(_, alias, _)| flow.qualifier(alias).to_string())
+                .collect(),
+        });
     }
 
     Ok(matches.into_iter().next())
 }
 
+/// A segment requested by name.
+pub(crate) enum Segment<'a> {
+    /// One of the flow's `segments`, over its fields.
+    Flow(&'a FilterExpr),
+    /// One of a table's `segments`, over the columns of `alias`.
+    Table { alias: String, expr: &'a Expr },
+}
+
+/// Resolve a segment name: the flow's own first, then a table's, qualified
+/// as `alias.segment` or unique among the flow's tables.
+pub(crate) fn resolve_segment<'a>(
+    name: &str,
+    flow: &'a SemanticFlow,
+    alias_map: &HashMap<String, &'a SemanticTable>,
+) -> Result<Segment<'a>> {
+    if let Some(filter) = flow.segments.get(name) {
+        return Ok(Segment::Flow(filter));
+    }
+    let mut matches = Vec::new();
+    if let Some((qualifier, segment)) = parse_qualified(name) {
+        let alias = flow.role_alias(qualifier);
+        if let Some(expr) = alias_map.get(alias).and_then(|t| t.segments.get(segment)) {
+            matches.push((alias.to_string(), expr));
+        }
+    } else {
+        let aliases =
+            std::iter::once(&flow.base_table.alias).chain(flow.joins.values().map(|j| &j.alias));
+        for alias in aliases {
+            if let Some(expr) = alias_map.get(alias).and_then(|t| t.segments.get(name)) {
+                matches.push((alias.clone(), expr));
+            }
+        }
+    }
+    if matches.len() > 1 {
+        return Err(SemaflowError::AmbiguousField {
+            kind: error::FieldKind::Segment,
+            field: name.to_string(),
+            candidates: matches
+                .iter()
+                .map(|(alias, _)| flow.qualifier(alias).to_string())
+                .collect(),
+        });
+    }
+    match matches.pop() {
+        Some((alias, expr)) => Ok(Segment::Table { alias, expr }),
+        None => Err(SemaflowError::UnknownField {
+            kind: error::FieldKind::Segment,
+            field: name.to_string(),
+            flow: flow.name.clone(),
+            suggestions: {
+                let mut names = field_names(name, flow, alias_map, FieldKind::Segment);
+                names.extend(flow.segments.keys().cloned());
+                closest(name, names)
+            },
+        }),
+    }
+}
+
+/// Resolve a field naming a table's `time_dimension`, either directly by
+/// column or through a dimension that selects that column unchanged.
+/// Returns the table, its alias, and the raw time column expression.
+pub(crate) fn resolve_time_dimension<'a>(
+    name: &str,
+    flow: &'a SemanticFlow,
+    alias_map: &HashMap<String, &'a SemanticTable>,
+) -> Result<(&'a SemanticTable, String, Expr)> {
+    let is_time_column = |table: &SemanticTable, field: &str| {
+        let Some(column) = table.time_dimension.as_deref() else {
+            return false;
+        };
+        field == column
+            || matches!(
+                table.dimensions.get(field).map(|d| &d.expr),
+                Some(Expr::Column { column: c }) if c == column
+            )
+    };
+    let candidates: Vec<(&str, &str)> = match parse_qualified(name) {
+        Some((qualifier, field)) => vec![(flow.role_alias(qualifier), field)],
+        None => std::iter::once(flow.base_table.alias.as_str())
+            .chain(flow.joins.values().map(|j| j.alias.as_str()))
+            .map(|alias| (alias, name))
+            .collect(),
+    };
+    let mut matches = Vec::new();
+    for (alias, field) in candidates {
+        if let Some(table) = alias_map.get(alias) {
+            if is_time_column(table, field) {
+                matches.push((*table, alias.to_string()));
+            }
+        }
+    }
+    if matches.len() > 1 {
+        return Err(SemaflowError::AmbiguousField {
+            kind: error::FieldKind::TimeDimension,
+            field: name.to_string(),
+            candidates: matches
+                .iter()
+                .map(|(_, alias)| flow.qualifier(alias).to_string())
+                .collect(),
+        });
+    }
+    let Some((table, alias)) = matches.into_iter().next() else {
+        return Err(SemaflowError::Validation(format!(
+            "grain can only be applied to a table's time_dimension; {name} is not one"
+        )));
+    };
+    let column = table
+        .time_dimension
+        .clone()
+        .expect("matched tables declare a time_dimension");
+    Ok((table, alias, Expr::Column { column }))
+}
+
 pub(crate) fn resolve_measure_inner<'a>(
     name: &str,
     flow: &'a SemanticFlow,
     registry: &'a FlowRegistry,
     alias_map: &HashMap<String, &'a SemanticTable>,
 ) -> Result<Option<(&'a SemanticTable, String, &'a crate::flows::Measure)>> {
-    if let Some((alias, field)) = parse_qualified(name) {
+    if let Some((qualifier, field)) = parse_qualified(name) {
+        let alias = flow.role_alias(qualifier);
         if alias == flow.base_table.alias {
             if let Some(base_table) = registry.get_table(&flow.base_table.semantic_table) {
                 if let Some(measure) = base_table.measures.get(field) {
@@ -147,24 +274,55 @@ pub(crate) fn resolve_measure_inner<'a>(
     }
 
     if matches.len() > 1 {
-        let aliases: Vec<String> = matches.iter().map(|(_, alias, _)| alias.clone()).collect();
-        return Err(SemaflowError::Validation(format!(
-            "ambiguous measure {name}; found on aliases {}",
-            aliases.join(", ")
-        )));
+        return Err(SemaflowError::AmbiguousField {
+            kind: error::FieldKind::Measure,
+            field: name.to_string(),
+            candidates: matches
+                .iter()
+                .map(|(_, alias, _)| flow.qualifier(alias).to_string())
+                .collect(),
+        });
     }
 
     Ok(matches.into_iter().next())
 }
 
+/// The time zone truncations of `table`'s `dimension` render in: `zone`, the
+/// request's or flow's override, else the table's. `None` for dimensions
+/// declared `data_type: date`, which carry no time of day.
+pub(crate) fn truncation_zone<'a>(
+    zone: Option<&'a str>,
+    table: &'a SemanticTable,
+    dimension: Option<&Dimension>,
+) -> Option<&'a str> {
+    let is_date = dimension
+        .and_then(|d| d.data_type.as_deref())
+        .is_some_and(|t| t.eq_ignore_ascii_case("date"));
+    if is_date {
+        return None;
+    }
+    zone.or(table.timezone.as_deref())
+}
+
+/// `name`'s expression, with dimension truncations in their
+/// [`truncation_zone`] given the request or flow override `zone`.
 pub(crate) fn resolve_field_expression(
     name: &str,
     flow: &SemanticFlow,
     registry: &FlowRegistry,
     alias_map: &HashMap<String, &SemanticTable>,
+    zone: Option<&str>,
 ) -> Result<(SqlExpr, FieldKind, Option<String>)> {
-    if let Some((_, alias, dim)) = resolve_dimension_inner(name, flow, registry, alias_map)? {
-        let expr = expr_to_sql(&dim.expr, &alias);
+    if let Some((table, alias, dim)) = resolve_dimension_inner(name, flow, registry, alias_map)? {
+        if dim.unnest {
+            return Ok((
+                unnest_element(&alias, name),
+                FieldKind::Dimension,
+                Some(alias),
+            ));
+        }
+        let zone = truncation_zone(zone, table, Some(dim));
+        let expr = expr_to_local_sql(&dim.labelled_expr(), &alias, zone);
         return Ok((expr, FieldKind::Dimension, Some(alias)));
     }
     if let Some((_, alias, _)) = resolve_measure_inner(name, flow, registry, alias_map)? {
@@ -177,10 +335,113 @@ pub(crate) fn resolve_field_expression(
             Some(alias),
         ));
     }
-    Err(SemaflowError::Validation(format!(
-        "field {name} not found in flow {}",
-        flow.name
-    )))
+    Err(SemaflowError::UnknownField {
+        kind: error::FieldKind::Field,
+        field: name.to_string(),
+        flow: flow.name.clone(),
+        suggestions: {
+            let mut names = field_names(name, flow, alias_map, FieldKind::Dimension);
+            names.extend(field_names(name, flow, alias_map, FieldKind::Measure));
+            closest(name, names)
+        },
+    })
+}
+
+/// Name of the column holding the elements of the unnested dimension
+/// `name` on `alias`: `<alias>__<field>`.
+pub(crate) fn unnest_alias(alias: &str, name: &str) -> String {
+    let field = name.rsplit('.').next().unwrap_or(name);
+    format!("{alias}__{field}")
+}
+
+/// Reference to the elements of the unnested dimension `name` on `alias`.
+pub(crate) fn unnest_element(alias: &str, name: &str) -> SqlExpr {
+    SqlExpr::Column {
+        table: None,
+        name: unnest_alias(alias, name),
+    }
+}
+
+/// Maximum number of "did you mean" suggestions attached to an error.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Fields of `kind` in the flow whose names are close to the unknown `name`,
+/// closest first.
+fn suggest_fields(
+    name: &str,
+    flow: &SemanticFlow,
+    alias_map: &HashMap<String, &SemanticTable>,
+    kind: FieldKind,
+) -> Vec<String> {
+    closest(name, field_names(name, flow, alias_map, kind))
+}
+
+/// Names of the flow's fields of `kind`, qualified as `alias.field` when
+/// `name` is qualified.
+fn field_names(
+    name: &str,
+    flow: &SemanticFlow,
+    alias_map: &HashMap<String, &SemanticTable>,
+    kind: FieldKind,
+) -> Vec<String> {
+    let qualified = parse_qualified(name).is_some();
+    let aliases =
+        std::iter::once(&flow.base_table.alias).chain(flow.joins.values().map(|j| &j.alias));
+    let mut names = Vec::new();
+    for alias in aliases {
+        let Some(table) = alias_map.get(alias) else {
+            continue;
+        };
+        let fields: Vec<&String> = match kind {
+            FieldKind::Dimension => table.dimensions.keys().collect(),
+            FieldKind::Measure => table.measures.keys().collect(),
+            FieldKind::Segment => table.segments.keys().collect(),
+        };
+        for field in fields {
+            names.push(if qualified {
+                format!("{alias}.{field}")
+            } else {
+                field.clone()
+            });
+        }
+    }
+    names
+}
+
+/// Candidates within a typo's distance of `name`, ordered by edit distance
+/// and then alphabetically.
+fn closest(name: &str, candidates: Vec<String>) -> Vec<String> {
+    let needle = name.to_lowercase();
+    let max_distance = (needle.chars().count() / 3).max(1);
+    let mut scored: Vec<(usize, String)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = levenshtein(&needle, &candidate.to_lowercase());
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .collect();
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
 }
 
 pub(crate) fn parse_qualified(name: &str) -> Option<(&str, &str)> {
@@ -191,3 +452,34 @@ pub(crate) fn parse_qualified(name: &str) -> Option<(&str, &str)> {
     }
     Some((alias, field))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_counts_edits() {
+        assert_eq!(levenshtein("revenue", "revenue"), 0);
+        assert_eq!(levenshtein("revenu", "revenue"), 1);
+        assert_eq!(levenshtein("cnt", "count"), 2);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn closest_ranks_near_names_and_drops_far_ones() {
+        let candidates = vec![
+            "order_total".to_string(),
+            "order_count".to_string(),
+            "customer_name".to_string(),
+        ];
+        assert_eq!(
+            closest("order_totl", candidates.clone()),
+            vec!["order_total".to_string()]
+        );
+        assert_eq!(
+            closest("ORDER_COUNT", candidates.clone()),
+            vec!["order_count".to_string()]
+        );
+        assert!(closest("zzz", candidates).is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+//! Table statistics collected from the warehouse.
+//!
+//! [`crate::backends::ConnectionManager::collect_stats`] counts the rows of
+//! each physical table and the distinct values of its key columns. The
+//! planner reads them from [`FlowRegistry::stats`]: a column with as many
+//! distinct values as the table has rows is treated as a unique key, which
+//! lets joins on it be pruned and measures be aggregated flat, and row
+//! counts order the joins of a query (see [`super::joins`]).
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+
+use crate::dialect::Dialect;
+use crate::error::{Result, SemaflowError};
+use crate::executor::QueryResult;
+use crate::flows::SemanticTable;
+use crate::registry::FlowRegistry;
+use crate::schema_cache::TableStats;
+
+/// Key columns of `table` worth counting distinct values of: its primary
+/// and unique keys, the columns of its entities and the columns joins of
+/// any flow match on it.
+pub(crate) fn stats_columns(registry: &FlowRegistry, table: &SemanticTable) -> Vec<String> {
+    let mut columns: BTreeSet<String> = table.primary_keys.iter().cloned().collect();
+    columns.extend(table.unique_keys.iter().flatten().cloned());
+    columns.extend(
+        table
+            .entities
+            .iter()
+            .map(|(name, entity)| entity.column(name).to_string()),
+    );
+    for flow in registry.flows.values() {
+        if flow.base_table.semantic_table == table.name {
+            columns.extend(
+                flow.joins
+                    .values()
+                    .filter(|join| join.to_table == flow.base_table.alias)
+                    .flat_map(|join| join.join_keys.iter().map(|key| key.left.clone())),
+            );
+        }
+        for join in flow.joins.values() {
+            if join.semantic_table == table.name {
+                columns.extend(join.join_keys.iter().map(|key| key.right.clone()));
+            }
+            let to_table = flow.joins.get(&join.to_table).map(|j| &j.semantic_table);
+            if to_table == Some(&table.name) {
+                columns.extend(join.join_keys.iter().map(|key| key.left.clone()));
+            }
+        }
+    }
+    columns.into_iter().collect()
+}
+
+/// A query counting the rows of `table` and the distinct values of each of
+/// `columns`, aliased `ndv_<index>`.
+pub(crate) fn stats_sql(dialect: &dyn Dialect, table: &str, columns: &[String]) -> String {
+    let mut select = vec![format!("COUNT(*) AS {}", dialect.quote_ident("row_count"))];
+    select.extend(columns.iter().enumerate().map(|(i, column)| {
+        format!(
+            "COUNT(DISTINCT {}) AS {}",
+            dialect.quote_ident(column),
+            dialect.quote_ident(&format!("ndv_{i}"))
+        )
+    }));
+    format!(
+        "SELECT {} FROM {}",
+        select.join(", "),
+        dialect.qualify_table(table)
+    )
+}
+
+/// Read the single row returned by [`stats_sql`].
+pub(crate) fn parse_stats(
+    table: &str,
+    columns: &[String],
+    result: &QueryResult,
+) -> Result<TableStats> {
+    let row = result.rows.first().ok_or_else(|| {
+        SemaflowError::Execution(format!("statistics query for {table} returned no rows"))
+    })?;
+    let count = |key: &str| {
+        row.get(key).and_then(count_value).ok_or_else(|| {
+            SemaflowError::Execution(format!(
+                "statistics query for {table} returned no count for {key}"
+            ))
+        })
+    };
+    let mut stats = TableStats {
+        row_count: count("row_count")?,
+        ..TableStats::default()
+    };
+    for (i, column) in columns.iter().enumerate() {
+        stats
+            .distinct_counts
+            .insert(column.clone(), count(&format!("ndv_{i}"))?);
+    }
+    Ok(stats)
+}
+
+/// Counts arrive as numbers, or as strings from backends returning
+/// INT64 as text.
+fn count_value(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The registry with every column its statistics show to be unique added
+/// to its table's `unique_keys`; borrowed unchanged when none is missing.
+pub(crate) fn with_stat_keys(registry: &FlowRegistry) -> Cow<'_, FlowRegistry> {
+    let missing = registry.stats.iter().any(|(name, stats)| {
+        registry
+            .get_table(name)
+            .is_some_and(|table| stats.unique_columns().any(|c| !has_key(table, c)))
+    });
+    if !missing {
+        return Cow::Borrowed(registry);
+    }
+    let mut registry = registry.clone();
+    add_stat_keys(&mut registry);
+    Cow::Owned(registry)
+}
+
+/// Add the unique columns of [`FlowRegistry::stats`] to the tables'
+/// `unique_keys`.
+pub(crate) fn add_stat_keys(registry: &mut FlowRegistry) {
+    for (name, stats) in &registry.stats {
+        let Some(table) = registry.tables.get_mut(name) else {
+            continue;
+        };
+        for column in stats.unique_columns() {
+            if !has_key(table, column) {
+                table.unique_keys.push(vec![column.to_string()]);
+            }
+        }
+    }
+}
+
+fn has_key(table: &SemanticTable, column: &str) -> bool {
+    table
+        .unique_keys
+        .iter()
+        .any(|key| key.len() == 1 && key[0] == column)
+}
@@ -0,0 +1,97 @@
+//! Symmetric aggregates: fanout-safe measures in a flat query.
+//!
+//! A join that repeats a table's rows repeats each row with its primary
+//! key, so aggregating over distinct keys counts every source row once:
+//! sums become `SUM(DISTINCT hash(key) * K + x) - SUM(DISTINCT hash(key) * K)`
+//! (see [`crate::dialect::Dialect::render_symmetric_sum`]), counts become
+//! `COUNT(DISTINCT CASE WHEN x IS NOT NULL THEN key END)` and averages their
+//! ratio. Minimums, maximums, distinct counts and boolean aggregates ignore
+//! repeated rows already.
+//!
+//! Flows opt in with `fanout_strategy: symmetric`. A request is rewritten
+//! when every measure is a simple or derived one over such aggregations, on
+//! a table with a single-column primary key; others are pre-aggregated.
+
+use std::collections::HashMap;
+
+use crate::flows::{Aggregation, Function};
+use crate::sql_ast::SqlExpr;
+
+use super::components::QueryComponents;
+use super::measures::aggregate_input;
+
+/// The components with every simple measure made fanout-safe, or `None`
+/// when a measure cannot be.
+pub(crate) fn rewrite(components: &QueryComponents) -> Option<QueryComponents> {
+    let mut measures = Vec::new();
+    let mut base_measure_exprs = HashMap::new();
+    for m in &components.measures {
+        let mut m = m.clone();
+        if m.measure.is_formula() {
+            return None;
+        }
+        if let Some(base_expr) = &m.base_expr {
+            let table = components.alias_to_table.get(&m.alias)?;
+            let [primary_key] = table.primary_keys.as_slice() else {
+                return None;
+            };
+            let key = SqlExpr::Column {
+                table: Some(m.alias.clone()),
+                name: primary_key.clone(),
+            };
+            let expr = symmetric_expr(m.measure.agg.as_ref()?, base_expr, key)?;
+            let unqualified = m.name.rsplit('.').next().unwrap_or(&m.name);
+            base_measure_exprs.insert(m.name.clone(), expr.clone());
+            base_measure_exprs
+                .entry(unqualified.to_string())
+                .or_insert_with(|| expr.clone());
+            base_measure_exprs
+                .entry(format!("{}.{unqualified}", m.alias))
+                .or_insert_with(|| expr.clone());
+            m.base_expr = Some(expr);
+        }
+        measures.push(m);
+    }
+    Some(QueryComponents {
+        measures,
+        base_measure_exprs,
+        ..components.clone()
+    })
+}
+
+/// `base_expr`, an `agg` aggregate, computed once per distinct `key`.
+fn symmetric_expr(agg: &Aggregation, base_expr: &SqlExpr, key: SqlExpr) -> Option<SqlExpr> {
+    let input = aggregate_input(base_expr)?;
+    let keys_where = |condition: SqlExpr| SqlExpr::Aggregate {
+        agg: Aggregation::CountDistinct,
+        expr: Box::new(SqlExpr::Case {
+            branches: vec![(condition, key.clone())],
+            else_expr: Box::new(SqlExpr::Literal(serde_json::Value::Null)),
+        }),
+    };
+    let not_null = |expr: &SqlExpr| SqlExpr::IsNull {
+        expr: Box::new(expr.clone()),
+        negated: true,
+    };
+    let sum = |expr: SqlExpr| SqlExpr::SymmetricSum {
+        expr: Box::new(expr),
+        key: Box::new(key.clone()),
+    };
+    match agg {
+        Aggregation::Sum => Some(sum(input)),
+        Aggregation::Count => Some(keys_where(not_null(&input))),
+        Aggregation::CountIf => Some(keys_where(input)),
+        Aggregation::Avg => Some(SqlExpr::Function {
+            func: Function::SafeDivide,
+            args: vec![sum(input.clone()), keys_where(not_null(&input))],
+        }),
+        // Repeated rows don't change these
+        Aggregation::Min
+        | Aggregation::Max
+        | Aggregation::CountDistinct
+        | Aggregation::ApproxCountDistinct
+        | Aggregation::BoolAnd
+        | Aggregation::BoolOr => Some(base_expr.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,102 @@
+//! Grand total and subtotal rows (`totals` / `subtotals` requests).
+//!
+//! Totals turn the planned GROUP BY into grouping sets: the detail rows, one
+//! set per subtotalled dimension (grouped by the requested dimensions up to
+//! and including it), and the empty set for the grand total. Rolled-up
+//! dimensions are NULL in those rows, so a `_grouping` column carries the
+//! `GROUPING()` bitmask of the requested dimensions to tell them apart
+//! (0 for detail rows).
+
+use crate::error::{Result, SemaflowError};
+use crate::flows::QueryRequest;
+use crate::sql_ast::{sanitize_alias, SelectItem, SelectQuery, SqlExpr};
+
+/// Output column holding the `GROUPING()` bitmask of each row.
+pub const GROUPING_COLUMN: &str = "_grouping";
+
+/// Add the request's total and subtotal grouping sets to `query`.
+pub(crate) fn apply_totals(query: &mut SelectQuery, request: &QueryRequest) -> Result<()> {
+    let Some(sets) = grouping_sets(request)? else {
+        return Ok(());
+    };
+    if query.group_by.len() != request.dimensions.len() {
+        return Err(SemaflowError::Validation(
+            "totals are not supported for this query shape".to_string(),
+        ));
+    }
+    if reads_unnest(query) {
+        return Err(SemaflowError::Validation(
+            "totals are not supported when grouping by an unnested dimension".to_string(),
+        ));
+    }
+    let clashes = query
+        .select
+        .iter()
+        .filter_map(|item| item.alias.as_deref())
+        .any(|alias| sanitize_alias(alias) == GROUPING_COLUMN);
+    if clashes {
+        return Err(SemaflowError::Validation(format!(
+            "totals add a '{GROUPING_COLUMN}' column, which clashes with a requested field"
+        )));
+    }
+    query.select.push(SelectItem {
+        expr: SqlExpr::Grouping {
+            exprs: query.group_by.clone(),
+        },
+        alias: Some(GROUPING_COLUMN.to_string()),
+    });
+    query.grouping_sets = sets;
+    Ok(())
+}
+
+/// Whether `query` or a subquery it reads expands rows with an `UNNEST`,
+/// which would count their array's rows once per element in the totals.
+fn reads_unnest(query: &SelectQuery) -> bool {
+    !query.unnests.is_empty()
+        || std::iter::once(&query.from)
+            .chain(query.joins.iter().map(|j| &j.table))
+            .filter_map(|t| t.subquery.as_deref())
+            .any(reads_unnest)
+}
+
+/// Grouping sets as indices into the requested dimensions, finest first, or
+/// `None` when the request asks for neither totals nor subtotals.
+fn grouping_sets(request: &QueryRequest) -> Result<Option<Vec<Vec<usize>>>> {
+    if !request.totals && request.subtotals.is_empty() {
+        return Ok(None);
+    }
+    let dims = request.dimensions.len();
+    if dims == 0 {
+        return Err(SemaflowError::Validation(
+            "totals and subtotals require at least one dimension".to_string(),
+        ));
+    }
+    let mut prefixes = Vec::new();
+    for name in &request.subtotals {
+        let idx = request
+            .dimensions
+            .iter()
+            .position(|d| d.field == *name)
+            .ok_or_else(|| {
+                SemaflowError::Validation(format!(
+                    "subtotal dimension '{name}' is not in the requested dimensions"
+                ))
+            })?;
+        if idx + 1 == dims {
+            return Err(SemaflowError::Validation(format!(
+                "subtotal on '{name}' would repeat the detail rows; \
+                 subtotal an earlier dimension or use totals"
+            )));
+        }
+        prefixes.push(idx + 1);
+    }
+    if request.totals {
+        prefixes.push(0);
+    }
+    prefixes.sort_unstable_by(|a, b| b.cmp(a));
+    prefixes.dedup();
+
+    let mut sets = vec![(0..dims).collect::<Vec<_>>()];
+    sets.extend(prefixes.into_iter().map(|len| (0..len).collect()));
+    Ok(Some(sets))
+}
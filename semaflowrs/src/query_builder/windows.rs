@@ -0,0 +1,114 @@
+//! Table calculations rendered as window functions.
+//!
+//! The planned query, stripped of calculations, ordering and limits, becomes a
+//! derived table. The outer select adds one window expression per calculation
+//! and applies the request's full ordering, `limit` and `offset`, so ranks and
+//! differences see every aggregated row rather than a single page.
+
+use std::collections::HashSet;
+
+use crate::calculations::row_order;
+use crate::dialect::Dialect;
+use crate::error::{Result, SemaflowError};
+use crate::flows::{CalculationKind, QueryRequest, SortDirection};
+use crate::sql_ast::{sanitize_alias, SelectQuery, SqlRenderer};
+
+const CALC_TABLE: &str = "__calc";
+
+/// Wrap `query`, planned from the request's base request, with its calculations.
+pub(crate) fn render_with_calculations(
+    mut query: SelectQuery,
+    request: &QueryRequest,
+    renderer: &SqlRenderer,
+    dialect: &dyn Dialect,
+) -> Result<String> {
+    let q = |ident: &str| dialect.quote_ident(ident);
+    let col = |ident: &str| format!("{}.{}", q(CALC_TABLE), q(ident));
+    let direction = |dir: &SortDirection| match dir {
+        SortDirection::Asc => "ASC",
+        SortDirection::Desc => "DESC",
+    };
+
+    query.order_by.clear();
+    query.limit = None;
+    query.offset = None;
+    let inner = renderer.render_select(&query);
+    let mut output: HashSet<String> = query
+        .select
+        .iter()
+        .filter_map(|item| item.alias.as_deref().map(sanitize_alias))
+        .collect();
+
+    let mut lag_order = Vec::new();
+    for item in row_order(request) {
+        let alias = sanitize_alias(&item.column);
+        if !output.contains(&alias) {
+            return Err(SemaflowError::Validation(format!(
+                "calculations can only order by requested fields; '{}' is not selected",
+                item.column
+            )));
+        }
+        lag_order.push(format!("{} {}", col(&alias), direction(&item.direction)));
+    }
+
+    let mut select = vec![format!("{}.*", q(CALC_TABLE))];
+    for calc in &request.calculations {
+        let partition = if calc.partition_by.is_empty() {
+            String::new()
+        } else {
+            let keys: Vec<String> = calc
+                .partition_by
+                .iter()
+                .map(|d| col(&sanitize_alias(d)))
+                .collect();
+            format!("PARTITION BY {} ", keys.join(", "))
+        };
+        let measure = col(&sanitize_alias(&calc.measure));
+        let expr = match calc.kind {
+            CalculationKind::Rank => {
+                format!("RANK() OVER ({partition}ORDER BY {measure} DESC NULLS LAST)")
+            }
+            CalculationKind::Difference | CalculationKind::PercentChange => {
+                let previous = format!(
+                    "LAG({measure}) OVER ({partition}ORDER BY {})",
+                    lag_order.join(", ")
+                );
+                if calc.kind == CalculationKind::Difference {
+                    format!("{measure} - {previous}")
+                } else {
+                    format!("({measure} - {previous}) * 1.0 / NULLIF({previous}, 0)")
+                }
+            }
+        };
+        let alias = sanitize_alias(&calc.name);
+        select.push(format!("{expr} AS {}", q(&alias)));
+        output.insert(alias);
+    }
+
+    let mut sql = format!(
+        "SELECT {} FROM ({inner}) {}",
+        select.join(", "),
+        q(CALC_TABLE)
+    );
+    if !request.order.is_empty() {
+        let orders: Vec<String> = request
+            .order
+            .iter()
+            .map(|item| {
+                format!(
+                    "{} {}",
+                    q(&sanitize_alias(&item.column)),
+                    direction(&item.direction)
+                )
+            })
+            .collect();
+        sql.push_str(&format!(" ORDER BY {}", orders.join(", ")));
+    }
+    sql.push_str(
+        &dialect
+            .capabilities()
+            .limit
+            .render(request.limit.map(u64::from), request.offset.map(u64::from)),
+    );
+    Ok(sql)
+}
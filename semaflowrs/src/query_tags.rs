@@ -0,0 +1,370 @@
+//! Workload tags attached to executed queries for cost attribution.
+//!
+//! Tags are resolved once per request from the flow name, a hash of the
+//! request, the caller's principal, the caller-supplied request tags, and the
+//! static tags in [`TaggingConfig`]. Each backend then renders them in its native form:
+//! - BigQuery: job labels
+//! - PostgreSQL: `application_name` for the session running the query
+//! - DuckDB: ignored (no warehouse billing to attribute)
+//!
+//! With [`TaggingConfig::comment`] the tags are also prepended to every
+//! statement as a SQL comment (see [`QueryTags::annotate`]), so they appear
+//! in query history on any backend.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use crate::config::TaggingConfig;
+use crate::flows::QueryRequest;
+use crate::pagination::compute_query_hash;
+use crate::policy::QueryContext;
+
+/// Tag key always set to the flow name when tagging is enabled.
+pub const FLOW_TAG: &str = "semaflow_flow";
+/// Tag key always set to a hash of the request when tagging is enabled, so
+/// repeated runs of one dashboard tile can be grouped. Pages of a paginated
+/// request share it.
+pub const REQUEST_TAG: &str = "semaflow_request";
+/// Tag key set to [`QueryContext::principal`] when the context has one.
+pub const PRINCIPAL_TAG: &str = "semaflow_principal";
+
+/// Maximum length of a BigQuery label key or value.
+const BQ_LABEL_MAX_LEN: usize = 63;
+/// Maximum number of labels on a BigQuery job.
+const BQ_MAX_LABELS: usize = 64;
+/// PostgreSQL truncates `application_name` to NAMEDATALEN - 1 bytes.
+const PG_APPLICATION_NAME_MAX_LEN: usize = 63;
+
+/// Resolved key/value tags for a single query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryTags {
+    tags: BTreeMap<String, String>,
+    comment: bool,
+}
+
+impl QueryTags {
+    /// No tags; backends execute queries unchanged.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Resolve tags for a request.
+    ///
+    /// Precedence (later wins): config tags, request tags, then the flow
+    /// name, request hash and principal, so requests can't spoof them.
+    /// Returns empty tags when tagging is disabled.
+    pub fn for_request(
+        request: &QueryRequest,
+        context: &QueryContext,
+        config: &TaggingConfig,
+    ) -> Self {
+        if !config.enabled {
+            return Self::none();
+        }
+        let mut tags = config.tags.clone();
+        tags.extend(request.tags.clone());
+        tags.remove(PRINCIPAL_TAG);
+        if let Some(principal) = &context.principal {
+            tags.insert(PRINCIPAL_TAG.to_string(), principal.clone());
+        }
+        tags.insert(FLOW_TAG.to_string(), request.flow.clone());
+        tags.insert(
+            REQUEST_TAG.to_string(),
+            format!("{:016x}", compute_query_hash(request)),
+        );
+        Self {
+            tags,
+            comment: config.comment,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.tags.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Render as BigQuery job labels.
+    ///
+    /// Keys and values are lowercased, characters outside `[a-z0-9_-]` become
+    /// `_`, and both are truncated to 63 characters. Keys must start with a
+    /// letter, so offending keys are prefixed with `t_`. When several tags
+    /// sanitize to the same key the first (in key order) is kept. Jobs take
+    /// at most 64 labels: the flow, request and principal labels are always
+    /// kept, and the remaining tags fill the rest in key order.
+    pub fn to_bigquery_labels(&self) -> BTreeMap<String, String> {
+        let builtins = [FLOW_TAG, REQUEST_TAG, PRINCIPAL_TAG]
+            .into_iter()
+            .filter_map(|key| self.tags.get_key_value(key));
+        let mut labels = BTreeMap::new();
+        for (k, v) in builtins.chain(&self.tags) {
+            if labels.len() == BQ_MAX_LABELS {
+                break;
+            }
+            labels
+                .entry(bigquery_label_key(k))
+                .or_insert_with(|| sanitize_label(v));
+        }
+        labels
+    }
+
+    /// Render as a PostgreSQL `application_name`, e.g. `semaflow semaflow_flow=sales,team=growth`.
+    ///
+    /// Commas, equals signs and non-printable characters are stripped from
+    /// keys and values; the result is truncated to 63 bytes. The request hash
+    /// is left out so it does not crowd out the other tags.
+    pub fn to_application_name(&self) -> String {
+        let pairs: Vec<String> = self
+            .tags
+            .iter()
+            .filter(|(k, _)| k.as_str() != REQUEST_TAG)
+            .map(|(k, v)| format!("{}={}", clean_pair_part(k), clean_pair_part(v)))
+            .collect();
+        let mut name = format!("semaflow {}", pairs.join(","));
+        if name.len() > PG_APPLICATION_NAME_MAX_LEN {
+            let mut end = PG_APPLICATION_NAME_MAX_LEN;
+            while !name.is_char_boundary(end) {
+                end -= 1;
+            }
+            name.truncate(end);
+        }
+        name
+    }
+
+    /// Render as a SQL comment, e.g.
+    /// `/* semaflow semaflow_flow='sales',semaflow_request='…',team='growth' */`.
+    ///
+    /// Quotes and non-printable characters are stripped from keys and values,
+    /// and comment delimiters inside them are broken up.
+    pub fn to_sql_comment(&self) -> String {
+        let pairs: Vec<String> = self
+            .tags
+            .iter()
+            .map(|(k, v)| format!("{}='{}'", clean_comment_part(k), clean_comment_part(v)))
+            .collect();
+        format!("/* semaflow {} */", pairs.join(","))
+    }
+
+    /// Prepend [`Self::to_sql_comment`] to `sql` when comments are enabled;
+    /// otherwise return it unchanged.
+    pub fn annotate<'a>(&self, sql: &'a str) -> Cow<'a, str> {
+        if self.comment && !self.is_empty() {
+            Cow::Owned(format!("{}\n{sql}", self.to_sql_comment()))
+        } else {
+            Cow::Borrowed(sql)
+        }
+    }
+}
+
+fn bigquery_label_key(raw: &str) -> String {
+    let mut key = sanitize_label(raw);
+    if !key.starts_with(|c: char| c.is_ascii_lowercase()) {
+        key = format!("t_{key}");
+        key.truncate(BQ_LABEL_MAX_LEN);
+    }
+    key
+}
+
+fn sanitize_label(raw: &str) -> String {
+    raw.to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(BQ_LABEL_MAX_LEN)
+        .collect()
+}
+
+fn clean_pair_part(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_ascii_graphic() && *c != ',' && *c != '=' && *c != '\'')
+        .collect()
+}
+
+fn clean_comment_part(raw: &str) -> String {
+    let cleaned: String = raw
+        .chars()
+        .filter(|c| (c.is_ascii_graphic() || *c == ' ') && *c != '\'')
+        .collect();
+    cleaned.replace("*/", "* /").replace("/*", "/ *")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(flow: &str, tags: &[(&str, &str)]) -> QueryRequest {
+        QueryRequest {
+            flow: flow.to_string(),
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn enabled(tags: &[(&str, &str)]) -> TaggingConfig {
+        TaggingConfig {
+            enabled: true,
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            comment: false,
+        }
+    }
+
+    #[test]
+    fn disabled_config_yields_no_tags() {
+        let tags = QueryTags::for_request(
+            &request("sales", &[]),
+            &QueryContext::default(),
+            &TaggingConfig::default(),
+        );
+        assert!(tags.is_empty());
+    }
+
+    #[test]
+    fn request_tags_override_config_and_flow_always_set() {
+        let config = enabled(&[("team", "finance"), ("env", "prod")]);
+        let req = request("sales", &[("team", "growth"), (FLOW_TAG, "spoofed")]);
+        let tags = QueryTags::for_request(&req, &QueryContext::default(), &config);
+        assert_eq!(tags.get("team"), Some("growth"));
+        assert_eq!(tags.get("env"), Some("prod"));
+        assert_eq!(tags.get(FLOW_TAG), Some("sales"));
+        assert_eq!(tags.get(REQUEST_TAG).map(str::len), Some(16));
+    }
+
+    #[test]
+    fn request_hash_groups_identical_requests() {
+        let config = enabled(&[]);
+        let hash = |req: &QueryRequest| {
+            QueryTags::for_request(req, &QueryContext::default(), &config)
+                .get(REQUEST_TAG)
+                .unwrap()
+                .to_string()
+        };
+        let mut req = request("sales", &[]);
+        let first = hash(&req);
+        req.offset = Some(100);
+        assert_eq!(hash(&req), first);
+        req.measures = vec!["order_total".to_string()];
+        assert_ne!(hash(&req), first);
+    }
+
+    #[test]
+    fn comment_is_prepended_only_when_enabled() {
+        let mut config = enabled(&[("team", "growth */ DROP"), ("owner", "o'brien")]);
+        let req = request("sales", &[]);
+        let sql = "SELECT 1";
+        assert_eq!(
+            QueryTags::for_request(&req, &QueryContext::default(), &config).annotate(sql),
+            sql
+        );
+
+        config.comment = true;
+        let tags = QueryTags::for_request(&req, &QueryContext::default(), &config);
+        let annotated = tags.annotate(sql);
+        let (comment, rest) = annotated.split_once('\n').unwrap();
+        assert_eq!(rest, sql);
+        assert!(comment.starts_with("/* semaflow owner='obrien',semaflow_flow='sales',"));
+        assert!(comment.contains(&format!(
+            "semaflow_request='{}'",
+            tags.get(REQUEST_TAG).unwrap()
+        )));
+        assert!(comment.ends_with("team='growth * / DROP' */"), "{comment}");
+        assert_eq!(QueryTags::none().annotate(sql), sql);
+    }
+
+    #[test]
+    fn bigquery_labels_are_sanitized() {
+        let config = enabled(&[("Team Name", "Growth/EMEA"), ("1st", "x")]);
+        let labels = QueryTags::for_request(
+            &request("Sales.Daily", &[]),
+            &QueryContext::default(),
+            &config,
+        )
+        .to_bigquery_labels();
+        assert_eq!(labels.get("team_name").unwrap(), "growth_emea");
+        assert_eq!(labels.get("t_1st").unwrap(), "x");
+        assert_eq!(labels.get(FLOW_TAG).unwrap(), "sales_daily");
+    }
+
+    #[test]
+    fn bigquery_labels_keep_builtins_within_the_limit() {
+        let many: Vec<(String, String)> = (0..100)
+            .map(|i| (format!("a{i:03}"), i.to_string()))
+            .collect();
+        let pairs: Vec<(&str, &str)> = many.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let tags = QueryTags::for_request(
+            &request("sales", &[]),
+            &QueryContext::default(),
+            &enabled(&pairs),
+        );
+        let labels = tags.to_bigquery_labels();
+        assert_eq!(labels.len(), BQ_MAX_LABELS);
+        assert_eq!(labels.get(FLOW_TAG).unwrap(), "sales");
+        assert_eq!(
+            labels.get(REQUEST_TAG).map(String::as_str),
+            tags.get(REQUEST_TAG)
+        );
+        assert_eq!(labels.get("a000").unwrap(), "0");
+        assert_eq!(labels.get("a061").unwrap(), "61");
+        assert!(!labels.contains_key("a062"));
+    }
+
+    #[test]
+    fn bigquery_labels_keep_the_first_of_colliding_keys() {
+        let config = enabled(&[("Team", "a"), ("team", "b"), ("Semaflow Flow", "spoofed")]);
+        let labels =
+            QueryTags::for_request(&request("sales", &[]), &QueryContext::default(), &config)
+                .to_bigquery_labels();
+        assert_eq!(labels.get("team").unwrap(), "a");
+        assert_eq!(labels.get(FLOW_TAG).unwrap(), "sales");
+        assert_eq!(labels.len(), 3);
+    }
+
+    #[test]
+    fn principal_is_tagged_and_sanitized() {
+        let config = enabled(&[(PRINCIPAL_TAG, "config")]);
+        let req = request("sales", &[(PRINCIPAL_TAG, "spoofed")]);
+        let tags = QueryTags::for_request(&req, &QueryContext::default(), &config);
+        assert_eq!(tags.get(PRINCIPAL_TAG), None);
+
+        let context = QueryContext::default().with_principal("Ann O'Neil,admin=1");
+        let tags = QueryTags::for_request(&req, &context, &config);
+        assert_eq!(tags.get(PRINCIPAL_TAG), Some("Ann O'Neil,admin=1"));
+        assert_eq!(
+            tags.to_bigquery_labels().get(PRINCIPAL_TAG).unwrap(),
+            "ann_o_neil_admin_1"
+        );
+        assert_eq!(
+            tags.to_application_name(),
+            "semaflow semaflow_flow=sales,semaflow_principal=AnnONeiladmin1"
+        );
+        assert!(tags
+            .to_sql_comment()
+            .contains("semaflow_principal='Ann ONeil,admin=1'"));
+    }
+
+    #[test]
+    fn application_name_is_bounded() {
+        let config = enabled(&[("team", &"x".repeat(100))]);
+        let name =
+            QueryTags::for_request(&request("sales", &[]), &QueryContext::default(), &config)
+                .to_application_name();
+        assert!(name.starts_with("semaflow semaflow_flow=sales,team="));
+        assert_eq!(name.len(), PG_APPLICATION_NAME_MAX_LEN);
+    }
+}
@@ -1,17 +1,33 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 
 use glob::glob;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
+use crate::dialect::grain_to_str;
 use crate::error::{Result, SemaflowError};
-use crate::flows::{Aggregation, Expr, FlowTableRef, SemanticFlow, SemanticTable};
+use crate::expr_utils::collect_time_grains;
+use crate::flows::{
+    Aggregation, Deprecation, Expr, FlowTableRef, JoinCardinality, JoinKey, QueryRequest,
+    SemanticFlow, SemanticTable, TimeGrain,
+};
+use crate::model_files::{
+    interpolate_table_env, is_partial, merge_table_parts, read_model_file, resolve_flow_extends,
+};
+use crate::registry_diff::ModelVersion;
+use crate::schema_cache::TableStats;
 
 #[derive(Debug, Default, Clone)]
 pub struct FlowRegistry {
     pub tables: HashMap<String, SemanticTable>,
     pub flows: HashMap<String, SemanticFlow>,
+    /// Model version per flow; see [`FlowRegistry::bump_versions_from`].
+    pub versions: HashMap<String, ModelVersion>,
+    /// Warehouse statistics per semantic table, used by the planner; see
+    /// [`crate::backends::ConnectionManager::collect_stats`].
+    pub stats: HashMap<String, TableStats>,
 }
 
 impl FlowRegistry {
@@ -27,12 +43,17 @@ impl FlowRegistry {
         for flow in flows {
             registry.flows.insert(flow.name.clone(), flow);
         }
+        registry.resolve_bridge_joins();
+        registry.infer_entity_joins();
         registry
     }
 
     /// Load tables/flows from disk. Accepts either:
     /// - a directory containing `tables/` and `flows/` subdirectories
     /// - a directory with YAML files directly inside (used for both tables and flows)
+    ///
+    /// Files may use `!include`, merge keys and environment variables, and a
+    /// table may be split across files; see [`crate::model_files`].
     pub fn load_from_dir<P: AsRef<Path>>(root: P) -> Result<Self> {
         let mut registry = FlowRegistry::new();
         let root = root.as_ref();
@@ -52,9 +73,65 @@ impl FlowRegistry {
 
         registry.load_tables(tables_path)?;
         registry.load_flows(flows_path)?;
+        registry.resolve_bridge_joins();
+        registry.infer_entity_joins();
         Ok(registry)
     }
 
+    /// Attach joins declared `through` a bridge to the bridge join, whose
+    /// columns their `join_keys` read. Other `to_table` values are left for
+    /// validation to report.
+    pub fn resolve_bridge_joins(&mut self) {
+        for flow in self.flows.values_mut() {
+            let parents: HashMap<String, String> = flow
+                .joins
+                .values()
+                .map(|join| (join.alias.clone(), join.to_table.clone()))
+                .collect();
+            for join in flow.joins.values_mut() {
+                let Some(bridge) = &join.through else {
+                    continue;
+                };
+                if join.to_table.is_empty() || parents.get(bridge) == Some(&join.to_table) {
+                    join.to_table = bridge.clone();
+                }
+            }
+        }
+    }
+
+    /// Fill in the `join_keys` of joins that leave them empty from the one
+    /// entity both joined tables declare, and their cardinality (unless
+    /// hinted) from the entity types. Joins sharing no entity, or several,
+    /// are left alone for validation to report.
+    pub fn infer_entity_joins(&mut self) {
+        for flow in self.flows.values_mut() {
+            let mut alias_tables = HashMap::new();
+            alias_tables.insert(
+                flow.base_table.alias.clone(),
+                flow.base_table.semantic_table.clone(),
+            );
+            for join in flow.joins.values() {
+                alias_tables.insert(join.alias.clone(), join.semantic_table.clone());
+            }
+            for join in flow.joins.values_mut() {
+                if !join.join_keys.is_empty() {
+                    continue;
+                }
+                let left = alias_tables
+                    .get(&join.to_table)
+                    .and_then(|name| self.tables.get(name));
+                let right = self.tables.get(&join.semantic_table);
+                let (Some(left), Some(right)) = (left, right) else {
+                    continue;
+                };
+                if let Some((key, cardinality)) = entity_join(left, right) {
+                    join.join_keys.push(key);
+                    join.cardinality.get_or_insert(cardinality);
+                }
+            }
+        }
+    }
+
     fn load_tables(&mut self, dir: PathBuf) -> Result<()> {
         if !dir.exists() {
             return Err(SemaflowError::Validation(format!(
@@ -62,40 +139,32 @@ impl FlowRegistry {
                 dir.display()
             )));
         }
-        let mut loaded = false;
-        for entry in glob(&format!("{}/*.yml", dir.display()))
-            .map_err(|e| SemaflowError::Other(e.into()))?
-            .flatten()
-        {
-            loaded |= self.load_table_file(&entry)?;
-        }
-        for entry in glob(&format!("{}/*.yaml", dir.display()))
-            .map_err(|e| SemaflowError::Other(e.into()))?
-            .flatten()
-        {
-            loaded |= self.load_table_file(&entry)?;
+        let mut parts = Vec::new();
+        for entry in model_files_in(&dir)? {
+            parts.push((entry.clone(), read_model_file(&entry)?));
         }
-        if !loaded {
+        if parts.is_empty() {
             return Err(SemaflowError::Validation(format!(
                 "no semantic tables found in {}",
                 dir.display()
             )));
         }
-        Ok(())
-    }
-
-    fn load_table_file(&mut self, path: &Path) -> Result<bool> {
-        let contents = fs::read_to_string(path)?;
-        match serde_yaml::from_str::<SemanticTable>(&contents) {
-            Ok(table) => {
-                self.tables.insert(table.name.clone(), table);
-                Ok(true)
+        // A table may be split across several files sharing its name
+        for (path, mut value) in merge_table_parts(parts)? {
+            interpolate_table_env(&mut value)?;
+            match serde_yaml::from_value::<SemanticTable>(value) {
+                Ok(table) => {
+                    self.tables.insert(table.name.clone(), table);
+                }
+                Err(e) => {
+                    return Err(SemaflowError::Validation(format!(
+                        "failed to parse table {}: {e}",
+                        path.display()
+                    )))
+                }
             }
-            Err(e) => Err(SemaflowError::Validation(format!(
-                "failed to parse table {}: {e}",
-                path.display()
-            ))),
         }
+        Ok(())
     }
 
     fn load_flows(&mut self, dir: PathBuf) -> Result<()> {
@@ -105,40 +174,31 @@ impl FlowRegistry {
                 dir.display()
             )));
         }
-        let mut loaded = false;
-        for entry in glob(&format!("{}/*.yml", dir.display()))
-            .map_err(|e| SemaflowError::Other(e.into()))?
-            .flatten()
-        {
-            loaded |= self.load_flow_file(&entry)?;
-        }
-        for entry in glob(&format!("{}/*.yaml", dir.display()))
-            .map_err(|e| SemaflowError::Other(e.into()))?
-            .flatten()
-        {
-            loaded |= self.load_flow_file(&entry)?;
+        let mut parts = Vec::new();
+        for entry in model_files_in(&dir)? {
+            parts.push((entry.clone(), read_model_file(&entry)?));
         }
-        if !loaded {
+        if parts.is_empty() {
             return Err(SemaflowError::Validation(format!(
                 "no semantic flows found in {}",
                 dir.display()
             )));
         }
-        Ok(())
-    }
-
-    fn load_flow_file(&mut self, path: &Path) -> Result<bool> {
-        let contents = fs::read_to_string(path)?;
-        match serde_yaml::from_str::<SemanticFlow>(&contents) {
-            Ok(flow) => {
-                self.flows.insert(flow.name.clone(), flow);
-                Ok(true)
+        // A flow may extend another defined in any file
+        for (path, value) in resolve_flow_extends(parts)? {
+            match serde_yaml::from_value::<SemanticFlow>(value) {
+                Ok(flow) => {
+                    self.flows.insert(flow.name.clone(), flow);
+                }
+                Err(e) => {
+                    return Err(SemaflowError::Validation(format!(
+                        "failed to parse flow {}: {e}",
+                        path.display()
+                    )))
+                }
             }
-            Err(e) => Err(SemaflowError::Validation(format!(
-                "failed to parse flow {}: {e}",
-                path.display()
-            ))),
         }
+        Ok(())
     }
 
     pub fn get_table(&self, name: &str) -> Option<&SemanticTable> {
@@ -149,6 +209,46 @@ impl FlowRegistry {
         self.flows.get(name)
     }
 
+    /// Fingerprint of a flow definition and every semantic table it references.
+    ///
+    /// Changes whenever the flow, its joins, or any referenced table changes.
+    /// Pagination cursors carry this so continuation is rejected after a reload.
+    pub fn flow_version(&self, name: &str) -> Result<u64> {
+        let flow = self
+            .get_flow(name)
+            .ok_or_else(|| SemaflowError::UnknownFlow {
+                flow: name.to_string(),
+            })?;
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_string(flow)?.hash(&mut hasher);
+
+        let table_names = std::iter::once(&flow.base_table.semantic_table)
+            .chain(flow.joins.values().map(|j| &j.semantic_table));
+        for table_name in table_names {
+            let table = self.tables.get(table_name).ok_or_else(|| {
+                SemaflowError::Validation(format!(
+                    "flow {} references missing table {table_name}",
+                    flow.name
+                ))
+            })?;
+            serde_json::to_string(table)?.hash(&mut hasher);
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Check a request against the semantic model without building or
+    /// executing SQL, returning every problem found rather than the first.
+    ///
+    /// Uses the default planner settings; call
+    /// [`crate::query_builder::SqlBuilder::validate_request`] to check with a
+    /// datasource's.
+    pub fn validate_request(
+        &self,
+        request: &QueryRequest,
+    ) -> std::result::Result<(), Vec<SemaflowError>> {
+        crate::query_builder::SqlBuilder::default().validate_request(self, request)
+    }
+
     /// List flow names and descriptions for discovery endpoints.
     pub fn list_flow_summaries(&self) -> Vec<FlowSummary> {
         self.flows
@@ -156,15 +256,27 @@ impl FlowRegistry {
             .map(|m| FlowSummary {
                 name: m.name.clone(),
                 description: m.description.clone(),
+                deprecated: m.deprecated.clone(),
+                version: self.flow_model_version(&m.name),
             })
             .collect()
     }
 
     /// Return a flow's schema (dimensions, measures, joins) including descriptions.
     pub fn flow_schema(&self, name: &str) -> Result<FlowSchema> {
+        self.flow_schema_filtered(name, &SchemaFilter::default())
+    }
+
+    /// Like [`Self::flow_schema`], but only returns fields matching `filter`.
+    ///
+    /// Fields are paged in order (dimensions first, then measures) so clients
+    /// can fetch large flows incrementally; `total_fields` counts every match.
+    pub fn flow_schema_filtered(&self, name: &str, filter: &SchemaFilter) -> Result<FlowSchema> {
         let flow = self
             .get_flow(name)
-            .ok_or_else(|| SemaflowError::Validation(format!("unknown flow {name}")))?;
+            .ok_or_else(|| SemaflowError::UnknownFlow {
+                flow: name.to_string(),
+            })?;
         let base_table = self
             .tables
             .get(&flow.base_table.semantic_table)
@@ -177,8 +289,16 @@ impl FlowRegistry {
 
         let mut dimensions = Vec::new();
         let mut measures = Vec::new();
+        let mut hierarchies = Vec::new();
 
-        collect_fields(&flow.base_table, base_table, &mut dimensions, &mut measures);
+        collect_fields(
+            &flow.base_table,
+            None,
+            base_table,
+            &mut dimensions,
+            &mut measures,
+        );
+        collect_hierarchies(&flow.base_table, None, base_table, &mut hierarchies);
 
         for (join_name, join) in &flow.joins {
             let table = self.tables.get(&join.semantic_table).ok_or_else(|| {
@@ -191,45 +311,159 @@ impl FlowRegistry {
                 semantic_table: join.semantic_table.clone(),
                 alias: join.alias.clone(),
             };
-            collect_fields(&join_ref, table, &mut dimensions, &mut measures);
+            let role = join.role.as_deref();
+            collect_fields(&join_ref, role, table, &mut dimensions, &mut measures);
+            collect_hierarchies(&join_ref, role, table, &mut hierarchies);
         }
 
+        if filter.kind == Some(SchemaFieldKind::Measures) {
+            dimensions.clear();
+        }
+        if filter.kind == Some(SchemaFieldKind::Dimensions) {
+            measures.clear();
+        }
+        dimensions.retain(|d| filter.matches(&d.tags, d.folder.as_deref(), d.deprecated.is_some()));
+        measures.retain(|m| filter.matches(&m.tags, m.folder.as_deref(), m.deprecated.is_some()));
+
+        let total_fields = dimensions.len() + measures.len();
+        let end = filter
+            .limit
+            .map_or(total_fields, |limit| filter.offset.saturating_add(limit))
+            .min(total_fields);
+        let next_offset = (end < total_fields).then_some(end);
+        // Page across dimensions then measures
+        let dim_count = dimensions.len();
+        measures.truncate(end.saturating_sub(dim_count));
+        measures.drain(..filter.offset.saturating_sub(dim_count).min(measures.len()));
+        dimensions.truncate(end);
+        dimensions.drain(..filter.offset.min(dimensions.len()));
+
+        let time_grains = match &base_table.time_dimension {
+            Some(_) => TimeGrain::ALL
+                .iter()
+                .filter(|grain| base_table.smallest_time_grain.as_ref() <= Some(*grain))
+                .map(|grain| grain_to_str(grain).to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+
         Ok(FlowSchema {
             name: flow.name.clone(),
             description: flow.description.clone(),
             base_table: flow.base_table.clone(),
+            tags: flow.tags.clone(),
+            synonyms: flow.synonyms.clone(),
+            deprecated: flow.deprecated.clone(),
             data_source: base_table.data_source.clone(),
             time_dimension: base_table.time_dimension.clone(),
             smallest_time_grain: base_table
                 .smallest_time_grain
                 .as_ref()
-                .map(|g| format!("{:?}", g)),
+                .map(|g| grain_to_str(g).to_string()),
+            time_grains,
             dimensions,
             measures,
+            hierarchies,
+            total_fields,
+            next_offset,
         })
     }
 }
 
+/// Field selection for [`FlowRegistry::flow_schema_filtered`]. The default
+/// returns every field, deprecated ones included.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchemaFilter {
+    /// Only dimensions or only measures.
+    pub kind: Option<SchemaFieldKind>,
+    /// Keep fields carrying at least one of these tags.
+    pub tags: Vec<String>,
+    /// Keep fields in this folder or one of its subfolders.
+    pub folder: Option<String>,
+    pub include_deprecated: bool,
+    /// Number of matching fields to skip.
+    pub offset: usize,
+    /// Maximum number of fields to return.
+    pub limit: Option<usize>,
+}
+
+impl Default for SchemaFilter {
+    fn default() -> Self {
+        Self {
+            kind: None,
+            tags: Vec::new(),
+            folder: None,
+            include_deprecated: true,
+            offset: 0,
+            limit: None,
+        }
+    }
+}
+
+impl SchemaFilter {
+    fn matches(&self, tags: &[String], folder: Option<&str>, deprecated: bool) -> bool {
+        if deprecated && !self.include_deprecated {
+            return false;
+        }
+        if !self.tags.is_empty() && !tags.iter().any(|t| self.tags.contains(t)) {
+            return false;
+        }
+        match (&self.folder, folder) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(wanted), Some(folder)) => {
+                let wanted = wanted.trim_end_matches('/');
+                folder == wanted
+                    || folder
+                        .strip_prefix(wanted)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaFieldKind {
+    Dimensions,
+    Measures,
+}
+
+/// Fields of a flow table, qualified by its `role` when it plays one.
 fn collect_fields(
     table_ref: &FlowTableRef,
+    role: Option<&str>,
     table: &SemanticTable,
     dimensions: &mut Vec<DimensionInfo>,
     measures: &mut Vec<MeasureInfo>,
 ) {
+    let qualifier = role.unwrap_or(&table_ref.alias);
     for (name, dim) in &table.dimensions {
-        let qualified = format!("{}.{}", table_ref.alias, name);
+        let qualified = format!("{qualifier}.{name}");
+        let mut grains = Vec::new();
+        collect_time_grains(&dim.expr, &mut grains);
         dimensions.push(DimensionInfo {
             name: name.clone(),
             qualified_name: qualified,
             description: dim.description.clone(),
             data_type: dim.data_type.clone(),
+            time_grain: grains.first().map(|grain| grain_to_str(grain).to_string()),
             semantic_table: table_ref.semantic_table.clone(),
             table_alias: table_ref.alias.clone(),
+            role: role.map(str::to_string),
             expr: dim.expr.clone(),
+            tags: dim.tags.clone(),
+            synonyms: dim.synonyms.clone(),
+            folder: dim.folder.clone(),
+            deprecated: dim.deprecated.clone(),
+            label: dim.label.clone(),
+            format: dim.format.clone(),
+            hidden: dim.hidden,
         });
     }
     for (name, measure) in &table.measures {
-        let qualified = format!("{}.{}", table_ref.alias, name);
+        let qualified = format!("{qualifier}.{name}");
         measures.push(MeasureInfo {
             name: name.clone(),
             qualified_name: qualified,
@@ -237,11 +471,61 @@ fn collect_fields(
             data_type: measure.data_type.clone(),
             semantic_table: table_ref.semantic_table.clone(),
             table_alias: table_ref.alias.clone(),
+            role: role.map(str::to_string),
             expr: measure.expr.clone(),
             agg: measure.agg.clone(),
             filter: measure.filter.clone(),
             post_expr: measure.post_expr.clone(),
             formula: measure.formula.as_ref().map(|f| f.raw.clone()),
+            tags: measure.tags.clone(),
+            synonyms: measure.synonyms.clone(),
+            folder: measure.folder.clone(),
+            deprecated: measure.deprecated.clone(),
+            label: measure.label.clone(),
+            format: measure.format.clone(),
+            hidden: measure.hidden,
+        });
+    }
+}
+
+/// Key and cardinality of a join from `left` to `right` on the single
+/// entity they share, where at least one side holds it uniquely.
+fn entity_join(left: &SemanticTable, right: &SemanticTable) -> Option<(JoinKey, JoinCardinality)> {
+    let mut shared = left.entities.iter().filter_map(|(name, left_entity)| {
+        let right_entity = right.entities.get(name)?;
+        let cardinality = match (left_entity.kind.is_unique(), right_entity.kind.is_unique()) {
+            (false, true) => JoinCardinality::ManyToOne,
+            (true, false) => JoinCardinality::OneToMany,
+            (true, true) => JoinCardinality::OneToOne,
+            // Two foreign keys: neither table owns the entity
+            (false, false) => return None,
+        };
+        let key = JoinKey {
+            left: left_entity.column(name).to_string(),
+            right: right_entity.column(name).to_string(),
+        };
+        Some((key, cardinality))
+    });
+    let found = shared.next()?;
+    shared.next().is_none().then_some(found)
+}
+
+fn collect_hierarchies(
+    table_ref: &FlowTableRef,
+    role: Option<&str>,
+    table: &SemanticTable,
+    hierarchies: &mut Vec<HierarchyInfo>,
+) {
+    let qualifier = role.unwrap_or(&table_ref.alias);
+    for (name, levels) in &table.hierarchies {
+        hierarchies.push(HierarchyInfo {
+            name: name.clone(),
+            semantic_table: table_ref.semantic_table.clone(),
+            table_alias: table_ref.alias.clone(),
+            levels: levels
+                .iter()
+                .map(|level| format!("{qualifier}.{level}"))
+                .collect(),
         });
     }
 }
@@ -250,6 +534,9 @@ fn collect_fields(
 pub struct FlowSummary {
     pub name: String,
     pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+    pub version: ModelVersion,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -257,11 +544,33 @@ pub struct FlowSchema {
     pub name: String,
     pub description: Option<String>,
     pub base_table: FlowTableRef,
+    pub tags: Vec<String>,
+    pub synonyms: Vec<String>,
+    pub deprecated: Option<Deprecation>,
     pub data_source: String,
     pub time_dimension: Option<String>,
     pub smallest_time_grain: Option<String>,
+    /// Grains the time dimension can be requested at, finest first; empty
+    /// without a time dimension.
+    pub time_grains: Vec<String>,
     pub dimensions: Vec<DimensionInfo>,
     pub measures: Vec<MeasureInfo>,
+    /// Drill-down paths of the flow's tables, whatever the field filter.
+    pub hierarchies: Vec<HierarchyInfo>,
+    /// Fields matching the filter before paging.
+    pub total_fields: usize,
+    /// Offset of the next page, if more fields remain.
+    pub next_offset: Option<usize>,
+}
+
+/// A table's hierarchy as seen through a flow.
+#[derive(Debug, Clone, Serialize)]
+pub struct HierarchyInfo {
+    pub name: String,
+    pub semantic_table: String,
+    pub table_alias: String,
+    /// Qualified dimension names, coarsest first.
+    pub levels: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -270,9 +579,21 @@ pub struct DimensionInfo {
     pub qualified_name: String,
     pub description: Option<String>,
     pub data_type: Option<String>,
+    /// Grain the dimension truncates time to, for `date_trunc` dimensions.
+    pub time_grain: Option<String>,
     pub semantic_table: String,
     pub table_alias: String,
+    /// Role of the table's join, which qualifies `qualified_name`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
     pub expr: Expr,
+    pub tags: Vec<String>,
+    pub synonyms: Vec<String>,
+    pub folder: Option<String>,
+    pub deprecated: Option<Deprecation>,
+    pub label: Option<String>,
+    pub format: Option<String>,
+    pub hidden: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -283,6 +604,9 @@ pub struct MeasureInfo {
     pub data_type: Option<String>,
     pub semantic_table: String,
     pub table_alias: String,
+    /// Role of the table's join, which qualifies `qualified_name`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
     // Simple measure fields (None for formula measures)
     pub expr: Option<Expr>,
     pub agg: Option<Aggregation>,
@@ -290,4 +614,26 @@ pub struct MeasureInfo {
     pub post_expr: Option<Expr>,
     // Formula measure field (None for simple measures)
     pub formula: Option<String>,
+    pub tags: Vec<String>,
+    pub synonyms: Vec<String>,
+    pub folder: Option<String>,
+    pub deprecated: Option<Deprecation>,
+    pub label: Option<String>,
+    pub format: Option<String>,
+    pub hidden: bool,
+}
+
+/// `*.yml` and `*.yaml` files (and `.j2` templates of them) directly inside
+/// `dir`, skipping partials.
+fn model_files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for pattern in ["yml", "yaml", "yml.j2", "yaml.j2"] {
+        files.extend(
+            glob(&format!("{}/*.{pattern}", dir.display()))
+                .map_err(|e| SemaflowError::Other(e.into()))?
+                .flatten()
+                .filter(|path| !is_partial(path)),
+        );
+    }
+    Ok(files)
 }
@@ -0,0 +1,424 @@
+//! Semantic model diffs and versioning.
+//!
+//! [`FlowRegistry::diff`] lists the tables, dimensions, measures, flows,
+//! joins and parameters added, removed or changed between two registries and
+//! classifies each change:
+//!
+//! - **breaking**: existing queries may fail or return different results,
+//!   e.g. a removed measure, a changed dimension expression or a new inner join;
+//! - **additive**: new fields or flows that existing queries don't see;
+//! - **cosmetic**: metadata only (descriptions, tags, synonyms, folders,
+//!   deprecation).
+//!
+//! CI can fail a model change when [`RegistryDiff::is_breaking`], and
+//! [`FlowRegistry::bump_versions_from`] carries per-flow semantic versions
+//! across reloads (major for breaking, minor for additive, patch for
+//! cosmetic changes), exposed as [`crate::registry::FlowSummary::version`].
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::flows::{SemanticFlow, SemanticTable};
+use crate::registry::FlowRegistry;
+
+/// Metadata keys whose changes never affect query results.
+const COSMETIC_KEYS: &[&str] = &[
+    "description",
+    "tags",
+    "synonyms",
+    "folder",
+    "deprecated",
+    "label",
+    "format",
+    "hidden",
+];
+
+/// How a change affects existing queries, from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeImpact {
+    Cosmetic,
+    Additive,
+    Breaking,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// The kind of model object a change applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelObject {
+    Table,
+    Dimension,
+    Measure,
+    Flow,
+    Join,
+    Param,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelChange {
+    pub object: ModelObject,
+    /// `table`, `table.field`, `flow`, `flow.join` or `flow.param`.
+    pub name: String,
+    pub kind: ChangeKind,
+    pub impact: ChangeImpact,
+    /// Flows whose queries see the change.
+    pub flows: Vec<String>,
+}
+
+/// Changes between two registries, tables first, then flows, by name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RegistryDiff {
+    pub changes: Vec<ModelChange>,
+}
+
+impl RegistryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|c| c.impact == ChangeImpact::Breaking)
+    }
+
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &ModelChange> {
+        self.changes
+            .iter()
+            .filter(|c| c.impact == ChangeImpact::Breaking)
+    }
+
+    /// The most severe change affecting `flow`, if any.
+    pub fn impact_on(&self, flow: &str) -> Option<ChangeImpact> {
+        self.changes
+            .iter()
+            .filter(|c| c.flows.iter().any(|f| f == flow))
+            .map(|c| c.impact)
+            .max()
+    }
+}
+
+/// Semantic version of a flow's model, `major.minor.patch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModelVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Default for ModelVersion {
+    fn default() -> Self {
+        Self {
+            major: 1,
+            minor: 0,
+            patch: 0,
+        }
+    }
+}
+
+impl ModelVersion {
+    /// The next version after a change of the given impact.
+    pub fn bump(self, impact: ChangeImpact) -> Self {
+        match impact {
+            ChangeImpact::Breaking => Self {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            ChangeImpact::Additive => Self {
+                minor: self.minor + 1,
+                patch: 0,
+                ..self
+            },
+            ChangeImpact::Cosmetic => Self {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+impl fmt::Display for ModelVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl Serialize for ModelVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FlowRegistry {
+    /// Compare two registries; see the [module docs](self) for how changes
+    /// are classified.
+    pub fn diff(old: &FlowRegistry, new: &FlowRegistry) -> RegistryDiff {
+        let mut changes = Vec::new();
+        diff_tables(old, new, &mut changes);
+        diff_flows(old, new, &mut changes);
+        RegistryDiff { changes }
+    }
+
+    /// Version of a flow's model; `1.0.0` until versions are bumped.
+    pub fn flow_model_version(&self, flow: &str) -> ModelVersion {
+        self.versions.get(flow).copied().unwrap_or_default()
+    }
+
+    /// Take over the flow versions of `previous`, the registry this one
+    /// replaces, bumped by the changes between them. New flows start at
+    /// `1.0.0`. Returns the diff the versions were derived from.
+    pub fn bump_versions_from(&mut self, previous: &FlowRegistry) -> RegistryDiff {
+        let diff = FlowRegistry::diff(previous, self);
+        self.versions = self
+            .flows
+            .keys()
+            .map(|name| {
+                let version = if previous.flows.contains_key(name) {
+                    let version = previous.flow_model_version(name);
+                    diff.impact_on(name).map_or(version, |i| version.bump(i))
+                } else {
+                    ModelVersion::default()
+                };
+                (name.clone(), version)
+            })
+            .collect();
+        diff
+    }
+}
+
+fn diff_tables(old: &FlowRegistry, new: &FlowRegistry, changes: &mut Vec<ModelChange>) {
+    let names: BTreeSet<&String> = old.tables.keys().chain(new.tables.keys()).collect();
+    for name in names {
+        let flows: Vec<String> = flows_using(old, name)
+            .union(&flows_using(new, name))
+            .cloned()
+            .collect();
+        let change = |object, member: String, kind, impact| ModelChange {
+            object,
+            name: member,
+            kind,
+            impact,
+            flows: flows.clone(),
+        };
+        let (old_table, new_table) = match (old.tables.get(name), new.tables.get(name)) {
+            (Some(a), Some(b)) => (a, b),
+            (None, _) => {
+                changes.push(change(
+                    ModelObject::Table,
+                    name.clone(),
+                    ChangeKind::Added,
+                    ChangeImpact::Additive,
+                ));
+                continue;
+            }
+            (_, None) => {
+                changes.push(change(
+                    ModelObject::Table,
+                    name.clone(),
+                    ChangeKind::Removed,
+                    ChangeImpact::Breaking,
+                ));
+                continue;
+            }
+        };
+
+        // Table settings such as primary keys and default filters; fields
+        // are compared one by one below
+        let settings = |table: &SemanticTable| {
+            let mut value = to_value(table);
+            if let Value::Object(map) = &mut value {
+                map.remove("dimensions");
+                map.remove("measures");
+            }
+            value
+        };
+        if let Some(impact) = classify(settings(old_table), settings(new_table)) {
+            changes.push(change(
+                ModelObject::Table,
+                name.clone(),
+                ChangeKind::Changed,
+                impact,
+            ));
+        }
+        for (object, old_fields, new_fields) in [
+            (
+                ModelObject::Dimension,
+                to_map(&old_table.dimensions),
+                to_map(&new_table.dimensions),
+            ),
+            (
+                ModelObject::Measure,
+                to_map(&old_table.measures),
+                to_map(&new_table.measures),
+            ),
+        ] {
+            for (field, kind, impact) in
+                diff_members(&old_fields, &new_fields, |_| ChangeImpact::Additive)
+            {
+                changes.push(change(object, format!("{name}.{field}"), kind, impact));
+            }
+        }
+    }
+}
+
+fn diff_flows(old: &FlowRegistry, new: &FlowRegistry, changes: &mut Vec<ModelChange>) {
+    let names: BTreeSet<&String> = old.flows.keys().chain(new.flows.keys()).collect();
+    for name in names {
+        let change = |object, member: String, kind, impact| ModelChange {
+            object,
+            name: member,
+            kind,
+            impact,
+            flows: vec![name.clone()],
+        };
+        let (old_flow, new_flow) = match (old.flows.get(name), new.flows.get(name)) {
+            (Some(a), Some(b)) => (a, b),
+            (None, _) => {
+                changes.push(change(
+                    ModelObject::Flow,
+                    name.clone(),
+                    ChangeKind::Added,
+                    ChangeImpact::Additive,
+                ));
+                continue;
+            }
+            (_, None) => {
+                changes.push(change(
+                    ModelObject::Flow,
+                    name.clone(),
+                    ChangeKind::Removed,
+                    ChangeImpact::Breaking,
+                ));
+                continue;
+            }
+        };
+
+        let settings = |flow: &SemanticFlow| {
+            let mut value = to_value(flow);
+            if let Value::Object(map) = &mut value {
+                map.remove("joins");
+                map.remove("params");
+            }
+            value
+        };
+        if let Some(impact) = classify(settings(old_flow), settings(new_flow)) {
+            changes.push(change(
+                ModelObject::Flow,
+                name.clone(),
+                ChangeKind::Changed,
+                impact,
+            ));
+        }
+        // A new left join is only used by queries that ask for its fields; an
+        // inner join filters every query
+        let join_added = |join: &Value| match join.get("join_type") {
+            Some(Value::String(join_type)) if join_type == "left" => ChangeImpact::Additive,
+            _ => ChangeImpact::Breaking,
+        };
+        for (join, kind, impact) in diff_members(
+            &to_map(&old_flow.joins),
+            &to_map(&new_flow.joins),
+            join_added,
+        ) {
+            changes.push(change(
+                ModelObject::Join,
+                format!("{name}.{join}"),
+                kind,
+                impact,
+            ));
+        }
+        // Queries written before a parameter existed can't supply it
+        let param_added = |param: &Value| match param.get("default") {
+            Some(default) if !default.is_null() => ChangeImpact::Additive,
+            _ => ChangeImpact::Breaking,
+        };
+        for (param, kind, impact) in diff_members(
+            &to_map(&old_flow.params),
+            &to_map(&new_flow.params),
+            param_added,
+        ) {
+            changes.push(change(
+                ModelObject::Param,
+                format!("{name}.{param}"),
+                kind,
+                impact,
+            ));
+        }
+    }
+}
+
+/// Compare two keyed collections of definitions. Added members get the
+/// impact `added` assigns them; removed members are breaking.
+fn diff_members(
+    old: &BTreeMap<String, Value>,
+    new: &BTreeMap<String, Value>,
+    added: impl Fn(&Value) -> ChangeImpact,
+) -> Vec<(String, ChangeKind, ChangeImpact)> {
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (kind, impact) = match (old.get(name), new.get(name)) {
+                (None, Some(member)) => (ChangeKind::Added, added(member)),
+                (Some(_), None) => (ChangeKind::Removed, ChangeImpact::Breaking),
+                (Some(a), Some(b)) => (ChangeKind::Changed, classify(a.clone(), b.clone())?),
+                (None, None) => return None,
+            };
+            Some((name.clone(), kind, impact))
+        })
+        .collect()
+}
+
+/// Impact of a definition changing from `old` to `new`; `None` if unchanged.
+fn classify(mut old: Value, mut new: Value) -> Option<ChangeImpact> {
+    if old == new {
+        return None;
+    }
+    for value in [&mut old, &mut new] {
+        if let Value::Object(map) = value {
+            for key in COSMETIC_KEYS {
+                map.remove(*key);
+            }
+        }
+    }
+    Some(if old == new {
+        ChangeImpact::Cosmetic
+    } else {
+        ChangeImpact::Breaking
+    })
+}
+
+fn flows_using(registry: &FlowRegistry, table: &str) -> BTreeSet<String> {
+    registry
+        .flows
+        .values()
+        .filter(|flow| {
+            flow.base_table.semantic_table == table
+                || flow.joins.values().any(|j| j.semantic_table == table)
+        })
+        .map(|flow| flow.name.clone())
+        .collect()
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn to_map<T: Serialize>(members: &BTreeMap<String, T>) -> BTreeMap<String, Value> {
+    members
+        .iter()
+        .map(|(name, member)| (name.clone(), to_value(member)))
+        .collect()
+}
@@ -0,0 +1,184 @@
+//! Query result caching.
+//!
+//! Dashboards re-issue identical queries constantly. When the result cache is
+//! enabled (see [`crate::config::ResultCacheConfig`]), [`crate::runtime::run_query`]
+//! looks up each query by data source and rendered SQL before executing it and
+//! stores fresh results for the flow's TTL.
+//!
+//! [`InMemoryQueryCache`] is a bounded LRU used by default; implement
+//! [`QueryCache`] to share results through an external store such as Redis
+//! ([`QueryResult`] serializes with serde).
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use crate::executor::QueryResult;
+
+/// Identifies a cached result: the SQL and the data source it ran against.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub data_source: String,
+    pub sql: String,
+}
+
+impl CacheKey {
+    pub fn new(data_source: impl Into<String>, sql: impl Into<String>) -> Self {
+        Self {
+            data_source: data_source.into(),
+            sql: sql.into(),
+        }
+    }
+}
+
+/// Storage for query results. Implementations must not return entries older
+/// than the TTL they were stored with.
+#[async_trait]
+pub trait QueryCache: Send + Sync {
+    async fn get(&self, key: &CacheKey) -> Option<QueryResult>;
+    async fn put(&self, key: &CacheKey, result: &QueryResult, ttl: Duration);
+}
+
+#[derive(Debug)]
+struct CachedResult {
+    result: QueryResult,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct LruEntries {
+    entries: HashMap<CacheKey, CachedResult>,
+    /// Keys from least to most recently used.
+    order: VecDeque<CacheKey>,
+}
+
+impl LruEntries {
+    fn touch(&mut self, key: &CacheKey) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            self.order.remove(pos);
+        }
+        self.order.push_back(key.clone());
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        self.entries.remove(key);
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            self.order.remove(pos);
+        }
+    }
+}
+
+/// In-process LRU cache holding at most `max_entries` results.
+#[derive(Debug)]
+pub struct InMemoryQueryCache {
+    max_entries: usize,
+    inner: Mutex<LruEntries>,
+}
+
+impl InMemoryQueryCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            inner: Mutex::new(LruEntries::default()),
+        }
+    }
+
+    /// Number of results currently held, expired ones included.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl QueryCache for InMemoryQueryCache {
+    async fn get(&self, key: &CacheKey) -> Option<QueryResult> {
+        let mut inner = self.inner.lock().unwrap();
+        let expired = inner.entries.get(key)?.expires_at <= Instant::now();
+        if expired {
+            inner.remove(key);
+            return None;
+        }
+        inner.touch(key);
+        inner.entries.get(key).map(|cached| cached.result.clone())
+    }
+
+    async fn put(&self, key: &CacheKey, result: &QueryResult, ttl: Duration) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.insert(
+            key.clone(),
+            CachedResult {
+                result: result.clone(),
+                expires_at: Instant::now() + ttl,
+            },
+        );
+        inner.touch(key);
+        while inner.order.len() > self.max_entries {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::ColumnMeta;
+
+    fn result(rows: usize) -> QueryResult {
+        QueryResult {
+            columns: vec![ColumnMeta::named("n")],
+            rows: (0..rows)
+                .map(|i| {
+                    let mut row = serde_json::Map::new();
+                    row.insert("n".to_string(), i.into());
+                    row
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_results_are_not_served() {
+        let cache = InMemoryQueryCache::new(10);
+        let key = CacheKey::new("ds", "SELECT 1");
+        cache.put(&key, &result(1), Duration::ZERO).await;
+        assert!(cache.get(&key).await.is_none());
+        assert!(cache.is_empty());
+
+        cache.put(&key, &result(2), Duration::from_secs(60)).await;
+        assert_eq!(cache.get(&key).await.unwrap().rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let cache = InMemoryQueryCache::new(2);
+        let ttl = Duration::from_secs(60);
+        let (a, b, c) = (
+            CacheKey::new("ds", "a"),
+            CacheKey::new("ds", "b"),
+            CacheKey::new("ds", "c"),
+        );
+        cache.put(&a, &result(1), ttl).await;
+        cache.put(&b, &result(1), ttl).await;
+        // Reading `a` makes `b` the least recently used
+        assert!(cache.get(&a).await.is_some());
+        cache.put(&c, &result(1), ttl).await;
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&a).await.is_some());
+        assert!(cache.get(&b).await.is_none());
+        assert!(cache.get(&c).await.is_some());
+        // The same SQL against another data source is a different entry
+        assert!(cache.get(&CacheKey::new("other", "a")).await.is_none());
+    }
+}
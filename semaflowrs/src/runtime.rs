@@ -1,14 +1,44 @@
-use crate::backends::ConnectionManager;
+use std::time::Duration;
+
+use tracing::Instrument;
+
+use crate::backends::{BackendConnection, BatchCallback, ConnectionManager};
+use crate::cancel::with_timeout;
 use crate::error::Result;
+#[cfg(feature = "arrow")]
+use crate::executor::ArrowResult;
 use crate::executor::PaginatedResult;
-use crate::pagination::{compute_query_hash, Cursor};
-use crate::query_builder::SqlBuilder;
+use crate::pagination::{
+    compute_query_hash, count_sql, stamp_model_version, Cursor, Keyset, RowCountCache,
+};
+use crate::policy::QueryContext;
+use crate::query_builder::{annotate_columns, SqlBuilder};
+use crate::query_tags::QueryTags;
 use crate::registry::FlowRegistry;
+use crate::result_cache::CacheKey;
+use crate::telemetry::{self, QueryKind};
+
+/// How long a query of `request` against `data_source` may run: the
+/// request's `query_timeout_ms`, else the datasource's `query.timeout_ms`.
+/// Zero means no timeout.
+fn query_timeout(
+    connections: &ConnectionManager,
+    data_source: &str,
+    request: &crate::flows::QueryRequest,
+) -> Option<Duration> {
+    let ms = request
+        .query_timeout_ms
+        .unwrap_or_else(|| connections.config_for(data_source).query.timeout_ms);
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
 
 #[tracing::instrument(
-    skip(registry, connections),
+    name = "semaflow.query",
+    skip(registry, connections, context),
     fields(
         flow = %request.flow,
+        kind = "rows",
+        data_source = tracing::field::Empty,
         dimensions = ?request.dimensions,
         measures = ?request.measures,
     )
@@ -17,18 +47,16 @@ pub async fn run_query(
     registry: &FlowRegistry,
     connections: &ConnectionManager,
     request: &crate::flows::QueryRequest,
+    context: &QueryContext,
 ) -> Result<crate::executor::QueryResult> {
     let start = std::time::Instant::now();
     tracing::debug!("starting query execution");
 
-    let builder = SqlBuilder;
-    let sql = builder.build_for_request(registry, connections, request)?;
-    tracing::debug!(sql_len = sql.len(), "SQL generated");
-    tracing::trace!(sql = %sql, "generated SQL");
-
     let flow = registry.get_flow(&request.flow).ok_or_else(|| {
         tracing::warn!(flow = %request.flow, "unknown flow requested");
-        crate::SemaflowError::Validation(format!("unknown flow {}", request.flow))
+        crate::SemaflowError::UnknownFlow {
+            flow: request.flow.clone(),
+        }
     })?;
     let base_table = registry
         .get_table(&flow.base_table.semantic_table)
@@ -41,16 +69,85 @@ pub async fn run_query(
         })?;
     let ds = connections.get(&base_table.data_source).ok_or_else(|| {
         tracing::warn!(data_source = %base_table.data_source, "data source not registered");
-        crate::SemaflowError::Validation(format!(
-            "data source {} not registered",
-            base_table.data_source
-        ))
+        crate::SemaflowError::UnknownDataSource {
+            data_source: base_table.data_source.clone(),
+        }
     })?;
 
-    tracing::debug!(data_source = %base_table.data_source, "executing SQL");
-    let result = ds.execute_sql(&sql).await;
+    tracing::Span::current().record("data_source", base_table.data_source.as_str());
+
+    // Without window functions, calculations are applied to the fetched rows
+    let in_memory_calculations =
+        !request.calculations.is_empty() && !ds.dialect().capabilities().window_functions;
+    let builder = SqlBuilder::default();
+    let sql = telemetry::plan_span(&request.flow).in_scope(|| {
+        if in_memory_calculations {
+            let base = crate::calculations::base_request(request);
+            crate::calculations::validate(request)?;
+            builder.build_for_request(registry, connections, &base, context)
+        } else {
+            builder.build_for_request(registry, connections, request, context)
+        }
+    })?;
+    tracing::debug!(sql_len = sql.len(), "SQL generated");
+    tracing::trace!(sql = %sql, "generated SQL");
+
+    let tags = QueryTags::for_request(
+        request,
+        context,
+        &connections.config_for(&base_table.data_source).tagging,
+    );
+
+    // Raw backend results are cached: calculations and column annotations
+    // depend on the request, not just the SQL
+    let cache = connections.result_cache_for(&request.flow);
+    let key = CacheKey::new(&base_table.data_source, &sql);
+    let cached = match cache {
+        Some((cache, _)) => {
+            let hit = cache.get(&key).await;
+            telemetry::record_cache_lookup(&request.flow, hit.is_some());
+            hit
+        }
+        None => None,
+    };
+    let mut result = match cached {
+        Some(hit) => {
+            tracing::debug!(data_source = %base_table.data_source, "serving cached result");
+            Ok(hit)
+        }
+        None => {
+            tracing::debug!(data_source = %base_table.data_source, "executing SQL");
+            let timeout = query_timeout(connections, &base_table.data_source, request);
+            let execution = async {
+                let _permit = connections
+                    .acquire(&request.flow, &base_table.data_source)
+                    .await?;
+                let annotated_sql = tags.annotate(&sql);
+                let call = with_timeout(timeout, ds.execute_sql_tagged(&annotated_sql, &tags));
+                telemetry::execute(&base_table.data_source, call, |r| r.rows.len() as u64).await
+            };
+            let result = execution.await;
+            if let (Ok(rows), Some((cache, ttl))) = (&result, cache) {
+                cache.put(&key, rows, ttl).await;
+            }
+            result
+        }
+    };
+    if let Ok(rows) = &mut result {
+        if in_memory_calculations {
+            crate::calculations::apply_in_memory(request, rows)?;
+        }
+        annotate_columns(registry, request, &mut rows.columns)?;
+    }
 
     let elapsed = start.elapsed();
+    telemetry::record_query(
+        &request.flow,
+        &base_table.data_source,
+        QueryKind::Rows,
+        elapsed,
+        result.as_ref().map(|r| r.rows.len() as u64),
+    );
     match &result {
         Ok(r) => tracing::info!(
             flow = %request.flow,
@@ -69,19 +166,272 @@ pub async fn run_query(
     result
 }
 
+/// Execute a query and deliver rows to `on_batch` as the backend produces them.
+///
+/// `page_size` and `cursor` are ignored: the whole result is streamed, in
+/// batches of at most `batch_size` rows. Returns the number of rows delivered.
+#[tracing::instrument(
+    name = "semaflow.query",
+    skip(registry, connections, context, on_batch),
+    fields(
+        flow = %request.flow,
+        kind = "stream",
+        data_source = tracing::field::Empty,
+        batch_size = batch_size,
+    )
+)]
+pub async fn run_query_streaming(
+    registry: &FlowRegistry,
+    connections: &ConnectionManager,
+    request: &crate::flows::QueryRequest,
+    context: &QueryContext,
+    batch_size: u32,
+    on_batch: &mut BatchCallback<'_>,
+) -> Result<u64> {
+    let start = std::time::Instant::now();
+    tracing::debug!("starting streaming query execution");
+
+    let sql_request = crate::flows::QueryRequest {
+        page_size: None,
+        cursor: None,
+        ..request.clone()
+    };
+
+    let builder = SqlBuilder::default();
+    let sql = telemetry::plan_span(&request.flow)
+        .in_scope(|| builder.build_for_request(registry, connections, &sql_request, context))?;
+    tracing::trace!(sql = %sql, "generated SQL");
+
+    let flow = registry.get_flow(&request.flow).ok_or_else(|| {
+        tracing::warn!(flow = %request.flow, "unknown flow requested");
+        crate::SemaflowError::UnknownFlow {
+            flow: request.flow.clone(),
+        }
+    })?;
+    let base_table = registry
+        .get_table(&flow.base_table.semantic_table)
+        .ok_or_else(|| {
+            crate::SemaflowError::Validation(format!(
+                "flow base table {} not found",
+                flow.base_table.semantic_table
+            ))
+        })?;
+    let ds = connections.get(&base_table.data_source).ok_or_else(|| {
+        tracing::warn!(data_source = %base_table.data_source, "data source not registered");
+        crate::SemaflowError::UnknownDataSource {
+            data_source: base_table.data_source.clone(),
+        }
+    })?;
+
+    let tags = QueryTags::for_request(
+        request,
+        context,
+        &connections.config_for(&base_table.data_source).tagging,
+    );
+
+    tracing::Span::current().record("data_source", base_table.data_source.as_str());
+
+    let mut annotated = |mut batch: crate::executor::QueryResult| -> Result<()> {
+        annotate_columns(registry, request, &mut batch.columns)?;
+        on_batch(batch)
+    };
+    let timeout = query_timeout(connections, &base_table.data_source, request);
+    let execution = async {
+        let _permit = connections
+            .acquire(&request.flow, &base_table.data_source)
+            .await?;
+        let annotated_sql = tags.annotate(&sql);
+        let call = with_timeout(
+            timeout,
+            ds.execute_sql_streaming(&annotated_sql, batch_size, &tags, &mut annotated),
+        );
+        telemetry::execute(&base_table.data_source, call, |rows| *rows).await
+    };
+    let result = execution.await;
+
+    let elapsed = start.elapsed();
+    telemetry::record_query(
+        &request.flow,
+        &base_table.data_source,
+        QueryKind::Stream,
+        elapsed,
+        result.as_ref().copied(),
+    );
+    match &result {
+        Ok(rows) => tracing::info!(
+            flow = %request.flow,
+            rows = rows,
+            ms = elapsed.as_millis(),
+            "streaming query completed successfully"
+        ),
+        Err(e) => tracing::error!(
+            flow = %request.flow,
+            error = %e,
+            ms = elapsed.as_millis(),
+            "streaming query failed"
+        ),
+    }
+
+    result
+}
+
+/// Execute a query and return the result as Arrow record batches.
+///
+/// Backends with native Arrow results keep the source column types, avoiding
+/// the JSON round trip of [`run_query`]. `page_size` and `cursor` are ignored.
+#[cfg(feature = "arrow")]
+#[tracing::instrument(
+    name = "semaflow.query",
+    skip(registry, connections, context),
+    fields(
+        flow = %request.flow,
+        kind = "arrow",
+        data_source = tracing::field::Empty,
+        dimensions = ?request.dimensions,
+        measures = ?request.measures,
+    )
+)]
+pub async fn run_query_arrow(
+    registry: &FlowRegistry,
+    connections: &ConnectionManager,
+    request: &crate::flows::QueryRequest,
+    context: &QueryContext,
+) -> Result<ArrowResult> {
+    let start = std::time::Instant::now();
+    tracing::debug!("starting arrow query execution");
+
+    let sql_request = crate::flows::QueryRequest {
+        page_size: None,
+        cursor: None,
+        ..request.clone()
+    };
+
+    let flow = registry.get_flow(&request.flow).ok_or_else(|| {
+        tracing::warn!(flow = %request.flow, "unknown flow requested");
+        crate::SemaflowError::UnknownFlow {
+            flow: request.flow.clone(),
+        }
+    })?;
+    let base_table = registry
+        .get_table(&flow.base_table.semantic_table)
+        .ok_or_else(|| {
+            crate::SemaflowError::Validation(format!(
+                "flow base table {} not found",
+                flow.base_table.semantic_table
+            ))
+        })?;
+    let ds = connections.get(&base_table.data_source).ok_or_else(|| {
+        tracing::warn!(data_source = %base_table.data_source, "data source not registered");
+        crate::SemaflowError::UnknownDataSource {
+            data_source: base_table.data_source.clone(),
+        }
+    })?;
+
+    // Calculations the dialect can't express are applied to fetched rows
+    if !request.calculations.is_empty() && !ds.dialect().capabilities().window_functions {
+        return run_query(registry, connections, &sql_request, context)
+            .await?
+            .try_into();
+    }
+
+    tracing::Span::current().record("data_source", base_table.data_source.as_str());
+    let sql = telemetry::plan_span(&request.flow).in_scope(|| {
+        SqlBuilder::default().build_for_request(registry, connections, &sql_request, context)
+    })?;
+    tracing::trace!(sql = %sql, "generated SQL");
+
+    let tags = QueryTags::for_request(
+        request,
+        context,
+        &connections.config_for(&base_table.data_source).tagging,
+    );
+
+    let timeout = query_timeout(connections, &base_table.data_source, request);
+    let execution = async {
+        let _permit = connections
+            .acquire(&request.flow, &base_table.data_source)
+            .await?;
+        let annotated_sql = tags.annotate(&sql);
+        let call = with_timeout(timeout, ds.execute_sql_arrow(&annotated_sql, &tags));
+        telemetry::execute(&base_table.data_source, call, |r| r.num_rows() as u64).await
+    };
+    let result = execution.await;
+
+    let elapsed = start.elapsed();
+    telemetry::record_query(
+        &request.flow,
+        &base_table.data_source,
+        QueryKind::Arrow,
+        elapsed,
+        result.as_ref().map(|r| r.num_rows() as u64),
+    );
+    match &result {
+        Ok(r) => tracing::info!(
+            flow = %request.flow,
+            rows = r.num_rows(),
+            ms = elapsed.as_millis(),
+            "arrow query completed successfully"
+        ),
+        Err(e) => tracing::error!(
+            flow = %request.flow,
+            error = %e,
+            ms = elapsed.as_millis(),
+            "arrow query failed"
+        ),
+    }
+
+    result
+}
+
+/// Total rows of the unpaged `sql` for `count_total`: counted with
+/// `COUNT(*)` for the first page, then served from the connection manager's
+/// [`RowCountCache`] for the pages that follow.
+async fn count_rows(
+    connections: &ConnectionManager,
+    ds: &dyn BackendConnection,
+    data_source: &str,
+    sql: &str,
+    tags: &QueryTags,
+    timeout: Option<Duration>,
+) -> Result<u64> {
+    let key = RowCountCache::key(data_source, sql);
+    if let Some(total) = connections.row_counts().get(key) {
+        tracing::debug!(data_source = %data_source, total, "serving cached row count");
+        return Ok(total);
+    }
+    let count = count_sql(sql, ds.dialect());
+    let result = with_timeout(timeout, ds.execute_sql_tagged(&tags.annotate(&count), tags)).await?;
+    let total = result
+        .rows
+        .first()
+        .and_then(|row| row.values().next())
+        .and_then(|value| value.as_u64().or_else(|| value.as_str()?.parse().ok()))
+        .ok_or_else(|| {
+            crate::SemaflowError::Execution("row count query returned no count".to_string())
+        })?;
+    connections.row_counts().insert(key, total);
+    Ok(total)
+}
+
 /// Execute a paginated query against a semantic flow.
 ///
 /// This function handles cursor-based pagination by:
 /// 1. Building SQL without LIMIT/OFFSET (the backend adds those or uses native pagination)
 /// 2. Computing a query hash for cursor validation
 /// 3. Decoding the cursor if present
-/// 4. Calling the backend's paginated execution method
+/// 4. Wrapping the SQL for keyset pagination when the order allows it
+/// 5. Calling the backend's paginated execution method
+/// 6. Counting the total rows when `count_total` is set and the backend
+///    doesn't report them
 ///
 /// Returns a `PaginatedResult` with the current page and cursor for the next page.
 #[tracing::instrument(
-    skip(registry, connections),
+    name = "semaflow.query",
+    skip(registry, connections, context),
     fields(
         flow = %request.flow,
+        kind = "page",
+        data_source = tracing::field::Empty,
         page_size = ?request.page_size,
         has_cursor = request.cursor.is_some(),
     )
@@ -90,6 +440,7 @@ pub async fn run_query_paginated(
     registry: &FlowRegistry,
     connections: &ConnectionManager,
     request: &crate::flows::QueryRequest,
+    context: &QueryContext,
 ) -> Result<PaginatedResult> {
     let start = std::time::Instant::now();
     tracing::debug!("starting paginated query execution");
@@ -101,20 +452,17 @@ pub async fn run_query_paginated(
 
     // Build SQL without limit/offset - the backend handles pagination via LIMIT/OFFSET
     // The request.limit is a total cap that should be enforced separately (future enhancement)
+    // page_size is kept: the planner ignores it, but flow limits check it against max_rows
     let sql_request = crate::flows::QueryRequest {
-        flow: request.flow.clone(),
-        dimensions: request.dimensions.clone(),
-        measures: request.measures.clone(),
-        filters: request.filters.clone(),
-        order: request.order.clone(),
-        limit: None,     // Don't include limit - backend adds LIMIT/OFFSET for pagination
-        offset: None,    // Don't pass offset - cursor handles this
-        page_size: None, // Don't include pagination in SQL
+        limit: None,  // Don't include limit - backend adds LIMIT/OFFSET for pagination
+        offset: None, // Don't pass offset - cursor handles this
         cursor: None,
+        ..request.clone()
     };
 
-    let builder = SqlBuilder;
-    let sql = builder.build_for_request(registry, connections, &sql_request)?;
+    let builder = SqlBuilder::default();
+    let sql = telemetry::plan_span(&request.flow)
+        .in_scope(|| builder.build_for_request(registry, connections, &sql_request, context))?;
     tracing::debug!(sql_len = sql.len(), "SQL generated for pagination");
     tracing::trace!(sql = %sql, "generated SQL");
 
@@ -122,10 +470,14 @@ pub async fn run_query_paginated(
     let query_hash = compute_query_hash(request);
     tracing::trace!(query_hash = query_hash, "computed query hash");
 
+    // Version of the flow definition, so cursors can't span model changes
+    let model_version = registry.flow_version(&request.flow)?;
+
     // Decode cursor if present
     let cursor = match &request.cursor {
         Some(encoded) => {
             let decoded = Cursor::decode(encoded)?;
+            decoded.validate_model_version(model_version)?;
             decoded.validate_query_hash(query_hash)?;
             Some(decoded)
         }
@@ -135,7 +487,9 @@ pub async fn run_query_paginated(
     // Get the backend connection
     let flow = registry.get_flow(&request.flow).ok_or_else(|| {
         tracing::warn!(flow = %request.flow, "unknown flow requested");
-        crate::SemaflowError::Validation(format!("unknown flow {}", request.flow))
+        crate::SemaflowError::UnknownFlow {
+            flow: request.flow.clone(),
+        }
     })?;
     let base_table = registry
         .get_table(&flow.base_table.semantic_table)
@@ -148,24 +502,104 @@ pub async fn run_query_paginated(
         })?;
     let ds = connections.get(&base_table.data_source).ok_or_else(|| {
         tracing::warn!(data_source = %base_table.data_source, "data source not registered");
-        crate::SemaflowError::Validation(format!(
-            "data source {} not registered",
-            base_table.data_source
-        ))
+        crate::SemaflowError::UnknownDataSource {
+            data_source: base_table.data_source.clone(),
+        }
     })?;
 
+    tracing::Span::current().record("data_source", base_table.data_source.as_str());
     tracing::debug!(
         data_source = %base_table.data_source,
         page_size = page_size,
         "executing paginated SQL"
     );
 
-    // Execute paginated query
-    let result = ds
-        .execute_sql_paginated(&sql, page_size, cursor.as_ref(), query_hash)
-        .await;
+    // Seek past the previous page instead of OFFSET when the order is deterministic
+    let keyset =
+        Keyset::for_request(request).filter(|_| ds.dialect().capabilities().row_value_comparison);
+    let (page_sql, backend_cursor) = match &keyset {
+        Some(keyset) => {
+            let after = cursor.as_ref().and_then(Cursor::after);
+            let page_sql = keyset.render(&sql, after, ds.dialect())?;
+            // A seek starts right after the previous page, so the backend adds no OFFSET
+            let backend_cursor = if after.is_some() {
+                None
+            } else {
+                cursor.clone()
+            };
+            (page_sql, backend_cursor)
+        }
+        None => (sql.clone(), cursor.clone()),
+    };
+    tracing::debug!(keyset = keyset.is_some(), "pagination strategy chosen");
+
+    let tags = QueryTags::for_request(
+        request,
+        context,
+        &connections.config_for(&base_table.data_source).tagging,
+    );
+
+    // Execute paginated query; the slot is held through the row count
+    let timeout = query_timeout(connections, &base_table.data_source, request);
+    let execution = async {
+        let _permit = connections
+            .acquire(&request.flow, &base_table.data_source)
+            .await?;
+        let annotated_sql = tags.annotate(&page_sql);
+        let call = with_timeout(
+            timeout,
+            ds.execute_sql_paginated_tagged(
+                &annotated_sql,
+                page_size,
+                backend_cursor.as_ref(),
+                query_hash,
+                &tags,
+            ),
+        );
+        let mut r =
+            telemetry::execute(&base_table.data_source, call, |r| r.rows.len() as u64).await?;
+        if let Some(keyset) = &keyset {
+            r.cursor = if r.has_more {
+                let offset = cursor.as_ref().map_or(0, Cursor::offset) + page_size as u64;
+                let next = match r.rows.last().and_then(|row| keyset.after(row, &r.columns)) {
+                    Some(after) => Cursor::keyset(offset, query_hash, after),
+                    None => Cursor::sql(offset, query_hash),
+                };
+                Some(next.encode()?)
+            } else {
+                None
+            };
+        }
+        r.cursor = r
+            .cursor
+            .map(|c| stamp_model_version(&c, model_version))
+            .transpose()?;
+        annotate_columns(registry, request, &mut r.columns)?;
+        if request.count_total && r.total_rows.is_none() {
+            let total = count_rows(
+                connections,
+                ds.as_ref(),
+                &base_table.data_source,
+                &sql,
+                &tags,
+                timeout,
+            )
+            .instrument(telemetry::count_span(&base_table.data_source))
+            .await?;
+            r.total_rows = Some(total);
+        }
+        Ok(r)
+    };
+    let result: Result<PaginatedResult> = execution.await;
 
     let elapsed = start.elapsed();
+    telemetry::record_query(
+        &request.flow,
+        &base_table.data_source,
+        QueryKind::Page,
+        elapsed,
+        result.as_ref().map(|r| r.rows.len() as u64),
+    );
     match &result {
         Ok(r) => tracing::info!(
             flow = %request.flow,
@@ -0,0 +1,831 @@
+//! Scheduled query definitions for periodic metric extracts.
+//!
+//! A [`ScheduledQuery`] pairs a [`QueryRequest`] with a cron expression and a
+//! sink. SemaFlow does not run a timer itself: the embedding service decides
+//! when to call [`run_scheduled_query`], using [`CronSchedule::next_after`] to
+//! find the next due time. Sinks are written by the warehouse itself, so
+//! results never pass through the process:
+//! - Parquet file: `COPY (query) TO 'path' (FORMAT PARQUET)` (DuckDB only)
+//! - Table: `CREATE OR REPLACE TABLE ... AS query`, or `INSERT INTO` when appending
+//!
+//! Table sinks in `incremental` mode rebuild only recent rows: each run
+//! deletes the rows from a few days before the previous run onwards and
+//! inserts the request's results for that window. The date of each run is
+//! kept in a `_semaflow_refresh_state` table in the sink's data source; the
+//! first run builds the whole table.
+//!
+//! Definitions live next to the semantic model in a `schedules/` directory,
+//! one YAML file per schedule:
+//!
+//! ```yaml
+//! name: daily_revenue
+//! cron: "0 6 * * *"
+//! request:
+//!   flow: sales
+//!   dimensions: [country]
+//!   measures: [revenue]
+//!   time_filter: yesterday
+//! sink:
+//!   type: table
+//!   table: daily_revenue
+//!   mode: append
+//! ```
+//!
+//! An incremental sink names the requested dimension that bounds the window.
+//! When it is requested at a grain, the window starts on a whole period:
+//!
+//! ```yaml
+//! sink:
+//!   type: table
+//!   table: monthly_revenue
+//!   mode: incremental
+//!   incremental:
+//!     field: o.created_at   # requested as {field: o.created_at, grain: month}
+//!     column: created_at    # sink column holding it; defaults to the field name
+//!     lookback_days: 3
+//! ```
+
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use glob::glob;
+use serde::{Deserialize, Serialize};
+
+use crate::backends::{BackendConnection, ConnectionManager};
+use crate::dialect::Dialect;
+use crate::error::{Result, SemaflowError};
+use crate::flows::{Filter, FilterExpr, FilterOp, QueryRequest, TimeGrain};
+use crate::policy::QueryContext;
+use crate::query_builder::SqlBuilder;
+use crate::query_tags::QueryTags;
+use crate::registry::FlowRegistry;
+
+/// A query run on a cron schedule with its results written to a sink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduledQuery {
+    pub name: String,
+    pub request: QueryRequest,
+    pub cron: CronSchedule,
+    pub sink: ScheduleSink,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// User attributes the extract runs with, for tables with access policies.
+    #[serde(default)]
+    pub context: QueryContext,
+}
+
+/// Destination for a scheduled query's results.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ScheduleSink {
+    /// Parquet file written by the warehouse (a local path for DuckDB).
+    Parquet { path: String },
+    /// Table in the flow's data source.
+    Table {
+        table: String,
+        #[serde(default)]
+        mode: WriteMode,
+        /// Refresh window settings, required in `incremental` mode.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        incremental: Option<IncrementalRefresh>,
+    },
+}
+
+/// How a table sink treats existing rows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    /// Recreate the table from each run's results.
+    #[default]
+    Replace,
+    /// Insert each run's results into an existing table.
+    Append,
+    /// Replace the rows of a recent time window, see [`IncrementalRefresh`].
+    Incremental,
+}
+
+/// The time window an `incremental` table sink rebuilds on each run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IncrementalRefresh {
+    /// Requested dimension bounding the window, e.g. `o.created_at`.
+    pub field: String,
+    /// Sink column holding `field`; defaults to its unqualified name.
+    #[serde(default)]
+    pub column: Option<String>,
+    /// Days before the previous run rebuilt again, for rows arriving late.
+    #[serde(default = "default_lookback_days")]
+    pub lookback_days: u32,
+}
+
+fn default_lookback_days() -> u32 {
+    1
+}
+
+impl IncrementalRefresh {
+    pub fn column(&self) -> &str {
+        self.column
+            .as_deref()
+            .unwrap_or_else(|| self.field.rsplit('.').next().unwrap_or(&self.field))
+    }
+
+    /// First day rebuilt after a run on `watermark` (`YYYY-MM-DD`), moved back
+    /// to the start of its `grain` period so no period is left half rebuilt.
+    /// `None` when the watermark is not a date or the grain is fiscal.
+    pub fn window_start(
+        &self,
+        watermark: &str,
+        grain: Option<&TimeGrain>,
+        weeks_start_sunday: bool,
+    ) -> Option<String> {
+        let day = days_from_date(watermark)? - i64::from(self.lookback_days);
+        let (year, month, day_of_month) = civil_from_days(day);
+        let start = match grain {
+            None
+            | Some(TimeGrain::Second | TimeGrain::Minute | TimeGrain::Hour | TimeGrain::Day) => day,
+            Some(TimeGrain::Week) => {
+                // 1970-01-01 was a Thursday
+                let weekday = (day + if weeks_start_sunday { 4 } else { 3 }).rem_euclid(7);
+                day - weekday
+            }
+            // Fiscal periods depend on the flow's calendar; schedules reject them
+            Some(
+                TimeGrain::FiscalWeek
+                | TimeGrain::FiscalMonth
+                | TimeGrain::FiscalQuarter
+                | TimeGrain::FiscalYear,
+            ) => return None,
+            Some(TimeGrain::Month) => day - day_of_month + 1,
+            Some(TimeGrain::Quarter) => days_from_civil(year, (month - 1) / 3 * 3 + 1, 1),
+            Some(TimeGrain::Year) => days_from_civil(year, 1, 1),
+        };
+        Some(format_date(start))
+    }
+}
+
+/// Table recording the date of each incremental refresh, per data source.
+pub const REFRESH_STATE_TABLE: &str = "_semaflow_refresh_state";
+
+impl ScheduledQuery {
+    /// Load every schedule in `root/schedules/*.yml|yaml`. Returns an empty
+    /// list when the directory does not exist.
+    pub fn load_from_dir<P: AsRef<Path>>(root: P) -> Result<Vec<ScheduledQuery>> {
+        let dir = root.as_ref().join("schedules");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut schedules = Vec::new();
+        for pattern in ["yml", "yaml"] {
+            for entry in glob(&format!("{}/*.{pattern}", dir.display()))
+                .map_err(|e| SemaflowError::Other(e.into()))?
+                .flatten()
+            {
+                let contents = fs::read_to_string(&entry)?;
+                let schedule: ScheduledQuery = serde_yaml::from_str(&contents).map_err(|e| {
+                    SemaflowError::Validation(format!(
+                        "failed to parse schedule {}: {e}",
+                        entry.display()
+                    ))
+                })?;
+                incremental_refresh(&schedule).map_err(|e| {
+                    SemaflowError::Validation(format!("schedule {}: {e}", entry.display()))
+                })?;
+                schedules.push(schedule);
+            }
+        }
+        schedules.sort_by(|a, b| a.name.cmp(&b.name));
+        if let Some(pair) = schedules.windows(2).find(|w| w[0].name == w[1].name) {
+            return Err(SemaflowError::Validation(format!(
+                "duplicate schedule name {}",
+                pair[0].name
+            )));
+        }
+        Ok(schedules)
+    }
+}
+
+/// Build the statements that write `sql`'s results to `sink`, in execution order.
+pub fn sink_statements(
+    dialect: &dyn Dialect,
+    sql: &str,
+    sink: &ScheduleSink,
+) -> Result<Vec<String>> {
+    match sink {
+        ScheduleSink::Parquet { path } => {
+            if !dialect.capabilities().parquet_export {
+                return Err(SemaflowError::Validation(
+                    "parquet sinks are not supported by this data source".to_string(),
+                ));
+            }
+            let path = path.replace('\'', "''");
+            Ok(vec![format!("COPY ({sql}) TO '{path}' (FORMAT PARQUET)")])
+        }
+        ScheduleSink::Table { table, mode, .. } => {
+            let target = dialect.qualify_table(table);
+            Ok(match mode {
+                WriteMode::Append => vec![format!("INSERT INTO {target} {sql}")],
+                // An incremental table is built in full on its first run
+                WriteMode::Replace | WriteMode::Incremental
+                    if dialect.capabilities().create_or_replace_table =>
+                {
+                    vec![format!("CREATE OR REPLACE TABLE {target} AS {sql}")]
+                }
+                WriteMode::Replace | WriteMode::Incremental => vec![
+                    format!("DROP TABLE IF EXISTS {target}"),
+                    format!("CREATE TABLE {target} AS {sql}"),
+                ],
+            })
+        }
+    }
+}
+
+/// Build the statements replacing the rows of `table` whose `column` is on or
+/// after `from` (`YYYY-MM-DD`) with `sql`'s results, in execution order.
+pub fn incremental_statements(
+    dialect: &dyn Dialect,
+    sql: &str,
+    table: &str,
+    column: &str,
+    from: &str,
+) -> Vec<String> {
+    let target = dialect.qualify_table(table);
+    let from = dialect.render_literal(&serde_json::Value::from(from));
+    vec![
+        format!(
+            "DELETE FROM {target} WHERE {} >= {from}",
+            dialect.quote_ident(column)
+        ),
+        format!("INSERT INTO {target} {sql}"),
+    ]
+}
+
+/// The table and refresh settings of an `incremental` sink; `None` for other sinks.
+fn incremental_refresh(
+    schedule: &ScheduledQuery,
+) -> std::result::Result<Option<(&str, &IncrementalRefresh)>, String> {
+    let ScheduleSink::Table {
+        table,
+        mode,
+        incremental,
+    } = &schedule.sink
+    else {
+        return Ok(None);
+    };
+    match (mode, incremental) {
+        (WriteMode::Incremental, Some(refresh)) => {
+            let mut requested = schedule
+                .request
+                .dimensions
+                .iter()
+                .filter(|d| d.field == refresh.field)
+                .peekable();
+            if requested.peek().is_none() {
+                return Err(format!(
+                    "incremental field {} is not a requested dimension",
+                    refresh.field
+                ));
+            }
+            if requested.any(|d| d.grain.as_ref().is_some_and(TimeGrain::is_fiscal)) {
+                return Err(format!(
+                    "incremental field {} cannot use a fiscal grain",
+                    refresh.field
+                ));
+            }
+            Ok(Some((table.as_str(), refresh)))
+        }
+        (WriteMode::Incremental, None) => {
+            Err("incremental mode requires `incremental` settings".to_string())
+        }
+        (_, Some(_)) => Err("`incremental` settings require mode: incremental".to_string()),
+        (_, None) => Ok(None),
+    }
+}
+
+/// Run a scheduled query once, writing its results to the configured sink.
+#[tracing::instrument(skip(registry, connections, schedule), fields(schedule = %schedule.name))]
+pub async fn run_scheduled_query(
+    registry: &FlowRegistry,
+    connections: &ConnectionManager,
+    schedule: &ScheduledQuery,
+) -> Result<()> {
+    let start = std::time::Instant::now();
+    // Extracts always cover the whole result
+    let request = QueryRequest {
+        page_size: None,
+        cursor: None,
+        ..schedule.request.clone()
+    };
+    let build = |request: &QueryRequest| {
+        SqlBuilder::default().build_for_request(registry, connections, request, &schedule.context)
+    };
+
+    let flow = registry
+        .get_flow(&request.flow)
+        .ok_or_else(|| SemaflowError::UnknownFlow {
+            flow: request.flow.clone(),
+        })?;
+    let base_table = registry
+        .get_table(&flow.base_table.semantic_table)
+        .ok_or_else(|| {
+            SemaflowError::Validation(format!(
+                "flow base table {} not found",
+                flow.base_table.semantic_table
+            ))
+        })?;
+    let ds = connections.get(&base_table.data_source).ok_or_else(|| {
+        SemaflowError::UnknownDataSource {
+            data_source: base_table.data_source.clone(),
+        }
+    })?;
+
+    let tags = QueryTags::for_request(
+        &request,
+        &schedule.context,
+        &connections.config_for(&base_table.data_source).tagging,
+    );
+    let dialect = ds.dialect();
+    let statements = match incremental_refresh(schedule).map_err(SemaflowError::Validation)? {
+        None => sink_statements(dialect, &build(&request)?, &schedule.sink)?,
+        Some((table, refresh)) => {
+            refresh_statements(
+                ds.as_ref(),
+                &tags,
+                schedule,
+                &request,
+                table,
+                refresh,
+                build,
+            )
+            .await?
+        }
+    };
+    for statement in statements {
+        tracing::trace!(sql = %statement, "executing sink statement");
+        ds.execute_sql_tagged(&tags.annotate(&statement), &tags)
+            .await?;
+    }
+    tracing::info!(
+        schedule = %schedule.name,
+        ms = start.elapsed().as_millis(),
+        "scheduled query completed"
+    );
+    Ok(())
+}
+
+/// Statements refreshing an `incremental` table sink from its previous run,
+/// followed by those recording this run in [`REFRESH_STATE_TABLE`].
+async fn refresh_statements(
+    ds: &dyn BackendConnection,
+    tags: &QueryTags,
+    schedule: &ScheduledQuery,
+    request: &QueryRequest,
+    table: &str,
+    refresh: &IncrementalRefresh,
+    build: impl Fn(&QueryRequest) -> Result<String>,
+) -> Result<Vec<String>> {
+    let dialect = ds.dialect();
+    let state = dialect.qualify_table(REFRESH_STATE_TABLE);
+    let key = |column: &str, value: &str| {
+        format!(
+            "{} = {}",
+            dialect.quote_ident(column),
+            dialect.render_literal(&serde_json::Value::from(value))
+        )
+    };
+    let state_key = format!(
+        "{} AND {}",
+        key("schedule_name", &schedule.name),
+        key("target_table", table)
+    );
+    ds.execute_sql_tagged(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {state} AS SELECT '' AS {}, '' AS {}, '' AS {} \
+             WHERE 1 = 0",
+            dialect.quote_ident("schedule_name"),
+            dialect.quote_ident("target_table"),
+            dialect.quote_ident("watermark"),
+        ),
+        tags,
+    )
+    .await?;
+    let previous = ds
+        .execute_sql_tagged(
+            &format!(
+                "SELECT {} FROM {state} WHERE {state_key}",
+                dialect.quote_ident("watermark")
+            ),
+            tags,
+        )
+        .await?;
+    let grain = request
+        .dimensions
+        .iter()
+        .filter(|d| d.field == refresh.field)
+        .filter_map(|d| d.grain.as_ref())
+        .max();
+    let from = previous
+        .rows
+        .iter()
+        .filter_map(|row| row.get("watermark")?.as_str())
+        .filter_map(|w| refresh.window_start(w, grain, dialect.weeks_start_sunday()))
+        .min();
+
+    let mut statements = match from {
+        Some(from) => {
+            let mut request = request.clone();
+            request.filters.push(FilterExpr::Condition(Filter {
+                field: refresh.field.clone(),
+                op: FilterOp::Gte,
+                value: serde_json::Value::from(from.as_str()),
+            }));
+            incremental_statements(dialect, &build(&request)?, table, refresh.column(), &from)
+        }
+        None => sink_statements(dialect, &build(request)?, &schedule.sink)?,
+    };
+    // Recorded last, so a failed run is retried from the same window
+    let today = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64 / 86_400)
+        .unwrap_or_default();
+    statements.push(format!("DELETE FROM {state} WHERE {state_key}"));
+    statements.push(format!(
+        "INSERT INTO {state} VALUES ({}, {}, {})",
+        dialect.render_literal(&serde_json::Value::from(schedule.name.as_str())),
+        dialect.render_literal(&serde_json::Value::from(table)),
+        dialect.render_literal(&serde_json::Value::from(format_date(today))),
+    ));
+    Ok(statements)
+}
+
+/// A five-field cron expression (`minute hour day-of-month month day-of-week`),
+/// evaluated in UTC.
+///
+/// Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps
+/// (`*/15`, `0-30/10`). Day of week runs 0-6 from Sunday; 7 is also Sunday.
+/// The `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` shorthands are
+/// accepted. As in standard cron, when both day fields are restricted a day
+/// matches if either does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+/// How far ahead [`CronSchedule::next_after`] searches (covers Feb 29 schedules).
+const MAX_SEARCH_DAYS: i64 = 366 * 8;
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> std::result::Result<Self, String> {
+        let expanded = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields.as_slice() else {
+            return Err(format!(
+                "invalid cron expression '{expr}': expected 5 fields (minute hour day month weekday)"
+            ));
+        };
+        let field = |raw: &str, name: &str, min: u32, max: u32| {
+            parse_cron_field(raw, min, max)
+                .map_err(|e| format!("invalid cron expression '{expr}': {name} {e}"))
+        };
+        let mut days_of_week = field(dow, "day-of-week", 0, 7)?;
+        // 7 is an alias for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(CronSchedule {
+            expr: expr.trim().to_string(),
+            minutes: field(minute, "minute", 0, 59)?,
+            hours: field(hour, "hour", 0, 23)?,
+            days_of_month: field(dom, "day-of-month", 1, 31)?,
+            months: field(month, "month", 1, 12)?,
+            days_of_week,
+            dom_restricted: *dom != "*",
+            dow_restricted: *dow != "*",
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+
+    /// The first matching minute strictly after `unix_secs`, as Unix seconds.
+    /// Returns `None` if nothing matches within eight years (e.g. `0 0 31 2 *`).
+    pub fn next_after(&self, unix_secs: i64) -> Option<i64> {
+        let first_minute = unix_secs.div_euclid(60) + 1;
+        let first_day = first_minute.div_euclid(1440);
+        for day in first_day..first_day + MAX_SEARCH_DAYS {
+            if !self.matches_day(day) {
+                continue;
+            }
+            let start = if day == first_day {
+                first_minute.rem_euclid(1440)
+            } else {
+                0
+            };
+            for minute_of_day in start..1440 {
+                let (hour, minute) = (minute_of_day / 60, minute_of_day % 60);
+                if bit(self.hours, hour) && bit(self.minutes, minute) {
+                    return Some((day * 1440 + minute_of_day) * 60);
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, days_since_epoch: i64) -> bool {
+        let (_, month, day) = civil_from_days(days_since_epoch);
+        if !bit(self.months, month) {
+            return false;
+        }
+        // 1970-01-01 was a Thursday
+        let weekday = (days_since_epoch + 4).rem_euclid(7);
+        let dom = bit(self.days_of_month, day);
+        let dow = bit(self.days_of_week, weekday);
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = String;
+
+    fn try_from(raw: String) -> std::result::Result<Self, Self::Error> {
+        CronSchedule::parse(&raw)
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.expr
+    }
+}
+
+fn bit(mask: u64, value: i64) -> bool {
+    (0..64).contains(&value) && mask & (1 << value) != 0
+}
+
+/// Parse one cron field into a bitmask of allowed values.
+fn parse_cron_field(raw: &str, min: u32, max: u32) -> std::result::Result<u64, String> {
+    let mut mask = 0u64;
+    for part in raw.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("has invalid step '{step}'"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let value = |s: &str| {
+            s.parse::<u32>()
+                .ok()
+                .filter(|v| (min..=max).contains(v))
+                .ok_or_else(|| format!("value '{s}' is outside {min}-{max}"))
+        };
+        let (lo, hi) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((lo, hi)) => (value(lo)?, value(hi)?),
+                // `5/10` means from 5 to the end in steps of 10
+                None if part.contains('/') => (value(range)?, max),
+                None => {
+                    let v = value(range)?;
+                    (v, v)
+                }
+            },
+        };
+        if lo > hi {
+            return Err(format!("range '{range}' is reversed"));
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+/// Days since 1970-01-01 of a value starting with a `YYYY-MM-DD` date.
+pub(crate) fn days_from_date(value: &str) -> Option<i64> {
+    let date = value.get(..10)?;
+    let mut parts = date.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+/// Convert a (year, month, day) civil date to days since 1970-01-01.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Format days since 1970-01-01 as `YYYY-MM-DD`.
+fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01 00:00:00 UTC, a Monday
+    const JAN_1_2024: i64 = 1_704_067_200;
+
+    #[test]
+    fn next_after_finds_following_match() {
+        let daily = CronSchedule::parse("30 6 * * *").unwrap();
+        assert_eq!(
+            daily.next_after(JAN_1_2024),
+            Some(JAN_1_2024 + 6 * 3600 + 30 * 60)
+        );
+        // Exactly on a match moves to the next day
+        let at = JAN_1_2024 + 6 * 3600 + 30 * 60;
+        assert_eq!(daily.next_after(at), Some(at + 86_400));
+
+        let quarter_hour = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            quarter_hour.next_after(JAN_1_2024 + 61),
+            Some(JAN_1_2024 + 900)
+        );
+
+        // Sundays only: 2024-01-07
+        let weekly = CronSchedule::parse("@weekly").unwrap();
+        assert_eq!(weekly.next_after(JAN_1_2024), Some(JAN_1_2024 + 6 * 86_400));
+
+        // Leap day 2024-02-29
+        let leap = CronSchedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(leap.next_after(JAN_1_2024), Some(1_709_164_800));
+        assert_eq!(
+            CronSchedule::parse("0 0 31 2 *").unwrap().next_after(0),
+            None
+        );
+    }
+
+    #[test]
+    fn dates_convert_to_days_since_epoch() {
+        assert_eq!(days_from_date("1970-01-01"), Some(0));
+        assert_eq!(days_from_date("2024-03-01T00:00:00"), Some(19_783));
+        // 2024 is a leap year
+        assert_eq!(days_from_date("2024-02-01"), Some(19_754));
+        assert_eq!(days_from_date("yesterday"), None);
+        assert_eq!(format_date(19_783), "2024-03-01");
+    }
+
+    #[test]
+    fn incremental_window_starts_on_whole_periods() {
+        let refresh = IncrementalRefresh {
+            field: "o.created_at".to_string(),
+            column: None,
+            lookback_days: 3,
+        };
+        assert_eq!(refresh.column(), "created_at");
+        let start = |grain: Option<TimeGrain>, sunday: bool| {
+            refresh.window_start("2024-05-17", grain.as_ref(), sunday)
+        };
+        assert_eq!(start(None, false).as_deref(), Some("2024-05-14"));
+        // 2024-05-14 was a Tuesday
+        assert_eq!(
+            start(Some(TimeGrain::Week), false).as_deref(),
+            Some("2024-05-13")
+        );
+        assert_eq!(
+            start(Some(TimeGrain::Week), true).as_deref(),
+            Some("2024-05-12")
+        );
+        assert_eq!(
+            start(Some(TimeGrain::Month), false).as_deref(),
+            Some("2024-05-01")
+        );
+        assert_eq!(
+            start(Some(TimeGrain::Quarter), false).as_deref(),
+            Some("2024-04-01")
+        );
+        assert_eq!(
+            start(Some(TimeGrain::Year), false).as_deref(),
+            Some("2024-01-01")
+        );
+        assert_eq!(refresh.window_start("never", None, false), None);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_expressions() {
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "5-1 * * * *",
+            "*/0 * * * *",
+        ] {
+            assert!(CronSchedule::parse(bad).is_err(), "{bad}");
+        }
+        let seven = CronSchedule::parse("0 0 * * 7").unwrap();
+        let zero = CronSchedule::parse("0 0 * * 0").unwrap();
+        assert_eq!(seven.next_after(JAN_1_2024), zero.next_after(JAN_1_2024));
+    }
+
+    #[test]
+    fn schedule_yaml_round_trips_sinks() {
+        let schedule: ScheduledQuery = serde_yaml::from_str(
+            r#"
+name: daily_revenue
+cron: "0 6 * * *"
+request:
+  flow: sales
+  measures: [revenue]
+sink:
+  type: parquet
+  path: /tmp/revenue.parquet
+"#,
+        )
+        .unwrap();
+        assert_eq!(schedule.cron.as_str(), "0 6 * * *");
+        assert_eq!(
+            schedule.sink,
+            ScheduleSink::Parquet {
+                path: "/tmp/revenue.parquet".to_string()
+            }
+        );
+
+        let sink: ScheduleSink = serde_yaml::from_str("type: table\ntable: extract").unwrap();
+        assert_eq!(
+            sink,
+            ScheduleSink::Table {
+                table: "extract".to_string(),
+                mode: WriteMode::Replace,
+                incremental: None,
+            }
+        );
+
+        let schedule: ScheduledQuery = serde_yaml::from_str(
+            r#"
+name: monthly_revenue
+cron: "@daily"
+request:
+  flow: sales
+  dimensions: [{field: o.created_at, grain: month}]
+  measures: [revenue]
+sink:
+  type: table
+  table: monthly_revenue
+  mode: incremental
+  incremental:
+    field: o.created_at
+"#,
+        )
+        .unwrap();
+        let (table, refresh) = incremental_refresh(&schedule).unwrap().unwrap();
+        assert_eq!(table, "monthly_revenue");
+        assert_eq!(refresh.lookback_days, 1);
+
+        let mut missing = schedule.clone();
+        missing.request.dimensions.clear();
+        assert!(incremental_refresh(&missing).is_err());
+    }
+}
@@ -1,41 +1,132 @@
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::config::SchemaCacheConfig;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+use crate::config::{SchemaCacheConfig, SemaflowConfig};
+use crate::error::Result;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ColumnSchema {
     pub name: String,
     pub data_type: String,
     pub nullable: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ForeignKey {
     pub from_column: String,
     pub to_table: String,
     pub to_column: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TableSchema {
     pub columns: Vec<ColumnSchema>,
     pub primary_keys: Vec<String>,
+    #[serde(default)]
     pub foreign_keys: Vec<ForeignKey>,
+    /// Columns of each UNIQUE constraint, where the backend reports them.
+    #[serde(default)]
+    pub unique_keys: Vec<Vec<String>>,
+}
+
+/// Row count and distinct value counts of a table, collected by
+/// [`crate::backends::ConnectionManager::collect_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableStats {
+    pub row_count: u64,
+    /// Distinct non-NULL values by column.
+    pub distinct_counts: BTreeMap<String, u64>,
+}
+
+impl TableStats {
+    /// Columns holding a different value on every row, when the table has
+    /// any rows.
+    pub fn unique_columns(&self) -> impl Iterator<Item = &str> {
+        self.distinct_counts
+            .iter()
+            .filter(|(_, count)| self.row_count > 0 && **count == self.row_count)
+            .map(|(column, _)| column.as_str())
+    }
+}
+
+/// A cached schema as written to a [`SchemaStore`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSchema {
+    pub data_source: String,
+    pub table: String,
+    /// When the schema was fetched, in seconds since the Unix epoch.
+    pub fetched_at: u64,
+    pub schema: TableSchema,
+}
+
+/// Storage keeping cached schemas across restarts. Entries are stored with
+/// the time they were fetched, so implementations need not track TTLs.
+pub trait SchemaStore: Send + Sync {
+    fn load(&self) -> Result<Vec<StoredSchema>>;
+    fn save(&self, schemas: &[StoredSchema]) -> Result<()>;
+}
+
+/// Keeps schemas in a JSON file, e.g. the `path` of
+/// [`SchemaCacheConfig`]. A missing file loads as empty.
+#[derive(Debug, Clone)]
+pub struct FileSchemaStore {
+    path: PathBuf,
+}
+
+impl FileSchemaStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SchemaStore for FileSchemaStore {
+    fn load(&self) -> Result<Vec<StoredSchema>> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, schemas: &[StoredSchema]) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write then rename, so a crash never leaves a truncated file
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(schemas)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
 }
 
 /// Cache entry with timestamp for TTL tracking.
 #[derive(Debug, Clone)]
-struct CacheEntry {
-    schema: TableSchema,
-    inserted_at: Instant,
+struct CacheEntry<T> {
+    value: T,
+    inserted_at: SystemTime,
+}
+
+impl<T> CacheEntry<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            inserted_at: SystemTime::now(),
+        }
+    }
 }
 
 /// Schema cache with TTL and size limits.
 #[derive(Debug)]
 pub struct SchemaCache {
-    schemas: HashMap<(String, String), CacheEntry>,
+    schemas: HashMap<(String, String), CacheEntry<TableSchema>>,
+    stats: HashMap<(String, String), CacheEntry<TableStats>>,
     ttl: Duration,
+    /// TTLs of data sources overriding `ttl`.
+    ttls: HashMap<String, Duration>,
     max_size: usize,
 }
 
@@ -54,31 +145,53 @@ impl SchemaCache {
     pub fn with_config(config: &SchemaCacheConfig) -> Self {
         Self {
             schemas: HashMap::new(),
+            stats: HashMap::new(),
             ttl: Duration::from_secs(config.ttl_secs),
+            ttls: HashMap::new(),
             max_size: config.max_size,
         }
     }
 
+    /// Create a schema cache with the default settings of `config` and the
+    /// TTL of each data source overriding them.
+    pub fn for_config(config: &SemaflowConfig) -> Self {
+        let mut cache = Self::with_config(&config.defaults.schema_cache);
+        for (name, datasource) in &config.datasources {
+            if let Some(schema_cache) = &datasource.schema_cache {
+                cache.set_ttl(name, Duration::from_secs(schema_cache.ttl_secs));
+            }
+        }
+        cache
+    }
+
+    /// Keep schemas and statistics of `data_source` for `ttl`.
+    pub fn set_ttl(&mut self, data_source: &str, ttl: Duration) {
+        self.ttls.insert(data_source.to_string(), ttl);
+    }
+
+    fn ttl_for(&self, data_source: &str) -> Duration {
+        self.ttls.get(data_source).copied().unwrap_or(self.ttl)
+    }
+
+    fn is_fresh<T>(&self, key: &(String, String), entry: &CacheEntry<T>) -> bool {
+        age(entry.inserted_at) < self.ttl_for(&key.0)
+    }
+
     pub fn insert(&mut self, data_source: String, table: String, schema: TableSchema) {
         // Evict oldest entry if at capacity
         if self.schemas.len() >= self.max_size {
             self.evict_oldest();
         }
 
-        self.schemas.insert(
-            (data_source, table),
-            CacheEntry {
-                schema,
-                inserted_at: Instant::now(),
-            },
-        );
+        self.schemas
+            .insert((data_source, table), CacheEntry::new(schema));
     }
 
     pub fn get(&self, data_source: &str, table: &str) -> Option<&TableSchema> {
         let key = (data_source.to_string(), table.to_string());
         self.schemas.get(&key).and_then(|entry| {
-            if entry.inserted_at.elapsed() < self.ttl {
-                Some(&entry.schema)
+            if self.is_fresh(&key, entry) {
+                Some(&entry.value)
             } else {
                 // Expired - treat as cache miss
                 None
@@ -86,14 +199,89 @@ impl SchemaCache {
         })
     }
 
+    /// Drop the schema of `table`, so the next lookup fetches it again.
+    pub fn remove(&mut self, data_source: &str, table: &str) -> Option<TableSchema> {
+        let key = (data_source.to_string(), table.to_string());
+        self.schemas.remove(&key).map(|entry| entry.value)
+    }
+
+    /// The latest schema seen for `table`, even if expired.
+    pub fn last_known(&self, data_source: &str, table: &str) -> Option<&TableSchema> {
+        let key = (data_source.to_string(), table.to_string());
+        self.schemas.get(&key).map(|entry| &entry.value)
+    }
+
+    /// The cached schemas, expired ones included, for writing to a
+    /// [`SchemaStore`].
+    pub fn snapshot(&self) -> Vec<StoredSchema> {
+        let mut stored: Vec<StoredSchema> = self
+            .schemas
+            .iter()
+            .map(|((data_source, table), entry)| StoredSchema {
+                data_source: data_source.clone(),
+                table: table.clone(),
+                fetched_at: entry
+                    .inserted_at
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs()),
+                schema: entry.value.clone(),
+            })
+            .collect();
+        stored.sort_by(|a, b| (&a.data_source, &a.table).cmp(&(&b.data_source, &b.table)));
+        stored
+    }
+
+    /// Add schemas read from a [`SchemaStore`], keeping their age. Expired
+    /// ones are lookup misses but remain the [`Self::last_known`] schema.
+    /// Returns the number added.
+    pub fn restore(&mut self, stored: Vec<StoredSchema>) -> usize {
+        let mut restored = 0;
+        for entry in stored {
+            let inserted_at = UNIX_EPOCH + Duration::from_secs(entry.fetched_at);
+            if self.schemas.len() >= self.max_size {
+                self.evict_oldest();
+            }
+            self.schemas.insert(
+                (entry.data_source, entry.table),
+                CacheEntry {
+                    value: entry.schema,
+                    inserted_at,
+                },
+            );
+            restored += 1;
+        }
+        restored
+    }
+
+    /// Store the statistics of a table; they expire with the same TTL as
+    /// schemas but are not counted against the size limit.
+    pub fn insert_stats(&mut self, data_source: String, table: String, stats: TableStats) {
+        self.stats
+            .insert((data_source, table), CacheEntry::new(stats));
+    }
+
+    pub fn get_stats(&self, data_source: &str, table: &str) -> Option<&TableStats> {
+        let key = (data_source.to_string(), table.to_string());
+        self.stats
+            .get(&key)
+            .filter(|entry| self.is_fresh(&key, entry))
+            .map(|entry| &entry.value)
+    }
+
     pub fn contains(&self, data_source: &str, table: &str) -> bool {
         self.get(data_source, table).is_some()
     }
 
     /// Remove expired entries from the cache.
     pub fn evict_expired(&mut self) {
+        let (ttl, ttls) = (self.ttl, &self.ttls);
+        let fresh = |data_source: &String, inserted_at: SystemTime| {
+            age(inserted_at) < ttls.get(data_source).copied().unwrap_or(ttl)
+        };
         self.schemas
-            .retain(|_, entry| entry.inserted_at.elapsed() < self.ttl);
+            .retain(|(data_source, _), entry| fresh(data_source, entry.inserted_at));
+        self.stats
+            .retain(|(data_source, _), entry| fresh(data_source, entry.inserted_at));
     }
 
     /// Remove the oldest entry from the cache.
@@ -123,8 +311,14 @@ impl SchemaCache {
         self.schemas.is_empty()
     }
 
-    /// Clear all cached schemas.
+    /// Clear all cached schemas and statistics.
     pub fn clear(&mut self) {
         self.schemas.clear();
+        self.stats.clear();
     }
 }
+
+/// Time since `at`; zero if the clock moved back past it.
+fn age(at: SystemTime) -> Duration {
+    at.elapsed().unwrap_or_default()
+}
@@ -0,0 +1,247 @@
+//! Ranked search over flows and their fields.
+//!
+//! [`FlowRegistry::search`] matches a term against the names, labels,
+//! synonyms, tags and descriptions of flows, dimensions and measures, so
+//! search UIs and LLM prompts can map a user's words ("revenue by region")
+//! onto fields without each client re-implementing fuzzy matching. Names are
+//! compared ignoring case and `_`/`-`/`.` separators, and tolerate a typo in
+//! longer terms. Deprecated flows and fields, and hidden fields, are left out.
+
+use serde::Serialize;
+
+use crate::registry::FlowRegistry;
+
+/// What a search hit refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchKind {
+    Flow,
+    Dimension,
+    Measure,
+}
+
+/// Which metadata of a hit matched the term.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedOn {
+    Name,
+    Synonym,
+    Tag,
+    Description,
+}
+
+impl MatchedOn {
+    /// Weight of a match on this metadata, in tenths.
+    fn weight(self) -> u32 {
+        match self {
+            MatchedOn::Name => 10,
+            MatchedOn::Synonym => 9,
+            MatchedOn::Tag => 6,
+            MatchedOn::Description => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    pub flow: String,
+    /// The flow name, or the field's qualified name (`alias.field`) in `flow`.
+    pub name: String,
+    pub description: Option<String>,
+    pub matched_on: MatchedOn,
+    /// Relevance from 1 to 100; hits are sorted by it, best first.
+    pub score: u32,
+}
+
+impl FlowRegistry {
+    /// Flows and fields matching `term`, best match first. A field shared by
+    /// several flows is listed once per flow, since requests name a flow.
+    pub fn search(&self, term: &str) -> Vec<SearchHit> {
+        let term = normalize(term);
+        if term.is_empty() {
+            return Vec::new();
+        }
+        let mut hits = Vec::new();
+        for flow in self.flows.values().filter(|f| f.deprecated.is_none()) {
+            let flow_hit = best_match(
+                &term,
+                [flow.name.as_str()],
+                &flow.synonyms,
+                &flow.tags,
+                flow.description.as_deref(),
+            );
+            if let Some((matched_on, score)) = flow_hit {
+                hits.push(SearchHit {
+                    kind: SearchKind::Flow,
+                    flow: flow.name.clone(),
+                    name: flow.name.clone(),
+                    description: flow.description.clone(),
+                    matched_on,
+                    score,
+                });
+            }
+            // Flows with missing tables are reported by validation, not here
+            let Ok(schema) = self.flow_schema(&flow.name) else {
+                continue;
+            };
+            let dimensions = schema.dimensions.into_iter().map(|d| {
+                let hit = best_match(
+                    &term,
+                    [d.name.as_str(), d.qualified_name.as_str()]
+                        .into_iter()
+                        .chain(d.label.as_deref()),
+                    &d.synonyms,
+                    &d.tags,
+                    d.description.as_deref(),
+                );
+                (
+                    SearchKind::Dimension,
+                    d.qualified_name,
+                    d.description,
+                    d.deprecated.is_some() || d.hidden,
+                    hit,
+                )
+            });
+            let measures = schema.measures.into_iter().map(|m| {
+                let hit = best_match(
+                    &term,
+                    [m.name.as_str(), m.qualified_name.as_str()]
+                        .into_iter()
+                        .chain(m.label.as_deref()),
+                    &m.synonyms,
+                    &m.tags,
+                    m.description.as_deref(),
+                );
+                (
+                    SearchKind::Measure,
+                    m.qualified_name,
+                    m.description,
+                    m.deprecated.is_some() || m.hidden,
+                    hit,
+                )
+            });
+            for (kind, name, description, unlisted, hit) in dimensions.chain(measures) {
+                let Some((matched_on, score)) = hit.filter(|_| !unlisted) else {
+                    continue;
+                };
+                hits.push(SearchHit {
+                    kind,
+                    flow: flow.name.clone(),
+                    name,
+                    description,
+                    matched_on,
+                    score,
+                });
+            }
+        }
+        hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.kind.cmp(&b.kind))
+                .then_with(|| a.flow.cmp(&b.flow))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        hits
+    }
+}
+
+/// The best weighted match of `term` against an object's metadata.
+fn best_match<'a>(
+    term: &str,
+    names: impl IntoIterator<Item = &'a str>,
+    synonyms: &[String],
+    tags: &[String],
+    description: Option<&str>,
+) -> Option<(MatchedOn, u32)> {
+    let names = names.into_iter().map(|n| (MatchedOn::Name, n));
+    let synonyms = synonyms.iter().map(|s| (MatchedOn::Synonym, s.as_str()));
+    let tags = tags.iter().map(|t| (MatchedOn::Tag, t.as_str()));
+    let mut best: Option<(MatchedOn, u32)> = names
+        .chain(synonyms)
+        .chain(tags)
+        .filter_map(|(on, candidate)| {
+            name_score(term, &normalize(candidate)).map(|s| (on, s * on.weight() / 10))
+        })
+        .max_by_key(|(_, score)| *score);
+    if let Some(description) = description {
+        let description = normalize(description);
+        if contains_words(&description, term) {
+            let score = 50 * MatchedOn::Description.weight() / 10;
+            if best.is_none_or(|(_, s)| s < score) {
+                best = Some((MatchedOn::Description, score));
+            }
+        }
+    }
+    best
+}
+
+/// How well a normalized name matches a normalized term.
+fn name_score(term: &str, candidate: &str) -> Option<u32> {
+    if candidate == term {
+        Some(100)
+    } else if candidate.starts_with(term) {
+        Some(80)
+    } else if contains_words(candidate, term) {
+        Some(60)
+    } else if candidate.contains(term) {
+        Some(50)
+    } else if term.len() >= 4 && edit_distance(term, candidate) <= typo_budget(term) {
+        Some(40)
+    } else {
+        None
+    }
+}
+
+/// Whether every word of `term` is a word of `text`.
+fn contains_words(text: &str, term: &str) -> bool {
+    term.split(' ')
+        .all(|word| text.split(' ').any(|candidate| candidate == word))
+}
+
+fn typo_budget(term: &str) -> usize {
+    if term.len() >= 8 {
+        2
+    } else {
+        1
+    }
+}
+
+/// Lowercase with `_`, `-`, `.` and runs of whitespace turned into single spaces.
+fn normalize(s: &str) -> String {
+    s.to_lowercase()
+        .split(|c: char| c.is_whitespace() || matches!(c, '_' | '-' | '.'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_names_by_closeness() {
+        assert_eq!(normalize("Order_Total"), "order total");
+        assert_eq!(name_score("order total", "order total"), Some(100));
+        assert_eq!(name_score("order", "order total"), Some(80));
+        assert_eq!(name_score("total", "order total"), Some(60));
+        assert_eq!(name_score("ordr total", "order total"), Some(40));
+        assert_eq!(name_score("country", "order total"), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -1,11 +1,14 @@
+use std::collections::BTreeMap;
+
 use serde_json::Value;
 
 use crate::dialect::Dialect;
-use crate::flows::{Aggregation, Function, SortDirection};
+use crate::flows::{Aggregation, FiscalCalendar, Function, SortDirection, TimeGrain};
+use crate::sql_format::{format_sql, RenderOptions};
 
 /// Sanitize an alias for SQL output by replacing dots with double underscores.
 /// This transforms "c.country" to "c__country" for SQL-safe column aliases.
-fn sanitize_alias(alias: &str) -> String {
+pub(crate) fn sanitize_alias(alias: &str) -> String {
     alias.replace('.', "__")
 }
 
@@ -46,6 +49,74 @@ pub enum SqlExpr {
     Exists {
         subquery: Box<SelectQuery>,
     },
+    /// Whether some element of the array `expr`, named `alias` in
+    /// `condition`, meets `condition`.
+    AnyElement {
+        expr: Box<SqlExpr>,
+        alias: String,
+        condition: Box<SqlExpr>,
+    },
+    /// Tri-state boolean test: `Some(true)` / `Some(false)` for TRUE / FALSE,
+    /// `None` for UNKNOWN (NULL).
+    BooleanTest {
+        expr: Box<SqlExpr>,
+        value: Option<bool>,
+    },
+    IsNull {
+        expr: Box<SqlExpr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<SqlExpr>,
+        low: Box<SqlExpr>,
+        high: Box<SqlExpr>,
+        negated: bool,
+    },
+    Not {
+        expr: Box<SqlExpr>,
+    },
+    /// The current date, optionally truncated to the start of a period, then
+    /// shifted by each `(unit, amount)` offset in order.
+    RelativeDate {
+        truncate: Option<TimeGrain>,
+        offsets: Vec<(TimeGrain, i64)>,
+        /// Compare against a TIMESTAMP column rather than a DATE.
+        as_timestamp: bool,
+    },
+    /// `GROUPING(...)` bitmask: bit set for each argument rolled up in the
+    /// row's grouping set, first argument most significant.
+    Grouping {
+        exprs: Vec<SqlExpr>,
+    },
+    /// A flow parameter, rendered as a literal of the value bound on the renderer.
+    Param {
+        name: String,
+    },
+    /// A UTC timestamp converted to wall-clock time in an IANA time zone.
+    AtTimeZone {
+        expr: Box<SqlExpr>,
+        zone: String,
+    },
+    /// Mergeable sketch of the distinct non-NULL values of `expr`, built in
+    /// a pre-aggregation CTE.
+    DistinctSketch {
+        expr: Box<SqlExpr>,
+    },
+    /// Distinct count of the union of the sketches in `expr`.
+    MergeSketches {
+        expr: Box<SqlExpr>,
+    },
+    /// `SUM(expr)` counting each distinct `key` once, however often joins
+    /// repeat its row.
+    SymmetricSum {
+        expr: Box<SqlExpr>,
+        key: Box<SqlExpr>,
+    },
+    /// Raw SQL, parenthesized, with `${TABLE}` replaced by the quoted `table`.
+    Raw {
+        sql: String,
+        table: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -78,6 +149,8 @@ pub struct TableRef {
     pub name: String,
     pub alias: Option<String>,
     pub subquery: Option<Box<SelectQuery>>,
+    /// Raw SQL read as a derived table; takes precedence over `name`.
+    pub sql: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -95,6 +168,14 @@ pub struct Join {
     pub on: Vec<SqlExpr>,
 }
 
+/// `CROSS JOIN UNNEST(expr)`: one row per element of the array `expr`,
+/// referenced as the unqualified column `alias`.
+#[derive(Debug, Clone)]
+pub struct Unnest {
+    pub expr: SqlExpr,
+    pub alias: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct OrderItem {
     pub expr: SqlExpr,
@@ -106,8 +187,12 @@ pub struct SelectQuery {
     pub select: Vec<SelectItem>,
     pub from: TableRef,
     pub joins: Vec<Join>,
+    /// Lateral unnests, after the joins.
+    pub unnests: Vec<Unnest>,
     pub filters: Vec<SqlExpr>,
     pub group_by: Vec<SqlExpr>,
+    /// Grouping sets as indices into `group_by`. Empty for a plain GROUP BY.
+    pub grouping_sets: Vec<Vec<usize>>,
     pub order_by: Vec<OrderItem>,
     pub limit: Option<u64>,
     pub offset: Option<u64>,
@@ -115,14 +200,61 @@ pub struct SelectQuery {
 
 pub struct SqlRenderer<'d> {
     dialect: &'d dyn Dialect,
+    group_by_all: bool,
+    params: BTreeMap<String, Value>,
+    fiscal_calendar: FiscalCalendar,
+    options: RenderOptions,
 }
 
 impl<'d> SqlRenderer<'d> {
     pub fn new(dialect: &'d dyn Dialect) -> Self {
-        Self { dialect }
+        Self {
+            dialect,
+            group_by_all: false,
+            params: BTreeMap::new(),
+            fiscal_calendar: FiscalCalendar::default(),
+            options: RenderOptions::default(),
+        }
     }
 
+    /// Bind the values `{{ param }}` references render as. Unbound params render as NULL.
+    pub fn with_params(mut self, params: BTreeMap<String, Value>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Set the calendar `fiscal_*` grains follow; calendar years by default.
+    pub fn with_fiscal_calendar(mut self, calendar: FiscalCalendar) -> Self {
+        self.fiscal_calendar = calendar;
+        self
+    }
+
+    /// Emit `GROUP BY ALL` instead of repeating the grouping expressions.
+    ///
+    /// Only takes effect when the dialect supports it and every grouping
+    /// expression is also selected, so the result matches the explicit list.
+    pub fn with_group_by_all(mut self, enabled: bool) -> Self {
+        self.group_by_all = enabled;
+        self
+    }
+
+    /// Lay out finished SQL with [`Self::format`]; one line by default.
+    pub fn with_options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Apply the render options to a complete statement. Queries are
+    /// rendered on one line and laid out once, after any wrapping.
+    pub fn format(&self, sql: &str) -> String {
+        format_sql(sql, &self.options, self.dialect.backslash_escapes())
+    }
+
+    /// Render a query on one line; see [`Self::format`].
     pub fn render_select(&self, query: &SelectQuery) -> String {
+        if !query.grouping_sets.is_empty() && !self.dialect.capabilities().grouping_sets {
+            return self.render_grouping_union(query);
+        }
         let select_items: Vec<String> = query
             .select
             .iter()
@@ -158,6 +290,12 @@ impl<'d> SqlRenderer<'d> {
                 on_clause.join(" AND ")
             ));
         }
+        for unnest in &query.unnests {
+            sql.push_str(&format!(
+                " CROSS JOIN {}",
+                self.render_unnest(&unnest.expr, &unnest.alias)
+            ));
+        }
 
         if !query.filters.is_empty() {
             let filters: Vec<String> = query.filters.iter().map(|f| self.render_expr(f)).collect();
@@ -166,7 +304,16 @@ impl<'d> SqlRenderer<'d> {
 
         if !query.group_by.is_empty() {
             let groups: Vec<String> = query.group_by.iter().map(|g| self.render_expr(g)).collect();
-            sql.push_str(&format!(" GROUP BY {}", groups.join(", ")));
+            if !query.grouping_sets.is_empty() {
+                sql.push_str(&format!(
+                    " GROUP BY {}",
+                    render_grouping_sets(&groups, &query.grouping_sets)
+                ));
+            } else if self.can_group_by_all(query, &groups) {
+                sql.push_str(" GROUP BY ALL");
+            } else {
+                sql.push_str(&format!(" GROUP BY {}", groups.join(", ")));
+            }
         }
 
         if !query.order_by.is_empty() {
@@ -185,16 +332,109 @@ impl<'d> SqlRenderer<'d> {
             sql.push_str(&format!(" ORDER BY {}", orders.join(", ")));
         }
 
-        if let Some(limit) = query.limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
-        }
-        if let Some(offset) = query.offset {
-            sql.push_str(&format!(" OFFSET {}", offset));
-        }
+        sql.push_str(
+            &self
+                .dialect
+                .capabilities()
+                .limit
+                .render(query.limit, query.offset),
+        );
+
+        sql
+    }
+
+    /// Emulate grouping sets with one aggregate query per set, combined with
+    /// `UNION ALL`. Rolled-up dimensions become NULL and `GROUPING()` becomes
+    /// the set's literal bitmask; ordering and limits apply to the union.
+    fn render_grouping_union(&self, query: &SelectQuery) -> String {
+        let groups: Vec<String> = query.group_by.iter().map(|g| self.render_expr(g)).collect();
+        let branches: Vec<String> = query
+            .grouping_sets
+            .iter()
+            .map(|set| {
+                let mut branch = query.clone();
+                branch.grouping_sets.clear();
+                branch.order_by.clear();
+                branch.limit = None;
+                branch.offset = None;
+                branch.group_by = set.iter().map(|&i| query.group_by[i].clone()).collect();
+                let rolled_up: Vec<&String> = groups
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !set.contains(i))
+                    .map(|(_, g)| g)
+                    .collect();
+                let bits = (0..groups.len())
+                    .filter(|i| !set.contains(i))
+                    .fold(0u64, |acc, i| acc | 1 << (groups.len() - 1 - i));
+                for item in &mut branch.select {
+                    if matches!(item.expr, SqlExpr::Grouping { .. }) {
+                        item.expr = SqlExpr::Literal(Value::from(bits));
+                    } else if rolled_up.contains(&&self.render_expr(&item.expr)) {
+                        item.expr = SqlExpr::Literal(Value::Null);
+                    }
+                }
+                self.render_select(&branch)
+            })
+            .collect();
 
+        let mut sql = format!(
+            "SELECT * FROM ({}) {}",
+            branches.join(" UNION ALL "),
+            self.dialect.quote_ident("__grouping_sets")
+        );
+        if !query.order_by.is_empty() {
+            // The union only exposes output aliases
+            let orders: Vec<String> = query
+                .order_by
+                .iter()
+                .map(|o| {
+                    let rendered = self.render_expr(&o.expr);
+                    let expr = query
+                        .select
+                        .iter()
+                        .find(|item| self.render_expr(&item.expr) == rendered)
+                        .and_then(|item| item.alias.as_deref())
+                        .map(|alias| self.dialect.quote_ident(&sanitize_alias(alias)))
+                        .unwrap_or(rendered);
+                    let dir = match o.direction {
+                        SortDirection::Asc => "ASC",
+                        SortDirection::Desc => "DESC",
+                    };
+                    format!("{expr} {dir}")
+                })
+                .collect();
+            sql.push_str(&format!(" ORDER BY {}", orders.join(", ")));
+        }
+        sql.push_str(
+            &self
+                .dialect
+                .capabilities()
+                .limit
+                .render(query.limit, query.offset),
+        );
         sql
     }
 
+    fn can_group_by_all(&self, query: &SelectQuery, groups: &[String]) -> bool {
+        if !self.group_by_all || !self.dialect.capabilities().group_by_all {
+            return false;
+        }
+        // GROUP BY ALL groups by every non-aggregate item, so the items must
+        // be exactly the grouping keys plus aggregates and constants
+        let selected: Vec<String> = query
+            .select
+            .iter()
+            .map(|item| self.render_expr(&item.expr))
+            .collect();
+        groups.iter().all(|g| selected.contains(g))
+            && query.select.iter().zip(&selected).all(|(item, sql)| {
+                groups.contains(sql)
+                    || contains_aggregate(&item.expr)
+                    || matches!(item.expr, SqlExpr::Literal(_) | SqlExpr::Param { .. })
+            })
+    }
+
     fn render_table_ref(&self, table: &TableRef) -> String {
         if let Some(subquery) = &table.subquery {
             let rendered = self.render_select(subquery);
@@ -204,6 +444,13 @@ impl<'d> SqlRenderer<'d> {
                 .expect("subquery table refs must include alias");
             return format!("({rendered}) {}", self.dialect.quote_ident(alias));
         }
+        if let Some(sql) = &table.sql {
+            let alias = table
+                .alias
+                .as_ref()
+                .expect("derived table refs must include alias");
+            return format!("({}) {}", sql.trim(), self.dialect.quote_ident(alias));
+        }
         match &table.alias {
             Some(alias) => format!(
                 "{} {}",
@@ -214,6 +461,11 @@ impl<'d> SqlRenderer<'d> {
         }
     }
 
+    fn render_unnest(&self, expr: &SqlExpr, alias: &str) -> String {
+        self.dialect
+            .render_unnest(&self.render_expr(expr), &self.dialect.quote_ident(alias))
+    }
+
     fn render_expr(&self, expr: &SqlExpr) -> String {
         match expr {
             SqlExpr::Column { table, name } => match table {
@@ -236,7 +488,31 @@ impl<'d> SqlRenderer<'d> {
             SqlExpr::Literal(v) => self.dialect.render_literal(v),
             SqlExpr::Function { func, args } => {
                 let rendered_args: Vec<String> = args.iter().map(|a| self.render_expr(a)).collect();
-                self.dialect.render_function(func, rendered_args)
+                match func {
+                    Function::DateTrunc(grain) if grain.is_fiscal() => match &rendered_args[..] {
+                        [expr] => {
+                            self.dialect
+                                .render_fiscal_trunc(grain, &self.fiscal_calendar, expr)
+                        }
+                        _ => "NULL".to_string(),
+                    },
+                    // Fiscal periods add and count like their calendar counterparts
+                    Function::DateAdd { unit } if unit.is_fiscal() => self.dialect.render_function(
+                        &Function::DateAdd {
+                            unit: unit.calendar(),
+                        },
+                        rendered_args,
+                    ),
+                    Function::DateDiff { unit } if unit.is_fiscal() => {
+                        self.dialect.render_function(
+                            &Function::DateDiff {
+                                unit: unit.calendar(),
+                            },
+                            rendered_args,
+                        )
+                    }
+                    _ => self.dialect.render_function(func, rendered_args),
+                }
             }
             SqlExpr::Case {
                 branches,
@@ -283,7 +559,7 @@ impl<'d> SqlRenderer<'d> {
                 .dialect
                 .render_aggregation(agg, &self.render_expr(expr)),
             SqlExpr::FilteredAggregate { agg, expr, filter } => {
-                if self.dialect.supports_filtered_aggregates() {
+                if self.dialect.capabilities().filtered_aggregates {
                     let agg_sql = self
                         .dialect
                         .render_aggregation(agg, &self.render_expr(expr));
@@ -317,6 +593,138 @@ impl<'d> SqlRenderer<'d> {
             SqlExpr::Exists { subquery } => {
                 format!("EXISTS ({})", self.render_select(subquery))
             }
+            SqlExpr::AnyElement {
+                expr,
+                alias,
+                condition,
+            } => format!(
+                "EXISTS (SELECT 1 FROM {} WHERE {})",
+                self.render_unnest(expr, alias),
+                self.render_expr(condition)
+            ),
+            SqlExpr::BooleanTest { expr, value } => self
+                .dialect
+                .render_boolean_test(&self.render_expr(expr), *value),
+            SqlExpr::IsNull { expr, negated } => {
+                let not_kw = if *negated { "NOT " } else { "" };
+                format!("({} IS {}NULL)", self.render_expr(expr), not_kw)
+            }
+            SqlExpr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let not_kw = if *negated { "NOT " } else { "" };
+                format!(
+                    "({} {}BETWEEN {} AND {})",
+                    self.render_expr(expr),
+                    not_kw,
+                    self.render_expr(low),
+                    self.render_expr(high)
+                )
+            }
+            SqlExpr::Not { expr } => format!("(NOT {})", self.render_expr(expr)),
+            SqlExpr::RelativeDate {
+                truncate,
+                offsets,
+                as_timestamp,
+            } => self
+                .dialect
+                .render_relative_date(truncate.as_ref(), offsets, *as_timestamp),
+            SqlExpr::Grouping { exprs } => {
+                let rendered: Vec<String> = exprs.iter().map(|e| self.render_expr(e)).collect();
+                self.dialect.render_grouping(&rendered)
+            }
+            SqlExpr::Param { name } => self
+                .dialect
+                .render_literal(self.params.get(name).unwrap_or(&Value::Null)),
+            SqlExpr::AtTimeZone { expr, zone } => self
+                .dialect
+                .render_at_time_zone(&self.render_expr(expr), zone),
+            // The planner builds sketches only for dialects with the capability
+            SqlExpr::DistinctSketch { expr } => self
+                .dialect
+                .render_distinct_sketch(&self.render_expr(expr))
+                .expect("dialect with distinct_sketches renders sketches"),
+            SqlExpr::MergeSketches { expr } => self
+                .dialect
+                .render_merge_sketches(&self.render_expr(expr))
+                .expect("dialect with distinct_sketches merges sketches"),
+            SqlExpr::SymmetricSum { expr, key } => self
+                .dialect
+                .render_symmetric_sum(&self.render_expr(expr), &self.render_expr(key))
+                .expect("dialect with symmetric_aggregates renders symmetric sums"),
+            SqlExpr::Raw { sql, table } => match table {
+                Some(t) => format!(
+                    "({})",
+                    sql.replace("${TABLE}", &self.dialect.quote_ident(t))
+                ),
+                None => format!("({sql})"),
+            },
+        }
+    }
+}
+
+/// `ROLLUP(...)` when the sets are every prefix of the grouping list from
+/// longest to empty, otherwise an explicit `GROUPING SETS (...)`.
+fn render_grouping_sets(groups: &[String], sets: &[Vec<usize>]) -> String {
+    let is_rollup = sets.len() == groups.len() + 1
+        && sets
+            .iter()
+            .enumerate()
+            .all(|(i, set)| set.iter().copied().eq(0..groups.len() - i));
+    if is_rollup {
+        return format!("ROLLUP({})", groups.join(", "));
+    }
+    let rendered: Vec<String> = sets
+        .iter()
+        .map(|set| {
+            let cols: Vec<&str> = set.iter().map(|&i| groups[i].as_str()).collect();
+            format!("({})", cols.join(", "))
+        })
+        .collect();
+    format!("GROUPING SETS ({})", rendered.join(", "))
+}
+
+/// Whether `expr` aggregates, so it needs no grouping key of its own.
+fn contains_aggregate(expr: &SqlExpr) -> bool {
+    match expr {
+        SqlExpr::Aggregate { .. }
+        | SqlExpr::FilteredAggregate { .. }
+        | SqlExpr::Grouping { .. }
+        | SqlExpr::DistinctSketch { .. }
+        | SqlExpr::MergeSketches { .. }
+        | SqlExpr::SymmetricSum { .. } => true,
+        SqlExpr::Function { args, .. } => args.iter().any(contains_aggregate),
+        SqlExpr::Case {
+            branches,
+            else_expr,
+        } => {
+            branches
+                .iter()
+                .any(|(when, then)| contains_aggregate(when) || contains_aggregate(then))
+                || contains_aggregate(else_expr)
+        }
+        SqlExpr::BinaryOp { left, right, .. } => {
+            contains_aggregate(left) || contains_aggregate(right)
+        }
+        SqlExpr::InList { expr, list, .. } => {
+            contains_aggregate(expr) || list.iter().any(contains_aggregate)
         }
+        SqlExpr::Between {
+            expr, low, high, ..
+        } => contains_aggregate(expr) || contains_aggregate(low) || contains_aggregate(high),
+        SqlExpr::BooleanTest { expr, .. }
+        | SqlExpr::IsNull { expr, .. }
+        | SqlExpr::Not { expr }
+        | SqlExpr::AtTimeZone { expr, .. } => contains_aggregate(expr),
+        SqlExpr::Column { .. }
+        | SqlExpr::Literal(_)
+        | SqlExpr::Exists { .. }
+        | SqlExpr::AnyElement { .. }
+        | SqlExpr::RelativeDate { .. }
+        | SqlExpr::Param { .. }
+        | SqlExpr::Raw { .. } => false,
     }
 }
@@ -0,0 +1,568 @@
+//! Layout of rendered SQL for debug output and audit logs.
+//!
+//! [`SqlRenderer`](crate::sql_ast::SqlRenderer) emits each query on one line.
+//! With [`RenderOptions::pretty`] set, the final SQL is reflowed: every clause
+//! starts a line, subqueries and CTEs are indented, and list or condition
+//! clauses longer than `max_line_len` put one item per line. Only whitespace
+//! between tokens and the case of keywords change; literals, quoted
+//! identifiers and comments are kept as they are.
+
+/// How rendered SQL is laid out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// One clause per line with indented subqueries; one line when false.
+    pub pretty: bool,
+    /// Spaces per nesting level in pretty output.
+    pub indent: usize,
+    /// Upper case keywords; lower case them when false.
+    pub uppercase_keywords: bool,
+    /// Pretty clauses longer than this put each item on its own line.
+    pub max_line_len: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            pretty: false,
+            indent: 2,
+            uppercase_keywords: true,
+            max_line_len: 80,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Pretty output with the default indent and line length.
+    pub fn pretty() -> Self {
+        Self {
+            pretty: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// Words whose case follows [`RenderOptions::uppercase_keywords`]. Function
+/// names are left as the dialect renders them.
+const KEYWORDS: &[&str] = &[
+    "ALL",
+    "AND",
+    "AS",
+    "ASC",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CROSS",
+    "DESC",
+    "DISTINCT",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "EXISTS",
+    "FALSE",
+    "FILTER",
+    "FIRST",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "ILIKE",
+    "IN",
+    "INNER",
+    "INTERSECT",
+    "INTERVAL",
+    "IS",
+    "JOIN",
+    "LAST",
+    "LATERAL",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NOT",
+    "NULL",
+    "NULLS",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "QUALIFY",
+    "RECURSIVE",
+    "RIGHT",
+    "SELECT",
+    "SETS",
+    "THEN",
+    "TRUE",
+    "UNION",
+    "WHEN",
+    "WHERE",
+    "WITH",
+];
+
+#[derive(Debug)]
+enum Token {
+    Space(String),
+    Word(String),
+    Other(String),
+    Open,
+    Close,
+}
+
+#[derive(Debug)]
+enum Node {
+    Space,
+    Word(String),
+    Text(String),
+    Group(Vec<Node>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Separator {
+    Comma,
+    And,
+    None,
+}
+
+/// Lay out `sql` according to `options`. SQL that cannot be tokenized, e.g.
+/// with an unterminated literal or unbalanced parentheses, is returned
+/// unchanged.
+pub(crate) fn format_sql(sql: &str, options: &RenderOptions, backslash_escapes: bool) -> String {
+    if !options.pretty && options.uppercase_keywords {
+        return sql.to_string();
+    }
+    let Some(tokens) = tokenize(sql, backslash_escapes) else {
+        return sql.to_string();
+    };
+    let formatter = Formatter { options };
+    if !options.pretty {
+        return tokens
+            .iter()
+            .map(|token| match token {
+                Token::Space(s) | Token::Other(s) => s.clone(),
+                Token::Word(w) => formatter.word(w),
+                Token::Open => "(".to_string(),
+                Token::Close => ")".to_string(),
+            })
+            .collect();
+    }
+    let Some(nodes) = parse(tokens) else {
+        return sql.to_string();
+    };
+    if is_query(&nodes) {
+        formatter.query(&nodes, 0)
+    } else {
+        formatter.inline(&nodes, 0).trim().to_string()
+    }
+}
+
+fn tokenize(sql: &str, backslash_escapes: bool) -> Option<Vec<Token>> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            tokens.push(Token::Space(chars[start..i].iter().collect()));
+        } else if matches!(c, '\'' | '"' | '`') {
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return None,
+                    Some('\\') if backslash_escapes => i += 2,
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => i += 2,
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(_) => i += 1,
+                }
+            }
+            tokens.push(Token::Other(
+                chars[start..i.min(chars.len())].iter().collect(),
+            ));
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            // Keep the newline so nothing after the comment joins it
+            i = (i + 1).min(chars.len());
+            tokens.push(Token::Other(chars[start..i].iter().collect()));
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while !(chars.get(i) == Some(&'*') && chars.get(i + 1) == Some(&'/')) {
+                if i >= chars.len() {
+                    return None;
+                }
+                i += 1;
+            }
+            i += 2;
+            tokens.push(Token::Other(chars[start..i].iter().collect()));
+        } else if c == '(' {
+            i += 1;
+            tokens.push(Token::Open);
+        } else if c == ')' {
+            i += 1;
+            tokens.push(Token::Close);
+        } else if c.is_alphanumeric() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '$')) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            // Qualified names like `o.last` are identifiers, not keywords
+            let qualified = chars.get(i) == Some(&'.')
+                || matches!(tokens.last(), Some(Token::Other(prev)) if prev == ".");
+            tokens.push(if qualified {
+                Token::Other(word)
+            } else {
+                Token::Word(word)
+            });
+        } else {
+            i += 1;
+            tokens.push(Token::Other(c.to_string()));
+        }
+    }
+    Some(tokens)
+}
+
+/// Nest tokens by parentheses; `None` when they are unbalanced.
+fn parse(tokens: Vec<Token>) -> Option<Vec<Node>> {
+    let mut stack: Vec<Vec<Node>> = vec![Vec::new()];
+    for token in tokens {
+        let node = match token {
+            Token::Space(_) => Node::Space,
+            Token::Word(w) => Node::Word(w),
+            Token::Other(s) => Node::Text(s),
+            Token::Open => {
+                stack.push(Vec::new());
+                continue;
+            }
+            Token::Close => {
+                if stack.len() < 2 {
+                    return None;
+                }
+                Node::Group(stack.pop()?)
+            }
+        };
+        stack.last_mut()?.push(node);
+    }
+    if stack.len() == 1 {
+        stack.pop()
+    } else {
+        None
+    }
+}
+
+fn upper(node: &Node) -> Option<String> {
+    match node {
+        Node::Word(w) => Some(w.to_ascii_uppercase()),
+        _ => None,
+    }
+}
+
+fn is_comment(node: &Node) -> bool {
+    matches!(node, Node::Text(t) if t.starts_with("--") || t.starts_with("/*"))
+}
+
+/// Whether a parenthesized group holds a query rather than an expression.
+fn is_query(nodes: &[Node]) -> bool {
+    nodes
+        .iter()
+        .find(|node| !matches!(node, Node::Space) && !is_comment(node))
+        .and_then(upper)
+        .is_some_and(|w| w == "SELECT" || w == "WITH")
+}
+
+/// Index of the next node after `i` that is not whitespace.
+fn next_significant(nodes: &[Node], i: usize) -> Option<usize> {
+    (i + 1..nodes.len()).find(|&j| !matches!(nodes[j], Node::Space))
+}
+
+struct Formatter<'a> {
+    options: &'a RenderOptions,
+}
+
+impl Formatter<'_> {
+    fn word(&self, word: &str) -> String {
+        let upper = word.to_ascii_uppercase();
+        if !KEYWORDS.contains(&upper.as_str()) {
+            word.to_string()
+        } else if self.options.uppercase_keywords {
+            upper
+        } else {
+            word.to_ascii_lowercase()
+        }
+    }
+
+    fn pad(&self, level: usize) -> String {
+        " ".repeat(level * self.options.indent)
+    }
+
+    /// Render nodes on one line, except for nested queries, which are laid
+    /// out one level deeper with the closing parenthesis back at `level`.
+    fn inline(&self, nodes: &[Node], level: usize) -> String {
+        let mut out = String::new();
+        for node in nodes {
+            match node {
+                Node::Space => {
+                    if !out.ends_with([' ', '\n']) {
+                        out.push(' ');
+                    }
+                }
+                Node::Word(w) => out.push_str(&self.word(w)),
+                Node::Text(t) => out.push_str(t),
+                Node::Group(children) if is_query(children) => {
+                    out.push_str("(\n");
+                    out.push_str(&self.query(children, level + 1));
+                    out.push('\n');
+                    out.push_str(&self.pad(level));
+                    out.push(')');
+                }
+                Node::Group(children) => {
+                    out.push('(');
+                    out.push_str(self.inline(children, level).trim());
+                    out.push(')');
+                }
+            }
+        }
+        out
+    }
+
+    /// The index after the keywords starting a clause at `i`, if one does.
+    /// `previous` is the word before `i`, `None` at the start of the query.
+    fn clause_head(&self, nodes: &[Node], i: usize, previous: Option<&str>) -> Option<usize> {
+        let word = upper(&nodes[i])?;
+        let next = next_significant(nodes, i);
+        let next_word = next.and_then(|j| upper(&nodes[j]));
+        match (word.as_str(), next, next_word.as_deref()) {
+            ("SELECT", Some(j), Some("DISTINCT")) => Some(j + 1),
+            ("SELECT", _, _) => Some(i + 1),
+            // `IS [NOT] DISTINCT FROM` is a comparison
+            ("FROM", _, _) if previous != Some("DISTINCT") => Some(i + 1),
+            // `WITH OFFSET` belongs to an unnest
+            ("OFFSET", _, _) if previous != Some("WITH") => Some(i + 1),
+            ("WITH", _, _) if previous.is_none() => Some(i + 1),
+            ("WHERE" | "HAVING" | "QUALIFY" | "LIMIT" | "ON" | "JOIN", _, _) => Some(i + 1),
+            ("GROUP" | "ORDER", Some(j), Some("BY")) => Some(j + 1),
+            ("UNION" | "INTERSECT" | "EXCEPT", Some(j), Some("ALL" | "DISTINCT")) => Some(j + 1),
+            ("UNION", _, _) | ("INTERSECT" | "EXCEPT", _, Some("SELECT")) => Some(i + 1),
+            ("INNER" | "LEFT" | "RIGHT" | "FULL" | "CROSS", Some(mut j), _) => {
+                if upper(&nodes[j]).as_deref() == Some("OUTER") {
+                    j = next_significant(nodes, j)?;
+                }
+                (upper(&nodes[j]).as_deref() == Some("JOIN")).then_some(j + 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Lay out a query one clause per line at `level`.
+    fn query(&self, nodes: &[Node], level: usize) -> String {
+        // (head start, head end) of each clause, in order
+        let mut heads: Vec<(usize, usize)> = Vec::new();
+        let mut previous: Option<String> = None;
+        let mut i = 0;
+        while i < nodes.len() {
+            if matches!(nodes[i], Node::Space) || is_comment(&nodes[i]) {
+                i += 1;
+                continue;
+            }
+            let end = match self.clause_head(nodes, i, previous.as_deref()) {
+                Some(end) => {
+                    heads.push((i, end));
+                    end
+                }
+                None => i + 1,
+            };
+            // Any node, word or not, ends the start of the query
+            previous = Some(upper(&nodes[end - 1]).unwrap_or_default());
+            i = end;
+        }
+
+        let mut lines = Vec::new();
+        let leading = heads.first().map_or(nodes.len(), |&(start, _)| start);
+        let prefix = self.inline(&nodes[..leading], level);
+        if !prefix.trim().is_empty() {
+            lines.push(format!("{}{}", self.pad(level), prefix.trim()));
+        }
+        for (n, &(start, end)) in heads.iter().enumerate() {
+            let body_end = heads.get(n + 1).map_or(nodes.len(), |&(next, _)| next);
+            let head = self.inline(&nodes[start..end], level).trim().to_string();
+            lines.push(self.clause(&head, &nodes[end..body_end], level));
+        }
+        lines.join("\n")
+    }
+
+    fn clause(&self, head: &str, body: &[Node], level: usize) -> String {
+        let keyword = head.to_ascii_uppercase();
+        let separator = match keyword.split(' ').next().unwrap_or_default() {
+            "SELECT" | "GROUP" | "ORDER" | "WITH" => Separator::Comma,
+            "WHERE" | "HAVING" | "QUALIFY" | "ON" => Separator::And,
+            _ => Separator::None,
+        };
+        // Join conditions sit under their join
+        let level = if keyword == "ON" { level + 1 } else { level };
+        let items = split_items(body, separator);
+        let render = |level: usize| -> Vec<String> {
+            items
+                .iter()
+                .map(|item| self.inline(item, level).trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        };
+
+        let pad = self.pad(level);
+        let flat = render(level);
+        if flat.is_empty() {
+            return format!("{pad}{head}");
+        }
+        let joiner = match separator {
+            Separator::And => " AND ",
+            _ => ", ",
+        };
+        let line = format!("{pad}{head} {}", flat.join(joiner));
+        if separator == Separator::None
+            || (!line.contains('\n') && line.chars().count() <= self.options.max_line_len)
+        {
+            return line;
+        }
+
+        let item_pad = self.pad(level + 1);
+        let and = self.word("AND");
+        let nested = render(level + 1);
+        let mut lines = vec![format!("{pad}{head}")];
+        for (n, item) in nested.iter().enumerate() {
+            lines.push(match separator {
+                Separator::Comma if n + 1 < nested.len() => format!("{item_pad}{item},"),
+                Separator::And if n > 0 => format!("{item_pad}{and} {item}"),
+                _ => format!("{item_pad}{item}"),
+            });
+        }
+        lines.join("\n")
+    }
+}
+
+/// Split a clause body at its top-level commas or `AND`s, leaving those in
+/// `BETWEEN ... AND` and `CASE` expressions alone.
+fn split_items(body: &[Node], separator: Separator) -> Vec<&[Node]> {
+    if separator == Separator::None {
+        return vec![body];
+    }
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut case_depth = 0usize;
+    let mut between = false;
+    for (i, node) in body.iter().enumerate() {
+        let word = upper(node);
+        let split = match (separator, node, word.as_deref()) {
+            (Separator::Comma, Node::Text(t), _) => t == "," && case_depth == 0,
+            (Separator::And, _, Some("AND")) if between => {
+                between = false;
+                false
+            }
+            (Separator::And, _, Some("AND")) => case_depth == 0,
+            (_, _, Some("BETWEEN")) => {
+                between = true;
+                false
+            }
+            (_, _, Some("CASE")) => {
+                case_depth += 1;
+                false
+            }
+            (_, _, Some("END")) => {
+                case_depth = case_depth.saturating_sub(1);
+                false
+            }
+            _ => false,
+        };
+        if split {
+            items.push(&body[start..i]);
+            start = i + 1;
+        }
+    }
+    items.push(&body[start..]);
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pretty(sql: &str) -> String {
+        format_sql(sql, &RenderOptions::pretty(), false)
+    }
+
+    #[test]
+    fn default_options_leave_sql_unchanged() {
+        let sql = "SELECT a FROM t WHERE b = 'x  y'";
+        assert_eq!(format_sql(sql, &RenderOptions::default(), false), sql);
+    }
+
+    #[test]
+    fn puts_clauses_on_lines_and_indents_subqueries() {
+        let sql = "SELECT \"o\".\"status\", SUM(\"o\".\"amount\") AS \"total\" FROM (SELECT * FROM \"orders\" WHERE \"x\" BETWEEN 1 AND 2) \"o\" LEFT JOIN \"c\" ON \"o\".\"id\" = \"c\".\"id\" GROUP BY 1 ORDER BY 2 DESC LIMIT 10";
+        assert_eq!(
+            pretty(sql),
+            [
+                "SELECT \"o\".\"status\", SUM(\"o\".\"amount\") AS \"total\"",
+                "FROM (",
+                "  SELECT *",
+                "  FROM \"orders\"",
+                "  WHERE \"x\" BETWEEN 1 AND 2",
+                ") \"o\"",
+                "LEFT JOIN \"c\"",
+                "  ON \"o\".\"id\" = \"c\".\"id\"",
+                "GROUP BY 1",
+                "ORDER BY 2 DESC",
+                "LIMIT 10",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn breaks_long_clauses_into_items() {
+        let options = RenderOptions {
+            max_line_len: 30,
+            ..RenderOptions::pretty()
+        };
+        let sql = "SELECT alpha, beta, CASE WHEN a AND b THEN 1 END AS gamma FROM t WHERE alpha > 1 AND beta IS NOT DISTINCT FROM 'a, b'";
+        assert_eq!(
+            format_sql(sql, &options, false),
+            [
+                "SELECT",
+                "  alpha,",
+                "  beta,",
+                "  CASE WHEN a AND b THEN 1 END AS gamma",
+                "FROM t",
+                "WHERE",
+                "  alpha > 1",
+                "  AND beta IS NOT DISTINCT FROM 'a, b'",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn lower_cases_keywords_outside_literals_and_names() {
+        let options = RenderOptions {
+            uppercase_keywords: false,
+            ..RenderOptions::default()
+        };
+        let sql = "SELECT o.LAST AS \"FROM\" FROM t WHERE x = 'SELECT' AND y IS NULL";
+        assert_eq!(
+            format_sql(sql, &options, false),
+            "select o.LAST as \"FROM\" from t where x = 'SELECT' and y is null"
+        );
+    }
+
+    #[test]
+    fn leaves_untokenizable_sql_alone() {
+        assert_eq!(pretty("SELECT 'open FROM t"), "SELECT 'open FROM t");
+        assert_eq!(pretty("SELECT (a FROM t"), "SELECT (a FROM t");
+    }
+}
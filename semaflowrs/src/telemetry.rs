@@ -0,0 +1,181 @@
+//! Spans and metrics for the query lifecycle.
+//!
+//! Every query runs in a `semaflow.query` span (the `run_query*` entry
+//! points) with `flow`, `kind` and `data_source` fields, holding:
+//!
+//! - `semaflow.plan`: building the SQL for the request
+//! - `semaflow.execute`: the backend call, with the `rows` it returned
+//! - `semaflow.count`: the total-row count of a paginated query with `count_total`
+//!
+//! The spans are plain `tracing` spans; export them to OpenTelemetry with a
+//! `tracing-opentelemetry` layer in the application's subscriber.
+//!
+//! With the `otel` feature, metrics are also recorded through the global
+//! OpenTelemetry meter (`semaflow`). Install the meter provider before the
+//! first query; instruments are created then.
+//!
+//! | Metric | Kind | Attributes |
+//! |--------|------|------------|
+//! | `semaflow.query.duration` | histogram (s) | `flow`, `data_source`, `kind`, `outcome` |
+//! | `semaflow.query.rows` | histogram | `flow`, `data_source`, `kind` |
+//! | `semaflow.query.errors` | counter | `flow`, `data_source`, `kind`, `error` (the error code) |
+//! | `semaflow.result_cache.lookups` | counter | `flow`, `hit` |
+
+use std::future::Future;
+use std::time::Duration;
+
+use tracing::{Instrument, Span};
+
+use crate::error::{Result, SemaflowError};
+
+/// How a query's results are delivered, recorded as the `kind` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryKind {
+    Rows,
+    Stream,
+    Arrow,
+    Page,
+}
+
+impl QueryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QueryKind::Rows => "rows",
+            QueryKind::Stream => "stream",
+            QueryKind::Arrow => "arrow",
+            QueryKind::Page => "page",
+        }
+    }
+}
+
+/// Span around building the SQL for a request of `flow`.
+pub fn plan_span(flow: &str) -> Span {
+    tracing::info_span!("semaflow.plan", flow)
+}
+
+/// Run a backend call against `data_source` in a `semaflow.execute` span,
+/// recording the number of `rows` it returned.
+pub async fn execute<T>(
+    data_source: &str,
+    call: impl Future<Output = Result<T>>,
+    rows: impl FnOnce(&T) -> u64,
+) -> Result<T> {
+    let span = tracing::info_span!(
+        "semaflow.execute",
+        data_source,
+        rows = tracing::field::Empty
+    );
+    let result = call.instrument(span.clone()).await;
+    if let Ok(value) = &result {
+        span.record("rows", rows(value));
+    }
+    result
+}
+
+/// Span around counting the rows of a paginated query.
+pub fn count_span(data_source: &str) -> Span {
+    tracing::info_span!("semaflow.count", data_source)
+}
+
+/// Record a finished query: its latency, and the rows it returned or its error.
+pub fn record_query(
+    flow: &str,
+    data_source: &str,
+    kind: QueryKind,
+    elapsed: Duration,
+    outcome: std::result::Result<u64, &SemaflowError>,
+) {
+    #[cfg(feature = "otel")]
+    otel::record_query(flow, data_source, kind, elapsed, outcome);
+    #[cfg(not(feature = "otel"))]
+    let _ = (flow, data_source, kind, elapsed, outcome);
+}
+
+/// Record a result cache lookup for a query of `flow`.
+pub fn record_cache_lookup(flow: &str, hit: bool) {
+    #[cfg(feature = "otel")]
+    otel::record_cache_lookup(flow, hit);
+    #[cfg(not(feature = "otel"))]
+    let _ = (flow, hit);
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use std::time::Duration;
+
+    use once_cell::sync::Lazy;
+    use opentelemetry::metrics::{Counter, Histogram};
+    use opentelemetry::{global, KeyValue};
+
+    use super::QueryKind;
+    use crate::error::SemaflowError;
+
+    struct Instruments {
+        duration: Histogram<f64>,
+        rows: Histogram<u64>,
+        errors: Counter<u64>,
+        cache_lookups: Counter<u64>,
+    }
+
+    static INSTRUMENTS: Lazy<Instruments> = Lazy::new(|| {
+        let meter = global::meter("semaflow");
+        Instruments {
+            duration: meter
+                .f64_histogram("semaflow.query.duration")
+                .with_unit("s")
+                .with_description("Time from request to result, including planning")
+                .build(),
+            rows: meter
+                .u64_histogram("semaflow.query.rows")
+                .with_description("Rows returned per query or page")
+                .build(),
+            errors: meter
+                .u64_counter("semaflow.query.errors")
+                .with_description("Failed queries by error code")
+                .build(),
+            cache_lookups: meter
+                .u64_counter("semaflow.result_cache.lookups")
+                .with_description("Result cache lookups; hit rate is hit=true over all")
+                .build(),
+        }
+    });
+
+    pub(super) fn record_query(
+        flow: &str,
+        data_source: &str,
+        kind: QueryKind,
+        elapsed: Duration,
+        outcome: std::result::Result<u64, &SemaflowError>,
+    ) {
+        let mut attributes = vec![
+            KeyValue::new("flow", flow.to_string()),
+            KeyValue::new("data_source", data_source.to_string()),
+            KeyValue::new("kind", kind.as_str()),
+        ];
+        match outcome {
+            Ok(rows) => {
+                INSTRUMENTS.rows.record(rows, &attributes);
+                attributes.push(KeyValue::new("outcome", "ok"));
+            }
+            Err(error) => {
+                let mut error_attributes = attributes.clone();
+                error_attributes.push(KeyValue::new("error", error.code()));
+                INSTRUMENTS.errors.add(1, &error_attributes);
+                attributes.push(KeyValue::new("outcome", "error"));
+            }
+        }
+        INSTRUMENTS
+            .duration
+            .record(elapsed.as_secs_f64(), &attributes);
+    }
+
+    pub(super) fn record_cache_lookup(flow: &str, hit: bool) {
+        INSTRUMENTS.cache_lookups.add(
+            1,
+            &[
+                KeyValue::new("flow", flow.to_string()),
+                KeyValue::new("hit", hit),
+            ],
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! Golden-SQL snapshot tests for semantic models.
+//!
+//! Model repositories can pin the SQL their flows compile to and catch
+//! unintended changes when models or SemaFlow change. [`GoldenSql`] builds
+//! each named request for each dialect and compares the SQL with
+//! `<case>.<dialect>.sql` in a snapshot directory:
+//!
+//! ```no_run
+//! use semaflow::registry::FlowRegistry;
+//! use semaflow::testing::GoldenSql;
+//! use semaflow::QueryRequest;
+//!
+//! let registry = FlowRegistry::load_from_dir("models").unwrap();
+//! let request: QueryRequest = serde_json::from_value(serde_json::json!({
+//!     "flow": "sales",
+//!     "dimensions": ["c.country"],
+//!     "measures": ["o.order_total"],
+//! }))
+//! .unwrap();
+//! GoldenSql::new(registry, "tests/snapshots")
+//!     .case("revenue_by_country", request)
+//!     .assert();
+//! ```
+//!
+//! Missing snapshots are written on the first run, except when the `CI`
+//! environment variable is set, where they fail. Set
+//! `SEMAFLOW_UPDATE_SNAPSHOTS=1` to overwrite snapshots that no longer match
+//! after an intended change, then review the diff.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dialect::Dialect;
+use crate::error::{Result, SemaflowError};
+use crate::flows::QueryRequest;
+use crate::query_builder::SqlBuilder;
+use crate::registry::FlowRegistry;
+use crate::sql_format::RenderOptions;
+
+/// Environment variable overwriting mismatched snapshots when set to `1`.
+pub const UPDATE_ENV: &str = "SEMAFLOW_UPDATE_SNAPSHOTS";
+
+/// Load a registry fixture from inline YAML documents, one per table and
+/// flow, with the same parsing, `extends` and join inference as model
+/// files on disk.
+pub fn registry_from_yaml(tables: &[&str], flows: &[&str]) -> Result<FlowRegistry> {
+    let dir = tempfile::tempdir().map_err(|e| SemaflowError::Other(e.into()))?;
+    for (kind, documents) in [("tables", tables), ("flows", flows)] {
+        let kind_dir = dir.path().join(kind);
+        fs::create_dir_all(&kind_dir).map_err(|e| SemaflowError::Other(e.into()))?;
+        for (i, document) in documents.iter().enumerate() {
+            fs::write(kind_dir.join(format!("{i}.yaml")), document)
+                .map_err(|e| SemaflowError::Other(e.into()))?;
+        }
+    }
+    FlowRegistry::load_from_dir(dir.path())
+}
+
+/// What happened to one snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotStatus {
+    Matched,
+    /// Written because it did not exist yet.
+    Created,
+    /// Overwritten in update mode.
+    Updated,
+    /// Missing and not recorded, e.g. in CI.
+    Missing,
+    /// Differs from the stored SQL.
+    Mismatched {
+        expected: String,
+    },
+}
+
+/// The outcome of one case for one dialect.
+#[derive(Debug, Clone)]
+pub struct SnapshotResult {
+    pub case: String,
+    pub dialect: String,
+    pub path: PathBuf,
+    pub sql: String,
+    pub status: SnapshotStatus,
+}
+
+impl SnapshotResult {
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self.status,
+            SnapshotStatus::Missing | SnapshotStatus::Mismatched { .. }
+        )
+    }
+}
+
+impl fmt::Display for SnapshotResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = format!("{} [{}] ({})", self.case, self.dialect, self.path.display());
+        match &self.status {
+            SnapshotStatus::Matched => write!(f, "{label}: matched"),
+            SnapshotStatus::Created => write!(f, "{label}: created"),
+            SnapshotStatus::Updated => write!(f, "{label}: updated"),
+            SnapshotStatus::Missing => write!(f, "{label}: missing snapshot\n{}", self.sql),
+            SnapshotStatus::Mismatched { expected } => {
+                writeln!(f, "{label}: SQL changed")?;
+                write!(f, "{}", line_diff(expected, &self.sql))
+            }
+        }
+    }
+}
+
+/// A set of named requests whose SQL is compared with stored snapshots, per
+/// dialect.
+pub struct GoldenSql {
+    registry: FlowRegistry,
+    dir: PathBuf,
+    builder: SqlBuilder,
+    options: RenderOptions,
+    dialects: Vec<(String, Box<dyn Dialect>)>,
+    cases: Vec<(String, QueryRequest)>,
+    update: bool,
+    record_missing: bool,
+}
+
+impl GoldenSql {
+    /// Snapshots are read from and written to `dir`. Without any
+    /// [`Self::dialect`], cases are rendered for DuckDB. Update and record
+    /// modes follow the environment; see the module docs.
+    pub fn new(registry: FlowRegistry, dir: impl Into<PathBuf>) -> Self {
+        let update = std::env::var(UPDATE_ENV).is_ok_and(|v| v == "1");
+        Self {
+            registry,
+            dir: dir.into(),
+            builder: SqlBuilder::default(),
+            options: RenderOptions::default(),
+            dialects: Vec::new(),
+            cases: Vec::new(),
+            update,
+            record_missing: update || std::env::var_os("CI").is_none(),
+        }
+    }
+
+    /// Render every case for `dialect` too, in snapshots suffixed `name`.
+    pub fn dialect(mut self, name: &str, dialect: impl Dialect + 'static) -> Self {
+        self.dialects.push((name.to_string(), Box::new(dialect)));
+        self
+    }
+
+    /// Add a request, snapshotted as `<name>.<dialect>.sql`.
+    pub fn case(mut self, name: &str, request: QueryRequest) -> Self {
+        self.cases.push((name.to_string(), request));
+        self
+    }
+
+    /// Build with a configured builder, e.g. one with a different default
+    /// strategy.
+    pub fn builder(mut self, builder: SqlBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    /// Lay out snapshot SQL with `options`; [`RenderOptions::pretty`] keeps
+    /// diffs to the clauses that changed.
+    pub fn render_options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Overwrite snapshots that no longer match instead of failing.
+    pub fn update_snapshots(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Write missing snapshots instead of failing.
+    pub fn record_missing(mut self, record: bool) -> Self {
+        self.record_missing = record;
+        self
+    }
+
+    /// Build every case for every dialect and compare with the snapshots,
+    /// writing new ones (and, in update mode, changed ones). Fails on the
+    /// first request that does not build.
+    pub fn check(&self) -> Result<Vec<SnapshotResult>> {
+        let default_dialects = self.default_dialects();
+        let dialects = if self.dialects.is_empty() {
+            &default_dialects
+        } else {
+            &self.dialects
+        };
+        if dialects.is_empty() {
+            return Err(SemaflowError::Config(
+                "golden SQL tests need a dialect; none is built in".to_string(),
+            ));
+        }
+
+        let mut results = Vec::new();
+        for (case, request) in &self.cases {
+            for (name, dialect) in dialects {
+                let sql = self
+                    .builder
+                    .build_with_options(&self.registry, request, dialect.as_ref(), &self.options)
+                    .map_err(|e| SemaflowError::Validation(format!("case {case} [{name}]: {e}")))?;
+                let sql = format!("{}\n", sql.trim_end());
+                let path = self.dir.join(format!("{case}.{name}.sql"));
+                let status = match fs::read_to_string(&path) {
+                    Ok(expected) if expected == sql => SnapshotStatus::Matched,
+                    Ok(_) if self.update => {
+                        write_snapshot(&path, &sql)?;
+                        SnapshotStatus::Updated
+                    }
+                    Ok(expected) => SnapshotStatus::Mismatched { expected },
+                    Err(_) if !self.record_missing => SnapshotStatus::Missing,
+                    Err(_) => {
+                        write_snapshot(&path, &sql)?;
+                        SnapshotStatus::Created
+                    }
+                };
+                results.push(SnapshotResult {
+                    case: case.clone(),
+                    dialect: name.clone(),
+                    path,
+                    sql,
+                    status,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Like [`Self::check`], panicking with a diff of every failing
+    /// snapshot.
+    pub fn assert(&self) {
+        let results = match self.check() {
+            Ok(results) => results,
+            Err(e) => panic!("golden SQL: {e}"),
+        };
+        let failures: Vec<String> = results
+            .iter()
+            .filter(|result| result.is_failure())
+            .map(|result| result.to_string())
+            .collect();
+        if !failures.is_empty() {
+            panic!(
+                "{} golden SQL snapshot(s) failed; rerun with {UPDATE_ENV}=1 to accept\n\n{}",
+                failures.len(),
+                failures.join("\n\n")
+            );
+        }
+    }
+
+    #[cfg(feature = "duckdb")]
+    fn default_dialects(&self) -> Vec<(String, Box<dyn Dialect>)> {
+        vec![(
+            "duckdb".to_string(),
+            Box::new(crate::dialect::DuckDbDialect) as Box<dyn Dialect>,
+        )]
+    }
+
+    #[cfg(not(feature = "duckdb"))]
+    fn default_dialects(&self) -> Vec<(String, Box<dyn Dialect>)> {
+        Vec::new()
+    }
+}
+
+fn write_snapshot(path: &Path, sql: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| SemaflowError::Other(e.into()))?;
+    }
+    fs::write(path, sql).map_err(|e| SemaflowError::Other(e.into()))
+}
+
+/// Lines of `expected` missing from `actual` prefixed `-`, and lines new in
+/// `actual` prefixed `+`, in order; matching lines are kept for context.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    // Longest common subsequence table, filled from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    out.join("\n")
+}
+
+#[cfg(all(test, feature = "duckdb"))]
+mod tests {
+    use super::*;
+
+    const ORDERS: &str = r#"
+name: orders
+data_source: local
+table: orders
+primary_key: id
+dimensions:
+  status: status
+measures:
+  order_total:
+    expr: amount
+    agg: sum
+"#;
+
+    const SALES: &str = r#"
+name: sales
+base_table:
+  semantic_table: orders
+  alias: o
+"#;
+
+    #[test]
+    fn records_then_compares_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let request: QueryRequest = serde_json::from_value(serde_json::json!({
+            "flow": "sales",
+            "dimensions": ["status"],
+            "measures": ["order_total"],
+        }))
+        .unwrap();
+        let suite = |measure_sql: &str| {
+            let orders = ORDERS.replace("amount", measure_sql);
+            let registry = registry_from_yaml(&[&orders], &[SALES]).unwrap();
+            GoldenSql::new(registry, dir.path())
+                .case("by_status", request.clone())
+                .update_snapshots(false)
+                .record_missing(true)
+        };
+
+        let first = suite("amount").check().unwrap();
+        assert_eq!(first[0].status, SnapshotStatus::Created);
+        assert!(dir.path().join("by_status.duckdb.sql").exists());
+        assert_eq!(
+            suite("amount").check().unwrap()[0].status,
+            SnapshotStatus::Matched
+        );
+
+        let missing = suite("amount")
+            .case("other", request.clone())
+            .record_missing(false);
+        assert_eq!(missing.check().unwrap()[1].status, SnapshotStatus::Missing);
+
+        let changed = suite("net_amount").check().unwrap();
+        assert!(changed[0].is_failure());
+        let report = changed[0].to_string();
+        assert!(report.contains("- ") && report.contains("+ "), "{report}");
+        assert!(report.contains("net_amount"), "{report}");
+    }
+
+    #[test]
+    fn diff_marks_changed_lines() {
+        let diff = line_diff("SELECT\na\nFROM t", "SELECT\nb\nFROM t");
+        assert_eq!(diff, "  SELECT\n- a\n+ b\n  FROM t");
+    }
+}
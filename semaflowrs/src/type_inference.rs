@@ -0,0 +1,231 @@
+//! Inference of `data_type` for dimensions and measures.
+//!
+//! During validation each table's fields without a declared `data_type` get
+//! one derived from the warehouse column types and their expressions: a
+//! column keeps the type the backend reports, `date_trunc` yields a
+//! timestamp, `count` a bigint, `avg` a double and so on. Declared types are
+//! never changed. Inferred types show up in flow schemas and are used to
+//! check request filter values, like declared ones.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::flows::{Aggregation, BinaryOp, BucketSpec, Expr, Function, Measure, SemanticTable};
+use crate::schema_cache::TableSchema;
+
+const BIGINT: &str = "bigint";
+const DOUBLE: &str = "double";
+const BOOLEAN: &str = "boolean";
+const STRING: &str = "string";
+const DATE: &str = "date";
+const TIMESTAMP: &str = "timestamp";
+
+/// Fill in the `data_type` of the dimensions and measures of `table` that
+/// declare none, from the columns of `schema`.
+pub fn infer_table_types(table: &mut SemanticTable, schema: &TableSchema) {
+    let columns: HashMap<&str, &str> = schema
+        .columns
+        .iter()
+        .map(|c| (c.name.as_str(), c.data_type.as_str()))
+        .collect();
+    for dimension in table.dimensions.values_mut() {
+        if dimension.data_type.is_none() {
+            dimension.data_type = infer_expr_type(&dimension.expr, &columns);
+        }
+    }
+    for measure in table.measures.values_mut() {
+        if measure.data_type.is_none() {
+            measure.data_type = infer_measure_type(measure, &columns);
+        }
+    }
+}
+
+/// Type of `expr` over a table with `columns`, when it can be told.
+pub fn infer_expr_type(expr: &Expr, columns: &HashMap<&str, &str>) -> Option<String> {
+    let infer = |expr: &Expr| infer_expr_type(expr, columns);
+    match expr {
+        Expr::Column { column } => {
+            let name = column.rsplit('.').next().unwrap_or(column);
+            columns.get(name).map(|t| t.to_string())
+        }
+        Expr::Literal { value } => match value {
+            Value::Number(n) if n.is_i64() || n.is_u64() => Some(BIGINT.to_string()),
+            Value::Number(_) => Some(DOUBLE.to_string()),
+            Value::String(_) => Some(STRING.to_string()),
+            Value::Bool(_) => Some(BOOLEAN.to_string()),
+            _ => None,
+        },
+        Expr::Func { func, args } => function_type(func, args, columns),
+        Expr::Case {
+            branches,
+            else_expr,
+        } => branches
+            .iter()
+            .find_map(|branch| infer(&branch.then))
+            .or_else(|| infer(else_expr)),
+        Expr::Binary { op, left, right } => match op {
+            BinaryOp::Divide => Some(DOUBLE.to_string()),
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Modulo => {
+                let (left, right) = (infer(left), infer(right));
+                // The wider operand wins: integers only when both are
+                match (&left, &right) {
+                    (Some(l), Some(r)) if is_integer(l) && !is_integer(r) => right,
+                    (None, _) => right,
+                    _ => left,
+                }
+            }
+            _ => Some(BOOLEAN.to_string()),
+        },
+        Expr::InList { .. } | Expr::IsNull { .. } | Expr::Between { .. } | Expr::Not { .. } => {
+            Some(BOOLEAN.to_string())
+        }
+        Expr::MeasureRef { .. } | Expr::Param { .. } | Expr::Sql { .. } => None,
+    }
+}
+
+fn function_type(func: &Function, args: &[Expr], columns: &HashMap<&str, &str>) -> Option<String> {
+    let arg = |i: usize| args.get(i).and_then(|a| infer_expr_type(a, columns));
+    let first_known = || args.iter().find_map(|a| infer_expr_type(a, columns));
+    let fixed = |t: &str| Some(t.to_string());
+    match func {
+        Function::DateTrunc(_) | Function::Now | Function::CurrentTimestamp => fixed(TIMESTAMP),
+        Function::CurrentDate => fixed(DATE),
+        Function::DateAdd { .. } => arg(2).or_else(|| fixed(TIMESTAMP)),
+        Function::DatePart { .. } | Function::Extract { .. } | Function::DateDiff { .. } => {
+            fixed(BIGINT)
+        }
+        Function::Lower
+        | Function::Upper
+        | Function::Concat
+        | Function::ConcatWs { .. }
+        | Function::Substring
+        | Function::Trim
+        | Function::Ltrim
+        | Function::Rtrim
+        | Function::Left
+        | Function::Right
+        | Function::Replace
+        | Function::Reverse
+        | Function::Repeat
+        | Function::RegexpExtract
+        | Function::RegexpReplace => fixed(STRING),
+        Function::Length | Function::Position => fixed(BIGINT),
+        Function::StartsWith
+        | Function::EndsWith
+        | Function::Contains
+        | Function::RegexpMatches
+        | Function::StWithin => fixed(BOOLEAN),
+        Function::Coalesce
+        | Function::IfNull
+        | Function::NullIf
+        | Function::Greatest
+        | Function::Least => first_known(),
+        Function::SafeDivide
+        | Function::Power
+        | Function::Sqrt
+        | Function::Ln
+        | Function::Log10
+        | Function::Log
+        | Function::Exp
+        | Function::StDistance => fixed(DOUBLE),
+        Function::Abs | Function::Ceil | Function::Floor | Function::Round | Function::Sign => {
+            arg(0)
+        }
+        Function::StPoint => fixed("geography"),
+        Function::Cast { data_type } | Function::TryCast { data_type } => Some(data_type.clone()),
+        Function::Bucket(BucketSpec::Width(_)) => fixed(DOUBLE),
+        Function::Bucket(BucketSpec::Edges(_)) => fixed(STRING),
+    }
+}
+
+/// Type of a simple measure's aggregate; formula measures and measures with
+/// a `post_expr` are left untyped.
+pub fn infer_measure_type(measure: &Measure, columns: &HashMap<&str, &str>) -> Option<String> {
+    if measure.post_expr.is_some() {
+        return None;
+    }
+    let input = || {
+        measure
+            .expr
+            .as_ref()
+            .and_then(|e| infer_expr_type(e, columns))
+    };
+    let fixed = |t: &str| Some(t.to_string());
+    match measure.agg.as_ref()? {
+        Aggregation::Count
+        | Aggregation::CountDistinct
+        | Aggregation::CountIf
+        | Aggregation::ApproxCountDistinct => fixed(BIGINT),
+        Aggregation::Sum => input().map(|t| {
+            if is_integer(&t) {
+                BIGINT.to_string()
+            } else {
+                t
+            }
+        }),
+        Aggregation::Avg
+        | Aggregation::Median
+        | Aggregation::Stddev
+        | Aggregation::StddevSamp
+        | Aggregation::Variance
+        | Aggregation::VarianceSamp
+        | Aggregation::Percentile { .. }
+        | Aggregation::ApproxPercentile { .. } => fixed(DOUBLE),
+        Aggregation::Min
+        | Aggregation::Max
+        | Aggregation::First { .. }
+        | Aggregation::Last { .. } => input(),
+        Aggregation::BoolAnd | Aggregation::BoolOr => fixed(BOOLEAN),
+        Aggregation::StringAgg { .. } => fixed(STRING),
+        Aggregation::ArrayAgg => None,
+    }
+}
+
+/// Whether `data_type` names an integer type, e.g. `INTEGER`, `INT64`.
+fn is_integer(data_type: &str) -> bool {
+    data_type.to_ascii_lowercase().contains("int")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flows::TimeGrain;
+
+    fn column(name: &str) -> Expr {
+        Expr::Column {
+            column: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn infers_expression_types() {
+        let columns = HashMap::from([
+            ("created_at", "DATE"),
+            ("qty", "INTEGER"),
+            ("price", "DECIMAL(10,2)"),
+        ]);
+        let trunc = Expr::Func {
+            func: Function::DateTrunc(TimeGrain::Month),
+            args: vec![column("created_at")],
+        };
+        assert_eq!(
+            infer_expr_type(&trunc, &columns).as_deref(),
+            Some("timestamp")
+        );
+        assert_eq!(
+            infer_expr_type(&column("o.qty"), &columns).as_deref(),
+            Some("INTEGER")
+        );
+        let total = Expr::Binary {
+            op: BinaryOp::Multiply,
+            left: Box::new(column("qty")),
+            right: Box::new(column("price")),
+        };
+        assert_eq!(
+            infer_expr_type(&total, &columns).as_deref(),
+            Some("DECIMAL(10,2)")
+        );
+        assert_eq!(infer_expr_type(&column("missing"), &columns), None);
+    }
+}
@@ -1,29 +1,203 @@
 use std::collections::HashSet;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use anyhow::anyhow;
+use serde::Serialize;
 
 use crate::backends::ConnectionManager;
+use crate::dialect::grain_to_str;
+use crate::drift::{self, DriftReport};
 use crate::error::{Result, SemaflowError};
 use crate::expr_parser::parse_formula;
-use crate::expr_utils::{collect_column_refs, collect_measure_refs, simple_column_name};
-use crate::flows::{FormulaAst, SemanticFlow, SemanticTable};
+use crate::expr_utils::{
+    collect_column_refs, collect_measure_refs, collect_param_refs, collect_time_grains,
+    simple_column_name,
+};
+use crate::flows::{
+    Aggregation, FilterExpr, FormulaAst, JoinType, SemanticFlow, SemanticTable, TableUnion,
+};
+use crate::policy::QueryContext;
+use crate::query_builder::{find_derived_cycle, find_join_cycle, union_sql, SqlBuilder};
 use crate::registry::FlowRegistry;
-use crate::schema_cache::{SchemaCache, TableSchema};
+use crate::schema_cache::{ColumnSchema, FileSchemaStore, SchemaCache, SchemaStore, TableSchema};
+use crate::type_inference;
 
 pub struct Validator {
     connections: ConnectionManager,
     cache: Mutex<SchemaCache>,
+    /// Where fetched schemas are persisted, if anywhere.
+    store: Option<Arc<dyn SchemaStore>>,
     warn_only: bool,
+    /// Set while [`Validator::collect_issues`] runs; failed checks are
+    /// recorded here instead of returned.
+    collector: Mutex<Option<Collector>>,
+}
+
+/// How serious a [`ValidationIssue`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    /// A failed check while the validator is in warn-only mode, or a
+    /// likely mistake that is never an error.
+    Warning,
+}
+
+/// One problem found in the semantic model.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// Semantic table being validated, if the issue is in a table.
+    pub table: Option<String>,
+    /// Flow being validated, if the issue is in a flow.
+    pub flow: Option<String>,
+    /// Column, dimension, measure, join or parameter the issue is about.
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct Collector {
+    table: Option<String>,
+    flow: Option<String>,
+    issues: Vec<ValidationIssue>,
 }
 
 impl Validator {
+    /// Schemas are cached with the TTLs of the connections' configuration,
+    /// and persisted to its `schema_cache.path` when set.
     pub fn new(connections: ConnectionManager, warn_only: bool) -> Self {
-        Self {
+        let config = connections.config();
+        let cache = config.map_or_else(SchemaCache::new, SchemaCache::for_config);
+        let store = config
+            .and_then(|config| config.defaults.schema_cache.path.clone())
+            .map(|path| Arc::new(FileSchemaStore::new(path)) as Arc<dyn SchemaStore>);
+        let validator = Self {
             connections,
-            cache: Mutex::new(SchemaCache::new()),
+            cache: Mutex::new(cache),
+            store: None,
             warn_only,
+            collector: Mutex::new(None),
+        };
+        match store {
+            Some(store) => validator.with_schema_store(store),
+            None => validator,
+        }
+    }
+
+    /// Persist fetched schemas to `store`, starting from the schemas it
+    /// holds. A store that cannot be read starts empty.
+    pub fn with_schema_store(mut self, store: Arc<dyn SchemaStore>) -> Self {
+        match store.load() {
+            Ok(stored) => {
+                let cache = self.cache.get_mut().unwrap_or_else(|e| e.into_inner());
+                let restored = cache.restore(stored);
+                tracing::debug!(restored, "schemas restored from store");
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to load stored schemas"),
+        }
+        self.store = Some(store);
+        self
+    }
+
+    /// Fetch the schema of every physical table not cached yet, e.g. at
+    /// startup, and persist the cache. Returns the number of tables fetched.
+    pub async fn warm_schemas(&self, registry: &FlowRegistry) -> Result<usize> {
+        let mut tables: Vec<&SemanticTable> = registry
+            .tables
+            .values()
+            .filter(|table| !table.is_derived())
+            .collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut fetched = 0;
+        for table in tables {
+            if self
+                .lock_cache()?
+                .contains(&table.data_source, &table.table)
+            {
+                continue;
+            }
+            self.ensure_schema(&table.data_source, &table.table).await?;
+            fetched += 1;
+        }
+        self.persist_schemas()?;
+        Ok(fetched)
+    }
+
+    /// Fetch the schema of a physical table again, replacing the cached
+    /// one, e.g. after a migration.
+    pub async fn refresh_schema(&self, table: &SemanticTable) -> Result<TableSchema> {
+        if table.is_derived() {
+            return Err(SemaflowError::Validation(format!(
+                "table {} is derived; its schema is read from its query, not cached",
+                table.name
+            )));
         }
+        self.lock_cache()?.remove(&table.data_source, &table.table);
+        let schema = self.ensure_schema(&table.data_source, &table.table).await?;
+        self.persist_schemas()?;
+        Ok(schema)
+    }
+
+    /// Fetch the live schema of every physical table, bypassing the cache,
+    /// and report columns the model reads that disappeared or changed type
+    /// since last seen, and new columns nothing reads; see
+    /// [`crate::drift`]. The live schemas replace the cached ones, so each
+    /// check compares against the previous one when a store keeps them.
+    pub async fn check_drift(&self, registry: &FlowRegistry) -> Result<DriftReport> {
+        let lineage = registry.lineage();
+        let mut tables: Vec<&SemanticTable> = registry
+            .tables
+            .values()
+            .filter(|table| !table.is_derived())
+            .collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut report = DriftReport::default();
+        for table in tables {
+            let provider = self.connections.get(&table.data_source).ok_or_else(|| {
+                SemaflowError::UnknownDataSource {
+                    data_source: table.data_source.clone(),
+                }
+            })?;
+            let live = match provider.fetch_schema(&table.table).await {
+                Ok(live) => live,
+                Err(e) => {
+                    report
+                        .drifts
+                        .push(drift::unavailable(table, &e.to_string()));
+                    continue;
+                }
+            };
+            let previous = self
+                .lock_cache()?
+                .last_known(&table.data_source, &table.table)
+                .cloned();
+            let used = drift::model_columns(table, &lineage);
+            report
+                .drifts
+                .extend(drift::compare(table, &used, previous.as_ref(), &live));
+            self.lock_cache()?
+                .insert(table.data_source.clone(), table.table.clone(), live);
+        }
+        self.persist_schemas()?;
+        report.sort();
+        Ok(report)
+    }
+
+    fn lock_cache(&self) -> Result<MutexGuard<'_, SchemaCache>> {
+        self.cache
+            .lock()
+            .map_err(|e| SemaflowError::Other(anyhow!("schema cache lock: {e}")))
+    }
+
+    /// Write the cached schemas to the store, if any.
+    fn persist_schemas(&self) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let snapshot = self.lock_cache()?.snapshot();
+        store.save(&snapshot)
     }
 
     #[tracing::instrument(skip(self, registry), fields(tables = registry.tables.len(), flows = registry.flows.len()))]
@@ -31,11 +205,25 @@ impl Validator {
         let start = std::time::Instant::now();
         tracing::info!("starting registry validation");
 
+        let mut schemas = Vec::new();
         for table in registry.tables.values() {
             tracing::debug!(table = %table.name, "validating table");
-            let schema = self.ensure_schema(&table.data_source, &table.table).await?;
+            let schema = self.table_schema(table, registry).await?;
+            schemas.push((table.name.clone(), schema.clone()));
             self.validate_table(table, schema)?;
         }
+        for (name, schema) in schemas {
+            if let Some(table) = registry.tables.get_mut(&name) {
+                if !table.is_derived() {
+                    seed_key_hints(table, &schema);
+                }
+                type_inference::infer_table_types(table, &schema);
+            }
+        }
+
+        if let Err(e) = self.persist_schemas() {
+            tracing::warn!(error = %e, "failed to persist schemas");
+        }
 
         for flow in registry.flows.values() {
             tracing::debug!(flow = %flow.name, "validating flow");
@@ -51,14 +239,79 @@ impl Validator {
         Ok(())
     }
 
+    /// Check flows (joins, aliases, field references) without fetching table
+    /// schemas, for validating models offline.
+    pub fn validate_flows(&self, registry: &FlowRegistry) -> Result<()> {
+        for flow in registry.flows.values() {
+            self.validate_flow(flow, registry)?;
+        }
+        Ok(())
+    }
+
+    /// Validate every table and flow, returning all issues found instead of
+    /// stopping at the first. Checks that cannot continue past a failure
+    /// (a table whose schema cannot be fetched, a formula that does not
+    /// parse) end validation of that table or flow only.
+    pub async fn collect_issues(&self, registry: &FlowRegistry) -> Vec<ValidationIssue> {
+        self.set_collector(Some(Collector::default()));
+
+        for table in registry.tables.values() {
+            self.set_subject(Some(&table.name), None);
+            let result = match self.table_schema(table, registry).await {
+                Ok(schema) => self.validate_table(table, schema),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                self.record(Severity::Error, None, e.to_string());
+            }
+        }
+        if let Err(e) = self.persist_schemas() {
+            tracing::warn!(error = %e, "failed to persist schemas");
+        }
+
+        for flow in registry.flows.values() {
+            self.set_subject(None, Some(&flow.name));
+            if let Err(e) = self.validate_flow(flow, registry) {
+                self.record(Severity::Error, None, e.to_string());
+            }
+        }
+
+        self.set_collector(None)
+            .map(|collector| collector.issues)
+            .unwrap_or_default()
+    }
+
+    fn set_collector(&self, collector: Option<Collector>) -> Option<Collector> {
+        let mut guard = self.collector.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *guard, collector)
+    }
+
+    fn set_subject(&self, table: Option<&str>, flow: Option<&str>) {
+        let mut guard = self.collector.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(collector) = guard.as_mut() {
+            collector.table = table.map(str::to_string);
+            collector.flow = flow.map(str::to_string);
+        }
+    }
+
+    /// Record an issue when collecting; returns false otherwise.
+    fn record(&self, severity: Severity, field: Option<&str>, message: String) -> bool {
+        let mut guard = self.collector.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(collector) = guard.as_mut() else {
+            return false;
+        };
+        collector.issues.push(ValidationIssue {
+            severity,
+            table: collector.table.clone(),
+            flow: collector.flow.clone(),
+            field: field.map(str::to_string),
+            message,
+        });
+        true
+    }
+
     async fn ensure_schema(&self, data_source: &str, table: &str) -> Result<TableSchema> {
-        if let Some(schema) = self
-            .cache
-            .lock()
-            .map_err(|e| SemaflowError::Other(anyhow!("schema cache lock: {e}")))?
-            .get(data_source, table)
-            .cloned()
-        {
+        if let Some(schema) = self.lock_cache()?.get(data_source, table).cloned() {
             tracing::debug!(data_source = %data_source, table = %table, "schema cache hit");
             return Ok(schema);
         }
@@ -66,7 +319,9 @@ impl Validator {
         tracing::debug!(data_source = %data_source, table = %table, "schema cache miss, fetching from backend");
         let provider = self.connections.get(data_source).ok_or_else(|| {
             tracing::warn!(data_source = %data_source, "unknown data source");
-            SemaflowError::Validation(format!("unknown data source {data_source}"))
+            SemaflowError::UnknownDataSource {
+                data_source: data_source.to_string(),
+            }
         })?;
 
         let start = std::time::Instant::now();
@@ -79,23 +334,261 @@ impl Validator {
             "schema fetched from backend"
         );
 
-        self.cache
-            .lock()
-            .map_err(|e| SemaflowError::Other(anyhow!("schema cache lock: {e}")))?
+        self.lock_cache()?
             .insert(data_source.to_string(), table.to_string(), schema.clone());
         Ok(schema)
     }
 
+    /// Columns the table reads: the physical table's, or those its derived
+    /// `sql`, `query` or `union` returns.
+    async fn table_schema(
+        &self,
+        table: &SemanticTable,
+        registry: &FlowRegistry,
+    ) -> Result<TableSchema> {
+        let sql = match (&table.sql, &table.query, &table.union) {
+            (Some(sql), _, _) => sql.clone(),
+            (None, Some(query), _) => {
+                let source = registry
+                    .get_flow(&query.flow)
+                    .and_then(|flow| registry.get_table(&flow.base_table.semantic_table));
+                if let Some(source) = source {
+                    self.check(
+                        source.data_source == table.data_source,
+                        "query",
+                        format!(
+                            "query flow {} reads data source {}, not {}",
+                            query.flow, source.data_source, table.data_source
+                        ),
+                    )?;
+                }
+                SqlBuilder::default().build_for_request(
+                    registry,
+                    &self.connections,
+                    query,
+                    &QueryContext::default(),
+                )?
+            }
+            (None, None, Some(union)) => return self.union_schema(table, union, registry).await,
+            (None, None, None) => {
+                return self.ensure_schema(&table.data_source, &table.table).await
+            }
+        };
+
+        self.probe_schema(&table.data_source, &sql).await
+    }
+
+    /// Schema of the rows `sql` returns, read from an empty result.
+    async fn probe_schema(&self, data_source: &str, sql: &str) -> Result<TableSchema> {
+        let provider =
+            self.connections
+                .get(data_source)
+                .ok_or_else(|| SemaflowError::UnknownDataSource {
+                    data_source: data_source.to_string(),
+                })?;
+        let dialect = provider.dialect();
+        let probe = format!(
+            "SELECT * FROM ({}) {}{}",
+            sql.trim(),
+            dialect.quote_ident("derived"),
+            dialect.capabilities().limit.render(Some(0), None)
+        );
+        let result = provider.execute_sql(&probe).await?;
+        Ok(TableSchema {
+            columns: result
+                .columns
+                .into_iter()
+                .map(|column| ColumnSchema {
+                    name: column.name,
+                    data_type: column.data_type.unwrap_or_default(),
+                    nullable: column.nullable.unwrap_or(true),
+                })
+                .collect(),
+            primary_keys: Vec::new(),
+            foreign_keys: Vec::new(),
+            unique_keys: Vec::new(),
+        })
+    }
+
+    /// Schema of a union table, after checking that every member has each
+    /// union column (or maps it) and warning when members disagree on a
+    /// column's type.
+    async fn union_schema(
+        &self,
+        table: &SemanticTable,
+        union: &TableUnion,
+        registry: &FlowRegistry,
+    ) -> Result<TableSchema> {
+        // Column -> (member, type) of the first member declaring a type
+        let mut types: std::collections::HashMap<&str, (&str, String)> =
+            std::collections::HashMap::new();
+        for member in &union.tables {
+            let Some(source) = registry.get_table(&member.semantic_table) else {
+                continue;
+            };
+            if source.query.is_some() || source.union.is_some() {
+                continue;
+            }
+            let schema = match &source.sql {
+                Some(sql) => self.probe_schema(&source.data_source, sql).await?,
+                None => {
+                    self.ensure_schema(&source.data_source, &source.table)
+                        .await?
+                }
+            };
+            for column in &union.columns {
+                let Some(source_column) = member.source_column(column) else {
+                    continue;
+                };
+                let found = schema.columns.iter().find(|c| c.name == source_column);
+                self.check(
+                    found.is_some(),
+                    column,
+                    format!(
+                        "union table {} column {column} is missing from member {}; map it \
+                         under the member's columns, or to null",
+                        table.name, source.name
+                    ),
+                )?;
+                let Some(found) = found.filter(|c| !c.data_type.is_empty()) else {
+                    continue;
+                };
+                match types.get(column.as_str()) {
+                    Some((first, data_type))
+                        if !data_type.eq_ignore_ascii_case(&found.data_type) =>
+                    {
+                        self.warn(
+                            column,
+                            format!(
+                                "union table {} column {column} is {data_type} in {first} but {} \
+                                 in {}",
+                                table.name, found.data_type, source.name
+                            ),
+                        );
+                    }
+                    Some(_) => {}
+                    None => {
+                        types.insert(column, (&source.name, found.data_type.clone()));
+                    }
+                }
+            }
+        }
+
+        let provider = self.connections.get(&table.data_source).ok_or_else(|| {
+            SemaflowError::UnknownDataSource {
+                data_source: table.data_source.clone(),
+            }
+        })?;
+        let sql = union_sql(registry, &table.name, union, provider.dialect())?;
+        self.probe_schema(&table.data_source, &sql).await
+    }
+
+    /// Checks of a union table that need no schema: its members exist on
+    /// the same data source, are not derived from queries or unions
+    /// themselves, and only map columns of the union.
+    fn validate_union(
+        &self,
+        table: &SemanticTable,
+        union: &TableUnion,
+        registry: &FlowRegistry,
+    ) -> Result<()> {
+        self.check(
+            !union.columns.is_empty() && !union.tables.is_empty(),
+            "union",
+            format!(
+                "union table {} needs columns and at least one member table",
+                table.name
+            ),
+        )?;
+        let mut seen = HashSet::new();
+        for column in &union.columns {
+            self.check(
+                seen.insert(column),
+                column,
+                format!("union table {} lists column {column} twice", table.name),
+            )?;
+        }
+        for member in &union.tables {
+            let Some(source) = registry.get_table(&member.semantic_table) else {
+                self.check(
+                    false,
+                    &member.semantic_table,
+                    format!(
+                        "union table {} appends unknown table {}",
+                        table.name, member.semantic_table
+                    ),
+                )?;
+                continue;
+            };
+            self.check(
+                source.data_source == table.data_source,
+                &member.semantic_table,
+                format!(
+                    "union table {} appends {} from data source {}, not {}",
+                    table.name, source.name, source.data_source, table.data_source
+                ),
+            )?;
+            self.check(
+                source.query.is_none() && source.union.is_none(),
+                &member.semantic_table,
+                format!(
+                    "union table {} appends {}, which reads a query or union; members must be \
+                     physical or sql tables",
+                    table.name, source.name
+                ),
+            )?;
+            for column in member.columns.keys() {
+                self.check(
+                    union.columns.contains(column),
+                    column,
+                    format!(
+                        "union table {} maps column {column} of member {}, which is not a \
+                         union column",
+                        table.name, source.name
+                    ),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     fn validate_table(&self, table: &SemanticTable, schema: TableSchema) -> Result<()> {
         let column_names: HashSet<_> = schema.columns.iter().map(|c| c.name.clone()).collect();
 
         for pk in &table.primary_keys {
             self.check(
                 column_names.contains(pk),
+                pk,
                 format!("primary key column {} missing on table {}", pk, table.name),
             )?;
         }
 
+        for key in &table.unique_keys {
+            self.check(
+                !key.is_empty(),
+                &table.name,
+                format!("unique key on table {} has no columns", table.name),
+            )?;
+            for col in key {
+                self.check(
+                    column_names.contains(col),
+                    col,
+                    format!("unique key column {col} missing on table {}", table.name),
+                )?;
+            }
+        }
+
+        for col in table.nullable.keys() {
+            self.check(
+                column_names.contains(col),
+                col,
+                format!(
+                    "nullability declared for missing column {col} on table {}",
+                    table.name
+                ),
+            )?;
+        }
+
         for (name, dim) in &table.dimensions {
             // Walk the entire expression tree to validate all column references
             let mut col_refs = Vec::new();
@@ -103,9 +596,106 @@ impl Validator {
             for col in col_refs {
                 self.check(
                     column_names.contains(&col),
+                    name,
                     format!("dimension {name} references missing column {col}"),
                 )?;
             }
+            if let Some(smallest) = &table.smallest_time_grain {
+                let mut grains = Vec::new();
+                collect_time_grains(&dim.expr, &mut grains);
+                for grain in grains {
+                    self.check(
+                        grain >= *smallest,
+                        name,
+                        format!(
+                            "dimension {name} truncates to '{}', finer than the smallest_time_grain \
+                             '{}' of table {}; use '{}' or coarser",
+                            grain_to_str(&grain),
+                            grain_to_str(smallest),
+                            table.name,
+                            grain_to_str(smallest)
+                        ),
+                    )?;
+                }
+            }
+        }
+
+        for filter in &table.default_filters {
+            let mut col_refs = Vec::new();
+            collect_column_refs(filter, &mut col_refs);
+            for col in col_refs {
+                self.check(
+                    column_names.contains(&col),
+                    &col,
+                    format!(
+                        "default filter on table {} references missing column {col}",
+                        table.name
+                    ),
+                )?;
+            }
+        }
+
+        for (segment, expr) in &table.segments {
+            let mut col_refs = Vec::new();
+            collect_column_refs(expr, &mut col_refs);
+            for col in col_refs {
+                self.check(
+                    column_names.contains(&col),
+                    segment,
+                    format!(
+                        "segment {segment} on table {} references missing column {col}",
+                        table.name
+                    ),
+                )?;
+            }
+        }
+
+        for (attribute, expr) in &table.access_policies {
+            let mut col_refs = Vec::new();
+            collect_column_refs(expr, &mut col_refs);
+            for col in col_refs {
+                self.check(
+                    column_names.contains(&col),
+                    attribute,
+                    format!(
+                        "access policy {attribute} on table {} references missing column {col}",
+                        table.name
+                    ),
+                )?;
+            }
+        }
+
+        for (hierarchy, levels) in &table.hierarchies {
+            self.check(
+                !levels.is_empty(),
+                hierarchy,
+                format!(
+                    "hierarchy {hierarchy} on table {} has no levels",
+                    table.name
+                ),
+            )?;
+            for level in levels {
+                self.check(
+                    table.dimensions.contains_key(level),
+                    hierarchy,
+                    format!(
+                        "hierarchy {hierarchy} on table {} references unknown dimension {level}",
+                        table.name
+                    ),
+                )?;
+            }
+        }
+
+        for (entity, spec) in &table.entities {
+            let column = spec.column(entity);
+            self.check(
+                column_names.contains(column),
+                entity,
+                format!(
+                    "entity {entity} on table {} references missing column {column}",
+                    table.name
+                ),
+            )?;
         }
 
         // Collect all measure names to identify measure refs in formulas
@@ -126,11 +716,24 @@ impl Validator {
                 for col in col_refs {
                     self.check(
                         column_names.contains(&col),
+                        name,
                         format!("measure {name} references missing column {col}"),
                     )?;
                 }
             }
 
+            // first/last order by a column of the measure's own table
+            if let Some(Aggregation::First { order_by, .. } | Aggregation::Last { order_by, .. }) =
+                &measure.agg
+            {
+                let column = order_by.rsplit('.').next().unwrap_or(order_by);
+                self.check(
+                    column_names.contains(column),
+                    name,
+                    format!("measure {name} orders by missing column {order_by}"),
+                )?;
+            }
+
             // Validate filter expression if present
             if let Some(filter) = &measure.filter {
                 let mut col_refs = Vec::new();
@@ -138,6 +741,7 @@ impl Validator {
                 for col in col_refs {
                     self.check(
                         column_names.contains(&col),
+                        name,
                         format!("measure {name} filter references missing column {col}"),
                     )?;
                 }
@@ -167,6 +771,7 @@ impl Validator {
         if let Some(time_dim) = &table.time_dimension {
             self.check(
                 column_names.contains(time_dim),
+                time_dim,
                 format!("time_dimension {time_dim} missing in table {}", table.name),
             )?;
         }
@@ -184,6 +789,7 @@ impl Validator {
                     })?;
                     self.check(
                         dep.post_expr.is_none(),
+                        name,
                         format!("derived measure {name} cannot reference derived measure {r}"),
                     )?;
                 }
@@ -194,6 +800,7 @@ impl Validator {
                 for col in col_refs {
                     self.check(
                         column_names.contains(&col),
+                        name,
                         format!("measure {name} post_expr references missing column {col}"),
                     )?;
                 }
@@ -215,6 +822,34 @@ impl Validator {
 
         let base_ds = &base_table.data_source;
 
+        let read = std::iter::once(&flow.base_table.semantic_table)
+            .chain(flow.joins.values().map(|join| &join.semantic_table));
+        for table in read.filter_map(|name| registry.get_table(name)) {
+            if let Some(union) = &table.union {
+                self.validate_union(table, union, registry)?;
+            }
+        }
+
+        // Checked first: a join attaching to one declared after it in a cycle
+        // would otherwise be reported as targeting an unknown alias
+        for (join_name, join) in &flow.joins {
+            self.check(
+                join.to_table != join.alias,
+                join_name,
+                format!(
+                    "join {join_name} attaches to itself: to_table {} is its own alias",
+                    join.to_table
+                ),
+            )?;
+        }
+        // Self-references come back as `[alias, alias]` and are reported above
+        if let Some(path) = find_join_cycle(flow).filter(|path| path.len() > 2) {
+            return Err(SemaflowError::JoinCycle {
+                flow: flow.name.clone(),
+                path,
+            });
+        }
+
         let mut aliases = HashSet::new();
         aliases.insert(flow.base_table.alias.clone());
         let mut alias_to_table = std::collections::HashMap::new();
@@ -223,8 +858,21 @@ impl Validator {
         for (join_name, join) in &flow.joins {
             self.check(
                 aliases.insert(join.alias.clone()),
+                join_name,
                 format!("duplicate alias {} in join {join_name}", join.alias),
             )?;
+            if let Some(role) = &join.role {
+                let taken = *role == flow.base_table.alias
+                    || flow.joins.values().any(|other| {
+                        other.alias == *role
+                            || (other.alias != join.alias && other.role.as_ref() == Some(role))
+                    });
+                self.check(
+                    !taken,
+                    join_name,
+                    format!("join {join_name} role {role} is already used as an alias or role"),
+                )?;
+            }
             let join_table = registry.get_table(&join.semantic_table).ok_or_else(|| {
                 SemaflowError::Validation(format!(
                     "join {} references missing table {}",
@@ -234,11 +882,13 @@ impl Validator {
 
             self.check(
                 aliases.contains(&join.to_table),
+                join_name,
                 format!("join {join_name} targets unknown alias {}", join.to_table),
             )?;
 
             self.check(
                 join_table.data_source == *base_ds,
+                join_name,
                 format!(
                     "join {join_name} mixes data sources ({}) with base ({})",
                     join_table.data_source, base_ds
@@ -249,9 +899,42 @@ impl Validator {
         }
 
         for (join_name, join) in &flow.joins {
+            if let Some(bridge) = &join.through {
+                self.check(
+                    join.to_table == *bridge,
+                    join_name,
+                    format!(
+                        "join {join_name} goes through {bridge} but attaches to {}",
+                        join.to_table
+                    ),
+                )?;
+                if let (Some(weight), Some(bridge_table)) =
+                    (&join.weight, alias_to_table.get(bridge))
+                {
+                    self.check(
+                        table_has_column(bridge_table, weight),
+                        join_name,
+                        format!(
+                            "join {join_name} weight {weight} not found on bridge table {}",
+                            bridge_table.name
+                        ),
+                    )?;
+                }
+            } else {
+                self.check(
+                    join.weight.is_none(),
+                    join_name,
+                    format!("join {join_name} has a weight but no bridge to go through"),
+                )?;
+            }
+
             self.check(
                 !join.join_keys.is_empty(),
-                format!("join {join_name} must include at least one join key"),
+                join_name,
+                format!(
+                    "join {join_name} must include at least one join key or share exactly one entity with {}",
+                    join.to_table
+                ),
             )?;
 
             let right_table = alias_to_table.get(&join.alias).ok_or_else(|| {
@@ -265,8 +948,21 @@ impl Validator {
             })?;
 
             for key in &join.join_keys {
+                if join.join_type == JoinType::Inner
+                    && left_table.nullable.get(&key.left) == Some(&true)
+                {
+                    self.warn(
+                        join_name,
+                        format!(
+                            "inner join {join_name} is keyed on nullable column {} of table {}; \
+                             rows where it is NULL are dropped",
+                            key.left, left_table.name
+                        ),
+                    );
+                }
                 self.check(
                     table_has_column(left_table, &key.left),
+                    join_name,
                     format!(
                         "join {join_name} left key {} not found on table {}",
                         key.left, left_table.name
@@ -274,12 +970,184 @@ impl Validator {
                 )?;
                 self.check(
                     table_has_column(right_table, &key.right),
+                    join_name,
                     format!(
                         "join {join_name} right key {} not found on table {}",
                         key.right, right_table.name
                     ),
                 )?;
             }
+
+            if let Some(condition) = &join.extra_conditions {
+                // The ON clause can only read this table and those it attaches through
+                let mut reachable = vec![join.alias.as_str()];
+                let mut current = join.to_table.as_str();
+                while !reachable.contains(&current) {
+                    reachable.push(current);
+                    match flow.joins.values().find(|j| j.alias == current) {
+                        Some(parent) => current = &parent.to_table,
+                        None => break,
+                    }
+                }
+                let mut col_refs = Vec::new();
+                collect_column_refs(condition, &mut col_refs);
+                for col in col_refs {
+                    let Some((qualifier, _)) = col.split_once('.') else {
+                        continue;
+                    };
+                    self.check(
+                        reachable.contains(&qualifier),
+                        join_name,
+                        format!(
+                            "join {join_name} extra_conditions reference {col}, but {qualifier} \
+                             is not {} or a table it joins through",
+                            join.alias
+                        ),
+                    )?;
+                }
+                let mut measure_refs = Vec::new();
+                collect_measure_refs(condition, &mut measure_refs);
+                self.check(
+                    measure_refs.is_empty(),
+                    join_name,
+                    format!("join {join_name} extra_conditions cannot reference measures"),
+                )?;
+            }
+        }
+
+        if let Some(path) = find_derived_cycle(registry, &flow.name) {
+            self.check(
+                false,
+                &flow.base_table.semantic_table,
+                format!(
+                    "derived tables form a cycle between flows: {}",
+                    path.join(" -> ")
+                ),
+            )?;
+        }
+
+        for (name, param) in &flow.params {
+            if let Some(default) = &param.default {
+                if let Err(e) = param.check(default) {
+                    self.check(
+                        false,
+                        name,
+                        format!("flow {} parameter {name} default: {e}", flow.name),
+                    )?;
+                }
+            }
+        }
+
+        let mut param_refs = Vec::new();
+        for table in alias_to_table.values() {
+            for dimension in table.dimensions.values() {
+                collect_param_refs(&dimension.expr, &mut param_refs);
+            }
+            for measure in table.measures.values() {
+                for expr in [&measure.expr, &measure.filter, &measure.post_expr]
+                    .into_iter()
+                    .flatten()
+                {
+                    collect_param_refs(expr, &mut param_refs);
+                }
+            }
+            for filter in table.default_filters.iter().chain(table.segments.values()) {
+                collect_param_refs(filter, &mut param_refs);
+            }
+        }
+        for name in param_refs {
+            self.check(
+                flow.params.contains_key(&name),
+                &name,
+                format!("flow {} references undeclared parameter {name}", flow.name),
+            )?;
+        }
+
+        for name in &flow.limits.require_filter_on {
+            let known = flow_has_field(flow, &alias_to_table, name, |table, field| {
+                table.dimensions.contains_key(field)
+                    || table.time_dimension.as_deref() == Some(field)
+            });
+            self.check(
+                known,
+                name,
+                format!(
+                    "flow {} requires a filter on unknown dimension {name}",
+                    flow.name
+                ),
+            )?;
+        }
+        let mut filtered = Vec::new();
+        for filter in flow.default_filters.iter().chain(flow.segments.values()) {
+            filter_fields(filter, &mut filtered);
+        }
+        for name in filtered {
+            let known = flow_has_field(flow, &alias_to_table, name, |table, field| {
+                table.dimensions.contains_key(field)
+                    || table.time_dimension.as_deref() == Some(field)
+            });
+            self.check(
+                known,
+                name,
+                format!(
+                    "flow {} default filter or segment references unknown dimension {name}",
+                    flow.name
+                ),
+            )?;
+        }
+        for (name, aggregate) in &flow.aggregates {
+            for dimension in aggregate.dimensions.keys() {
+                self.check(
+                    flow_has_field(flow, &alias_to_table, dimension, |table, field| {
+                        table.dimensions.contains_key(field)
+                    }),
+                    name,
+                    format!(
+                        "flow {} aggregate {name} maps unknown dimension {dimension}",
+                        flow.name
+                    ),
+                )?;
+            }
+            for measure in aggregate.measures.keys() {
+                self.check(
+                    flow_has_field(flow, &alias_to_table, measure, |table, field| {
+                        table.measures.contains_key(field)
+                    }),
+                    name,
+                    format!(
+                        "flow {} aggregate {name} maps unknown measure {measure}",
+                        flow.name
+                    ),
+                )?;
+            }
+            self.check(
+                aggregate.time_column.is_some() == aggregate.grain.is_some(),
+                name,
+                format!(
+                    "flow {} aggregate {name} must set time_column and grain together",
+                    flow.name
+                ),
+            )?;
+        }
+        if flow.limits.max_time_range_days.is_some() {
+            self.check(
+                base_table.time_dimension.is_some(),
+                &flow.name,
+                format!(
+                    "flow {} sets max_time_range_days but base table {} has no time_dimension",
+                    flow.name, base_table.name
+                ),
+            )?;
+        }
+        if let Some(calendar) = &flow.fiscal_calendar {
+            self.check(
+                (1..=12).contains(&calendar.start_month),
+                &flow.name,
+                format!(
+                    "flow {} fiscal_calendar start_month must be between 1 and 12, got {}",
+                    flow.name, calendar.start_month
+                ),
+            )?;
         }
         Ok(())
     }
@@ -300,6 +1168,7 @@ impl Validator {
                 let col_name = column.split('.').next_back().unwrap_or(column);
                 self.check(
                     column_names.contains(col_name),
+                    measure_name,
                     format!(
                         "Measure '{}' formula references unknown column '{}'.",
                         measure_name, column
@@ -322,6 +1191,7 @@ impl Validator {
                 // Explicit measure reference - validate it exists and is simple
                 self.check(
                     all_measures.contains(name),
+                    measure_name,
                     format!(
                         "Measure '{}' formula references unknown measure '{}'.\n\
                          Available measures: {:?}",
@@ -333,6 +1203,7 @@ impl Validator {
 
                 self.check(
                     name != measure_name,
+                    measure_name,
                     format!(
                         "Measure '{}' formula cannot reference itself - this would create infinite recursion.",
                         measure_name
@@ -341,6 +1212,7 @@ impl Validator {
 
                 self.check(
                     !formula_measures.contains(name),
+                    measure_name,
                     format!(
                         "Measure '{}' formula references '{}', which is also a formula measure.\n\
                          Formula measures can only reference simple measures (those with 'expr' + 'agg').",
@@ -359,6 +1231,7 @@ impl Validator {
                     // It's a measure reference
                     self.check(
                         column != measure_name,
+                        measure_name,
                         format!(
                             "Measure '{}' formula cannot reference itself - this would create infinite recursion.",
                             measure_name
@@ -367,6 +1240,7 @@ impl Validator {
 
                     self.check(
                         !formula_measures.contains(column),
+                        measure_name,
                         format!(
                             "Measure '{}' formula references '{}', which is also a formula measure.\n\
                              Formula measures can only reference simple measures (those with 'expr' + 'agg').",
@@ -377,6 +1251,7 @@ impl Validator {
                     // It's a column reference - validate it exists
                     self.check(
                         column_names.contains(col_name),
+                        measure_name,
                         format!(
                             "Measure '{}' formula references unknown column or measure '{}'.\n\
                              Not found in table columns or measure names.",
@@ -407,6 +1282,61 @@ impl Validator {
                 )?;
             }
 
+            FormulaAst::InList { expr, list, .. } => {
+                for node in std::iter::once(&**expr).chain(list) {
+                    self.validate_formula_ast(
+                        measure_name,
+                        node,
+                        all_measures,
+                        formula_measures,
+                        column_names,
+                    )?;
+                }
+            }
+
+            FormulaAst::Between {
+                expr, low, high, ..
+            } => {
+                for node in [expr, low, high] {
+                    self.validate_formula_ast(
+                        measure_name,
+                        node,
+                        all_measures,
+                        formula_measures,
+                        column_names,
+                    )?;
+                }
+            }
+
+            FormulaAst::IsNull { expr, .. } | FormulaAst::Not { expr } => {
+                self.validate_formula_ast(
+                    measure_name,
+                    expr,
+                    all_measures,
+                    formula_measures,
+                    column_names,
+                )?;
+            }
+
+            FormulaAst::Case {
+                branches,
+                else_expr,
+            } => {
+                let nodes = branches
+                    .iter()
+                    .flat_map(|(when, then)| [when, then])
+                    .chain(else_expr.as_deref());
+                for node in nodes {
+                    self.validate_formula_ast(
+                        measure_name,
+                        node,
+                        all_measures,
+                        formula_measures,
+                        column_names,
+                    )?;
+                }
+            }
+
             FormulaAst::Function { args, .. } => {
                 for arg in args {
                     self.validate_formula_ast(
@@ -423,17 +1353,74 @@ impl Validator {
         Ok(())
     }
 
-    fn check(&self, condition: bool, message: String) -> Result<()> {
+    fn check(&self, condition: bool, field: &str, message: String) -> Result<()> {
         if condition {
             return Ok(());
         }
-        if self.warn_only {
+        let severity = if self.warn_only {
+            Severity::Warning
+        } else {
+            Severity::Error
+        };
+        if self.record(severity, Some(field), message.clone()) {
+            Ok(())
+        } else if self.warn_only {
             eprintln!("[warn] {}", message);
             Ok(())
         } else {
             Err(SemaflowError::Validation(message))
         }
     }
+
+    /// Report a likely mistake that never fails validation.
+    fn warn(&self, field: &str, message: String) {
+        if !self.record(Severity::Warning, Some(field), message.clone()) {
+            eprintln!("[warn] {}", message);
+        }
+    }
+}
+
+/// Fill in the unique keys and nullability `table` leaves undeclared from
+/// its physical `schema`.
+fn seed_key_hints(table: &mut SemanticTable, schema: &TableSchema) {
+    if table.unique_keys.is_empty() {
+        table.unique_keys = schema.unique_keys.clone();
+    }
+    for column in &schema.columns {
+        table
+            .nullable
+            .entry(column.name.clone())
+            .or_insert(column.nullable);
+    }
+}
+
+/// Whether `has` finds `key`, an `alias.field` (or `role.field`) of the flow
+/// or a bare field of any of its tables.
+fn flow_has_field(
+    flow: &SemanticFlow,
+    alias_to_table: &std::collections::HashMap<String, &SemanticTable>,
+    key: &str,
+    has: impl Fn(&SemanticTable, &str) -> bool,
+) -> bool {
+    match key.split_once('.') {
+        Some((qualifier, field)) => alias_to_table
+            .get(flow.role_alias(qualifier))
+            .is_some_and(|table| has(table, field)),
+        None => alias_to_table.values().any(|table| has(table, key)),
+    }
+}
+
+/// Fields of the leaf conditions in `filter`.
+fn filter_fields<'a>(filter: &'a FilterExpr, out: &mut Vec<&'a str>) {
+    match filter {
+        FilterExpr::Condition(condition) => out.push(&condition.field),
+        FilterExpr::All { all: items } | FilterExpr::Any { any: items } => {
+            for item in items {
+                filter_fields(item, out);
+            }
+        }
+        FilterExpr::Not { not } => filter_fields(not, out),
+    }
 }
 
 fn table_has_column(table: &SemanticTable, col: &str) -> bool {
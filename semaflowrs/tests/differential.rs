@@ -0,0 +1,566 @@
+//! Differential tests of the planner: random data, flows and requests,
+//! executed on DuckDB with the `pre_aggregate` and `symmetric` fanout
+//! strategies and compared with results computed in memory. Each flow joins
+//! a random subset of one many-to-one and two one-to-many tables to its
+//! base table, so cases cover flat, single and double fanout topologies.
+//!
+//! Requests without fanout plan flat under both strategies; requests with
+//! fanout plan pre-aggregated CTEs or symmetric aggregates, so every
+//! strategy is checked against the same reference.
+//!
+//! Run with `cargo test --features differential-tests --test differential`.
+//! `SEMAFLOW_DIFF_SEED` and `SEMAFLOW_DIFF_CASES` pick the first seed and
+//! the number of cases; failures print the seed reproducing them.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use semaflow::backends::{BackendConnection, DuckDbConnection};
+use semaflow::flows::{SemanticFlow, SemanticTable};
+use semaflow::registry::FlowRegistry;
+use semaflow::{DuckDbDialect, QueryRequest, QueryResult, SqlBuilder};
+use serde_json::{json, Value};
+
+const REGIONS: [&str; 3] = ["north", "south", "east"];
+const COUNTRIES: [&str; 3] = ["US", "UK", "DE"];
+const TIERS: [&str; 2] = ["gold", "silver"];
+const DIMENSIONS: [&str; 3] = ["region", "country", "tier"];
+/// Tables a flow may join to `orders`, by join name.
+const JOINS: [&str; 3] = ["customers", "items", "payments"];
+
+/// Measures of the flow, by the table they aggregate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MeasureDef {
+    OrderTotal,
+    OrderCount,
+    AvgAmount,
+    MaxAmount,
+    DistinctCustomers,
+    ItemQty,
+    ItemCount,
+    PaidTotal,
+    MaxPaid,
+}
+
+const MEASURES: [MeasureDef; 9] = [
+    MeasureDef::OrderTotal,
+    MeasureDef::OrderCount,
+    MeasureDef::AvgAmount,
+    MeasureDef::MaxAmount,
+    MeasureDef::DistinctCustomers,
+    MeasureDef::ItemQty,
+    MeasureDef::ItemCount,
+    MeasureDef::PaidTotal,
+    MeasureDef::MaxPaid,
+];
+
+impl MeasureDef {
+    fn name(self) -> &'static str {
+        match self {
+            MeasureDef::OrderTotal => "order_total",
+            MeasureDef::OrderCount => "order_count",
+            MeasureDef::AvgAmount => "avg_amount",
+            MeasureDef::MaxAmount => "max_amount",
+            MeasureDef::DistinctCustomers => "distinct_customers",
+            MeasureDef::ItemQty => "item_qty",
+            MeasureDef::ItemCount => "item_count",
+            MeasureDef::PaidTotal => "paid_total",
+            MeasureDef::MaxPaid => "max_paid",
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            MeasureDef::ItemQty | MeasureDef::ItemCount => "items",
+            MeasureDef::PaidTotal | MeasureDef::MaxPaid => "payments",
+            _ => "orders",
+        }
+    }
+
+    /// Counts of no rows may come back as 0 or NULL depending on the plan.
+    fn is_count(self) -> bool {
+        matches!(
+            self,
+            MeasureDef::OrderCount | MeasureDef::DistinctCustomers | MeasureDef::ItemCount
+        )
+    }
+}
+
+/// xorshift64*: small, seedable and good enough for test data.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+
+    /// A random subset of `items` of `min..=max` elements, in their order.
+    fn subset<T: Copy>(&mut self, items: &[T], min: usize, max: usize) -> Vec<T> {
+        let count = min + self.below(max - min + 1);
+        let mut chosen: BTreeSet<usize> = BTreeSet::new();
+        while chosen.len() < count.min(items.len()) {
+            chosen.insert(self.below(items.len()));
+        }
+        chosen.into_iter().map(|i| items[i]).collect()
+    }
+}
+
+struct Customer {
+    id: i64,
+    country: &'static str,
+    tier: &'static str,
+}
+
+struct Order {
+    id: i64,
+    customer_id: Option<i64>,
+    region: Option<&'static str>,
+    amount: Option<f64>,
+}
+
+/// An item or payment: a value attached to an order.
+struct Child {
+    id: i64,
+    order_id: i64,
+    value: f64,
+}
+
+struct Data {
+    customers: Vec<Customer>,
+    orders: Vec<Order>,
+    items: Vec<Child>,
+    payments: Vec<Child>,
+}
+
+impl Data {
+    /// Some orders reference missing customers, and some items and payments
+    /// missing orders, so LEFT joins leave gaps.
+    fn generate(rng: &mut Rng) -> Self {
+        let customers: Vec<Customer> = (1..=rng.below(8) as i64)
+            .map(|id| Customer {
+                id,
+                country: rng.pick(&COUNTRIES),
+                tier: rng.pick(&TIERS),
+            })
+            .collect();
+        let orders: Vec<Order> = (1..=rng.below(30) as i64)
+            .map(|id| Order {
+                id,
+                customer_id: (!rng.chance(10)).then(|| 1 + rng.below(customers.len() + 2) as i64),
+                region: (!rng.chance(10)).then(|| rng.pick(&REGIONS)),
+                amount: (!rng.chance(10)).then(|| rng.below(200) as f64 / 2.0),
+            })
+            .collect();
+        let mut children = |max: usize, scale: usize| -> Vec<Child> {
+            (1..=rng.below(max) as i64)
+                .map(|id| Child {
+                    id,
+                    order_id: 1 + rng.below(orders.len() + 2) as i64,
+                    value: (1 + rng.below(scale)) as f64,
+                })
+                .collect()
+        };
+        let items = children(60, 5);
+        let payments = children(40, 100);
+        Data {
+            customers,
+            orders,
+            items,
+            payments,
+        }
+    }
+
+    fn load(&self, db_path: &Path) -> anyhow::Result<()> {
+        let text = |v: Option<&str>| v.map_or("NULL".to_string(), |v| format!("'{v}'"));
+        let number = |v: Option<f64>| v.map_or("NULL".to_string(), |v| v.to_string());
+        let int = |v: Option<i64>| v.map_or("NULL".to_string(), |v| v.to_string());
+        let mut sql = String::from(
+            "CREATE TABLE customers (id INTEGER PRIMARY KEY, country VARCHAR, tier VARCHAR);
+             CREATE TABLE orders (id INTEGER PRIMARY KEY, customer_id INTEGER, region VARCHAR, amount DOUBLE);
+             CREATE TABLE items (id INTEGER PRIMARY KEY, order_id INTEGER, qty INTEGER);
+             CREATE TABLE payments (id INTEGER PRIMARY KEY, order_id INTEGER, paid DOUBLE);\n",
+        );
+        for c in &self.customers {
+            sql += &format!(
+                "INSERT INTO customers VALUES ({}, '{}', '{}');\n",
+                c.id, c.country, c.tier
+            );
+        }
+        for o in &self.orders {
+            sql += &format!(
+                "INSERT INTO orders VALUES ({}, {}, {}, {});\n",
+                o.id,
+                int(o.customer_id),
+                text(o.region),
+                number(o.amount)
+            );
+        }
+        for i in &self.items {
+            sql += &format!(
+                "INSERT INTO items VALUES ({}, {}, {});\n",
+                i.id, i.order_id, i.value as i64
+            );
+        }
+        for p in &self.payments {
+            sql += &format!(
+                "INSERT INTO payments VALUES ({}, {}, {});\n",
+                p.id, p.order_id, p.value
+            );
+        }
+        duckdb::Connection::open(db_path)?.execute_batch(&sql)?;
+        Ok(())
+    }
+
+    fn customer(&self, order: &Order) -> Option<&Customer> {
+        let id = order.customer_id?;
+        self.customers.iter().find(|c| c.id == id)
+    }
+
+    fn dimension(&self, order: &Order, name: &str) -> Option<String> {
+        match name {
+            "region" => order.region.map(str::to_string),
+            "country" => self.customer(order).map(|c| c.country.to_string()),
+            "tier" => self.customer(order).map(|c| c.tier.to_string()),
+            other => panic!("unknown dimension {other}"),
+        }
+    }
+}
+
+/// The joins of a flow over `orders`.
+#[derive(Debug)]
+struct Topology {
+    joins: Vec<&'static str>,
+}
+
+impl Topology {
+    fn generate(rng: &mut Rng) -> Self {
+        Topology {
+            joins: rng.subset(&JOINS, 0, JOINS.len()),
+        }
+    }
+
+    fn has(&self, table: &str) -> bool {
+        table == "orders" || self.joins.contains(&table)
+    }
+
+    fn dimensions(&self) -> Vec<&'static str> {
+        let table = |d: &str| if d == "region" { "orders" } else { "customers" };
+        DIMENSIONS
+            .into_iter()
+            .filter(|d| self.has(table(d)))
+            .collect()
+    }
+
+    fn measures(&self) -> Vec<MeasureDef> {
+        MEASURES
+            .into_iter()
+            .filter(|m| self.has(m.table()))
+            .collect()
+    }
+}
+
+/// Result rows: dimension values and measure values.
+type Raw = Vec<(Vec<Option<String>>, Vec<Option<f64>>)>;
+
+/// Result rows keyed by dimension values, with measure values scaled to
+/// millionths so float noise compares equal.
+type Rows = BTreeMap<Vec<Option<String>>, Vec<Option<i64>>>;
+
+struct Case {
+    dimensions: Vec<&'static str>,
+    measures: Vec<MeasureDef>,
+    filter: Option<(&'static str, &'static str)>,
+}
+
+impl Case {
+    /// A request for fields of the tables `topology` joins.
+    fn generate(rng: &mut Rng, topology: &Topology) -> Self {
+        let filter = rng.chance(30).then(|| {
+            if !topology.has("customers") || rng.chance(50) {
+                ("region", rng.pick(&REGIONS))
+            } else {
+                ("country", rng.pick(&COUNTRIES))
+            }
+        });
+        Case {
+            dimensions: rng.subset(&topology.dimensions(), 0, 2),
+            measures: rng.subset(&topology.measures(), 1, 3),
+            filter,
+        }
+    }
+
+    fn request(&self) -> QueryRequest {
+        let filters: Vec<Value> = self
+            .filter
+            .iter()
+            .map(|(field, value)| json!({"field": field, "op": "==", "value": value}))
+            .collect();
+        serde_json::from_value(json!({
+            "flow": "orders",
+            "dimensions": self.dimensions,
+            "measures": self.measures.iter().map(|m| m.name()).collect::<Vec<_>>(),
+            "filters": filters,
+        }))
+        .expect("request")
+    }
+
+    /// The request's answer: each measure aggregates its own table's rows,
+    /// grouped by the dimensions of the order they belong to.
+    fn expected(&self, data: &Data) -> Raw {
+        #[derive(Default)]
+        struct Group {
+            amounts: Vec<f64>,
+            orders: usize,
+            customers: BTreeSet<i64>,
+            items: Vec<f64>,
+            payments: Vec<f64>,
+        }
+        let key = |order: &Order| -> Vec<Option<String>> {
+            self.dimensions
+                .iter()
+                .map(|d| data.dimension(order, d))
+                .collect()
+        };
+        let visible = |order: &Order| match self.filter {
+            Some((field, value)) => data.dimension(order, field).as_deref() == Some(value),
+            None => true,
+        };
+        let mut groups: BTreeMap<Vec<Option<String>>, Group> = BTreeMap::new();
+        for order in data.orders.iter().filter(|o| visible(o)) {
+            let group = groups.entry(key(order)).or_default();
+            group.orders += 1;
+            group.amounts.extend(order.amount);
+            group.customers.extend(order.customer_id);
+        }
+        let parent = |child: &Child| {
+            data.orders
+                .iter()
+                .find(|o| o.id == child.order_id)
+                .filter(|o| visible(o))
+        };
+        for item in &data.items {
+            if let Some(order) = parent(item) {
+                groups.get_mut(&key(order)).unwrap().items.push(item.value);
+            }
+        }
+        for payment in &data.payments {
+            if let Some(order) = parent(payment) {
+                groups
+                    .get_mut(&key(order))
+                    .unwrap()
+                    .payments
+                    .push(payment.value);
+            }
+        }
+
+        let sum = |values: &[f64]| (!values.is_empty()).then(|| values.iter().sum::<f64>());
+        let max = |values: &[f64]| values.iter().copied().reduce(f64::max);
+        groups
+            .into_iter()
+            .map(|(key, g)| {
+                let values = self
+                    .measures
+                    .iter()
+                    .map(|m| match m {
+                        MeasureDef::OrderTotal => sum(&g.amounts),
+                        MeasureDef::OrderCount => Some(g.orders as f64),
+                        MeasureDef::AvgAmount => {
+                            sum(&g.amounts).map(|s| s / g.amounts.len() as f64)
+                        }
+                        MeasureDef::MaxAmount => max(&g.amounts),
+                        MeasureDef::DistinctCustomers => Some(g.customers.len() as f64),
+                        MeasureDef::ItemQty => sum(&g.items),
+                        MeasureDef::ItemCount => Some(g.items.len() as f64),
+                        MeasureDef::PaidTotal => sum(&g.payments),
+                        MeasureDef::MaxPaid => max(&g.payments),
+                    })
+                    .collect();
+                (key, values)
+            })
+            .collect()
+    }
+
+    fn actual(&self, result: &QueryResult) -> Raw {
+        result
+            .rows
+            .iter()
+            .map(|row| {
+                let key = self
+                    .dimensions
+                    .iter()
+                    .map(|d| row.get(*d).and_then(Value::as_str).map(str::to_string))
+                    .collect();
+                let values = self
+                    .measures
+                    .iter()
+                    .map(|m| row.get(m.name()).and_then(number))
+                    .collect();
+                (key, values)
+            })
+            .collect()
+    }
+
+    /// Scale values and drop groups whose measures are all empty: a plan
+    /// may or may not keep a group that only has rows in other tables.
+    fn normalize(&self, rows: Raw) -> Rows {
+        rows.into_iter()
+            .filter_map(|(key, values)| {
+                let values: Vec<Option<i64>> = values
+                    .into_iter()
+                    .zip(&self.measures)
+                    .map(|(value, m)| match value {
+                        Some(v) if m.is_count() && v == 0.0 => None,
+                        v => v.map(|v| (v * 1e6).round() as i64),
+                    })
+                    .collect();
+                values.iter().any(Option::is_some).then_some((key, values))
+            })
+            .collect()
+    }
+}
+
+/// Sums of integers can arrive as strings (HUGEINT).
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn registry(fanout_strategy: &str, topology: &Topology) -> anyhow::Result<FlowRegistry> {
+    let table = |value: Value| serde_json::from_value::<SemanticTable>(value);
+    let tables = vec![
+        table(json!({
+            "name": "customers", "data_source": "diff", "table": "customers",
+            "primary_key": "id",
+            "dimensions": {"country": "country", "tier": "tier"},
+        }))?,
+        table(json!({
+            "name": "orders", "data_source": "diff", "table": "orders",
+            "primary_key": "id",
+            "dimensions": {"region": "region"},
+            "measures": {
+                "order_total": {"expr": "amount", "agg": "sum"},
+                "order_count": {"expr": "id", "agg": "count"},
+                "avg_amount": {"expr": "amount", "agg": "avg"},
+                "max_amount": {"expr": "amount", "agg": "max"},
+                "distinct_customers": {"expr": "customer_id", "agg": "count_distinct"},
+            },
+        }))?,
+        table(json!({
+            "name": "items", "data_source": "diff", "table": "items",
+            "primary_key": "id",
+            "measures": {
+                "item_qty": {"expr": "qty", "agg": "sum"},
+                "item_count": {"expr": "id", "agg": "count"},
+            },
+        }))?,
+        table(json!({
+            "name": "payments", "data_source": "diff", "table": "payments",
+            "primary_key": "id",
+            "measures": {
+                "paid_total": {"expr": "paid", "agg": "sum"},
+                "max_paid": {"expr": "paid", "agg": "max"},
+            },
+        }))?,
+    ];
+    let joins: serde_json::Map<String, Value> = topology
+        .joins
+        .iter()
+        .map(|&table| {
+            let (alias, left, right) = match table {
+                "customers" => ("c", "customer_id", "id"),
+                "items" => ("i", "id", "order_id"),
+                _ => ("p", "id", "order_id"),
+            };
+            let join = json!({
+                "semantic_table": table, "alias": alias, "to_table": "o", "join_type": "left",
+                "join_keys": [{"left": left, "right": right}],
+            });
+            (table.to_string(), join)
+        })
+        .collect();
+    let flow: SemanticFlow = serde_json::from_value(json!({
+        "name": "orders",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+        "joins": joins,
+        "fanout_strategy": fanout_strategy,
+    }))?;
+    Ok(FlowRegistry::from_parts(tables, vec![flow]))
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::test]
+async fn strategies_agree_with_reference_results() -> anyhow::Result<()> {
+    let first_seed = env_u64("SEMAFLOW_DIFF_SEED", 0x5EED);
+    let cases = env_u64("SEMAFLOW_DIFF_CASES", 64);
+    let builder = SqlBuilder::default();
+
+    for seed in first_seed..first_seed + cases {
+        let mut rng = Rng::new(seed);
+        let data = Data::generate(&mut rng);
+        let dir = tempfile::tempdir()?;
+        let db_path = dir.path().join("diff.duckdb");
+        data.load(&db_path)?;
+        let connection = DuckDbConnection::new(&db_path);
+
+        let topology = Topology::generate(&mut rng);
+        let registries = [
+            ("pre_aggregate", registry("pre_aggregate", &topology)?),
+            ("symmetric", registry("symmetric", &topology)?),
+        ];
+        let case = Case::generate(&mut rng, &topology);
+        let request = case.request();
+        let expected = case.normalize(case.expected(&data));
+
+        let joins = &topology.joins;
+        for (strategy, registry) in &registries {
+            let explain = builder.explain_with_dialect(registry, &request, &DuckDbDialect)?;
+            let result = connection.execute_sql(&explain.sql).await?;
+            let rows = case.actual(&result);
+            let groups: BTreeSet<_> = rows.iter().map(|(key, _)| key).collect();
+            assert_eq!(
+                groups.len(),
+                rows.len(),
+                "seed {seed} ({strategy} flow joining {joins:?}): repeated groups\nsql: {}",
+                explain.sql
+            );
+            let actual = case.normalize(rows);
+            assert_eq!(
+                actual,
+                expected,
+                "seed {seed} ({strategy} flow joining {joins:?}, {:?} plan)\nrequest: {}\nsql: {}",
+                explain.strategy,
+                serde_json::to_string(&request)?,
+                explain.sql
+            );
+        }
+    }
+    Ok(())
+}
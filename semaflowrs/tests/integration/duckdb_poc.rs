@@ -3,13 +3,14 @@ use std::path::Path;
 
 use semaflow::{
     backends::{BackendConnection, ConnectionManager, DuckDbConnection},
+    config::{DuckDbConfig, SemaflowConfig},
+    executor::ColumnKind,
     query_builder::SqlBuilder,
     registry::FlowRegistry,
-    runtime::{run_query, run_query_paginated},
+    runtime::{run_query, run_query_arrow, run_query_paginated, run_query_streaming},
     validation::Validator,
-    QueryRequest, QueryResult, TableSchema,
+    QueryContext, QueryRequest, QueryResult, SemaflowError, TableSchema,
 };
-use tokio;
 
 #[derive(Clone)]
 struct FakeConnection;
@@ -29,6 +30,7 @@ impl BackendConnection for FakeConnection {
             }],
             primary_keys: vec!["id".to_string()],
             foreign_keys: vec![],
+            unique_keys: vec![],
         })
     }
     async fn execute_sql(&self, _sql: &str) -> semaflow::error::Result<QueryResult> {
@@ -170,7 +172,7 @@ async fn duckdb_query_round_trip() -> anyhow::Result<()> {
     let mut connections = ConnectionManager::new();
     connections.insert(
         "duckdb_local",
-        std::sync::Arc::new(DuckDbConnection::new(&db_path).with_max_concurrency(8)),
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
     );
     let validator = Validator::new(connections.clone(), false);
 
@@ -180,7 +182,7 @@ async fn duckdb_query_round_trip() -> anyhow::Result<()> {
     let builder = SqlBuilder::default();
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["country".to_string()],
+        dimensions: vec!["country".into()],
         measures: vec!["order_total".to_string(), "distinct_customers".to_string()],
         filters: vec![],
         order: vec![],
@@ -188,8 +190,10 @@ async fn duckdb_query_round_trip() -> anyhow::Result<()> {
         offset: None,
         page_size: None,
         cursor: None,
+        ..Default::default()
     };
-    let sql = builder.build_for_request(&registry, &connections, &request)?;
+    let sql =
+        builder.build_for_request(&registry, &connections, &request, &QueryContext::default())?;
     let result = connections
         .get("duckdb_local")
         .unwrap()
@@ -207,6 +211,107 @@ async fn duckdb_query_round_trip() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn duckdb_connections_from_config_file() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+    let config_path = dir.path().join("semaflow.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "[datasources.duckdb_local.connection]\n\
+             backend = \"duckdb\"\n\
+             path = \"${{SEMAFLOW_TEST_UNSET_DIR:-{}}}/demo.duckdb\"\n\
+             \n\
+             [datasources.duckdb_local.duckdb]\n\
+             read_only = true\n",
+            dir.path().display()
+        ),
+    )?;
+
+    let connections = ConnectionManager::from_config_file(&config_path)?;
+    assert!(connections.config_for("duckdb_local").duckdb.read_only);
+    let validator = Validator::new(connections.clone(), false);
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        ..Default::default()
+    };
+    let result = run_query(&registry, &connections, &request, &QueryContext::default()).await?;
+    assert_eq!(result.rows.len(), 2);
+
+    fs::write(
+        &config_path,
+        "[datasources.duckdb_local.connection]\nbackend = \"duckdb\"\npath = \"${SEMAFLOW_TEST_UNSET_DIR}/demo.duckdb\"\n",
+    )?;
+    let err = ConnectionManager::from_config_file(&config_path)
+        .err()
+        .expect("unset variable should fail");
+    assert!(
+        err.to_string()
+            .contains("datasource duckdb_local: environment variable SEMAFLOW_TEST_UNSET_DIR"),
+        "{err}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn duckdb_tagged_queries_run_with_comments() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+
+    let mut connections = ConnectionManager::with_config(SemaflowConfig::from_toml(
+        "[datasources.duckdb_local.tagging]\n\
+         enabled = true\n\
+         comment = true\n\
+         tags = { team = \"analytics\" }\n",
+    )?);
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections.clone(), false);
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        tags: [("user".to_string(), "*/ alice".to_string())].into(),
+        ..Default::default()
+    };
+    let ctx = QueryContext::default();
+
+    let result = run_query(&registry, &connections, &request, &ctx).await?;
+    assert_eq!(result.rows.len(), 2);
+    let batch = run_query_arrow(&registry, &connections, &request, &ctx).await?;
+    assert_eq!(batch.num_rows(), 2);
+    let mut batches = 0;
+    let mut collect = |_: QueryResult| -> semaflow::error::Result<()> {
+        batches += 1;
+        Ok(())
+    };
+    let total =
+        run_query_streaming(&registry, &connections, &request, &ctx, 1, &mut collect).await?;
+    assert_eq!((total, batches), (2, 2));
+    let paged = QueryRequest {
+        page_size: Some(1),
+        count_total: true,
+        ..request
+    };
+    let page = run_query_paginated(&registry, &connections, &paged, &ctx).await?;
+    assert_eq!(page.rows.len(), 1);
+    assert_eq!(page.total_rows, Some(2));
+    Ok(())
+}
+
 #[tokio::test]
 async fn duckdb_runtime_run_query() -> anyhow::Result<()> {
     let dir = tempfile::tempdir()?;
@@ -217,7 +322,7 @@ async fn duckdb_runtime_run_query() -> anyhow::Result<()> {
     let mut connections = ConnectionManager::new();
     connections.insert(
         "duckdb_local",
-        std::sync::Arc::new(DuckDbConnection::new(&db_path).with_max_concurrency(8)),
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
     );
     let validator = Validator::new(connections.clone(), false);
 
@@ -226,7 +331,7 @@ async fn duckdb_runtime_run_query() -> anyhow::Result<()> {
 
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["country".to_string()],
+        dimensions: vec!["country".into()],
         measures: vec!["order_total".to_string()],
         filters: vec![],
         order: vec![],
@@ -234,10 +339,278 @@ async fn duckdb_runtime_run_query() -> anyhow::Result<()> {
         offset: None,
         page_size: None,
         cursor: None,
+        ..Default::default()
     };
 
-    let result = run_query(&registry, &connections, &request).await?;
+    let result = run_query(&registry, &connections, &request, &QueryContext::default()).await?;
     assert_eq!(result.rows.len(), 2);
+
+    let country = &result.columns[0];
+    assert_eq!(country.name, "country");
+    assert_eq!(country.kind, Some(ColumnKind::Dimension));
+    assert_eq!(country.data_type.as_deref(), Some("Utf8"));
+    let total = &result.columns[1];
+    assert_eq!(total.kind, Some(ColumnKind::Measure));
+    assert_eq!(total.data_type.as_deref(), Some("Float64"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn validation_infers_field_types() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+
+    let mut connections = ConnectionManager::new();
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections.clone(), false);
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+
+    let schema = registry.flow_schema("sales")?;
+    let dimension = |name: &str| {
+        let info = schema.dimensions.iter().find(|d| d.name == name).unwrap();
+        info.data_type.clone()
+    };
+    let measure = |name: &str| {
+        let info = schema.measures.iter().find(|m| m.name == name).unwrap();
+        info.data_type.clone()
+    };
+    assert_eq!(dimension("country").as_deref(), Some("VARCHAR"));
+    assert_eq!(dimension("customer_id").as_deref(), Some("INTEGER"));
+    assert_eq!(measure("order_total").as_deref(), Some("DOUBLE"));
+    assert_eq!(measure("distinct_customers").as_deref(), Some("bigint"));
+
+    // Inferred types check filter values like declared ones
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        measures: vec!["order_total".to_string()],
+        filters: vec![semaflow::flows::Filter {
+            field: "customer_id".to_string(),
+            op: semaflow::flows::FilterOp::Eq,
+            value: serde_json::json!("alice"),
+        }
+        .into()],
+        ..Default::default()
+    };
+    let err = SqlBuilder::default()
+        .build_for_request(&registry, &connections, &request, &QueryContext::default())
+        .unwrap_err();
+    assert!(
+        matches!(err, SemaflowError::InvalidFilterValue { .. }),
+        "err={err}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn duckdb_streaming_query_delivers_batches() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+
+    let mut connections = ConnectionManager::new();
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections.clone(), false);
+
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        ..Default::default()
+    };
+
+    let mut batches: Vec<QueryResult> = Vec::new();
+    let mut collect = |batch: QueryResult| -> semaflow::error::Result<()> {
+        batches.push(batch);
+        Ok(())
+    };
+    let total = run_query_streaming(
+        &registry,
+        &connections,
+        &request,
+        &QueryContext::default(),
+        1,
+        &mut collect,
+    )
+    .await?;
+    assert_eq!(total, 2);
+    assert_eq!(
+        batches.len(),
+        2,
+        "batch_size=1 should yield one batch per row"
+    );
+    assert!(batches.iter().all(|b| b.rows.len() == 1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn duckdb_arrow_query_keeps_column_types() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+
+    let mut connections = ConnectionManager::new();
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections.clone(), false);
+
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        ..Default::default()
+    };
+
+    let result =
+        run_query_arrow(&registry, &connections, &request, &QueryContext::default()).await?;
+    assert_eq!(result.num_rows(), 2);
+    let country = result.schema.field_with_name("country")?;
+    assert_eq!(country.data_type(), &arrow::datatypes::DataType::Utf8);
+    let total = result.schema.field_with_name("order_total")?;
+    assert_eq!(total.data_type(), &arrow::datatypes::DataType::Float64);
+
+    // The JSON rows convert with types inferred from their values
+    let rows = run_query(&registry, &connections, &request, &QueryContext::default()).await?;
+    let batch = rows.to_arrow()?;
+    assert_eq!(batch.num_rows(), 2);
+    assert_eq!(
+        batch.schema().field_with_name("order_total")?.data_type(),
+        &arrow::datatypes::DataType::Float64
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn duckdb_query_timeout_interrupts_and_releases_connection() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+    {
+        let conn = duckdb::Connection::open(&db_path)?;
+        conn.execute_batch(
+            "CREATE VIEW orders_exploded AS
+             SELECT o.* FROM orders o CROSS JOIN range(100000000000)",
+        )?;
+    }
+    let slow_orders = r#"
+name: slow_orders
+data_source: duckdb_local
+table: orders_exploded
+primary_key: id
+dimensions:
+  customer_id:
+    expr:
+      type: column
+      column: customer_id
+measures:
+  order_total:
+    expr:
+      type: column
+      column: amount
+    agg: sum
+"#;
+    fs::write(dir.path().join("tables/slow_orders.yaml"), slow_orders)?;
+    let slow_flow = r#"
+name: slow
+base_table:
+  semantic_table: slow_orders
+  alias: o
+"#;
+    fs::write(dir.path().join("flows/slow.yaml"), slow_flow)?;
+
+    // A single slot: the follow-up query only runs if the timed-out one released it
+    let config = SemaflowConfig::from_toml(
+        r#"
+[datasources.duckdb_local.concurrency]
+max_concurrent = 1
+"#,
+    )?;
+    let mut connections = ConnectionManager::with_config(config);
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections.clone(), false);
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+
+    let slow = QueryRequest {
+        flow: "slow".to_string(),
+        measures: vec!["order_total".to_string()],
+        query_timeout_ms: Some(100),
+        ..Default::default()
+    };
+    let err = run_query(&registry, &connections, &slow, &QueryContext::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, SemaflowError::Timeout(_)), "{err}");
+
+    let fast = QueryRequest {
+        flow: "sales".to_string(),
+        measures: vec!["order_total".to_string()],
+        query_timeout_ms: Some(10_000),
+        ..Default::default()
+    };
+    let result = run_query(&registry, &connections, &fast, &QueryContext::default()).await?;
+    assert_eq!(result.rows.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn duckdb_read_only_pool_serves_concurrent_queries() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+
+    let conn = std::sync::Arc::new(DuckDbConnection::with_config(
+        &db_path,
+        DuckDbConfig {
+            max_concurrency: None,
+            pool_size: 2,
+            read_only: true,
+            database_threads: Some(2),
+            database_memory_limit: Some("256MB".to_string()),
+        },
+    ));
+    conn.initialize_pool().await?;
+
+    let queries = (0..8).map(|_| {
+        let conn = conn.clone();
+        tokio::spawn(async move { conn.execute_sql("SELECT COUNT(*) AS n FROM orders").await })
+    });
+    for query in queries {
+        let result = query.await??;
+        assert_eq!(result.rows.len(), 1);
+    }
+
+    // Read-only, so another process could open the file alongside us
+    let err = conn
+        .execute_sql("CREATE TABLE scratch AS SELECT 1 AS x")
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().to_lowercase().contains("read-only"),
+        "{err}"
+    );
     Ok(())
 }
 
@@ -251,7 +624,7 @@ async fn duckdb_paginated_query() -> anyhow::Result<()> {
     let mut connections = ConnectionManager::new();
     connections.insert(
         "duckdb_local",
-        std::sync::Arc::new(DuckDbConnection::new(&db_path).with_max_concurrency(8)),
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
     );
     let validator = Validator::new(connections.clone(), false);
 
@@ -261,7 +634,7 @@ async fn duckdb_paginated_query() -> anyhow::Result<()> {
     // First page - page_size=1 to ensure multiple pages
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["country".to_string()],
+        dimensions: vec!["country".into()],
         measures: vec!["order_total".to_string()],
         filters: vec![],
         order: vec![],
@@ -269,9 +642,11 @@ async fn duckdb_paginated_query() -> anyhow::Result<()> {
         offset: None,
         page_size: Some(1),
         cursor: None,
+        ..Default::default()
     };
 
-    let result = run_query_paginated(&registry, &connections, &request).await?;
+    let result =
+        run_query_paginated(&registry, &connections, &request, &QueryContext::default()).await?;
     assert_eq!(result.rows.len(), 1, "First page should have 1 row");
     assert!(result.has_more, "Should have more pages");
     assert!(result.cursor.is_some(), "Should have cursor for next page");
@@ -279,7 +654,7 @@ async fn duckdb_paginated_query() -> anyhow::Result<()> {
     // Second page using cursor
     let request2 = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["country".to_string()],
+        dimensions: vec!["country".into()],
         measures: vec!["order_total".to_string()],
         filters: vec![],
         order: vec![],
@@ -287,9 +662,11 @@ async fn duckdb_paginated_query() -> anyhow::Result<()> {
         offset: None,
         page_size: Some(1),
         cursor: result.cursor,
+        ..Default::default()
     };
 
-    let result2 = run_query_paginated(&registry, &connections, &request2).await?;
+    let result2 =
+        run_query_paginated(&registry, &connections, &request2, &QueryContext::default()).await?;
     assert_eq!(result2.rows.len(), 1, "Second page should have 1 row");
     // With 2 countries total, second page is the last
     assert!(!result2.has_more, "Should be last page");
@@ -298,6 +675,119 @@ async fn duckdb_paginated_query() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn duckdb_keyset_paginated_query() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+
+    let mut connections = ConnectionManager::new();
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections.clone(), false);
+
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+
+    // Ordering by every dimension makes the order deterministic
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        order: vec![semaflow::flows::OrderItem {
+            column: "country".to_string(),
+            direction: semaflow::flows::SortDirection::Desc,
+        }],
+        page_size: Some(1),
+        ..Default::default()
+    };
+    let all = run_query(
+        &registry,
+        &connections,
+        &QueryRequest {
+            page_size: None,
+            ..request.clone()
+        },
+        &QueryContext::default(),
+    )
+    .await?;
+
+    let first =
+        run_query_paginated(&registry, &connections, &request, &QueryContext::default()).await?;
+    assert_eq!(first.rows, all.rows[..1]);
+    let cursor = semaflow::pagination::Cursor::decode(first.cursor.as_deref().unwrap())?;
+    assert_eq!(cursor.after(), Some(&[all.rows[0]["country"].clone()][..]));
+
+    let second = run_query_paginated(
+        &registry,
+        &connections,
+        &QueryRequest {
+            cursor: first.cursor,
+            ..request.clone()
+        },
+        &QueryContext::default(),
+    )
+    .await?;
+    assert_eq!(second.rows, all.rows[1..2]);
+    assert!(!second.has_more);
+    Ok(())
+}
+
+#[tokio::test]
+async fn duckdb_paginated_count_total() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+
+    let mut connections = ConnectionManager::new();
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections.clone(), false);
+
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        page_size: Some(1),
+        ..Default::default()
+    };
+    let uncounted =
+        run_query_paginated(&registry, &connections, &request, &QueryContext::default()).await?;
+    assert_eq!(uncounted.total_rows, None);
+
+    let counted = QueryRequest {
+        count_total: true,
+        ..request
+    };
+    let first =
+        run_query_paginated(&registry, &connections, &counted, &QueryContext::default()).await?;
+    assert_eq!(first.total_rows, Some(2));
+
+    // The next page reuses the cached count
+    let second = run_query_paginated(
+        &registry,
+        &connections,
+        &QueryRequest {
+            cursor: first.cursor,
+            ..counted
+        },
+        &QueryContext::default(),
+    )
+    .await?;
+    assert_eq!(second.total_rows, Some(2));
+    assert!(!second.has_more);
+    Ok(())
+}
+
 #[tokio::test]
 async fn duckdb_paginated_invalid_cursor_rejected() -> anyhow::Result<()> {
     let dir = tempfile::tempdir()?;
@@ -308,7 +798,7 @@ async fn duckdb_paginated_invalid_cursor_rejected() -> anyhow::Result<()> {
     let mut connections = ConnectionManager::new();
     connections.insert(
         "duckdb_local",
-        std::sync::Arc::new(DuckDbConnection::new(&db_path).with_max_concurrency(8)),
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
     );
     let validator = Validator::new(connections.clone(), false);
 
@@ -318,7 +808,7 @@ async fn duckdb_paginated_invalid_cursor_rejected() -> anyhow::Result<()> {
     // Invalid cursor should be rejected
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["country".to_string()],
+        dimensions: vec!["country".into()],
         measures: vec!["order_total".to_string()],
         filters: vec![],
         order: vec![],
@@ -326,14 +816,108 @@ async fn duckdb_paginated_invalid_cursor_rejected() -> anyhow::Result<()> {
         offset: None,
         page_size: Some(10),
         cursor: Some("invalid_cursor".to_string()),
+        ..Default::default()
     };
 
-    let result = run_query_paginated(&registry, &connections, &request).await;
+    let result =
+        run_query_paginated(&registry, &connections, &request, &QueryContext::default()).await;
     assert!(result.is_err(), "Invalid cursor should error");
 
     Ok(())
 }
 
+#[tokio::test]
+async fn first_and_last_skip_null_values() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("demo.duckdb");
+    bootstrap_duckdb(&db_path)?;
+    write_flows(dir.path())?;
+    {
+        // Alice's latest order has no status
+        let conn = duckdb::Connection::open(&db_path)?;
+        conn.execute_batch(
+            "
+            ALTER TABLE orders ADD COLUMN status VARCHAR;
+            UPDATE orders SET status = 'new' WHERE id = 1;
+            UPDATE orders SET status = 'paid' WHERE id = 3;
+            ",
+        )?;
+    }
+    let statuses = r#"
+name: order_statuses
+data_source: duckdb_local
+table: orders
+primary_key: id
+time_dimension: created_at
+dimensions:
+  customer_id:
+    expr:
+      type: column
+      column: customer_id
+measures:
+  first_status:
+    expr:
+      type: column
+      column: status
+    agg: !first {order_by: created_at}
+  last_status:
+    expr:
+      type: column
+      column: status
+    agg: !last {order_by: created_at}
+"#;
+    fs::write(dir.path().join("tables/order_statuses.yaml"), statuses)?;
+    let statuses_flow = r#"
+name: statuses
+base_table:
+  semantic_table: order_statuses
+  alias: s
+"#;
+    fs::write(dir.path().join("flows/statuses.yaml"), statuses_flow)?;
+
+    let mut connections = ConnectionManager::new();
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    Validator::new(connections.clone(), false)
+        .validate_registry(&mut registry)
+        .await?;
+
+    let request = QueryRequest {
+        flow: "statuses".to_string(),
+        dimensions: vec!["customer_id".into()],
+        measures: vec!["first_status".to_string(), "last_status".to_string()],
+        order: vec![semaflow::flows::OrderItem {
+            column: "customer_id".to_string(),
+            direction: semaflow::flows::SortDirection::Asc,
+        }],
+        ..Default::default()
+    };
+    let sql = SqlBuilder::default().build_for_request(
+        &registry,
+        &connections,
+        &request,
+        &QueryContext::default(),
+    )?;
+    let result = connections
+        .get("duckdb_local")
+        .unwrap()
+        .execute_sql(&sql)
+        .await?;
+    let statuses: Vec<_> = result
+        .rows
+        .iter()
+        .map(|row| (row["first_status"].as_str(), row["last_status"].as_str()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![(Some("new"), Some("new")), (Some("paid"), Some("paid"))]
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn mixed_data_sources_fail_validation() -> anyhow::Result<()> {
     let dir = tempfile::tempdir()?;
@@ -399,3 +983,350 @@ joins:
     }
     Ok(())
 }
+
+#[tokio::test]
+async fn inner_joins_on_nullable_keys_warn() -> anyhow::Result<()> {
+    let t1: semaflow::flows::SemanticTable = serde_json::from_value(serde_json::json!({
+        "name": "t1",
+        "data_source": "ds1",
+        "table": "t1",
+        "primary_key": "id",
+        "nullable": {"id": true},
+        "dimensions": {"id": "id"},
+    }))?;
+    let t2: semaflow::flows::SemanticTable = serde_json::from_value(serde_json::json!({
+        "name": "t2",
+        "data_source": "ds1",
+        "table": "t2",
+        "primary_key": "id",
+        "dimensions": {"id": "id"},
+    }))?;
+    let flow: semaflow::flows::SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "pairs",
+        "base_table": {"semantic_table": "t1", "alias": "a"},
+        "joins": {"b": {
+            "semantic_table": "t2",
+            "alias": "b",
+            "to_table": "a",
+            "join_type": "inner",
+            "join_keys": [{"left": "id", "right": "id"}],
+        }},
+    }))?;
+    let mut registry = FlowRegistry::from_parts(vec![t1, t2], vec![flow]);
+    let mut connections = ConnectionManager::new();
+    connections.insert("ds1", std::sync::Arc::new(FakeConnection));
+
+    let validator = Validator::new(connections, false);
+    let issues = validator.collect_issues(&registry).await;
+    assert_eq!(issues.len(), 1, "issues={issues:?}");
+    assert_eq!(issues[0].severity, semaflow::validation::Severity::Warning);
+    assert!(issues[0].message.contains("nullable column id"));
+
+    // Declared nullability wins; undeclared columns are read from the schema
+    validator.validate_registry(&mut registry).await?;
+    assert!(registry.get_table("t1").unwrap().nullable["id"]);
+    assert!(!registry.get_table("t2").unwrap().nullable["id"]);
+    Ok(())
+}
+
+#[test]
+fn validation_rejects_join_cycles() -> anyhow::Result<()> {
+    let table = |name: &str| {
+        serde_json::from_value::<semaflow::flows::SemanticTable>(serde_json::json!({
+            "name": name,
+            "data_source": "ds1",
+            "table": name,
+            "primary_key": "id",
+            "dimensions": {"id": "id"},
+        }))
+    };
+    let join = |alias: &str, to_table: &str| {
+        serde_json::json!({
+            "semantic_table": "t2",
+            "alias": alias,
+            "to_table": to_table,
+            "join_type": "left",
+            "join_keys": [{"left": "id", "right": "id"}],
+        })
+    };
+    let flow = |joins: serde_json::Value| {
+        serde_json::from_value::<semaflow::flows::SemanticFlow>(serde_json::json!({
+            "name": "pairs",
+            "base_table": {"semantic_table": "t1", "alias": "a"},
+            "joins": joins,
+        }))
+    };
+    let validator = Validator::new(ConnectionManager::new(), false);
+
+    let cyclic = flow(serde_json::json!({"b": join("b", "c"), "c": join("c", "b")}))?;
+    let registry = FlowRegistry::from_parts(vec![table("t1")?, table("t2")?], vec![cyclic]);
+    match validator.validate_flows(&registry) {
+        Err(SemaflowError::JoinCycle { flow, path }) => {
+            assert_eq!(flow, "pairs");
+            assert_eq!(path.first(), path.last());
+            assert_eq!(path.len(), 3, "path={path:?}");
+        }
+        other => panic!("unexpected result {other:?}"),
+    }
+
+    let looped = flow(serde_json::json!({"b": join("b", "b")}))?;
+    let registry = FlowRegistry::from_parts(vec![table("t1")?, table("t2")?], vec![looped]);
+    let err = validator.validate_flows(&registry).unwrap_err();
+    assert!(err.to_string().contains("attaches to itself"), "err={err}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn stored_schemas_survive_restarts() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let store = std::sync::Arc::new(semaflow::schema_cache::FileSchemaStore::new(
+        dir.path().join("cache").join("schemas.json"),
+    ));
+    let table: semaflow::flows::SemanticTable = serde_json::from_value(serde_json::json!({
+        "name": "t1",
+        "data_source": "ds1",
+        "table": "t1",
+        "primary_key": "id",
+        "dimensions": {"id": "id"},
+    }))?;
+    let registry = FlowRegistry::from_parts(vec![table.clone()], vec![]);
+    let mut connections = ConnectionManager::new();
+    connections.insert("ds1", std::sync::Arc::new(FakeConnection));
+
+    let validator = Validator::new(connections.clone(), false).with_schema_store(store.clone());
+    assert_eq!(validator.warm_schemas(&registry).await?, 1);
+    assert_eq!(validator.warm_schemas(&registry).await?, 0);
+
+    // A new validator starts from the stored schemas
+    let restarted = Validator::new(connections, false).with_schema_store(store);
+    assert_eq!(restarted.warm_schemas(&registry).await?, 0);
+    let schema = restarted.refresh_schema(&table).await?;
+    assert_eq!(schema.primary_keys, vec!["id".to_string()]);
+    Ok(())
+}
+
+/// Serves whatever schema the test last set.
+struct DriftingConnection(std::sync::Mutex<TableSchema>);
+
+#[async_trait::async_trait]
+impl BackendConnection for DriftingConnection {
+    fn dialect(&self) -> &(dyn semaflow::dialect::Dialect + Send + Sync) {
+        &semaflow::dialect::DuckDbDialect
+    }
+
+    async fn fetch_schema(&self, _table: &str) -> semaflow::error::Result<TableSchema> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+
+    async fn execute_sql(&self, sql: &str) -> semaflow::error::Result<QueryResult> {
+        FakeConnection.execute_sql(sql).await
+    }
+
+    async fn execute_sql_paginated(
+        &self,
+        sql: &str,
+        page_size: u32,
+        cursor: Option<&semaflow::pagination::Cursor>,
+        query_hash: u64,
+    ) -> semaflow::error::Result<semaflow::executor::PaginatedResult> {
+        FakeConnection
+            .execute_sql_paginated(sql, page_size, cursor, query_hash)
+            .await
+    }
+}
+
+#[tokio::test]
+async fn drift_reports_changed_missing_and_new_columns() -> anyhow::Result<()> {
+    use semaflow::drift::DriftKind;
+    use semaflow::schema_cache::ColumnSchema;
+
+    let column = |name: &str, data_type: &str| ColumnSchema {
+        name: name.to_string(),
+        data_type: data_type.to_string(),
+        nullable: true,
+    };
+    let schema = |columns| TableSchema {
+        columns,
+        primary_keys: vec!["id".to_string()],
+        foreign_keys: vec![],
+        unique_keys: vec![],
+    };
+    let orders: semaflow::flows::SemanticTable = serde_json::from_value(serde_json::json!({
+        "name": "orders",
+        "data_source": "ds1",
+        "table": "orders",
+        "primary_key": "id",
+        "dimensions": {"status": "status"},
+        "measures": {"revenue": {"expr": "amount", "agg": "sum"}},
+    }))?;
+    let registry = FlowRegistry::from_parts(vec![orders], vec![]);
+    let conn = std::sync::Arc::new(DriftingConnection(std::sync::Mutex::new(schema(vec![
+        column("id", "INTEGER"),
+        column("status", "VARCHAR"),
+        column("amount", "DOUBLE"),
+    ]))));
+    let mut connections = ConnectionManager::new();
+    connections.insert("ds1", conn.clone());
+    let validator = Validator::new(connections, false);
+
+    // Nothing to compare types against yet
+    assert!(validator.check_drift(&registry).await?.is_empty());
+
+    *conn.0.lock().unwrap() = schema(vec![
+        column("id", "INTEGER"),
+        column("amount", "VARCHAR"),
+        column("discount", "DOUBLE"),
+    ]);
+    let report = validator.check_drift(&registry).await?;
+    assert!(report.has_breaking());
+    let found: Vec<(DriftKind, Option<&str>)> = report
+        .drifts
+        .iter()
+        .map(|drift| (drift.kind, drift.column.as_deref()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (DriftKind::TypeChanged, Some("amount")),
+            (DriftKind::ColumnMissing, Some("status")),
+            (DriftKind::NewColumn, Some("discount")),
+        ]
+    );
+    assert_eq!(report.drifts[0].previous_type.as_deref(), Some("DOUBLE"));
+    assert_eq!(report.drifts[0].used_by, vec!["measure:orders.revenue"]);
+    assert_eq!(report.drifts[1].used_by, vec!["dimension:orders.status"]);
+    Ok(())
+}
+
+fn write_union_flows(root: &Path, orders_2024_columns: &str) -> anyhow::Result<()> {
+    let tables_dir = root.join("tables");
+    let flows_dir = root.join("flows");
+    fs::create_dir_all(&tables_dir)?;
+    fs::create_dir_all(&flows_dir)?;
+
+    let orders_2023 = r#"
+name: orders_2023
+data_source: duckdb_local
+table: orders_2023
+primary_key: id
+"#;
+    fs::write(tables_dir.join("orders_2023.yaml"), orders_2023)?;
+
+    let orders_2024 = r#"
+name: orders_2024
+data_source: duckdb_local
+table: orders_2024
+primary_key: id
+"#;
+    fs::write(tables_dir.join("orders_2024.yaml"), orders_2024)?;
+
+    let all_orders = format!(
+        r#"
+name: all_orders
+data_source: duckdb_local
+primary_key: id
+union:
+  columns: [id, region, amount]
+  tables:
+    - semantic_table: orders_2023
+    - semantic_table: orders_2024
+      columns:
+{orders_2024_columns}
+dimensions:
+  region: region
+measures:
+  order_total:
+    expr: amount
+    agg: sum
+  order_count:
+    expr: id
+    agg: count
+"#
+    );
+    fs::write(tables_dir.join("all_orders.yaml"), all_orders)?;
+
+    let flow_yaml = r#"
+name: all_sales
+base_table:
+  semantic_table: all_orders
+  alias: o
+"#;
+    fs::write(flows_dir.join("all_sales.yaml"), flow_yaml)?;
+    Ok(())
+}
+
+fn bootstrap_union_duckdb(db_path: &Path) -> anyhow::Result<()> {
+    let conn = duckdb::Connection::open(db_path)?;
+    conn.execute_batch(
+        "
+        CREATE TABLE orders_2023 (id INTEGER, region VARCHAR, amount DOUBLE);
+        CREATE TABLE orders_2024 (id INTEGER, total_amount DOUBLE);
+        INSERT INTO orders_2023 VALUES (1, 'EU', 10.0), (2, 'US', 20.0);
+        INSERT INTO orders_2024 VALUES (3, 30.0), (4, 40.0), (5, 50.0);
+        ",
+    )?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn union_table_appends_members() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("union.duckdb");
+    bootstrap_union_duckdb(&db_path)?;
+    write_union_flows(
+        dir.path(),
+        "        amount: total_amount\n        region: null",
+    )?;
+
+    let mut connections = ConnectionManager::new();
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections.clone(), false);
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    validator.validate_registry(&mut registry).await?;
+
+    let request = QueryRequest {
+        flow: "all_sales".to_string(),
+        measures: vec!["order_total".to_string(), "order_count".to_string()],
+        ..Default::default()
+    };
+    let result = run_query(&registry, &connections, &request, &QueryContext::default()).await?;
+    assert_eq!(result.rows.len(), 1);
+    let row = &result.rows[0];
+    assert_eq!(row.get("order_total").unwrap().as_f64().unwrap(), 150.0);
+    assert_eq!(row.get("order_count").unwrap().as_u64().unwrap(), 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn union_member_missing_column_fails_validation() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("union.duckdb");
+    bootstrap_union_duckdb(&db_path)?;
+    // `region` is neither mapped nor nulled for orders_2024.
+    write_union_flows(dir.path(), "        amount: total_amount")?;
+
+    let mut connections = ConnectionManager::new();
+    connections.insert(
+        "duckdb_local",
+        std::sync::Arc::new(DuckDbConnection::new(&db_path)),
+    );
+    let validator = Validator::new(connections, false);
+    let mut registry = FlowRegistry::load_from_dir(dir.path())?;
+    let err = validator
+        .validate_registry(&mut registry)
+        .await
+        .unwrap_err();
+    match err {
+        SemaflowError::Validation(msg) => {
+            assert!(
+                msg.contains("column region is missing from member orders_2024"),
+                "{msg}"
+            );
+        }
+        other => panic!("unexpected error {other:?}"),
+    }
+    Ok(())
+}
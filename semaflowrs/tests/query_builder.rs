@@ -2,16 +2,33 @@
 //!
 //! These tests exercise the public API: SqlBuilder, FlowRegistry, QueryRequest.
 
-use semaflow::dialect::DuckDbDialect;
+use semaflow::blend::build_blend_sql;
 #[cfg(feature = "postgres")]
 use semaflow::dialect::PostgresDialect;
+use semaflow::dialect::{Dialect, DuckDbDialect};
+use semaflow::error::FieldKind;
 use semaflow::flows::{
-    Aggregation, BinaryOp, Expr, FlowJoin, FlowTableRef, Function, JoinKey, JoinType, Measure,
-    QueryRequest, SemanticFlow, SemanticTable,
+    Aggregation, BinaryOp, BlendRequest, BucketSpec, Expr, FanoutStrategy, Filter, FilterExpr,
+    FilterOp, FlowJoin, FlowTableRef, Function, JoinCardinality, JoinKey, JoinType, Measure,
+    QueryRequest, SemanticFlow, SemanticTable, StrategyOverride,
 };
-use semaflow::query_builder::SqlBuilder;
+use semaflow::policy::QueryContext;
+use semaflow::query_builder::{FilterPlacement, MeasureStrategy, PlanStrategy, SqlBuilder};
 use semaflow::registry::FlowRegistry;
-use semaflow::SemaflowError;
+use semaflow::{PlannerConfig, RenderOptions, SemaflowError, TableStats};
+
+/// Dialect with none of the optional SQL features.
+struct PlainDialect;
+
+impl Dialect for PlainDialect {
+    fn quote_ident(&self, ident: &str) -> String {
+        format!("\"{ident}\"")
+    }
+
+    fn render_function(&self, func: &Function, args: Vec<String>) -> String {
+        DuckDbDialect.render_function(func, args)
+    }
+}
 
 // ============================================================================
 // Test fixtures
@@ -26,9 +43,15 @@ mod fixtures {
             data_source: "ds1".to_string(),
             name: "orders".to_string(),
             table: "orders".to_string(),
+            sql: None,
+            query: None,
+            union: None,
             primary_keys: vec!["id".to_string()],
+            unique_keys: Vec::new(),
+            nullable: Default::default(),
             time_dimension: Some("created_at".to_string()),
             smallest_time_grain: None,
+            timezone: None,
             dimensions: [
                 (
                     "country".to_string(),
@@ -38,6 +61,15 @@ mod fixtures {
                         },
                         data_type: None,
                         description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
+                        unnest: false,
+                        labels: Default::default(),
                     },
                 ),
                 (
@@ -51,6 +83,53 @@ mod fixtures {
                         },
                         data_type: None,
                         description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
+                        unnest: false,
+                        labels: Default::default(),
+                    },
+                ),
+                (
+                    "amount".to_string(),
+                    Dimension {
+                        expr: Expr::Column {
+                            column: "amount".to_string(),
+                        },
+                        data_type: None,
+                        description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
+                        unnest: false,
+                        labels: Default::default(),
+                    },
+                ),
+                (
+                    "is_gift".to_string(),
+                    Dimension {
+                        expr: Expr::Column {
+                            column: "is_gift".to_string(),
+                        },
+                        data_type: Some("boolean".to_string()),
+                        description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
+                        unnest: false,
+                        labels: Default::default(),
                     },
                 ),
             ]
@@ -69,6 +148,13 @@ mod fixtures {
                         post_expr: None,
                         data_type: None,
                         description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
                     },
                 ),
                 (
@@ -83,12 +169,24 @@ mod fixtures {
                         post_expr: None,
                         data_type: None,
                         description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
                     },
                 ),
             ]
             .into_iter()
             .collect(),
             description: None,
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            access_policies: Default::default(),
+            hierarchies: Default::default(),
+            entities: Default::default(),
         };
 
         let flow = SemanticFlow {
@@ -99,6 +197,18 @@ mod fixtures {
             },
             joins: std::collections::BTreeMap::new(),
             description: None,
+            params: Default::default(),
+            tags: Vec::new(),
+            synonyms: Vec::new(),
+            limits: Default::default(),
+            aggregates: Default::default(),
+            fiscal_calendar: None,
+            timezone: None,
+            fanout_strategy: Default::default(),
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            extends: None,
+            deprecated: None,
         };
 
         FlowRegistry::from_parts(vec![table], vec![flow])
@@ -109,9 +219,15 @@ mod fixtures {
             data_source: "ds1".to_string(),
             name: "orders".to_string(),
             table: "orders".to_string(),
+            sql: None,
+            query: None,
+            union: None,
             primary_keys: vec!["id".to_string()],
+            unique_keys: Vec::new(),
+            nullable: Default::default(),
             time_dimension: None,
             smallest_time_grain: None,
+            timezone: None,
             dimensions: [(
                 "amount".to_string(),
                 Dimension {
@@ -120,6 +236,15 @@ mod fixtures {
                     },
                     data_type: None,
                     description: None,
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: None,
+                    format: None,
+                    hidden: false,
+                    unnest: false,
+                    labels: Default::default(),
                 },
             )]
             .into_iter()
@@ -136,20 +261,38 @@ mod fixtures {
                     post_expr: None,
                     data_type: None,
                     description: None,
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: None,
+                    format: None,
+                    hidden: false,
                 },
             )]
             .into_iter()
             .collect(),
             description: None,
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            access_policies: Default::default(),
+            hierarchies: Default::default(),
+            entities: Default::default(),
         };
 
         let customers = SemanticTable {
             data_source: "ds1".to_string(),
             name: "customers".to_string(),
             table: "customers".to_string(),
+            sql: None,
+            query: None,
+            union: None,
             primary_keys: vec!["id".to_string()],
+            unique_keys: Vec::new(),
+            nullable: Default::default(),
             time_dimension: None,
             smallest_time_grain: None,
+            timezone: None,
             dimensions: [(
                 "country".to_string(),
                 Dimension {
@@ -158,12 +301,26 @@ mod fixtures {
                     },
                     data_type: None,
                     description: None,
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: None,
+                    format: None,
+                    hidden: false,
+                    unnest: false,
+                    labels: Default::default(),
                 },
             )]
             .into_iter()
             .collect(),
             measures: Default::default(),
             description: None,
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            access_policies: Default::default(),
+            hierarchies: Default::default(),
+            entities: Default::default(),
         };
 
         let flow = SemanticFlow {
@@ -184,12 +341,28 @@ mod fixtures {
                         right: "id".to_string(),
                     }],
                     cardinality: None,
+                    through: None,
+                    weight: None,
+                    role: None,
+                    extra_conditions: None,
                     description: None,
                 },
             )]
             .into_iter()
             .collect(),
             description: None,
+            params: Default::default(),
+            tags: Vec::new(),
+            synonyms: Vec::new(),
+            limits: Default::default(),
+            aggregates: Default::default(),
+            fiscal_calendar: None,
+            timezone: None,
+            fanout_strategy: Default::default(),
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            extends: None,
+            deprecated: None,
         };
 
         FlowRegistry::from_parts(vec![orders, customers], vec![flow])
@@ -200,9 +373,15 @@ mod fixtures {
             data_source: "ds1".to_string(),
             name: "orders".to_string(),
             table: "orders".to_string(),
+            sql: None,
+            query: None,
+            union: None,
             primary_keys: vec!["id".to_string()],
+            unique_keys: Vec::new(),
+            nullable: Default::default(),
             time_dimension: None,
             smallest_time_grain: None,
+            timezone: None,
             dimensions: [(
                 "amount".to_string(),
                 Dimension {
@@ -211,6 +390,15 @@ mod fixtures {
                     },
                     data_type: None,
                     description: None,
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: None,
+                    format: None,
+                    hidden: false,
+                    unnest: false,
+                    labels: Default::default(),
                 },
             )]
             .into_iter()
@@ -227,20 +415,38 @@ mod fixtures {
                     post_expr: None,
                     data_type: None,
                     description: None,
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: None,
+                    format: None,
+                    hidden: false,
                 },
             )]
             .into_iter()
             .collect(),
             description: None,
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            access_policies: Default::default(),
+            hierarchies: Default::default(),
+            entities: Default::default(),
         };
 
         let customers = SemanticTable {
             data_source: "ds1".to_string(),
             name: "customers".to_string(),
             table: "customers".to_string(),
+            sql: None,
+            query: None,
+            union: None,
             primary_keys: vec!["id".to_string()],
+            unique_keys: Vec::new(),
+            nullable: Default::default(),
             time_dimension: None,
             smallest_time_grain: None,
+            timezone: None,
             dimensions: [(
                 "country".to_string(),
                 Dimension {
@@ -249,21 +455,41 @@ mod fixtures {
                     },
                     data_type: None,
                     description: None,
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: None,
+                    format: None,
+                    hidden: false,
+                    unnest: false,
+                    labels: Default::default(),
                 },
             )]
             .into_iter()
             .collect(),
             measures: Default::default(),
             description: None,
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            access_policies: Default::default(),
+            hierarchies: Default::default(),
+            entities: Default::default(),
         };
 
         let regions = SemanticTable {
             data_source: "ds1".to_string(),
             name: "regions".to_string(),
             table: "regions".to_string(),
+            sql: None,
+            query: None,
+            union: None,
             primary_keys: vec!["id".to_string()],
+            unique_keys: Vec::new(),
+            nullable: Default::default(),
             time_dimension: None,
             smallest_time_grain: None,
+            timezone: None,
             dimensions: [(
                 "region".to_string(),
                 Dimension {
@@ -272,12 +498,26 @@ mod fixtures {
                     },
                     data_type: None,
                     description: None,
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: None,
+                    format: None,
+                    hidden: false,
+                    unnest: false,
+                    labels: Default::default(),
                 },
             )]
             .into_iter()
             .collect(),
             measures: Default::default(),
             description: None,
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            access_policies: Default::default(),
+            hierarchies: Default::default(),
+            entities: Default::default(),
         };
 
         let flow = SemanticFlow {
@@ -299,6 +539,10 @@ mod fixtures {
                             right: "id".to_string(),
                         }],
                         cardinality: None,
+                        through: None,
+                        weight: None,
+                        role: None,
+                        extra_conditions: None,
                         description: None,
                     },
                 ),
@@ -314,6 +558,10 @@ mod fixtures {
                             right: "id".to_string(),
                         }],
                         cardinality: None,
+                        through: None,
+                        weight: None,
+                        role: None,
+                        extra_conditions: None,
                         description: None,
                     },
                 ),
@@ -321,19 +569,88 @@ mod fixtures {
             .into_iter()
             .collect(),
             description: None,
+            params: Default::default(),
+            tags: Vec::new(),
+            synonyms: Vec::new(),
+            limits: Default::default(),
+            aggregates: Default::default(),
+            fiscal_calendar: None,
+            timezone: None,
+            fanout_strategy: Default::default(),
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            extends: None,
+            deprecated: None,
         };
 
         FlowRegistry::from_parts(vec![orders, customers, regions], vec![flow])
     }
 
+    /// Orders with a many-to-one join to customers and a one-to-many join
+    /// to payments, all keyed by `id`.
+    pub fn payments_registry() -> FlowRegistry {
+        let table =
+            |value: serde_json::Value| -> SemanticTable { serde_json::from_value(value).unwrap() };
+        let orders = table(serde_json::json!({
+            "data_source": "ds1",
+            "name": "orders",
+            "table": "orders",
+            "primary_key": "id",
+            "measures": {"order_total": {"expr": "amount", "agg": "sum"}},
+        }));
+        let customers = table(serde_json::json!({
+            "data_source": "ds1",
+            "name": "customers",
+            "table": "customers",
+            "primary_key": "id",
+            "dimensions": {"country": "country"},
+            "measures": {"customer_count": {"expr": "id", "agg": "count"}},
+        }));
+        let payments = table(serde_json::json!({
+            "data_source": "ds1",
+            "name": "payments",
+            "table": "payments",
+            "primary_key": "id",
+            "measures": {"paid_total": {"expr": "amount", "agg": "sum"}},
+        }));
+        let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+            "name": "sales",
+            "base_table": {"semantic_table": "orders", "alias": "o"},
+            "joins": {
+                "customers": {
+                    "semantic_table": "customers",
+                    "alias": "c",
+                    "to_table": "o",
+                    "join_type": "left",
+                    "join_keys": [{"left": "customer_id", "right": "id"}],
+                },
+                "payments": {
+                    "semantic_table": "payments",
+                    "alias": "p",
+                    "to_table": "o",
+                    "join_type": "left",
+                    "join_keys": [{"left": "id", "right": "order_id"}],
+                },
+            },
+        }))
+        .unwrap();
+        FlowRegistry::from_parts(vec![orders, customers, payments], vec![flow])
+    }
+
     pub fn measures_registry() -> FlowRegistry {
         let table = SemanticTable {
             data_source: "ds1".to_string(),
             name: "orders".to_string(),
             table: "orders".to_string(),
+            sql: None,
+            query: None,
+            union: None,
             primary_keys: vec!["id".to_string()],
+            unique_keys: Vec::new(),
+            nullable: Default::default(),
             time_dimension: None,
             smallest_time_grain: None,
+            timezone: None,
             dimensions: [(
                 "country".to_string(),
                 Dimension {
@@ -342,6 +659,15 @@ mod fixtures {
                     },
                     data_type: None,
                     description: None,
+                    tags: Vec::new(),
+                    synonyms: Vec::new(),
+                    folder: None,
+                    deprecated: None,
+                    label: None,
+                    format: None,
+                    hidden: false,
+                    unnest: false,
+                    labels: Default::default(),
                 },
             )]
             .into_iter()
@@ -359,6 +685,13 @@ mod fixtures {
                         post_expr: None,
                         data_type: None,
                         description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
                     },
                 ),
                 (
@@ -373,6 +706,13 @@ mod fixtures {
                         post_expr: None,
                         data_type: None,
                         description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
                     },
                 ),
                 (
@@ -395,6 +735,13 @@ mod fixtures {
                         post_expr: None,
                         data_type: None,
                         description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
                     },
                 ),
                 (
@@ -419,12 +766,24 @@ mod fixtures {
                         }),
                         data_type: None,
                         description: None,
+                        tags: Vec::new(),
+                        synonyms: Vec::new(),
+                        folder: None,
+                        deprecated: None,
+                        label: None,
+                        format: None,
+                        hidden: false,
                     },
                 ),
             ]
             .into_iter()
             .collect(),
             description: None,
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            access_policies: Default::default(),
+            hierarchies: Default::default(),
+            entities: Default::default(),
         };
 
         let flow = SemanticFlow {
@@ -435,55 +794,1788 @@ mod fixtures {
             },
             joins: Default::default(),
             description: None,
+            params: Default::default(),
+            tags: Vec::new(),
+            synonyms: Vec::new(),
+            limits: Default::default(),
+            aggregates: Default::default(),
+            fiscal_calendar: None,
+            timezone: None,
+            fanout_strategy: Default::default(),
+            default_filters: Vec::new(),
+            segments: Default::default(),
+            extends: None,
+            deprecated: None,
         };
 
-        FlowRegistry::from_parts(vec![table], vec![flow])
-    }
-}
+        FlowRegistry::from_parts(vec![table], vec![flow])
+    }
+}
+
+// ============================================================================
+// Basic query building tests
+// ============================================================================
+
+#[test]
+fn build_with_functions_and_distinct() {
+    let registry = fixtures::simple_orders_registry();
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["month".into()],
+        measures: vec!["distinct_customers".to_string()],
+        filters: vec![],
+        order: vec![],
+        limit: None,
+        offset: None,
+        ..Default::default()
+    };
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains("date_trunc('month'"));
+    assert!(sql.contains("COUNT(DISTINCT"));
+    assert!(sql.contains("FROM \"orders\" \"o\""));
+}
+
+#[test]
+fn build_with_options_lays_out_pretty_sql() {
+    let registry = fixtures::simple_orders_registry();
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["month".into()],
+        measures: vec!["distinct_customers".to_string()],
+        ..Default::default()
+    };
+    let builder = SqlBuilder::default();
+    let one_line = builder
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    let pretty = builder
+        .build_with_options(
+            &registry,
+            &request,
+            &DuckDbDialect,
+            &RenderOptions::pretty(),
+        )
+        .unwrap();
+    assert!(!one_line.contains('\n'), "sql={one_line}");
+    assert!(pretty.starts_with("SELECT"), "sql={pretty}");
+    assert!(pretty.contains("\nFROM \"orders\" \"o\""), "sql={pretty}");
+    assert!(pretty.contains("\nGROUP BY"), "sql={pretty}");
+    let strip = |sql: &str| sql.split_whitespace().collect::<String>();
+    assert_eq!(strip(&pretty), strip(&one_line));
+
+    let lower = builder
+        .build_with_options(
+            &registry,
+            &request,
+            &DuckDbDialect,
+            &RenderOptions {
+                uppercase_keywords: false,
+                ..RenderOptions::default()
+            },
+        )
+        .unwrap();
+    assert!(lower.starts_with("select "), "sql={lower}");
+    assert!(lower.contains(" from \"orders\" \"o\""), "sql={lower}");
+}
+
+#[test]
+fn accepts_alias_qualified_fields() {
+    let registry = fixtures::orders_with_customers_registry();
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["c.country".into()],
+        measures: vec!["o.order_total".to_string()],
+        filters: vec![],
+        order: vec![],
+        limit: None,
+        offset: None,
+        ..Default::default()
+    };
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains("\"c\".\"country\""));
+    assert!(sql.contains("\"o\".\"amount\""));
+}
+
+#[test]
+fn unknown_fields_suggest_similar_names() {
+    let registry = fixtures::orders_with_customers_registry();
+    let mut request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["c.contry".into()],
+        measures: vec!["o.order_total".to_string()],
+        ..Default::default()
+    };
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    match &err {
+        SemaflowError::UnknownField {
+            kind, suggestions, ..
+        } => {
+            assert_eq!(*kind, FieldKind::Dimension);
+            assert_eq!(suggestions, &vec!["c.country".to_string()]);
+        }
+        other => panic!("unexpected error {other:?}"),
+    }
+    assert!(err.to_string().ends_with("did you mean c.country?"));
+
+    request.dimensions = vec!["c.country".into()];
+    request.measures = vec!["order_totl".to_string()];
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(
+        matches!(&err, SemaflowError::UnknownField { suggestions, .. } if suggestions == &["order_total"]),
+        "err={err:?}"
+    );
+}
+
+#[test]
+fn validate_request_reports_every_problem() {
+    let mut registry = fixtures::simple_orders_registry();
+    registry
+        .tables
+        .get_mut("orders")
+        .unwrap()
+        .dimensions
+        .get_mut("amount")
+        .unwrap()
+        .data_type = Some("decimal(10, 2)".to_string());
+    let filter = |field: &str, value: serde_json::Value| -> FilterExpr {
+        Filter {
+            field: field.to_string(),
+            op: FilterOp::Eq,
+            value,
+        }
+        .into()
+    };
+    let mut request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["contry".into()],
+        measures: vec!["order_totl".to_string()],
+        filters: vec![
+            filter("amount", serde_json::json!("lots")),
+            filter("is_gift", serde_json::json!("maybe")),
+        ],
+        ..Default::default()
+    };
+    let errors = registry.validate_request(&request).unwrap_err();
+    let codes: Vec<&str> = errors.iter().map(|e| e.code()).collect();
+    assert_eq!(
+        codes,
+        [
+            "unknown_field",
+            "unknown_field",
+            "invalid_filter_value",
+            "validation_error"
+        ],
+        "{errors:?}"
+    );
+    assert!(matches!(
+        &errors[2],
+        SemaflowError::InvalidFilterValue { field, expected, .. }
+            if field == "amount" && expected == "number"
+    ));
+
+    request.dimensions = vec!["country".into()];
+    request.measures = vec!["order_total".to_string()];
+    request.filters = vec![
+        filter("amount", serde_json::json!("10.5")),
+        filter("is_gift", serde_json::json!(true)),
+    ];
+    registry.validate_request(&request).unwrap();
+}
+
+#[test]
+fn bins_dimension_into_labelled_ranges() {
+    let registry = fixtures::simple_orders_registry();
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["amount".into()],
+        measures: vec!["order_total".to_string()],
+        bins: [(
+            "amount".to_string(),
+            BucketSpec::Edges(vec![0.0, 10.0, 50.0]),
+        )]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    let bucket = "CASE WHEN \"o\".\"amount\" < 0 THEN '< 0' \
+                  WHEN \"o\".\"amount\" < 10 THEN '[0-10)' \
+                  WHEN \"o\".\"amount\" < 50 THEN '[10-50)' \
+                  WHEN \"o\".\"amount\" >= 50 THEN '>= 50' ELSE NULL END";
+    assert!(
+        sql.contains(&format!("{bucket} AS \"amount\"")),
+        "sql={sql}"
+    );
+    assert!(sql.contains(" GROUP BY ALL"), "sql={sql}");
+}
+
+#[test]
+fn bins_require_requested_dimension_and_valid_spec() {
+    let registry = fixtures::simple_orders_registry();
+    let mut request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        bins: [("amount".to_string(), BucketSpec::Width(10.0))]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(matches!(err, SemaflowError::Validation(msg) if msg.contains("not in the requested")));
+
+    request.dimensions = vec!["amount".into()];
+    request.bins = [("amount".to_string(), BucketSpec::Edges(vec![10.0, 0.0]))]
+        .into_iter()
+        .collect();
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(matches!(err, SemaflowError::Validation(msg) if msg.contains("ascending")));
+}
+
+#[test]
+fn boolean_dimension_filters_use_boolean_sql() {
+    let registry = fixtures::simple_orders_registry();
+    let filter = |op: FilterOp, value: serde_json::Value| -> FilterExpr {
+        Filter {
+            field: "is_gift".to_string(),
+            op,
+            value,
+        }
+        .into()
+    };
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        filters: vec![
+            filter(FilterOp::IsTrue, serde_json::Value::Null),
+            filter(FilterOp::Neq, serde_json::json!("FALSE")),
+        ],
+        ..Default::default()
+    };
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains("(\"o\".\"is_gift\" IS TRUE)"), "sql={sql}");
+    assert!(sql.contains("(\"o\".\"is_gift\" != false)"), "sql={sql}");
+    assert!(!sql.contains("'FALSE'"), "sql={sql}");
+
+    let request = QueryRequest {
+        filters: vec![filter(FilterOp::Gt, serde_json::json!(true))],
+        ..request
+    };
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(matches!(err, SemaflowError::Validation(msg) if msg.contains("boolean dimension")));
+}
+
+#[test]
+fn nested_filter_groups_render_and_or_not() {
+    let registry = fixtures::simple_orders_registry();
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total"],
+        "filters": [
+            {"any": [
+                {"field": "country", "op": "==", "value": "US"},
+                {"field": "country", "op": "==", "value": "CA"}
+            ]},
+            {"field": "amount", "op": ">", "value": 10},
+            {"not": {"all": [
+                {"field": "is_gift", "op": "is true"},
+                {"field": "amount", "op": "<", "value": 100}
+            ]}}
+        ]
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains(
+            "WHERE ((\"o\".\"country\" = 'US') OR (\"o\".\"country\" = 'CA')) \
+             AND (\"o\".\"amount\" > 10) \
+             AND (NOT ((\"o\".\"is_gift\" IS TRUE) AND (\"o\".\"amount\" < 100)))"
+        ),
+        "sql={sql}"
+    );
+
+    let err = serde_json::from_value::<QueryRequest>(serde_json::json!({
+        "flow": "sales",
+        "filters": [{"any": []}]
+    }))
+    .unwrap_err();
+    assert!(err.to_string().contains("at least one filter"), "{err}");
+}
+
+#[test]
+fn time_filter_applies_relative_range_to_time_dimension() {
+    let registry = fixtures::simple_orders_registry();
+    let mut request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total"],
+        "time_filter": "last_30_days",
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("(\"o\".\"created_at\" >= CAST(current_date + INTERVAL '1 day' + INTERVAL '-30 day' AS DATE))"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("(\"o\".\"created_at\" < CAST(current_date + INTERVAL '1 day' AS DATE))"),
+        "sql={sql}"
+    );
+
+    request.time_filter = Some("previous_quarter".to_string().try_into().unwrap());
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains(">= CAST(date_trunc('quarter', current_date) + INTERVAL '-3 month' AS DATE)"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("< CAST(date_trunc('quarter', current_date) AS DATE)"),
+        "sql={sql}"
+    );
+}
+
+#[test]
+fn time_filter_requires_time_dimension_and_known_range() {
+    let registry = fixtures::orders_with_customers_registry();
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        measures: vec!["order_total".to_string()],
+        time_filter: Some("this_month".to_string().try_into().unwrap()),
+        ..Default::default()
+    };
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(err.to_string().contains("time_dimension"), "{err}");
+
+    let err = serde_json::from_value::<QueryRequest>(serde_json::json!({
+        "flow": "sales",
+        "time_filter": "last_fortnight",
+    }))
+    .unwrap_err();
+    assert!(err.to_string().contains("last_fortnight"), "{err}");
+}
+
+#[test]
+fn flow_limits_reject_unbounded_requests() {
+    let mut registry = fixtures::simple_orders_registry();
+    registry
+        .tables
+        .get_mut("orders")
+        .unwrap()
+        .dimensions
+        .insert(
+            "created_at".to_string(),
+            semaflow::flows::Dimension {
+                expr: Expr::Column {
+                    column: "created_at".to_string(),
+                },
+                data_type: Some("date".to_string()),
+                description: None,
+                tags: Vec::new(),
+                synonyms: Vec::new(),
+                folder: None,
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
+                unnest: false,
+                labels: Default::default(),
+            },
+        );
+    registry.flows.get_mut("sales").unwrap().limits = serde_json::from_value(serde_json::json!({
+        "max_rows": 1000,
+        "require_filter_on": ["created_at", "country"],
+        "max_time_range_days": 90,
+    }))
+    .unwrap();
+    let build = |request: serde_json::Value| {
+        let request: QueryRequest = serde_json::from_value(request).unwrap();
+        SqlBuilder::default().build_with_dialect(&registry, &request, &DuckDbDialect)
+    };
+
+    let err = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total"],
+    }))
+    .unwrap_err();
+    assert!(err.is_validation(), "{err}");
+    assert!(
+        err.to_string()
+            .contains("flow 'sales' requires a filter on created_at"),
+        "{err}"
+    );
+
+    // Excluding one value still scans nearly every row
+    let err = build(serde_json::json!({
+        "flow": "sales",
+        "measures": ["order_total"],
+        "filters": [{"field": "country", "op": "!=", "value": "US"}],
+        "time_filter": "last_30_days",
+    }))
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("flow 'sales' requires a filter on country"),
+        "{err}"
+    );
+
+    // A relative time filter counts, and the default limit is max_rows
+    let sql = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total"],
+        "filters": [{"field": "country", "op": "==", "value": "US"}],
+        "time_filter": "last_30_days",
+    }))
+    .unwrap();
+    assert!(sql.contains(" LIMIT 1000"), "sql={sql}");
+
+    let err = build(serde_json::json!({
+        "flow": "sales",
+        "measures": ["order_total"],
+        "filters": [{"field": "country", "op": "==", "value": "US"}],
+        "time_filter": "this_year",
+    }))
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("366 days exceeds the 90-day limit"),
+        "{err}"
+    );
+
+    // Explicit bounds on the time dimension, nested in an `all` group
+    let bounded = |from: &str, to: &str, limit: u32| {
+        build(serde_json::json!({
+            "flow": "sales",
+            "measures": ["order_total"],
+            "filters": [{"all": [
+                {"field": "country", "op": "==", "value": "US"},
+                {"field": "created_at", "op": ">=", "value": from},
+                {"field": "created_at", "op": "<", "value": to},
+            ]}],
+            "limit": limit,
+        }))
+    };
+    let sql = bounded("2024-01-01", "2024-03-01", 10).unwrap();
+    assert!(sql.contains(" LIMIT 10"), "sql={sql}");
+    let err = bounded("2024-01-01", "2024-06-01", 10).unwrap_err();
+    assert!(err.to_string().contains("152 days"), "{err}");
+    let err = bounded("2024-01-01", "2024-03-01", 5000).unwrap_err();
+    assert!(
+        err.to_string().contains("limit 5000 exceeds max_rows 1000"),
+        "{err}"
+    );
+
+    // A lower bound alone leaves the range open
+    let err = build(serde_json::json!({
+        "flow": "sales",
+        "measures": ["order_total"],
+        "filters": [
+            {"field": "country", "op": "==", "value": "US"},
+            {"field": "created_at", "op": ">=", "value": "2024-01-01"},
+        ],
+    }))
+    .unwrap_err();
+    assert!(err.to_string().contains("at most 90 days"), "{err}");
+}
+
+#[test]
+fn covered_requests_read_from_flow_aggregates() {
+    let mut registry = fixtures::simple_orders_registry();
+    registry.flows.get_mut("sales").unwrap().aggregates =
+        serde_json::from_value(serde_json::json!({
+            "daily": {
+                "table": "orders_daily",
+                "time_column": "day",
+                "grain": "day",
+                "dimensions": {"country": "country"},
+                "measures": {"o.order_total": "total"},
+            },
+            "monthly": {
+                "table": "orders_monthly",
+                "time_column": "month_start",
+                "grain": "month",
+                "measures": {"order_total": "total"},
+            },
+        }))
+        .unwrap();
+    let explain = |request: serde_json::Value| {
+        let request: QueryRequest = serde_json::from_value(request).unwrap();
+        SqlBuilder::default()
+            .explain_with_dialect(&registry, &request, &DuckDbDialect)
+            .unwrap()
+    };
+
+    let explain_daily = explain(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total"],
+        "time_filter": "last_30_days",
+        "order": [{"column": "order_total", "direction": "desc"}],
+    }));
+    let sql = &explain_daily.sql;
+    assert_eq!(explain_daily.strategy, PlanStrategy::Aggregate);
+    assert_eq!(explain_daily.plan.aggregate.as_deref(), Some("daily"));
+    assert!(sql.contains("FROM \"orders_daily\" \"o\""), "sql={sql}");
+    assert!(
+        sql.contains("\"o\".\"country\" AS \"country\""),
+        "sql={sql}"
+    );
+    assert!(sql.contains("SUM(\"o\".\"total\")"), "sql={sql}");
+    assert!(sql.contains("\"o\".\"day\" >="), "sql={sql}");
+
+    // Coarser grains roll up; the aggregate with fewer dimensions wins
+    let sql = explain(serde_json::json!({
+        "flow": "sales",
+        "dimensions": [{"field": "created_at", "grain": "quarter"}],
+        "measures": ["order_total"],
+    }))
+    .sql;
+    assert!(sql.contains("FROM \"orders_monthly\" \"o\""), "sql={sql}");
+    assert!(
+        sql.contains("date_trunc('quarter', \"o\".\"month_start\")"),
+        "sql={sql}"
+    );
+
+    // A daily time filter rules the monthly table out
+    let explain_filtered = explain(serde_json::json!({
+        "flow": "sales",
+        "dimensions": [{"field": "created_at", "grain": "month"}],
+        "measures": ["order_total"],
+        "time_filter": "last_7_days",
+    }));
+    assert_eq!(explain_filtered.plan.aggregate.as_deref(), Some("daily"));
+
+    // Unmapped measures, finer grains and unmapped filters use the base table
+    for request in [
+        serde_json::json!({"flow": "sales", "measures": ["distinct_customers"]}),
+        serde_json::json!({
+            "flow": "sales",
+            "dimensions": [{"field": "created_at", "grain": "week"}],
+            "measures": ["order_total"],
+            "filters": [{"field": "is_gift", "op": "is true"}],
+        }),
+    ] {
+        let explain = explain(request);
+        assert_ne!(explain.strategy, PlanStrategy::Aggregate);
+        assert!(
+            explain.sql.contains("FROM \"orders\" \"o\""),
+            "sql={}",
+            explain.sql
+        );
+    }
+}
+
+#[test]
+fn fill_gaps_joins_results_onto_date_spine() {
+    let registry = fixtures::simple_orders_registry();
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["month", "country"],
+        "measures": ["order_total", "distinct_customers"],
+        "order": [{"column": "month", "direction": "asc"}],
+        "limit": 100,
+        "fill_gaps": true,
+        "gap_fill": {"order_total": "zero", "distinct_customers": "previous"},
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.starts_with("WITH \"__results\" AS (SELECT"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("UNNEST(generate_series(MIN(\"month\"), MAX(\"month\"), INTERVAL 1 month))"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("FROM \"__spine\" CROSS JOIN \"__keys\" LEFT JOIN \"__results\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("\"__results\".\"country\" IS NOT DISTINCT FROM \"__keys\".\"country\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("COALESCE(\"__results\".\"order_total\", 0) AS \"order_total\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("LAST_VALUE(\"__results\".\"distinct_customers\" IGNORE NULLS) OVER (PARTITION BY \"__keys\".\"country\" ORDER BY \"__spine\".\"__bucket\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.ends_with(" ORDER BY \"month\" ASC LIMIT 100"),
+        "sql={sql}"
+    );
+}
+
+#[test]
+fn blend_full_outer_joins_flows_on_shared_dimensions() {
+    let orders = fixtures::simple_orders_registry();
+    let mut refunds = orders.get_table("orders").unwrap().clone();
+    refunds.name = "refunds".to_string();
+    refunds.table = "refunds".to_string();
+    let mut returns = orders.get_flow("sales").unwrap().clone();
+    returns.name = "returns".to_string();
+    returns.base_table.semantic_table = "refunds".to_string();
+    returns.base_table.alias = "r".to_string();
+    let registry = FlowRegistry::from_parts(
+        vec![orders.get_table("orders").unwrap().clone(), refunds],
+        vec![orders.get_flow("sales").unwrap().clone(), returns],
+    );
+
+    let mut blend: BlendRequest = serde_json::from_value(serde_json::json!({
+        "dimensions": ["country"],
+        "flows": {
+            "sales": {"measures": ["order_total"]},
+            "returns": {"measures": ["distinct_customers"]},
+        },
+        "order": [{"column": "order_total", "direction": "desc"}],
+        "limit": 10,
+    }))
+    .unwrap();
+    let builder = SqlBuilder::default();
+    let context = QueryContext::default();
+    let sql = build_blend_sql(&builder, &registry, &blend, &context, &DuckDbDialect).unwrap();
+    assert!(
+        sql.starts_with("WITH \"__blend_0\" AS (SELECT"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains(
+            "COALESCE(\"__blend_0\".\"country\", \"__blend_1\".\"country\") AS \"country\""
+        ),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("FROM \"__blend_0\" FULL OUTER JOIN \"__blend_1\" ON \"__blend_0\".\"country\" IS NOT DISTINCT FROM \"__blend_1\".\"country\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.ends_with(" ORDER BY \"order_total\" DESC LIMIT 10"),
+        "sql={sql}"
+    );
+    assert!(sql.contains(" GROUP BY ALL"), "sql={sql}");
+
+    // Each flow is planned with the builder's switches
+    let builder = SqlBuilder::with_planner(PlannerConfig {
+        group_by_all: false,
+        ..Default::default()
+    });
+    let sql = build_blend_sql(&builder, &registry, &blend, &context, &DuckDbDialect).unwrap();
+    assert!(!sql.contains("GROUP BY ALL"), "sql={sql}");
+
+    blend.flows.get_mut("returns").unwrap().measures = vec!["order_total".to_string()];
+    let err = build_blend_sql(&builder, &registry, &blend, &context, &DuckDbDialect).unwrap_err();
+    assert!(err.to_string().contains("collides"), "err={err}");
+}
+
+#[test]
+fn totals_and_subtotals_emit_grouping_sets() {
+    let registry = fixtures::simple_orders_registry();
+    let mut request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country", "month"],
+        "measures": ["order_total"],
+        "totals": true,
+        "subtotals": ["country"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("GROUPING(\"o\".\"country\", date_trunc('month', \"o\".\"created_at\")) AS \"_grouping\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.ends_with(
+            "GROUP BY ROLLUP(\"o\".\"country\", date_trunc('month', \"o\".\"created_at\"))"
+        ),
+        "sql={sql}"
+    );
+
+    request.totals = false;
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.ends_with("GROUP BY GROUPING SETS ((\"o\".\"country\", date_trunc('month', \"o\".\"created_at\")), (\"o\".\"country\"))"),
+        "sql={sql}"
+    );
+
+    for (subtotal, expected) in [
+        ("month", "would repeat the detail rows"),
+        ("amount", "not in the requested dimensions"),
+    ] {
+        request.subtotals = vec![subtotal.to_string()];
+        let err = SqlBuilder::default()
+            .build_with_dialect(&registry, &request, &DuckDbDialect)
+            .unwrap_err();
+        assert!(err.to_string().contains(expected), "err={err}");
+    }
+}
+
+#[test]
+fn calculations_wrap_the_query_in_window_functions() {
+    let registry = fixtures::simple_orders_registry();
+    let mut request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country", "month"],
+        "measures": ["order_total"],
+        "order": [{"column": "month", "direction": "asc"}, {"column": "rank", "direction": "asc"}],
+        "limit": 10,
+        "calculations": [
+            {"name": "rank", "type": "rank", "measure": "order_total", "partition_by": ["month"]},
+            {"name": "growth", "type": "percent_change", "measure": "order_total", "partition_by": ["country"]},
+        ],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.starts_with(
+            "SELECT \"__calc\".*, RANK() OVER (PARTITION BY \"__calc\".\"month\" ORDER BY \"__calc\".\"order_total\" DESC NULLS LAST) AS \"rank\", "
+        ),
+        "sql={sql}"
+    );
+    let previous = "LAG(\"__calc\".\"order_total\") OVER (PARTITION BY \"__calc\".\"country\" ORDER BY \"__calc\".\"month\" ASC)";
+    assert!(
+        sql.contains(&format!(
+            "(\"__calc\".\"order_total\" - {previous}) * 1.0 / NULLIF({previous}, 0) AS \"growth\""
+        )),
+        "sql={sql}"
+    );
+    assert!(
+        sql.ends_with("\"__calc\" ORDER BY \"month\" ASC, \"rank\" ASC LIMIT 10"),
+        "sql={sql}"
+    );
+    // Ordering and limits apply outside the aggregated derived table
+    assert!(
+        !sql.contains("ORDER BY \"month\" ASC, \"rank\" ASC LIMIT 10)"),
+        "sql={sql}"
+    );
+
+    request.calculations[1].measure = "order_count".to_string();
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("not in the requested measures"),
+        "err={err}"
+    );
+}
+
+#[test]
+fn table_default_filters_apply_unless_ignored() {
+    let orders: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "table": "orders",
+        "primary_key": "id",
+        "measures": {"order_total": {"expr": "amount", "agg": "sum"}},
+        "default_filters": ["deleted_at IS NULL", "status != 'test'"],
+    }))
+    .unwrap();
+    let customers: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "customers",
+        "table": "customers",
+        "primary_key": "id",
+        "dimensions": {"country": {"expr": "country"}},
+        "measures": {"customer_count": {"expr": "id", "agg": "count"}},
+        "default_filters": ["deleted_at IS NULL"],
+    }))
+    .unwrap();
+    let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+        "joins": {"customers": {
+            "semantic_table": "customers",
+            "alias": "c",
+            "to_table": "o",
+            "join_type": "left",
+            "join_keys": [{"left": "customer_id", "right": "id"}],
+            "cardinality": "many_to_one",
+        }},
+    }))
+    .unwrap();
+    let registry = FlowRegistry::from_parts(vec![orders, customers], vec![flow]);
+    let mut request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total", "customer_count"],
+    }))
+    .unwrap();
+
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("WHERE (\"o\".\"deleted_at\" IS NULL) AND (\"o\".\"status\" != 'test')"),
+        "sql={sql}"
+    );
+    // The joined table's default filter applies to its measures, not the join
+    assert!(
+        sql.contains("COUNT(\"c\".\"id\") FILTER (WHERE (\"c\".\"deleted_at\" IS NULL))"),
+        "sql={sql}"
+    );
+
+    request.ignore_default_filters = true;
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(!sql.contains("deleted_at"), "sql={sql}");
+}
+
+#[test]
+fn extended_flows_inherit_joins_and_default_filters() {
+    let dir = tempfile::tempdir().unwrap();
+    let tables = dir.path().join("tables");
+    let flows = dir.path().join("flows");
+    std::fs::create_dir_all(&tables).unwrap();
+    std::fs::create_dir_all(&flows).unwrap();
+    std::fs::write(
+        tables.join("orders.yml"),
+        r#"
+name: orders
+data_source: ds1
+table: orders
+primary_key: id
+dimensions: {status: status}
+measures: {order_total: {expr: amount, agg: sum}}
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        tables.join("customers.yml"),
+        r#"
+name: customers
+data_source: ds1
+table: customers
+primary_key: id
+dimensions: {country: country}
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        flows.join("sales.yml"),
+        r#"
+name: sales
+base_table: {semantic_table: orders, alias: o}
+joins:
+  customers:
+    semantic_table: customers
+    alias: c
+    to_table: o
+    join_type: left
+    join_keys: [{left: customer_id, right: id}]
+default_filters:
+  - {field: o.status, op: "!=", value: returned}
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        flows.join("sales_including_returns.yml"),
+        "name: sales_including_returns\nextends: sales\ndefault_filters: []\n",
+    )
+    .unwrap();
+    let registry = FlowRegistry::load_from_dir(dir.path()).expect("load");
+    let child = registry.get_flow("sales_including_returns").unwrap();
+    assert_eq!(child.extends.as_deref(), Some("sales"));
+    assert!(child.joins.contains_key("customers"));
+
+    let mut request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["c.country"],
+        "measures": ["o.order_total"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains("'returned'"), "sql={sql}");
+
+    request.flow = "sales_including_returns".to_string();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains("\"c\".\"country\""), "sql={sql}");
+    assert!(!sql.contains("'returned'"), "sql={sql}");
+
+    request.flow = "sales".to_string();
+    request.ignore_default_filters = true;
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(!sql.contains("'returned'"), "sql={sql}");
+}
+
+#[test]
+fn access_policies_restrict_rows_for_the_query_context() {
+    let orders: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "table": "orders",
+        "primary_key": "id",
+        "measures": {"order_total": {"expr": "amount", "agg": "sum"}},
+        "access_policies": {"region": "region"},
+    }))
+    .unwrap();
+    let customers: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "customers",
+        "table": "customers",
+        "primary_key": "id",
+        "dimensions": {"country": {"expr": "country"}},
+        "access_policies": {"tenant": "tenant_id"},
+    }))
+    .unwrap();
+    let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+        "joins": {"customers": {
+            "semantic_table": "customers",
+            "alias": "c",
+            "to_table": "o",
+            "join_type": "left",
+            "join_keys": [{"left": "customer_id", "right": "id"}],
+            "cardinality": "many_to_one",
+        }},
+    }))
+    .unwrap();
+    let registry = FlowRegistry::from_parts(vec![orders, customers], vec![flow]);
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total"],
+    }))
+    .unwrap();
+
+    let context = QueryContext::default().with_attribute("region", ["EMEA", "APAC"]);
+    let sql = SqlBuilder::default()
+        .build_with_context(&registry, &request, &context, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("WHERE \"o\".\"region\" IN ('EMEA', 'APAC')"),
+        "sql={sql}"
+    );
+    // Without a tenant attribute no customer row matches the join
+    assert!(sql.contains(" AND false WHERE"), "sql={sql}");
+
+    let context = context.with_attribute("tenant", [7]);
+    let sql = SqlBuilder::default()
+        .build_with_context(&registry, &request, &context, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains("\"c\".\"tenant_id\" IN (7)"), "sql={sql}");
+    assert!(!sql.contains("false"), "sql={sql}");
+}
+
+#[test]
+fn derived_tables_read_sql_and_other_flows() {
+    let orders: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "primary_key": "id",
+        "sql": "SELECT DISTINCT * FROM raw_orders",
+        "dimensions": {"customer_id": "customer_id"},
+        "measures": {"order_total": {"expr": "amount", "agg": "sum"}},
+    }))
+    .unwrap();
+    assert_eq!(orders.table, "orders");
+    let revenue: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "customer_revenue",
+        "primary_key": "o__customer_id",
+        "query": {
+            "flow": "sales",
+            "dimensions": ["o.customer_id"],
+            "measures": ["o.order_total"],
+        },
+        "measures": {"lifetime_revenue": {"expr": "o__order_total", "agg": "sum"}},
+    }))
+    .unwrap();
+    let sales: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+    }))
+    .unwrap();
+    let customers: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "customers",
+        "base_table": {"semantic_table": "customer_revenue", "alias": "r"},
+    }))
+    .unwrap();
+    let registry = FlowRegistry::from_parts(vec![orders, revenue], vec![sales, customers]);
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "customers",
+        "measures": ["r.lifetime_revenue"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("(SELECT DISTINCT * FROM raw_orders) \"o\""),
+        "sql={sql}"
+    );
+    assert!(sql.contains(") \"r\""), "sql={sql}");
+    assert!(sql.contains("\"o__customer_id\""), "sql={sql}");
+    assert!(!sql.contains("\"orders\""), "sql={sql}");
+
+    assert!(serde_json::from_value::<SemanticTable>(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "primary_key": "id",
+    }))
+    .is_err());
+}
+
+#[test]
+fn union_tables_append_members_with_mapped_columns() {
+    let table =
+        |value: serde_json::Value| -> SemanticTable { serde_json::from_value(value).unwrap() };
+    let orders_2023 = table(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders_2023",
+        "table": "orders_2023",
+        "primary_key": "id",
+    }));
+    let orders_2024 = table(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders_2024",
+        "sql": "SELECT * FROM raw_orders WHERE year = 2024",
+        "primary_key": "id",
+    }));
+    let orders = table(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "primary_key": "id",
+        "union": {
+            "columns": ["id", "region", "amount"],
+            "tables": [
+                {"semantic_table": "orders_2023"},
+                {
+                    "semantic_table": "orders_2024",
+                    "columns": {"amount": "total_amount", "region": null},
+                },
+            ],
+        },
+        "dimensions": {"region": "region"},
+        "measures": {"order_total": {"expr": "amount", "agg": "sum"}},
+    }));
+    assert_eq!(orders.table, "orders");
+    let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+    }))
+    .unwrap();
+    let registry = FlowRegistry::from_parts(vec![orders_2023, orders_2024, orders], vec![flow]);
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["o.region"],
+        "measures": ["o.order_total"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("(SELECT \"id\", \"region\", \"amount\" FROM \"orders_2023\" UNION ALL "),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("SELECT \"id\", NULL AS \"region\", \"total_amount\" AS \"amount\" FROM (SELECT * FROM raw_orders WHERE year = 2024) \"orders_2024\") \"o\""),
+        "sql={sql}"
+    );
+
+    assert!(serde_json::from_value::<SemanticTable>(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "sql": "SELECT 1",
+        "union": {"columns": ["id"], "tables": [{"semantic_table": "orders_2023"}]},
+    }))
+    .is_err());
+}
+
+#[test]
+fn segments_apply_named_table_and_flow_filters() {
+    let table =
+        |value: serde_json::Value| -> SemanticTable { serde_json::from_value(value).unwrap() };
+    let orders = table(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "table": "orders",
+        "primary_key": "id",
+        "segments": {"completed": "status = 'completed'"},
+        "measures": {"order_total": {"expr": "amount", "agg": "sum"}},
+    }));
+    let customers = table(serde_json::json!({
+        "data_source": "ds1",
+        "name": "customers",
+        "table": "customers",
+        "primary_key": "id",
+        "segments": {"active_customers": "active"},
+        "dimensions": {"country": "country"},
+    }));
+    let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+        "joins": {
+            "customers": {
+                "semantic_table": "customers",
+                "alias": "c",
+                "to_table": "o",
+                "join_type": "left",
+                "join_keys": [{"left": "customer_id", "right": "id"}],
+                "cardinality": "many_to_one",
+            },
+        },
+        "segments": {
+            "eu_only": {"field": "c.country", "op": "in", "value": ["DE", "FR"]},
+        },
+    }))
+    .unwrap();
+    let registry = FlowRegistry::from_parts(vec![orders, customers], vec![flow]);
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "measures": ["o.order_total"],
+        "segments": ["completed", "c.active_customers", "eu_only"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains("\"o\".\"status\" = 'completed'"), "sql={sql}");
+    assert!(sql.contains("\"c\".\"active\""), "sql={sql}");
+    assert!(
+        sql.contains("\"c\".\"country\" IN ('DE', 'FR')"),
+        "sql={sql}"
+    );
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "measures": ["o.order_total"],
+        "segments": ["complete"],
+    }))
+    .unwrap();
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    match &err {
+        SemaflowError::UnknownField {
+            kind, suggestions, ..
+        } => {
+            assert_eq!(*kind, FieldKind::Segment);
+            assert_eq!(suggestions, &vec!["completed".to_string()]);
+        }
+        other => panic!("unexpected error {other:?}"),
+    }
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "measures": ["o.order_total"],
+        "filters": [{"field": "completed", "op": "==", "value": true}],
+    }))
+    .unwrap();
+    let errors = registry.validate_request(&request).unwrap_err();
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert!(
+        errors[0]
+            .to_string()
+            .contains("segments cannot be used as filter fields"),
+        "{errors:?}"
+    );
+}
+
+#[test]
+fn bridge_joins_count_near_side_rows_once_per_group() {
+    let table =
+        |value: serde_json::Value| -> SemanticTable { serde_json::from_value(value).unwrap() };
+    let orders = table(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "table": "orders",
+        "primary_key": "id",
+        "measures": {
+            "order_total": {"expr": "amount", "agg": "sum"},
+            "order_count": {"expr": "id", "agg": "count"},
+        },
+    }));
+    let order_tags = table(serde_json::json!({
+        "data_source": "ds1",
+        "name": "order_tags",
+        "table": "order_tags",
+        "primary_keys": ["order_id", "tag_id"],
+        "dimensions": {"weight": "weight"},
+    }));
+    let tags = table(serde_json::json!({
+        "data_source": "ds1",
+        "name": "tags",
+        "table": "tags",
+        "primary_key": "id",
+        "dimensions": {"category": "category"},
+    }));
+    let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+        "joins": {
+            "order_tags": {
+                "semantic_table": "order_tags",
+                "alias": "ot",
+                "to_table": "o",
+                "join_type": "left",
+                "join_keys": [{"left": "id", "right": "order_id"}],
+            },
+            "tags": {
+                "semantic_table": "tags",
+                "alias": "t",
+                "to_table": "o",
+                "through": "ot",
+                "join_type": "left",
+                "join_keys": [{"left": "tag_id", "right": "id"}],
+            },
+        },
+    }))
+    .unwrap();
+    let mut registry = FlowRegistry::from_parts(vec![orders, order_tags, tags], vec![flow]);
+    assert_eq!(
+        registry.get_flow("sales").unwrap().joins["tags"].to_table,
+        "ot"
+    );
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["t.category"],
+        "measures": ["o.order_total", "o.order_count"],
+    }))
+    .unwrap();
+    let explained = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    let sql = explained.sql;
+    assert_eq!(explained.strategy, PlanStrategy::Symmetric);
+    assert!(sql.contains("LEFT JOIN \"order_tags\" \"ot\""), "sql={sql}");
+    assert!(
+        sql.contains("\"ot\".\"tag_id\" = \"t\".\"id\""),
+        "sql={sql}"
+    );
+    assert!(sql.contains("HASH(\"o\".\"id\")"), "sql={sql}");
+
+    registry
+        .flows
+        .get_mut("sales")
+        .unwrap()
+        .joins
+        .get_mut("tags")
+        .unwrap()
+        .weight = Some("weight".to_string());
+    let explained = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    let sql = explained.sql;
+    assert_eq!(explained.strategy, PlanStrategy::Weighted);
+    assert!(
+        sql.contains("SUM((\"o\".\"amount\" * \"ot\".\"weight\"))"),
+        "sql={sql}"
+    );
+    assert!(sql.contains("THEN \"ot\".\"weight\""), "sql={sql}");
+
+    // Without a dimension beyond the bridge the query stays as it was
+    let request = QueryRequest {
+        dimensions: Vec::new(),
+        ..request
+    };
+    let explained = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert_eq!(explained.strategy, PlanStrategy::Flat);
+    assert!(
+        !explained.sql.contains("order_tags"),
+        "sql={}",
+        explained.sql
+    );
+}
+
+#[test]
+fn role_playing_joins_are_qualified_by_role() {
+    let orders: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "table": "orders",
+        "primary_key": "id",
+        "measures": {"order_total": {"expr": "amount", "agg": "sum"}},
+    }))
+    .unwrap();
+    let dates: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "dates",
+        "table": "dates",
+        "primary_key": "date_id",
+        "dimensions": {"month": "month"},
+    }))
+    .unwrap();
+    let join = |alias: &str, role: &str| {
+        serde_json::json!({
+            "semantic_table": "dates",
+            "alias": alias,
+            "to_table": "o",
+            "role": role,
+            "join_type": "left",
+            "join_keys": [{"left": format!("{role}_id"), "right": "date_id"}],
+        })
+    };
+    let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+        "joins": {"order_date": join("od", "order_date"), "ship_date": join("sd", "ship_date")},
+    }))
+    .unwrap();
+    let registry = FlowRegistry::from_parts(vec![orders, dates], vec![flow]);
+
+    let schema = registry.flow_schema("sales").unwrap();
+    let months: Vec<(&str, &str, Option<&str>)> = schema
+        .dimensions
+        .iter()
+        .map(|d| {
+            (
+                d.qualified_name.as_str(),
+                d.table_alias.as_str(),
+                d.role.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        months,
+        [
+            ("order_date.month", "od", Some("order_date")),
+            ("ship_date.month", "sd", Some("ship_date")),
+        ]
+    );
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["ship_date.month"],
+        "measures": ["o.order_total"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("\"sd\".\"month\" AS \"ship_date__month\""),
+        "sql={sql}"
+    );
+    assert!(!sql.contains("\"od\""), "sql={sql}");
+
+    let request = QueryRequest {
+        dimensions: vec!["month".into()],
+        ..request
+    };
+    match SqlBuilder::default().build_with_dialect(&registry, &request, &DuckDbDialect) {
+        Err(SemaflowError::AmbiguousField { candidates, .. }) => {
+            assert_eq!(candidates, ["order_date", "ship_date"]);
+        }
+        other => panic!("expected an ambiguous field, got {other:?}"),
+    }
+}
+
+#[test]
+fn extra_join_conditions_are_anded_into_the_on_clause() {
+    let orders: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "table": "orders",
+        "primary_key": "id",
+        "time_dimension": "created_at",
+        "measures": {"order_total": {"expr": "amount", "agg": "sum"}},
+    }))
+    .unwrap();
+    let customers: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "customer_history",
+        "table": "customer_history",
+        "primary_keys": ["customer_id", "valid_from"],
+        "dimensions": {"tier": "tier"},
+    }))
+    .unwrap();
+    let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+        "joins": {"customers": {
+            "semantic_table": "customer_history",
+            "alias": "c",
+            "to_table": "o",
+            "join_type": "left",
+            "cardinality": "many_to_one",
+            "join_keys": [{"left": "customer_id", "right": "customer_id"}],
+            "extra_conditions": "o.created_at BETWEEN valid_from AND valid_to",
+        }},
+    }))
+    .unwrap();
+    let registry = FlowRegistry::from_parts(vec![orders, customers], vec![flow]);
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["c.tier"],
+        "measures": ["o.order_total"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains(
+            "ON (\"o\".\"customer_id\" = \"c\".\"customer_id\") AND (\"o\".\"created_at\" \
+             BETWEEN \"c\".\"valid_from\" AND \"c\".\"valid_to\")"
+        ),
+        "sql={sql}"
+    );
+}
+
+#[test]
+fn flow_params_are_validated_and_rendered_as_literals() {
+    let orders: SemanticTable = serde_json::from_value(serde_json::json!({
+        "data_source": "ds1",
+        "name": "orders",
+        "table": "orders",
+        "primary_key": "id",
+        "measures": {"local_revenue": {
+            "expr": "amount",
+            "agg": "sum",
+            "filter": "currency == {{ currency }}",
+        }},
+    }))
+    .unwrap();
+    let flow: SemanticFlow = serde_json::from_value(serde_json::json!({
+        "name": "sales",
+        "base_table": {"semantic_table": "orders", "alias": "o"},
+        "params": {"currency": {
+            "type": "string",
+            "default": "USD",
+            "allowed_values": ["USD", "EUR"],
+        }},
+    }))
+    .unwrap();
+    let registry = FlowRegistry::from_parts(vec![orders], vec![flow]);
+    let request = |params: serde_json::Value| -> QueryRequest {
+        serde_json::from_value(serde_json::json!({
+            "flow": "sales",
+            "measures": ["local_revenue"],
+            "params": params,
+        }))
+        .unwrap()
+    };
+    let build = |request: &QueryRequest| {
+        SqlBuilder::default().build_with_dialect(&registry, request, &DuckDbDialect)
+    };
+
+    let sql = build(&request(serde_json::json!({}))).unwrap();
+    assert!(sql.contains("\"o\".\"currency\" = 'USD'"), "sql={sql}");
+    let sql = build(&request(serde_json::json!({"currency": "EUR"}))).unwrap();
+    assert!(sql.contains("\"o\".\"currency\" = 'EUR'"), "sql={sql}");
+
+    for params in [
+        serde_json::json!({"currency": "GBP"}),
+        serde_json::json!({"currency": 3}),
+        serde_json::json!({"fx_rate": 1.1}),
+    ] {
+        let err = build(&request(params.clone())).unwrap_err().to_string();
+        assert!(err.contains("param"), "params={params} err={err}");
+    }
+}
+
+#[test]
+fn fill_gaps_requires_one_time_grained_dimension() {
+    let registry = fixtures::simple_orders_registry();
+    let build = |value: serde_json::Value| {
+        let request: QueryRequest = serde_json::from_value(value).unwrap();
+        SqlBuilder::default()
+            .build_with_dialect(&registry, &request, &DuckDbDialect)
+            .unwrap_err()
+            .to_string()
+    };
+    let err = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total"],
+        "fill_gaps": true,
+    }));
+    assert!(err.contains("time grain"), "{err}");
+
+    let err = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["month", {"field": "created_at", "grain": "day"}],
+        "measures": ["order_total"],
+        "fill_gaps": true,
+    }));
+    assert!(err.contains("single time dimension"), "{err}");
+
+    let err = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["month"],
+        "measures": ["order_total"],
+        "gap_fill": {"order_total": "zero"},
+    }));
+    assert!(err.contains("requires fill_gaps"), "{err}");
+}
+
+#[test]
+fn grain_override_truncates_time_dimension() {
+    let mut registry = fixtures::simple_orders_registry();
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": [{"field": "created_at", "grain": "week"}, "country"],
+        "measures": ["order_total"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("date_trunc('week', \"o\".\"created_at\") AS \"created_at\""),
+        "sql={sql}"
+    );
+
+    let hourly: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": [{"field": "created_at", "grain": "hour"}],
+        "measures": ["order_total"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &hourly, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("date_trunc('hour', \"o\".\"created_at\") AS \"created_at\""),
+        "sql={sql}"
+    );
+
+    registry
+        .tables
+        .get_mut("orders")
+        .unwrap()
+        .smallest_time_grain = Some(semaflow::flows::TimeGrain::Month);
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(err.to_string().contains("smallest_time_grain"), "{err}");
+    assert!(err.to_string().contains("use 'month' or coarser"), "{err}");
+
+    // Dimensions truncating finer than the table allows are rejected too
+    registry
+        .tables
+        .get_mut("orders")
+        .unwrap()
+        .dimensions
+        .insert(
+            "created_hour".to_string(),
+            semaflow::flows::Dimension {
+                expr: Expr::Func {
+                    func: Function::DateTrunc(semaflow::flows::TimeGrain::Hour),
+                    args: vec![Expr::Column {
+                        column: "created_at".to_string(),
+                    }],
+                },
+                data_type: None,
+                description: None,
+                tags: Vec::new(),
+                synonyms: Vec::new(),
+                folder: None,
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
+                unnest: false,
+                labels: Default::default(),
+            },
+        );
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["created_hour"],
+        "measures": ["order_total"],
+    }))
+    .unwrap();
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("grain 'hour' for created_hour"),
+        "{err}"
+    );
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": [{"field": "country", "grain": "month"}],
+        "measures": ["order_total"],
+    }))
+    .unwrap();
+    let err = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap_err();
+    assert!(err.to_string().contains("time_dimension"), "{err}");
+}
+
+#[test]
+fn fiscal_grains_follow_flow_calendar() {
+    let mut registry = fixtures::simple_orders_registry();
+    let request = |grain: &str| -> QueryRequest {
+        serde_json::from_value(serde_json::json!({
+            "flow": "sales",
+            "dimensions": [{"field": "created_at", "grain": grain}],
+            "measures": ["order_total"],
+        }))
+        .unwrap()
+    };
+    let build = |registry: &FlowRegistry, grain: &str| {
+        SqlBuilder::default()
+            .build_with_dialect(registry, &request(grain), &DuckDbDialect)
+            .unwrap()
+    };
+
+    // Without a calendar fiscal years are calendar years
+    let sql = build(&registry, "fiscal_year");
+    assert!(
+        sql.contains("CAST(date_trunc('year', CAST(\"o\".\"created_at\" AS DATE)) AS DATE)"),
+        "sql={sql}"
+    );
 
-// ============================================================================
-// Basic query building tests
-// ============================================================================
+    registry.flows.get_mut("sales").unwrap().fiscal_calendar =
+        Some(serde_json::from_value(serde_json::json!({"start_month": 4})).unwrap());
+    let sql = build(&registry, "fiscal_quarter");
+    assert!(
+        sql.contains(
+            "CAST(date_trunc('quarter', CAST(\"o\".\"created_at\" AS DATE) - INTERVAL '3 month') \
+             + INTERVAL '3 month' AS DATE) AS \"created_at\""
+        ),
+        "sql={sql}"
+    );
+
+    // 4-4-5 months start 4, 8 and 13 weeks into each quarter
+    registry.flows.get_mut("sales").unwrap().fiscal_calendar = Some(
+        serde_json::from_value(serde_json::json!({"start_month": 4, "week_pattern": "4-4-5"}))
+            .unwrap(),
+    );
+    let sql = build(&registry, "fiscal_month");
+    assert!(sql.contains(">= 329 THEN 329"), "sql={sql}");
+    assert!(sql.contains(">= 28 THEN 28 ELSE 0 END"), "sql={sql}");
+    let sql = build(&registry, "fiscal_quarter");
+    assert!(sql.contains(">= 273 THEN 273"), "sql={sql}");
+}
 
 #[test]
-fn build_with_functions_and_distinct() {
-    let registry = fixtures::simple_orders_registry();
-    let request = QueryRequest {
-        flow: "sales".to_string(),
-        dimensions: vec!["month".to_string()],
-        measures: vec!["distinct_customers".to_string()],
-        filters: vec![],
-        order: vec![],
-        limit: None,
-        offset: None,
-        ..Default::default()
-    };
-    let sql = SqlBuilder::default()
-        .build_with_dialect(&registry, &request, &DuckDbDialect)
+fn time_grains_truncate_in_configured_timezone() {
+    let mut registry = fixtures::simple_orders_registry();
+    let build = |registry: &FlowRegistry, timezone: Option<&str>| {
+        let mut request: QueryRequest = serde_json::from_value(serde_json::json!({
+            "flow": "sales",
+            "dimensions": [{"field": "created_at", "grain": "day"}],
+            "measures": ["order_total"],
+        }))
         .unwrap();
-    assert!(sql.contains("date_trunc('month'"));
-    assert!(sql.contains("COUNT(DISTINCT"));
-    assert!(sql.contains("FROM \"orders\" \"o\""));
+        request.timezone = timezone.map(str::to_string);
+        SqlBuilder::default()
+            .build_with_dialect(registry, &request, &DuckDbDialect)
+            .unwrap()
+    };
+
+    let sql = build(&registry, None);
+    assert!(!sql.contains("AT TIME ZONE"), "sql={sql}");
+
+    registry.tables.get_mut("orders").unwrap().timezone = Some("Europe/Berlin".to_string());
+    let sql = build(&registry, None);
+    assert!(
+        sql.contains("date_trunc('day', (\"o\".\"created_at\" AT TIME ZONE 'Europe/Berlin'))"),
+        "sql={sql}"
+    );
+
+    // The flow's zone overrides the table's, the request's both
+    registry.flows.get_mut("sales").unwrap().timezone = Some("UTC".to_string());
+    let sql = build(&registry, None);
+    assert!(sql.contains("AT TIME ZONE 'UTC'"), "sql={sql}");
+    let sql = build(&registry, Some("America/New_York"));
+    assert!(sql.contains("AT TIME ZONE 'America/New_York'"), "sql={sql}");
 }
 
 #[test]
-fn accepts_alias_qualified_fields() {
-    let registry = fixtures::orders_with_customers_registry();
+fn ratio_measures_divide_filtered_aggregates() {
+    let mut registry = fixtures::simple_orders_registry();
+    let share: Measure = serde_json::from_value(serde_json::json!({
+        "ratio": {
+            "numerator": {"expr": "amount", "filter": "country == 'US'"},
+            "denominator": {"expr": "amount"},
+        }
+    }))
+    .unwrap();
+    assert_eq!(
+        share.formula.as_ref().unwrap().raw,
+        "safe_divide(sum(amount) filter (where country == 'US'), sum(amount))"
+    );
+    registry
+        .tables
+        .get_mut("orders")
+        .unwrap()
+        .measures
+        .insert("us_share".to_string(), share);
+
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["c.country".to_string()],
-        measures: vec!["o.order_total".to_string()],
-        filters: vec![],
-        order: vec![],
-        limit: None,
-        offset: None,
+        dimensions: vec!["country".into()],
+        measures: vec!["us_share".to_string()],
         ..Default::default()
     };
     let sql = SqlBuilder::default()
         .build_with_dialect(&registry, &request, &DuckDbDialect)
         .unwrap();
-    assert!(sql.contains("\"c\".\"country\""));
-    assert!(sql.contains("\"o\".\"amount\""));
+    assert!(
+        sql.contains("SUM(\"o\".\"amount\") FILTER (WHERE (\"o\".\"country\" = 'US'))"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("/ NULLIF(SUM(\"o\".\"amount\"), 0)"),
+        "sql={sql}"
+    );
+
+    let err = serde_json::from_value::<Measure>(serde_json::json!({
+        "ratio": {"numerator": {"expr": "amount"}, "denominator": {"expr": "amount"}},
+        "formula": "sum(amount)",
+    }))
+    .unwrap_err();
+    assert!(err.to_string().contains("'ratio' and 'formula'"), "{err}");
 }
 
 // ============================================================================
@@ -495,13 +2587,14 @@ fn measure_filters_rejected() {
     let registry = fixtures::simple_orders_registry();
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["country".to_string()],
+        dimensions: vec!["country".into()],
         measures: vec!["order_total".to_string()],
         filters: vec![semaflow::flows::Filter {
             field: "order_total".to_string(),
             op: semaflow::flows::FilterOp::Eq,
             value: serde_json::json!(1),
-        }],
+        }
+        .into()],
         order: vec![],
         limit: None,
         offset: None,
@@ -525,9 +2618,15 @@ fn unqualified_fields_error_when_ambiguous() {
         data_source: "ds1".to_string(),
         name: "customers".to_string(),
         table: "customers".to_string(),
+        sql: None,
+        query: None,
+        union: None,
         primary_keys: vec!["id".to_string()],
+        unique_keys: Vec::new(),
+        nullable: Default::default(),
         time_dimension: None,
         smallest_time_grain: None,
+        timezone: None,
         dimensions: [(
             "country".to_string(),
             semaflow::flows::Dimension {
@@ -536,12 +2635,26 @@ fn unqualified_fields_error_when_ambiguous() {
                 },
                 data_type: None,
                 description: None,
+                tags: Vec::new(),
+                synonyms: Vec::new(),
+                folder: None,
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
+                unnest: false,
+                labels: Default::default(),
             },
         )]
         .into_iter()
         .collect(),
         measures: Default::default(),
         description: None,
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        access_policies: Default::default(),
+        hierarchies: Default::default(),
+        entities: Default::default(),
     };
 
     let flow = SemanticFlow {
@@ -562,12 +2675,28 @@ fn unqualified_fields_error_when_ambiguous() {
                     right: "id".to_string(),
                 }],
                 cardinality: None,
+                through: None,
+                weight: None,
+                role: None,
+                extra_conditions: None,
                 description: None,
             },
         )]
         .into_iter()
         .collect(),
         description: None,
+        params: Default::default(),
+        tags: Vec::new(),
+        synonyms: Vec::new(),
+        limits: Default::default(),
+        aggregates: Default::default(),
+        fiscal_calendar: None,
+        timezone: None,
+        fanout_strategy: Default::default(),
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        extends: None,
+        deprecated: None,
     };
 
     registry.tables.insert(customers.name.clone(), customers);
@@ -575,7 +2704,7 @@ fn unqualified_fields_error_when_ambiguous() {
 
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["country".to_string()],
+        dimensions: vec!["country".into()],
         measures: vec![],
         filters: vec![],
         order: vec![],
@@ -587,10 +2716,12 @@ fn unqualified_fields_error_when_ambiguous() {
         .build_with_dialect(&registry, &request, &DuckDbDialect)
         .unwrap_err();
     match err {
-        SemaflowError::Validation(msg) => {
-            assert!(msg.contains("ambiguous"));
-            assert!(msg.contains("o"));
-            assert!(msg.contains("c"));
+        SemaflowError::AmbiguousField {
+            kind, candidates, ..
+        } => {
+            assert_eq!(kind, FieldKind::Dimension);
+            assert!(candidates.contains(&"o".to_string()));
+            assert!(candidates.contains(&"c".to_string()));
         }
         other => panic!("unexpected error {other:?}"),
     }
@@ -604,9 +2735,15 @@ fn supports_measures_from_multiple_tables() {
         data_source: "ds1".to_string(),
         name: "customers".to_string(),
         table: "customers".to_string(),
+        sql: None,
+        query: None,
+        union: None,
         primary_keys: vec!["id".to_string()],
+        unique_keys: Vec::new(),
+        nullable: Default::default(),
         time_dimension: None,
         smallest_time_grain: None,
+        timezone: None,
         dimensions: [].into_iter().collect(),
         measures: [(
             "customer_count".to_string(),
@@ -620,11 +2757,23 @@ fn supports_measures_from_multiple_tables() {
                 post_expr: None,
                 data_type: None,
                 description: None,
+                tags: Vec::new(),
+                synonyms: Vec::new(),
+                folder: None,
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
             },
         )]
         .into_iter()
         .collect(),
         description: None,
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        access_policies: Default::default(),
+        hierarchies: Default::default(),
+        entities: Default::default(),
     };
 
     let flow = SemanticFlow {
@@ -645,12 +2794,28 @@ fn supports_measures_from_multiple_tables() {
                     right: "id".to_string(),
                 }],
                 cardinality: None,
+                through: None,
+                weight: None,
+                role: None,
+                extra_conditions: None,
                 description: None,
             },
         )]
         .into_iter()
         .collect(),
         description: None,
+        params: Default::default(),
+        tags: Vec::new(),
+        synonyms: Vec::new(),
+        limits: Default::default(),
+        aggregates: Default::default(),
+        fiscal_calendar: None,
+        timezone: None,
+        fanout_strategy: Default::default(),
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        extends: None,
+        deprecated: None,
     };
 
     registry.tables.insert(customers.name.clone(), customers);
@@ -689,6 +2854,162 @@ fn supports_measures_from_multiple_tables() {
         sql.contains("LEFT JOIN"),
         "should join CTEs with flow join type; sql={sql}"
     );
+
+    let plan = SqlBuilder::default().explain(&registry, &request).unwrap();
+    assert_eq!(plan.strategy, PlanStrategy::PreAggregated);
+    let ctes: Vec<&str> = plan.ctes.iter().map(|c| c.name.as_str()).collect();
+    assert!(ctes.contains(&"o_agg") && ctes.contains(&"c_agg"), "{plan}");
+    assert!(plan
+        .measures
+        .iter()
+        .all(|m| m.strategy == Some(MeasureStrategy::PreAggregatable)));
+
+    // COUNT DISTINCT pre-aggregates as sketches merged in the final query
+    let request = QueryRequest {
+        measures: vec![
+            "o.distinct_customers".to_string(),
+            "c.customer_count".to_string(),
+        ],
+        ..request
+    };
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("LIST(DISTINCT \"o\".\"customer_id\") AS \"distinct_customers\""),
+        "should build a distinct sketch in the CTE; sql={sql}"
+    );
+    assert!(
+        sql.contains("LEN(LIST_DISTINCT(FLATTEN(LIST(\"o_agg\".\"distinct_customers\"))))"),
+        "should merge the sketches in the final query; sql={sql}"
+    );
+
+    // Without sketches, distinct counts are joined back from a flat subquery
+    #[cfg(feature = "postgres")]
+    {
+        let sql = SqlBuilder::default()
+            .build_with_dialect(&registry, &request, &PostgresDialect::new("public"))
+            .unwrap();
+        assert!(
+            sql.contains("COUNT(DISTINCT \"o\".\"customer_id\") AS \"o__distinct_customers\""),
+            "should count distinct in a flat subquery; sql={sql}"
+        );
+        assert!(
+            sql.contains("\"__distinct\" ON true"),
+            "should join the subquery back; sql={sql}"
+        );
+        assert!(
+            sql.contains("MAX(\"__distinct\".\"o__distinct_customers\")"),
+            "should select the joined distinct count; sql={sql}"
+        );
+    }
+
+    // Symmetric aggregates keep the same request flat
+    registry.flows.get_mut("sales").unwrap().fanout_strategy = FanoutStrategy::Symmetric;
+    let request = QueryRequest {
+        measures: vec!["o.order_total".to_string(), "c.customer_count".to_string()],
+        ..request
+    };
+    let explained = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    let sql = explained.sql;
+    assert_eq!(explained.strategy, PlanStrategy::Symmetric);
+    assert!(
+        !sql.contains("o_agg"),
+        "should not pre-aggregate; sql={sql}"
+    );
+    assert!(
+        sql.contains("SUM(DISTINCT CAST(HASH(\"o\".\"id\") AS HUGEINT) * 100000000"),
+        "should sum over distinct order keys; sql={sql}"
+    );
+    assert!(
+        sql.contains("COUNT(DISTINCT CASE WHEN"),
+        "should count distinct customer keys; sql={sql}"
+    );
+
+    // Dialects without symmetric sums pre-aggregate instead
+    let explained = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &PlainDialect)
+        .unwrap();
+    assert_eq!(explained.strategy, PlanStrategy::PreAggregated);
+}
+
+#[test]
+fn multi_table_measures_join_lookup_tables_to_the_base_cte() {
+    let registry = fixtures::payments_registry();
+
+    // Customers are read only by the filter: the base CTE keeps the key
+    // they join on and the final query joins them
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "measures": ["o.order_total", "p.paid_total"],
+        "filters": [{"field": "c.country", "op": "==", "value": "UK"}],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("\"o\".\"customer_id\" AS \"customer_id\""),
+        "base CTE should keep the customers join key; sql={sql}"
+    );
+    assert!(
+        sql.contains("LEFT JOIN \"customers\" \"c\" ON (\"o_agg\".\"customer_id\" = \"c\".\"id\")"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("WHERE (\"c\".\"country\" = 'UK')"),
+        "sql={sql}"
+    );
+
+    // Customers are read only by the dimension
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["c.country"],
+        "measures": ["o.order_total", "p.paid_total"],
+    }))
+    .unwrap();
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("\"o\".\"customer_id\" AS \"customer_id\""),
+        "base CTE should keep the customers join key; sql={sql}"
+    );
+    assert!(
+        sql.contains("LEFT JOIN \"customers\" \"c\" ON (\"o_agg\".\"customer_id\" = \"c\".\"id\")"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("SELECT \"c\".\"country\" AS \"c__country\""),
+        "sql={sql}"
+    );
+}
+
+#[test]
+fn multi_table_measures_build_the_base_cte_without_base_measures() {
+    let registry = fixtures::payments_registry();
+
+    // The customer and payment CTEs both join to the base CTE
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "measures": ["c.customer_count", "p.paid_total"],
+    }))
+    .unwrap();
+    let plan = SqlBuilder::default().explain(&registry, &request).unwrap();
+    assert_eq!(plan.strategy, PlanStrategy::PreAggregated);
+    let ctes: Vec<&str> = plan.ctes.iter().map(|c| c.name.as_str()).collect();
+    for cte in ["o_agg", "c_agg", "p_agg"] {
+        assert!(ctes.contains(&cte), "{plan}");
+    }
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains("FROM \"orders\" \"o\" GROUP BY ALL) \"o_agg\" LEFT JOIN"),
+        "sql={sql}"
+    );
 }
 
 // ============================================================================
@@ -700,7 +3021,7 @@ fn prunes_all_joins_when_only_base_fields_used() {
     let registry = fixtures::chain_registry();
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["o.amount".to_string()],
+        dimensions: vec!["o.amount".into()],
         measures: vec!["o.order_total".to_string()],
         filters: vec![],
         order: vec![],
@@ -722,7 +3043,7 @@ fn includes_only_needed_join_for_single_hop_dimension() {
     let registry = fixtures::chain_registry();
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["c.country".to_string()],
+        dimensions: vec!["c.country".into()],
         measures: vec!["o.order_total".to_string()],
         filters: vec![],
         order: vec![],
@@ -745,10 +3066,20 @@ fn includes_only_needed_join_for_single_hop_dimension() {
 
 #[test]
 fn includes_dependency_chain_for_deeper_dimension() {
-    let registry = fixtures::chain_registry();
+    let mut registry = fixtures::chain_registry();
+    // A smaller table is still joined after the join it depends on
+    for (table, row_count) in [("customers", 1_000), ("regions", 5)] {
+        registry.stats.insert(
+            table.to_string(),
+            TableStats {
+                row_count,
+                ..Default::default()
+            },
+        );
+    }
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["r.region".to_string()],
+        dimensions: vec!["r.region".into()],
         measures: vec!["o.order_total".to_string()],
         filters: vec![],
         order: vec![],
@@ -781,7 +3112,7 @@ fn keeps_inner_join_when_unused() {
     }
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["o.amount".to_string()],
+        dimensions: vec!["o.amount".into()],
         measures: vec!["o.order_total".to_string()],
         filters: vec![],
         order: vec![],
@@ -798,6 +3129,26 @@ fn keeps_inner_join_when_unused() {
     );
 }
 
+#[test]
+fn prunes_unrequested_one_to_many_left_join() {
+    let registry = fixtures::payments_registry();
+
+    // Joining payments would repeat each order once per payment
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["c.country"],
+        "measures": ["o.order_total"],
+    }))
+    .unwrap();
+    let explained = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    let sql = explained.sql;
+    assert_eq!(explained.strategy, PlanStrategy::Flat);
+    assert!(sql.contains("LEFT JOIN \"customers\" \"c\""), "sql={sql}");
+    assert!(!sql.contains("payments"), "sql={sql}");
+}
+
 // ============================================================================
 // Cardinality and pre-aggregation tests
 // ============================================================================
@@ -809,9 +3160,15 @@ fn uses_flat_query_for_many_to_one_join_filter() {
         data_source: "ds1".to_string(),
         name: "customers".to_string(),
         table: "customers".to_string(),
+        sql: None,
+        query: None,
+        union: None,
         primary_keys: vec!["id".to_string()],
+        unique_keys: Vec::new(),
+        nullable: Default::default(),
         time_dimension: None,
         smallest_time_grain: None,
+        timezone: None,
         dimensions: [(
             "customer_country".to_string(),
             semaflow::flows::Dimension {
@@ -820,12 +3177,26 @@ fn uses_flat_query_for_many_to_one_join_filter() {
                 },
                 data_type: None,
                 description: None,
+                tags: Vec::new(),
+                synonyms: Vec::new(),
+                folder: None,
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
+                unnest: false,
+                labels: Default::default(),
             },
         )]
         .into_iter()
         .collect(),
         measures: Default::default(),
         description: None,
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        access_policies: Default::default(),
+        hierarchies: Default::default(),
+        entities: Default::default(),
     };
 
     let flow = SemanticFlow {
@@ -846,12 +3217,28 @@ fn uses_flat_query_for_many_to_one_join_filter() {
                     right: "id".to_string(), // Joining to customers.id (PK) = ManyToOne
                 }],
                 cardinality: None,
+                through: None,
+                weight: None,
+                role: None,
+                extra_conditions: None,
                 description: None,
             },
         )]
         .into_iter()
         .collect(),
         description: None,
+        params: Default::default(),
+        tags: Vec::new(),
+        synonyms: Vec::new(),
+        limits: Default::default(),
+        aggregates: Default::default(),
+        fiscal_calendar: None,
+        timezone: None,
+        fanout_strategy: Default::default(),
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        extends: None,
+        deprecated: None,
     };
 
     registry.tables.insert(customers.name.clone(), customers);
@@ -859,13 +3246,14 @@ fn uses_flat_query_for_many_to_one_join_filter() {
 
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["customer_country".to_string()],
+        dimensions: vec!["customer_country".into()],
         measures: vec!["order_total".to_string()],
         filters: vec![semaflow::flows::Filter {
             field: "customer_country".to_string(),
             op: semaflow::flows::FilterOp::Eq,
             value: serde_json::json!("US"),
-        }],
+        }
+        .into()],
         order: vec![],
         limit: None,
         offset: None,
@@ -897,9 +3285,15 @@ fn preaggregates_when_join_cardinality_unknown() {
         data_source: "ds1".to_string(),
         name: "customers".to_string(),
         table: "customers".to_string(),
+        sql: None,
+        query: None,
+        union: None,
         primary_keys: vec!["id".to_string()],
+        unique_keys: Vec::new(),
+        nullable: Default::default(),
         time_dimension: None,
         smallest_time_grain: None,
+        timezone: None,
         dimensions: [(
             "customer_country".to_string(),
             semaflow::flows::Dimension {
@@ -908,12 +3302,26 @@ fn preaggregates_when_join_cardinality_unknown() {
                 },
                 data_type: None,
                 description: None,
+                tags: Vec::new(),
+                synonyms: Vec::new(),
+                folder: None,
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
+                unnest: false,
+                labels: Default::default(),
             },
         )]
         .into_iter()
         .collect(),
         measures: Default::default(),
         description: None,
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        access_policies: Default::default(),
+        hierarchies: Default::default(),
+        entities: Default::default(),
     };
 
     let flow = SemanticFlow {
@@ -934,12 +3342,28 @@ fn preaggregates_when_join_cardinality_unknown() {
                     right: "external_id".to_string(), // NOT the PK - unknown cardinality
                 }],
                 cardinality: None,
+                through: None,
+                weight: None,
+                role: None,
+                extra_conditions: None,
                 description: None,
             },
         )]
         .into_iter()
         .collect(),
         description: None,
+        params: Default::default(),
+        tags: Vec::new(),
+        synonyms: Vec::new(),
+        limits: Default::default(),
+        aggregates: Default::default(),
+        fiscal_calendar: None,
+        timezone: None,
+        fanout_strategy: Default::default(),
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        extends: None,
+        deprecated: None,
     };
 
     registry.tables.insert(customers.name.clone(), customers);
@@ -947,13 +3371,14 @@ fn preaggregates_when_join_cardinality_unknown() {
 
     let request = QueryRequest {
         flow: "sales".to_string(),
-        dimensions: vec!["customer_country".to_string()],
+        dimensions: vec!["customer_country".into()],
         measures: vec!["order_total".to_string()],
         filters: vec![semaflow::flows::Filter {
             field: "customer_country".to_string(),
             op: semaflow::flows::FilterOp::Eq,
             value: serde_json::json!("US"),
-        }],
+        }
+        .into()],
         order: vec![],
         limit: None,
         offset: None,
@@ -973,6 +3398,153 @@ fn preaggregates_when_join_cardinality_unknown() {
         sql.contains("WHERE") && sql.contains("country") && sql.contains("'US'"),
         "dimension filter should be applied as WHERE clause; sql={sql}"
     );
+
+    // Statistics showing external_id to be unique make the join many-to-one
+    registry.stats.insert(
+        "customers".to_string(),
+        TableStats {
+            row_count: 100,
+            distinct_counts: [("external_id".to_string(), 100)].into_iter().collect(),
+        },
+    );
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        !sql.contains("FROM (SELECT"),
+        "unique join key should allow a flat query; sql={sql}"
+    );
+}
+
+#[test]
+fn explain_reports_one_to_many_join_in_flat_plan() {
+    let mut registry = fixtures::orders_with_customers_registry();
+    let set_cardinality = |registry: &mut FlowRegistry, cardinality| {
+        registry
+            .flows
+            .get_mut("sales")
+            .unwrap()
+            .joins
+            .get_mut("customers")
+            .unwrap()
+            .cardinality = Some(cardinality);
+    };
+    set_cardinality(&mut registry, JoinCardinality::OneToMany);
+
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["order_total".to_string()],
+        ..Default::default()
+    };
+    let explain = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert_eq!(explain.strategy, PlanStrategy::Flat);
+    assert_eq!(explain.diagnostics.len(), 1, "{:?}", explain.diagnostics);
+    let diagnostic = &explain.diagnostics[0];
+    assert_eq!(diagnostic.join_alias, "c");
+    assert_eq!(diagnostic.from_alias, "o");
+    assert_eq!(diagnostic.cardinality, JoinCardinality::OneToMany);
+    assert!(diagnostic.hinted);
+    assert!(diagnostic.message.contains("order_total"));
+
+    set_cardinality(&mut registry, JoinCardinality::ManyToOne);
+    let explain = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(explain.diagnostics.is_empty());
+}
+
+#[test]
+fn request_strategy_forces_or_rejects_plans() {
+    let mut registry = fixtures::orders_with_customers_registry();
+    registry
+        .flows
+        .get_mut("sales")
+        .unwrap()
+        .joins
+        .get_mut("customers")
+        .unwrap()
+        .cardinality = Some(JoinCardinality::OneToMany);
+    let mut request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["country"],
+        "measures": ["order_total"],
+    }))
+    .unwrap();
+    let strategy = |request: &QueryRequest| {
+        SqlBuilder::default()
+            .explain_with_dialect(&registry, request, &DuckDbDialect)
+            .map(|explain| explain.strategy)
+    };
+    assert_eq!(strategy(&request).unwrap(), PlanStrategy::Flat);
+
+    request.strategy = StrategyOverride::PreAggregate;
+    assert_eq!(strategy(&request).unwrap(), PlanStrategy::PreAggregated);
+
+    // Filtering on the one-to-many side would double count a flat sum
+    request.filters = vec![Filter {
+        field: "country".to_string(),
+        op: FilterOp::Eq,
+        value: serde_json::json!("US"),
+    }
+    .into()];
+    request.strategy = StrategyOverride::Auto;
+    assert_eq!(strategy(&request).unwrap(), PlanStrategy::PreAggregated);
+    request.strategy = StrategyOverride::Flat;
+    let err = strategy(&request).unwrap_err().to_string();
+    assert!(err.contains("strategy 'flat' is unsafe"), "{err}");
+
+    // A single-table request has nothing to pre-aggregate below
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "measures": ["order_total"],
+        "strategy": "preagg",
+    }))
+    .unwrap();
+    let err = strategy(&request).unwrap_err().to_string();
+    assert!(err.contains("strategy 'preagg' can't be applied"), "{err}");
+}
+
+#[test]
+fn explain_reports_pruned_joins_and_filter_placement() {
+    let registry = fixtures::orders_with_customers_registry();
+    let mut request = QueryRequest {
+        flow: "sales".to_string(),
+        measures: vec!["o.order_total".to_string()],
+        ..Default::default()
+    };
+    let plan = SqlBuilder::default().explain(&registry, &request).unwrap();
+    assert_eq!(plan.strategy, PlanStrategy::Flat);
+    assert!(plan.ctes.is_empty());
+    assert_eq!(plan.joins.len(), 1);
+    assert!(
+        !plan.joins[0].included,
+        "unused left join on the primary key is pruned"
+    );
+    assert_eq!(plan.measures[0].strategy, None);
+
+    request.filters = vec![Filter {
+        field: "c.country".to_string(),
+        op: FilterOp::Eq,
+        value: serde_json::json!("US"),
+    }
+    .into()];
+    let explain = SqlBuilder::default()
+        .explain_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    let plan = &explain.plan;
+    assert!(plan.joins[0].included);
+    assert_eq!(plan.filters.len(), 1);
+    assert_eq!(plan.filters[0].filter, "c.country == \"US\"");
+    assert_eq!(plan.filters[0].placement, FilterPlacement::Where);
+    let text = plan.to_string();
+    assert!(
+        text.contains("left join c (customers) to o: included"),
+        "{text}"
+    );
+    assert!(text.contains("c.country == \"US\" -> where"), "{text}");
 }
 
 // ============================================================================
@@ -1063,6 +3635,50 @@ fn falls_back_to_case_when_filter_not_supported() {
     );
 }
 
+#[test]
+fn planner_config_switches_off_dialect_features() {
+    let registry = fixtures::measures_registry();
+    let request = QueryRequest {
+        flow: "sales".to_string(),
+        dimensions: vec!["country".into()],
+        measures: vec!["us_amount".to_string()],
+        ..Default::default()
+    };
+    let builder = SqlBuilder::with_planner(PlannerConfig {
+        filtered_aggregates: false,
+        group_by_all: false,
+        ..Default::default()
+    });
+    let sql = builder
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(
+        sql.contains(
+            "SUM(CASE WHEN (\"o\".\"country\" = 'US') THEN \"o\".\"amount\" ELSE NULL END)"
+        ),
+        "sql={sql}"
+    );
+    assert!(!sql.contains("GROUP BY ALL"), "sql={sql}");
+
+    let sql = SqlBuilder::default()
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains(" FILTER (WHERE "), "sql={sql}");
+    assert!(sql.contains(" GROUP BY ALL"), "sql={sql}");
+
+    // The flow's data source picks its switches from the configuration
+    let config = semaflow::config::SemaflowConfig::from_toml(
+        "[datasources.ds1.planner]\ngroup_by_all = false\n",
+    )
+    .unwrap();
+    let connections = semaflow::backends::ConnectionManager::with_config(config);
+    let sql = SqlBuilder::for_request(&registry, &connections, &request)
+        .build_with_dialect(&registry, &request, &DuckDbDialect)
+        .unwrap();
+    assert!(sql.contains(" FILTER (WHERE "), "sql={sql}");
+    assert!(!sql.contains("GROUP BY ALL"), "sql={sql}");
+}
+
 // ============================================================================
 // PostgreSQL Dialect Tests
 // ============================================================================
@@ -1076,7 +3692,7 @@ mod postgres_tests {
         let registry = fixtures::simple_orders_registry();
         let request = QueryRequest {
             flow: "sales".to_string(),
-            dimensions: vec!["country".to_string()],
+            dimensions: vec!["country".into()],
             measures: vec!["order_total".to_string()],
             filters: vec![],
             order: vec![],
@@ -1096,6 +3712,32 @@ mod postgres_tests {
         assert!(sql.contains("LIMIT 10"));
     }
 
+    #[test]
+    fn postgres_bins_with_width_bucket() {
+        let registry = fixtures::simple_orders_registry();
+        let request = QueryRequest {
+            flow: "sales".to_string(),
+            dimensions: vec!["amount".into()],
+            measures: vec!["order_total".to_string()],
+            bins: [(
+                "amount".to_string(),
+                BucketSpec::Edges(vec![0.0, 10.0, 50.0]),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let sql = SqlBuilder::default()
+            .build_with_dialect(&registry, &request, &PostgresDialect::new("public"))
+            .unwrap();
+        assert!(
+            sql.contains(
+                "(ARRAY['< 0', '[0-10)', '[10-50)', '>= 50'])[width_bucket(CAST(\"o\".\"amount\" AS double precision), ARRAY[0, 10, 50]::double precision[]) + 1]"
+            ),
+            "sql={sql}"
+        );
+    }
+
     #[test]
     fn postgres_renders_filtered_measure_with_filter_syntax() {
         let registry = fixtures::measures_registry();
@@ -1125,13 +3767,14 @@ mod postgres_tests {
         let registry = fixtures::orders_with_customers_registry();
         let request = QueryRequest {
             flow: "sales".to_string(),
-            dimensions: vec!["c.country".to_string()],
+            dimensions: vec!["c.country".into()],
             measures: vec!["o.order_total".to_string()],
             filters: vec![semaflow::flows::Filter {
                 field: "c.country".to_string(),
                 op: semaflow::flows::FilterOp::Eq,
                 value: serde_json::json!("US"),
-            }],
+            }
+            .into()],
             order: vec![],
             limit: None,
             offset: None,
@@ -1176,4 +3819,151 @@ mod postgres_tests {
             "composite measure should use safe divide; sql={sql}"
         );
     }
+
+    #[test]
+    fn postgres_fill_gaps_uses_generate_series() {
+        let registry = fixtures::simple_orders_registry();
+        let mut request: QueryRequest = serde_json::from_value(serde_json::json!({
+            "flow": "sales",
+            "dimensions": [{"field": "created_at", "grain": "quarter"}],
+            "measures": ["order_total"],
+            "fill_gaps": true,
+        }))
+        .unwrap();
+        let dialect = PostgresDialect::new("public");
+        let sql = SqlBuilder::default()
+            .build_with_dialect(&registry, &request, &dialect)
+            .unwrap();
+        assert!(
+            sql.contains(
+                "generate_series(MIN(\"created_at\"), MAX(\"created_at\"), INTERVAL '3 month')"
+            ),
+            "sql={sql}"
+        );
+
+        // Postgres has no IGNORE NULLS for carrying values forward
+        request.gap_fill = [(
+            "order_total".to_string(),
+            semaflow::flows::GapFill::Previous,
+        )]
+        .into_iter()
+        .collect();
+        let err = SqlBuilder::default()
+            .build_with_dialect(&registry, &request, &dialect)
+            .unwrap_err();
+        assert!(err.to_string().contains("previous"), "{err}");
+    }
+}
+
+#[test]
+fn labelled_dimensions_decode_values_and_filter_stored_ones() {
+    let mut registry = fixtures::simple_orders_registry();
+    let status: semaflow::flows::Dimension = serde_json::from_value(serde_json::json!({
+        "expr": {"type": "column", "column": "status_code"},
+        "labels": {"1": "Active", "2": "Churned"},
+    }))
+    .unwrap();
+    registry
+        .tables
+        .get_mut("orders")
+        .unwrap()
+        .dimensions
+        .insert("status".to_string(), status);
+    let build = |request: serde_json::Value| {
+        let request: QueryRequest = serde_json::from_value(request).unwrap();
+        SqlBuilder::default()
+            .build_with_dialect(&registry, &request, &DuckDbDialect)
+            .unwrap()
+    };
+
+    let sql = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["status"],
+        "measures": ["order_total"],
+        "filters": [{"field": "status", "op": "in", "value": ["Churned"]}],
+    }));
+    assert!(
+        sql.contains(
+            "CASE WHEN (\"o\".\"status_code\" = 1) THEN 'Active' \
+             WHEN (\"o\".\"status_code\" = 2) THEN 'Churned' ELSE NULL END AS \"status\""
+        ),
+        "sql={sql}"
+    );
+    assert!(sql.contains("\"o\".\"status_code\" IN (2)"), "sql={sql}");
+
+    // Values without a label are compared with the decoded label
+    let sql = build(serde_json::json!({
+        "flow": "sales",
+        "measures": ["order_total"],
+        "filters": [{"field": "status", "op": "like", "value": "Ch%"}],
+    }));
+    assert!(sql.contains("ELSE NULL END LIKE 'Ch%'"), "sql={sql}");
+}
+
+#[test]
+fn drill_moves_along_a_hierarchy() {
+    let mut registry = fixtures::orders_with_customers_registry();
+    let customers = registry.tables.get_mut("customers").unwrap();
+    for level in ["region", "city"] {
+        let dimension = semaflow::flows::Dimension {
+            expr: Expr::Column {
+                column: level.to_string(),
+            },
+            ..customers.dimensions["country"].clone()
+        };
+        customers.dimensions.insert(level.to_string(), dimension);
+    }
+    customers.hierarchies.insert(
+        "geography".to_string(),
+        vec![
+            "country".to_string(),
+            "region".to_string(),
+            "city".to_string(),
+        ],
+    );
+
+    let schema = registry.flow_schema("sales").unwrap();
+    assert_eq!(schema.hierarchies.len(), 1);
+    assert_eq!(
+        schema.hierarchies[0].levels,
+        ["c.country", "c.region", "c.city"]
+    );
+
+    let build = |request: serde_json::Value| {
+        let request: QueryRequest = serde_json::from_value(request).unwrap();
+        SqlBuilder::default().build_with_dialect(&registry, &request, &DuckDbDialect)
+    };
+    let sql = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["c.country"],
+        "measures": ["order_total"],
+        "drill": {"dimension": "c.country", "level": "city"},
+    }))
+    .unwrap();
+    for level in ["country", "region", "city"] {
+        assert!(sql.contains(&format!("\"c\".\"{level}\"")), "sql={sql}");
+    }
+
+    // Drilling back up drops the finer levels
+    let sql = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["c.country", "c.region", "c.city"],
+        "measures": ["order_total"],
+        "drill": {"dimension": "c.city", "level": "country"},
+    }))
+    .unwrap();
+    assert!(!sql.contains("\"c\".\"region\""), "sql={sql}");
+
+    let err = build(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["c.country"],
+        "measures": ["order_total"],
+        "drill": {"dimension": "c.country", "level": "street"},
+    }))
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("hierarchy geography has levels country, region, city"),
+        "{err}"
+    );
 }
@@ -1,18 +1,28 @@
 //! Integration tests for the FlowRegistry introspection API.
 
 use semaflow::flows::{
-    Aggregation, Expr, FlowJoin, FlowTableRef, JoinKey, JoinType, SemanticFlow, SemanticTable,
+    Aggregation, BinaryOp, Expr, FlowJoin, FlowTableRef, FormulaAst, FormulaExpr, JoinCardinality,
+    JoinKey, JoinType, QueryRequest, SemanticFlow, SemanticTable,
 };
-use semaflow::registry::FlowRegistry;
+use semaflow::interop::{cube, dbt, lookml};
+use semaflow::lineage::{Lineage, LineageNodeKind};
+use semaflow::registry::{FlowRegistry, SchemaFieldKind, SchemaFilter};
+use semaflow::registry_diff::{ChangeImpact, ChangeKind, ModelObject};
 
 fn introspection_registry() -> FlowRegistry {
     let customers = SemanticTable {
         data_source: "ds1".to_string(),
         name: "customers".to_string(),
         table: "customers".to_string(),
+        sql: None,
+        query: None,
+        union: None,
         primary_keys: vec!["id".to_string()],
+        unique_keys: Vec::new(),
+        nullable: Default::default(),
         time_dimension: None,
         smallest_time_grain: None,
+        timezone: None,
         dimensions: [(
             "country".to_string(),
             semaflow::flows::Dimension {
@@ -21,21 +31,41 @@ fn introspection_registry() -> FlowRegistry {
                 },
                 data_type: Some("string".to_string()),
                 description: Some("customer country".to_string()),
+                tags: vec!["geo".to_string()],
+                synonyms: Vec::new(),
+                folder: Some("Customer/Geography".to_string()),
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
+                unnest: false,
+                labels: Default::default(),
             },
         )]
         .into_iter()
         .collect(),
         measures: Default::default(),
         description: Some("customer table".to_string()),
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        access_policies: Default::default(),
+        hierarchies: Default::default(),
+        entities: Default::default(),
     };
 
     let orders = SemanticTable {
         data_source: "ds1".to_string(),
         name: "orders".to_string(),
         table: "orders".to_string(),
+        sql: None,
+        query: None,
+        union: None,
         primary_keys: vec!["id".to_string()],
+        unique_keys: Vec::new(),
+        nullable: Default::default(),
         time_dimension: Some("created_at".to_string()),
         smallest_time_grain: None,
+        timezone: None,
         dimensions: [(
             "id".to_string(),
             semaflow::flows::Dimension {
@@ -44,6 +74,15 @@ fn introspection_registry() -> FlowRegistry {
                 },
                 data_type: Some("int".to_string()),
                 description: Some("order id".to_string()),
+                tags: Vec::new(),
+                synonyms: Vec::new(),
+                folder: None,
+                deprecated: Some(Default::default()),
+                label: None,
+                format: None,
+                hidden: false,
+                unnest: false,
+                labels: Default::default(),
             },
         )]
         .into_iter()
@@ -60,11 +99,23 @@ fn introspection_registry() -> FlowRegistry {
                 post_expr: None,
                 data_type: Some("double".to_string()),
                 description: Some("sum of amounts".to_string()),
+                tags: Vec::new(),
+                synonyms: vec!["revenue".to_string()],
+                folder: None,
+                deprecated: None,
+                label: None,
+                format: None,
+                hidden: false,
             },
         )]
         .into_iter()
         .collect(),
         description: Some("orders table".to_string()),
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        access_policies: Default::default(),
+        hierarchies: Default::default(),
+        entities: Default::default(),
     };
 
     let flow = SemanticFlow {
@@ -85,12 +136,28 @@ fn introspection_registry() -> FlowRegistry {
                     right: "id".to_string(),
                 }],
                 cardinality: None,
+                through: None,
+                weight: None,
+                role: None,
+                extra_conditions: None,
                 description: Some("customer join".to_string()),
             },
         )]
         .into_iter()
         .collect(),
         description: Some("sales flow".to_string()),
+        params: Default::default(),
+        tags: Vec::new(),
+        synonyms: Vec::new(),
+        limits: Default::default(),
+        aggregates: Default::default(),
+        fiscal_calendar: None,
+        timezone: None,
+        fanout_strategy: Default::default(),
+        default_filters: Vec::new(),
+        segments: Default::default(),
+        extends: None,
+        deprecated: None,
     };
 
     FlowRegistry::from_parts(vec![customers, orders], vec![flow])
@@ -105,6 +172,29 @@ fn list_flow_summaries_returns_names_and_descriptions() {
     assert_eq!(summaries[0].description.as_deref(), Some("sales flow"));
 }
 
+#[test]
+fn flow_schema_lists_allowed_time_grains() {
+    let mut registry = introspection_registry();
+    registry
+        .tables
+        .get_mut("orders")
+        .unwrap()
+        .smallest_time_grain = Some(semaflow::flows::TimeGrain::Month);
+    let schema = registry.flow_schema("sales").expect("schema");
+    assert_eq!(schema.smallest_time_grain.as_deref(), Some("month"));
+    assert_eq!(
+        schema.time_grains,
+        [
+            "month",
+            "fiscal_month",
+            "quarter",
+            "fiscal_quarter",
+            "year",
+            "fiscal_year"
+        ]
+    );
+}
+
 #[test]
 fn flow_schema_includes_dimensions_measures_and_joins() {
     let registry = introspection_registry();
@@ -113,6 +203,10 @@ fn flow_schema_includes_dimensions_measures_and_joins() {
     assert_eq!(schema.data_source, "ds1");
     assert_eq!(schema.time_dimension.as_deref(), Some("created_at"));
     assert!(schema.smallest_time_grain.is_none());
+    assert_eq!(
+        schema.time_grains.first().map(String::as_str),
+        Some("second")
+    );
 
     let dim_names: Vec<_> = schema.dimensions.iter().map(|d| d.name.as_str()).collect();
     assert!(dim_names.contains(&"country"));
@@ -121,3 +215,528 @@ fn flow_schema_includes_dimensions_measures_and_joins() {
     let measure_names: Vec<_> = schema.measures.iter().map(|m| m.name.as_str()).collect();
     assert!(measure_names.contains(&"order_total"));
 }
+
+#[test]
+fn flow_schema_filters_and_pages_fields() {
+    let registry = introspection_registry();
+    let names = |filter: SchemaFilter| {
+        let schema = registry.flow_schema_filtered("sales", &filter).unwrap();
+        let names: Vec<String> = schema
+            .dimensions
+            .iter()
+            .map(|d| d.qualified_name.clone())
+            .chain(schema.measures.iter().map(|m| m.qualified_name.clone()))
+            .collect();
+        (names, schema.total_fields, schema.next_offset)
+    };
+
+    let (all, total, next) = names(SchemaFilter::default());
+    assert_eq!(all, ["o.id", "c.country", "o.order_total"]);
+    assert_eq!((total, next), (3, None));
+
+    let (current, _, _) = names(SchemaFilter {
+        include_deprecated: false,
+        ..Default::default()
+    });
+    assert_eq!(current, ["c.country", "o.order_total"]);
+
+    let (measures, _, _) = names(SchemaFilter {
+        kind: Some(SchemaFieldKind::Measures),
+        ..Default::default()
+    });
+    assert_eq!(measures, ["o.order_total"]);
+
+    let (tagged, _, _) = names(SchemaFilter {
+        tags: vec!["geo".to_string()],
+        ..Default::default()
+    });
+    assert_eq!(tagged, ["c.country"]);
+    let (in_folder, _, _) = names(SchemaFilter {
+        folder: Some("Customer".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(in_folder, ["c.country"]);
+    let (prefix_only, _, _) = names(SchemaFilter {
+        folder: Some("Cust".to_string()),
+        ..Default::default()
+    });
+    assert!(prefix_only.is_empty());
+
+    // Pages span the dimension/measure boundary
+    let (page, total, next) = names(SchemaFilter {
+        offset: 1,
+        limit: Some(1),
+        ..Default::default()
+    });
+    assert_eq!(page, ["c.country"]);
+    assert_eq!((total, next), (3, Some(2)));
+    let (page, _, next) = names(SchemaFilter {
+        offset: 1,
+        limit: Some(5),
+        ..Default::default()
+    });
+    assert_eq!(page, ["c.country", "o.order_total"]);
+    assert_eq!(next, None);
+}
+
+#[test]
+fn flow_schema_surfaces_display_metadata() {
+    let mut registry = introspection_registry();
+    let measure = |value: serde_json::Value| -> semaflow::flows::Measure {
+        serde_json::from_value(value).unwrap()
+    };
+    let avg_basket = measure(serde_json::json!({
+        "expr": "amount",
+        "agg": "avg",
+        "label": "Average Basket",
+        "format": "$0,0.00",
+        "group": "Revenue",
+    }));
+    let max_basket = measure(serde_json::json!({"expr": "amount", "agg": "max", "hidden": true}));
+    let orders = registry.tables.get_mut("orders").unwrap();
+    orders.measures.insert("avg_basket".to_string(), avg_basket);
+    orders.measures.insert("max_basket".to_string(), max_basket);
+
+    let schema = registry.flow_schema("sales").expect("schema");
+    let info = |name: &str| schema.measures.iter().find(|m| m.name == name).unwrap();
+    let avg = info("avg_basket");
+    assert_eq!(avg.label.as_deref(), Some("Average Basket"));
+    assert_eq!(avg.format.as_deref(), Some("$0,0.00"));
+    assert_eq!(avg.folder.as_deref(), Some("Revenue"));
+    assert!(!avg.hidden);
+    assert!(info("max_basket").hidden);
+
+    let hits = registry.search("average basket");
+    assert_eq!(hits[0].name, "o.avg_basket");
+    assert!(registry
+        .search("max_basket")
+        .iter()
+        .all(|h| h.name != "o.max_basket"));
+}
+
+#[test]
+fn requests_report_deprecated_flows_and_fields() {
+    use semaflow::deprecation::DeprecatedKind;
+
+    let mut registry = introspection_registry();
+    let revenue: semaflow::flows::Measure = serde_json::from_value(serde_json::json!({
+        "expr": "amount",
+        "agg": "sum",
+        "deprecated": {"since": "2024-06", "replacement": "o.order_total"},
+    }))
+    .unwrap();
+    let orders = registry.tables.get_mut("orders").unwrap();
+    orders.measures.insert("revenue".to_string(), revenue);
+
+    let request: QueryRequest = serde_json::from_value(serde_json::json!({
+        "flow": "sales",
+        "dimensions": ["c.country"],
+        "measures": ["revenue", "o.revenue", "o.order_total"],
+        "filters": [{"field": "o.id", "op": ">", "value": 10}],
+    }))
+    .unwrap();
+    let warnings = registry.deprecations(&request);
+    assert_eq!(warnings.len(), 2, "{warnings:?}");
+    assert_eq!(warnings[0].kind, DeprecatedKind::Measure);
+    assert_eq!(warnings[0].name, "o.revenue");
+    assert_eq!(
+        warnings[0].to_string(),
+        "measure o.revenue is deprecated since 2024-06; use o.order_total instead"
+    );
+    assert_eq!(warnings[1].kind, DeprecatedKind::Dimension);
+    assert_eq!(warnings[1].name, "o.id");
+
+    let schema = registry.flow_schema("sales").expect("schema");
+    let info = schema
+        .measures
+        .iter()
+        .find(|m| m.name == "revenue")
+        .unwrap();
+    assert_eq!(
+        info.deprecated
+            .as_ref()
+            .and_then(|d| d.replacement.as_deref()),
+        Some("o.order_total")
+    );
+
+    registry.flows.get_mut("sales").unwrap().deprecated = Some(Default::default());
+    let warnings = registry.deprecations(&request);
+    assert_eq!(warnings[0].kind, DeprecatedKind::Flow);
+    assert!(registry.list_flow_summaries()[0].deprecated.is_some());
+    assert!(registry
+        .search("sales")
+        .iter()
+        .all(|h| h.kind != semaflow::search::SearchKind::Flow));
+}
+
+#[test]
+fn search_ranks_names_synonyms_and_descriptions() {
+    use semaflow::search::{MatchedOn, SearchKind};
+
+    let registry = introspection_registry();
+
+    let hits = registry.search("Revenue");
+    assert_eq!(hits[0].name, "o.order_total");
+    assert_eq!(hits[0].kind, SearchKind::Measure);
+    assert_eq!(hits[0].matched_on, MatchedOn::Synonym);
+
+    let hits = registry.search("contry");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].name, "c.country");
+    assert_eq!(hits[0].matched_on, MatchedOn::Name);
+
+    let hits = registry.search("sales");
+    assert_eq!(hits[0].kind, SearchKind::Flow);
+    assert!(hits.windows(2).all(|w| w[0].score >= w[1].score));
+
+    // "order id" is deprecated, so only the order_total prefix match remains
+    let hits = registry.search("order");
+    assert!(hits.iter().all(|h| h.name != "o.id"), "{hits:?}");
+    assert!(registry.search("   ").is_empty());
+}
+
+#[test]
+fn flow_version_tracks_referenced_table_changes() {
+    let registry = introspection_registry();
+    let v1 = registry.flow_version("sales").expect("version");
+    assert_eq!(v1, introspection_registry().flow_version("sales").unwrap());
+
+    let mut changed = registry.clone();
+    changed
+        .tables
+        .get_mut("customers")
+        .unwrap()
+        .dimensions
+        .remove("country");
+    assert_ne!(v1, changed.flow_version("sales").unwrap());
+
+    assert!(registry.flow_version("missing").is_err());
+}
+
+#[test]
+fn diff_classifies_changes_and_bumps_flow_versions() {
+    let old = introspection_registry();
+    assert!(FlowRegistry::diff(&old, &old).is_empty());
+
+    // Cosmetic: only a description changes
+    let mut new = introspection_registry();
+    new.tables.get_mut("customers").unwrap().description = Some("customers".to_string());
+    let diff = new.bump_versions_from(&old);
+    assert!(!diff.is_breaking());
+    assert_eq!(diff.impact_on("sales"), Some(ChangeImpact::Cosmetic));
+    assert_eq!(new.list_flow_summaries()[0].version.to_string(), "1.0.1");
+
+    // Additive: a new measure
+    let mut added = new.clone();
+    let order_total = added.tables["orders"].measures["order_total"].clone();
+    added
+        .tables
+        .get_mut("orders")
+        .unwrap()
+        .measures
+        .insert("order_total_copy".to_string(), order_total);
+    let diff = added.bump_versions_from(&new);
+    let change = &diff.changes[0];
+    assert_eq!(change.name, "orders.order_total_copy");
+    assert_eq!(
+        (change.object, change.kind, change.impact),
+        (
+            ModelObject::Measure,
+            ChangeKind::Added,
+            ChangeImpact::Additive
+        )
+    );
+    assert_eq!(change.flows, ["sales"]);
+    assert_eq!(added.flow_model_version("sales").to_string(), "1.1.0");
+
+    // Breaking: a dimension removed and a measure's aggregation changed
+    let mut broken = added.clone();
+    let orders = broken.tables.get_mut("orders").unwrap();
+    orders.dimensions.remove("id");
+    orders.measures.get_mut("order_total").unwrap().agg = Some(Aggregation::Max);
+    let diff = broken.bump_versions_from(&added);
+    assert!(diff.is_breaking());
+    let breaking: Vec<&str> = diff.breaking_changes().map(|c| c.name.as_str()).collect();
+    assert_eq!(breaking, ["orders.id", "orders.order_total"]);
+    assert_eq!(broken.flow_model_version("sales").to_string(), "2.0.0");
+}
+
+#[test]
+fn lineage_traces_columns_through_measures_to_flows() {
+    let mut registry = introspection_registry();
+    let orders = registry.tables.get_mut("orders").unwrap();
+    let mut average = orders.measures["order_total"].clone();
+    average.expr = None;
+    average.agg = None;
+    average.formula = Some(FormulaExpr {
+        raw: "order_total / count(id)".to_string(),
+        ast: FormulaAst::Binary {
+            op: BinaryOp::Divide,
+            left: Box::new(FormulaAst::Column {
+                column: "order_total".to_string(),
+            }),
+            right: Box::new(FormulaAst::Aggregation {
+                agg: Aggregation::Count,
+                column: "id".to_string(),
+                filter: None,
+            }),
+        },
+    });
+    orders.measures.insert("average_order".to_string(), average);
+
+    let lineage = registry.lineage();
+    let amount = Lineage::column_id("ds1", "orders", "amount");
+    assert_eq!(
+        lineage.node(&amount).map(|n| n.kind),
+        Some(LineageNodeKind::Column)
+    );
+    let downstream: Vec<&str> = lineage
+        .downstream(&amount)
+        .iter()
+        .map(|n| n.id.as_str())
+        .collect();
+    assert_eq!(
+        downstream,
+        [
+            "measure:orders.order_total",
+            "flow:sales",
+            "measure:orders.average_order"
+        ]
+    );
+
+    // Join keys feed the flow even though no field reads customers.id
+    let customer_id = Lineage::column_id("ds1", "customers", "id");
+    assert!(lineage
+        .downstream(&customer_id)
+        .iter()
+        .any(|n| n.id == "flow:sales"));
+
+    let upstream: Vec<&str> = lineage
+        .upstream("measure:orders.average_order")
+        .iter()
+        .map(|n| n.id.as_str())
+        .collect();
+    assert_eq!(
+        upstream,
+        [
+            "column:ds1.orders.id",
+            "measure:orders.order_total",
+            "column:ds1.orders.amount"
+        ]
+    );
+    let json = serde_json::to_value(&lineage).unwrap();
+    assert_eq!(json["nodes"][0]["kind"], "column");
+}
+
+#[test]
+fn lineage_reads_table_columns_from_raw_sql() {
+    let mut registry = introspection_registry();
+    let orders = registry.tables.get_mut("orders").unwrap();
+    let channel: semaflow::flows::Dimension = serde_json::from_value(serde_json::json!({
+        "sql": "json_extract_string(${TABLE}.payload, '$.channel')",
+        "data_type": "string",
+    }))
+    .unwrap();
+    assert!(matches!(channel.expr, Expr::Sql { .. }));
+    orders.dimensions.insert("channel".to_string(), channel);
+
+    let lineage = registry.lineage();
+    let upstream: Vec<&str> = lineage
+        .upstream("dimension:orders.channel")
+        .iter()
+        .map(|n| n.id.as_str())
+        .collect();
+    assert_eq!(upstream, ["column:ds1.orders.payload"]);
+
+    let both = serde_json::json!({ "expr": "channel", "sql": "channel" });
+    assert!(serde_json::from_value::<semaflow::flows::Dimension>(both).is_err());
+}
+
+#[test]
+fn load_from_dir_composes_split_tables_includes_and_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let tables = dir.path().join("tables");
+    let flows = dir.path().join("flows");
+    std::fs::create_dir_all(tables.join("shared")).unwrap();
+    std::fs::create_dir_all(&flows).unwrap();
+    std::env::set_var("SEMAFLOW_TEST_SCHEMA", "analytics");
+
+    std::fs::write(
+        tables.join("orders.yml"),
+        r#"
+name: orders
+data_source: ${SEMAFLOW_TEST_DATA_SOURCE:-warehouse}
+table: ${SEMAFLOW_TEST_SCHEMA}.orders
+primary_key: id
+dimensions: !include shared/order_dimensions.yml
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        tables.join("shared/order_dimensions.yml"),
+        "id: id\nstatus: status\n",
+    )
+    .unwrap();
+    // Second part of the orders table, using a partial for shared settings
+    std::fs::write(tables.join("_amount.yml"), "expr: amount\n").unwrap();
+    std::fs::write(
+        tables.join("orders_measures.yml"),
+        r#"
+name: orders
+data_source: ${SEMAFLOW_TEST_DATA_SOURCE:-warehouse}
+measures:
+  order_total:
+    <<: !include _amount.yml
+    agg: sum
+  largest_order:
+    <<: !include _amount.yml
+    agg: max
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        flows.join("sales.yml"),
+        "name: sales\nbase_table:\n  semantic_table: orders\n  alias: o\n",
+    )
+    .unwrap();
+
+    let registry = FlowRegistry::load_from_dir(dir.path()).expect("load");
+    let orders = registry.get_table("orders").expect("orders");
+    assert_eq!(orders.data_source, "warehouse");
+    assert_eq!(orders.table, "analytics.orders");
+    assert_eq!(
+        orders.dimensions.keys().collect::<Vec<_>>(),
+        ["id", "status"]
+    );
+    assert_eq!(orders.measures["largest_order"].agg, Some(Aggregation::Max));
+    assert!(matches!(
+        &orders.measures["order_total"].expr,
+        Some(Expr::Column { column }) if column == "amount"
+    ));
+    assert_eq!(registry.tables.len(), 1);
+
+    // Both parts defining the same field is an error
+    std::fs::write(
+        tables.join("orders_more.yml"),
+        "name: orders\nmeasures:\n  order_total: {expr: amount, agg: avg}\n",
+    )
+    .unwrap();
+    let err = FlowRegistry::load_from_dir(dir.path()).unwrap_err();
+    assert!(err.to_string().contains("measures.order_total"), "{err}");
+}
+
+#[test]
+fn joins_without_keys_are_inferred_from_shared_entities() {
+    let dir = tempfile::tempdir().unwrap();
+    let tables = dir.path().join("tables");
+    let flows = dir.path().join("flows");
+    std::fs::create_dir_all(&tables).unwrap();
+    std::fs::create_dir_all(&flows).unwrap();
+    std::fs::write(
+        tables.join("orders.yml"),
+        r#"
+name: orders
+data_source: warehouse
+table: orders
+primary_key: id
+entities:
+  order: {type: primary, column: id}
+  customer: {type: foreign, column: customer_id}
+  store: {type: foreign, column: store_id}
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        tables.join("customers.yml"),
+        r#"
+name: customers
+data_source: warehouse
+table: customers
+primary_key: id
+entities:
+  customer: {type: primary, column: id}
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        tables.join("returns.yml"),
+        r#"
+name: returns
+data_source: warehouse
+table: returns
+primary_key: id
+entities:
+  store: {type: foreign}
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        flows.join("sales.yml"),
+        r#"
+name: sales
+base_table: {semantic_table: orders, alias: o}
+joins:
+  customers: {semantic_table: customers, alias: c, to_table: o, join_type: left}
+  returns: {semantic_table: returns, alias: r, to_table: o, join_type: left}
+"#,
+    )
+    .unwrap();
+
+    let registry = FlowRegistry::load_from_dir(dir.path()).expect("load");
+    let sales = registry.get_flow("sales").expect("sales");
+    let customers = &sales.joins["customers"];
+    assert_eq!(customers.join_keys.len(), 1);
+    assert_eq!(customers.join_keys[0].left, "customer_id");
+    assert_eq!(customers.join_keys[0].right, "id");
+    assert_eq!(customers.cardinality, Some(JoinCardinality::ManyToOne));
+    // Both sides only reference stores, so neither owns the key
+    assert!(sales.joins["returns"].join_keys.is_empty());
+}
+
+#[test]
+fn exports_registry_to_cube_lookml_and_dbt() {
+    let mut registry = introspection_registry();
+    let orders = registry.tables.get_mut("orders").unwrap();
+    let mut by_param = orders.measures["order_total"].clone();
+    by_param.expr = Some(Expr::Param {
+        name: "column".to_string(),
+    });
+    orders.measures.insert("by_param".to_string(), by_param);
+
+    let cube = cube::export(&registry);
+    let yaml: serde_yaml::Value = serde_yaml::from_str(&cube.contents).unwrap();
+    let cubes = yaml["cubes"].as_sequence().unwrap();
+    let orders = cubes.iter().find(|c| c["name"] == "orders").unwrap();
+    assert_eq!(orders["sql_table"], "orders");
+    assert_eq!(orders["measures"][0]["sql"], "{CUBE}.amount");
+    assert_eq!(orders["joins"][0]["sql"], "{CUBE}.id = {customers}.id");
+    assert_eq!(
+        yaml["views"][0]["cubes"][1]["join_path"],
+        "orders.customers"
+    );
+    assert_eq!(cube.warnings.len(), 1, "{:?}", cube.warnings);
+    assert!(cube.warnings[0].starts_with("orders.by_param"));
+
+    let lookml = lookml::export(&registry);
+    assert!(lookml.contents.contains("view: orders {"));
+    assert!(lookml
+        .contents
+        .contains("  measure: order_total {\n    type: sum\n    sql: ${TABLE}.amount ;;"));
+    assert!(lookml
+        .contents
+        .contains("explore: sales {\n  from: orders\n  view_name: o"));
+    assert!(lookml.contents.contains("    sql_on: o.id = c.id ;;"));
+    assert_eq!(lookml.warnings.len(), 1, "{:?}", lookml.warnings);
+
+    let dbt = dbt::export(&registry);
+    let yaml: serde_yaml::Value = serde_yaml::from_str(&dbt.contents).unwrap();
+    let models = yaml["semantic_models"].as_sequence().unwrap();
+    let orders = models.iter().find(|m| m["name"] == "orders").unwrap();
+    assert_eq!(orders["model"], "ref('orders')");
+    assert_eq!(orders["measures"][0]["agg"], "sum");
+    let entities = orders["entities"].as_sequence().unwrap();
+    assert!(entities
+        .iter()
+        .any(|e| e["name"] == "customers" && e["type"] == "foreign" && e["expr"] == "id"));
+    assert_eq!(yaml["metrics"][0]["type"], "simple");
+}
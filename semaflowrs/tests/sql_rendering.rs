@@ -2,13 +2,14 @@
 //!
 //! These tests exercise the SqlRenderer with various query structures.
 
-use semaflow::dialect::DuckDbDialect;
 #[cfg(feature = "postgres")]
 use semaflow::dialect::PostgresDialect;
-use semaflow::flows::{Aggregation, Function, SortDirection, TimeGrain};
+use semaflow::dialect::{DialectCapabilities, DuckDbDialect, LimitSyntax};
+use semaflow::flows::{Aggregation, BucketSpec, Function, SortDirection, TimeGrain};
+use semaflow::schedule::{incremental_statements, sink_statements, ScheduleSink, WriteMode};
 use semaflow::sql_ast::{
     Join, OrderItem, SelectItem, SelectQuery, SqlBinaryOperator, SqlExpr, SqlJoinType, SqlRenderer,
-    TableRef,
+    TableRef, Unnest,
 };
 
 fn col(table: &str, name: &str) -> SqlExpr {
@@ -31,11 +32,14 @@ fn order_asc(name: &str) -> OrderItem {
 #[test]
 fn renders_join_group_order_and_aggregates() {
     let dialect = DuckDbDialect;
-    let mut query = SelectQuery::default();
-    query.from = TableRef {
-        name: "orders".to_string(),
-        alias: Some("o".to_string()),
-        subquery: None,
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
     };
     query.select = vec![
         SelectItem {
@@ -57,6 +61,7 @@ fn renders_join_group_order_and_aggregates() {
             name: "customers".to_string(),
             alias: Some("c".to_string()),
             subquery: None,
+            sql: None,
         },
         on: vec![SqlExpr::BinaryOp {
             op: SqlBinaryOperator::Eq,
@@ -87,11 +92,14 @@ fn renders_join_group_order_and_aggregates() {
 #[test]
 fn renders_functions_and_not_in_list() {
     let dialect = DuckDbDialect;
-    let mut query = SelectQuery::default();
-    query.from = TableRef {
-        name: "orders".to_string(),
-        alias: Some("o".to_string()),
-        subquery: None,
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
     };
     query.select.push(SelectItem {
         expr: SqlExpr::Function {
@@ -117,14 +125,232 @@ fn renders_functions_and_not_in_list() {
     assert!(sql.ends_with("LIMIT 5 OFFSET 10"));
 }
 
+#[test]
+fn renders_sub_day_time_grains() {
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "events".to_string(),
+            alias: Some("e".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    let function = |func: Function, args: Vec<SqlExpr>, alias: &str| SelectItem {
+        expr: SqlExpr::Function { func, args },
+        alias: Some(alias.to_string()),
+    };
+    query.select.push(function(
+        Function::DateTrunc(TimeGrain::Hour),
+        vec![col("e", "ts")],
+        "hour",
+    ));
+    query.select.push(function(
+        Function::DateDiff {
+            unit: TimeGrain::Minute,
+        },
+        vec![col("e", "started_at"), col("e", "ts")],
+        "minutes",
+    ));
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains("date_trunc('hour', \"e\".\"ts\") AS \"hour\""),
+        "{sql}"
+    );
+    assert!(
+        sql.contains("date_diff('minute', \"e\".\"started_at\", \"e\".\"ts\")"),
+        "{sql}"
+    );
+
+    #[cfg(feature = "postgres")]
+    {
+        let sql = SqlRenderer::new(&PostgresDialect::new("public")).render_select(&query);
+        assert!(
+            sql.contains("floor(extract(epoch FROM \"e\".\"ts\" - \"e\".\"started_at\") / 60)"),
+            "{sql}"
+        );
+    }
+}
+
+#[test]
+fn renders_regex_functions_per_dialect() {
+    let text = |s: &str| SqlExpr::Literal(serde_json::json!(s));
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "pageviews".to_string(),
+            alias: Some("p".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    for (func, args, alias) in [
+        (
+            Function::RegexpMatches,
+            vec![col("p", "url"), text("^/docs/")],
+            "is_docs",
+        ),
+        (
+            Function::RegexpExtract,
+            vec![col("p", "url"), text("^/([^/]+)")],
+            "section",
+        ),
+        (
+            Function::RegexpReplace,
+            vec![col("p", "url"), text("\\?.*$"), text("")],
+            "path",
+        ),
+    ] {
+        query.select.push(SelectItem {
+            expr: SqlExpr::Function { func, args },
+            alias: Some(alias.to_string()),
+        });
+    }
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains("regexp_matches(\"p\".\"url\", '^/docs/')"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("regexp_extract(\"p\".\"url\", '^/([^/]+)', 1)"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("regexp_replace(\"p\".\"url\", '\\?.*$', '', 'g')"),
+        "sql={sql}"
+    );
+
+    #[cfg(feature = "postgres")]
+    {
+        let sql = SqlRenderer::new(&PostgresDialect::new("public")).render_select(&query);
+        assert!(sql.contains("(\"p\".\"url\" ~ '^/docs/')"), "{sql}");
+        assert!(
+            sql.contains("substring(\"p\".\"url\" FROM '^/([^/]+)')"),
+            "{sql}"
+        );
+    }
+}
+
+#[test]
+fn renders_geo_functions_in_meters() {
+    let num = |n: f64| SqlExpr::Literal(serde_json::json!(n));
+    let warehouse = SqlExpr::Function {
+        func: Function::StPoint,
+        args: vec![num(13.4), num(52.5)],
+    };
+    let delivery = SqlExpr::Function {
+        func: Function::StPoint,
+        args: vec![col("o", "lon"), col("o", "lat")],
+    };
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.select.push(SelectItem {
+        expr: SqlExpr::Function {
+            func: Function::StDistance,
+            args: vec![delivery, warehouse],
+        },
+        alias: Some("distance_m".to_string()),
+    });
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains(
+            "ST_Distance_Sphere(ST_FlipCoordinates(ST_Point(\"o\".\"lon\", \"o\".\"lat\")), \
+             ST_FlipCoordinates(ST_Point(13.4, 52.5)))"
+        ),
+        "sql={sql}"
+    );
+
+    #[cfg(feature = "postgres")]
+    {
+        let sql = SqlRenderer::new(&PostgresDialect::new("public")).render_select(&query);
+        assert!(
+            sql.contains("ST_Distance((ST_SetSRID(ST_MakePoint(\"o\".\"lon\", \"o\".\"lat\"), 4326)::geography)::geography"),
+            "{sql}"
+        );
+    }
+}
+
+#[test]
+fn renders_width_bucket_as_lower_bound() {
+    let dialect = DuckDbDialect;
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.select.push(SelectItem {
+        expr: SqlExpr::Function {
+            func: Function::Bucket(BucketSpec::Width(2.5)),
+            args: vec![col("o", "amount")],
+        },
+        alias: Some("amount_bucket".to_string()),
+    });
+
+    let sql = SqlRenderer::new(&dialect).render_select(&query);
+    assert!(sql.contains("floor(\"o\".\"amount\" / 2.5) * 2.5 AS \"amount_bucket\""));
+}
+
+#[test]
+fn renders_null_between_and_not_predicates() {
+    let dialect = DuckDbDialect;
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.select.push(SelectItem {
+        expr: col("o", "id"),
+        alias: Some("id".to_string()),
+    });
+    query.filters.push(SqlExpr::IsNull {
+        expr: Box::new(col("o", "shipped_at")),
+        negated: true,
+    });
+    query.filters.push(SqlExpr::Between {
+        expr: Box::new(col("o", "amount")),
+        low: Box::new(SqlExpr::Literal(serde_json::json!(10))),
+        high: Box::new(SqlExpr::Literal(serde_json::json!(20))),
+        negated: false,
+    });
+    query.filters.push(SqlExpr::Not {
+        expr: Box::new(col("o", "is_gift")),
+    });
+
+    let sql = SqlRenderer::new(&dialect).render_select(&query);
+    assert!(sql.contains("(\"o\".\"shipped_at\" IS NOT NULL)"));
+    assert!(sql.contains("(\"o\".\"amount\" BETWEEN 10 AND 20)"));
+    assert!(sql.contains("(NOT \"o\".\"is_gift\")"));
+}
+
 #[test]
 fn renders_filtered_aggregate_when_supported() {
     let dialect = DuckDbDialect;
-    let mut query = SelectQuery::default();
-    query.from = TableRef {
-        name: "orders".to_string(),
-        alias: Some("o".to_string()),
-        subquery: None,
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
     };
     query.select.push(SelectItem {
         expr: SqlExpr::FilteredAggregate {
@@ -143,6 +369,211 @@ fn renders_filtered_aggregate_when_supported() {
     assert!(sql.contains("SUM(\"o\".\"amount\") FILTER (WHERE (\"o\".\"country\" = 'US'))"));
 }
 
+#[test]
+fn renders_group_by_all_only_when_every_group_is_selected() {
+    let dialect = DuckDbDialect;
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.select = vec![
+        SelectItem {
+            expr: col("o", "country"),
+            alias: Some("country".to_string()),
+        },
+        SelectItem {
+            expr: SqlExpr::Aggregate {
+                agg: Aggregation::Sum,
+                expr: Box::new(col("o", "amount")),
+            },
+            alias: Some("order_total".to_string()),
+        },
+    ];
+    query.group_by.push(col("o", "country"));
+
+    let sql = SqlRenderer::new(&dialect)
+        .with_group_by_all(true)
+        .render_select(&query);
+    assert!(sql.ends_with("GROUP BY ALL"), "sql={sql}");
+
+    // Off by default
+    let sql = SqlRenderer::new(&dialect).render_select(&query);
+    assert!(sql.ends_with("GROUP BY \"o\".\"country\""), "sql={sql}");
+
+    // A grouping key missing from SELECT keeps the explicit list
+    query.group_by.push(col("o", "customer_id"));
+    let sql = SqlRenderer::new(&dialect)
+        .with_group_by_all(true)
+        .render_select(&query);
+    assert!(
+        sql.ends_with("GROUP BY \"o\".\"country\", \"o\".\"customer_id\""),
+        "sql={sql}"
+    );
+
+    // So does an ungrouped non-aggregate item, which GROUP BY ALL would group by
+    query.group_by.pop();
+    query.select.push(SelectItem {
+        expr: col("o", "status"),
+        alias: Some("status".to_string()),
+    });
+    let sql = SqlRenderer::new(&dialect)
+        .with_group_by_all(true)
+        .render_select(&query);
+    assert!(sql.ends_with("GROUP BY \"o\".\"country\""), "sql={sql}");
+}
+
+/// Minimal dialect without grouping set support, to exercise the fallback.
+struct PlainDialect;
+
+impl semaflow::dialect::Dialect for PlainDialect {
+    fn quote_ident(&self, ident: &str) -> String {
+        format!("\"{ident}\"")
+    }
+
+    fn render_function(&self, func: &Function, args: Vec<String>) -> String {
+        DuckDbDialect.render_function(func, args)
+    }
+}
+
+#[test]
+fn renders_grouping_sets_natively_or_as_union() {
+    use semaflow::dialect::Dialect;
+
+    let mut query = SelectQuery::default();
+    query.from = TableRef {
+        name: "orders".to_string(),
+        alias: Some("o".to_string()),
+        subquery: None,
+        sql: None,
+    };
+    query.group_by = vec![col("o", "country"), col("o", "city")];
+    query.select = vec![
+        SelectItem {
+            expr: col("o", "country"),
+            alias: Some("country".to_string()),
+        },
+        SelectItem {
+            expr: col("o", "city"),
+            alias: Some("city".to_string()),
+        },
+        SelectItem {
+            expr: SqlExpr::Aggregate {
+                agg: Aggregation::Sum,
+                expr: Box::new(col("o", "amount")),
+            },
+            alias: Some("order_total".to_string()),
+        },
+        SelectItem {
+            expr: SqlExpr::Grouping {
+                exprs: query.group_by.clone(),
+            },
+            alias: Some("_grouping".to_string()),
+        },
+    ];
+    query.grouping_sets = vec![vec![0, 1], vec![0], vec![]];
+    query.order_by = vec![OrderItem {
+        expr: col("o", "country"),
+        direction: SortDirection::Asc,
+    }];
+    query.limit = Some(10);
+
+    let sql = SqlRenderer::new(&DuckDbDialect)
+        .with_group_by_all(true)
+        .render_select(&query);
+    assert!(
+        sql.contains("GROUPING(\"o\".\"country\", \"o\".\"city\") AS \"_grouping\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("GROUP BY ROLLUP(\"o\".\"country\", \"o\".\"city\") ORDER BY"),
+        "sql={sql}"
+    );
+
+    query.grouping_sets = vec![vec![0, 1], vec![]];
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains("GROUP BY GROUPING SETS ((\"o\".\"country\", \"o\".\"city\"), ())"),
+        "sql={sql}"
+    );
+
+    assert!(!PlainDialect.capabilities().grouping_sets);
+    let sql = SqlRenderer::new(&PlainDialect).render_select(&query);
+    assert!(sql.starts_with("SELECT * FROM (SELECT"), "sql={sql}");
+    assert!(
+        sql.contains("0 AS \"_grouping\" FROM \"orders\" \"o\" GROUP BY \"o\".\"country\", \"o\".\"city\" UNION ALL SELECT NULL AS \"country\", NULL AS \"city\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("3 AS \"_grouping\" FROM \"orders\" \"o\") \"__grouping_sets\""),
+        "sql={sql}"
+    );
+    assert!(
+        sql.ends_with(" ORDER BY \"country\" ASC LIMIT 10"),
+        "sql={sql}"
+    );
+}
+
+/// Dialect writing row limits in the SQL standard form.
+struct FetchFirstDialect;
+
+impl semaflow::dialect::Dialect for FetchFirstDialect {
+    fn quote_ident(&self, ident: &str) -> String {
+        format!("\"{ident}\"")
+    }
+
+    fn capabilities(&self) -> DialectCapabilities {
+        DialectCapabilities {
+            limit: LimitSyntax::FetchFirst,
+            ..Default::default()
+        }
+    }
+
+    fn render_function(&self, func: &Function, args: Vec<String>) -> String {
+        DuckDbDialect.render_function(func, args)
+    }
+}
+
+#[test]
+fn renders_limits_in_the_dialects_syntax() {
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.select = vec![SelectItem {
+        expr: col("o", "id"),
+        alias: Some("id".to_string()),
+    }];
+    query.order_by = vec![order_asc("id")];
+    query.limit = Some(10);
+    query.offset = Some(20);
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.ends_with("ORDER BY \"id\" ASC LIMIT 10 OFFSET 20"),
+        "sql={sql}"
+    );
+
+    let sql = SqlRenderer::new(&FetchFirstDialect).render_select(&query);
+    assert!(
+        sql.ends_with("ORDER BY \"id\" ASC OFFSET 20 ROWS FETCH FIRST 10 ROWS ONLY"),
+        "sql={sql}"
+    );
+
+    query.offset = None;
+    let sql = SqlRenderer::new(&FetchFirstDialect).render_select(&query);
+    assert!(sql.ends_with("ASC FETCH FIRST 10 ROWS ONLY"), "sql={sql}");
+}
+
 // ============================================================================
 // PostgreSQL Dialect Tests
 // ============================================================================
@@ -151,14 +582,41 @@ fn renders_filtered_aggregate_when_supported() {
 mod postgres_tests {
     use super::*;
 
+    #[test]
+    fn postgres_ignores_group_by_all_option() {
+        let dialect = PostgresDialect::new("public");
+        let mut query = SelectQuery {
+            from: TableRef {
+                name: "orders".to_string(),
+                alias: Some("o".to_string()),
+                subquery: None,
+                sql: None,
+            },
+            ..Default::default()
+        };
+        query.select.push(SelectItem {
+            expr: col("o", "country"),
+            alias: Some("country".to_string()),
+        });
+        query.group_by.push(col("o", "country"));
+
+        let sql = SqlRenderer::new(&dialect)
+            .with_group_by_all(true)
+            .render_select(&query);
+        assert!(sql.ends_with("GROUP BY \"o\".\"country\""), "sql={sql}");
+    }
+
     #[test]
     fn renders_postgres_date_trunc() {
         let dialect = PostgresDialect::new("public");
-        let mut query = SelectQuery::default();
-        query.from = TableRef {
-            name: "orders".to_string(),
-            alias: Some("o".to_string()),
-            subquery: None,
+        let mut query = SelectQuery {
+            from: TableRef {
+                name: "orders".to_string(),
+                alias: Some("o".to_string()),
+                subquery: None,
+                sql: None,
+            },
+            ..Default::default()
         };
         query.select.push(SelectItem {
             expr: SqlExpr::Function {
@@ -176,11 +634,14 @@ mod postgres_tests {
     #[test]
     fn renders_postgres_filtered_aggregate() {
         let dialect = PostgresDialect::new("public");
-        let mut query = SelectQuery::default();
-        query.from = TableRef {
-            name: "orders".to_string(),
-            alias: Some("o".to_string()),
-            subquery: None,
+        let mut query = SelectQuery {
+            from: TableRef {
+                name: "orders".to_string(),
+                alias: Some("o".to_string()),
+                subquery: None,
+                sql: None,
+            },
+            ..Default::default()
         };
         query.select.push(SelectItem {
             expr: SqlExpr::FilteredAggregate {
@@ -206,11 +667,14 @@ mod postgres_tests {
     #[test]
     fn renders_postgres_median_as_percentile_cont() {
         let dialect = PostgresDialect::new("public");
-        let mut query = SelectQuery::default();
-        query.from = TableRef {
-            name: "orders".to_string(),
-            alias: Some("o".to_string()),
-            subquery: None,
+        let mut query = SelectQuery {
+            from: TableRef {
+                name: "orders".to_string(),
+                alias: Some("o".to_string()),
+                subquery: None,
+                sql: None,
+            },
+            ..Default::default()
         };
         query.select.push(SelectItem {
             expr: SqlExpr::Aggregate {
@@ -229,27 +693,51 @@ mod postgres_tests {
     }
 
     #[test]
-    fn renders_postgres_first_as_array_agg() {
+    fn renders_postgres_first_as_ordered_array_agg() {
         let dialect = PostgresDialect::new("public");
-        let mut query = SelectQuery::default();
-        query.from = TableRef {
-            name: "orders".to_string(),
-            alias: Some("o".to_string()),
-            subquery: None,
+        let mut query = SelectQuery {
+            from: TableRef {
+                name: "orders".to_string(),
+                alias: Some("o".to_string()),
+                subquery: None,
+                sql: None,
+            },
+            ..Default::default()
         };
         query.select.push(SelectItem {
             expr: SqlExpr::Aggregate {
-                agg: Aggregation::First,
+                agg: Aggregation::First {
+                    order_by: "o.created_at".to_string(),
+                    direction: SortDirection::Asc,
+                },
                 expr: Box::new(col("o", "amount")),
             },
             alias: Some("first_amount".to_string()),
         });
+        query.select.push(SelectItem {
+            expr: SqlExpr::Aggregate {
+                agg: Aggregation::Last {
+                    order_by: "o.created_at".to_string(),
+                    direction: SortDirection::Asc,
+                },
+                expr: Box::new(col("o", "amount")),
+            },
+            alias: Some("last_amount".to_string()),
+        });
 
         let sql = SqlRenderer::new(&dialect).render_select(&query);
-        // PostgreSQL uses (array_agg(x))[1] for FIRST
+        // PostgreSQL takes the first non-NULL element of an ordered array_agg
         assert!(
-            sql.contains("(array_agg(\"o\".\"amount\"))[1]"),
-            "PostgreSQL should render FIRST as array_agg[1]; sql={sql}"
+            sql.contains(
+                "(ARRAY_REMOVE(ARRAY_AGG(\"o\".\"amount\" ORDER BY \"o\".\"created_at\" ASC), NULL))[1]"
+            ),
+            "PostgreSQL should render FIRST as ordered ARRAY_AGG; sql={sql}"
+        );
+        assert!(
+            sql.contains(
+                "(ARRAY_REMOVE(ARRAY_AGG(\"o\".\"amount\" ORDER BY \"o\".\"created_at\" DESC), NULL))[1]"
+            ),
+            "PostgreSQL should render LAST as descending ARRAY_AGG; sql={sql}"
         );
     }
 
@@ -260,3 +748,386 @@ mod postgres_tests {
         }
     }
 }
+
+#[test]
+fn renders_relative_dates_per_dialect() {
+    let expr = SqlExpr::RelativeDate {
+        truncate: Some(TimeGrain::Week),
+        offsets: vec![(TimeGrain::Week, -1)],
+        as_timestamp: false,
+    };
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.select.push(SelectItem {
+        expr: col("o", "id"),
+        alias: Some("id".to_string()),
+    });
+    query.filters.push(SqlExpr::BinaryOp {
+        op: SqlBinaryOperator::Gte,
+        left: Box::new(col("o", "created_at")),
+        right: Box::new(expr),
+    });
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains(">= CAST(date_trunc('week', current_date) + INTERVAL '-7 day' AS DATE)"),
+        "sql={sql}"
+    );
+
+    #[cfg(feature = "postgres")]
+    {
+        let sql = SqlRenderer::new(&PostgresDialect::new("public")).render_select(&query);
+        assert!(
+            sql.contains("CAST(date_trunc('week', current_date) + INTERVAL '-7 day' AS DATE)"),
+            "sql={sql}"
+        );
+    }
+}
+
+#[test]
+fn renders_percentiles_per_dialect() {
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "requests".to_string(),
+            alias: Some("r".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    for (agg, alias) in [
+        (Aggregation::Percentile { p: 0.95 }, "p95"),
+        (Aggregation::ApproxPercentile { p: 0.99 }, "p99"),
+    ] {
+        query.select.push(SelectItem {
+            expr: SqlExpr::Aggregate {
+                agg,
+                expr: Box::new(col("r", "latency_ms")),
+            },
+            alias: Some(alias.to_string()),
+        });
+    }
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains("QUANTILE_CONT(\"r\".\"latency_ms\", 0.95)"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("APPROX_QUANTILE(\"r\".\"latency_ms\", 0.99)"),
+        "sql={sql}"
+    );
+
+    #[cfg(feature = "postgres")]
+    {
+        let sql = SqlRenderer::new(&PostgresDialect::new("public")).render_select(&query);
+        assert!(
+            sql.contains("PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY \"r\".\"latency_ms\")"),
+            "sql={sql}"
+        );
+        assert!(
+            sql.contains("PERCENTILE_CONT(0.99) WITHIN GROUP"),
+            "sql={sql}"
+        );
+    }
+
+    #[cfg(feature = "bigquery")]
+    {
+        let dialect = semaflow::dialect::BigQueryDialect {
+            project_id: "p".to_string(),
+            dataset: "d".to_string(),
+        };
+        let sql = SqlRenderer::new(&dialect).render_select(&query);
+        assert!(
+            sql.contains("APPROX_QUANTILES(`r`.`latency_ms`, 1000)[OFFSET(950)]"),
+            "sql={sql}"
+        );
+        assert!(sql.contains("[OFFSET(990)]"), "sql={sql}");
+    }
+}
+
+#[test]
+fn renders_boolean_aggregations_per_dialect() {
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    for (agg, alias) in [
+        (Aggregation::CountIf, "refunds"),
+        (Aggregation::BoolAnd, "all_refunded"),
+        (Aggregation::BoolOr, "any_refunded"),
+    ] {
+        query.select.push(SelectItem {
+            expr: SqlExpr::Aggregate {
+                agg,
+                expr: Box::new(col("o", "is_refund")),
+            },
+            alias: Some(alias.to_string()),
+        });
+    }
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(sql.contains("COUNT_IF(\"o\".\"is_refund\")"), "sql={sql}");
+    assert!(sql.contains("BOOL_AND(\"o\".\"is_refund\")"), "sql={sql}");
+    assert!(sql.contains("BOOL_OR(\"o\".\"is_refund\")"), "sql={sql}");
+
+    #[cfg(feature = "postgres")]
+    {
+        let sql = SqlRenderer::new(&PostgresDialect::new("public")).render_select(&query);
+        assert!(
+            sql.contains("COUNT(CASE WHEN \"o\".\"is_refund\" THEN 1 END)"),
+            "sql={sql}"
+        );
+        assert!(sql.contains("BOOL_OR(\"o\".\"is_refund\")"), "sql={sql}");
+    }
+
+    #[cfg(feature = "bigquery")]
+    {
+        let dialect = semaflow::dialect::BigQueryDialect {
+            project_id: "p".to_string(),
+            dataset: "d".to_string(),
+        };
+        let sql = SqlRenderer::new(&dialect).render_select(&query);
+        assert!(sql.contains("COUNTIF(`o`.`is_refund`)"), "sql={sql}");
+        assert!(sql.contains("LOGICAL_AND(`o`.`is_refund`)"), "sql={sql}");
+        assert!(sql.contains("LOGICAL_OR(`o`.`is_refund`)"), "sql={sql}");
+    }
+}
+
+#[test]
+fn renders_raw_sql_with_table_alias() {
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.select.push(SelectItem {
+        expr: SqlExpr::Raw {
+            sql: "json_extract_string(${TABLE}.payload, '$.channel')".to_string(),
+            table: Some("o".to_string()),
+        },
+        alias: Some("channel".to_string()),
+    });
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains("(json_extract_string(\"o\".payload, '$.channel')) AS \"channel\""),
+        "sql={sql}"
+    );
+}
+
+#[test]
+fn renders_unnest_as_cross_join_and_exists() {
+    let element = |name: &str| SqlExpr::Column {
+        table: None,
+        name: name.to_string(),
+    };
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.unnests.push(Unnest {
+        expr: col("o", "tags"),
+        alias: "o__tags".to_string(),
+    });
+    query.select.push(SelectItem {
+        expr: element("o__tags"),
+        alias: Some("tag".to_string()),
+    });
+    query.filters.push(SqlExpr::AnyElement {
+        expr: Box::new(col("o", "coupons")),
+        alias: "o__coupons".to_string(),
+        condition: Box::new(SqlExpr::BinaryOp {
+            op: SqlBinaryOperator::Eq,
+            left: Box::new(element("o__coupons")),
+            right: Box::new(SqlExpr::Literal(serde_json::json!("WELCOME"))),
+        }),
+    });
+    query.group_by.push(element("o__tags"));
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains(
+            "FROM \"orders\" \"o\" CROSS JOIN UNNEST(\"o\".\"tags\") AS \"o__tags\"(\"o__tags\")"
+        ),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains(
+            "EXISTS (SELECT 1 FROM UNNEST(\"o\".\"coupons\") AS \"o__coupons\"(\"o__coupons\") \
+             WHERE (\"o__coupons\" = 'WELCOME'))"
+        ),
+        "sql={sql}"
+    );
+}
+
+#[test]
+fn renders_time_zone_conversion_per_dialect() {
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    query.select.push(SelectItem {
+        expr: SqlExpr::Function {
+            func: Function::DateTrunc(TimeGrain::Day),
+            args: vec![SqlExpr::AtTimeZone {
+                expr: Box::new(col("o", "created_at")),
+                zone: "Europe/Berlin".to_string(),
+            }],
+        },
+        alias: Some("day".to_string()),
+    });
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains("date_trunc('day', (\"o\".\"created_at\" AT TIME ZONE 'Europe/Berlin'))"),
+        "sql={sql}"
+    );
+
+    #[cfg(feature = "bigquery")]
+    {
+        let dialect = semaflow::dialect::BigQueryDialect {
+            project_id: "p".to_string(),
+            dataset: "d".to_string(),
+        };
+        let sql = SqlRenderer::new(&dialect).render_select(&query);
+        assert!(
+            sql.contains(
+                "TIMESTAMP_TRUNC(TIMESTAMP(DATETIME(`o`.`created_at`, 'Europe/Berlin')), DAY)"
+            ),
+            "sql={sql}"
+        );
+    }
+}
+
+#[test]
+fn renders_schedule_sink_statements() {
+    let sql = "SELECT 1";
+    let parquet = ScheduleSink::Parquet {
+        path: "/tmp/o'clock.parquet".to_string(),
+    };
+    assert_eq!(
+        sink_statements(&DuckDbDialect, sql, &parquet).unwrap(),
+        vec!["COPY (SELECT 1) TO '/tmp/o''clock.parquet' (FORMAT PARQUET)"]
+    );
+
+    let replace = ScheduleSink::Table {
+        table: "daily".to_string(),
+        mode: WriteMode::Replace,
+        incremental: None,
+    };
+    assert_eq!(
+        sink_statements(&DuckDbDialect, sql, &replace).unwrap(),
+        vec!["CREATE OR REPLACE TABLE \"daily\" AS SELECT 1"]
+    );
+    assert_eq!(
+        incremental_statements(&DuckDbDialect, sql, "daily", "day", "2024-05-01"),
+        vec![
+            "DELETE FROM \"daily\" WHERE \"day\" >= '2024-05-01'",
+            "INSERT INTO \"daily\" SELECT 1",
+        ]
+    );
+
+    #[cfg(feature = "postgres")]
+    {
+        let dialect = PostgresDialect::new("public");
+        assert!(sink_statements(&dialect, sql, &parquet).is_err());
+        let statements = sink_statements(&dialect, sql, &replace).unwrap();
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].starts_with("DROP TABLE IF EXISTS"));
+        let append = ScheduleSink::Table {
+            table: "daily".to_string(),
+            mode: WriteMode::Append,
+            incremental: None,
+        };
+        assert!(sink_statements(&dialect, sql, &append).unwrap()[0].starts_with("INSERT INTO"));
+    }
+}
+
+#[test]
+fn renders_first_and_last_by_ordering_column_per_dialect() {
+    let mut query = SelectQuery {
+        from: TableRef {
+            name: "orders".to_string(),
+            alias: Some("o".to_string()),
+            subquery: None,
+            sql: None,
+        },
+        ..Default::default()
+    };
+    for (agg, alias) in [
+        (
+            Aggregation::First {
+                order_by: "o.created_at".to_string(),
+                direction: SortDirection::Asc,
+            },
+            "first_status",
+        ),
+        (
+            Aggregation::Last {
+                order_by: "o.created_at".to_string(),
+                direction: SortDirection::Asc,
+            },
+            "latest_status",
+        ),
+    ] {
+        query.select.push(SelectItem {
+            expr: SqlExpr::Aggregate {
+                agg,
+                expr: Box::new(col("o", "status")),
+            },
+            alias: Some(alias.to_string()),
+        });
+    }
+
+    let sql = SqlRenderer::new(&DuckDbDialect).render_select(&query);
+    assert!(
+        sql.contains("ARG_MIN(\"o\".\"status\", \"o\".\"created_at\")"),
+        "sql={sql}"
+    );
+    assert!(
+        sql.contains("ARG_MAX(\"o\".\"status\", \"o\".\"created_at\")"),
+        "sql={sql}"
+    );
+
+    #[cfg(feature = "bigquery")]
+    {
+        let dialect = semaflow::dialect::BigQueryDialect {
+            project_id: "p".to_string(),
+            dataset: "d".to_string(),
+        };
+        let sql = SqlRenderer::new(&dialect).render_select(&query);
+        assert!(
+            sql.contains(
+                "ARRAY_AGG(`o`.`status` IGNORE NULLS ORDER BY `o`.`created_at` DESC LIMIT 1)[SAFE_OFFSET(0)]"
+            ),
+            "sql={sql}"
+        );
+    }
+}
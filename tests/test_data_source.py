@@ -0,0 +1,33 @@
+"""Tests for DataSource construction."""
+
+import pytest
+
+from semaflow import DataSource
+
+
+class TestDataSourceFromUri:
+    """DataSource(name, uri) picks the backend from the URI scheme."""
+
+    def test_duckdb_path(self):
+        ds = DataSource("warehouse", "analytics.duckdb")
+        assert ds.backend_type == "duckdb"
+        assert ds.uri == "analytics.duckdb"
+
+    def test_postgres_url_with_schema(self):
+        ds = DataSource("warehouse", "postgresql://localhost/db#analytics")
+        assert ds.backend_type == "postgres"
+        assert ds.uri == "postgresql://localhost/db"
+        assert ds.schema == "analytics"
+
+    def test_postgres_schema_defaults_to_public(self):
+        assert DataSource("warehouse", "postgres://localhost/db").schema == "public"
+        assert DataSource.postgres("postgres://localhost/db").schema == "public"
+
+    def test_bigquery_project_and_dataset(self):
+        ds = DataSource("warehouse", "bigquery://my-project/analytics")
+        assert ds.backend_type == "bigquery"
+        assert ds.schema == "analytics"
+
+    def test_bigquery_requires_dataset(self):
+        with pytest.raises(ValueError, match="bigquery://project/dataset"):
+            DataSource("warehouse", "bigquery://my-project")
@@ -6,7 +6,8 @@ from pathlib import Path
 
 import pytest
 
-from semaflow import DataSource, FlowHandle
+from semaflow import DataSource, Dimension, FlowHandle, Measure, SemanticFlow, SemanticTable
+from semaflow.semaflow import Config
 
 
 class TestFlowHandleFromParts:
@@ -27,6 +28,87 @@ class TestFlowHandleFromParts:
         assert "simple_orders" in flow_names
 
 
+class TestFlowHandleValidate:
+    """Tests for FlowHandle.validate() model checks."""
+
+    @pytest.fixture
+    def broken_orders_table(self) -> SemanticTable:
+        return SemanticTable(
+            name="orders",
+            data_source="test_db",
+            table="orders",
+            primary_key="id",
+            dimensions={
+                "status": Dimension(expr="status"),
+                "region": Dimension(expr="region"),
+            },
+            measures={
+                "order_total": Measure(expr="amount", agg="sum"),
+                "discount_total": Measure(expr="discount", agg="sum"),
+            },
+        )
+
+    def test_valid_model_has_no_issues(self, simple_flow_handle: FlowHandle):
+        assert simple_flow_handle.validate() == []
+
+    def test_construction_fails_on_first_error(
+        self, seeded_datasource: DataSource, broken_orders_table: SemanticTable
+    ):
+        flow = SemanticFlow(name="broken", base_table=broken_orders_table, base_table_alias="o")
+        with pytest.raises(ValueError):
+            FlowHandle.from_parts([broken_orders_table], [flow], [seeded_datasource])
+
+    def test_warn_only_handle_reports_every_issue(
+        self, seeded_datasource: DataSource, broken_orders_table: SemanticTable
+    ):
+        config = Config()
+        config.set_validation_warn_only(True)
+        flow = SemanticFlow(name="broken", base_table=broken_orders_table, base_table_alias="o")
+        handle = FlowHandle.from_parts([broken_orders_table], [flow], [seeded_datasource], config)
+
+        issues = handle.validate()
+        assert {(i["table"], i["field"]) for i in issues} == {
+            ("orders", "region"),
+            ("orders", "discount_total"),
+        }
+        assert all(i["severity"] == "error" and i["flow"] is None for i in issues)
+        assert any("missing column region" in i["message"] for i in issues)
+
+
+class TestFlowHandleHealthCheck:
+    """Tests for FlowHandle.health_check() data source checks."""
+
+    @pytest.mark.asyncio
+    async def test_reports_healthy_sources(
+        self, simple_flow_handle: FlowHandle, seeded_datasource: DataSource
+    ):
+        assert await simple_flow_handle.health_check() == {seeded_datasource.name: None}
+
+
+class TestFlowHandleConcurrency:
+    """Tests for concurrency budgets and FlowHandle.concurrency_stats()."""
+
+    @pytest.mark.asyncio
+    async def test_reports_budget_counters(
+        self,
+        seeded_datasource: DataSource,
+        orders_table: SemanticTable,
+        simple_flow: SemanticFlow,
+    ):
+        config = Config()
+        config.set_flow_concurrency("simple_orders", 2)
+        handle = FlowHandle.from_parts([orders_table], [simple_flow], [seeded_datasource], config)
+        assert handle.concurrency_stats() == []  # budgets start on first use
+
+        await handle.execute({"flow": "simple_orders", "measures": ["o.order_total"]})
+
+        [stats] = handle.concurrency_stats()
+        assert stats["scope"] == "flow"
+        assert stats["name"] == "simple_orders"
+        assert stats["max_concurrent"] == 2
+        assert (stats["admitted"], stats["in_flight"], stats["rejected"]) == (1, 0, 0)
+
+
 class TestFlowHandleFromDir:
     """Tests for FlowHandle.from_dir() initialization."""
 
@@ -168,6 +250,39 @@ class TestFlowHandleExecute:
         assert len(result) == 1
 
 
+class TestFlowHandleArrow:
+    """Tests for Arrow and DataFrame results."""
+
+    @pytest.mark.asyncio
+    async def test_execute_arrow_returns_table(self, simple_flow_handle: FlowHandle):
+        """execute_arrow() returns a pyarrow Table with qualified column names."""
+        table = await simple_flow_handle.execute_arrow({
+            "flow": "simple_orders",
+            "dimensions": ["o.status"],
+            "measures": ["o.order_total"],
+        })
+        assert table.column_names == ["o.status", "o.order_total"]
+        assert table.num_rows > 0
+
+    @pytest.mark.asyncio
+    async def test_execute_df_returns_pandas_frame(self, simple_flow_handle: FlowHandle):
+        """execute_df() returns a pandas DataFrame matching execute()."""
+        df = await simple_flow_handle.execute_df({
+            "flow": "simple_orders",
+            "dimensions": ["o.status"],
+            "measures": ["o.order_total"],
+            "filters": [{"field": "o.status", "op": "==", "value": "complete"}],
+        })
+        assert list(df.columns) == ["o.status", "o.order_total"]
+        assert df["o.order_total"].tolist() == [350.0]
+
+    @pytest.mark.asyncio
+    async def test_execute_df_rejects_unknown_engine(self, simple_flow_handle: FlowHandle):
+        """execute_df() raises for engines other than pandas and polars."""
+        with pytest.raises(ValueError, match="engine"):
+            await simple_flow_handle.execute_df({"flow": "simple_orders"}, engine="spark")
+
+
 class TestFlowHandlePagination:
     """Tests for paginated query execution."""
 
@@ -210,6 +325,45 @@ class TestFlowHandlePagination:
         # Second page should have different data
         assert page1["rows"][0] != page2["rows"][0]
 
+    @pytest.mark.asyncio
+    async def test_count_total_reports_total_rows(self, simple_flow_handle: FlowHandle):
+        """count_total fills total_rows on DuckDB, which has no native count."""
+        request = {
+            "flow": "simple_orders",
+            "dimensions": ["o.status"],
+            "measures": ["o.order_total"],
+            "page_size": 1,
+        }
+        page = await simple_flow_handle.execute(request)
+        assert page["total_rows"] is None
+
+        page = await simple_flow_handle.execute({**request, "count_total": True})
+        assert page["total_rows"] == 2
+
+    @pytest.mark.asyncio
+    async def test_execute_page_returns_paginated_result(self, simple_flow_handle: FlowHandle):
+        """execute_page() returns pagination metadata without page_size in the request."""
+        page = await simple_flow_handle.execute_page(
+            {"flow": "simple_orders", "dimensions": ["o.status"], "measures": ["o.order_total"]},
+            page_size=1,
+        )
+        assert len(page["rows"]) == 1
+        assert page["has_more"] is True
+
+    @pytest.mark.asyncio
+    async def test_execute_pages_walks_all_pages(self, simple_flow_handle: FlowHandle):
+        """execute_pages() yields every page until has_more is False."""
+        request = {
+            "flow": "simple_orders",
+            "dimensions": ["o.status"],
+            "measures": ["o.order_total"],
+            "order": [{"column": "o.status", "direction": "asc"}],
+        }
+        pages = [page async for page in simple_flow_handle.execute_pages(request, page_size=1)]
+        paged_rows = [row for page in pages for row in page["rows"]]
+        assert pages[-1]["has_more"] is False
+        assert paged_rows == await simple_flow_handle.execute(request)
+
 
 class TestFlowHandleJoins:
     """Tests for queries with joins."""
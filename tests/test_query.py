@@ -0,0 +1,68 @@
+"""Tests for the fluent Query builder."""
+
+import pytest
+
+from semaflow import FlowHandle, QueryValidationError
+
+
+class TestQueryBuilder:
+    """Tests for FlowHandle.query()."""
+
+    def test_builds_request_dict(self, simple_flow_handle: FlowHandle):
+        """Builder steps accumulate into the request dict."""
+        query = (
+            simple_flow_handle.query("simple_orders")
+            .dimensions("o.status")
+            .measures("o.order_total")
+            .filter("o.status", "==", "complete")
+            .order_by("o.order_total", "desc")
+            .limit(10)
+        )
+        assert query.to_dict() == {
+            "flow": "simple_orders",
+            "dimensions": ["o.status"],
+            "measures": ["o.order_total"],
+            "filters": [{"field": "o.status", "op": "==", "value": "complete"}],
+            "order": [{"column": "o.order_total", "direction": "desc"}],
+            "limit": 10,
+        }
+
+    def test_steps_return_new_queries(self, simple_flow_handle: FlowHandle):
+        """A base query can be reused; steps do not modify it."""
+        base = simple_flow_handle.query("simple_orders").measures("o.order_total")
+        by_status = base.dimensions("o.status")
+        assert "dimensions" not in base.to_dict()
+        assert by_status.to_dict()["dimensions"] == ["o.status"]
+
+    def test_unknown_field_raises_with_suggestions(self, simple_flow_handle: FlowHandle):
+        """Unknown fields fail at the step that adds them."""
+        query = simple_flow_handle.query("simple_orders")
+        with pytest.raises(QueryValidationError) as excinfo:
+            query.measures("o.order_totl")
+        error = excinfo.value.errors[0]
+        assert error["code"] == "unknown_field"
+        assert any("order_total" in s for s in error["details"]["suggestions"])
+
+    def test_unknown_flow_raises(self, simple_flow_handle: FlowHandle):
+        with pytest.raises(QueryValidationError):
+            simple_flow_handle.query("no_such_flow")
+
+    def test_filter_before_fields_is_allowed(self, simple_flow_handle: FlowHandle):
+        """Filters may come before any dimension or measure is selected."""
+        query = simple_flow_handle.query("simple_orders").filter("o.status", "==", "complete")
+        assert query.to_dict()["filters"][0]["field"] == "o.status"
+
+    def test_unknown_operator_raises(self, simple_flow_handle: FlowHandle):
+        with pytest.raises(QueryValidationError, match="operator"):
+            simple_flow_handle.query("simple_orders").filter("o.status", "=", "complete")
+
+    @pytest.mark.asyncio
+    async def test_executes(self, simple_flow_handle: FlowHandle):
+        rows = await (
+            simple_flow_handle.query("simple_orders")
+            .dimensions("o.status")
+            .measures("o.order_total")
+            .filter("o.status", "==", "complete")
+            .execute()
+        )
+        assert rows == [{"o.status": "complete", "o.order_total": 350.0}]